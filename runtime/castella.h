@@ -0,0 +1,215 @@
+// Runtime support for programs compiled by castella.
+//
+// Dynamic arrays (`[^]T`) are represented by `castella_darray`, which doesn't know its element
+// type, so the element size is passed to every operation that needs it. The backing buffer grows
+// geometrically (doubling, starting at CASTELLA_DARRAY_MIN_CAP elements), so appending is
+// amortized O(1). Growing may move the buffer, so pointers to elements are invalidated by
+// `append`.
+
+#ifndef CASTELLA_H
+#define CASTELLA_H
+
+#include <errno.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#define CASTELLA_DARRAY_MIN_CAP 8
+
+// `--sanitize` defines CASTELLA_SANITIZE_ADDRESS and CASTELLA_SANITIZE_UNDEFINED before this
+// header. Traps then stop with `__builtin_trap` at the failing check instead of going through
+// `abort`, AddressSanitizer prints the stack leading to them, and it reports accesses to the
+// unused capacity of dynamic arrays as container overflows.
+#ifdef CASTELLA_SANITIZE_ADDRESS
+#include <sanitizer/common_interface_defs.h>
+
+#if defined(__SANITIZE_ADDRESS__)
+#define CASTELLA_HAS_ASAN 1
+#elif defined(__has_feature)
+#if __has_feature(address_sanitizer)
+#define CASTELLA_HAS_ASAN 1
+#endif
+#endif
+
+#ifndef CASTELLA_HAS_ASAN
+#error "This file was generated with '--sanitize=address', so compile it with '-fsanitize=address'"
+#endif
+#endif
+
+typedef struct castella_darray {
+  void* data;
+  size_t len;
+  size_t cap;
+} castella_darray;
+
+// Reports an unrecoverable runtime error and aborts
+static inline void castella_trap(const char* file, int line, const char* msg) {
+  fflush(stdout);
+  fprintf(stderr, "%s(%d) Runtime error: %s\n", file, line, msg);
+#ifdef CASTELLA_SANITIZE_ADDRESS
+  __sanitizer_print_stack_trace();
+#endif
+#if defined(CASTELLA_SANITIZE_ADDRESS) || defined(CASTELLA_SANITIZE_UNDEFINED)
+  __builtin_trap();
+#else
+  abort();
+#endif
+}
+
+// Allocates a zeroed `size` bytes for `new`, trapping with the location of the `new` if there is
+// no memory left
+static inline void* castella_new(size_t size, const char* file, int line) {
+  void* ptr = calloc(1, size);
+  if (ptr == NULL) castella_trap(file, line, "out of memory");
+  return ptr;
+}
+
+// Tells AddressSanitizer that the accessible elements of `arr` go from the first `old_len` to the
+// first `new_len`, with the rest of its capacity poisoned
+static inline void castella_darray_annotate(const castella_darray* arr, size_t elem_size, size_t old_len,
+                                            size_t new_len) {
+#ifdef CASTELLA_SANITIZE_ADDRESS
+  if (arr->data != NULL) {
+    char* data = (char*)arr->data;
+    __sanitizer_annotate_contiguous_container(data, data + arr->cap * elem_size, data + old_len * elem_size,
+                                              data + new_len * elem_size);
+  }
+#endif
+  (void)arr, (void)elem_size, (void)old_len, (void)new_len;
+}
+
+// Makes sure that at least `min_cap` elements fit without reallocating
+static inline void castella_darray_reserve(castella_darray* arr, size_t elem_size, size_t min_cap) {
+  if (min_cap <= arr->cap) return;
+
+  size_t cap = arr->cap < CASTELLA_DARRAY_MIN_CAP ? CASTELLA_DARRAY_MIN_CAP : arr->cap;
+  while (cap < min_cap) {
+    if (cap > SIZE_MAX / 2 / elem_size) castella_trap(__FILE__, __LINE__, "dynamic array is too large");
+    cap *= 2;
+  }
+
+  void* data = realloc(arr->data, cap * elem_size);
+  if (data == NULL) castella_trap(__FILE__, __LINE__, "out of memory");
+
+  arr->data = data;
+  arr->cap = cap;
+  // The new buffer is accessible as a whole
+  castella_darray_annotate(arr, elem_size, arr->cap, arr->len);
+}
+
+// Grows the array by one element and returns a pointer to the new, uninitialized slot
+static inline void* castella_darray_push(castella_darray* arr, size_t elem_size) {
+  castella_darray_reserve(arr, elem_size, arr->len + 1);
+  castella_darray_annotate(arr, elem_size, arr->len, arr->len + 1);
+  return (char*)arr->data + arr->len++ * elem_size;
+}
+
+// Frees the backing buffer and leaves the array empty
+static inline void castella_darray_free(castella_darray* arr) {
+  free(arr->data);
+  arr->data = NULL;
+  arr->len = 0;
+  arr->cap = 0;
+}
+
+// Whether `a` and `b` differ by at most `eps`, which `approx_eq` compiles to. Infinities of the
+// same sign are equal, and NaN is never equal to anything.
+static inline bool castella_approx_eq(double a, double b, double eps) {
+  if (a == b) return true;
+  double diff = a > b ? a - b : b - a;
+  return diff <= eps;
+}
+
+// Returns `idx` if it is in bounds, otherwise traps with the location of the indexing
+static inline size_t castella_bounds_check(int64_t idx, size_t len, const char* file, int line) {
+  if (idx < 0 || (uint64_t)idx >= len) {
+    char msg[96];
+    snprintf(msg, sizeof(msg), "index %lld is out of bounds for length %zu", (long long)idx, len);
+    castella_trap(file, line, msg);
+  }
+  return (size_t)idx;
+}
+
+// The checks of `--checked-casts`, which return the value if it fits into the type the cast
+// converts it to (`min..=max`, or between the exclusive bounds for doubles), and otherwise trap
+// with the location of the cast. Release builds (NDEBUG) leave the checks out.
+static inline int64_t castella_check_signed_cast(int64_t value, int64_t min, int64_t max, const char* t,
+                                                 const char* file, int line) {
+#ifndef NDEBUG
+  if (value < min || value > max) {
+    char msg[96];
+    snprintf(msg, sizeof(msg), "%lld doesn't fit into '%s'", (long long)value, t);
+    castella_trap(file, line, msg);
+  }
+#endif
+  (void)min, (void)max, (void)t, (void)file, (void)line;
+  return value;
+}
+
+static inline uint64_t castella_check_unsigned_cast(uint64_t value, uint64_t max, const char* t,
+                                                    const char* file, int line) {
+#ifndef NDEBUG
+  if (value > max) {
+    char msg[96];
+    snprintf(msg, sizeof(msg), "%llu doesn't fit into '%s'", (unsigned long long)value, t);
+    castella_trap(file, line, msg);
+  }
+#endif
+  (void)max, (void)t, (void)file, (void)line;
+  return value;
+}
+
+static inline double castella_check_float_cast(double value, double lo, double hi, const char* t,
+                                               const char* file, int line) {
+#ifndef NDEBUG
+  if (!(value > lo && value < hi)) {
+    char msg[96];
+    snprintf(msg, sizeof(msg), "%g doesn't fit into '%s'", value, t);
+    castella_trap(file, line, msg);
+  }
+#endif
+  (void)lo, (void)hi, (void)t, (void)file, (void)line;
+  return value;
+}
+
+// Copies the `len` characters of `buf` into a string on the heap, which `free` gives back
+static inline const char* castella_heap_str(const char* buf, int len, const char* file, int line) {
+  char* str = (char*)castella_new((size_t)len + 1, file, line);
+  memcpy(str, buf, (size_t)len);
+  return str;
+}
+
+// `int_to_str` for signed and unsigned integers, which formats `value` in decimal into a string
+// on the heap
+static inline const char* castella_int_to_str(int64_t value, const char* file, int line) {
+  char buf[24];
+  int len = snprintf(buf, sizeof(buf), "%lld", (long long)value);
+  return castella_heap_str(buf, len, file, line);
+}
+
+static inline const char* castella_uint_to_str(uint64_t value, const char* file, int line) {
+  char buf[24];
+  int len = snprintf(buf, sizeof(buf), "%llu", (unsigned long long)value);
+  return castella_heap_str(buf, len, file, line);
+}
+
+// Parses all of `s` as a decimal integer in `min..=max` into `*value` for `parse_i32`, and returns
+// NULL if it is one, or otherwise why it isn't. Unlike `strtoll` alone, whitespace isn't skipped.
+static inline const char* castella_parse_int(const char* s, int64_t min, int64_t max, int64_t* value) {
+  const char* digits = (*s == '-' || *s == '+') ? s + 1 : s;
+  if (*digits < '0' || *digits > '9') return "not a number";
+
+  char* end;
+  errno = 0;
+  long long parsed = strtoll(s, &end, 10);
+  if (*end != '\0') return "not a number";
+  if (errno == ERANGE || parsed < min || parsed > max) return "out of range";
+
+  *value = parsed;
+  return NULL;
+}
+
+#endif
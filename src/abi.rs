@@ -0,0 +1,322 @@
+//! Describes the C interface of a compiled program as JSON (`--emit abi-json`), so that binding
+//! generators for other languages can use castella libraries without parsing C.
+//!
+//! The description is made from the lowered AST, so it has the names the C uses (like
+//! `geo_shapes_area` for a function of an imported module), an instance of a generic function
+//! for each array length it is called with, and functions returning multiple values already
+//! returning a result struct or taking out parameters. It looks like
+//!
+//! ```text
+//! {
+//!   "version": 1,
+//!   "types": [
+//!     {
+//!       "kind": "struct",
+//!       "name": "Point",
+//!       "size": 8,
+//!       "align": 4,
+//!       "fields": [{ "name": "x", "type": "i32", "offset": 0 }, ...]
+//!     },
+//!     { "kind": "enum", "name": "Color", "size": 4, "align": 4, "variants": [...] },
+//!     { "kind": "alias", "name": "Id", "type": "u64" }
+//!   ],
+//!   "functions": [{ "name": "add", "params": [{ "name": "a", "type": "i32" }], "ret": "i32" }]
+//! }
+//! ```
+//!
+//! Types are written the way castella writes them, like `^[4]u8`, and enum variants are
+//! `Enum_Variant` in C. Sizes, alignments, and offsets are those of 64-bit targets, where
+//! pointers and `size_t` are 8 bytes and C enums are `int`s, and are left out for types whose
+//! size isn't known. `version` changes whenever the format does. `@statemachine` functions
+//! aren't described, since their C interface is generated, and neither are extern functions,
+//! which the program only uses.
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use crate::lowering::results;
+use crate::parser::{FuncAttr, GlobalStmt, LocatedGlobalStmt, LocatedType, Type};
+
+/// The version of the format, which changes whenever it does
+pub const VERSION: u64 = 1;
+
+/// Returns the JSON describing the types and functions `ast` declares
+pub fn describe(ast: &[LocatedGlobalStmt]) -> String {
+    let layouts = Layouts::new(ast);
+    let mut types = vec![];
+    let mut functions = vec![];
+
+    for stmt in ast {
+        match &stmt.node {
+            GlobalStmt::Struct { name, fields } | GlobalStmt::Union { name, fields } => {
+                let is_union = matches!(stmt.node, GlobalStmt::Union { .. });
+                let offsets = layouts.field_offsets(fields, is_union);
+                let fields = fields
+                    .iter()
+                    .zip(offsets)
+                    .map(|((field, t), offset)| {
+                        Json::Object(vec![
+                            ("name", Json::Str(field.clone())),
+                            ("type", Json::Str(t.node.to_string())),
+                            ("offset", offset.map_or(Json::Null, Json::UInt)),
+                        ])
+                    })
+                    .collect();
+
+                let mut desc = vec![
+                    (
+                        "kind",
+                        Json::Str(if is_union { "union" } else { "struct" }.into()),
+                    ),
+                    ("name", Json::Str(name.clone())),
+                ];
+                desc.extend(layouts.size_and_align(&Type::UserDefinedType(name.clone())));
+                desc.push(("fields", Json::Array(fields)));
+                types.push(Json::Object(desc));
+            }
+            GlobalStmt::Enum { name, variants, .. } => {
+                // Variants without a value follow the previous one, like in C
+                let mut next = 0;
+                let variants = variants
+                    .iter()
+                    .map(|(variant, value)| {
+                        let value = value.unwrap_or(next);
+                        next = value + 1;
+                        Json::Object(vec![
+                            ("name", Json::Str(variant.clone())),
+                            ("value", Json::Int(value)),
+                        ])
+                    })
+                    .collect();
+
+                let mut desc = vec![
+                    ("kind", Json::Str("enum".into())),
+                    ("name", Json::Str(name.clone())),
+                ];
+                desc.extend(layouts.size_and_align(&Type::UserDefinedType(name.clone())));
+                desc.push(("variants", Json::Array(variants)));
+                types.push(Json::Object(desc));
+            }
+            GlobalStmt::Alias { t, name } => types.push(Json::Object(vec![
+                ("kind", Json::Str("alias".into())),
+                ("name", Json::Str(name.clone())),
+                ("type", Json::Str(t.node.to_string())),
+            ])),
+            GlobalStmt::Function {
+                name,
+                params,
+                ret,
+                attrs,
+                ..
+            } if !attrs.contains(&FuncAttr::StateMachine) => functions.push(Json::Object(vec![
+                ("name", Json::Str(name.clone())),
+                ("params", describe_params(params)),
+                ("ret", Json::Str(ret.node.to_string())),
+            ])),
+            _ => {}
+        }
+    }
+
+    let desc = Json::Object(vec![
+        ("version", Json::UInt(VERSION)),
+        ("types", Json::Array(types)),
+        ("functions", Json::Array(functions)),
+    ]);
+    format!("{desc}\n")
+}
+
+fn describe_params(params: &[(String, LocatedType)]) -> Json {
+    Json::Array(
+        params
+            .iter()
+            .map(|(name, t)| {
+                Json::Object(vec![
+                    ("name", Json::Str(name.clone())),
+                    ("type", Json::Str(t.node.to_string())),
+                ])
+            })
+            .collect(),
+    )
+}
+
+/// Computes the sizes and alignments of types on 64-bit targets
+struct Layouts<'ast> {
+    types: HashMap<&'ast str, &'ast GlobalStmt>,
+}
+
+impl<'ast> Layouts<'ast> {
+    fn new(ast: &'ast [LocatedGlobalStmt]) -> Self {
+        let types = ast
+            .iter()
+            .filter_map(|stmt| match &stmt.node {
+                GlobalStmt::Struct { name, .. }
+                | GlobalStmt::Union { name, .. }
+                | GlobalStmt::Enum { name, .. }
+                | GlobalStmt::Alias { name, .. } => Some((name.as_str(), &stmt.node)),
+                _ => None,
+            })
+            .collect();
+
+        Self { types }
+    }
+
+    /// The `size` and `align` entries of the description of `t`, if its size is known
+    fn size_and_align(&self, t: &Type) -> Vec<(&'static str, Json)> {
+        match self.layout(t, &mut vec![]) {
+            Some((size, align)) => vec![("size", Json::UInt(size)), ("align", Json::UInt(align))],
+            None => vec![],
+        }
+    }
+
+    /// The offset of each field of a struct, or 0 for each of a union
+    ///
+    /// The offsets after a field whose size isn't known aren't known either.
+    fn field_offsets(&self, fields: &[(String, LocatedType)], is_union: bool) -> Vec<Option<u64>> {
+        if is_union {
+            return vec![Some(0); fields.len()];
+        }
+
+        let mut end = Some(0u64);
+        let mut offsets = vec![];
+        for (_, t) in fields {
+            let layout = self.layout(&t.node, &mut vec![]);
+            let offset = end
+                .zip(layout)
+                .map(|(end, (_, align))| end.next_multiple_of(align));
+            end = offset.zip(layout).map(|(offset, (size, _))| offset + size);
+            offsets.push(offset);
+        }
+        offsets
+    }
+
+    /// The size and alignment of `t`, or `None` for `void` and types that contain themselves,
+    /// which are the types in `visiting`
+    fn layout(&self, t: &Type, visiting: &mut Vec<String>) -> Option<(u64, u64)> {
+        use Type::*;
+
+        let scalar = |size| Some((size, size));
+        match t {
+            Int8 | UInt8 | Bool | Char => scalar(1),
+            Int16 | UInt16 => scalar(2),
+            Int32 | UInt32 | Float => scalar(4),
+            Int64 | UInt64 | Double | Str | Pointer(_) | Func(..) => scalar(8),
+            // `castella_darray` is a pointer and two `size_t`s
+            DArray(_) => Some((24, 8)),
+            Array(len, t) => {
+                let (size, align) = self.layout(t, visiting)?;
+                Some((size * *len as u64, align))
+            }
+            UserDefinedType(name) => {
+                if visiting.contains(name) {
+                    return None;
+                }
+                visiting.push(name.clone());
+                let layout = self.user_type_layout(name, visiting);
+                visiting.pop();
+                layout
+            }
+            Result(ok, err) => {
+                let fields = results::fields(ok, err);
+                self.struct_layout(fields.iter().map(|(_, t)| t), visiting)
+            }
+            Void | GenericArray(..) | ExprArray(..) | Tuple(_) => None,
+        }
+    }
+
+    /// The size and alignment of a struct with fields of the types `fields`
+    fn struct_layout<'t>(
+        &self,
+        fields: impl IntoIterator<Item = &'t Type>,
+        visiting: &mut Vec<String>,
+    ) -> Option<(u64, u64)> {
+        let (mut size, mut align) = (0u64, 1);
+        for t in fields {
+            let (field_size, field_align) = self.layout(t, visiting)?;
+            size = size.next_multiple_of(field_align) + field_size;
+            align = align.max(field_align);
+        }
+        Some((size.next_multiple_of(align), align))
+    }
+
+    fn user_type_layout(&self, name: &str, visiting: &mut Vec<String>) -> Option<(u64, u64)> {
+        match self.types.get(name)? {
+            GlobalStmt::Enum { .. } => Some((4, 4)),
+            GlobalStmt::Alias { t, .. } => self.layout(&t.node, visiting),
+            GlobalStmt::Struct { fields, .. } => {
+                self.struct_layout(fields.iter().map(|(_, t)| &t.node), visiting)
+            }
+            GlobalStmt::Union { fields, .. } => {
+                let (mut size, mut align) = (0u64, 1);
+                for (_, t) in fields {
+                    let (field_size, field_align) = self.layout(&t.node, visiting)?;
+                    size = size.max(field_size);
+                    align = align.max(field_align);
+                }
+                Some((size.next_multiple_of(align), align))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Just enough JSON to write the description, pretty-printed with two spaces
+enum Json {
+    Null,
+    Int(i64),
+    UInt(u64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    fn write(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        let pad = "  ".repeat(indent + 1);
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Int(n) => write!(f, "{n}"),
+            Json::UInt(n) => write!(f, "{n}"),
+            Json::Str(s) => write_str(f, s),
+            Json::Array(elems) if elems.is_empty() => write!(f, "[]"),
+            Json::Array(elems) => {
+                writeln!(f, "[")?;
+                for (i, elem) in elems.iter().enumerate() {
+                    write!(f, "{pad}")?;
+                    elem.write(f, indent + 1)?;
+                    writeln!(f, "{}", if i + 1 < elems.len() { "," } else { "" })?;
+                }
+                write!(f, "{}]", "  ".repeat(indent))
+            }
+            Json::Object(entries) => {
+                writeln!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    write!(f, "{pad}\"{key}\": ")?;
+                    value.write(f, indent + 1)?;
+                    writeln!(f, "{}", if i + 1 < entries.len() { "," } else { "" })?;
+                }
+                write!(f, "{}}}", "  ".repeat(indent))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+/// Writes `s` as a JSON string
+fn write_str(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
@@ -0,0 +1,321 @@
+//! Compiles the generated C with the system C compiler (`--emit obj`, `--emit bin`, and `run`)
+//!
+//! The C is piped to the compiler, which is `$CC`, or `cc` if it isn't set, unless another one is
+//! given (`--cc`), along with the include and library directories and the libraries to link
+//! (`-I`, `-L`, and `-l`). What the compiler reports at a line of the source, which the `#line`
+//! directives of the C point it at unless `--no-line-directives` is given, is mapped back to that
+//! line, so it is shown like castella's own diagnostics:
+//!
+//! ```text
+//! main.clla(3:5) Error: In the generated C: 'y' undeclared (first use in this function)
+//!  3 |     let x: i32 = y;
+//!    |     ^~~~~~~~~~~~~~~
+//! ```
+//!
+//! Since the compiler only knows the line, the whole line is underlined. The sanitizers of
+//! `--sanitize` are passed to the compiler as well (`-fsanitize`), since the runtime configured
+//! for them doesn't compile without them.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+
+use crate::Message;
+use crate::diagnostics::{Level, NOTE_PREFIX};
+use crate::lowering::Sanitizers;
+
+/// What the C is compiled to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Artifact {
+    /// An object file to link with other C (`-c`)
+    Object,
+
+    /// An executable
+    Executable,
+}
+
+/// The C compiler and the flags it is run with
+#[derive(Debug, Clone)]
+pub struct CCompiler {
+    program: String,
+    include_dirs: Vec<String>,
+    lib_dirs: Vec<String>,
+    libs: Vec<String>,
+    sanitizers: Sanitizers,
+}
+
+/// How the C compiler exited and what it reported
+#[derive(Debug)]
+pub struct CcOutput {
+    pub status: ExitStatus,
+    pub stderr: String,
+}
+
+impl CCompiler {
+    pub fn new(program: &str) -> Self {
+        Self {
+            program: program.to_string(),
+            include_dirs: vec![],
+            lib_dirs: vec![],
+            libs: vec![],
+            sanitizers: Sanitizers::default(),
+        }
+    }
+
+    /// Uses `$CC`, or `cc` if it isn't set
+    pub fn detect() -> Self {
+        let program = std::env::var("CC").ok().filter(|cc| !cc.is_empty());
+        Self::new(program.as_deref().unwrap_or("cc"))
+    }
+
+    /// Looks for headers in `dir` as well (`-I`)
+    pub fn include_dir(mut self, dir: &str) -> Self {
+        self.include_dirs.push(dir.to_string());
+        self
+    }
+
+    /// Looks for libraries in `dir` as well (`-L`)
+    pub fn lib_dir(mut self, dir: &str) -> Self {
+        self.lib_dirs.push(dir.to_string());
+        self
+    }
+
+    /// Links the library `lib`, e.g. `m` for `libm` (`-l`)
+    pub fn lib(mut self, lib: &str) -> Self {
+        self.libs.push(lib.to_string());
+        self
+    }
+
+    /// Compiles and links the C with `sanitizers` (`-fsanitize`)
+    pub fn sanitizers(mut self, sanitizers: Sanitizers) -> Self {
+        self.sanitizers = sanitizers;
+        self
+    }
+
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// Compiles the C `code` to `out`, or returns why the compiler couldn't be run
+    ///
+    /// What the compiler reports is returned rather than printed, for `map_diagnostics`.
+    pub fn compile(&self, code: &str, artifact: Artifact, out: &Path) -> Result<CcOutput, String> {
+        let cc = &self.program;
+        let mut child = self
+            .command(artifact, out)
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("Cannot run '{cc}': {err}"))?;
+
+        // The compiler may exit before reading everything, which its output explains
+        let _ = child.stdin.take().unwrap().write_all(code.as_bytes());
+        let output = child
+            .wait_with_output()
+            .map_err(|err| format!("Cannot run '{cc}': {err}"))?;
+        Ok(CcOutput {
+            status: output.status,
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+
+    /// Returns the command compiling the C read from stdin to `out`
+    fn command(&self, artifact: Artifact, out: &Path) -> Command {
+        let mut cmd = Command::new(&self.program);
+        if artifact == Artifact::Object {
+            cmd.arg("-c");
+        }
+        let sanitizers = self.sanitizers.names();
+        if !sanitizers.is_empty() {
+            // The frame pointers make the stacks AddressSanitizer prints complete
+            cmd.arg(format!("-fsanitize={}", sanitizers.join(",")));
+            cmd.arg("-fno-omit-frame-pointer");
+        }
+        for dir in &self.include_dirs {
+            cmd.arg(format!("-I{dir}"));
+        }
+        cmd.args(["-x", "c", "-", "-o"]).arg(out);
+
+        // Libraries are linked after the code that uses them
+        if artifact == Artifact::Executable {
+            for dir in &self.lib_dirs {
+                cmd.arg(format!("-L{dir}"));
+            }
+            for lib in &self.libs {
+                cmd.arg(format!("-l{lib}"));
+            }
+        }
+        cmd
+    }
+}
+
+/// A line of what the C compiler reported
+#[derive(Debug, PartialEq)]
+pub enum CDiagnostic {
+    /// A problem at a line of the source, which notes are prefixed for like castella's own
+    Mapped(Level, Message),
+
+    /// A line about something else, like the C runtime or the linker, which is shown as it is
+    Unmapped(String),
+}
+
+/// Maps what the C compiler reported, `stderr`, back to `source`, which the `#line` directives
+/// of the C call `path`
+///
+/// The lines naming the function a problem is in and the source the compiler shows under it are
+/// left out, since the source is shown when the problem is rendered.
+pub fn map_diagnostics(stderr: &str, source: &str, path: &str) -> Vec<CDiagnostic> {
+    let lines: Vec<&str> = source.split('\n').collect();
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+
+    let mut res = vec![];
+    let mut shown: Option<&str> = None;
+    for line in stderr.lines() {
+        // e.g. `main.clla: In function 'main':`
+        if line
+            .strip_prefix(path)
+            .is_some_and(|rest| rest.starts_with(": "))
+        {
+            continue;
+        }
+
+        if let Some(text) = shown {
+            let trimmed = line.trim_start();
+            if line.starts_with(char::is_whitespace)
+                || trimmed.starts_with(['^', '~'])
+                || line.trim_end() == text.trim_end()
+            {
+                continue;
+            }
+        }
+        shown = None;
+
+        let Some((number, level, msg)) =
+            parse_header(line, path).filter(|(number, ..)| (1..=lines.len()).contains(number))
+        else {
+            res.push(CDiagnostic::Unmapped(line.to_string()));
+            continue;
+        };
+
+        let text = lines[number - 1].trim_end_matches('\r');
+        let start = line_starts[number - 1] + (text.len() - text.trim_start().len());
+        let span = start..line_starts[number - 1] + text.len();
+        let msg = match level {
+            Level::Note => format!("{NOTE_PREFIX}{msg}"),
+            _ => format!("In the generated C: {msg}"),
+        };
+        res.push(CDiagnostic::Mapped(level, (span, msg)));
+        shown = Some(text);
+    }
+    res
+}
+
+/// Parses a line like `main.clla:3:26: error: 'y' undeclared`, whose column is left out since it
+/// counts the characters of the C
+fn parse_header<'a>(line: &'a str, path: &str) -> Option<(usize, Level, &'a str)> {
+    let rest = line.strip_prefix(path)?.strip_prefix(':')?;
+    let (number, mut rest) = rest.split_once(':')?;
+    let number = number.parse().ok()?;
+    if let Some((column, after)) = rest.split_once(':')
+        && column.parse::<usize>().is_ok()
+    {
+        rest = after;
+    }
+
+    let (kind, msg) = rest.trim_start().split_once(": ")?;
+    let level = match kind {
+        "error" | "fatal error" => Level::Error,
+        "warning" => Level::Warning,
+        "note" => Level::Note,
+        _ => return None,
+    };
+    Some((number, level, msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mapping() {
+        let source = "func main(): void {\n    let x: i32 = y;\n}\n";
+        let stderr = "main.clla: In function 'main':\n\
+                      main.clla:2:26: error: 'y' undeclared (first use in this function)\n    \
+                      2 |     let x: i32 = y;\n      |                          ^\n\
+                      main.clla:2:26: note: each undeclared identifier is reported only once\n\
+                      main.clla:9:1: warning: control reaches end of non-void function\n\
+                      /usr/bin/ld: cannot find -lgeo\n";
+
+        assert_eq!(
+            map_diagnostics(stderr, source, "main.clla"),
+            vec![
+                CDiagnostic::Mapped(
+                    Level::Error,
+                    (
+                        24..39,
+                        "In the generated C: 'y' undeclared (first use in this function)"
+                            .to_string()
+                    )
+                ),
+                CDiagnostic::Mapped(
+                    Level::Note,
+                    (
+                        24..39,
+                        "note: each undeclared identifier is reported only once".to_string()
+                    )
+                ),
+                // The line is past the end of the source, so it is in the runtime
+                CDiagnostic::Unmapped(
+                    "main.clla:9:1: warning: control reaches end of non-void function".to_string()
+                ),
+                CDiagnostic::Unmapped("/usr/bin/ld: cannot find -lgeo".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn sanitizer_flags() {
+        let args = |cc: &CCompiler, artifact| {
+            cc.command(artifact, Path::new("out"))
+                .get_args()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let cc = CCompiler::new("cc").lib("m");
+        assert_eq!(
+            args(&cc, Artifact::Object),
+            ["-c", "-x", "c", "-", "-o", "out"]
+        );
+
+        let cc = cc.sanitizers(Sanitizers::from_names("undefined,address").unwrap());
+        assert_eq!(
+            args(&cc, Artifact::Object),
+            [
+                "-c",
+                "-fsanitize=address,undefined",
+                "-fno-omit-frame-pointer",
+                "-x",
+                "c",
+                "-",
+                "-o",
+                "out"
+            ]
+        );
+        assert_eq!(
+            args(&cc, Artifact::Executable),
+            [
+                "-fsanitize=address,undefined",
+                "-fno-omit-frame-pointer",
+                "-x",
+                "c",
+                "-",
+                "-o",
+                "out",
+                "-lm"
+            ]
+        );
+    }
+}
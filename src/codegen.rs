@@ -0,0 +1,1201 @@
+//! Generates a C translation unit from a checked AST
+//!
+//! The AST is expected to have gone through the AST-to-AST lowering passes, so destructuring
+//! `let`s are expanded and constants are folded. Each castella construct maps onto tamago:
+//!
+//! - structs, unions, and enums become C types of the same name, which are `typedef`ed so that
+//!   they can be referred to without `struct`, and enum variants become `Enum_Variant`
+//! - functions become C functions declared with a prototype up front, so they can call each
+//!   other in any order, and `@statemachine` functions are lowered by
+//!   [`StateMachine`](crate::lowering::statemachine::StateMachine)
+//! - constants become `static const` data
+//! - `defer`red blocks are copied to every exit of their scope, in reverse order
+//!
+//! The includes the program needs are added at the end, along with the C runtime if anything
+//! uses it. Unless `--no-line-directives` is given, each function, global, and statement is
+//! preceded by a `#line` directive pointing at where it is in the source, so the C compiler
+//! reports its problems there and debuggers show the source.
+
+use std::collections::HashMap;
+
+use tamago::{
+    BaseType, Block, DoWhile, Enum, Expr, Field, Format, Formatter, Function, GlobalStatement, If,
+    IncludeAnalysis, Macro, ObjMacro, Parameter, Scope, Statement, Struct, Switch, TypeDef,
+    UnaryOp, Union, Variable, Variant, While,
+};
+
+use crate::Message;
+use crate::lowering::statemachine::StateMachine;
+use crate::lowering::*;
+use crate::parser::*;
+use crate::semantic_analyzer::consteval::ConstEvaluator;
+use crate::semantic_analyzer::type_checker::TypeChecker;
+
+/// The variable a returned value is kept in while the deferred blocks run
+const RETURN_VALUE: &str = "__ret";
+
+pub struct CodeGen<'ast> {
+    ast: &'ast [LocatedGlobalStmt],
+    options: LowerOptions,
+
+    /// The structs, unions, enums, and aliases by name
+    user_types: HashMap<&'ast str, &'ast GlobalStmt>,
+
+    /// The types of global variables, constants, and functions
+    globals: HashMap<&'ast str, Type>,
+
+    /// The scopes of the function being generated, innermost last
+    scopes: Vec<LocalScope<'ast>>,
+
+    /// The return type of the function being generated
+    ret: Type,
+
+    /// Whether the function being generated is a `main` returning `void`, which returns `int` in
+    /// C
+    in_void_main: bool,
+}
+
+#[derive(Default)]
+struct LocalScope<'ast> {
+    types: HashMap<&'ast str, Type>,
+
+    /// The deferred blocks in the order they were deferred
+    deferred: Vec<&'ast Vec<LocatedStmt>>,
+
+    /// What the scope is the body of, which decides whether `break` and `continue` leave it
+    kind: ScopeKind,
+}
+
+#[derive(Default, Clone, Copy, PartialEq)]
+enum ScopeKind {
+    #[default]
+    Block,
+
+    /// The body of a loop, which `break` and `continue` leave
+    Loop,
+
+    /// A case of a switch, which only `break` leaves
+    Case,
+}
+
+impl<'ast> CodeGen<'ast> {
+    pub fn new(ast: &'ast [LocatedGlobalStmt]) -> Self {
+        let mut user_types = HashMap::new();
+        let mut globals = HashMap::new();
+
+        for stmt in ast {
+            match &stmt.node {
+                GlobalStmt::Enum { name, .. }
+                | GlobalStmt::Struct { name, .. }
+                | GlobalStmt::Union { name, .. }
+                | GlobalStmt::Alias { name, .. } => {
+                    user_types.insert(name.as_str(), &stmt.node);
+                }
+                GlobalStmt::Function {
+                    name, params, ret, ..
+                }
+                | GlobalStmt::ExternFunction { name, params, ret } => {
+                    let params = params.iter().map(|(_, t)| t.node.clone()).collect();
+                    globals.insert(
+                        name.as_str(),
+                        Type::Func(params, Box::new(ret.node.clone())),
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        let mut codegen = Self {
+            ast,
+            options: LowerOptions::default(),
+            user_types,
+            globals,
+            scopes: vec![],
+            ret: Type::Void,
+            in_void_main: false,
+        };
+
+        // Globals are typed in order, so later ones can be inferred from earlier ones
+        for stmt in ast {
+            let (name, t, value) = match &stmt.node {
+                GlobalStmt::Variable { name, t, value, .. } => (name, t.clone(), value.as_ref()),
+                GlobalStmt::Constant { name, t, value, .. } => {
+                    (name, t.as_ref().map(|t| t.node.clone()), Some(value))
+                }
+                _ => continue,
+            };
+
+            if let Some(t) = t.or_else(|| value.and_then(|v| codegen.type_of(v).ok())) {
+                codegen.globals.insert(name.as_str(), t);
+            }
+        }
+
+        codegen
+    }
+
+    pub fn options(mut self, options: LowerOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the translation unit, or the constructs that cannot be represented in C
+    pub fn generate(mut self) -> Result<Scope, Vec<Message>> {
+        let mut errors = vec![];
+        let mut types = vec![];
+        let mut prototypes = vec![];
+        let mut data = vec![];
+        let mut functions = vec![];
+
+        // Structs and unions are declared up front so that they can refer to each other
+        for stmt in self.ast {
+            match &stmt.node {
+                GlobalStmt::Struct { name, .. } => {
+                    types.push(typedef(BaseType::Struct(name.clone()), name));
+                }
+                GlobalStmt::Union { name, .. } => {
+                    types.push(typedef(BaseType::Union(name.clone()), name));
+                }
+                _ => {}
+            }
+        }
+
+        // Each `Result` is defined as soon as the types it holds by value are
+        let mut pending = results::used_types(self.ast);
+        for t in &pending {
+            if let Type::Result(ok, err) = t {
+                let name = results::type_name(ok, err);
+                types.push(typedef(BaseType::Struct(name.clone()), &name));
+            }
+        }
+        let mut defined = vec![];
+        self.define_results(&mut pending, &defined, &mut types, &mut errors);
+
+        let includes = IncludeAnalysis::new();
+
+        for stmt in self.ast {
+            let directive = self
+                .options
+                .line_directive(&stmt.span)
+                .map(GlobalStatement::LineDirective);
+            let res = match &stmt.node {
+                GlobalStmt::Enum { name, variants, .. } => {
+                    types.push(GlobalStatement::Enum(lower_enum(name, variants)));
+                    types.push(typedef(BaseType::Enum(name.clone()), name));
+                    Ok(())
+                }
+                GlobalStmt::Struct { name, fields } => lower_fields(fields).map(|fields| {
+                    types.push(GlobalStatement::Struct(
+                        Struct::new(name.clone()).fields(fields).build(),
+                    ));
+                }),
+                GlobalStmt::Union { name, fields } => lower_fields(fields).map(|fields| {
+                    types.push(GlobalStatement::Union(
+                        Union::new(name.clone()).fields(fields).build(),
+                    ));
+                }),
+                GlobalStmt::Alias { t, name } => lower_located_type(t).map(|lowered| {
+                    types.push(GlobalStatement::TypeDef(
+                        TypeDef::new(lowered, name.clone()).build(),
+                    ));
+                }),
+                // Standard functions are declared by their headers, whose prototypes may not
+                // match the castella declaration exactly (e.g. `printf` is variadic)
+                GlobalStmt::ExternFunction { name, .. } if includes.header_of(name).is_some() => {
+                    Ok(())
+                }
+                GlobalStmt::ExternFunction { name, params, ret } => {
+                    self.prototype(name, params, ret).map(|prototype| {
+                        prototypes.extend(directive.clone());
+                        prototypes.push(GlobalStatement::Function(prototype));
+                    })
+                }
+                GlobalStmt::Function { attrs, .. } if attrs.contains(&FuncAttr::StateMachine) => {
+                    self.lower_statemachine(stmt).map(|lowered| {
+                        functions.push(GlobalStatement::NewLine);
+
+                        // The state struct and each function stand in for the declaration
+                        for lowered in lowered {
+                            if !matches!(lowered, GlobalStatement::NewLine) {
+                                functions.extend(directive.clone());
+                            }
+                            functions.push(lowered);
+                        }
+                    })
+                }
+                GlobalStmt::Function {
+                    name,
+                    params,
+                    ret,
+                    body,
+                    ..
+                } => self.lower_function(name, params, ret, body).map(|func| {
+                    if name != "main" {
+                        prototypes.extend(directive.clone());
+                        prototypes.push(GlobalStatement::Function(Function {
+                            body: Block::new().build(),
+                            ..func.clone()
+                        }));
+                    }
+                    functions.push(GlobalStatement::NewLine);
+                    functions.extend(directive.clone());
+                    functions.push(GlobalStatement::Function(func));
+                }),
+                GlobalStmt::Variable { name, t, value, .. } => self
+                    .lower_global_variable(&stmt.span, name, t.as_ref(), value.as_ref())
+                    .map(|var| {
+                        data.extend(directive.clone());
+                        data.push(GlobalStatement::Variable(var));
+                    }),
+                GlobalStmt::Constant { name, value, .. } => self
+                    .lower_constant(&stmt.span, name, value)
+                    .map(|constant| {
+                        data.extend(directive.clone());
+                        data.push(constant);
+                    }),
+                GlobalStmt::Import { .. } => Ok(()),
+            };
+
+            if let Err(err) = res {
+                errors.push(err);
+            }
+
+            if let GlobalStmt::Enum { name, .. }
+            | GlobalStmt::Struct { name, .. }
+            | GlobalStmt::Union { name, .. }
+            | GlobalStmt::Alias { name, .. } = &stmt.node
+            {
+                defined.push(name.as_str());
+                self.define_results(&mut pending, &defined, &mut types, &mut errors);
+            }
+        }
+
+        // The rest hold types that aren't defined, which C reports
+        defined.extend(self.user_types.keys());
+        self.define_results(&mut pending, &defined, &mut types, &mut errors);
+
+        // `parse_i32` calls a helper returning its `Result`, which comes before every function
+        if functions
+            .iter()
+            .any(|stmt| stmt.to_string().contains(conversions::PARSE_I32))
+        {
+            match conversions::parse_i32_helper() {
+                Ok(helper) => {
+                    functions.insert(0, GlobalStatement::Function(helper));
+                    functions.insert(0, GlobalStatement::NewLine);
+                }
+                Err(msg) => errors.push((0..0, msg)),
+            }
+        }
+
+        // A program whose entry point has another name starts at a `main` that calls it
+        if let Some(entry) = &self.options.entry
+            && let Some(Type::Func(_, ret)) = self.globals.get(entry.as_str())
+        {
+            let call = Expr::new_fn_call_with_name(entry.clone(), vec![]);
+            let body = if **ret == Type::Void {
+                vec![Statement::expr(call), Statement::ret(Expr::Int(0))]
+            } else {
+                vec![Statement::ret(call)]
+            };
+            functions.push(GlobalStatement::NewLine);
+
+            // It is reported at the entry point, which it stands in for
+            let span = self.ast.iter().find_map(|stmt| match &stmt.node {
+                GlobalStmt::Function { name, .. } if name == entry => Some(&stmt.span),
+                _ => None,
+            });
+            if let Some(directive) = span.and_then(|span| self.options.line_directive(span)) {
+                functions.push(GlobalStatement::LineDirective(directive));
+            }
+            functions.push(GlobalStatement::Function(
+                Function::new("main".to_string(), tamago::Type::new(BaseType::Int).build())
+                    .body(Block::new().statements(body).build())
+                    .build(),
+            ));
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let mut global_stmts = vec![];
+        for section in [types, prototypes, data] {
+            if !section.is_empty() {
+                global_stmts.extend(section);
+                global_stmts.push(GlobalStatement::NewLine);
+            }
+        }
+        global_stmts.extend(functions.into_iter().skip(1));
+
+        let mut scope = Scope::new().global_statements(global_stmts).build();
+        includes.insert_missing(&mut scope);
+
+        // The runtime's names are all prefixed with `castella_`
+        if scope.to_string().contains("castella_") {
+            scope.prepend(GlobalStatement::NewLine);
+            scope.prepend(GlobalStatement::Raw(
+                darray::RUNTIME_HEADER.trim_end().to_string(),
+            ));
+            for define in self.options.sanitizers.defines().into_iter().rev() {
+                scope.prepend(GlobalStatement::Macro(Macro::Obj(
+                    ObjMacro::new(define.to_string()).build(),
+                )));
+            }
+        }
+
+        Ok(scope)
+    }
+
+    /// Defines the `Result`s in `pending` whose types are all in `defined` or built in
+    fn define_results(
+        &self,
+        pending: &mut Vec<Type>,
+        defined: &[&str],
+        types: &mut Vec<GlobalStatement>,
+        errors: &mut Vec<Message>,
+    ) {
+        // The `Result`s a `Result` holds come before it, so one pass in order is enough
+        for t in std::mem::take(pending) {
+            let Type::Result(ok, err) = &t else {
+                unreachable!()
+            };
+
+            let mut held = vec![];
+            value_types(&t, &mut held);
+            if !held.iter().all(|name| defined.contains(name)) {
+                pending.push(t);
+                continue;
+            }
+
+            let fields = results::fields(ok, err)
+                .into_iter()
+                .map(|(name, t)| lower_type(&t).map(|t| Field::new(name.to_string(), t).build()))
+                .collect::<Result<Vec<_>, _>>();
+            match fields {
+                Ok(fields) => types.push(GlobalStatement::Struct(
+                    Struct::new(results::type_name(ok, err))
+                        .fields(fields)
+                        .build(),
+                )),
+                Err(msg) => errors.push((0..0, msg)),
+            }
+        }
+    }
+
+    fn lower_statemachine(
+        &self,
+        stmt: &'ast LocatedGlobalStmt,
+    ) -> Result<Vec<GlobalStatement>, Message> {
+        let GlobalStmt::Function {
+            name,
+            params,
+            ret,
+            body,
+            ..
+        } = &stmt.node
+        else {
+            unreachable!()
+        };
+
+        StateMachine::new(name, params, ret, body)
+            .options(self.options.clone())
+            .lower()
+    }
+
+    fn prototype(
+        &self,
+        name: &str,
+        params: &[(String, LocatedType)],
+        ret: &LocatedType,
+    ) -> Result<Function, Message> {
+        let params = params
+            .iter()
+            .map(|(name, t)| {
+                lower_param_type(&t.node)
+                    .map(|lowered| Parameter::new(name.clone(), lowered).build())
+                    .map_err(|msg| (t.span.clone(), msg))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Function::new(name.to_string(), lower_located_type(ret)?)
+            .params(params)
+            .build())
+    }
+
+    fn lower_function(
+        &mut self,
+        name: &'ast str,
+        params: &'ast [(String, LocatedType)],
+        ret: &'ast LocatedType,
+        body: &'ast Vec<LocatedStmt>,
+    ) -> Result<Function, Message> {
+        let mut func = self.prototype(name, params, ret)?;
+
+        self.ret = ret.node.clone();
+        self.in_void_main = name == "main" && ret.node == Type::Void;
+        if self.in_void_main {
+            func.ret = tamago::Type::new(BaseType::Int).build();
+        }
+
+        self.scopes = vec![LocalScope {
+            types: params
+                .iter()
+                .map(|(name, t)| (name.as_str(), t.node.clone()))
+                .collect(),
+            ..Default::default()
+        }];
+        let mut stmts = self.lower_scope(body, ScopeKind::Block)?;
+        self.scopes.clear();
+
+        // An empty body would be printed as a prototype
+        if stmts.is_empty() || (self.in_void_main && !ends_with_exit(body)) {
+            stmts.append(&mut self.lower_return(None)?);
+        }
+
+        func.body = Block::new().statements(stmts).build();
+        Ok(func)
+    }
+
+    fn lower_global_variable(
+        &self,
+        span: &Span,
+        name: &str,
+        t: Option<&Type>,
+        value: Option<&LocatedExpr>,
+    ) -> Result<Variable, Message> {
+        let t = match (t, value) {
+            (Some(t), _) => t.clone(),
+            (None, Some(value)) => self.type_of(value)?,
+            (None, None) => unreachable!("the type checker requires a type or a value"),
+        };
+
+        let mut var = Variable::new(
+            name.to_string(),
+            lower_type(&t).map_err(|msg| (span.clone(), msg))?,
+        );
+        if let Some(value) = value {
+            var = var.value(self.lower_init(value)?);
+        }
+        Ok(var.build())
+    }
+
+    fn lower_constant(
+        &self,
+        span: &Span,
+        name: &str,
+        value: &'ast LocatedExpr,
+    ) -> Result<GlobalStatement, Message> {
+        let t = &self.globals[name];
+
+        if let Some(evaluated) = ConstEvaluator::new(self.ast).eval(value)? {
+            return constants::lower_constant(name, t, &evaluated)
+                .map(GlobalStatement::Variable)
+                .map_err(|msg| (span.clone(), msg));
+        }
+
+        // The rest, like `sizeof(T)`, only C can evaluate, so they become macros that can still
+        // be used where C requires constant expressions
+        let lowered = lower_type(t).map_err(|msg| (span.clone(), msg))?;
+        let cast = Expr::new_cast(lowered, self.lower_expr(value)?);
+        let mut res = String::new();
+        cast.format(&mut Formatter::new(&mut res))
+            .map_err(|_| (span.clone(), format!("Couldn't lower constant '{name}'")))?;
+
+        Ok(GlobalStatement::Macro(Macro::Obj(
+            ObjMacro::new(name.to_string())
+                .value(format!("({res})"))
+                .build(),
+        )))
+    }
+
+    /// Lowers the statements of a new scope, followed by its deferred blocks if the end of the
+    /// scope can be reached
+    fn lower_scope(
+        &mut self,
+        body: &'ast Vec<LocatedStmt>,
+        kind: ScopeKind,
+    ) -> Result<Vec<Statement>, Message> {
+        if !self.scopes.is_empty() {
+            self.scopes.push(LocalScope {
+                kind,
+                ..Default::default()
+            });
+        }
+
+        let mut stmts = vec![];
+        for stmt in body {
+            let directive = self.options.line_directive(&stmt.span);
+            stmts.extend(directive.map(Statement::LineDirective));
+            stmts.append(&mut self.lower_stmt(stmt)?);
+        }
+
+        if !ends_with_exit(body) {
+            stmts.append(&mut self.run_deferred(1)?);
+        }
+
+        if self.scopes.len() > 1 {
+            self.scopes.pop();
+        }
+
+        Ok(stmts)
+    }
+
+    fn lower_block(
+        &mut self,
+        body: &'ast Vec<LocatedStmt>,
+        kind: ScopeKind,
+    ) -> Result<Block, Message> {
+        Ok(Block::new()
+            .statements(self.lower_scope(body, kind)?)
+            .build())
+    }
+
+    /// Lowers the deferred blocks of the innermost `scopes` scopes, innermost and most recently
+    /// deferred first
+    fn run_deferred(&mut self, scopes: usize) -> Result<Vec<Statement>, Message> {
+        let blocks: Vec<_> = self
+            .scopes
+            .iter()
+            .rev()
+            .take(scopes)
+            .flat_map(|scope| scope.deferred.iter().rev().copied())
+            .collect();
+
+        // Each copy is scoped so that the locals of a deferred block can't clash with the ones
+        // around it
+        blocks
+            .into_iter()
+            .map(|block| Ok(self.lower_block(block, ScopeKind::Block)?.scoped()))
+            .collect()
+    }
+
+    fn lower_return(
+        &mut self,
+        value: Option<&'ast LocatedExpr>,
+    ) -> Result<Vec<Statement>, Message> {
+        let mut deferred = self.run_deferred(self.scopes.len())?;
+
+        let value = match value {
+            Some(value) => Some(self.lower_value(value, &self.ret)?),
+            None if self.in_void_main => Some(Expr::Int(0)),
+            None => None,
+        };
+
+        Ok(match value {
+            Some(value) if !deferred.is_empty() => {
+                let t = lower_type(&self.ret).map_err(|msg| (0..0, msg))?;
+                let mut stmts = vec![Statement::Variable(
+                    Variable::new(RETURN_VALUE.to_string(), t)
+                        .value(value)
+                        .build(),
+                )];
+                stmts.append(&mut deferred);
+                stmts.push(Statement::ret(Expr::new_ident_with_str(RETURN_VALUE)));
+                stmts
+            }
+            Some(value) => vec![Statement::ret(value)],
+            None => {
+                deferred.push(Statement::ret_void());
+                deferred
+            }
+        })
+    }
+
+    /// Returns the statements that run the deferred blocks of the scopes `break` or `continue`
+    /// leaves, up to the innermost scope of one of the `kinds`
+    fn leave(&mut self, kinds: &[ScopeKind]) -> Result<Vec<Statement>, Message> {
+        let scopes = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| kinds.contains(&scope.kind))
+            .map_or(0, |i| i + 1);
+        self.run_deferred(scopes)
+    }
+
+    fn lower_stmt(&mut self, stmt: &'ast LocatedStmt) -> Result<Vec<Statement>, Message> {
+        let Located { node: stmt, span } = stmt;
+
+        match stmt {
+            Stmt::Variable {
+                name,
+                t,
+                value,
+                is_const,
+                ..
+            } => {
+                let t = match (t, value) {
+                    (Some(t), _) => t.clone(),
+                    (None, Some(value)) => self.type_of(value)?,
+                    (None, None) => unreachable!("the type checker requires a type or a value"),
+                };
+
+                let mut lowered = lower_type(&t).map_err(|msg| (span.clone(), msg))?;
+                if *is_const {
+                    make_const(&mut lowered);
+                }
+
+                let mut var = Variable::new(name.clone(), lowered);
+                if let Some(value) = value {
+                    var = var.value(self.lower_init(value)?);
+                }
+
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.types.insert(name, t);
+                }
+
+                Ok(vec![Statement::Variable(var.build())])
+            }
+
+            Stmt::Destructure { .. } => Err((
+                span.clone(),
+                "Destructuring must be expanded before code generation".to_string(),
+            )),
+
+            Stmt::Expression { expr } => Ok(vec![Statement::expr(self.lower_expr(expr)?)]),
+
+            Stmt::Return { value } => self.lower_return(value.as_ref()),
+
+            Stmt::Yield { .. } => Err((
+                span.clone(),
+                "'yield' is only allowed in @statemachine functions".to_string(),
+            )),
+
+            Stmt::Break => {
+                let mut stmts = self.leave(&[ScopeKind::Loop, ScopeKind::Case])?;
+                stmts.push(Statement::Break);
+                Ok(stmts)
+            }
+
+            Stmt::Continue => {
+                let mut stmts = self.leave(&[ScopeKind::Loop])?;
+                stmts.push(Statement::Continue);
+                Ok(stmts)
+            }
+
+            Stmt::If { cond, then, other } => {
+                let mut lowered =
+                    If::new(self.lower_expr(cond)?).then(self.lower_block(then, ScopeKind::Block)?);
+                if let Some(other) = other {
+                    lowered = lowered.other(self.lower_block(other, ScopeKind::Block)?);
+                }
+
+                Ok(vec![Statement::If(lowered.build())])
+            }
+
+            Stmt::While {
+                cond,
+                body,
+                do_while: false,
+            } => Ok(vec![Statement::While(
+                While::new(self.lower_expr(cond)?)
+                    .body(self.lower_block(body, ScopeKind::Loop)?)
+                    .build(),
+            )]),
+
+            Stmt::While {
+                cond,
+                body,
+                do_while: true,
+            } => Ok(vec![Statement::DoWhile(
+                DoWhile::new(self.lower_expr(cond)?)
+                    .body(self.lower_block(body, ScopeKind::Loop)?)
+                    .build(),
+            )]),
+
+            Stmt::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                let mut lowered = Switch::new(self.lower_expr(value)?);
+                for case in cases {
+                    // The values of a case but the last fall through to its body
+                    let (last, rest) = case.values.split_last().expect("a case has a value");
+                    for value in rest {
+                        lowered = lowered.case(self.lower_expr(value)?, Block::new().build());
+                    }
+                    lowered = lowered.case(self.lower_expr(last)?, self.lower_case(&case.body)?);
+                }
+                if let Some(default) = default {
+                    lowered = lowered.default(self.lower_case(default)?);
+                }
+
+                Ok(vec![Statement::Switch(lowered.build())])
+            }
+
+            Stmt::Block { body } => Ok(vec![self.lower_block(body, ScopeKind::Block)?.scoped()]),
+
+            Stmt::Defer { body } => {
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.deferred.push(body);
+                }
+                Ok(vec![])
+            }
+
+            Stmt::Destroy { expr } => Ok(vec![Statement::expr(darray::destroy(
+                self.lower_expr(expr)?,
+            ))]),
+
+            Stmt::Free { expr } => {
+                let ptr = self.lower_expr(expr)?;
+                let ptr = match self.type_of(expr)? {
+                    Type::Str => alloc::void_ptr(ptr),
+                    _ => ptr,
+                };
+                Ok(vec![Statement::expr(alloc::free(ptr))])
+            }
+        }
+    }
+
+    /// Lowers the body of a case, which leaves the switch at its end instead of falling through
+    fn lower_case(&mut self, body: &'ast Vec<LocatedStmt>) -> Result<Block, Message> {
+        let mut stmts = self.lower_scope(body, ScopeKind::Case)?;
+        if !ends_with_exit(body) {
+            stmts.push(Statement::Break);
+        }
+
+        Ok(Block::new().statements(stmts).build())
+    }
+
+    fn lower_expr(&self, expr: &LocatedExpr) -> Result<Expr, Message> {
+        use crate::parser::Expr::*;
+
+        let whole = expr;
+        let Located { node: expr, span } = expr;
+        let lower_all = |exprs: &[LocatedExpr]| {
+            exprs
+                .iter()
+                .map(|expr| self.lower_expr(expr))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        Ok(match expr {
+            Int(i) => int_literal((*i).into()),
+            UInt(u) => int_literal((*u).into()),
+            Double(d) => Expr::Double(*d),
+            Bool(b) => Expr::Bool(*b),
+            Char(c) => Expr::Char(*c as char),
+            Str(s) => Expr::Str(escape_c_str(s)),
+            Ident(name) => Expr::new_ident(name.clone()),
+            Binary { left, op, right } => {
+                Expr::new_binary(self.lower_expr(left)?, op.clone(), self.lower_expr(right)?)
+            }
+            Parenthesized { expr } => Expr::new_parenthesized(self.lower_expr(expr)?),
+            Unary { op, expr } => Expr::new_unary(self.lower_expr(expr)?, op.clone()),
+            Assign { lvalue, op, value } => Expr::new_assign(
+                self.lower_expr(lvalue)?,
+                op.clone(),
+                self.lower_value(value, &self.type_of(lvalue)?)?,
+            ),
+            Ternary { cond, lexpr, rexpr } => Expr::new_ternary(
+                self.lower_expr(cond)?,
+                self.lower_expr(lexpr)?,
+                self.lower_expr(rexpr)?,
+            ),
+            // `ok(x)` and `err(e)`, which the type checker gives the `Result` they create
+            FnCall {
+                name, args, result, ..
+            } if result.borrow().is_some() => {
+                let t = result.borrow().clone().unwrap();
+                let is_ok = matches!(&name.node, Ident(name) if name == "ok");
+                results::literal(
+                    &lower_type(&t).map_err(|msg| (span.clone(), msg))?,
+                    is_ok,
+                    args.first().map(|arg| self.lower_expr(arg)).transpose()?,
+                )
+            }
+            FnCall { name, args, .. } if self.is_builtin(name, "append") => {
+                let Type::Pointer(arr_t) = self.type_of(&args[0])? else {
+                    unreachable!("the type checker only allows pointers to dynamic arrays")
+                };
+                darray::append(
+                    self.lower_expr(&args[0])?,
+                    &self.elem_type(&arr_t, span)?,
+                    self.lower_expr(&args[1])?,
+                )
+            }
+            FnCall { name, args, .. } if self.is_builtin(name, "approx_eq") => {
+                Expr::new_fn_call_with_name("castella_approx_eq".to_string(), lower_all(args)?)
+            }
+            FnCall { name, args, .. } if self.is_builtin(name, "int_to_str") => {
+                conversions::int_to_str(
+                    self.lower_expr(&args[0])?,
+                    self.type_of(&args[0])?.is_unsigned(),
+                    self.options.location(span),
+                )
+            }
+            FnCall { name, args, .. } if self.is_builtin(name, "parse_i32") => {
+                conversions::parse_i32(self.lower_expr(&args[0])?)
+            }
+            FnCall { name, args, .. } if self.is_builtin(name, "len") => {
+                match self.type_of(&args[0])? {
+                    Type::Str => Expr::new_cast(
+                        tamago::Type::new(BaseType::UInt64).build(),
+                        Expr::new_fn_call_with_name("strlen".to_string(), lower_all(args)?),
+                    ),
+                    Type::Array(len, _) => Expr::UInt(len as u64),
+                    _ => darray::len(self.lower_expr(&args[0])?),
+                }
+            }
+            FnCall { name, args, .. } => {
+                let params = match self.type_of(name) {
+                    Ok(Type::Func(params, _)) => params,
+                    _ => vec![],
+                };
+                let args = args
+                    .iter()
+                    .enumerate()
+                    .map(|(i, arg)| match params.get(i) {
+                        Some(t) => self.lower_value(arg, t),
+                        None => self.lower_expr(arg),
+                    })
+                    .collect::<Result<_, _>>()?;
+                Expr::new_fn_call(self.lower_expr(name)?, args)
+            }
+            MemAccess { expr, member } => {
+                Expr::new_mem_access(self.lower_expr(expr)?, member.clone())
+            }
+            EnumVarAccess { ident, variant } => Expr::new_ident(format!("{ident}_{variant}")),
+            ModuleAccess { module, member } => {
+                return Err((
+                    span.clone(),
+                    format!("'{module}::{member}' refers to a module that isn't linked"),
+                ));
+            }
+            Tuple { .. } => {
+                return Err((
+                    span.clone(),
+                    "Multiple return values must be lowered before code generation".to_string(),
+                ));
+            }
+            Try { .. } => {
+                return Err((
+                    span.clone(),
+                    "'try' must be lowered before code generation".to_string(),
+                ));
+            }
+            ArrIndex { arr, idx } => match self.type_of(arr)? {
+                Type::DArray(elem) => darray::index(
+                    self.lower_expr(arr)?,
+                    &self.elem_type(&Type::DArray(elem), span)?,
+                    self.lower_expr(idx)?,
+                    self.options.trap_location(span),
+                ),
+                _ => Expr::new_arr_index(self.lower_expr(arr)?, self.lower_expr(idx)?),
+            },
+            Cast { t, expr, check } => casts::cast(
+                &t.node,
+                lower_located_type(t)?,
+                self.lower_expr(expr)?,
+                check.get(),
+                self.options.cast_location(span),
+            ),
+            Sizeof { t } => Expr::new_sizeof(lower_type(t).map_err(|msg| (span.clone(), msg))?),
+            InitArr { .. } | InitArrDesignated { .. } | InitStruct { .. } => {
+                self.lower_value(whole, &self.type_of(whole)?)?
+            }
+            Make { t: Type::DArray(_) } => darray::make(),
+            Make { t } => {
+                return Err((span.clone(), format!("'make' cannot create '{t}'")));
+            }
+            New { t } => alloc::new(
+                lower_type(t).map_err(|msg| (span.clone(), msg))?,
+                self.options.location(span),
+            ),
+        })
+    }
+
+    /// Lowers a value of type `t`, where an initializer becomes a compound literal like
+    /// `(Point){.x=1, .y=2}`, since it's only valid C on its own in a declaration
+    fn lower_value(&self, expr: &LocatedExpr, t: &Type) -> Result<Expr, Message> {
+        use crate::parser::Expr::*;
+
+        if !matches!(
+            expr.node,
+            InitArr { .. } | InitArrDesignated { .. } | InitStruct { .. }
+        ) {
+            return self.lower_expr(expr);
+        }
+
+        let lowered = lower_type(t).map_err(|msg| (expr.span.clone(), msg))?;
+        let dims: String = lowered
+            .array
+            .iter()
+            .map(|size| format!("[{size}]"))
+            .collect();
+        let init = self.lower_init(expr)?;
+        Ok(Expr::Raw(format!("({lowered}{dims}){init}")))
+    }
+
+    /// Lowers the value a variable is declared with, where an initializer is left as it is, and
+    /// so are the ones nested in it
+    fn lower_init(&self, expr: &LocatedExpr) -> Result<Expr, Message> {
+        use crate::parser::Expr::*;
+
+        let lower_all = |exprs: &[LocatedExpr]| {
+            exprs
+                .iter()
+                .map(|expr| self.lower_init(expr))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        Ok(match &expr.node {
+            InitArr { elems } => Expr::new_init_arr_in_order(lower_all(elems)?),
+            InitArrDesignated { idxs, elems } => {
+                Expr::new_init_arr_designated(idxs.clone(), lower_all(elems)?)
+            }
+            InitStruct { args, .. } => {
+                let mut names = vec![];
+                let mut values = vec![];
+                for (name, value) in args {
+                    names.push(name.clone());
+                    values.push(self.lower_init(value)?);
+                }
+                Expr::new_init_struct_designated(names, values)
+            }
+            _ => self.lower_expr(expr)?,
+        })
+    }
+
+    /// Whether `name` refers to the built-in function `builtin` rather than a declaration that
+    /// shadows it
+    fn is_builtin(&self, name: &LocatedExpr, builtin: &str) -> bool {
+        matches!(&name.node, crate::parser::Expr::Ident(name)
+            if name == builtin && self.lookup(name).is_none())
+    }
+
+    /// Returns the lowered element type of a dynamic array
+    fn elem_type(&self, arr_t: &Type, span: &Span) -> Result<tamago::Type, Message> {
+        let Type::DArray(elem) = arr_t else {
+            unreachable!("the type checker only allows dynamic arrays")
+        };
+
+        let lowered = lower_type(elem).map_err(|msg| (span.clone(), msg))?;
+        if lowered.is_array() {
+            return Err((
+                span.clone(),
+                format!("'{arr_t}' cannot be represented in C yet"),
+            ));
+        }
+        Ok(lowered)
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Type> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.types.get(name))
+            .or_else(|| self.globals.get(name))
+    }
+
+    /// Returns the type of an expression the type checker has accepted, which C needs for
+    /// variables declared without one
+    fn type_of(&self, expr: &LocatedExpr) -> Result<Type, Message> {
+        use crate::parser::Expr::*;
+        use tamago::BinOp;
+
+        let Located { node: e, span } = expr;
+        let unknown = || {
+            (
+                span.clone(),
+                "The type of this expression is unknown".to_string(),
+            )
+        };
+
+        Ok(match e {
+            Int(i) if i32::try_from(*i).is_ok() => Type::Int32,
+            Int(_) => Type::Int64,
+            UInt(_) => Type::UInt64,
+            Double(_) => Type::Double,
+            Bool(_) => Type::Bool,
+            Char(_) => Type::Char,
+            Str(_) => Type::Str,
+            Ident(name) => self.lookup(name).cloned().ok_or_else(unknown)?,
+            Binary { left, op, right } => {
+                let (left_t, right_t) = (self.type_of(left)?, self.type_of(right)?);
+                match op {
+                    BinOp::Eq
+                    | BinOp::NEq
+                    | BinOp::LT
+                    | BinOp::GT
+                    | BinOp::LTE
+                    | BinOp::GTE
+                    | BinOp::And
+                    | BinOp::Or => Type::Bool,
+                    BinOp::LShift | BinOp::RShift => left_t,
+                    _ => match (&left_t, &right_t) {
+                        (Type::Pointer(_), Type::Pointer(_)) => Type::Int64,
+                        (Type::Pointer(_), _) => left_t,
+                        (_, Type::Pointer(_)) => right_t,
+                        _ => TypeChecker::arith_result(left, left_t, right, right_t),
+                    },
+                }
+            }
+            Parenthesized { expr } => self.type_of(expr)?,
+            Unary { op, expr } => match (op, self.type_of(expr)?) {
+                (UnaryOp::AddrOf, t) => Type::Pointer(Box::new(t)),
+                (UnaryOp::Deref, Type::Pointer(inner)) => *inner,
+                (_, t) => t,
+            },
+            Assign { lvalue, .. } => self.type_of(lvalue)?,
+            Ternary { lexpr, .. } => self.type_of(lexpr)?,
+            FnCall { result, .. } if result.borrow().is_some() => result.borrow().clone().unwrap(),
+            FnCall { name, .. } if self.is_builtin(name, "append") => Type::Void,
+            FnCall { name, .. } if self.is_builtin(name, "approx_eq") => Type::Bool,
+            FnCall { name, .. } if self.is_builtin(name, "int_to_str") => Type::Str,
+            FnCall { name, .. } if self.is_builtin(name, "parse_i32") => {
+                conversions::parse_i32_type()
+            }
+            FnCall { name, .. } if self.is_builtin(name, "len") => Type::UInt64,
+            FnCall { name, .. } => match self.type_of(name)? {
+                Type::Func(_, ret) => *ret,
+                _ => return Err(unknown()),
+            },
+            MemAccess { expr, member } => {
+                let t = self.type_of(expr)?;
+                if let Type::Result(ok, err) = self.resolve_alias(&t) {
+                    return results::fields(ok, err)
+                        .into_iter()
+                        .find(|(name, _)| name == member)
+                        .map(|(_, t)| t)
+                        .ok_or_else(unknown);
+                }
+
+                let fields = match self.user_type(&t) {
+                    Some(GlobalStmt::Struct { fields, .. } | GlobalStmt::Union { fields, .. }) => {
+                        fields
+                    }
+                    _ => return Err(unknown()),
+                };
+                fields
+                    .iter()
+                    .find(|(name, _)| name == member)
+                    .map(|(_, t)| t.node.clone())
+                    .ok_or_else(unknown)?
+            }
+            EnumVarAccess { ident, .. } | InitStruct { ident, .. } => {
+                Type::UserDefinedType(ident.clone())
+            }
+            Try { expr, .. } => match self.resolve_alias(&self.type_of(expr)?) {
+                Type::Result(ok, _) => *ok.clone(),
+                _ => return Err(unknown()),
+            },
+            ModuleAccess { .. } | Tuple { .. } => return Err(unknown()),
+            ArrIndex { arr, .. } => match self.type_of(arr)? {
+                Type::Array(_, elem) | Type::DArray(elem) | Type::Pointer(elem) => *elem,
+                Type::Str => Type::Char,
+                _ => return Err(unknown()),
+            },
+            Cast { t, .. } => t.node.clone(),
+            Sizeof { .. } => Type::UInt64,
+            InitArr { elems } => {
+                let (first, rest) = elems.split_first().ok_or_else(unknown)?;
+                Type::Array(elems.len(), Box::new(self.type_of_elems(first, rest)?))
+            }
+            InitArrDesignated { idxs, elems } => {
+                let (first, rest) = elems.split_first().ok_or_else(unknown)?;
+                let len = idxs.iter().max().map_or(0, |i| i + 1);
+                Type::Array(len, Box::new(self.type_of_elems(first, rest)?))
+            }
+            Make { t } => t.clone(),
+            New { t } => Type::Pointer(Box::new(t.clone())),
+        })
+    }
+
+    /// Returns the type of the elements of an array literal, promoted like the type checker does
+    fn type_of_elems(&self, first: &LocatedExpr, rest: &[LocatedExpr]) -> Result<Type, Message> {
+        let mut widest = (first, self.type_of(first)?);
+        for elem in rest {
+            let t = self.type_of(elem)?;
+            widest = TypeChecker::unify_elem(widest, elem, t).ok_or_else(|| {
+                (
+                    elem.span.clone(),
+                    "The type of this element doesn't match the others".to_string(),
+                )
+            })?;
+        }
+
+        Ok(widest.1)
+    }
+
+    /// Returns the type the alias `t` refers to, or `t` if it isn't an alias
+    fn resolve_alias<'t>(&self, t: &'t Type) -> &'t Type
+    where
+        'ast: 't,
+    {
+        match t {
+            Type::UserDefinedType(name) => match self.user_types.get(name.as_str()) {
+                Some(GlobalStmt::Alias { t, .. }) => self.resolve_alias(&t.node),
+                _ => t,
+            },
+            _ => t,
+        }
+    }
+
+    /// Returns the struct, union, or enum `t` refers to, looking through aliases
+    fn user_type(&self, t: &Type) -> Option<&'ast GlobalStmt> {
+        let Type::UserDefinedType(name) = t else {
+            return None;
+        };
+
+        match self.user_types.get(name.as_str())? {
+            GlobalStmt::Alias { t, .. } => self.user_type(&t.node),
+            user_type => Some(user_type),
+        }
+    }
+}
+
+/// Adds the names of the user-defined types `t` holds by value, rather than through a pointer, to
+/// `names`
+fn value_types<'t>(t: &'t Type, names: &mut Vec<&'t str>) {
+    match t {
+        Type::UserDefinedType(name) => names.push(name),
+        Type::Array(_, elem) => value_types(elem, names),
+        Type::Result(ok, err) => {
+            value_types(ok, names);
+            value_types(err, names);
+        }
+        Type::Tuple(elems) => {
+            for elem in elems {
+                value_types(elem, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn lower_located_type(t: &LocatedType) -> Result<tamago::Type, Message> {
+    lower_type(&t.node).map_err(|msg| (t.span.clone(), msg))
+}
+
+fn lower_fields(fields: &[(String, LocatedType)]) -> Result<Vec<Field>, Message> {
+    fields
+        .iter()
+        .map(|(name, t)| lower_located_type(t).map(|t| Field::new(name.clone(), t).build()))
+        .collect()
+}
+
+fn lower_enum(name: &str, variants: &[(String, Option<i64>)]) -> Enum {
+    Enum::new(name.to_string())
+        .variants(
+            variants
+                .iter()
+                .map(|(variant, value)| {
+                    let mut lowered = Variant::new(format!("{name}_{variant}"));
+                    if let Some(value) = value {
+                        lowered = lowered.value(*value);
+                    }
+                    lowered.build()
+                })
+                .collect(),
+        )
+        .build()
+}
+
+/// `typedef struct Name Name;`, so that the type can be used without its tag
+fn typedef(base: BaseType, name: &str) -> GlobalStatement {
+    GlobalStatement::TypeDef(
+        TypeDef::new(tamago::Type::new(base).build(), name.to_string()).build(),
+    )
+}
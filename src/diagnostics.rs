@@ -0,0 +1,203 @@
+//! Shows warnings and errors along with the source they point at.
+//!
+//! The parser, resolver, and type checker report each problem as a `Message`, a span and a text,
+//! and add context to it with notes and help, which are messages with a prefix that follow it.
+//! A note points at somewhere else in the source, like where a name was first declared, while
+//! help suggests how to fix the problem and is shown under it. Rendered, an error looks like
+//!
+//! ```text
+//! main.clla(4:5) Error: Cannot return a value from a @statemachine function
+//!  4 |     return 2;
+//!    |     ^~~~~~
+//!    = help: Use 'yield' to give the caller a value, and 'return;' to finish
+//! ```
+//!
+//! Lines and columns start at 1, and columns count characters rather than bytes.
+
+#[cfg(feature = "color")]
+use colored::Colorize;
+
+use crate::Message;
+use crate::parser::Span;
+
+/// The prefix of messages that add context to the message before them instead of standing on
+/// their own
+pub const NOTE_PREFIX: &str = "note: ";
+
+/// The prefix of messages that suggest how to fix the problem reported before them
+pub const HELP_PREFIX: &str = "help: ";
+
+/// Returns a note pointing at `span` for the message before it
+pub fn note(span: Span, msg: impl AsRef<str>) -> Message {
+    (span, format!("{NOTE_PREFIX}{}", msg.as_ref()))
+}
+
+/// Returns help for the message before it, which is shown under it rather than at `span`
+pub fn help(span: Span, msg: impl AsRef<str>) -> Message {
+    (span, format!("{HELP_PREFIX}{}", msg.as_ref()))
+}
+
+/// Whether `msg` adds to the message before it rather than standing on its own
+pub fn is_attached(msg: &str) -> bool {
+    msg.starts_with(NOTE_PREFIX) || msg.starts_with(HELP_PREFIX)
+}
+
+/// How severe a message is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+impl Level {
+    /// The level of `msg` reported as `level`, which its prefix turns into a note or help, and
+    /// the text without the prefix
+    pub fn of(level: Level, msg: &str) -> (Level, &str) {
+        if let Some(note) = msg.strip_prefix(NOTE_PREFIX) {
+            (Level::Note, note)
+        } else if let Some(help) = msg.strip_prefix(HELP_PREFIX) {
+            (Level::Help, help)
+        } else {
+            (level, msg)
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Level::Error => "Error",
+            Level::Warning => "Warning",
+            Level::Note => "Note",
+            Level::Help => "help",
+        }
+    }
+
+    #[cfg(feature = "color")]
+    fn paint(self, s: &str) -> String {
+        match self {
+            Level::Error => s.red().to_string(),
+            Level::Warning => s.yellow().to_string(),
+            Level::Note | Level::Help => s.cyan().to_string(),
+        }
+    }
+
+    #[cfg(not(feature = "color"))]
+    fn paint(self, s: &str) -> String {
+        s.to_string()
+    }
+}
+
+/// A position in the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Returns the location of the byte `offset` in `source`
+pub fn locate(source: &str, offset: usize) -> Location {
+    let before = &source[..offset.min(source.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Location {
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+    }
+}
+
+/// Renders the message `msg` of `source`, read from `path`, reported as `level`
+///
+/// Notes and help are recognized by their prefix. Each line of the result ends with a newline.
+pub fn render(source: &str, path: &str, level: Level, (span, msg): &Message) -> String {
+    let (level, msg) = Level::of(level, msg);
+    if level == Level::Help {
+        return format!("   = {}: {msg}\n", level.paint(level.label()));
+    }
+
+    let Location { line, column } = locate(source, span.start);
+    let mut res = format!(
+        "{path}({line}:{column}) {}: {msg}\n",
+        level.paint(level.label())
+    );
+
+    // The underline stops at the end of the first line of the span
+    let start = span.start.min(source.len());
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |i| start + i);
+    let text = source[line_start..line_end].trim_end_matches('\r');
+    let end = span.end.clamp(start, line_start + text.len());
+
+    // Tabs are kept so that the underline lines up however wide they are shown
+    let indent: String = source[line_start..start]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let width = source[start..end].chars().count().max(1);
+    let underline = format!("^{}", "~".repeat(width - 1));
+
+    let gutter = " ".repeat(line.to_string().len());
+    res.push_str(&format!(" {line} | {text}\n"));
+    res.push_str(&format!(
+        " {gutter} | {indent}{}\n",
+        level.paint(&underline)
+    ));
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locations() {
+        let source = "let a = 1;\n\tlet bé = a;";
+        assert_eq!(locate(source, 0), Location { line: 1, column: 1 });
+        assert_eq!(locate(source, 4), Location { line: 1, column: 5 });
+        assert_eq!(locate(source, 12), Location { line: 2, column: 2 });
+        assert_eq!(
+            locate(source, 22),
+            Location {
+                line: 2,
+                column: 11
+            }
+        );
+        assert_eq!(
+            locate(source, source.len()),
+            Location {
+                line: 2,
+                column: 13
+            }
+        );
+    }
+
+    #[test]
+    fn rendering() {
+        #[cfg(feature = "color")]
+        colored::control::set_override(false);
+
+        let source = "func main(): void {\n\tlet y = xs + 1;\n}";
+        let error = (29..31, "'xs' is not declared".to_string());
+        assert_eq!(
+            render(source, "main.clla", Level::Error, &error),
+            "main.clla(2:10) Error: 'xs' is not declared\n \
+             2 | \tlet y = xs + 1;\n   | \t        ^~\n"
+        );
+
+        // A span over several lines is underlined up to the end of the first one
+        let warning = note(13..40, "Declared here");
+        assert_eq!(
+            render(source, "main.clla", Level::Warning, &warning),
+            "main.clla(1:14) Note: Declared here\n 1 | func main(): void {\n   \
+             |              ^~~~~~\n"
+        );
+
+        let suggestion = help(0..0, "Use 'or' instead");
+        assert!(is_attached(&suggestion.1));
+        assert_eq!(
+            render(source, "main.clla", Level::Error, &suggestion),
+            "   = help: Use 'or' instead\n"
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! Generates the header of a library (`--crate-type lib`), which declares what the C of the
+//! library defines for the C that uses it
+//!
+//! The header is made from the generated C, so it has the same includes and types, along with a
+//! prototype for each function and an `extern` declaration for each global variable that isn't
+//! private, all under an include guard:
+//!
+//! ```text
+//! #ifndef GEOMETRY_H
+//! #define GEOMETRY_H
+//! #include <stdint.h>
+//!
+//! typedef struct Point Point;
+//! struct Point {
+//!   int32_t x;
+//!   int32_t y;
+//! };
+//!
+//! int32_t area(Point p);
+//!
+//! extern int32_t origin_x;
+//! #endif
+//! ```
+//!
+//! Constants are kept as they are, since each one is `static`. Like the ABI description,
+//! `@statemachine` functions are left out since their C interface is generated, and so are
+//! extern functions, which the library only uses.
+
+use std::collections::HashSet;
+
+use tamago::{
+    Block, GlobalStatement, IfDefDirective, Macro, ObjMacro, Scope, ScopeOrBlock, Variable,
+};
+
+use crate::parser::{FuncAttr, GlobalStmt, LocatedGlobalStmt};
+
+/// Returns the header for `c`, the C generated for the lowered `ast`, guarded by the macro `guard`
+pub fn generate(ast: &[LocatedGlobalStmt], c: &Scope, guard: &str) -> Scope {
+    let mut exported = HashSet::new();
+    for stmt in ast {
+        match &stmt.node {
+            GlobalStmt::Function { name, attrs, .. }
+                if !attrs.contains(&FuncAttr::StateMachine) && name != "main" =>
+            {
+                exported.insert(name.as_str());
+            }
+            GlobalStmt::Variable {
+                name,
+                private: false,
+                ..
+            }
+            | GlobalStmt::Constant {
+                name,
+                private: false,
+                ..
+            } => {
+                exported.insert(name.as_str());
+            }
+            _ => {}
+        }
+    }
+
+    let mut stmts = vec![GlobalStatement::Macro(Macro::Obj(
+        ObjMacro::new(guard.to_string()).build(),
+    ))];
+    let mut declared = HashSet::new();
+    for stmt in &c.global_stmts {
+        let stmt = match stmt {
+            GlobalStatement::Include(_)
+            | GlobalStatement::Enum(_)
+            | GlobalStatement::Struct(_)
+            | GlobalStatement::Union(_)
+            | GlobalStatement::TypeDef(_)
+            | GlobalStatement::Macro(_)
+            | GlobalStatement::Raw(_) => stmt.clone(),
+            // Only the first newline of a run is kept, since most of what they separate is not
+            GlobalStatement::NewLine => match stmts.last() {
+                Some(GlobalStatement::NewLine) => continue,
+                _ => GlobalStatement::NewLine,
+            },
+            // Each function is declared once, although the C has a prototype and a definition
+            GlobalStatement::Function(f)
+                if exported.contains(f.name.as_str()) && declared.insert(f.name.as_str()) =>
+            {
+                GlobalStatement::Function(tamago::Function {
+                    body: Block::new().build(),
+                    ..f.clone()
+                })
+            }
+            GlobalStatement::Variable(var) if exported.contains(var.name.as_str()) => {
+                GlobalStatement::Variable(declaration(var))
+            }
+            _ => continue,
+        };
+        stmts.push(stmt);
+    }
+    if let Some(GlobalStatement::NewLine) = stmts.last() {
+        stmts.pop();
+    }
+
+    let guarded = Scope::new().global_statements(stmts).build();
+    Scope::new()
+        .global_statement(GlobalStatement::IfDefDirective(
+            IfDefDirective::new(guard.to_string())
+                .not()
+                .then(ScopeOrBlock::Scope(guarded))
+                .build(),
+        ))
+        .build()
+}
+
+/// Returns the `extern` declaration of a global variable, or the constant itself since it is
+/// `static`
+fn declaration(var: &Variable) -> Variable {
+    if var.is_static {
+        return var.clone();
+    }
+
+    Variable {
+        value: None,
+        is_extern: true,
+        ..var.clone()
+    }
+}
+
+/// Returns the include guard of the header of `file`, e.g. `GEO_SHAPES_H` for `geo-shapes.clla`
+pub fn guard(file: &str) -> String {
+    let stem = std::path::Path::new(file)
+        .file_stem()
+        .map_or("library".into(), |stem| stem.to_string_lossy());
+    let mut guard: String = stem
+        .chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect();
+    if guard.starts_with(|c: char| c.is_ascii_digit()) {
+        guard.insert(0, '_');
+    }
+    guard + "_H"
+}
@@ -1,17 +1,22 @@
-use logos::{skip, Logos};
-use snailquote::{unescape, UnescapeError};
+use logos::{skip, Lexer, Logos};
 
 use std::num::{ParseFloatError, ParseIntError};
+use std::ops::Range;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct LexError {
     pub msg: String,
+
+    /// The part of the token the error is about, relative to the start of the token, if it isn't
+    /// the whole token (e.g. an invalid escape sequence in a string literal)
+    pub span: Option<Range<usize>>,
 }
 
 impl From<ParseIntError> for LexError {
     fn from(value: ParseIntError) -> Self {
         Self {
             msg: value.to_string(),
+            span: None,
         }
     }
 }
@@ -20,41 +25,243 @@ impl From<ParseFloatError> for LexError {
     fn from(value: ParseFloatError) -> Self {
         Self {
             msg: value.to_string(),
+            span: None,
         }
     }
 }
 
-impl From<UnescapeError> for LexError {
-    fn from(value: UnescapeError) -> Self {
+/// The error logos produces for input that matches no token, which the parser replaces with
+/// `LexError::unexpected` since only it knows the offending character
+impl Default for LexError {
+    fn default() -> Self {
         Self {
-            msg: value.to_string(),
+            msg: "".to_string(),
+            span: None,
         }
     }
 }
 
-impl Default for LexError {
-    fn default() -> Self {
+impl LexError {
+    fn new(msg: impl Into<String>) -> Self {
         Self {
-            msg: "".to_string(),
+            msg: msg.into(),
+            span: None,
+        }
+    }
+
+    /// Returns the error for `slice`, the input that matches no token, naming the character since
+    /// it may well be invisible or look like another one
+    pub fn unexpected(slice: &str) -> Self {
+        let Some(c) = slice.chars().next() else {
+            return Self::new("Unexpected end of file");
+        };
+
+        let shown = if c.is_whitespace() || c.is_control() || matches!(c, '\u{200B}' | '\u{FEFF}')
+        {
+            c.escape_default().to_string()
+        } else {
+            c.to_string()
+        };
+        let code = format!("U+{:04X}", c as u32);
+        let described = match char_name(c) {
+            Some(name) => format!("'{shown}' ({code} {name})"),
+            None => format!("'{shown}' ({code})"),
+        };
+
+        let hint = match c {
+            '!' => ", use 'not' for negation",
+            '\u{201C}' | '\u{201D}' => ", use '\"' for string literals",
+            _ => "",
+        };
+
+        Self::new(format!("Unexpected character {described}{hint}"))
+    }
+}
+
+/// The Unicode names of the characters that are easy to type by accident but aren't part of any
+/// token
+fn char_name(c: char) -> Option<&'static str> {
+    Some(match c {
+        '!' => "EXCLAMATION MARK",
+        '#' => "NUMBER SIGN",
+        '$' => "DOLLAR SIGN",
+        '?' => "QUESTION MARK",
+        '\\' => "REVERSE SOLIDUS",
+        '`' => "GRAVE ACCENT",
+        '~' => "TILDE",
+        '\r' => "CARRIAGE RETURN",
+        '\u{0B}' => "LINE TABULATION",
+        '\u{A0}' => "NO-BREAK SPACE",
+        '\u{200B}' => "ZERO WIDTH SPACE",
+        '\u{2013}' => "EN DASH",
+        '\u{2014}' => "EM DASH",
+        '\u{2018}' => "LEFT SINGLE QUOTATION MARK",
+        '\u{2019}' => "RIGHT SINGLE QUOTATION MARK",
+        '\u{201C}' => "LEFT DOUBLE QUOTATION MARK",
+        '\u{201D}' => "RIGHT DOUBLE QUOTATION MARK",
+        '\u{FEFF}' => "ZERO WIDTH NO-BREAK SPACE",
+        _ => return None,
+    })
+}
+
+/// Parses a string literal, which has to end on the line it starts on
+fn parse_str(lex: &mut Lexer<Token>) -> Result<String, LexError> {
+    let slice = lex.slice();
+
+    if slice.len() < 2 || !slice.ends_with('"') {
+        return Err(LexError::new(
+            "Unterminated string literal, expected a closing '\"' before the end of the line",
+        ));
+    }
+
+    unescape(slice, "string")
+}
+
+/// Parses a character literal, which is a single byte like in C
+fn parse_char(lex: &mut Lexer<Token>) -> Result<u8, LexError> {
+    let slice = lex.slice();
+
+    if slice.len() < 2 || !slice.ends_with('\'') {
+        return Err(LexError::new(
+            "Unterminated character literal, expected a closing ''' before the end of the line",
+        ));
+    }
+
+    let unescaped = unescape(slice, "character")?;
+    let mut chars = unescaped.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii() => Ok(c as u8),
+        (Some(c), None) => Err(LexError::new(format!(
+            "'{c}' is not an ASCII character, which a character literal must be since it is a \
+             single byte, use a string literal instead"
+        ))),
+        _ => Err(LexError::new(
+            "A character literal must contain exactly one character",
+        )),
+    }
+}
+
+/// Replaces the escape sequences of a string or character (`kind`) literal, given with its
+/// quotes, with the characters they stand for
+fn unescape(slice: &str, kind: &str) -> Result<String, LexError> {
+    let body = &slice[1..slice.len() - 1];
+    let mut res = String::with_capacity(body.len());
+    let mut chars = body.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
         }
+
+        let escaped = match chars.next().map(|(_, c)| c) {
+            Some('n') => Ok('\n'),
+            Some('t') => Ok('\t'),
+            Some('r') => Ok('\r'),
+            Some('0') => Ok('\0'),
+            Some(c @ ('\\' | '"' | '\'')) => Ok(c),
+            Some('x') => {
+                let mut digits = String::new();
+                while digits.len() < 2
+                    && let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_hexdigit())
+                {
+                    digits.push(c);
+                }
+
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) if digits.len() == 2 && byte.is_ascii() => Ok(byte as char),
+                    Ok(_) if digits.len() == 2 => {
+                        Err("'\\x' escapes must be ASCII, i.e. at most '\\x7f'".to_string())
+                    }
+                    _ => Err("expected two hex digits like '\\x7f'".to_string()),
+                }
+            }
+            Some('u') => {
+                let braced = chars.next_if(|(_, c)| *c == '{').is_some();
+                let mut digits = String::new();
+                while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_hexdigit()) {
+                    digits.push(c);
+                }
+                let closed = chars.next_if(|(_, c)| *c == '}').is_some();
+
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .filter(|_| braced && closed)
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| "expected a Unicode escape like '\\u{1F600}'".to_string())
+            }
+            _ => Err("expected one of '\\n', '\\t', '\\r', '\\0', '\\\\', '\\\"', '\\'', \
+                      '\\xNN', or '\\u{...}'"
+                .to_string()),
+        };
+
+        match escaped {
+            Ok(c) => res.push(c),
+            Err(reason) => {
+                // The offsets are into the token, which starts with the opening quote
+                let end = chars.peek().map_or(body.len(), |(i, _)| *i);
+                return Err(LexError {
+                    msg: format!(
+                        "Invalid escape sequence '{}' in {kind} literal, {reason}",
+                        &body[start..end]
+                    ),
+                    span: Some(start + 1..end + 1),
+                });
+            }
+        }
+    }
+
+    Ok(res)
+}
+
+/// Parses a double literal
+fn parse_double(lex: &mut Lexer<Token>) -> Result<f64, LexError> {
+    let slice = lex.slice();
+
+    slice
+        .parse::<f64>()
+        .map_err(|_| LexError::new(format!("Invalid numeric literal '{slice}'")))
+}
+
+/// Parses an integer literal, which can be anywhere from `i64::MIN` to `u64::MAX` so that
+/// unsigned 64-bit values can be written directly
+fn parse_int(lex: &mut Lexer<Token>) -> Result<i128, LexError> {
+    let slice = lex.slice();
+
+    match slice.parse::<i128>() {
+        Ok(n) if (i64::MIN.into()..=u64::MAX.into()).contains(&n) => Ok(n),
+        Err(_) if !slice.bytes().all(|b| b.is_ascii_digit() || b == b'-') => Err(LexError::new(
+            format!("Invalid numeric literal '{slice}'"),
+        )),
+        _ => Err(LexError::new(format!(
+            "Integer literal '{slice}' is out of range, it must be between {} and {}",
+            i64::MIN,
+            u64::MAX
+        ))),
     }
 }
 
 #[derive(Logos, Clone, Debug, PartialEq)]
 #[logos(error = LexError)]
 pub enum Token {
-    #[regex(r"[A-Za-z_][A-Za-z1-9_]*", |lex| lex.slice().to_string(), priority = 1)]
+    #[regex(r"[A-Za-z_][A-Za-z0-9_]*", |lex| lex.slice().to_string(), priority = 1)]
     Ident(String),
 
-    // TODO: Char!
-    /// Regex inspired by <https://stackoverflow.com/questions/32155133/regex-to-match-a-json-string>
-    #[regex(r#""([^"\\]|\\["\\bnfrt]|u[a-fA-F0-9]{4})*""#, |lex| unescape(lex.slice()))]
+    /// Also matches unterminated strings and invalid escapes, so that they are reported as a
+    /// whole instead of as stray characters
+    #[regex(r#""([^"\\\n]|\\[^\n])*"?"#, parse_str)]
     Str(String),
 
-    #[regex(r"-?(?:0|[1-9]\d*)(?:[eE]?\d+)?", |lex| lex.slice().parse::<i64>())]
-    Int(i64),
+    /// Matches unterminated literals and ones that are too long like `Str` does
+    #[regex(r"'([^'\\\n]|\\[^\n])*'?", parse_char)]
+    Char(u8),
 
-    #[regex(r"-?(?:0|[1-9]\d*)\.\d+(?:[eE][+-]?\d+)?", |lex| lex.slice().parse::<f64>())]
+    /// Also matches letters right after the digits, so that e.g. `12ab` is reported as an
+    /// invalid literal instead of as a number followed by an identifier
+    #[regex(r"-?\d[0-9A-Za-z_]*", parse_int)]
+    Int(i128),
+
+    #[regex(r"-?\d+\.\d+(?:[eE][+-]?\d+)?[0-9A-Za-z_]*", parse_double)]
     Double(f64),
 
     #[regex(r"[ \n\t\f]+", skip)]
@@ -81,9 +288,33 @@ pub enum Token {
     #[token("&")]
     Ampersand,
 
+    #[token("&=")]
+    AmpersandEq,
+
     #[token("^")]
     Caret,
 
+    #[token("^=")]
+    CaretEq,
+
+    #[token("|")]
+    Pipe,
+
+    #[token("|=")]
+    PipeEq,
+
+    #[token("<<")]
+    LShift,
+
+    #[token("<<=")]
+    LShiftEq,
+
+    #[token(">>")]
+    RShift,
+
+    #[token(">>=")]
+    RShiftEq,
+
     #[token(".")]
     Dot,
 
@@ -156,10 +387,16 @@ pub enum Token {
     #[token("::")]
     DColon,
 
+    #[token("@")]
+    At,
+
     // Keywords
     #[token("import")]
     Import,
 
+    #[token("from")]
+    From,
+
     #[token("let")]
     Let,
 
@@ -169,6 +406,9 @@ pub enum Token {
     #[token("func")]
     Func,
 
+    #[token("extern")]
+    Extern,
+
     #[token("if")]
     If,
 
@@ -199,6 +439,15 @@ pub enum Token {
     #[token("do")]
     Do,
 
+    #[token("switch")]
+    Switch,
+
+    #[token("case")]
+    Case,
+
+    #[token("default")]
+    Default,
+
     #[token("in")]
     In,
 
@@ -208,6 +457,9 @@ pub enum Token {
     #[token("defer")]
     Defer,
 
+    #[token("try")]
+    Try,
+
     #[token("make")]
     Make,
 
@@ -220,6 +472,12 @@ pub enum Token {
     #[token("free")]
     Free,
 
+    #[token("sizeof")]
+    Sizeof,
+
+    #[token("cast")]
+    Cast,
+
     #[token("enum")]
     Enum,
 
@@ -238,6 +496,9 @@ pub enum Token {
     #[token("continue")]
     Continue,
 
+    #[token("yield")]
+    Yield,
+
     #[token("void")]
     TVoid,
 
@@ -288,6 +549,7 @@ impl std::fmt::Display for Token {
         match self {
             Ident(ident) => write!(f, "identifier '{}'", ident),
             Str(string) => write!(f, "string literal '{}'", string),
+            Char(c) => write!(f, "character literal '{}'", c.escape_ascii()),
             Int(integer) => write!(f, "integer literal '{}'", integer),
             Double(double) => write!(f, "double literal '{}'", double),
             LeftParen => write!(f, "'('"),
@@ -297,6 +559,13 @@ impl std::fmt::Display for Token {
             LeftBrak => write!(f, "'['"),
             RightBrak => write!(f, "']'"),
             Caret => write!(f, "'^'"),
+            CaretEq => write!(f, "'^='"),
+            Pipe => write!(f, "'|'"),
+            PipeEq => write!(f, "'|='"),
+            LShift => write!(f, "'<<'"),
+            LShiftEq => write!(f, "'<<='"),
+            RShift => write!(f, "'>>'"),
+            RShiftEq => write!(f, "'>>='"),
             Dot => write!(f, "'.'"),
             DDot => write!(f, "'..'"),
             Plus => write!(f, "'+'"),
@@ -309,7 +578,7 @@ impl std::fmt::Display for Token {
             DivEq => write!(f, "'/='"),
             Mod => write!(f, "'%'"),
             ModEq => write!(f, "'%='"),
-            Comma => write!(f, "'"),
+            Comma => write!(f, "','"),
             RightArrow => write!(f, "'->'"),
             GT => write!(f, "'>'"),
             GE => write!(f, "'>='"),
@@ -318,17 +587,22 @@ impl std::fmt::Display for Token {
             NotEq => write!(f, "'!='"),
             Eq => write!(f, "'='"),
             DEq => write!(f, "'=='"),
-            Ampersand => writeln!(f, "'&'"),
+            Ampersand => write!(f, "'&'"),
+            AmpersandEq => write!(f, "'&='"),
             SemiColon => write!(f, "';'"),
             Colon => write!(f, "':'"),
             Not => write!(f, "'not'"),
             DColon => write!(f, "'::'"),
+            At => write!(f, "'@'"),
             Import => write!(f, "'import'"),
+            From => write!(f, "'from'"),
             Let => write!(f, "'let'"),
             Const => write!(f, "'const'"),
             Func => write!(f, "'func'"),
+            Extern => write!(f, "'extern'"),
             Break => write!(f, "'break'"),
             Continue => write!(f, "'continue'"),
+            Yield => write!(f, "'yield'"),
             If => write!(f, "'if'"),
             And => write!(f, "'and'"),
             Or => write!(f, "'or'"),
@@ -338,13 +612,19 @@ impl std::fmt::Display for Token {
             For => write!(f, "'for'"),
             While => write!(f, "'while'"),
             Do => write!(f, "'do'"),
+            Switch => write!(f, "'switch'"),
+            Case => write!(f, "'case'"),
+            Default => write!(f, "'default'"),
             In => write!(f, "'in'"),
             Return => write!(f, "'return'"),
             Defer => write!(f, "'defer'"),
+            Try => write!(f, "'try'"),
             Make => write!(f, "'make'"),
             Destroy => write!(f, "'destroy'"),
             New => write!(f, "'new'"),
             Free => write!(f, "'free'"),
+            Sizeof => write!(f, "'sizeof'"),
+            Cast => write!(f, "'cast'"),
             Enum => write!(f, "'enum'"),
             Struct => write!(f, "'struct'"),
             Union => write!(f, "'union'"),
@@ -368,3 +648,141 @@ impl std::fmt::Display for Token {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn int_literal_range() {
+        let tokens = |s| Token::lexer(s).collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens("-9223372036854775808 18446744073709551615"),
+            vec![
+                Ok(Token::Int(i64::MIN.into())),
+                Ok(Token::Int(u64::MAX.into()))
+            ]
+        );
+
+        let mut lexer = Token::lexer("x = 18446744073709551616;");
+        assert_eq!(lexer.next(), Some(Ok(Token::Ident("x".to_string()))));
+        assert_eq!(lexer.next(), Some(Ok(Token::Eq)));
+        assert_eq!(
+            lexer.next(),
+            Some(Err(LexError {
+                msg: "Integer literal '18446744073709551616' is out of range, it must be between \
+                      -9223372036854775808 and 18446744073709551615"
+                    .to_string(),
+                span: None,
+            }))
+        );
+        assert_eq!(lexer.span(), 4..24);
+    }
+
+    #[test]
+    fn lex_errors() {
+        let error = |s| {
+            let mut lexer = Token::lexer(s);
+            let err = lexer.find_map(|t| t.err()).unwrap();
+            let span = match err.span {
+                Some(part) => lexer.span().start + part.start..lexer.span().start + part.end,
+                None => lexer.span(),
+            };
+            (span, err.msg)
+        };
+
+        assert_eq!(
+            error("x = \"abc;\ny"),
+            (
+                4..9,
+                "Unterminated string literal, expected a closing '\"' before the end of the line"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            error(r#"x = "a\qb""#),
+            (
+                6..8,
+                "Invalid escape sequence '\\q' in string literal, expected one of '\\n', '\\t', \
+                 '\\r', '\\0', '\\\\', '\\\"', '\\'', '\\xNN', or '\\u{...}'"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            error(r#"x = "\x4""#),
+            (
+                5..8,
+                "Invalid escape sequence '\\x4' in string literal, expected two hex digits like \
+                 '\\x7f'"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            error(r"x = '\xff'"),
+            (
+                5..9,
+                "Invalid escape sequence '\\xff' in character literal, '\\x' escapes must be \
+                 ASCII, i.e. at most '\\x7f'"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            error("x = 'ab'"),
+            (
+                4..8,
+                "A character literal must contain exactly one character".to_string()
+            )
+        );
+        assert_eq!(
+            error("x = 'a"),
+            (
+                4..6,
+                "Unterminated character literal, expected a closing ''' before the end of the line"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            error("x = 12ab + 1.5f"),
+            (4..8, "Invalid numeric literal '12ab'".to_string())
+        );
+        assert_eq!(
+            error("x = 1.5f"),
+            (4..8, "Invalid numeric literal '1.5f'".to_string())
+        );
+
+        // Input matching no token is described by the parser, which knows the character
+        let mut lexer = Token::lexer("x \u{201C}");
+        lexer.next();
+        assert_eq!(lexer.next(), Some(Err(LexError::default())));
+        assert_eq!(lexer.span(), 2..5);
+        assert_eq!(
+            LexError::unexpected(lexer.slice()).msg,
+            "Unexpected character '\u{201C}' (U+201C LEFT DOUBLE QUOTATION MARK), use '\"' for \
+             string literals"
+        );
+        assert_eq!(
+            LexError::unexpected("#").msg,
+            "Unexpected character '#' (U+0023 NUMBER SIGN)"
+        );
+        assert_eq!(
+            LexError::unexpected("\r").msg,
+            "Unexpected character '\\r' (U+000D CARRIAGE RETURN)"
+        );
+
+        let tokens = Token::lexer(r#"x0 "a\n\x41\0" '\t' '\'' 1.5e3 0..<"#).collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Ok(Token::Ident("x0".to_string())),
+                Ok(Token::Str("a\nA\0".to_string())),
+                Ok(Token::Char(b'\t')),
+                Ok(Token::Char(b'\'')),
+                Ok(Token::Double(1500.0)),
+                Ok(Token::Int(0)),
+                Ok(Token::DDot),
+                Ok(Token::LT),
+            ]
+        );
+    }
+}
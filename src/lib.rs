@@ -0,0 +1,2822 @@
+//! The castella compiler as a library.
+//!
+//! The command line driver is a thin wrapper around this crate, so other Rust programs (a web
+//! playground, build scripts, ...) can run the same pipeline in-process:
+//!
+//! ```no_run
+//! use castella_core::{CompileOptions, compile_to_c_string};
+//!
+//! let c = compile_to_c_string("func main(): void {}", &CompileOptions::default());
+//! ```
+//!
+//! Malformed input is reported as errors, never by panicking. The `unreachable!()`s and
+//! `unwrap()`s left hold by construction: in the parser they follow an `expect!` that has already
+//! returned an error for any other token, and the phases after type checking only see programs
+//! it accepted. The `fuzz_pipeline` test checks this by compiling thousands of mutations of
+//! programs that compile, about a quarter of which get through type checking to code generation.
+
+pub mod abi;
+pub mod cc;
+pub mod codegen;
+pub mod diagnostics;
+pub mod header;
+pub mod lexer;
+pub mod lowering;
+pub mod module;
+pub mod output;
+pub mod parser;
+pub mod printer;
+pub mod semantic_analyzer;
+pub mod timings;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+use std::collections::HashMap;
+
+use logos::Logos;
+
+use lowering::Sanitizers;
+use lowering::tuples::MultipleReturns;
+use module::{Module, ModuleLoader};
+use semantic_analyzer::naming::NamingConventions;
+use timings::Timings;
+
+/// Could be either a warning or an error
+pub type Message = (parser::Span, String);
+
+/// The warnings and errors reported by a failed compilation
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    pub warnings: Vec<Message>,
+    pub errors: Vec<Message>,
+}
+
+/// What a source file is compiled to (`--crate-type`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CrateType {
+    /// A program, which starts at the entry point (`bin`)
+    #[default]
+    Bin,
+
+    /// A library, which needs no entry point and comes with a header declaring its types,
+    /// functions, and global variables (`lib`)
+    Lib,
+}
+
+impl CrateType {
+    /// Parses the name given to `--crate-type`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bin" => Some(Self::Bin),
+            "lib" => Some(Self::Lib),
+            _ => None,
+        }
+    }
+}
+
+/// Settings for a single compilation
+#[derive(Debug)]
+pub struct CompileOptions {
+    /// The name the source is reported as, e.g. by runtime traps
+    pub file: String,
+
+    /// Lower indexing into dynamic arrays through a bounds-checking helper (`--bounds-checks`)
+    pub bounds_checks: bool,
+
+    /// Check the casts that may lose information at runtime, unless the C is compiled with
+    /// `NDEBUG` (`--checked-casts`)
+    pub checked_casts: bool,
+
+    /// Locates imported modules, which can also be given in memory with `ModuleLoader::source`
+    pub loader: ModuleLoader,
+
+    /// The naming conventions that are warned about (`--no-naming` and `--naming-prefix`)
+    pub naming: NamingConventions,
+
+    /// How functions returning multiple values are generated (`--multiple-returns`)
+    pub multiple_returns: MultipleReturns,
+
+    /// The sanitizers the generated C is compiled with, which runtime traps are reported to
+    /// (`--sanitize`)
+    pub sanitizers: Sanitizers,
+
+    /// Whether the source is a program or a library (`--crate-type`)
+    pub crate_type: CrateType,
+
+    /// The function a program starts at, which is called by the C `main` if it has another name
+    /// (`--entry`)
+    pub entry: String,
+
+    /// Precede what is generated with `#line` directives pointing back at the source, so the C
+    /// compiler reports its problems there and debuggers show the source, which the driver does
+    /// unless `--no-line-directives` is given
+    pub line_directives: bool,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            file: "main.clla".to_string(),
+            bounds_checks: false,
+            checked_casts: false,
+            loader: ModuleLoader::new("."),
+            naming: NamingConventions::default(),
+            multiple_returns: MultipleReturns::default(),
+            sanitizers: Sanitizers::default(),
+            crate_type: CrateType::default(),
+            entry: "main".to_string(),
+            line_directives: false,
+        }
+    }
+}
+
+/// Runs the whole pipeline on `source` and returns the generated C, without writing anything
+/// to the filesystem
+///
+/// Warnings of successful compilations are dropped; use `compile` to get them as well.
+pub fn compile_to_c_string(source: &str, options: &CompileOptions) -> Result<String, Diagnostics> {
+    match compile(source, options, &mut Timings::default()) {
+        (_, Ok(c)) => Ok(c),
+        (warnings, Err(errors)) => Err(Diagnostics { warnings, errors }),
+    }
+}
+
+/// Runs the whole pipeline on `source`, recording how long each phase takes in `timings`
+pub fn compile(
+    source: &str,
+    options: &CompileOptions,
+    timings: &mut Timings,
+) -> (Vec<Message>, Result<String, Vec<Message>>) {
+    let mut warnings: Vec<Message> = vec![];
+    let res = generate(source, options, timings, &mut warnings);
+    (warnings, res.map(|(_, scope)| scope.to_string()))
+}
+
+/// The generated C of a library and the header declaring what it exports
+#[derive(Debug)]
+pub struct Library {
+    pub c: String,
+    pub header: String,
+}
+
+/// Runs the whole pipeline on `source` like `compile`, and also generates the header of the
+/// library, whose include guard is named after `options.file` (`--crate-type lib`)
+pub fn compile_library(
+    source: &str,
+    options: &CompileOptions,
+    timings: &mut Timings,
+) -> (Vec<Message>, Result<Library, Vec<Message>>) {
+    let mut warnings: Vec<Message> = vec![];
+    let res = generate(source, options, timings, &mut warnings).map(|(ast, scope)| {
+        let header = timings.time("header", || {
+            header::generate(&ast, &scope, &header::guard(&options.file))
+        });
+        Library {
+            c: scope.to_string(),
+            header: header.to_string(),
+        }
+    });
+    (warnings, res)
+}
+
+/// Runs the whole pipeline on `source` and returns the lowered AST along with its C
+fn generate(
+    source: &str,
+    options: &CompileOptions,
+    timings: &mut Timings,
+    warnings: &mut Vec<Message>,
+) -> Result<(Vec<parser::LocatedGlobalStmt>, tamago::Scope), Vec<Message>> {
+    let (mut ast, modules) = analyze(source, options, timings, warnings)?;
+
+    timings.time("lower", || lower(&mut ast, modules, options));
+
+    let mut lower_options = lowering::LowerOptions::new(&options.file, source)
+        .bounds_checks(options.bounds_checks)
+        .checked_casts(options.checked_casts)
+        .sanitizers(options.sanitizers)
+        .line_directives(options.line_directives);
+    if options.crate_type == CrateType::Bin && options.entry != "main" {
+        lower_options = lower_options.entry(&options.entry);
+    }
+    let scope = timings.time("codegen", || {
+        codegen::CodeGen::new(&ast).options(lower_options).generate()
+    })?;
+
+    Ok((ast, scope))
+}
+
+/// Runs the front-end and the AST-to-AST lowering passes on `source` and returns the JSON
+/// describing the types and functions of the generated C (`--emit abi-json`)
+pub fn abi_json(
+    source: &str,
+    options: &CompileOptions,
+) -> (Vec<Message>, Result<String, Vec<Message>>) {
+    let mut warnings: Vec<Message> = vec![];
+    let (mut ast, modules) = match analyze(source, options, &mut Timings::default(), &mut warnings)
+    {
+        Ok(res) => res,
+        Err(errs) => return (warnings, Err(errs)),
+    };
+    lower(&mut ast, modules, options);
+
+    (warnings, Ok(abi::describe(&ast)))
+}
+
+/// Runs the AST-to-AST lowering passes on a checked AST, which leaves it for code generation
+fn lower(
+    ast: &mut Vec<parser::LocatedGlobalStmt>,
+    modules: HashMap<String, Module>,
+    options: &CompileOptions,
+) {
+    lowering::link::link_modules(ast, modules);
+    lowering::generics::monomorphize(ast);
+    lowering::shadowing::rename_shadowed(ast);
+    lowering::results::lower_try(ast);
+    lowering::tuples::lower_multiple_returns(ast, options.multiple_returns);
+    lowering::destructure::expand_destructuring(ast);
+    lowering::fold::fold_constants(ast);
+}
+
+/// The points in the pipeline the AST can be dumped at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AstStage {
+    /// Right after parsing, before any checks (`--after parse`)
+    Parse,
+
+    /// After the front-end and the AST-to-AST lowering passes, with constants folded
+    /// (`--after lowering`)
+    Lowering,
+}
+
+/// Returns the AST of `source` at `stage`, pretty-printed for debugging (`--dump-ast`)
+pub fn dump_ast(
+    source: &str,
+    options: &CompileOptions,
+    stage: AstStage,
+) -> Result<String, Diagnostics> {
+    let ast = ast_at(source, options, stage)?;
+    Ok(format!("{ast:#?}"))
+}
+
+/// Returns the AST of `source` at `stage` as an indented tree (`--emit ast`), which is easier to
+/// read than the full dump of `dump_ast`
+pub fn print_ast(
+    source: &str,
+    options: &CompileOptions,
+    stage: AstStage,
+) -> Result<String, Diagnostics> {
+    let ast = ast_at(source, options, stage)?;
+    Ok(printer::print_ast(&ast))
+}
+
+/// Returns the tokens of `source` one per line along with where they start (`--emit tokens`)
+///
+/// Input that is not a token is shown as an error in its place rather than stopping there, so
+/// the rest of the source is still shown.
+pub fn dump_tokens(source: &str) -> String {
+    let mut lexer = lexer::Token::lexer(source);
+    let mut res = String::new();
+    while let Some(token) = lexer.next() {
+        let diagnostics::Location { line, column } =
+            diagnostics::locate(source, lexer.span().start);
+        let token = match token {
+            Ok(token) => token.to_string(),
+            Err(e) if e.msg.is_empty() => {
+                format!("error: {}", lexer::LexError::unexpected(lexer.slice()).msg)
+            }
+            Err(e) => format!("error: {}", e.msg),
+        };
+        res.push_str(&format!("{line}:{column} {token}\n"));
+    }
+    res
+}
+
+fn ast_at(
+    source: &str,
+    options: &CompileOptions,
+    stage: AstStage,
+) -> Result<Vec<parser::LocatedGlobalStmt>, Diagnostics> {
+    Ok(match stage {
+        AstStage::Parse => parser::Parser::new(lexer::Token::lexer(source))
+            .parse()
+            .map_err(|errors| Diagnostics {
+                warnings: vec![],
+                errors,
+            })?,
+        AstStage::Lowering => {
+            let mut warnings = vec![];
+            let (mut ast, modules) =
+                analyze(source, options, &mut Timings::default(), &mut warnings)
+                    .map_err(|errors| Diagnostics { warnings, errors })?;
+            lower(&mut ast, modules, options);
+            ast
+        }
+    })
+}
+
+/// Runs only the front-end (lexing, parsing, loading imports, resolving, and type checking) on
+/// `source`, which is enough to report every diagnostic
+pub fn check(source: &str, options: &CompileOptions) -> (Vec<Message>, Result<(), Vec<Message>>) {
+    let mut warnings: Vec<Message> = vec![];
+    let res = analyze(source, options, &mut Timings::default(), &mut warnings);
+    (warnings, res.map(|_| ()))
+}
+
+/// What an editor shows when the cursor rests on an expression (`hover`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hover {
+    /// The innermost expression at the cursor
+    pub span: parser::Span,
+
+    /// The type of the expression, unless it doesn't check
+    pub t: Option<parser::Type>,
+
+    /// Where the name the expression refers to is declared, if it is a name
+    pub declaration: Option<parser::Span>,
+}
+
+/// Returns what to show for the innermost expression at the byte `offset` of `source`, or `None`
+/// if there is none or `source` doesn't parse
+///
+/// The program is resolved and type checked as it is, so what checks can be hovered over even
+/// while something else doesn't.
+pub fn hover(source: &str, options: &CompileOptions, offset: usize) -> Option<Hover> {
+    use semantic_analyzer::resolver::Resolver;
+    use semantic_analyzer::type_checker::TypeChecker;
+
+    let mut ast = parser::Parser::new(lexer::Token::lexer(source))
+        .parse()
+        .ok()?;
+    let (modules, _) = options.loader.load_imports(&ast);
+    semantic_analyzer::const_exprs::evaluate(&mut ast);
+
+    let (_, _, declarations) = Resolver::new(&ast)
+        .modules(&modules)
+        .resolve_with_declarations();
+    let (_, _, types) = TypeChecker::new(&ast).modules(&modules).check_with_types();
+
+    let span = types
+        .keys()
+        .chain(declarations.keys())
+        .filter(|span| span.contains(&offset))
+        .min_by_key(|span| (span.len(), span.start))?
+        .clone();
+    Some(Hover {
+        t: types.get(&span).cloned(),
+        declaration: declarations.get(&span).cloned(),
+        span,
+    })
+}
+
+/// A checked AST along with the modules it imports
+type Analyzed = (Vec<parser::LocatedGlobalStmt>, HashMap<String, Module>);
+
+/// Runs the front-end, adding its warnings to `warnings`, and returns the checked AST along with
+/// the imported modules
+fn analyze(
+    source: &str,
+    options: &CompileOptions,
+    timings: &mut Timings,
+    warnings: &mut Vec<Message>,
+) -> Result<Analyzed, Vec<Message>> {
+    time_lexing(source, timings);
+
+    let lexer = lexer::Token::lexer(source);
+
+    let mut ast = timings.time("parse", || parser::Parser::new(lexer).parse())?;
+    timings.count(ast.len(), "items");
+
+    let (mut modules, errs) = timings.time("load", || options.loader.load_imports(&ast));
+    timings.count(modules.len(), "modules");
+    if !errs.is_empty() {
+        return Err(errs);
+    }
+
+    let errs = timings.time("modules", || check_modules(&ast, &modules));
+    if !errs.is_empty() {
+        return Err(errs);
+    }
+
+    let resolved = timings.time("resolve", || {
+        semantic_analyzer::resolver::Resolver::new(&ast)
+            .modules(&modules)
+            .resolve()
+    });
+    timings.count(ast.len(), "items");
+    match resolved {
+        (mut w, Ok(())) => warnings.append(&mut w),
+        (mut w, Err(errs)) => {
+            warnings.append(&mut w);
+            return Err(errs);
+        }
+    }
+
+    let mut lints = timings.time("lint", || {
+        let mut lints = semantic_analyzer::unused::check(&ast, options.crate_type, &options.entry);
+        lints.append(&mut options.naming.check(&ast));
+        lints
+    });
+    warnings.append(&mut lints);
+
+    let errs = semantic_analyzer::const_exprs::evaluate(&mut ast);
+    if !errs.is_empty() {
+        return Err(errs);
+    }
+
+    // The instances of functions with type parameters are only checked once the calls to them
+    // have been, so the program is checked again for as long as there are new ones, and only the
+    // warnings of the last check are kept since the others repeat them
+    loop {
+        let checked = timings.time("typecheck", || {
+            semantic_analyzer::type_checker::TypeChecker::new(&ast)
+                .modules(&modules)
+                .check()
+        });
+        timings.count(ast.len(), "items");
+        let mut w = match checked {
+            (w, Ok(())) => w,
+            (mut w, Err(errs)) => {
+                warnings.append(&mut w);
+                return Err(errs);
+            }
+        };
+
+        let instantiated = timings.time("instantiate", || {
+            lowering::generics::instantiate_types(&mut ast, &mut modules)
+        });
+        match instantiated {
+            Ok(true) => {}
+            Ok(false) => {
+                warnings.append(&mut w);
+                break;
+            }
+            Err(err) => {
+                warnings.append(&mut w);
+                return Err(vec![err]);
+            }
+        }
+
+        let errs = timings.time("modules", || check_modules(&ast, &modules));
+        if !errs.is_empty() {
+            return Err(errs);
+        }
+    }
+
+    // Checked last, since a missing entry point matters less than anything else being wrong
+    if options.crate_type == CrateType::Bin {
+        let errs = check_entry(&ast, &options.entry);
+        if !errs.is_empty() {
+            return Err(errs);
+        }
+    }
+
+    Ok((ast, modules))
+}
+
+/// Resolves and type checks the modules imported by `ast`, and the modules they import, and
+/// returns their errors at the imports that brought them in
+///
+/// The warnings of modules are dropped, since e.g. a library doesn't use most of what it
+/// declares.
+fn check_modules(
+    ast: &[parser::LocatedGlobalStmt],
+    modules: &HashMap<String, Module>,
+) -> Vec<Message> {
+    let mut errors = vec![];
+
+    for stmt in ast {
+        let parser::GlobalStmt::Import { name, .. } = &stmt.node else {
+            continue;
+        };
+        let Some(module) = modules.get(name) else {
+            continue;
+        };
+
+        let mut errs = check_modules(&module.ast, &module.modules);
+        if errs.is_empty() {
+            let (_, res) = semantic_analyzer::resolver::Resolver::new(&module.ast)
+                .modules(&module.modules)
+                .resolve();
+            errs = res.err().unwrap_or_default();
+        }
+        if errs.is_empty() {
+            let (_, res) = semantic_analyzer::type_checker::TypeChecker::new(&module.ast)
+                .modules(&module.modules)
+                .check();
+            errs = res.err().unwrap_or_default();
+        }
+
+        errors.extend(errs.into_iter().map(|(_, msg)| {
+            let msg = if diagnostics::is_attached(&msg) {
+                msg
+            } else {
+                format!("In module '{}': {msg}", module.path)
+            };
+            (stmt.span.clone(), msg)
+        }));
+    }
+
+    errors
+}
+
+/// Returns the errors of a program whose entry point `entry` is missing or can't be called by the
+/// C `main`
+fn check_entry(ast: &[parser::LocatedGlobalStmt], entry: &str) -> Vec<Message> {
+    use parser::GlobalStmt;
+
+    let mut errors = vec![];
+    let mut found = false;
+    for stmt in ast {
+        let GlobalStmt::Function {
+            name,
+            type_params,
+            size_params,
+            params,
+            ret,
+            attrs,
+            ..
+        } = &stmt.node
+        else {
+            continue;
+        };
+
+        if name == "main" && entry != "main" {
+            errors.push((
+                stmt.span.clone(),
+                format!("'main' is generated to call the entry point '{entry}'"),
+            ));
+            errors.push(diagnostics::help(
+                stmt.span.clone(),
+                "Rename the function, or build with '--crate-type lib'",
+            ));
+        }
+        if name != entry {
+            continue;
+        }
+
+        found = true;
+        let problem = if !type_params.is_empty() || !size_params.is_empty() {
+            Some("cannot have type or size parameters")
+        } else if attrs.contains(&parser::FuncAttr::StateMachine) {
+            Some("cannot be a @statemachine function")
+        } else if !params.is_empty() {
+            Some("cannot have parameters")
+        } else if !matches!(ret.node, parser::Type::Void | parser::Type::Int32) {
+            Some("must return 'void' or 'i32'")
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            errors.push((
+                stmt.span.clone(),
+                format!("The entry point '{entry}' {problem}"),
+            ));
+        }
+    }
+
+    if !found {
+        errors.push((
+            0..0,
+            format!("There is no function '{entry}' to start the program at"),
+        ));
+        errors.push(diagnostics::help(
+            0..0,
+            format!("Define 'func {entry}(): void', or build with '--crate-type lib'"),
+        ));
+    }
+
+    errors
+}
+
+/// Lexes the whole source once just to measure how long lexing takes, since the parser pulls
+/// tokens lazily
+fn time_lexing(source: &str, timings: &mut Timings) {
+    if timings.is_enabled() {
+        let count = timings.time("lex", || lexer::Token::lexer(source).count());
+        timings.count(count, "tokens");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use semantic_analyzer::naming::NamingRule;
+
+    /// Compiles the generated C of the test `name` with the system C compiler, which must not
+    /// report anything
+    fn assert_c_compiles(c: &str, name: &str) {
+        let out =
+            std::env::temp_dir().join(format!("castella-test-{name}-{}.o", std::process::id()));
+        let output = cc::CCompiler::detect()
+            .compile(c, cc::Artifact::Object, &out)
+            .unwrap();
+        let _ = std::fs::remove_file(&out);
+        assert!(
+            output.status.success() && output.stderr.is_empty(),
+            "{}\n{c}",
+            output.stderr
+        );
+    }
+
+    #[test]
+    fn compile_in_memory() {
+        let options = CompileOptions {
+            loader: ModuleLoader::new("/nonexistent").source("util", "func f(): void {}"),
+            ..Default::default()
+        };
+
+        let diagnostics = compile_to_c_string("func main(): void {", &options).unwrap_err();
+        assert_eq!(
+            diagnostics.errors,
+            [
+                (19..19, "Unexpected end of file".to_string()),
+                (18..19, "note: The block opened here is never closed".to_string()),
+            ]
+        );
+
+        let diagnostics =
+            compile_to_c_string("import \"missing\"; func main(): void {}", &options).unwrap_err();
+        assert!(
+            diagnostics.errors[0]
+                .1
+                .starts_with("Cannot find module 'missing'")
+        );
+
+        let diagnostics =
+            compile_to_c_string("import \"util\"; func main(): void { g(); }", &options)
+                .unwrap_err();
+        assert_eq!(diagnostics.errors[0].1, "'g' is not declared");
+    }
+
+    /// A xorshift generator, so that the inputs `fuzz_pipeline` tries are the same on every run
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    /// Compiles mutations of programs covering most of the syntax, which must be reported as
+    /// errors instead of panicking the compiler
+    #[test]
+    fn fuzz_pipeline() {
+        let options = CompileOptions {
+            loader: ModuleLoader::new("/nonexistent").bundled_std(),
+            ..Default::default()
+        };
+        let seeds = [
+            "\
+enum Color { Red = 1; Green; Blue = 4; }
+struct Point { x: i32; y: i32; }
+union Bits { i: i32; d: double; }
+alias Id = u64;
+const LEN = 4;
+let grid: [LEN]i32;
+extern func puts(s: str): i32;
+
+func sum<N: u64>(xs: [N]i32): i32 {
+    let total = 0;
+    let i: u64 = 0;
+    while (i < N) {
+        total += xs[i];
+        i += 1;
+    }
+    return total;
+}
+
+func largest<T>(a: T, b: T): T {
+    if (a > b) {
+        return a;
+    }
+    return b;
+}
+
+func divide(a: i32, b: i32): Result<i32, str> {
+    if (b == 0) {
+        return err(\"division by zero\");
+    }
+    return ok(a / b);
+}
+
+func halves(n: i32): Result<i32, str> {
+    let q = try divide(n, 2);
+    return ok(q);
+}
+
+func divmod(a: i32, b: i32): (i32, i32) {
+    return (a / b, a % b);
+}
+
+func name_of(c: Color): str {
+    switch (c) {
+        case Color->Red: { return \"red\"; }
+        case Color->Green, Color->Blue: { return \"other\"; }
+    }
+}
+
+func main(): i32 {
+    let p = Point { x = 1, y = 2 };
+    let q: ^Point = &p;
+    (^q).y += 3;
+    let b: Bits;
+    b.i = 7;
+    let id: u64 = cast(u64, b.i);
+    let _first: Id;
+    let xs: [^]i32 = make [^]i32;
+    defer destroy xs;
+    append(&xs, sum([1, 2, 3]));
+    let heap = new Point;
+    defer { free heap; }
+    let (d, m) = divmod(7, 2);
+    let h = halves(8);
+    if (h.is_ok and not (d < m) or id == 7) {
+        puts(name_of(Color->Blue));
+    } else {
+        grid[0] = largest(d, m) << 2 | 1;
+    }
+    let c: u8 = cast(u8, 'c');
+    if (largest(1.5, 2.5) > 3.0) {
+        return cast(i32, sizeof(Point));
+    }
+    return xs[0] + p.y + cast(i32, c);
+}",
+            "\
+import \"std/mem\";
+from std::math import min;
+
+const SIDE: i32 = 3;
+
+func square(n: i32): i32 {
+    return n * n;
+}
+
+const AREA: i32 = square(SIDE);
+
+enum Size { Small = square(2); Large; }
+
+let cells: [square(2)]u8;
+
+@statemachine
+func countdown(n: i32): i32 {
+    while (n > 0) {
+        yield n;
+        n -= 1;
+    }
+}
+
+func fill(xs: ^[4]u8, v: u8): void {
+    let i = 0;
+    while (true) {
+        if (i >= 4) {
+            break;
+        }
+        (^xs)[i] = v;
+        i += 1;
+        continue;
+    }
+}
+
+func pair(a: i32): (i32, bool) {
+    return (a, a > 0);
+}
+
+func forward(a: i32): (i32, bool) {
+    return pair(a - 1);
+}
+
+func main(): void {
+    fill(&cells, cast(u8, '\\n'));
+    let copy: [4]u8 = [0, 0, 0, 0];
+    mem::copy(&copy, &cells, sizeof([4]u8));
+    let (n, positive) = forward(AREA);
+    {
+        let n = n + min(1, 2);
+        if (positive and n != 0) {
+            let s = \"tab\\there \\u{e9}\";
+            let big: u64 = 18446744073709551615;
+            let _neg = -cast(i64, big % 7) ^ 3;
+            let _t = s;
+        }
+    }
+    switch (cast(i32, Size->Large)) {
+        case 5: {}
+        default: { return; }
+    }
+}",
+        ];
+
+        // The mutations start from programs that compile, so that many of them get past parsing
+        for seed in seeds {
+            if let Err(diagnostics) = compile_to_c_string(seed, &options) {
+                panic!("{:?}\n{seed}", diagnostics.errors);
+            }
+        }
+
+        // Each token is kept with its kind, so that it can be swapped for another of the same
+        let tokens_of = |source: &'static str| {
+            lexer::Token::lexer(source)
+                .spanned()
+                .map(|(token, span)| (std::mem::discriminant(&token.unwrap()), &source[span]))
+                .collect::<Vec<_>>()
+        };
+        let vocabulary = tokens_of(
+            "func let const return if else while switch case default break continue { } ( ) [ ] \
+             [^] ^ & ; , : -> :: = += < > and not x n main Point Color 0 -1 1.5 'a' \"s\" \
+             18446744073709551615 i32 u8 <T> @statemachine yield defer cast sizeof ok err try \
+             new free make append",
+        );
+
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        let (mut checked, mut generated) = (0, 0);
+        for i in 0..2000 {
+            let mut tokens = tokens_of(seeds[i % seeds.len()]);
+            for _ in 0..=rng.below(2) {
+                let at = rng.below(tokens.len());
+                let (kind, _) = tokens[at];
+                match rng.below(8) {
+                    // Most mutations swap a token for another of the same kind, like an
+                    // identifier or a number, which keeps the program parsing for the later
+                    // phases to check
+                    0..=3 => {
+                        let same_kind: Vec<_> = tokens
+                            .iter()
+                            .chain(&vocabulary)
+                            .filter(|(other, _)| *other == kind)
+                            .collect();
+                        tokens[at] = *same_kind[rng.below(same_kind.len())];
+                    }
+                    4 if tokens.len() > 1 => {
+                        tokens.remove(at);
+                    }
+                    4 | 5 => tokens.insert(at, tokens[rng.below(tokens.len())]),
+                    6 => tokens.insert(at, vocabulary[rng.below(vocabulary.len())]),
+                    _ => tokens.truncate(at + 1),
+                }
+            }
+
+            let source: Vec<&str> = tokens.iter().map(|(_, text)| *text).collect();
+            let source = source.join(" ");
+            let phases = std::panic::catch_unwind(|| {
+                let mut timings = Timings::default();
+                let _ = compile(&source, &options, &mut timings);
+                timings.phases().collect::<Vec<_>>()
+            });
+            let Ok(phases) = phases else {
+                panic!("The compiler panicked on:\n{source}");
+            };
+            checked += phases.contains(&"typecheck") as usize;
+            generated += phases.contains(&"codegen") as usize;
+        }
+
+        // Otherwise the mutations only test the parser
+        assert!(checked >= 500, "Only {checked} mutations were type checked");
+        assert!(
+            generated >= 400,
+            "Only {generated} mutations were generated"
+        );
+    }
+
+    #[test]
+    fn void_pointers() {
+        let options = CompileOptions {
+            loader: ModuleLoader::new("/nonexistent").bundled_std(),
+            ..Default::default()
+        };
+
+        let source = "import \"std/mem\";\n\
+                      func main(): i32 { let a: i32 = 1; let b: i32 = 42; let xs: [4]i32;\n\
+                      mem::copy(&a, &b, sizeof(i32)); mem::zero(&xs, sizeof([4]i32));\n\
+                      let p: ^void = &a; return a + xs[2]; }";
+        let c = compile_to_c_string(source, &options).unwrap();
+        assert!(c.contains("std_mem_copy(&a, &b, sizeof(int32_t));"));
+        assert!(c.contains("void* p = &a;"));
+        assert_c_compiles(&c, "void_pointers");
+
+        // Only `^void` takes any pointer, like in C
+        let source = "func f(p: ^i32): void {}\n\
+                      func main(): void { let x: u8 = 1; let v: ^void = &x; f(v); }";
+        let errors = check(source, &options).1.unwrap_err();
+        assert_eq!(errors[0].1, "Expected '^i32' but got '^void'");
+    }
+
+    #[test]
+    fn modules() {
+        let options = CompileOptions {
+            loader: ModuleLoader::new("/nonexistent")
+                .source(
+                    "geo/shapes",
+                    "import \"util\";\n\
+                     func area(w: i32, h: i32): i32 { return util::mul(w, h); }",
+                )
+                .source("util", "func mul(x: i32, y: i32): i32 { return x * y; }")
+                .source("bad", "func f(): i32 { return \"x\"; }"),
+            ..Default::default()
+        };
+
+        let source = "import shapes \"geo/shapes\";\nimport \"util\";\n\
+                      func mul(x: i32): i32 { return x; }\n\
+                      func main(): void { let mul = shapes::area(2, 3) + util::mul(1, 2); }";
+        let c = compile_to_c_string(source, &options).unwrap();
+        assert!(c.contains("int32_t util_mul(int32_t x, int32_t y) {"));
+        assert!(c.contains("return util_mul(w, h);"));
+        assert!(c.contains("int32_t mul(int32_t x) {"));
+        assert!(c.contains("int32_t mul = geo_shapes_area(2, 3) + util_mul(1, 2);"));
+        assert_eq!(c.matches("int32_t util_mul(int32_t x, int32_t y) {").count(), 1);
+
+        let diagnostics =
+            compile_to_c_string("import \"bad\";\nfunc main(): void { bad::f(); }", &options)
+                .unwrap_err();
+        assert_eq!(
+            diagnostics.errors,
+            [(
+                0..6,
+                "In module 'bad': Expected i32 as return type but got str".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn selective_imports() {
+        let options = CompileOptions {
+            loader: ModuleLoader::new("/nonexistent")
+                .source(
+                    "geo/shapes",
+                    "struct Point { x: i32; y: i32; }\n\
+                     const ORIGIN: i32 = 0;\n\
+                     func area(w: i32, h: i32): i32 { return w * h; }",
+                )
+                .source("util", "func mul(x: i32, y: i32): i32 { return x * y; }"),
+            ..Default::default()
+        };
+
+        let source = "import u = util;\nfrom geo::shapes import area, Point, ORIGIN;\n\
+                      func main(): void { let p = Point { x = 2, y = ORIGIN }; \
+                      let a = area(p.x, p.y) + u::mul(1, 2); }";
+        let c = compile_to_c_string(source, &options).unwrap();
+        assert!(c.contains("geo_shapes_Point p = {.x=2, .y=0};"));
+        assert!(c.contains("int32_t a = geo_shapes_area(p.x, p.y) + util_mul(1, 2);"));
+
+        let errors = |source: &str| compile_to_c_string(source, &options).unwrap_err().errors;
+
+        // Only the imported names are visible, not the module
+        let errs =
+            errors("from \"geo/shapes\" import area;\nfunc main(): void { shapes::area(1, 2); }");
+        assert_eq!(errs[0].1, "'shapes' is not declared");
+
+        let errs = errors("from geo::shapes import aera;\nfunc main(): void {}");
+        assert_eq!(errs[0].1, "By 'aera' from 'geo/shapes', did you mean 'area'?");
+
+        let errs = errors(
+            "from geo::shapes import area;\nfunc area(): void {}\nfunc main(): void { area(); }",
+        );
+        assert_eq!(errs[0], (35..39, "'area' is already declared".to_string()));
+        assert_eq!(
+            errs[2].1,
+            "note: The previous declaration is 'func(i32, i32): i32', but this one is \
+             'func(): void'"
+        );
+    }
+
+    #[test]
+    fn redeclaration_notes() {
+        let source = "func f(x: i32): void {}\nfunc f(x: str): i32 {}\nfunc main(): void {}";
+        let (_, res) = check(source, &CompileOptions::default());
+        let errors = res.unwrap_err();
+
+        let first = 5..6;
+        assert_eq!(errors[0].1, "'f' is already declared");
+        assert_eq!(
+            errors[1],
+            (
+                first.clone(),
+                "note: 'f' was first declared here".to_string()
+            )
+        );
+        assert_eq!(
+            errors[2],
+            (
+                first,
+                "note: The previous declaration is 'func(i32): void', but this one is \
+                 'func(str): i32'"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn array_params() {
+        let check_fn = |body: &str| {
+            let source = format!("{body}\nfunc main(): void {{}}");
+            check(&source, &CompileOptions::default()).1
+        };
+
+        assert!(check_fn("func f(a: ^[4]i32): void { (^a)[0] = 1; }").is_ok());
+        assert!(check_fn("func f(a: [4]i32): i32 { return a[0]; }").is_ok());
+
+        let errors = check_fn("func f(a: [4]i32): void { a[0] = 1; }").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "'a' is a read-only array parameter, declare it as '^[4]i32' to modify it"
+        );
+
+        let errors = check_fn("func f(a: [4]i32): void { &a; }").unwrap_err();
+        assert!(errors[0].1.starts_with("Cannot take the address of the array parameter 'a'"));
+
+        // Pointers into the elements would let the caller's array be modified as well
+        let part = "Cannot take the address of part of the read-only array parameter 'a'; \
+                    declare it as '^[4]P' to modify it";
+        for body in [
+            "let p: ^P = &a[0]; (^p).x = 5;",
+            "let p = &(a[1]).x;",
+            "let p = &a[2].x;",
+        ] {
+            let source = format!("struct P {{ x: i32; }}\nfunc f(a: [4]P): void {{ {body} }}");
+            let errors = check_fn(&source).unwrap_err();
+            assert_eq!(errors[0].1, part, "{body}");
+        }
+        assert!(
+            check_fn("func f(a: [4]i32): i32 { let b = a[0]; let p = &b; return ^p; }").is_ok()
+        );
+
+        let source = "func set(a: ^[4]i32, v: i32): void { (^a)[0] = v; }\n\
+                      func main(): i32 {\n\
+                      let xs = [1, 2, 3, 4]; set(&xs, 5);\n\
+                      const p: ^[4]i32 = &xs; return (^p)[3]; }";
+        let c = compile_to_c_string(source, &CompileOptions::default()).unwrap();
+        assert!(c.contains("void set(int32_t (*a)[4], int32_t v) {\n  (*a)[0] = v;"));
+        assert!(c.contains("set(&xs, 5);"));
+        assert!(c.contains("int32_t (* const p)[4] = &xs;"));
+        assert_c_compiles(&c, "array_params");
+    }
+
+    #[test]
+    fn size_params() {
+        let source = "func sum<N: u64>(xs: [N]i32): i32 {\n\
+                      let total = 0; let i: u64 = 0;\n\
+                      while (i < N) { total += xs[i]; i += 1; }\n\
+                      return total; }\n\
+                      func twice<N: u64>(xs: [N]i32): i32 { return sum(xs) * 2; }\n\
+                      func main(): i32 {\n\
+                      let a = [1, 2, 3]; let b = [1, 2, 3, 4];\n\
+                      return sum(a) + sum(b) + twice(a); }";
+        let c = compile_to_c_string(source, &CompileOptions::default()).unwrap();
+        assert!(c.contains("int32_t sum_3(const int32_t xs[3]) {"));
+        assert!(c.contains("int32_t sum_4(const int32_t xs[4]) {"));
+        assert!(c.contains("while (i < (uint64_t)(4)) {"));
+        assert!(c.contains("return sum_3(xs) * 2;"));
+        assert!(c.contains("return sum_3(a) + sum_4(b) + twice_3(a);"));
+        assert!(!c.contains("sum("));
+
+        let check_fns = |fns: &str| {
+            let source = format!("{fns}\nfunc main(): void {{}}");
+            check(&source, &CompileOptions::default()).1
+        };
+
+        let errors = check_fns("func f<N: u64>(x: i32): void {}").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "The size parameter 'N' of 'f' must be the length of an array parameter, like '[N]i32'"
+        );
+        let errors = check_fns("func f<N: double>(xs: [N]i32): void {}").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "The size parameter 'N' must be an integer but got 'double'"
+        );
+        let errors = check_fns("func f(xs: [N]i32): void {}").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "'N' is neither a global constant nor a size parameter"
+        );
+
+        let dot = "func dot<N: u64>(a: [N]i32, b: [N]i32): i32 { return a[0] * b[0]; }";
+        let errors =
+            check_fns(&format!("{dot}\nfunc f(): i32 {{ return dot([1, 2], [1, 2, 3]); }}"))
+                .unwrap_err();
+        assert_eq!(errors[0].1, "The size parameter 'N' cannot be both 2 and 3");
+        let errors = check_fns(&format!("{dot}\nfunc f(): void {{ let g = dot; }}")).unwrap_err();
+        assert_eq!(errors[0].1, "The generic function 'dot' can only be called");
+    }
+
+    #[test]
+    fn constant_lengths() {
+        let source = "const N = 4;\nconst M = N * 2;\n\
+                      struct Buf { data: [M]u8; }\n\
+                      func first<N: u64>(xs: [N]i32): i32 { return xs[0]; }\n\
+                      func main(): i32 { let xs: [N]i32 = [1, 2, 3, 4]; let b: Buf;\n\
+                      b.data[7] = 1; return first(xs); }";
+        let (warnings, res) = check(source, &CompileOptions::default());
+        assert!(res.is_ok());
+        assert!(warnings.iter().all(|(_, msg)| !msg.contains("'N'")));
+        let c = compile_to_c_string(source, &CompileOptions::default()).unwrap();
+        assert!(c.contains("uint8_t data[8];"));
+        assert!(c.contains("int32_t xs[4] = {1, 2, 3, 4};"));
+        assert!(c.contains("int32_t first_4(const int32_t xs[4]) {"));
+
+        let check_body = |body: &str| {
+            let source = format!(
+                "const NEG = -1;\nconst HALF = 0.5;\nlet n = 2;\nfunc main(): void {{ {body} }}"
+            );
+            check(&source, &CompileOptions::default()).1
+        };
+        let errors = check_body("let a: [NEG]i32;").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "'NEG' cannot be the length of an array, since it is -1"
+        );
+        let errors = check_body("let a: [HALF]i32;").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "'HALF' cannot be the length of an array, since it isn't an integer"
+        );
+        let errors = check_body("let a: [n]i32;").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "'n' is neither a global constant nor a size parameter"
+        );
+    }
+
+    #[test]
+    fn integer_literal_range() {
+        let source = "func main(): i32 { let big: u64 = 18446744073709551615;\n\
+                      let min: i64 = -9223372036854775808; let max: i64 = 9223372036854775807;\n\
+                      return cast(i32, big - cast(u64, min + max) - 1); }";
+        let c = compile_to_c_string(source, &CompileOptions::default()).unwrap();
+        assert!(c.contains("uint64_t big = 18446744073709551615ULL;"));
+        assert!(c.contains("int64_t min = (-9223372036854775807LL - 1);"));
+        assert!(c.contains("int64_t max = 9223372036854775807;"));
+        assert_c_compiles(&c, "integer_literal_range");
+    }
+
+    #[test]
+    fn constant_function_values() {
+        let source = "func sq(x: i32): i32 { return x * x; }\n\
+                      enum E { A = sq(2); B = -sq(3); C; }\n\
+                      struct Buf { data: [sq(2) + 1]u8; }\n\
+                      func main(): i32 { let xs: [sq(3)]i32; let b: Buf;\n\
+                      xs[8] = 1; b.data[4] = 2; return cast(i32, E->A); }";
+        let (warnings, res) = check(source, &CompileOptions::default());
+        assert!(res.is_ok(), "{res:?}");
+        assert!(warnings.iter().all(|(_, msg)| !msg.contains("'sq'")));
+        let c = compile_to_c_string(source, &CompileOptions::default()).unwrap();
+        assert!(c.contains("E_A = 4,"));
+        assert!(c.contains("E_B = -9,"));
+        assert!(c.contains("uint8_t data[5];"));
+        assert!(c.contains("int32_t xs[9];"));
+        assert_c_compiles(&c, "constant_function_values");
+
+        let check_source = |source: &str| {
+            let source = format!(
+                "extern func puts(s: str): i32;\n\
+                 func loud(): i32 {{ puts(\"x\"); return 1; }}\n{source}\nfunc main(): void {{}}"
+            );
+            check(&source, &CompileOptions::default()).1.unwrap_err()
+        };
+        let errors = check_source("let xs: [loud()]i32;");
+        assert_eq!(
+            errors[0].1,
+            "'loud()' cannot be the length of an array, since its value isn't known at compile time"
+        );
+        let errors = check_source("let xs: [1 - 2]i32;");
+        assert_eq!(
+            errors[0].1,
+            "'1 - 2' cannot be the length of an array, since it is -1"
+        );
+        let errors = check_source("enum E { A = loud(); }");
+        assert_eq!(
+            errors[0].1,
+            "The value of 'E->A' must be known at compile time"
+        );
+        let errors = check_source("enum E { A = 0.5; }");
+        assert_eq!(errors[0].1, "The value of 'E->A' must be an integer");
+    }
+
+    #[test]
+    fn statemachine_return() {
+        let source = "@statemachine\nfunc gen(): i32 { yield 1; return 2; }\nfunc main(): void {}";
+        let errors = check(source, &CompileOptions::default()).1.unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Cannot return a value from a @statemachine function"
+        );
+        assert_eq!(
+            errors[1].1,
+            "help: Use 'yield' to give the caller a value, and 'return;' to finish"
+        );
+    }
+
+    #[test]
+    fn statemachine_uses() {
+        let counter = "@statemachine\n\
+                       func counter(n: i32): i32 { let i: i32 = 0; while (i < n) { yield i; } }\n";
+        for body in ["let g = counter(3);", "let f = counter;"] {
+            let source = format!("{counter}func main(): void {{ {body} }}");
+            let errors = check(&source, &CompileOptions::default()).1.unwrap_err();
+            assert_eq!(
+                errors[0].1,
+                "The @statemachine function 'counter' cannot be used directly, C runs it through \
+                 'counter_init' and 'counter_step'"
+            );
+        }
+
+        let source = format!("{counter}func main(): void {{ let counter = 1; counter += 1; }}");
+        assert!(check(&source, &CompileOptions::default()).1.is_ok());
+    }
+
+    #[test]
+    fn type_params() {
+        let options = CompileOptions {
+            loader: ModuleLoader::new("/nonexistent")
+                .source("util", "func id<T>(x: T): T { return x; }"),
+            ..Default::default()
+        };
+        let source = "import util \"util\";\n\
+                      func max<T>(a: T, b: T): T { if (a > b) { return a; } return b; }\n\
+                      func swap<T>(a: ^T, b: ^T): void { let tmp: T = ^a; ^a = ^b; ^b = tmp; }\n\
+                      func first<T, N: u64>(xs: [N]T): T { return util::id(xs[0]); }\n\
+                      func main(): void {\n\
+                      let i = max(1, 2); let d = max(1.5, 2.0); swap(&i, &i);\n\
+                      let xs = [1.5, 2.5]; let x = first(xs); }";
+        let c = compile_to_c_string(source, &options).unwrap();
+        assert!(c.contains("int32_t max_i32(int32_t a, int32_t b) {"));
+        assert!(c.contains("double max_double(double a, double b) {"));
+        assert!(c.contains("void swap_i32(int32_t* a, int32_t* b) {\n  int32_t tmp = *a;"));
+        assert!(c.contains("double first_double_2(const double xs[2]) {"));
+        assert!(c.contains("return util_id_double(xs[0]);"));
+        assert!(c.contains("int32_t i = max_i32(1, 2);"));
+        assert!(!c.contains("max("));
+
+        let check_fns = |fns: &str| {
+            let source = format!("{fns}\nfunc main(): void {{}}");
+            check(&source, &CompileOptions::default()).1
+        };
+
+        let errors = check_fns("func f<T>(x: i32): void {}").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "The type parameter 'T' of 'f' must be the type of a parameter, like 'x: T'"
+        );
+        let errors = check_fns("struct T { x: i32; }\nfunc f<T>(x: T): void {}").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "The type parameter 'T' has the same name as a global"
+        );
+        assert_eq!(errors[1], (0..6, "note: 'T' is declared here".to_string()));
+
+        // The body is checked for each type the function is called with
+        let inc = "func inc<T>(x: T): T { return x + 1; }";
+        assert!(check_fns(&format!("{inc}\nfunc f(): i32 {{ return inc(1); }}")).is_ok());
+        let errors =
+            check_fns(&format!("{inc}\nfunc f(): str {{ return inc(\"a\"); }}")).unwrap_err();
+        assert_eq!(errors[0].1, "Cannot apply '+' to 'str' and 'i32'");
+        let errors = check_fns(&format!("{inc}\nfunc f(): void {{ let g = inc; }}")).unwrap_err();
+        assert_eq!(errors[0].1, "The generic function 'inc' can only be called");
+
+        let errors = check_fns("func f<T>(x: T): void { f(&x); }\nfunc g(): void { f(1); }")
+            .unwrap_err();
+        assert!(errors[0].1.starts_with("Instantiating this call nests types too deeply"));
+    }
+
+    #[test]
+    fn abi_json() {
+        let source = "enum Color { Red; Green = 5; Blue; }\n\
+                      struct Pixel { c: u8; next: ^Pixel; rgb: [3]u8; }\n\
+                      func divmod(a: i32, b: i32): (i32, i32) { return (a / b, a % b); }\n\
+                      extern func puts(s: str): i32;\n\
+                      func main(): i32 { let (q, r) = divmod(7, 2); return q + r; }";
+        let (_, res) = super::abi_json(source, &CompileOptions::default());
+        let json = res.unwrap();
+
+        let compact: String = json.split_whitespace().collect();
+        assert!(compact.starts_with("{\"version\":1,"));
+        assert!(compact.contains(
+            "{\"name\":\"Green\",\"value\":5},{\"name\":\"Blue\",\"value\":6}"
+        ));
+        assert!(compact.contains(
+            "\"name\":\"Pixel\",\"size\":24,\"align\":8,\"fields\":[{\"name\":\"c\",\
+             \"type\":\"u8\",\"offset\":0},{\"name\":\"next\",\"type\":\"^Pixel\",\
+             \"offset\":8},{\"name\":\"rgb\",\"type\":\"[3]u8\",\"offset\":16}]"
+        ));
+        assert!(compact.contains(
+            "{\"name\":\"divmod\",\"params\":[{\"name\":\"a\",\"type\":\"i32\"},\
+             {\"name\":\"b\",\"type\":\"i32\"}],\"ret\":\"divmod_result\"}"
+        ));
+        assert!(compact.contains("\"name\":\"divmod_result\",\"size\":8,\"align\":4"));
+        assert!(!compact.contains("puts"));
+    }
+
+    #[test]
+    fn array_literals() {
+        let check_ret = |ret: &str, expr: &str| {
+            let source = format!(
+                "func f(x: u8, xs: [^]i32): {ret} {{ let a = [1, 2, 3]; return {expr}; }}\n\
+                 func main(): void {{}}"
+            );
+            let (warnings, res) = check(&source, &CompileOptions::default());
+            let warnings: Vec<_> = warnings
+                .into_iter()
+                .map(|w| w.1)
+                .filter(|w| w.starts_with("Index"))
+                .collect();
+            (warnings, res)
+        };
+
+        assert!(check_ret("[2]double", "[1, 2.5]").1.is_ok());
+        assert!(check_ret("[2]double", "[2.5, 1]").1.is_ok());
+        assert!(check_ret("[2]u8", "[x, 1]").1.is_ok());
+        assert!(check_ret("[2]u8", "[1, x]").1.is_ok());
+        assert!(check_ret("[3]i32", "[x, 300, 1]").1.is_ok());
+
+        let (_, res) = check_ret("[2]i32", "[1, \"a\"]");
+        assert_eq!(
+            res.unwrap_err()[0].1,
+            "Expected 'i32' like the first element but got 'str'"
+        );
+
+        let (warnings, res) = check_ret("i32", "a[2] + xs[0]");
+        assert!(res.is_ok() && warnings.is_empty());
+
+        let (warnings, _) = check_ret("i32", "a[3] + a[1 - 2]");
+        assert_eq!(
+            warnings,
+            [
+                "Index 3 is out of bounds for an array of length 3",
+                "Index -1 is out of bounds for an array of length 3"
+            ]
+        );
+
+        let (warnings, _) = check_ret("i32", "xs[-1]");
+        assert_eq!(warnings, ["Index -1 is always out of bounds for '[^]i32'"]);
+    }
+
+    #[test]
+    fn global_order() {
+        let check_src = |source: &str| check(source, &CompileOptions::default()).1;
+
+        assert!(check_src("func main(): void { f(); }\nfunc f(): void {}").is_ok());
+        assert!(check_src("let x: i32;\nfunc main(): void { x; }").is_ok());
+
+        let errors =
+            check_src("let x: i32 = f();\nfunc f(): i32 { return 1; }\nfunc main(): void { x; }")
+                .unwrap_err();
+        assert_eq!(
+            errors[0],
+            (
+                13..14,
+                "Non-constant global initializer: 'x' cannot be initialized by calling a function"
+                    .to_string()
+            )
+        );
+
+        let errors = check_src("let x: i32 = y + 1;\nlet y: i32;\nfunc main(): void { x; }")
+            .unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Non-constant global initializer: 'x' cannot be initialized with the value of the \
+             global variable 'y'"
+        );
+    }
+
+    #[test]
+    fn declarations() {
+        let check_body = |body: &str| {
+            let source = format!(
+                "const LIMIT: u8 = 8;\nlet counter: u8 = 1;\n\
+                 func f(p: ^i32): void {{ {body} }}\nfunc main(): void {{}}"
+            );
+            check(&source, &CompileOptions::default()).1
+        };
+
+        assert!(check_body("let x: u8 = 255; const y: u8 = LIMIT; counter = x + y;").is_ok());
+        assert!(check_body("const q = p; q[0] = 1; let LIMIT = 1; LIMIT += 1;").is_ok());
+
+        let errors = check_body("let x;").unwrap_err();
+        assert_eq!(errors[0].1, "Expected an explicit type but got nothing");
+
+        let errors = check_body("let x: u8 = 256;").unwrap_err();
+        assert_eq!(errors[0].1, "Expected 'u8' but got 'i32'");
+
+        let errors = check_body("LIMIT = 1;").unwrap_err();
+        assert_eq!(errors[0].1, "'LIMIT' is a constant and cannot be modified");
+
+        let errors = check_body("const x = 1; x += 1;").unwrap_err();
+        assert_eq!(errors[0].1, "'x' is a constant and cannot be modified");
+
+        let errors = check_body("const a = [1, 2]; a[0] = 3;").unwrap_err();
+        assert_eq!(errors[0].1, "'a' is a constant and cannot be modified");
+
+        let errors = check_body("const [a, b] = [1, 2]; b = a;").unwrap_err();
+        assert_eq!(errors[0].1, "'b' is a constant and cannot be modified");
+    }
+
+    #[test]
+    fn division() {
+        let check_body = |body: &str| {
+            let source = format!(
+                "const ZERO: i32 = 0;\n\
+                 func f(x: i32, y: double, n: i32): i32 {{ {body} return n + cast(i32, y); }}\n\
+                 func g(ZERO: i32): i32 {{ return 1 / ZERO; }}\n\
+                 func main(): void {{ f(1, 1.0, 1); g(1); }}"
+            );
+            // The parameter of `g` is named like a constant on purpose to shadow `ZERO`
+            let options = CompileOptions {
+                naming: NamingConventions::default().rule(NamingRule::Variables, false),
+                ..Default::default()
+            };
+            check(&source, &options)
+        };
+
+        let (warnings, res) = check_body("x / n; x % 3; y / 0.0; x / (ZERO + 1);");
+        assert!(res.is_ok() && warnings.is_empty());
+
+        let errors = check_body("x / 0;").1.unwrap_err();
+        assert_eq!(errors[0].1, "Division by zero, the right operand of '/' is always 0");
+
+        let errors = check_body("x % (ZERO * 2);").1.unwrap_err();
+        assert_eq!(errors[0].1, "Division by zero, the right operand of '%' is always 0");
+
+        let (warnings, res) = check_body("x % -2; x % 2 == 1; x + ZERO;");
+        assert!(res.is_ok());
+        assert!(warnings[0].1.starts_with("The result of '%' takes the sign"));
+        assert!(warnings[1].1.starts_with("The remainder of a negative 'i32'"));
+    }
+
+    #[test]
+    fn constants() {
+        let check_globals = |globals: &str| {
+            let source = format!("{globals}\nfunc main(): void {{}}");
+            check(&source, &CompileOptions::default()).1
+        };
+
+        assert!(check_globals("const A: u64 = 4; const B: u64 = sizeof(i32) * A;").is_ok());
+
+        let errors = check_globals("const S: u64 = sizeof(void);").unwrap_err();
+        assert_eq!(errors[0].1, "'void' has no size");
+
+        let errors = check_globals("const S: u64 = sizeof(Point);").unwrap_err();
+        assert_eq!(errors[0].1, "'Point' is not declared");
+
+        let errors = check_globals("const D: i32 = [1, 2][sizeof(i32) - 4];").unwrap_err();
+        assert_eq!(errors[0].1, "'D' must be initialized with constant expressions");
+
+        let source = "const S: u64 = sizeof(i32) * 2;\nconst N: i32 = 3 + 4;\n\
+                      func main(): void { let x: u64 = S; }";
+        let c = compile_to_c_string(source, &CompileOptions::default()).unwrap();
+        assert!(c.contains("#define S ((uint64_t)(sizeof(int32_t) * 2))"));
+        assert!(c.contains("static const int32_t N = 7;"));
+    }
+
+    #[test]
+    fn constant_functions() {
+        let functions = "extern func puts(s: str): i32;\n\
+                         func fact(n: i32): i32 { let acc = 1; while (n > 1) { acc *= n; n -= 1; } \
+                         return acc; }\n\
+                         func fib(n: i32): i32 { if (n < 2) { return n; } \
+                         return fib(n - 1) + fib(n - 2); }\n\
+                         func noisy(n: i32): i32 { puts(\"hi\"); return n; }\n\
+                         func twice(n: i32): i32 { return noisy(n) * 2; }\n\
+                         func spin(): i32 { while (true) {} return 0; }\n";
+        let compile = |rest: &str| {
+            let source = format!("{functions}{rest}\nfunc main(): void {{}}");
+            compile_to_c_string(&source, &CompileOptions::default())
+        };
+
+        let c = compile("const F: i32 = fact(5) + fib(10);\nconst G: i32 = F * 2;").unwrap();
+        assert!(c.contains("static const int32_t F = 175;"));
+        assert!(c.contains("static const int32_t G = 350;"));
+
+        let c =
+            compile("func pick(n: i32): i32 { switch (n) { case fib(6): return 1; } return 0; }")
+                .unwrap();
+        assert!(c.contains("case 8:"));
+
+        // The notes point at the statements that keep each call from being constant
+        let errors = compile("const T: i32 = twice(3);").unwrap_err().errors;
+        assert_eq!(
+            errors[0].1,
+            "'T' must be initialized with constant expressions"
+        );
+        assert_eq!(
+            errors[1].1,
+            "note: 'twice' isn't constant because this statement calls 'noisy', which isn't \
+             constant"
+        );
+        assert_eq!(
+            errors[2].1,
+            "note: 'noisy' isn't constant because this statement calls 'puts', which isn't a \
+             constant function"
+        );
+
+        let errors = compile("const S: i32 = spin();").unwrap_err().errors;
+        assert_eq!(
+            errors[1].1,
+            "note: 'spin' ran more than 100000 statements at compile time, so it may not terminate"
+        );
+
+        let errors = compile("const P: i32 = puts(\"x\");").unwrap_err().errors;
+        assert_eq!(
+            errors[0].1,
+            "Non-constant global initializer: 'P' cannot be initialized by calling a function"
+        );
+    }
+
+    #[test]
+    fn switch() {
+        let check_main = |body: &str| {
+            let source = format!(
+                "enum Color {{ Red, Green, Blue, }}\n\
+                 func main(): void {{ let c = Color->Red; let n: i32 = 1; {body} }}"
+            );
+            check(&source, &CompileOptions::default()).1
+        };
+
+        assert!(
+            check_main("switch (c) { case Color->Red: case Color->Green, Color->Blue: }").is_ok()
+        );
+        assert!(check_main("switch (n) { case 1, 2 + 1: n = 0; default: break; }").is_ok());
+
+        let errors = check_main("switch (c) { case Color->Red: }").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "The switch on 'Color' doesn't handle 'Green', 'Blue', add cases for them or a default"
+        );
+
+        let errors = check_main("switch (n) { case 1: case 3 - 2: }").unwrap_err();
+        assert_eq!(errors[0].1, "1 is already handled by an earlier case");
+
+        let errors = check_main("switch (n) { case n: }").unwrap_err();
+        assert_eq!(errors[0].1, "Cases must be constant expressions");
+
+        let errors = check_main("switch (c) { case 1: default: }").unwrap_err();
+        assert_eq!(errors[0].1, "Expected a case of type 'Color' but got 'i32'");
+
+        let errors = check_main("switch (c) { case Color->Pink: default: }").unwrap_err();
+        assert_eq!(errors[0].1, "The enum 'Color' has no variant 'Pink'");
+
+        let errors = check_main("switch (1.5) { default: }").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Cannot switch on 'double', only on integers, characters, booleans, and enums"
+        );
+
+        // A switch returns if every case does and it can't be skipped
+        let source = "enum Color { Red, Green, }\n\
+                      func f(c: Color): i32 { switch (c) { case Color->Red: return 1; \
+                      case Color->Green: return 2; } return 0; }\n\
+                      func g(n: i32): i32 { switch (n) { case 0: return 1; } return 0; }\n\
+                      func main(): void {}";
+        let (warnings, res) = check(source, &CompileOptions::default());
+        assert!(res.is_ok());
+        let unreachable = warnings
+            .iter()
+            .filter(|(_, msg)| msg == "Unreachable code after return")
+            .count();
+        assert_eq!(unreachable, 1);
+
+        let source = "func main(): void { let n: i32 = 0; while (true) { defer n = 1; \
+                      switch (n) { case 0, 1: continue; default: } } }";
+        let c = compile_to_c_string(source, &CompileOptions::default()).unwrap();
+        assert!(c.contains("case 0: {\n    }\n    case 1: {\n      {\n        n = 1;"));
+        assert!(c.contains("default: {\n      break;\n    }"));
+    }
+
+    #[test]
+    fn defer() {
+        let source = "func f(): void {}\nfunc g(): void {}\n\
+                      func main(): void { let i = 0; defer f(); defer g();\n\
+                      while (i < 3) { defer i += 1; if (i == 1) { break; } } }";
+        let c = compile_to_c_string(source, &CompileOptions::default()).unwrap();
+        assert!(c.contains(
+            "if (i == 1) {\n      {\n        i += 1;\n      }\n      break;\n    }\n    \
+             {\n      i += 1;\n    }\n  }\n  {\n    g();\n  }\n  {\n    f();\n  }\n  \
+             return 0;"
+        ));
+
+        let check_body = |body: &str| {
+            let source = format!("func f(): void {{ {body} }}\nfunc main(): void {{ f(); }}");
+            check(&source, &CompileOptions::default()).1
+        };
+        assert!(check_body("defer { while (true) { break; } }").is_ok());
+        assert!(check_body("while (true) { switch (1) { case 1: continue; } }").is_ok());
+
+        for (body, expected) in [
+            ("while (true) { defer { break; } }", "'break' cannot leave a deferred block"),
+            (
+                "while (true) { defer { if (true) { continue; } } }",
+                "'continue' cannot leave a deferred block",
+            ),
+            ("defer { return; }", "No return statement is allowed in defer body"),
+            (
+                "defer { while (true) { return; } }",
+                "No return statement is allowed in defer body",
+            ),
+            ("break;", "'break' must be in a loop or a switch"),
+            ("switch (1) { case 1: continue; }", "'continue' must be in a loop"),
+        ] {
+            let errors = check_body(body).unwrap_err();
+            assert_eq!(errors[0].1, expected, "{body}");
+        }
+    }
+
+    #[test]
+    fn multiple_returns() {
+        let check_source = |source: &str| check(source, &CompileOptions::default()).1;
+        let divmod = "func divmod(a: i32, b: i32): (i32, i32) { return (a / b, a % b); }\n";
+
+        let source = format!("{divmod}func main(): void {{ let (q, r) = divmod(7, 2); }}");
+        assert!(check_source(&source).is_ok());
+
+        let source = format!("{divmod}func main(): void {{ let (q) = divmod(7, 2); }}");
+        let errors = check_source(&source).unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Cannot destructure the 2 values of '(i32, i32)' into 1 variables"
+        );
+
+        let source = format!("{divmod}func main(): void {{ let qr = divmod(7, 2); }}");
+        let errors = check_source(&source).unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Cannot store '(i32, i32)' in a variable, destructure multiple return values like \
+             'let (a, b) = ...'"
+        );
+
+        let errors = check_source("func f(): (i32, bool) { return (1, 2); }").unwrap_err();
+        assert_eq!(errors[0].1, "Expected 'bool' but got 'i32'");
+
+        let errors = check_source("func f(): (i32, i32) { return (1, 2, 3); }").unwrap_err();
+        assert_eq!(errors[0].1, "Expected 2 values for '(i32, i32)' but got 3");
+
+        let errors = check_source("func f(): i32 { return (1, 2); }").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Multiple values can only be returned from a function declared to return them"
+        );
+
+        let source = format!(
+            "{divmod}func main(): i32 {{ let (q, r) = divmod(7, 2); divmod(1, 1); return q + r; }}"
+        );
+        let c = compile_to_c_string(&source, &CompileOptions::default()).unwrap();
+        assert!(c.contains("struct divmod_result {\n  int32_t _0;\n  int32_t _1;\n};"));
+        assert!(c.contains("return (divmod_result){._0=a / b, ._1=a % b};"));
+        assert!(c.contains("int32_t q = __destructured0._0;"));
+
+        let options = CompileOptions {
+            multiple_returns: MultipleReturns::OutParams,
+            ..Default::default()
+        };
+        let c = compile_to_c_string(&source, &options).unwrap();
+        assert!(c.contains("void divmod(int32_t a, int32_t b, int32_t* __out0, int32_t* __out1)"));
+        assert!(c.contains("*__out0 = a / b;\n  *__out1 = a % b;\n  return;"));
+        assert!(c.contains("int32_t q;\n  int32_t r;\n  divmod(7, 2, &q, &r);"));
+        assert!(c.contains("divmod(1, 1, &__results0_0, &__results0_1);"));
+
+        // A call returning the same values can be returned directly
+        let source = format!(
+            "{divmod}func by3(a: i32): (i32, i32) {{ return (divmod(a, 3)); }}\n\
+             func main(): i32 {{ let (q, r) = by3(10); return q * 10 + r; }}"
+        );
+        let c = compile_to_c_string(&source, &CompileOptions::default()).unwrap();
+        assert!(c.contains("divmod_result __destructured0 = divmod(a, 3);"));
+        assert!(c.contains("return (by3_result){._0=__results0_0, ._1=__results0_1};"));
+        assert_c_compiles(&c, "forwarded_returns");
+        let c = compile_to_c_string(&source, &options).unwrap();
+        assert!(c.contains("divmod(a, 3, &__results0_0, &__results0_1);"));
+        assert!(c.contains("*__out0 = __results0_0;\n  *__out1 = __results0_1;\n  return;"));
+        assert_c_compiles(&c, "forwarded_out_params");
+
+        let source = "func pair(): (i32, bool) { return (1, true); }\n\
+                      func f(): (i32, i32) { return pair(); }";
+        let errors = check_source(source).unwrap_err();
+        assert_eq!(errors[0].1, "Expected '(i32, i32)' but got '(i32, bool)'");
+    }
+
+    #[test]
+    fn compound_literals() {
+        let source = "struct P { x: i32; y: i32; }\n\
+                      struct Err { code: i32; }\n\
+                      struct Line { a: P; b: P; }\n\
+                      func sx(p: P): i32 { return p.x; }\n\
+                      func tot(xs: [3]i32): i32 { return xs[0] + xs[1] + xs[2]; }\n\
+                      func pick<T>(c: bool, a: T, b: T): T { if (c) { return a; } return b; }\n\
+                      func first<T, N: u64>(xs: [N]T): T { return xs[0]; }\n\
+                      func origin(): P { return P { x = 0, y = 0 }; }\n\
+                      func main(): i32 {\n\
+                      let p = P { x = 1, y = 2 }; p = P { x = 3, y = 3 };\n\
+                      let l = Line { a = P { x = 1, y = 2 }, b = p };\n\
+                      l.a = P { x = 5, y = 5 };\n\
+                      let e = pick(true, Err { code = 1 }, Err { code = 2 });\n\
+                      return sx(P { x = 7, y = 0 }) + tot([1, 2, 3]) + first([5, 6]) + e.code \
+                      + l.a.x; }";
+        let c = compile_to_c_string(source, &CompileOptions::default()).unwrap();
+
+        // Declarations keep their initializers, along with the ones nested in them
+        assert!(c.contains("P p = {.x=1, .y=2};"));
+        assert!(c.contains("Line l = {.a={.x=1, .y=2}, .b=p};"));
+        assert!(c.contains("return (P){.x=0, .y=0};"));
+        assert!(c.contains("p = (P){.x=3, .y=3};"));
+        assert!(c.contains("l.a = (P){.x=5, .y=5};"));
+        assert!(c.contains("pick_Err(true, (Err){.code=1}, (Err){.code=2})"));
+        assert!(c.contains("sx((P){.x=7, .y=0})"));
+        assert!(c.contains("tot((int32_t[3]){1, 2, 3})"));
+        assert!(c.contains("first_i32_2((int32_t[2]){5, 6})"));
+        assert_c_compiles(&c, "compound_literals");
+    }
+
+    #[test]
+    fn naming_conventions() {
+        let source = "extern func GetTickCount(): u32;\n\
+                      struct point_2d { x: i32; y: i32; }\n\
+                      alias Meters = i32;\n\
+                      const maxLen: i32 = 4;\n\
+                      let SDL_Count: i32 = 0;\n\
+                      func drawPoint(P: point_2d): void { let { x, y } = P; let Total = x + y; }\n\
+                      func main(): void { GetTickCount(); drawPoint(point_2d { x = 1, y = 2 }); }";
+        let lint = |naming: NamingConventions| {
+            let options = CompileOptions {
+                naming,
+                ..Default::default()
+            };
+            let (warnings, res) = check(source, &options);
+            assert!(res.is_ok());
+            warnings
+                .into_iter()
+                .map(|(_, msg)| msg)
+                .filter(|msg| msg.contains("should be named"))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            lint(NamingConventions::default()),
+            [
+                "The struct 'point_2d' should be named in CamelCase, like 'Point2d'",
+                "The constant 'maxLen' should be named in SCREAMING_SNAKE_CASE, like 'MAX_LEN'",
+                "The variable 'SDL_Count' should be named in snake_case, like 'sdl_count'",
+                "The function 'drawPoint' should be named in snake_case, like 'draw_point'",
+                "The parameter 'P' should be named in snake_case, like 'p'",
+                "The variable 'Total' should be named in snake_case, like 'total'",
+            ]
+        );
+
+        let naming = NamingConventions::default()
+            .rule(NamingRule::Variables, false)
+            .rule(NamingRule::Types, false)
+            .allow_prefix("draw");
+        assert_eq!(
+            lint(naming),
+            ["The constant 'maxLen' should be named in SCREAMING_SNAKE_CASE, like 'MAX_LEN'"]
+        );
+    }
+
+    #[test]
+    fn free_and_destroy() {
+        let check_body = |body: &str| {
+            let source = format!(
+                "func f(p: ^i32, a: [^]i32, x: i32): void {{ {body} }}\nfunc main(): void {{}}"
+            );
+            check(&source, &CompileOptions::default()).1
+        };
+
+        assert!(check_body("free p; destroy a;").is_ok());
+
+        let errors = check_body("free x;").unwrap_err();
+        assert_eq!(errors[0].1, "'free' expects a pointer but got 'i32'");
+
+        let errors = check_body("free a;").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "'free' expects a pointer but got '[^]i32', use 'destroy' for dynamic arrays"
+        );
+
+        let errors = check_body("destroy p;").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "'destroy' expects a dynamic array but got '^i32', use 'free' for pointers"
+        );
+    }
+
+    #[test]
+    fn allocation() {
+        let source = "struct P { x: i32; }\n\
+                      func main(): i32 {\n\
+                      let p = new P; let xs = make [^]^P;\n\
+                      append(&xs, p); let x = (^xs[0]).x;\n\
+                      free p; destroy xs; return x; }";
+        let (warnings, result) = check(source, &CompileOptions::default());
+        assert!(result.is_ok());
+        assert!(warnings.is_empty());
+
+        let c = compile_to_c_string(source, &CompileOptions::default()).unwrap();
+        assert!(c.contains("P* p = (P*)(castella_new(sizeof(P), \"main.clla\", 3));"));
+        assert!(c.contains("castella_darray xs = (castella_darray){0};"));
+        assert!(c.contains("free(p);"));
+        assert!(c.contains("castella_darray_free(&xs);"));
+
+        let check_body = |body: &str| {
+            let source = format!("func main(): void {{ {body} }}");
+            check(&source, &CompileOptions::default()).1
+        };
+
+        let errors = check_body("let p = new void;").unwrap_err();
+        assert_eq!(errors[0].1, "'new' cannot allocate 'void'");
+        let errors = check_body("let p = new [2]Q;").unwrap_err();
+        assert_eq!(errors[0].1, "'Q' is not declared");
+        let errors = check_body("let a = make [^]void;").unwrap_err();
+        assert_eq!(errors[0].1, "A dynamic array cannot hold 'void'");
+        let errors = check_body("let a = make [^]^Q;").unwrap_err();
+        assert_eq!(errors[0].1, "'Q' is not declared");
+        let errors = check_body("let a = make [4]i32;").unwrap_err();
+        assert_eq!(errors[0].1, "'make' creates dynamic arrays, but got '[4]i32'");
+    }
+    #[test]
+    fn function_pointer_fields() {
+        let check_body = |body: &str| {
+            let source = format!(
+                "struct Handler {{ on_read: func(^u8, u64): i32; }}\n\
+                 func read_all(buf: ^u8, n: u64): i32 {{ return 0; }}\n\
+                 func f(h: Handler, buf: ^u8): i32 {{ {body} }}\n\
+                 func main(): void {{}}"
+            );
+            check(&source, &CompileOptions::default()).1
+        };
+
+        assert!(check_body("h.on_read = read_all; return h.on_read(buf, 4);").is_ok());
+
+        let errors = check_body("h.on_read(buf);").unwrap_err();
+        assert_eq!(errors[0].1, "Expected 2 argument(s) but got 1");
+
+        let errors = check_body("h.on_read(buf, true);").unwrap_err();
+        assert_eq!(errors[0].1, "Expected 'u64' but got 'bool'");
+
+        let errors = check_body("h.on_write(buf, 4);").unwrap_err();
+        assert_eq!(errors[0].1, "'Handler' has no field 'on_write'");
+
+        let errors = check_body("h.on_read = f;").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Cannot apply '=' to 'func(^u8, u64): i32' and 'func(Handler, ^u8): i32'"
+        );
+    }
+    #[test]
+    fn constant_strings() {
+        let check_consts = |consts: &str| {
+            let source = format!(
+                "const NAME: str = \"castella\";\n{consts}\nfunc main(): void {{}}"
+            );
+            check(&source, &CompileOptions::default()).1
+        };
+
+        assert!(
+            check_consts("const FIRST: char = NAME[0];\nconst LEN: u64 = len(NAME);").is_ok()
+        );
+
+        let errors = check_consts("const LAST: char = NAME[8];").unwrap_err();
+        assert_eq!(
+            errors[0],
+            (
+                54..55,
+                "Index 8 is out of bounds for the constant string 'NAME' of length 8".to_string()
+            )
+        );
+
+        let errors =
+            check_consts("const LEN: u64 = len(NAME);\nconst C: char = \"ab\"[LEN - 6];")
+                .unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Index 2 is out of bounds for a string of length 2"
+        );
+    }
+    #[test]
+    fn destructuring() {
+        let source = |body: &str| {
+            format!(
+                "struct Point {{ x: i32; y: i32; }}\n\
+                 func origin(): Point {{ return Point {{ x = 0, y = 0 }}; }}\n\
+                 func main(): void {{ {body} }}"
+            )
+        };
+        let error = |body: &str| {
+            check(&source(body), &CompileOptions::default())
+                .1
+                .unwrap_err()[0]
+                .1
+                .clone()
+        };
+
+        assert_eq!(
+            error("let p = origin(); let { x, z } = p;"),
+            "'Point' has no field 'z'"
+        );
+        assert_eq!(
+            error("let [a, b, c] = [1, 2];"),
+            "Cannot destructure 3 elements from '[2]i32', which has only 2"
+        );
+        assert_eq!(
+            error("let { x } = 1;"),
+            "Cannot destructure 'i32' with '{ }', which is not a struct"
+        );
+        assert_eq!(
+            error("let { x, y } = origin(); let b: bool = y;"),
+            "Expected 'bool' but got 'i32'"
+        );
+
+        let mut warnings = vec![];
+        let (mut ast, _) = analyze(
+            &source("let p = origin(); let { x, y } = p; let [a, b] = [x, y]; a + b;"),
+            &CompileOptions::default(),
+            &mut Timings::default(),
+            &mut warnings,
+        )
+        .unwrap();
+        lowering::destructure::expand_destructuring(&mut ast);
+
+        let parser::GlobalStmt::Function { body, .. } = &ast[2].node else {
+            panic!("expected a function");
+        };
+        let declared: Vec<String> = body
+            .iter()
+            .filter_map(|stmt| match &stmt.node {
+                parser::Stmt::Variable {
+                    name,
+                    value: Some(value),
+                    ..
+                } => Some(match &value.node {
+                    parser::Expr::MemAccess { expr, member } => {
+                        format!("{name} = {:?}.{member}", expr.node)
+                    }
+                    parser::Expr::ArrIndex { arr, idx } => {
+                        format!("{name} = {:?}[{:?}]", arr.node, idx.node)
+                    }
+                    _ => name.clone(),
+                }),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            declared,
+            [
+                "p",
+                "x = Ident(\"p\").x",
+                "y = Ident(\"p\").y",
+                "__destructured0",
+                "a = Ident(\"__destructured0\")[Int(0)]",
+                "b = Ident(\"__destructured0\")[Int(1)]",
+            ]
+        );
+    }
+
+    #[test]
+    fn aggregate_constants() {
+        let analyze_consts = |consts: &str| {
+            let source = format!(
+                "struct Point {{ x: i32; y: i32; }}\n{consts}\nfunc main(): void {{}}"
+            );
+            let mut warnings = vec![];
+            analyze(
+                &source,
+                &CompileOptions::default(),
+                &mut Timings::default(),
+                &mut warnings,
+            )
+            .map(|(ast, _)| ast)
+        };
+
+        let ast = analyze_consts(
+            "const TABLE: [3]u8 = [1, 2, 3];\n\
+             const ORIGIN: Point = Point { x = 0, y = -1 };\n\
+             const LAST: u8 = TABLE[2];\n\
+             const Y: i32 = ORIGIN.y;",
+        )
+        .unwrap();
+
+        let mut evaluator = semantic_analyzer::consteval::ConstEvaluator::new(&ast);
+        let lowered: Vec<String> = ast
+            .iter()
+            .filter_map(|stmt| match &stmt.node {
+                parser::GlobalStmt::Constant {
+                    name,
+                    t: Some(t),
+                    value,
+                    ..
+                } => {
+                    let value = evaluator.eval(value).unwrap().unwrap();
+                    Some(
+                        lowering::constants::lower_constant(name, &t.node, &value)
+                            .unwrap()
+                            .to_string(),
+                    )
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            lowered,
+            [
+                "static const uint8_t TABLE[3] = {1, 2, 3}",
+                "static const Point ORIGIN = {.x=0, .y=-1}",
+                "static const uint8_t LAST = 3",
+                "static const int32_t Y = -1",
+            ]
+        );
+
+        let errors = analyze_consts("const TABLE: [2]u8 = [1, 256];").unwrap_err();
+        assert_eq!(errors[0].1, "Expected '[2]u8' but got '[2]i32'");
+
+        let errors = analyze_consts(
+            "extern func f(): i32;\nconst ORIGIN: Point = Point { x = f(), y = 0 };",
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Non-constant global initializer: 'ORIGIN' cannot be initialized by calling a function"
+        );
+
+        let errors =
+            analyze_consts("const TABLE: [3]u8 = [1, 2, 3];\nconst C: u8 = TABLE[3];").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Index 3 is out of bounds for the constant array 'TABLE' of length 3"
+        );
+
+        let errors = analyze_consts("const ORIGIN: Point = Point { x = 0, z = 0 };").unwrap_err();
+        assert_eq!(errors[0].1, "'Point' has no field 'z', did you mean 'y'?");
+    }
+
+    #[test]
+    fn struct_init_fields() {
+        let check_init = |init: &str| {
+            let source = format!(
+                "struct Person {{ name: str; age: i32; height: double; }}\n\
+                 func main(): void {{ let p = Person {{ {init} }}; }}"
+            );
+            check(&source, &CompileOptions::default()).1
+        };
+
+        assert!(check_init("name = \"x\", age = 1, height = 1.8").is_ok());
+
+        let errors = check_init("nmae = \"x\", age = 1, height = 1.8").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].1,
+            "'Person' has no field 'nmae', did you mean 'name'?"
+        );
+
+        let errors = check_init("name = \"x\", id = 1").unwrap_err();
+        assert_eq!(errors[0].1, "'Person' has no field 'id'");
+
+        let errors = check_init("name = \"x\", age = 1, age = 2, height = 1.8").unwrap_err();
+        assert_eq!(errors[0].1, "The field 'age' is initialized more than once");
+
+        let errors = check_init("age = 1").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "The fields 'name' and 'height' of 'Person' are not initialized"
+        );
+        let errors = check_init("name = \"x\", age = 1").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "The field 'height' of 'Person' is not initialized"
+        );
+    }
+
+    #[test]
+    fn constant_folding() {
+        let source = "const SIZE: i32 = 4;\nconst NAME: str = \"castella\";\n\
+                      func twice(n: i32): i32 { return n * 2; }\n\
+                      func f(): i32 { return SIZE * 2 + twice(SIZE); }\n\
+                      func g(): char { return NAME[1]; }\n\
+                      func h(SIZE: i32): i32 { return SIZE + 1; }\n\
+                      func main(): void {}";
+        let (mut ast, _) = analyze(
+            source,
+            &CompileOptions::default(),
+            &mut Timings::default(),
+            &mut vec![],
+        )
+        .unwrap();
+        lowering::fold::fold_constants(&mut ast);
+
+        let returned = |func: &str| {
+            ast.iter()
+                .find_map(|stmt| match &stmt.node {
+                    parser::GlobalStmt::Function { name, body, .. } if name == func => {
+                        match &body[0].node {
+                            parser::Stmt::Return { value: Some(value) } => Some(&value.node),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                })
+                .unwrap()
+        };
+
+        let parser::Expr::Binary { left, right, .. } = returned("f") else {
+            panic!("expected a binary expression");
+        };
+        assert!(matches!(left.node, parser::Expr::Int(8)));
+        assert!(matches!(
+            &right.node,
+            parser::Expr::FnCall { args, .. } if matches!(args[0].node, parser::Expr::Int(4))
+        ));
+        assert!(matches!(returned("g"), parser::Expr::Char(b'a')));
+
+        // A parameter shadowing a constant isn't replaced by its value
+        let parser::Expr::Binary { left, .. } = returned("h") else {
+            panic!("expected a binary expression");
+        };
+        assert!(matches!(&left.node, parser::Expr::Ident(name) if name == "SIZE"));
+
+        let dump = dump_ast(source, &CompileOptions::default(), AstStage::Parse).unwrap();
+        assert!(dump.contains("\"SIZE\""));
+    }
+
+    #[test]
+    fn inspection() {
+        assert_eq!(
+            dump_tokens("let x = 1 # 2;"),
+            "1:1 'let'\n1:5 identifier 'x'\n1:7 '='\n1:9 integer literal '1'\n\
+             1:11 error: Unexpected character '#' (U+0023 NUMBER SIGN)\n\
+             1:13 integer literal '2'\n1:14 ';'\n"
+        );
+
+        let source = "func f(a: i32): bool {\n\
+                      \tif (not (a > 1) and true) { return a == 2; }\n\
+                      \treturn false;\n}";
+        let tree = print_ast(source, &CompileOptions::default(), AstStage::Parse).unwrap();
+        let expected = "\
+func f(a: i32): bool
+  if
+    Binary and
+      Unary not
+        Parenthesized
+          Binary >
+            Ident a
+            Int 1
+      Bool true
+    then
+      return
+        Binary ==
+          Ident a
+          Int 2
+  return
+    Bool false
+";
+        assert_eq!(tree, expected);
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        let check_main = |body: &str| {
+            let source = format!(
+                "const FLAGS: i32 = 1 << 3 | 1;\n\
+                 func main(): void {{ let n: i32 = 6; let x = 1.5; {body} }}"
+            );
+            check(&source, &CompileOptions::default()).1
+        };
+
+        assert!(check_main("n &= 3; n |= 1 << 4; n ^= n >> 1; n <<= 1; n >>= 2;").is_ok());
+        assert!(check_main("let b = (n & 1) == 0;").is_ok());
+
+        let errors = check_main("n <<= x;").unwrap_err();
+        assert_eq!(errors[0].1, "Cannot apply '<<=' to 'i32' and 'double'");
+
+        let errors = check_main("let y = x | 1;").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Expected integer operands for '|' but got 'double' and 'i32'"
+        );
+
+        let errors = check_main("let b = n & 1 == 0;").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Expected integer operands for '&' but got 'i32' and 'bool', '&' binds looser than \
+             comparisons like in C, so it needs parentheses"
+        );
+
+        let errors = check_main("switch (n) { case 1 << 64: default: }").unwrap_err();
+        assert_eq!(errors[0].1, "Cannot shift by 64 in a constant expression");
+
+        let c = compile_to_c_string(
+            "const FLAGS: i32 = 1 << 3 | 1;\nfunc main(): i32 { return FLAGS & 8; }",
+            &CompileOptions::default(),
+        )
+        .unwrap();
+        assert!(c.contains("static const int32_t FLAGS = 9;"));
+    }
+
+    #[test]
+    fn checked_casts() {
+        let source = |body: &str| {
+            format!(
+                "func main(): i32 {{ let n: i32 = 200; let d = 1.5; let p: ^i32 = &n; {body} \
+                 return 0; }}"
+            )
+        };
+        let check_main = |body: &str| check(&source(body), &CompileOptions::default());
+
+        assert!(check_main("let a = cast(i64, n); let b = cast(u8, d); let c = cast(u64, p);")
+            .1
+            .is_ok());
+
+        let errors = check_main("let b = cast(bool, n);").1.unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Cannot cast 'i32' to 'bool', compare it with 0 instead"
+        );
+        let errors = check_main("let q = cast(^u8, d);").1.unwrap_err();
+        assert_eq!(errors[0].1, "Cannot cast 'double' to '^u8'");
+
+        let (warnings, _) = check_main("let b = cast(u8, 256);");
+        assert!(
+            warnings
+                .iter()
+                .any(|(_, w)| w == "256 doesn't fit into 'u8', so the cast changes it")
+        );
+
+        let options = CompileOptions {
+            checked_casts: true,
+            ..Default::default()
+        };
+        let c = compile_to_c_string(
+            &source("let a = cast(u8, n); let b = cast(i64, n); let c = cast(u8, 255);"),
+            &options,
+        )
+        .unwrap();
+        assert!(c.contains(
+            "uint8_t a = (uint8_t)(castella_check_signed_cast(n, 0, 255, \"u8\", \"main.clla\", \
+             1));"
+        ));
+        assert!(c.contains("int64_t b = (int64_t)(n);"));
+        assert!(c.contains("uint8_t c = (uint8_t)(255);"));
+
+        // Without the option, the casts are plain C casts
+        let c = compile_to_c_string(&source("let a = cast(u8, n);"), &Default::default()).unwrap();
+        assert!(c.contains("uint8_t a = (uint8_t)(n);"));
+        assert!(!c.contains("castella_check"));
+    }
+
+    #[test]
+    fn error_recovery() {
+        let source = "\
+func a(): i32 {
+    let x = \"s\" + 1;
+    let y = x * 2;
+    let z: i32 = true;
+    if (z) { let q: bool = 1; }
+    switch (z) { case \"a\": let s: i32 = \"t\"; }
+    return z;
+}
+let g = 1 + \"x\";
+func b(): i32 { let (m, n) = 3; return g + m; }";
+        let errors = check(source, &CompileOptions::default()).1.unwrap_err();
+        let errors: Vec<&str> = errors.iter().map(|(_, msg)| msg.as_str()).collect();
+
+        // Uses of 'x', 'g', and 'm', whose declarations failed, aren't reported
+        assert_eq!(
+            errors,
+            [
+                "Cannot apply '+' to 'str' and 'i32'",
+                "Expected 'i32' but got 'bool'",
+                "If condition must be boolean but got i32",
+                "Expected 'bool' but got 'i32'",
+                "Expected a case of type 'i32' but got 'str'",
+                "Expected 'i32' but got 'str'",
+                "Cannot apply '+' to 'i32' and 'str'",
+                "Cannot destructure 'i32' with '( )', which is only for multiple return values",
+            ]
+        );
+    }
+
+    #[test]
+    fn sanitizers() {
+        let source = "func main(): i32 { let xs = make [^]i32; append(&xs, 1); return xs[0]; }";
+        let options = CompileOptions {
+            sanitizers: Sanitizers::from_names("undefined,address").unwrap(),
+            ..Default::default()
+        };
+        let c = compile_to_c_string(source, &options).unwrap();
+        assert!(c.starts_with(
+            "#define CASTELLA_SANITIZE_ADDRESS\n#define CASTELLA_SANITIZE_UNDEFINED\n// Runtime"
+        ));
+
+        // Programs that don't use the runtime don't need to know about the sanitizers
+        let c = compile_to_c_string("func main(): i32 { return 0; }", &options).unwrap();
+        assert!(!c.contains("CASTELLA_SANITIZE"));
+
+        let c = compile_to_c_string(source, &Default::default()).unwrap();
+        assert!(!c.contains("#define CASTELLA_SANITIZE"));
+
+        assert_eq!(
+            Sanitizers::from_names("address,thread"),
+            Err("Unknown sanitizer 'thread', expected 'address' or 'undefined'".to_string())
+        );
+    }
+
+    #[test]
+    fn scopes() {
+        let source = "\
+func main(): i32 {
+    let x: i32 = 1;
+    {
+        let x: i32 = x + 1;
+        if (x > 1) { let t: i32 = x; x = t * 10; }
+        else { let t: i32 = 0; x = t; }
+    }
+    return x;
+}";
+        let (warnings, res) = check(source, &CompileOptions::default());
+        assert!(res.is_ok());
+        let warnings: Vec<&str> = warnings.iter().map(|(_, msg)| msg.as_str()).collect();
+        assert_eq!(
+            warnings,
+            [
+                "'x' shadows a variable of the same name",
+                "note: The shadowed 'x' is declared here",
+            ]
+        );
+
+        // The inner 'x' is initialized from the outer one, so it can't be emitted as 'x' too
+        let c = compile_to_c_string(source, &CompileOptions::default()).unwrap();
+        assert!(c.contains("int32_t __x_1 = x + 1;"));
+        assert!(c.contains("__x_1 = t * 10;"));
+        assert!(c.contains("return x;"));
+
+        let source = "\
+func main(): i32 {
+    y = 2;
+    let y: i32 = 1;
+    if (true) { let z: i32 = y; }
+    return z;
+}";
+        let errors = check(source, &CompileOptions::default()).1.unwrap_err();
+        let errors: Vec<&str> = errors.iter().map(|(_, msg)| msg.as_str()).collect();
+        assert_eq!(
+            errors,
+            [
+                "'y' is used before it is declared",
+                "note: 'y' is declared here",
+                "'z' is used after its block has ended",
+                "note: 'z' is declared here",
+            ]
+        );
+    }
+
+    #[test]
+    fn results() {
+        let source = "\
+enum Error { Empty; Odd; }
+func half(n: i32): Result<i32, Error> {
+    if (n % 2 != 0) { return err(Error->Odd); }
+    return ok(n / 2);
+}
+func quarter(n: i32): Result<i32, Error> {
+    let h = try half(n);
+    return ok(try half(h) + 0);
+}
+func check(n: i32): Result<void, Error> {
+    try quarter(n);
+    return ok();
+}
+func main(): i32 {
+    let r = quarter(8);
+    let v: Result<void, Error> = check(6);
+    if (r.is_ok and r.value == 2 and not v.is_ok and v.error == Error->Odd) { return 0; }
+    return 1;
+}";
+        let c = compile_to_c_string(source, &CompileOptions::default()).unwrap();
+        assert!(c.contains("typedef struct Result_i32_Error Result_i32_Error;"));
+        assert!(c.contains("struct Result_void_Error {\n  bool is_ok;\n  Error error;\n};"));
+        assert!(c.contains("return (Result_i32_Error){.is_ok=false, .error=Error_Odd};"));
+        assert!(c.contains("Result_i32_Error __try0 = half(n);"));
+        assert!(c.contains("int32_t h = __try0.value;"));
+        assert!(c.contains("return (Result_void_Error){.is_ok=false, .error=__try0.error};"));
+
+        let check_body = |ret: &str, body: &str| {
+            let source = format!(
+                "func f(): Result<i32, bool> {{ return ok(1); }}\n\
+                 func g(): {ret} {{ {body} }}"
+            );
+            let errors = check(&source, &CompileOptions::default()).1.unwrap_err();
+            errors[0].1.clone()
+        };
+
+        assert_eq!(
+            check_body("i32", "return try f();"),
+            "'try' can only be used in a function returning a 'Result', not 'i32'"
+        );
+        assert_eq!(
+            check_body("Result<i32, i32>", "return ok(try f());"),
+            "'try' cannot return the error 'bool' from a function whose error type is 'i32'"
+        );
+        assert_eq!(
+            check_body("Result<i32, bool>", "return ok(try 1);"),
+            "'try' expects a 'Result' but got 'i32'"
+        );
+        assert_eq!(
+            check_body("Result<i32, bool>", "while (try f() > 0) {} return ok(1);"),
+            "'try' cannot be used in a loop condition"
+        );
+        assert_eq!(
+            check_body("Result<i32, bool>", "let b = false and try f() > 0; return ok(1);"),
+            "'try' cannot be used in an operand that may not be evaluated"
+        );
+        assert_eq!(
+            check_body("Result<i32, bool>", "defer { try f(); } return ok(1);"),
+            "'try' cannot return from a deferred block"
+        );
+        assert_eq!(
+            check_body("Result<void, bool>", "let r = err(true); return ok(1);"),
+            "'err' needs to know the 'Result' it creates, use it where one is expected, like \
+             'let r: Result<i32, Error> = err(x);'"
+        );
+        assert_eq!(
+            check_body("Result<void, bool>", "return ok(1);"),
+            "'ok' expects 0 argument(s) but got 1"
+        );
+    }
+
+    #[test]
+    fn unused() {
+        let source = "\
+import \"util\";
+struct Used { n: i32; }
+func count(n: i32): i32 {
+    if (n == 0) { return 0; }
+    return count(n - 1);
+}
+func build(u: Used, _spare: i32): Used {
+    let total = 0;
+    total += 1;
+    let read = 3;
+    { let read = read + 1; }
+    return u;
+}
+func main(): i32 { return build(Used { n = 1 }, 2).n; }";
+        let options = CompileOptions {
+            loader: ModuleLoader::new("/nonexistent").source("util", "func f(): void {}"),
+            ..Default::default()
+        };
+        let (warnings, res) = check(source, &options);
+        assert!(res.is_ok());
+        let warnings: Vec<&str> = warnings
+            .iter()
+            .map(|(_, msg)| msg.as_str())
+            .filter(|msg| !msg.contains("shadow"))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                "'total' is assigned but never read",
+                "help: Name it '_total' if it is unused on purpose",
+                "'read' is not used",
+                "help: Name it '_read' if it is unused on purpose",
+                "'util' is imported but not used",
+                "'count' is not used",
+            ]
+        );
+    }
+
+    #[test]
+    fn crate_types() {
+        let source = "struct Point { x: i32; y: i32; }\n\
+                      let origin: i32 = 0;\n\
+                      func area(p: Point): i32 { return p.x * p.y; }";
+        let errors = check(source, &CompileOptions::default()).1.unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "There is no function 'main' to start the program at"
+        );
+
+        let options = CompileOptions {
+            file: "geo/shapes.clla".to_string(),
+            crate_type: CrateType::Lib,
+            ..Default::default()
+        };
+        let (warnings, library) = compile_library(source, &options, &mut Timings::default());
+        assert!(warnings.is_empty(), "{warnings:?}");
+        let library = library.unwrap();
+        assert!(library.c.contains("int32_t area(Point p) {"));
+        assert!(
+            library
+                .header
+                .starts_with("#ifndef SHAPES_H\n#define SHAPES_H\n")
+        );
+        assert!(library.header.contains("int32_t area(Point p);"));
+        assert!(library.header.contains("extern int32_t origin;"));
+        assert!(library.header.ends_with("#endif\n"));
+
+        let options = CompileOptions {
+            entry: "start".to_string(),
+            ..Default::default()
+        };
+        let c = compile_to_c_string("func start(): void {}", &options).unwrap();
+        assert!(c.contains("int main(void) {\n  start();\n  return 0;\n}"));
+
+        let errors = check(
+            "func start(n: i32): void {}\nfunc main(): void {}",
+            &options,
+        )
+        .1
+        .unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "The entry point 'start' cannot have parameters"
+        );
+        assert_eq!(
+            errors[1].1,
+            "'main' is generated to call the entry point 'start'"
+        );
+    }
+
+    #[test]
+    fn hovering() {
+        use parser::Type;
+
+        let source = "func area(w: i32, h: i32): i32 { return w * h; }\n\
+                      func main(): void {\n  let a = area(2, 3);\n  let b: bool = a;\n}";
+        let at = |needle: &str| source.find(needle).unwrap();
+        let hover = |offset| hover(source, &CompileOptions::default(), offset).unwrap();
+
+        // A parameter, declared at its type
+        let w = at("w * h");
+        let h = hover(w);
+        assert_eq!(h.span, w..w + 1);
+        assert_eq!(h.t, Some(Type::Int32));
+        assert_eq!(h.declaration, Some(at("i32")..at("i32") + 3));
+
+        // A function called by name is hovered over as the call, so its type is what it returns
+        let call = at("area(2");
+        let h = hover(call + 1);
+        assert_eq!(h.span, call..call + 4);
+        assert_eq!(h.t, Some(Type::Int32));
+        assert_eq!(h.declaration, Some(at("area")..at("area") + 4));
+
+        // The local is still typed, although the declaration using it doesn't check, and is declared
+        // at its `let`
+        let h = hover(at("a;"));
+        assert_eq!(h.t, Some(Type::Int32));
+        assert_eq!(h.declaration, Some(at("let a")..at("let a") + 3));
+
+        assert_eq!(super::hover(source, &CompileOptions::default(), 4), None);
+        assert_eq!(super::hover("func (", &CompileOptions::default(), 0), None);
+    }
+
+    #[test]
+    fn line_directives() {
+        let options = CompileOptions {
+            file: "dir/main \"1\".clla".to_string(),
+            line_directives: true,
+            ..Default::default()
+        };
+        let source = "let n: i32 = 2;\n\nfunc main(): i32 {\n  if (n > 1) {\n    return n;\n  }\n  \
+                      return 0;\n}";
+        let c = compile_to_c_string(source, &options).unwrap();
+        let path = "\"dir/main \\\"1\\\".clla\"";
+        assert!(c.contains(&format!("#line 1 {path}\nint32_t n = 2;")));
+        assert!(c.contains(
+            "#line 3\nint32_t main(void) {\n  #line 4\n  if (n > 1) {\n    #line 5\n    \
+             return n;\n  }\n  #line 7\n  return 0;\n}"
+        ));
+
+        let c = compile_to_c_string(source, &CompileOptions::default()).unwrap();
+        assert!(!c.contains("#line"));
+
+        // The generated `main` stands in for the entry point, and a state machine's statements
+        // are still where they are in the source
+        let options = CompileOptions {
+            entry: "start".to_string(),
+            line_directives: true,
+            ..Default::default()
+        };
+        let source = "@statemachine\nfunc count(): i32 {\n  yield 1;\n}\n\nfunc start(): void {}";
+        let c = compile_to_c_string(source, &options).unwrap();
+        assert!(c.contains("#line 6\nint main(void) {"));
+        assert!(c.contains("  #line 3\n"));
+    }
+
+    #[test]
+    fn char_literals() {
+        let check_main = |body: &str| {
+            let source = format!("func main(): void {{ let c: char = 'x'; {body} }}");
+            check(&source, &CompileOptions::default()).1
+        };
+
+        assert!(check_main("let d = c == '\\'' or c >= '0';").is_ok());
+        assert!(check_main("switch (c) { case 'a', '\\n': default: }").is_ok());
+
+        let errors = check_main("let d: char = \"y\";").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Expected 'char' but got 'str', use 'y' for a character literal"
+        );
+
+        let errors = check_main("let s: str = 'y';").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Expected 'str' but got 'char', use \"y\" for a string literal"
+        );
+
+        let errors = check_main("switch (c) { case '\\t', '\\x09': default: }").unwrap_err();
+        assert_eq!(errors[0].1, "'\\t' is already handled by an earlier case");
+
+        let c = compile_to_c_string(
+            "const Q: char = '\\'';\nfunc main(): i32 { let c = '\\x1b'; return 0; }",
+            &CompileOptions::default(),
+        )
+        .unwrap();
+        assert!(c.contains("static const char Q = '\\'';"));
+        assert!(c.contains("char c = '\\033';"));
+    }
+
+    #[test]
+    fn float_equality() {
+        let check_ret = |expr: &str| {
+            let source = format!(
+                "func f(x: double, _n: i32): bool {{ return {expr}; }}\nfunc main(): void {{ f(1.0, 1); }}"
+            );
+            check(&source, &CompileOptions::default())
+        };
+
+        let (warnings, res) = check_ret("x == 0.5");
+        assert!(res.is_ok());
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].1.contains("use 'approx_eq(a, b, eps)'"));
+
+        let (warnings, res) = check_ret("approx_eq(x, 0.5, 0.001)");
+        assert!(res.is_ok());
+        assert!(warnings.is_empty());
+
+        let (_, res) = check_ret("approx_eq(x, _n == 1, 0.001)");
+        assert_eq!(
+            res.unwrap_err()[0].1,
+            "'approx_eq' expects numbers but got 'bool'"
+        );
+    }
+
+    #[test]
+    fn conversions() {
+        let source = "func parse(s: str): Result<i32, str> { return ok(try parse_i32(s) + 1); }\n\
+                      func main(): void {\n  let n: u64 = 7;\n  let s = int_to_str(n);\n  \
+                      free s;\n  parse(int_to_str(-1));\n}";
+        let c = compile_to_c_string(source, &CompileOptions::default()).unwrap();
+        assert!(c.contains("static Result_i32_str castella_parse_i32(const char* s) {"));
+        assert!(c.contains("castella_parse_int(s, -2147483648, 2147483647, &value);"));
+        assert!(c.contains("Result_i32_str __try0 = castella_parse_i32(s);"));
+        assert!(c.contains("const char* s = castella_uint_to_str(n, \"main.clla\", 4);"));
+        assert!(c.contains("free((void*)(s));"));
+        assert!(c.contains("parse(castella_int_to_str(-1, \"main.clla\", 6));"));
+
+        // The helper is only generated for programs that parse
+        let c = compile_to_c_string(
+            "func main(): void { let r: Result<i32, str> = err(\"no\"); }",
+            &CompileOptions::default(),
+        )
+        .unwrap();
+        assert!(!c.contains("castella_"));
+
+        let check_main = |body: &str| {
+            let source = format!("func main(): void {{ {body} }}");
+            check(&source, &CompileOptions::default()).1
+        };
+        let errors = check_main("int_to_str(true);").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "'int_to_str' expects an integer but got 'bool'"
+        );
+        let errors = check_main("let n = parse_i32(1);").unwrap_err();
+        assert_eq!(errors[0].1, "'parse_i32' expects a string but got 'i32'");
+        let errors = check_main("let n: i32 = parse_i32(\"1\");").unwrap_err();
+        assert_eq!(errors[0].1, "Expected 'i32' but got 'Result<i32, str>'");
+    }
+
+    #[test]
+    fn pointer_arithmetic() {
+        let check_ret = |ret: &str, expr: &str| {
+            let source = format!(
+                "func f(p: ^i32, q: ^i32, b: ^u8, v: ^void, n: u64): {ret} {{ return {expr}; }}\n\
+                 func main(): void {{}}"
+            );
+            check(&source, &CompileOptions::default()).1
+        };
+
+        assert!(check_ret("^i32", "p + n").is_ok());
+        assert!(check_ret("^i32", "n + p").is_ok());
+        assert!(check_ret("^i32", "p - 1").is_ok());
+        assert!(check_ret("i64", "p - q").is_ok());
+        assert!(check_ret("bool", "p < q").is_ok());
+
+        let errors = check_ret("i64", "p - b").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Cannot subtract '^u8' from '^i32', pointers can only be subtracted if they point to \
+             the same type"
+        );
+
+        let errors = check_ret("bool", "p == b").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Cannot compare '^i32' with '^u8', pointers can only be compared if they point to the \
+             same type"
+        );
+
+        let errors = check_ret("^i32", "p + q").unwrap_err();
+        assert_eq!(errors[0].1, "Cannot apply '+' to '^i32' and '^i32'");
+
+        let errors = check_ret("^i32", "n - p").unwrap_err();
+        assert_eq!(errors[0].1, "Cannot apply '-' to 'u64' and '^i32'");
+
+        let errors = check_ret("^void", "v + 1").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Cannot do pointer arithmetic on '^void' because 'void' has no size"
+        );
+    }
+
+    #[test]
+    fn lvalues() {
+        let check_stmt = |stmt: &str| {
+            let source = format!(
+                "struct Point {{ x: i32; y: i32; }}\n\
+                 func buf(): ^i32 {{ return new i32; }}\n\
+                 func origin(): Point {{ return Point {{ x = 0, y = 0 }}; }}\n\
+                 func main(): void {{ let p = new Point; let n = 0; {stmt} }}"
+            );
+            check(&source, &CompileOptions::default()).1
+        };
+
+        assert!(check_stmt("n = 1;").is_ok());
+        assert!(check_stmt("(^p).x = 1;").is_ok());
+        assert!(check_stmt("^buf() = 1;").is_ok());
+        assert!(check_stmt("buf()[1] += 2;").is_ok());
+
+        let expected = "Expected a variable, field, element, or dereferenced pointer to modify";
+        for stmt in ["1 = n;", "buf() = p;", "origin().x = 1;", "(n + 1) = 2;"] {
+            let errors = check_stmt(stmt).unwrap_err();
+            assert_eq!(errors[0].1, expected, "{stmt}");
+        }
+
+        let errors = check_stmt("let q = new Shape;").unwrap_err();
+        assert_eq!(errors[0].1, "'Shape' is not declared");
+        let errors = check_stmt("let v = new void;").unwrap_err();
+        assert_eq!(errors[0].1, "'new' cannot allocate 'void'");
+    }
+
+    #[test]
+    fn array_copies() {
+        let check_stmt = |stmt: &str| {
+            let source = format!(
+                "struct P {{ xs: [3]i32; }}\n\
+                 func main(): i32 {{ let a: [3]i32 = [1, 2, 3]; let b: [3]i32 = [0, 0, 0];\n\
+                 {stmt} return a[0] + b[0]; }}"
+            );
+            compile_to_c_string(&source, &CompileOptions::default())
+        };
+
+        // Arrays are only initialized from literals and copied element by element
+        let c = check_stmt("b[0] = a[1]; let p = P { xs = [4, 5, 6] }; b[1] = p.xs[2];").unwrap();
+        assert_c_compiles(&c, "array_copies");
+
+        let copy =
+            "Cannot copy the array '[3]i32', copy its elements one by one or with 'mem::copy'";
+        let assign = "Cannot assign to the array '[3]i32', assign its elements one by one or copy \
+                      them with 'mem::copy'";
+        for (stmt, expected) in [
+            ("let c: [3]i32 = a;", copy),
+            ("let c = b;", copy),
+            ("let p = P { xs = a };", copy),
+            ("b = a;", assign),
+            ("b = [4, 5, 6];", assign),
+        ] {
+            let diagnostics = check_stmt(stmt).unwrap_err();
+            assert_eq!(diagnostics.errors[0].1, expected, "{stmt}");
+        }
+    }
+
+    #[test]
+    fn codegen() {
+        let source = "extern func printf(fmt: str, x: i32): i32;\n\
+                      struct Point { x: i32; y: i32; }\n\
+                      let counter: i32 = 0;\n\
+                      func sum(p: Point): i32 {\n\
+                          defer { counter += 1; }\n\
+                          let { x, y } = p;\n\
+                          return x + y;\n\
+                      }\n\
+                      func main(): void { printf(\"%d\\n\", sum(Point { x = 1, y = 2 })); }";
+        let c = compile_to_c_string(source, &CompileOptions::default()).unwrap();
+
+        assert!(c.contains("#include <stdio.h>"));
+        assert!(!c.contains("int32_t printf("));
+        assert!(c.contains("typedef struct Point Point;"));
+        assert!(c.contains("int32_t sum(Point p);"));
+        assert!(c.contains("int32_t x = p.x;"));
+        assert!(c.contains("int32_t __ret = x + y;\n  {\n    counter += 1;\n  }\n  return __ret;"));
+        assert!(c.contains("int main(void) {"));
+        assert!(c.contains("return 0;"));
+        assert!(!c.contains("castella_darray"));
+    }
+}
@@ -0,0 +1,41 @@
+//! Lowers `new T` onto `castella_new` from the C runtime.
+//!
+//! `new T` allocates a zeroed `T` on the heap and yields a `^T` to it, which `free` gives back,
+//! like it does the strings `int_to_str` allocates. Running out of memory traps with the file and
+//! line of the `new` instead of yielding a null pointer, like growing a dynamic array does, so
+//! the result never has to be checked.
+
+use tamago::Expr;
+
+/// `(T*)(castella_new(sizeof(T), "main.clla", 3))`
+pub fn new(lowered: tamago::Type, (file, line): (&str, usize)) -> Expr {
+    let mut ptr = lowered.clone();
+    ptr.pointers.push(vec![]);
+
+    Expr::new_cast(
+        ptr,
+        Expr::new_fn_call_with_name(
+            "castella_new".to_string(),
+            vec![
+                Expr::new_sizeof(lowered),
+                Expr::Str(super::escape_c_str(file)),
+                Expr::UInt(line as u64),
+            ],
+        ),
+    )
+}
+
+/// `(void*)(ptr)`, for freeing a string, which points at `const char`
+pub fn void_ptr(ptr: Expr) -> Expr {
+    Expr::new_cast(
+        tamago::Type::new(tamago::BaseType::Void)
+            .make_pointer()
+            .build(),
+        ptr,
+    )
+}
+
+/// `free(ptr)`
+pub fn free(ptr: Expr) -> Expr {
+    Expr::new_fn_call_with_name("free".to_string(), vec![ptr])
+}
@@ -0,0 +1,103 @@
+//! Lowers `cast(T, value)` to a C cast, which for the casts the type checker marked as possibly
+//! lossy can first check that the value fits into `T` (`--checked-casts`).
+//!
+//! The checks are helpers in the C runtime that trap with the location of the cast, like
+//! `(uint8_t)(castella_check_signed_cast(x, 0, 255, "u8", "main.clla", 3))`. They return the value
+//! unchanged when the C is compiled with `NDEBUG`, so release builds get plain C casts.
+
+use tamago::Expr;
+
+use crate::lowering::int_literal;
+use crate::parser::{CastCheck, Type};
+
+/// `(T)value`, or with `value` wrapped in the runtime's check for `check` if it is given along
+/// with a `location`
+pub fn cast(
+    t: &Type,
+    lowered: tamago::Type,
+    value: Expr,
+    check: Option<CastCheck>,
+    location: Option<(&str, usize)>,
+) -> Expr {
+    let (Some(check), Some((file, line)), Some((min, max))) = (check, location, t.int_range())
+    else {
+        return Expr::new_cast(lowered, value);
+    };
+
+    let (helper, bounds) = match check {
+        // The value is an `int64_t`, so the bounds never have to go beyond it
+        CastCheck::Signed => (
+            "castella_check_signed_cast",
+            vec![
+                int_literal(min.max(i64::MIN.into())),
+                int_literal(max.min(i64::MAX.into())),
+            ],
+        ),
+        CastCheck::Unsigned => (
+            "castella_check_unsigned_cast",
+            vec![int_literal(max.min(i64::MAX.into()))],
+        ),
+        // The bounds are exclusive since the fraction is truncated, and written like `256.0` so
+        // that C reads them as doubles
+        CastCheck::Float => (
+            "castella_check_float_cast",
+            vec![
+                Expr::Raw(format!("{:?}", (min - 1) as f64)),
+                Expr::Raw(format!("{:?}", (max + 1) as f64)),
+            ],
+        ),
+    };
+
+    let mut args = vec![value];
+    args.extend(bounds);
+    args.extend([
+        Expr::Str(super::escape_c_str(&t.to_string())),
+        Expr::Str(super::escape_c_str(file)),
+        Expr::UInt(line as u64),
+    ]);
+
+    Expr::new_cast(
+        lowered,
+        Expr::new_fn_call_with_name(helper.to_string(), args),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_casts() {
+        let u8_t = || tamago::Type::new(tamago::BaseType::UInt8).build();
+        let x = || Expr::new_ident_with_str("x");
+        let location = Some(("main.clla", 3));
+
+        assert_eq!(
+            cast(&Type::UInt8, u8_t(), x(), Some(CastCheck::Signed), None).to_string(),
+            "(uint8_t)(x)"
+        );
+        assert_eq!(
+            cast(&Type::UInt8, u8_t(), x(), None, location).to_string(),
+            "(uint8_t)(x)"
+        );
+        assert_eq!(
+            cast(&Type::UInt8, u8_t(), x(), Some(CastCheck::Signed), location).to_string(),
+            "(uint8_t)(castella_check_signed_cast(x, 0, 255, \"u8\", \"main.clla\", 3))"
+        );
+        assert_eq!(
+            cast(
+                &Type::UInt8,
+                u8_t(),
+                x(),
+                Some(CastCheck::Unsigned),
+                location
+            )
+            .to_string(),
+            "(uint8_t)(castella_check_unsigned_cast(x, 255, \"u8\", \"main.clla\", 3))"
+        );
+        assert_eq!(
+            cast(&Type::UInt8, u8_t(), x(), Some(CastCheck::Float), location).to_string(),
+            "(uint8_t)(castella_check_float_cast(x, -1.0, 256.0, \"u8\", \"main.clla\", 3))"
+        );
+    }
+}
@@ -0,0 +1,49 @@
+//! Lowers global constants to `static const` data
+
+use tamago::{Expr, Variable, VariableBuilder};
+
+use super::{escape_c_str, lower_type, make_const};
+use crate::parser::Type;
+use crate::semantic_analyzer::consteval::Value;
+
+/// Lowers the global constant `name` of type `t` with the value computed by the constant
+/// evaluator, e.g. `static const int32_t TABLE[3] = {1, 2, 3}`
+pub fn lower_constant(name: &str, t: &Type, value: &Value) -> Result<Variable, String> {
+    let mut lowered = lower_type(t)?;
+    make_const(&mut lowered);
+
+    Ok(VariableBuilder::new_with_str(name, lowered)
+        .value(lower_value(value)?)
+        .make_static()
+        .build())
+}
+
+fn lower_value(value: &Value) -> Result<Expr, String> {
+    Ok(match value {
+        Value::Int(i) => match i64::try_from(*i) {
+            Ok(i) => Expr::Int(i),
+            Err(_) => {
+                Expr::UInt(u64::try_from(*i).map_err(|_| format!("{i} does not fit in 64 bits"))?)
+            }
+        },
+        Value::Double(d) if d.is_finite() => Expr::Double(*d),
+        Value::Double(d) => return Err(format!("{d} cannot be written as a C literal")),
+        Value::Bool(b) => Expr::Bool(*b),
+        Value::Char(c) => Expr::Char(*c as char),
+        Value::Str(s) => Expr::Str(escape_c_str(s)),
+        Value::Array(elems) => Expr::new_init_arr_in_order(
+            elems
+                .iter()
+                .map(lower_value)
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        Value::Struct(fields) => {
+            let names = fields.iter().map(|(name, _)| name.clone()).collect();
+            let values = fields
+                .iter()
+                .map(|(_, value)| lower_value(value))
+                .collect::<Result<_, _>>()?;
+            Expr::new_init_struct_designated(names, values)
+        }
+    })
+}
@@ -0,0 +1,108 @@
+//! Lowers the conversions between numbers and strings onto helpers in the C runtime.
+//!
+//! - `int_to_str(x)` formats `x` with `snprintf` into a string on the heap, which `free` gives
+//!   back, like `castella_int_to_str(x, "main.clla", 3)`, or `castella_uint_to_str` for unsigned
+//!   integers so that large ones aren't read as negative
+//! - `parse_i32(s)` parses `s` with `strtoll`, and is a `Result<i32, str>` with why `s` isn't an
+//!   `i32` as the error, `"not a number"` or `"out of range"`
+//!
+//! The runtime doesn't know the `Result` structs of the program, so `parse_i32` calls a helper
+//! generated along with them, which turns what `castella_parse_int` finds into a `Result_i32_str`.
+
+use tamago::{BaseType, BinOp, Block, Expr, Function, If, Parameter, Statement, Variable};
+
+use crate::lowering::{escape_c_str, lower_type, results};
+use crate::parser::Type;
+
+/// The name of the helper `parse_i32` calls
+pub const PARSE_I32: &str = "castella_parse_i32";
+
+/// The type `parse_i32` returns
+pub fn parse_i32_type() -> Type {
+    Type::Result(Box::new(Type::Int32), Box::new(Type::Str))
+}
+
+/// `castella_int_to_str(value, "main.clla", 3)`, or `castella_uint_to_str` if `unsigned`
+pub fn int_to_str(value: Expr, unsigned: bool, (file, line): (&str, usize)) -> Expr {
+    let helper = match unsigned {
+        true => "castella_uint_to_str",
+        false => "castella_int_to_str",
+    };
+    Expr::new_fn_call_with_name(
+        helper.to_string(),
+        vec![
+            value,
+            Expr::Str(escape_c_str(file)),
+            Expr::UInt(line as u64),
+        ],
+    )
+}
+
+/// `castella_parse_i32(s)`
+pub fn parse_i32(s: Expr) -> Expr {
+    Expr::new_fn_call_with_name(PARSE_I32.to_string(), vec![s])
+}
+
+/// The helper `parse_i32` calls, which is generated once a program uses it:
+///
+/// ```c
+/// static Result_i32_str castella_parse_i32(const char* s) {
+///   int64_t value = 0;
+///   const char* error = castella_parse_int(s, -2147483648, 2147483647, &value);
+///   if (error != NULL) {
+///     return (Result_i32_str){.is_ok=false, .error=error};
+///   }
+///   return (Result_i32_str){.is_ok=true, .value=(int32_t)(value)};
+/// }
+/// ```
+pub fn parse_i32_helper() -> Result<Function, String> {
+    let result_t = lower_type(&parse_i32_type())?;
+    let str_t = lower_type(&Type::Str)?;
+    let ident = |name: &str| Expr::new_ident_with_str(name);
+
+    let parse = Expr::new_fn_call_with_name(
+        "castella_parse_int".to_string(),
+        vec![
+            ident("s"),
+            Expr::Int(i32::MIN.into()),
+            Expr::Int(i32::MAX.into()),
+            Expr::new_unary(ident("value"), tamago::UnaryOp::AddrOf),
+        ],
+    );
+    let failed = If::new(Expr::new_binary(ident("error"), BinOp::NEq, Expr::Null))
+        .statement_to_then(Statement::ret(results::literal(
+            &result_t,
+            false,
+            Some(ident("error")),
+        )))
+        .build();
+    let value = Expr::new_cast(lower_type(&Type::Int32)?, ident("value"));
+
+    let body = Block::new()
+        .statement(Statement::decl(
+            Variable::new(
+                "value".to_string(),
+                tamago::Type::new(BaseType::Int64).build(),
+            )
+            .value(Expr::Int(0))
+            .build(),
+        ))
+        .statement(Statement::decl(
+            Variable::new("error".to_string(), str_t.clone())
+                .value(parse)
+                .build(),
+        ))
+        .statement(Statement::If(failed))
+        .statement(Statement::ret(results::literal(
+            &result_t,
+            true,
+            Some(value),
+        )))
+        .build();
+
+    Ok(Function::new(PARSE_I32.to_string(), result_t)
+        .make_static()
+        .param(Parameter::new("s".to_string(), str_t).build())
+        .body(body)
+        .build())
+}
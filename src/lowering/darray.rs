@@ -0,0 +1,120 @@
+//! Lowers dynamic arrays (`[^]T`) onto `castella_darray` from the C runtime.
+//!
+//! A dynamic array is a `{ data, len, cap }` triple whose buffer grows by doubling, so `append` is
+//! amortized O(1). The operations are:
+//!
+//! - `make [^]T` creates an empty array without allocating
+//! - `append(&arr, x)` pushes `x`, growing the buffer when it is full
+//! - `len(arr)` is the number of elements
+//! - `arr[i]` indexes the buffer directly, or through `castella_bounds_check` with
+//!   `--bounds-checks`, which traps with the file and line of the indexing when `i` is out of
+//!   bounds
+//! - `destroy arr` frees the buffer and leaves the array empty
+
+use tamago::{AssignOp, BaseType, Expr, UnaryOp};
+
+/// The name of the C type dynamic arrays are represented with
+pub const TYPE_NAME: &str = "castella_darray";
+
+/// The C runtime every program using dynamic arrays needs
+pub const RUNTIME_HEADER: &str = include_str!("../../runtime/castella.h");
+
+pub fn lower_type() -> tamago::Type {
+    tamago::Type::new(BaseType::TypeDef(TYPE_NAME.to_string())).build()
+}
+
+/// `(castella_darray){0}`
+pub fn make() -> Expr {
+    Expr::Raw(format!("({TYPE_NAME}){{0}}"))
+}
+
+/// `((T*)(arr.data))[idx]`, or with `idx` wrapped in `castella_bounds_check` if `location` is
+/// given
+pub fn index(arr: Expr, elem: &tamago::Type, idx: Expr, location: Option<(&str, usize)>) -> Expr {
+    let idx = match location {
+        Some((file, line)) => Expr::new_fn_call_with_name(
+            "castella_bounds_check".to_string(),
+            vec![
+                idx,
+                len(arr.clone()),
+                Expr::Str(super::escape_c_str(file)),
+                Expr::UInt(line as u64),
+            ],
+        ),
+        None => idx,
+    };
+
+    Expr::new_arr_index(Expr::new_parenthesized(data(arr, elem)), idx)
+}
+
+/// `*(T*)(castella_darray_push(arr_ptr, sizeof(T))) = value`
+pub fn append(arr_ptr: Expr, elem: &tamago::Type, value: Expr) -> Expr {
+    let slot = Expr::new_fn_call_with_name(
+        "castella_darray_push".to_string(),
+        vec![arr_ptr, Expr::new_sizeof(elem.clone())],
+    );
+
+    Expr::new_assign(
+        Expr::new_unary(Expr::new_cast(pointer_to(elem), slot), UnaryOp::Deref),
+        AssignOp::Assign,
+        value,
+    )
+}
+
+/// `arr.len`
+pub fn len(arr: Expr) -> Expr {
+    Expr::new_mem_access_with_str(arr, "len")
+}
+
+/// `castella_darray_free(&arr)`
+pub fn destroy(arr: Expr) -> Expr {
+    Expr::new_fn_call_with_name(
+        "castella_darray_free".to_string(),
+        vec![Expr::new_unary(arr, UnaryOp::AddrOf)],
+    )
+}
+
+/// `(T*)(arr.data)`
+fn data(arr: Expr, elem: &tamago::Type) -> Expr {
+    Expr::new_cast(
+        pointer_to(elem),
+        Expr::new_mem_access_with_str(arr, "data"),
+    )
+}
+
+fn pointer_to(elem: &tamago::Type) -> tamago::Type {
+    let mut t = elem.clone();
+    t.pointers.push(vec![]);
+    t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn darray_operations() {
+        let arr = Expr::new_ident_with_str("xs");
+        let elem = tamago::Type::new(BaseType::Int32).build();
+
+        assert_eq!(
+            index(arr.clone(), &elem, Expr::Int(3), None).to_string(),
+            "((int32_t*)(xs.data))[3]"
+        );
+        assert_eq!(
+            index(arr.clone(), &elem, Expr::Int(3), Some(("main.clla", 7))).to_string(),
+            "((int32_t*)(xs.data))[castella_bounds_check(3, xs.len, \"main.clla\", 7)]"
+        );
+        assert_eq!(
+            append(
+                Expr::new_unary(arr.clone(), UnaryOp::AddrOf),
+                &elem,
+                Expr::Int(1)
+            )
+            .to_string(),
+            "*(int32_t*)(castella_darray_push(&xs, sizeof(int32_t))) = 1"
+        );
+        assert_eq!(make().to_string(), "(castella_darray){0}");
+        assert_eq!(destroy(arr).to_string(), "castella_darray_free(&xs)");
+    }
+}
@@ -0,0 +1,145 @@
+//! Expands destructuring `let`s into a variable for each field or element
+//!
+//! `let { x, y } = point;` becomes `let x = point.x; let y = point.y;` and `let [a, b] = arr;`
+//! becomes `let a = arr[0]; let b = arr[1];`. The multiple values returned from a function are
+//! the fields of its result struct by then, so `let (q, r) = divmod(7, 2);` declares
+//! `q = result._0` and `r = result._1`. A value that isn't a variable is stored in a temporary
+//! first so that it is evaluated only once.
+
+use crate::lowering::tuples;
+use crate::parser::*;
+
+/// Expands the destructuring `let`s in a checked AST in place
+pub fn expand_destructuring(ast: &mut [LocatedGlobalStmt]) {
+    for stmt in ast {
+        if let GlobalStmt::Function { body, .. } = &mut stmt.node {
+            let mut temps = 0;
+            expand_body(body, &mut temps);
+        }
+    }
+}
+
+fn expand_body(body: &mut Vec<LocatedStmt>, temps: &mut usize) {
+    for stmt in std::mem::take(body) {
+        let Located { node, span } = stmt;
+
+        match node {
+            Stmt::Destructure {
+                pattern,
+                value,
+                is_const,
+            } => expand(body, span, pattern, value, is_const, temps),
+            mut node => {
+                match &mut node {
+                    Stmt::If { then, other, .. } => {
+                        expand_body(then, temps);
+                        if let Some(other) = other {
+                            expand_body(other, temps);
+                        }
+                    }
+                    Stmt::While { body, .. } | Stmt::Defer { body } | Stmt::Block { body } => {
+                        expand_body(body, temps)
+                    }
+                    Stmt::Switch { cases, default, .. } => {
+                        for case in cases {
+                            expand_body(&mut case.body, temps);
+                        }
+                        if let Some(default) = default {
+                            expand_body(default, temps);
+                        }
+                    }
+                    _ => {}
+                }
+                body.push(Located { node, span });
+            }
+        }
+    }
+}
+
+fn expand(
+    body: &mut Vec<LocatedStmt>,
+    span: Span,
+    pattern: Pattern,
+    value: LocatedExpr,
+    is_const: bool,
+    temps: &mut usize,
+) {
+    let variable = |name: String, value: LocatedExpr| Located {
+        node: Stmt::Variable {
+            name,
+            t: None,
+            value: Some(value),
+            private: false,
+            is_const,
+        },
+        span: span.clone(),
+    };
+
+    let value_span = value.span.clone();
+    let source = match value.node {
+        Expr::Ident(name) => name,
+        _ => {
+            let temp = format!("__destructured{temps}");
+            *temps += 1;
+            body.push(variable(temp.clone(), value));
+            temp
+        }
+    };
+    let source = || {
+        Box::new(Located {
+            node: Expr::Ident(source.clone()),
+            span: value_span.clone(),
+        })
+    };
+
+    match pattern {
+        Pattern::Struct(names) => {
+            for name in names {
+                let access = Expr::MemAccess {
+                    expr: source(),
+                    member: name.clone(),
+                };
+                body.push(variable(
+                    name,
+                    Located {
+                        node: access,
+                        span: value_span.clone(),
+                    },
+                ));
+            }
+        }
+        Pattern::Tuple(names) => {
+            for (i, name) in names.into_iter().enumerate() {
+                let access = Expr::MemAccess {
+                    expr: source(),
+                    member: tuples::field(i),
+                };
+                body.push(variable(
+                    name,
+                    Located {
+                        node: access,
+                        span: value_span.clone(),
+                    },
+                ));
+            }
+        }
+        Pattern::Array(names) => {
+            for (i, name) in names.into_iter().enumerate() {
+                let access = Expr::ArrIndex {
+                    arr: source(),
+                    idx: Box::new(Located {
+                        node: Expr::Int(i as i64),
+                        span: value_span.clone(),
+                    }),
+                };
+                body.push(variable(
+                    name,
+                    Located {
+                        node: access,
+                        span: value_span.clone(),
+                    },
+                ));
+            }
+        }
+    }
+}
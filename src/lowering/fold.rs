@@ -0,0 +1,302 @@
+//! Folds constant expressions into literals before code generation
+//!
+//! Uses of global constants with scalar values are replaced by their values, and so is any
+//! expression the constant evaluator can compute, e.g. `SIZE * 2` or `NAME[0]`. Constant strings,
+//! arrays, and structs are left as they are so they aren't copied into every use. The cases of
+//! switches that call constant functions are replaced by the values the calls evaluate to, since C
+//! requires the cases to be constant expressions.
+
+use std::collections::{HashMap, HashSet};
+
+use tamago::UnaryOp;
+
+use crate::parser::*;
+use crate::semantic_analyzer::consteval::{ConstEvaluator, Value};
+
+/// Folds the constant expressions in a checked AST in place
+pub fn fold_constants(ast: &mut [LocatedGlobalStmt]) {
+    let mut folder = Folder {
+        values: constant_values(ast),
+        cases: case_values(ast),
+        scopes: vec![],
+    };
+
+    for stmt in ast {
+        folder.fold_global_stmt(stmt);
+    }
+}
+
+/// Returns the values of the global constants known at compile time
+fn constant_values(ast: &[LocatedGlobalStmt]) -> HashMap<String, Value> {
+    let mut evaluator = ConstEvaluator::new(ast);
+
+    ast.iter()
+        .filter_map(|stmt| match &stmt.node {
+            GlobalStmt::Constant { name, value, .. } => {
+                Some((name.clone(), evaluator.eval(value).ok().flatten()?))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the values of the cases of switches that call functions, which are only known by
+/// interpreting the functions
+fn case_values(ast: &[LocatedGlobalStmt]) -> HashMap<Span, Value> {
+    fn visit<'ast>(
+        evaluator: &mut ConstEvaluator<'ast>,
+        body: &'ast [LocatedStmt],
+        values: &mut HashMap<Span, Value>,
+    ) {
+        for stmt in body {
+            match &stmt.node {
+                Stmt::Switch { cases, default, .. } => {
+                    for case in cases {
+                        for value in &case.values {
+                            if let Expr::FnCall { .. } = value.node
+                                && let Ok(Some(v)) = evaluator.eval(value)
+                            {
+                                values.insert(value.span.clone(), v);
+                            }
+                        }
+                        visit(evaluator, &case.body, values);
+                    }
+                    if let Some(default) = default {
+                        visit(evaluator, default, values);
+                    }
+                }
+                Stmt::If { then, other, .. } => {
+                    visit(evaluator, then, values);
+                    if let Some(other) = other {
+                        visit(evaluator, other, values);
+                    }
+                }
+                Stmt::While { body, .. } | Stmt::Defer { body } | Stmt::Block { body } => {
+                    visit(evaluator, body, values)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut evaluator = ConstEvaluator::new(ast);
+    let mut values = HashMap::new();
+    for stmt in ast {
+        if let GlobalStmt::Function { body, .. } = &stmt.node {
+            visit(&mut evaluator, body, &mut values);
+        }
+    }
+    values
+}
+
+struct Folder {
+    values: HashMap<String, Value>,
+
+    /// The values of the cases of switches that call functions
+    cases: HashMap<Span, Value>,
+
+    /// The parameters and local variables in scope, which shadow global constants
+    scopes: Vec<HashSet<String>>,
+}
+
+impl Folder {
+    fn fold_global_stmt(&mut self, stmt: &mut LocatedGlobalStmt) {
+        match &mut stmt.node {
+            GlobalStmt::Function { params, body, .. } => {
+                self.scopes
+                    .push(params.iter().map(|(name, _)| name.clone()).collect());
+                self.fold_block(body);
+                self.scopes.pop();
+            }
+            GlobalStmt::Variable {
+                value: Some(value), ..
+            }
+            | GlobalStmt::Constant { value, .. } => self.fold_expr(value),
+            _ => {}
+        }
+    }
+
+    fn fold_block(&mut self, body: &mut [LocatedStmt]) {
+        self.scopes.push(HashSet::new());
+        for stmt in body {
+            self.fold_stmt(stmt);
+        }
+        self.scopes.pop();
+    }
+
+    fn fold_stmt(&mut self, stmt: &mut LocatedStmt) {
+        match &mut stmt.node {
+            Stmt::Variable { name, value, .. } => {
+                if let Some(value) = value {
+                    self.fold_expr(value);
+                }
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(name.clone());
+                }
+            }
+            Stmt::Destructure { pattern, value, .. } => {
+                self.fold_expr(value);
+                let (Pattern::Struct(names) | Pattern::Array(names) | Pattern::Tuple(names)) =
+                    pattern;
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.extend(names.iter().cloned());
+                }
+            }
+            Stmt::Expression { expr }
+            | Stmt::Yield { value: expr }
+            | Stmt::Return { value: Some(expr) }
+            | Stmt::Destroy { expr }
+            | Stmt::Free { expr } => self.fold_expr(expr),
+            Stmt::If { cond, then, other } => {
+                self.fold_expr(cond);
+                self.fold_block(then);
+                if let Some(other) = other {
+                    self.fold_block(other);
+                }
+            }
+            Stmt::While { cond, body, .. } => {
+                self.fold_expr(cond);
+                self.fold_block(body);
+            }
+            Stmt::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                self.fold_expr(value);
+                for case in cases {
+                    for value in &mut case.values {
+                        match self.cases.get(&value.span).cloned().and_then(literal) {
+                            Some(literal) => value.node = literal,
+                            None => self.fold_expr(value),
+                        }
+                    }
+                    self.fold_block(&mut case.body);
+                }
+                if let Some(default) = default {
+                    self.fold_block(default);
+                }
+            }
+            // TODO: expand the deferred statements at every exit of their scope
+            Stmt::Defer { body } => self.fold_block(body),
+            Stmt::Block { body } => self.fold_block(body),
+            Stmt::Return { value: None } | Stmt::Break | Stmt::Continue => {}
+        }
+    }
+
+    fn fold_expr(&mut self, expr: &mut LocatedExpr) {
+        if self.is_constant(expr)
+            && let Ok(Some(value)) = ConstEvaluator::with_values(self.values.clone()).eval(expr)
+            && let Some(literal) = literal(value)
+        {
+            expr.node = literal;
+            return;
+        }
+
+        match &mut expr.node {
+            Expr::Binary { left, right, .. } => {
+                self.fold_expr(left);
+                self.fold_expr(right);
+            }
+            Expr::Unary {
+                op: UnaryOp::AddrOf,
+                expr,
+            } => self.fold_lvalue(expr),
+            Expr::Parenthesized { expr }
+            | Expr::Unary { expr, .. }
+            | Expr::MemAccess { expr, .. }
+            | Expr::Cast { expr, .. } => self.fold_expr(expr),
+            Expr::Assign { lvalue, value, .. } => {
+                self.fold_lvalue(lvalue);
+                self.fold_expr(value);
+            }
+            Expr::Ternary { cond, lexpr, rexpr } => {
+                self.fold_expr(cond);
+                self.fold_expr(lexpr);
+                self.fold_expr(rexpr);
+            }
+            Expr::FnCall { args, .. } => {
+                for arg in args {
+                    self.fold_expr(arg);
+                }
+            }
+            Expr::ArrIndex { arr, idx } => {
+                self.fold_expr(arr);
+                self.fold_expr(idx);
+            }
+            Expr::InitArr { elems }
+            | Expr::InitArrDesignated { elems, .. }
+            | Expr::Tuple { elems } => {
+                for elem in elems {
+                    self.fold_expr(elem);
+                }
+            }
+            Expr::InitStruct { args, .. } => {
+                for (_, value) in args {
+                    self.fold_expr(value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Folds the expressions inside an lvalue, which itself has to stay a place
+    fn fold_lvalue(&mut self, lvalue: &mut LocatedExpr) {
+        match &mut lvalue.node {
+            Expr::ArrIndex { arr, idx } => {
+                self.fold_lvalue(arr);
+                self.fold_expr(idx);
+            }
+            Expr::Parenthesized { expr } | Expr::MemAccess { expr, .. } => self.fold_lvalue(expr),
+            Expr::Unary {
+                op: UnaryOp::Deref,
+                expr,
+            } => self.fold_expr(expr),
+            _ => {}
+        }
+    }
+
+    /// Whether `expr` only consists of literals and global constants the evaluator understands
+    fn is_constant(&self, expr: &LocatedExpr) -> bool {
+        match &expr.node {
+            Expr::Int(_)
+            | Expr::UInt(_)
+            | Expr::Double(_)
+            | Expr::Bool(_)
+            | Expr::Char(_)
+            | Expr::Str(_) => true,
+            Expr::Ident(name) => !self.is_local(name) && self.values.contains_key(name),
+            Expr::Parenthesized { expr } | Expr::Unary { expr, .. } => self.is_constant(expr),
+            Expr::Binary { left, right, .. } => self.is_constant(left) && self.is_constant(right),
+            Expr::Ternary { cond, lexpr, rexpr } => {
+                self.is_constant(cond) && self.is_constant(lexpr) && self.is_constant(rexpr)
+            }
+            Expr::ArrIndex { arr, idx } => self.is_constant(arr) && self.is_constant(idx),
+            Expr::MemAccess { expr, .. } => self.is_constant(expr),
+            Expr::FnCall { name, args, .. } => {
+                matches!(&name.node, Expr::Ident(func) if func == "len" && !self.is_local(func))
+                    && args.iter().all(|arg| self.is_constant(arg))
+            }
+            _ => false,
+        }
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+}
+
+/// Returns the literal for a scalar value, or `None` for strings, aggregates, and values that
+/// can't be written as a literal
+fn literal(value: Value) -> Option<Expr> {
+    match value {
+        Value::Int(i) => match i64::try_from(i) {
+            Ok(i) => Some(Expr::Int(i)),
+            Err(_) => u64::try_from(i).ok().map(Expr::UInt),
+        },
+        Value::Double(d) if d.is_finite() => Some(Expr::Double(d)),
+        Value::Bool(b) => Some(Expr::Bool(b)),
+        Value::Char(c) => Some(Expr::Char(c)),
+        Value::Double(_) | Value::Str(_) | Value::Array(_) | Value::Struct(_) => None,
+    }
+}
@@ -0,0 +1,700 @@
+//! Monomorphizes generic functions for the types and array lengths they are called with
+//!
+//! A function like `func max<T>(a: T, b: T): T` is instantiated for each type it is called with,
+//! as `max_i32` for `T = i32`, before its instances are type checked like any other function,
+//! since what the body can do with a `T` depends on the type. Instantiating a function may call
+//! for further instances, so it is repeated along with type checking until there are none.
+//!
+//! A function like `func sum<N: u64>(xs: [N]i32): i32` is generated once for each size it is
+//! called with, as `sum_3` for `N = 3`, with `[N]i32` replaced by `[3]i32` and `N` by its value.
+//! The type checker records the sizes at each call, which a call from another generic function
+//! may give in terms of its own size parameters, so instances can call further instances.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::module::Module;
+use crate::parser::*;
+
+use super::Message;
+
+/// How deeply the types a function is instantiated for can nest, which only a function that
+/// keeps calling itself with a deeper type, like a pointer to its parameter, gets past
+const MAX_TYPE_DEPTH: usize = 32;
+
+/// Instantiates the functions with type parameters in `ast` and the modules it imports for the
+/// types they are called with, and renames the calls to refer to the instances
+///
+/// Each instance is added after its generic function, in the module declaring it, and is named
+/// after the types, like `max_i32`. Returns whether any instance was added, which has to be type
+/// checked before the calls it makes can be instantiated in turn.
+pub fn instantiate_types(
+    ast: &mut Vec<LocatedGlobalStmt>,
+    modules: &mut HashMap<String, Module>,
+) -> Result<bool, Message> {
+    let mut instances = vec![];
+    find_instances(ast, None, modules, &mut instances)?;
+    Ok(add_instances(ast, None, modules, &instances))
+}
+
+/// An instance of the generic function `func` that the module at `path`, or the program itself
+/// for `None`, declares
+struct Instance {
+    path: Option<String>,
+    func: String,
+    types: Vec<Type>,
+}
+
+/// Renames the calls that the type checker inferred types for in `ast`, which is the module at
+/// `path`, and the modules it imports, and collects the instances they call
+fn find_instances(
+    ast: &mut [LocatedGlobalStmt],
+    path: Option<&str>,
+    modules: &mut HashMap<String, Module>,
+    instances: &mut Vec<Instance>,
+) -> Result<(), Message> {
+    let mut calls = TypeCalls {
+        path,
+        generics: ast
+            .iter()
+            .filter_map(|stmt| match &stmt.node {
+                GlobalStmt::Function {
+                    name, type_params, ..
+                } if !type_params.is_empty() => Some(name.clone()),
+                _ => None,
+            })
+            .collect(),
+        modules: HashMap::new(),
+        members: HashMap::new(),
+        imported: vec![],
+        instances,
+    };
+    for stmt in ast.iter() {
+        if let GlobalStmt::Import {
+            name,
+            path,
+            members,
+        } = &stmt.node
+        {
+            for member in members.iter().flatten() {
+                calls.members.insert(member.clone(), path.clone());
+            }
+            calls.modules.insert(name.clone(), path.clone());
+        }
+    }
+
+    for stmt in ast.iter_mut() {
+        if let GlobalStmt::Function {
+            type_params, body, ..
+        } = &mut stmt.node
+            && type_params.is_empty()
+        {
+            calls.rename_block(body)?;
+        }
+    }
+
+    // The instances of selectively imported functions are imported along with them
+    for (member, mangled) in std::mem::take(&mut calls.imported) {
+        for stmt in ast.iter_mut() {
+            if let GlobalStmt::Import {
+                members: Some(members),
+                ..
+            } = &mut stmt.node
+                && members.contains(&member)
+                && !members.contains(&mangled)
+            {
+                members.push(mangled.clone());
+            }
+        }
+    }
+
+    for module in modules.values_mut() {
+        find_instances(
+            &mut module.ast,
+            Some(&module.path),
+            &mut module.modules,
+            instances,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Adds the `instances` declared by `ast`, which is the module at `path`, and the modules it
+/// imports, and returns whether any wasn't there yet
+///
+/// A module imported in several places has a copy at each, and all of them get the instances.
+fn add_instances(
+    ast: &mut Vec<LocatedGlobalStmt>,
+    path: Option<&str>,
+    modules: &mut HashMap<String, Module>,
+    instances: &[Instance],
+) -> bool {
+    let mut added = false;
+
+    for instance in instances.iter().filter(|i| i.path.as_deref() == path) {
+        let mangled = mangle_types(&instance.func, &instance.types);
+        let exists = ast.iter().any(
+            |stmt| matches!(&stmt.node, GlobalStmt::Function { name, .. } if *name == mangled),
+        );
+        let generic = ast.iter().position(|stmt| {
+            matches!(
+                &stmt.node,
+                GlobalStmt::Function { name, type_params, .. }
+                    if *name == instance.func && !type_params.is_empty()
+            )
+        });
+        let (false, Some(generic)) = (exists, generic) else {
+            continue;
+        };
+
+        let mut stmt = ast[generic].clone();
+        let GlobalStmt::Function {
+            name,
+            type_params,
+            size_params,
+            params,
+            ret,
+            body,
+            ..
+        } = &mut stmt.node
+        else {
+            unreachable!()
+        };
+
+        let generics = HashMap::new();
+        let mut instantiator = Instantiator {
+            generics: &generics,
+            sizes: HashMap::new(),
+            size_types: HashMap::new(),
+            types: type_params
+                .iter()
+                .map(|t| t.node.clone())
+                .zip(instance.types.iter().cloned())
+                .collect(),
+            pending: vec![],
+        };
+        *name = mangled;
+        type_params.clear();
+        for (_, t) in size_params.iter_mut().chain(params.iter_mut()) {
+            t.node = instantiator.instantiate_type(&t.node);
+        }
+        ret.node = instantiator.instantiate_type(&ret.node);
+        instantiator.instantiate_block(body);
+
+        ast.insert(generic + 1, stmt);
+        added = true;
+    }
+
+    for module in modules.values_mut() {
+        added |= add_instances(
+            &mut module.ast,
+            Some(&module.path),
+            &mut module.modules,
+            instances,
+        );
+    }
+
+    added
+}
+
+/// The name of the instance of the generic function `name` for `types`, like `max_i32`
+fn mangle_types(name: &str, types: &[Type]) -> String {
+    let types: Vec<String> = types.iter().map(mangle_type).collect();
+    format!("{name}_{}", types.join("_"))
+}
+
+/// `t` as part of a C identifier, like `ptr_i32` for `^i32`
+pub fn mangle_type(t: &Type) -> String {
+    match t {
+        Type::Pointer(t) => format!("ptr_{}", mangle_type(t)),
+        Type::Array(len, t) => format!("arr{len}_{}", mangle_type(t)),
+        Type::GenericArray(size, t) => format!("arr{size}_{}", mangle_type(t)),
+        Type::DArray(t) => format!("darr_{}", mangle_type(t)),
+        Type::Func(params, ret) => {
+            let params: Vec<String> = params.iter().map(mangle_type).collect();
+            format!("func_{}_ret_{}", params.join("_"), mangle_type(ret))
+        }
+        Type::Tuple(elems) => {
+            let elems: Vec<String> = elems.iter().map(mangle_type).collect();
+            format!("tuple_{}", elems.join("_"))
+        }
+        Type::Result(ok, err) => format!("result_{}_{}", mangle_type(ok), mangle_type(err)),
+        t => t.to_string(),
+    }
+}
+
+/// How deeply `t` nests other types, e.g. 2 for `^^i32`
+fn type_depth(t: &Type) -> usize {
+    match t {
+        Type::Pointer(t) | Type::Array(_, t) | Type::GenericArray(_, t) | Type::DArray(t) => {
+            1 + type_depth(t)
+        }
+        Type::Func(params, ret) => {
+            1 + params
+                .iter()
+                .chain([&**ret])
+                .map(type_depth)
+                .max()
+                .unwrap_or(0)
+        }
+        Type::Tuple(elems) => 1 + elems.iter().map(type_depth).max().unwrap_or(0),
+        Type::Result(ok, err) => 1 + type_depth(ok).max(type_depth(err)),
+        _ => 0,
+    }
+}
+
+/// Renames the calls to functions with type parameters in one module to their instances
+struct TypeCalls<'i> {
+    /// The path of the module, or `None` for the program itself
+    path: Option<&'i str>,
+
+    /// The functions with type parameters the module declares
+    generics: HashSet<String>,
+
+    /// The paths of the modules it imports, by the names they are imported as
+    modules: HashMap<String, String>,
+
+    /// The paths of the modules the members of selective imports are imported from
+    members: HashMap<String, String>,
+
+    /// The instances of selectively imported functions called, with the functions
+    imported: Vec<(String, String)>,
+
+    instances: &'i mut Vec<Instance>,
+}
+
+impl TypeCalls<'_> {
+    fn rename_block(&mut self, body: &mut [LocatedStmt]) -> Result<(), Message> {
+        for stmt in body {
+            self.rename_stmt(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn rename_stmt(&mut self, stmt: &mut LocatedStmt) -> Result<(), Message> {
+        match &mut stmt.node {
+            Stmt::Variable {
+                value: Some(expr), ..
+            }
+            | Stmt::Destructure { value: expr, .. }
+            | Stmt::Expression { expr }
+            | Stmt::Yield { value: expr }
+            | Stmt::Return { value: Some(expr) }
+            | Stmt::Destroy { expr }
+            | Stmt::Free { expr } => self.rename_expr(expr),
+            Stmt::If { cond, then, other } => {
+                self.rename_expr(cond)?;
+                self.rename_block(then)?;
+                if let Some(other) = other {
+                    self.rename_block(other)?;
+                }
+                Ok(())
+            }
+            Stmt::While { cond, body, .. } => {
+                self.rename_expr(cond)?;
+                self.rename_block(body)
+            }
+            Stmt::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                self.rename_expr(value)?;
+                for case in cases {
+                    self.rename_block(&mut case.body)?;
+                }
+                if let Some(default) = default {
+                    self.rename_block(default)?;
+                }
+                Ok(())
+            }
+            Stmt::Defer { body } | Stmt::Block { body } => self.rename_block(body),
+            Stmt::Variable { value: None, .. }
+            | Stmt::Return { value: None }
+            | Stmt::Break
+            | Stmt::Continue => Ok(()),
+        }
+    }
+
+    fn rename_expr(&mut self, expr: &mut LocatedExpr) -> Result<(), Message> {
+        match &mut expr.node {
+            Expr::FnCall {
+                name, args, types, ..
+            } => {
+                for arg in args {
+                    self.rename_expr(arg)?;
+                }
+
+                let types = std::mem::take(types.get_mut());
+                if types.is_empty() {
+                    return self.rename_expr(name);
+                }
+                if let Some(t) = types.iter().find(|t| type_depth(t) > MAX_TYPE_DEPTH) {
+                    return Err((
+                        expr.span.clone(),
+                        format!("Instantiating this call nests types too deeply, like '{t}'"),
+                    ));
+                }
+
+                let (path, func) = match &mut name.node {
+                    Expr::Ident(func) if self.generics.contains(func.as_str()) => {
+                        (self.path.map(str::to_string), func)
+                    }
+                    Expr::Ident(func) => {
+                        let path = self.members.get(func.as_str()).cloned();
+                        self.imported
+                            .push((func.clone(), mangle_types(func, &types)));
+                        (path, func)
+                    }
+                    Expr::ModuleAccess { module, member } => {
+                        (self.modules.get(module.as_str()).cloned(), member)
+                    }
+                    _ => return Ok(()),
+                };
+
+                self.instances.push(Instance {
+                    path,
+                    func: func.clone(),
+                    types: types.clone(),
+                });
+                *func = mangle_types(func, &types);
+                Ok(())
+            }
+            Expr::Binary { left, right, .. } => {
+                self.rename_expr(left)?;
+                self.rename_expr(right)
+            }
+            Expr::Parenthesized { expr }
+            | Expr::Unary { expr, .. }
+            | Expr::MemAccess { expr, .. }
+            | Expr::Cast { expr, .. }
+            | Expr::Try { expr, .. } => self.rename_expr(expr),
+            Expr::Assign { lvalue, value, .. } => {
+                self.rename_expr(lvalue)?;
+                self.rename_expr(value)
+            }
+            Expr::Ternary { cond, lexpr, rexpr } => {
+                self.rename_expr(cond)?;
+                self.rename_expr(lexpr)?;
+                self.rename_expr(rexpr)
+            }
+            Expr::ArrIndex { arr, idx } => {
+                self.rename_expr(arr)?;
+                self.rename_expr(idx)
+            }
+            Expr::InitArr { elems }
+            | Expr::InitArrDesignated { elems, .. }
+            | Expr::Tuple { elems } => {
+                for elem in elems {
+                    self.rename_expr(elem)?;
+                }
+                Ok(())
+            }
+            Expr::InitStruct { args, .. } => {
+                for (_, value) in args {
+                    self.rename_expr(value)?;
+                }
+                Ok(())
+            }
+            Expr::Int(_)
+            | Expr::UInt(_)
+            | Expr::Double(_)
+            | Expr::Bool(_)
+            | Expr::Char(_)
+            | Expr::Str(_)
+            | Expr::Ident(_)
+            | Expr::Sizeof { .. }
+            | Expr::Make { .. }
+            | Expr::New { .. }
+            | Expr::EnumVarAccess { .. }
+            | Expr::ModuleAccess { .. } => Ok(()),
+        }
+    }
+}
+
+/// Replaces the generic functions in `ast` with an instance for each combination of sizes they
+/// are called with, and renames the calls to refer to the instances
+pub fn monomorphize(ast: &mut Vec<LocatedGlobalStmt>) {
+    // Functions with type parameters were instantiated before type checking
+    ast.retain(|stmt| {
+        !matches!(&stmt.node, GlobalStmt::Function { type_params, .. } if !type_params.is_empty())
+    });
+
+    let generics: HashMap<String, LocatedGlobalStmt> = ast
+        .iter()
+        .filter_map(|stmt| match &stmt.node {
+            GlobalStmt::Function {
+                name, size_params, ..
+            } if !size_params.is_empty() => Some((name.clone(), stmt.clone())),
+            _ => None,
+        })
+        .collect();
+    if generics.is_empty() {
+        return;
+    }
+
+    let mut instantiator = Instantiator {
+        generics: &generics,
+        sizes: HashMap::new(),
+        size_types: HashMap::new(),
+        types: HashMap::new(),
+        pending: vec![],
+    };
+    for stmt in ast.iter_mut() {
+        match &mut stmt.node {
+            GlobalStmt::Function {
+                size_params, body, ..
+            } if size_params.is_empty() => instantiator.instantiate_block(body),
+            _ => {}
+        }
+    }
+
+    // Each instance is generated where its generic function is declared
+    let mut instances: HashMap<&str, Vec<LocatedGlobalStmt>> = HashMap::new();
+    let mut seen = HashSet::new();
+    while let Some((name, sizes)) = instantiator.pending.pop() {
+        let mangled = mangle(&name, &sizes);
+        if !seen.insert(mangled.clone()) {
+            continue;
+        }
+
+        let (generic_name, generic) = generics.get_key_value(&name).unwrap();
+        let mut instance = generic.clone();
+        let GlobalStmt::Function {
+            name,
+            size_params,
+            params,
+            ret,
+            body,
+            ..
+        } = &mut instance.node
+        else {
+            unreachable!()
+        };
+
+        let mut nested = Instantiator {
+            generics: &generics,
+            sizes: size_params
+                .iter()
+                .zip(&sizes)
+                .map(|((size, _), len)| (size.clone(), Size::Known(*len)))
+                .collect(),
+            size_types: size_params
+                .iter()
+                .zip(&sizes)
+                .map(|((size, t), len)| (size.clone(), (*len, t.node.clone())))
+                .collect(),
+            types: HashMap::new(),
+            pending: vec![],
+        };
+        *name = mangled;
+        size_params.clear();
+        for (_, t) in params.iter_mut() {
+            t.node = t.node.with_sizes(&nested.sizes);
+        }
+        ret.node = ret.node.with_sizes(&nested.sizes);
+        nested.instantiate_block(body);
+
+        instantiator.pending.append(&mut nested.pending);
+        instances
+            .entry(generic_name.as_str())
+            .or_default()
+            .push(instance);
+    }
+
+    *ast = std::mem::take(ast)
+        .into_iter()
+        .flat_map(|stmt| match &stmt.node {
+            GlobalStmt::Function {
+                name, size_params, ..
+            } if !size_params.is_empty() => instances.remove(name.as_str()).unwrap_or_default(),
+            _ => vec![stmt],
+        })
+        .collect();
+}
+
+/// The name of the instance of the generic function `name` for `sizes`, like `sum_3`
+fn mangle(name: &str, sizes: &[usize]) -> String {
+    let sizes: Vec<String> = sizes.iter().map(usize::to_string).collect();
+    format!("{name}_{}", sizes.join("_"))
+}
+
+struct Instantiator<'g> {
+    generics: &'g HashMap<String, LocatedGlobalStmt>,
+
+    /// The sizes of the instance being generated, or nothing outside of generic functions
+    sizes: HashMap<String, Size>,
+
+    /// The values and types of the size parameters of the instance being generated
+    size_types: HashMap<String, (usize, Type)>,
+
+    /// The types of the type parameters of the instance being generated
+    types: HashMap<String, Type>,
+
+    /// The instances called that may not have been generated yet
+    pending: Vec<(String, Vec<usize>)>,
+}
+
+impl Instantiator<'_> {
+    fn instantiate_type(&self, t: &Type) -> Type {
+        t.with_sizes(&self.sizes).with_types(&self.types)
+    }
+
+    fn instantiate_block(&mut self, body: &mut [LocatedStmt]) {
+        for stmt in body {
+            self.instantiate_stmt(stmt);
+        }
+    }
+
+    fn instantiate_stmt(&mut self, stmt: &mut LocatedStmt) {
+        match &mut stmt.node {
+            Stmt::Variable { t, value, .. } => {
+                if let Some(t) = t {
+                    *t = self.instantiate_type(t);
+                }
+                if let Some(value) = value {
+                    self.instantiate_expr(value);
+                }
+            }
+            Stmt::Destructure { value: expr, .. }
+            | Stmt::Expression { expr }
+            | Stmt::Yield { value: expr }
+            | Stmt::Return { value: Some(expr) }
+            | Stmt::Destroy { expr }
+            | Stmt::Free { expr } => self.instantiate_expr(expr),
+            Stmt::If { cond, then, other } => {
+                self.instantiate_expr(cond);
+                self.instantiate_block(then);
+                if let Some(other) = other {
+                    self.instantiate_block(other);
+                }
+            }
+            Stmt::While { cond, body, .. } => {
+                self.instantiate_expr(cond);
+                self.instantiate_block(body);
+            }
+            Stmt::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                self.instantiate_expr(value);
+                for case in cases {
+                    for value in &mut case.values {
+                        self.instantiate_expr(value);
+                    }
+                    self.instantiate_block(&mut case.body);
+                }
+                if let Some(default) = default {
+                    self.instantiate_block(default);
+                }
+            }
+            Stmt::Defer { body } | Stmt::Block { body } => self.instantiate_block(body),
+            Stmt::Return { value: None } | Stmt::Break | Stmt::Continue => {}
+        }
+    }
+
+    fn instantiate_expr(&mut self, expr: &mut LocatedExpr) {
+        match &mut expr.node {
+            // A size parameter becomes its value, cast so that it keeps its type
+            Expr::Ident(name) => {
+                if let Some((len, t)) = self.size_types.get(name) {
+                    expr.node = Expr::Cast {
+                        t: Located {
+                            node: t.clone(),
+                            span: expr.span.clone(),
+                        },
+                        expr: Box::new(Located {
+                            node: Expr::UInt(*len as u64),
+                            span: expr.span.clone(),
+                        }),
+                        check: Default::default(),
+                    };
+                }
+            }
+            Expr::FnCall {
+                name,
+                args,
+                sizes,
+                result,
+                ..
+            } => {
+                if let Some(t) = result.get_mut() {
+                    *t = self.instantiate_type(t);
+                }
+                self.instantiate_expr(name);
+                for arg in args {
+                    self.instantiate_expr(arg);
+                }
+
+                if let Expr::Ident(func) = &mut name.node
+                    && self.generics.contains_key(func.as_str())
+                {
+                    let sizes: Vec<usize> = sizes
+                        .get_mut()
+                        .iter()
+                        .map(|size| match size {
+                            Size::Known(len) => *len,
+                            Size::Param(param) => self.size_types[param].0,
+                        })
+                        .collect();
+                    self.pending.push((func.clone(), sizes.clone()));
+                    *func = mangle(func, &sizes);
+                }
+            }
+            Expr::Binary { left, right, .. } => {
+                self.instantiate_expr(left);
+                self.instantiate_expr(right);
+            }
+            Expr::Parenthesized { expr }
+            | Expr::Unary { expr, .. }
+            | Expr::MemAccess { expr, .. } => self.instantiate_expr(expr),
+            Expr::Cast { t, expr, .. } => {
+                t.node = self.instantiate_type(&t.node);
+                self.instantiate_expr(expr);
+            }
+            Expr::Try { expr, t } => {
+                if let Some(t) = t.get_mut() {
+                    *t = self.instantiate_type(t);
+                }
+                self.instantiate_expr(expr);
+            }
+            Expr::Assign { lvalue, value, .. } => {
+                self.instantiate_expr(lvalue);
+                self.instantiate_expr(value);
+            }
+            Expr::Ternary { cond, lexpr, rexpr } => {
+                self.instantiate_expr(cond);
+                self.instantiate_expr(lexpr);
+                self.instantiate_expr(rexpr);
+            }
+            Expr::ArrIndex { arr, idx } => {
+                self.instantiate_expr(arr);
+                self.instantiate_expr(idx);
+            }
+            Expr::InitArr { elems }
+            | Expr::InitArrDesignated { elems, .. }
+            | Expr::Tuple { elems } => {
+                for elem in elems {
+                    self.instantiate_expr(elem);
+                }
+            }
+            Expr::InitStruct { args, .. } => {
+                for (_, value) in args {
+                    self.instantiate_expr(value);
+                }
+            }
+            Expr::Sizeof { t } | Expr::Make { t } | Expr::New { t } => {
+                *t = self.instantiate_type(t);
+            }
+            Expr::Int(_)
+            | Expr::UInt(_)
+            | Expr::Double(_)
+            | Expr::Bool(_)
+            | Expr::Char(_)
+            | Expr::Str(_)
+            | Expr::EnumVarAccess { .. }
+            | Expr::ModuleAccess { .. } => {}
+        }
+    }
+}
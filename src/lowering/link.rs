@@ -0,0 +1,372 @@
+//! Links the imported modules into the program so that they are generated in one translation
+//! unit
+//!
+//! The globals of a module are prefixed with its import path, so `println` in `std/io` becomes
+//! `std_io_println`, and `io::println` becomes a reference to it. That way modules can declare
+//! the same names as each other and the C library. Extern functions keep their names since they
+//! refer to symbols defined elsewhere.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::module::Module;
+use crate::parser::*;
+
+/// Prepends the globals of the imported modules to `ast`, the modules a module imports before
+/// the module itself, and replaces every module access with the global it refers to
+pub fn link_modules(ast: &mut Vec<LocatedGlobalStmt>, modules: HashMap<String, Module>) {
+    let mut linked = vec![];
+    let mut seen = HashSet::new();
+
+    let mut linker = Linker::new(ast, &modules, HashMap::new());
+    for stmt in ast.iter_mut() {
+        linker.link_global_stmt(stmt);
+    }
+
+    for module in modules.into_values() {
+        link(module, &mut linked, &mut seen);
+    }
+
+    linked.append(ast);
+    *ast = linked;
+}
+
+/// Links `module` into `linked` unless a module with the same path already was
+fn link(module: Module, linked: &mut Vec<LocatedGlobalStmt>, seen: &mut HashSet<String>) {
+    if !seen.insert(module.path.clone()) {
+        return;
+    }
+
+    let Module {
+        path,
+        mut ast,
+        modules,
+    } = module;
+
+    let prefix = prefix(&path);
+    let renames = ast
+        .iter()
+        .filter_map(|stmt| match &stmt.node {
+            GlobalStmt::Enum { name, .. }
+            | GlobalStmt::Struct { name, .. }
+            | GlobalStmt::Union { name, .. }
+            | GlobalStmt::Function { name, .. }
+            | GlobalStmt::Variable { name, .. }
+            | GlobalStmt::Constant { name, .. }
+            | GlobalStmt::Alias { name, .. } => Some((name.clone(), format!("{prefix}_{name}"))),
+            GlobalStmt::ExternFunction { .. } | GlobalStmt::Import { .. } => None,
+        })
+        .collect();
+
+    let mut linker = Linker::new(&ast, &modules, renames);
+    for stmt in &mut ast {
+        linker.link_global_stmt(stmt);
+    }
+
+    for module in modules.into_values() {
+        link(module, linked, seen);
+    }
+
+    linked.extend(
+        ast.into_iter()
+            .filter(|stmt| !matches!(stmt.node, GlobalStmt::Import { .. })),
+    );
+}
+
+/// The prefix of the globals of the module imported as `path`, e.g. `std_io` for `std/io`
+fn prefix(path: &str) -> String {
+    let path = path.strip_suffix(".clla").unwrap_or(path);
+    path.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Renames the globals of one module and resolves its module accesses
+struct Linker {
+    /// The names the globals of the module are generated as
+    renames: HashMap<String, String>,
+
+    /// The names the members of the modules it imports are generated as, by module and member
+    members: HashMap<(String, String), String>,
+
+    /// The parameters and local variables in scope, which shadow globals
+    scopes: Vec<HashSet<String>>,
+}
+
+impl Linker {
+    fn new(
+        ast: &[LocatedGlobalStmt],
+        modules: &HashMap<String, Module>,
+        mut renames: HashMap<String, String>,
+    ) -> Self {
+        let mut members = HashMap::new();
+
+        for stmt in ast {
+            let GlobalStmt::Import {
+                name,
+                members: imported,
+                ..
+            } = &stmt.node
+            else {
+                continue;
+            };
+            let Some(module) = modules.get(name) else {
+                continue;
+            };
+
+            let prefix = prefix(&module.path);
+            for member in module.exports() {
+                let generated = match module.find(member).map(|stmt| &stmt.node) {
+                    Some(GlobalStmt::ExternFunction { .. }) => member.to_string(),
+                    _ => format!("{prefix}_{member}"),
+                };
+
+                // The members of a selective import are referred to like the module's own globals
+                if imported
+                    .as_ref()
+                    .is_some_and(|imported| imported.iter().any(|m| m == member))
+                {
+                    renames.insert(member.to_string(), generated.clone());
+                }
+                members.insert((name.clone(), member.to_string()), generated);
+            }
+        }
+
+        Self {
+            renames,
+            members,
+            scopes: vec![],
+        }
+    }
+
+    fn link_global_stmt(&mut self, stmt: &mut LocatedGlobalStmt) {
+        match &mut stmt.node {
+            GlobalStmt::Enum { name, .. } => self.rename_global(name),
+            GlobalStmt::Struct { name, fields } | GlobalStmt::Union { name, fields } => {
+                self.rename_global(name);
+                for (_, t) in fields {
+                    self.link_type(&mut t.node);
+                }
+            }
+            GlobalStmt::Function {
+                name,
+                size_params,
+                params,
+                ret,
+                body,
+                ..
+            } => {
+                self.rename_global(name);
+                self.link_type(&mut ret.node);
+                for (_, t) in params.iter_mut() {
+                    self.link_type(&mut t.node);
+                }
+
+                self.scopes.push(
+                    size_params
+                        .iter()
+                        .chain(params.iter())
+                        .map(|(name, _)| name.clone())
+                        .collect(),
+                );
+                self.link_block(body);
+                self.scopes.pop();
+            }
+            GlobalStmt::ExternFunction { params, ret, .. } => {
+                self.link_type(&mut ret.node);
+                for (_, t) in params {
+                    self.link_type(&mut t.node);
+                }
+            }
+            GlobalStmt::Variable { name, t, value, .. } => {
+                self.rename_global(name);
+                if let Some(t) = t {
+                    self.link_type(t);
+                }
+                if let Some(value) = value {
+                    self.link_expr(value);
+                }
+            }
+            GlobalStmt::Constant { name, t, value, .. } => {
+                self.rename_global(name);
+                if let Some(t) = t {
+                    self.link_type(&mut t.node);
+                }
+                self.link_expr(value);
+            }
+            GlobalStmt::Alias { t, name } => {
+                self.rename_global(name);
+                self.link_type(&mut t.node);
+            }
+            GlobalStmt::Import { .. } => {}
+        }
+    }
+
+    fn link_block(&mut self, body: &mut [LocatedStmt]) {
+        self.scopes.push(HashSet::new());
+        for stmt in body {
+            self.link_stmt(stmt);
+        }
+        self.scopes.pop();
+    }
+
+    fn link_stmt(&mut self, stmt: &mut LocatedStmt) {
+        match &mut stmt.node {
+            Stmt::Variable { name, t, value, .. } => {
+                if let Some(t) = t {
+                    self.link_type(t);
+                }
+                if let Some(value) = value {
+                    self.link_expr(value);
+                }
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(name.clone());
+                }
+            }
+            Stmt::Destructure { pattern, value, .. } => {
+                self.link_expr(value);
+                let (Pattern::Struct(names) | Pattern::Array(names) | Pattern::Tuple(names)) =
+                    pattern;
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.extend(names.iter().cloned());
+                }
+            }
+            Stmt::Expression { expr }
+            | Stmt::Yield { value: expr }
+            | Stmt::Return { value: Some(expr) }
+            | Stmt::Destroy { expr }
+            | Stmt::Free { expr } => self.link_expr(expr),
+            Stmt::If { cond, then, other } => {
+                self.link_expr(cond);
+                self.link_block(then);
+                if let Some(other) = other {
+                    self.link_block(other);
+                }
+            }
+            Stmt::While { cond, body, .. } => {
+                self.link_expr(cond);
+                self.link_block(body);
+            }
+            Stmt::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                self.link_expr(value);
+                for case in cases {
+                    for value in &mut case.values {
+                        self.link_expr(value);
+                    }
+                    self.link_block(&mut case.body);
+                }
+                if let Some(default) = default {
+                    self.link_block(default);
+                }
+            }
+            Stmt::Defer { body } | Stmt::Block { body } => self.link_block(body),
+            Stmt::Return { value: None } | Stmt::Break | Stmt::Continue => {}
+        }
+    }
+
+    fn link_expr(&mut self, expr: &mut LocatedExpr) {
+        match &mut expr.node {
+            Expr::Ident(name) => {
+                if !self.is_local(name) {
+                    self.rename_global(name);
+                }
+            }
+            Expr::ModuleAccess { module, member } => {
+                if let Some(name) = self.members.get(&(module.clone(), member.clone())) {
+                    expr.node = Expr::Ident(name.clone());
+                }
+            }
+            Expr::EnumVarAccess { ident, .. } => self.rename_global(ident),
+            Expr::InitStruct { ident, args } => {
+                self.rename_global(ident);
+                for (_, value) in args {
+                    self.link_expr(value);
+                }
+            }
+            Expr::Binary { left, right, .. } => {
+                self.link_expr(left);
+                self.link_expr(right);
+            }
+            Expr::Parenthesized { expr }
+            | Expr::Unary { expr, .. }
+            | Expr::MemAccess { expr, .. }
+            | Expr::Try { expr, .. } => self.link_expr(expr),
+            Expr::Cast { t, expr, .. } => {
+                self.link_type(&mut t.node);
+                self.link_expr(expr);
+            }
+            Expr::Assign { lvalue, value, .. } => {
+                self.link_expr(lvalue);
+                self.link_expr(value);
+            }
+            Expr::Ternary { cond, lexpr, rexpr } => {
+                self.link_expr(cond);
+                self.link_expr(lexpr);
+                self.link_expr(rexpr);
+            }
+            Expr::FnCall { name, args, .. } => {
+                self.link_expr(name);
+                for arg in args {
+                    self.link_expr(arg);
+                }
+            }
+            Expr::ArrIndex { arr, idx } => {
+                self.link_expr(arr);
+                self.link_expr(idx);
+            }
+            Expr::InitArr { elems }
+            | Expr::InitArrDesignated { elems, .. }
+            | Expr::Tuple { elems } => {
+                for elem in elems {
+                    self.link_expr(elem);
+                }
+            }
+            Expr::Sizeof { t } | Expr::Make { t } | Expr::New { t } => self.link_type(t),
+            Expr::Int(_)
+            | Expr::UInt(_)
+            | Expr::Double(_)
+            | Expr::Bool(_)
+            | Expr::Char(_)
+            | Expr::Str(_) => {}
+        }
+    }
+
+    fn link_type(&self, t: &mut Type) {
+        match t {
+            Type::UserDefinedType(name) => self.rename_global(name),
+            Type::Pointer(t)
+            | Type::Array(_, t)
+            | Type::GenericArray(_, t)
+            | Type::DArray(t) => self.link_type(t),
+            Type::Func(params, ret) => {
+                for param in params {
+                    self.link_type(param);
+                }
+                self.link_type(ret);
+            }
+            Type::Tuple(elems) => {
+                for elem in elems {
+                    self.link_type(elem);
+                }
+            }
+            Type::Result(ok, err) => {
+                self.link_type(ok);
+                self.link_type(err);
+            }
+            _ => {}
+        }
+    }
+
+    fn rename_global(&self, name: &mut String) {
+        if let Some(renamed) = self.renames.get(name) {
+            *name = renamed.clone();
+        }
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+}
@@ -0,0 +1,306 @@
+pub mod alloc;
+pub mod casts;
+pub mod constants;
+pub mod conversions;
+pub mod darray;
+pub mod destructure;
+pub mod fold;
+pub mod generics;
+pub mod link;
+pub mod results;
+pub mod shadowing;
+pub mod statemachine;
+pub mod tuples;
+
+use tamago::{BaseType, LineDirective};
+
+use crate::parser::{LocatedStmt, Span, Stmt, Type};
+
+/// Could be either a warning or an error
+type Message = (Span, String);
+
+/// Whether the statements end by leaving the scope, so nothing after them runs
+pub fn ends_with_exit(body: &[LocatedStmt]) -> bool {
+    matches!(
+        body.last().map(|stmt| &stmt.node),
+        Some(Stmt::Return { .. } | Stmt::Break | Stmt::Continue)
+    )
+}
+
+/// The sanitizers the generated C is meant to be compiled with (`--sanitize`), which the runtime
+/// reports its traps to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sanitizers {
+    /// AddressSanitizer (`-fsanitize=address`)
+    pub address: bool,
+
+    /// UndefinedBehaviorSanitizer (`-fsanitize=undefined`)
+    pub undefined: bool,
+}
+
+impl Sanitizers {
+    /// Parses a comma-separated list of sanitizers, like `address,undefined`
+    pub fn from_names(names: &str) -> Result<Self, String> {
+        let mut sanitizers = Sanitizers::default();
+        for name in names.split(',') {
+            match name.trim() {
+                "address" => sanitizers.address = true,
+                "undefined" => sanitizers.undefined = true,
+                other => {
+                    return Err(format!(
+                        "Unknown sanitizer '{other}', expected 'address' or 'undefined'"
+                    ));
+                }
+            }
+        }
+        Ok(sanitizers)
+    }
+
+    /// The names of the sanitizers, which the C compiler is given (`-fsanitize`)
+    pub fn names(&self) -> Vec<&'static str> {
+        [(self.address, "address"), (self.undefined, "undefined")]
+            .into_iter()
+            .filter_map(|(enabled, name)| enabled.then_some(name))
+            .collect()
+    }
+
+    /// The macros the runtime is configured with, which are defined before it
+    pub fn defines(&self) -> Vec<&'static str> {
+        [
+            (self.address, "CASTELLA_SANITIZE_ADDRESS"),
+            (self.undefined, "CASTELLA_SANITIZE_UNDEFINED"),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, define)| enabled.then_some(define))
+        .collect()
+    }
+}
+
+/// Settings that affect the C code castella is lowered to
+#[derive(Debug, Clone, Default)]
+pub struct LowerOptions {
+    /// Whether indexing into dynamic arrays goes through a helper that traps when the index is
+    /// out of bounds (`--bounds-checks`)
+    pub bounds_checks: bool,
+
+    /// Whether casts the type checker marked as possibly lossy go through a helper that traps
+    /// when the value doesn't fit, unless the C is compiled with `NDEBUG` (`--checked-casts`)
+    pub checked_casts: bool,
+
+    /// The sanitizers the runtime reports its traps to (`--sanitize`)
+    pub sanitizers: Sanitizers,
+
+    /// The entry point of a program whose name isn't `main`, which a generated `main` calls
+    /// (`--entry`)
+    pub entry: Option<String>,
+
+    /// Whether the C has `#line` directives pointing back at the source, so the C compiler
+    /// reports its problems there and debuggers step through the source, unless
+    /// `--no-line-directives` is given
+    pub line_directives: bool,
+
+    /// The source file being lowered, reported by runtime traps
+    file: String,
+
+    /// The offset of the start of each line in the source file
+    line_starts: Vec<usize>,
+}
+
+impl LowerOptions {
+    pub fn new(file: &str, source: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Self {
+            bounds_checks: false,
+            checked_casts: false,
+            sanitizers: Sanitizers::default(),
+            entry: None,
+            line_directives: false,
+            file: file.to_string(),
+            line_starts,
+        }
+    }
+
+    pub fn bounds_checks(mut self, enabled: bool) -> Self {
+        self.bounds_checks = enabled;
+        self
+    }
+
+    pub fn checked_casts(mut self, enabled: bool) -> Self {
+        self.checked_casts = enabled;
+        self
+    }
+
+    pub fn sanitizers(mut self, sanitizers: Sanitizers) -> Self {
+        self.sanitizers = sanitizers;
+        self
+    }
+
+    pub fn entry(mut self, entry: &str) -> Self {
+        self.entry = Some(entry.to_string());
+        self
+    }
+
+    pub fn line_directives(mut self, enabled: bool) -> Self {
+        self.line_directives = enabled;
+        self
+    }
+
+    /// Returns the file and line that runtime traps should report for `span`, if bounds checks
+    /// are enabled
+    pub fn trap_location(&self, span: &Span) -> Option<(&str, usize)> {
+        self.bounds_checks.then(|| self.location(span))
+    }
+
+    /// Returns the file and line that a checked cast at `span` should report, if casts are
+    /// checked
+    pub fn cast_location(&self, span: &Span) -> Option<(&str, usize)> {
+        self.checked_casts.then(|| self.location(span))
+    }
+
+    /// Returns the `#line` directive for what is generated from `span`, if they are emitted
+    pub fn line_directive(&self, span: &Span) -> Option<LineDirective> {
+        if !self.line_directives {
+            return None;
+        }
+
+        let (file, line) = self.location(span);
+        let path = file.replace('\\', "\\\\").replace('"', "\\\"");
+        Some(LineDirective::new(line as u64, path).build())
+    }
+
+    /// Returns the file and line of `span`, for runtime traps that are always reported
+    pub fn location(&self, span: &Span) -> (&str, usize) {
+        let line = match self.line_starts.binary_search(&span.start) {
+            Ok(i) => i + 1,
+            Err(i) => i,
+        };
+
+        (&self.file, line)
+    }
+}
+
+/// Converts a castella type into the C type it is represented with
+pub fn lower_type(t: &Type) -> Result<tamago::Type, String> {
+    use Type::*;
+
+    let base = match t {
+        Void => BaseType::Void,
+        Double => BaseType::Double,
+        Float => BaseType::Float,
+        Char => BaseType::Char,
+        Str => {
+            return Ok(tamago::Type::new(BaseType::Char)
+                .make_const()
+                .make_pointer()
+                .build());
+        }
+        Int8 => BaseType::Int8,
+        Int16 => BaseType::Int16,
+        Int32 => BaseType::Int32,
+        Int64 => BaseType::Int64,
+        UInt8 => BaseType::UInt8,
+        UInt16 => BaseType::UInt16,
+        UInt32 => BaseType::UInt32,
+        UInt64 => BaseType::UInt64,
+        Bool => BaseType::Bool,
+        // A pointer to an array keeps its length, like `int32_t (*p)[4]`, so that `(^p)[i]`
+        // indexes the array it points to
+        Pointer(inner) => {
+            let mut lowered = lower_type(inner)?;
+            if lowered.array.is_empty() {
+                lowered.pointers.push(vec![]);
+            } else {
+                lowered.array_pointers.push(vec![]);
+            }
+            return Ok(lowered);
+        }
+        Array(size, inner) => {
+            let mut lowered = lower_type(inner)?;
+            if lowered.is_array() {
+                return Err(format!("'{t}' cannot be represented in C yet"));
+            }
+            lowered.array = vec![*size];
+            return Ok(lowered);
+        }
+        DArray(_) => return Ok(darray::lower_type()),
+        UserDefinedType(name) => BaseType::TypeDef(name.clone()),
+        Func(params, ret) => BaseType::new_fn_ptr(
+            lower_type(ret)?,
+            params
+                .iter()
+                .map(lower_param_type)
+                .collect::<std::result::Result<_, _>>()?,
+        ),
+        Result(ok, err) => BaseType::TypeDef(results::type_name(ok, err)),
+        Tuple(_) | GenericArray(..) | ExprArray(..) => {
+            return Err(format!("'{t}' must be lowered before code generation"));
+        }
+    };
+
+    Ok(tamago::Type::new(base).build())
+}
+
+/// Converts the type of a function parameter into the C parameter type
+///
+/// Arrays are never copied when passed to a function. A `[N]T` parameter is passed by reference
+/// and read-only, so it becomes `const T name[N]`, and a `^[N]T` parameter, which can modify the
+/// caller's array, becomes a pointer to it, `T (*name)[N]`, which `&xs` passes.
+pub fn lower_param_type(t: &Type) -> Result<tamago::Type, String> {
+    let mut lowered = lower_type(t)?;
+    if let Type::Array(..) = t {
+        lowered.qualifiers.push(tamago::TypeQualifier::Const);
+    }
+    Ok(lowered)
+}
+
+/// Makes a lowered type read-only, which for a pointer such as a string means making both the
+/// pointer and what it points to constant
+pub fn make_const(t: &mut tamago::Type) {
+    if let Some(outermost) = t.array_pointers.last_mut() {
+        outermost.push(tamago::TypeQualifier::Const);
+        return;
+    }
+
+    match t.pointers.last_mut() {
+        Some(outermost) => outermost.push(tamago::TypeQualifier::Const),
+        None => t.qualifiers.push(tamago::TypeQualifier::Const),
+    }
+}
+
+/// Converts an integer from `INT64_MIN` to `UINT64_MAX` into a C literal with the same value
+///
+/// A decimal literal above `INT64_MAX` has no type in C without a `ULL` suffix, and `INT64_MIN`
+/// can't be written as a negated literal, since `9223372036854775808` is already too large.
+pub fn int_literal(n: i128) -> tamago::Expr {
+    if n == i64::MIN.into() {
+        tamago::Expr::Raw(format!("({}LL - 1)", i64::MIN + 1))
+    } else if n > i64::MAX.into() {
+        tamago::Expr::Raw(format!("{n}ULL"))
+    } else {
+        tamago::Expr::Int(n as i64)
+    }
+}
+
+/// Escapes a string so that it can be emitted as a C string literal
+pub fn escape_c_str(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            // Octal escapes are at most three digits long, unlike hex ones which would swallow
+            // any hex digits following them, so `\0` is written out too
+            c if c.is_ascii_control() => res.push_str(&format!("\\{:03o}", c as u8)),
+            c => res.push(c),
+        }
+    }
+
+    res
+}
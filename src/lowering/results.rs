@@ -0,0 +1,413 @@
+//! Lowers `Result<T, E>`, `ok(x)`, `err(e)`, and `try`
+//!
+//! Each `Result<T, E>` the program uses becomes a struct of its own, like
+//!
+//! ```c
+//! typedef struct Result_i32_ParseError {
+//!   bool is_ok;
+//!   int32_t value;
+//!   ParseError error;
+//! } Result_i32_ParseError;
+//! ```
+//!
+//! without `value` for `Result<void, E>`, and `ok(x)` and `err(e)` become compound literals of
+//! it. A `try` is expanded before code generation: the `Result` is kept in a temporary, which is
+//! checked before the statement the `try` is in, returning its error from the function, so
+//! `let n = try parse(s);` becomes
+//!
+//! ```text
+//! let __try0: Result<i32, ParseError> = parse(s);
+//! if (not __try0.is_ok) { return err(__try0.error); }
+//! let n = __try0.value;
+//! ```
+//!
+//! The deferred blocks run on the way out like for any other `return`.
+
+use std::cell::RefCell;
+
+use tamago::UnaryOp;
+
+use crate::lowering::{conversions, generics};
+use crate::parser::*;
+
+/// The name of the C struct `Result<ok, err>` is represented with, like `Result_i32_ParseError`
+pub fn type_name(ok: &Type, err: &Type) -> String {
+    format!(
+        "Result_{}_{}",
+        generics::mangle_type(ok),
+        generics::mangle_type(err)
+    )
+}
+
+/// The fields of the C struct `Result<ok, err>` is represented with
+pub fn fields(ok: &Type, err: &Type) -> Vec<(&'static str, Type)> {
+    let mut fields = vec![("is_ok", Type::Bool)];
+    if *ok != Type::Void {
+        fields.push(("value", ok.clone()));
+    }
+    fields.push(("error", err.clone()));
+    fields
+}
+
+/// Returns the `Result` types a checked AST uses, each after the ones it contains
+pub fn used_types(ast: &[LocatedGlobalStmt]) -> Vec<Type> {
+    let mut types = vec![];
+    for stmt in ast {
+        match &stmt.node {
+            GlobalStmt::Function {
+                params, ret, body, ..
+            } => {
+                for (_, t) in params {
+                    add_type(&t.node, &mut types);
+                }
+                add_type(&ret.node, &mut types);
+                add_block_types(body, &mut types);
+            }
+            GlobalStmt::ExternFunction { params, ret, .. } => {
+                for (_, t) in params {
+                    add_type(&t.node, &mut types);
+                }
+                add_type(&ret.node, &mut types);
+            }
+            GlobalStmt::Struct { fields, .. } | GlobalStmt::Union { fields, .. } => {
+                for (_, t) in fields {
+                    add_type(&t.node, &mut types);
+                }
+            }
+            GlobalStmt::Alias { t, .. } => add_type(&t.node, &mut types),
+            GlobalStmt::Variable { t: Some(t), .. } => add_type(t, &mut types),
+            GlobalStmt::Constant { t: Some(t), .. } => add_type(&t.node, &mut types),
+            _ => {}
+        }
+    }
+    types
+}
+
+/// Adds the `Result` types in `t` to `types`, the ones it contains first
+pub fn add_type(t: &Type, types: &mut Vec<Type>) {
+    match t {
+        Type::Pointer(t) | Type::Array(_, t) | Type::GenericArray(_, t) | Type::DArray(t) => {
+            add_type(t, types)
+        }
+        Type::Func(params, ret) => {
+            for param in params {
+                add_type(param, types);
+            }
+            add_type(ret, types);
+        }
+        Type::Tuple(elems) => {
+            for elem in elems {
+                add_type(elem, types);
+            }
+        }
+        Type::Result(ok, err) => {
+            add_type(ok, types);
+            add_type(err, types);
+            if !types.contains(t) {
+                types.push(t.clone());
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Adds the `Result` types of the variables in `body` and the types its expressions name
+fn add_block_types(body: &[LocatedStmt], types: &mut Vec<Type>) {
+    for stmt in body {
+        match &stmt.node {
+            Stmt::Variable { t, value, .. } => {
+                if let Some(t) = t {
+                    add_type(t, types);
+                }
+                if let Some(value) = value {
+                    add_expr_types(value, types);
+                }
+            }
+            Stmt::Destructure { value: expr, .. }
+            | Stmt::Expression { expr }
+            | Stmt::Yield { value: expr }
+            | Stmt::Return { value: Some(expr) }
+            | Stmt::Destroy { expr }
+            | Stmt::Free { expr } => add_expr_types(expr, types),
+            Stmt::If { cond, then, other } => {
+                add_expr_types(cond, types);
+                add_block_types(then, types);
+                if let Some(other) = other {
+                    add_block_types(other, types);
+                }
+            }
+            Stmt::While { cond, body, .. } => {
+                add_expr_types(cond, types);
+                add_block_types(body, types);
+            }
+            Stmt::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                add_expr_types(value, types);
+                for case in cases {
+                    add_block_types(&case.body, types);
+                }
+                if let Some(default) = default {
+                    add_block_types(default, types);
+                }
+            }
+            Stmt::Defer { body } | Stmt::Block { body } => add_block_types(body, types),
+            Stmt::Return { value: None } | Stmt::Break | Stmt::Continue => {}
+        }
+    }
+}
+
+fn add_expr_types(expr: &LocatedExpr, types: &mut Vec<Type>) {
+    match &expr.node {
+        Expr::Sizeof { t } | Expr::Make { t } | Expr::New { t } => add_type(t, types),
+        Expr::Cast { t, expr, .. } => {
+            add_type(&t.node, types);
+            add_expr_types(expr, types);
+        }
+        Expr::FnCall {
+            name, args, result, ..
+        } => {
+            if let Some(t) = &*result.borrow() {
+                add_type(t, types);
+            }
+            // The built-in `parse_i32` returns a `Result` without it being named
+            if matches!(&name.node, Expr::Ident(func) if func == "parse_i32") {
+                add_type(&conversions::parse_i32_type(), types);
+            }
+            add_expr_types(name, types);
+            for arg in args {
+                add_expr_types(arg, types);
+            }
+        }
+        Expr::Binary { left, right, .. } => {
+            add_expr_types(left, types);
+            add_expr_types(right, types);
+        }
+        Expr::Parenthesized { expr }
+        | Expr::Unary { expr, .. }
+        | Expr::MemAccess { expr, .. }
+        | Expr::Try { expr, .. } => add_expr_types(expr, types),
+        Expr::Assign { lvalue, value, .. } => {
+            add_expr_types(lvalue, types);
+            add_expr_types(value, types);
+        }
+        Expr::Ternary { cond, lexpr, rexpr } => {
+            add_expr_types(cond, types);
+            add_expr_types(lexpr, types);
+            add_expr_types(rexpr, types);
+        }
+        Expr::ArrIndex { arr, idx } => {
+            add_expr_types(arr, types);
+            add_expr_types(idx, types);
+        }
+        Expr::InitArr { elems } | Expr::InitArrDesignated { elems, .. } | Expr::Tuple { elems } => {
+            for elem in elems {
+                add_expr_types(elem, types);
+            }
+        }
+        Expr::InitStruct { args, .. } => {
+            for (_, value) in args {
+                add_expr_types(value, types);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `(Result_T_E){.is_ok=true, .value=x}` for `ok(x)`, or `(Result_T_E){.is_ok=false, .error=e}`
+/// for `err(e)`
+pub fn literal(t: &tamago::Type, is_ok: bool, value: Option<tamago::Expr>) -> tamago::Expr {
+    let mut names = vec!["is_ok".to_string()];
+    let mut values = vec![tamago::Expr::Bool(is_ok)];
+    if let Some(value) = value {
+        names.push(if is_ok { "value" } else { "error" }.to_string());
+        values.push(value);
+    }
+
+    let init = tamago::Expr::new_init_struct_designated(names, values);
+    tamago::Expr::Raw(format!("({t}){init}"))
+}
+
+/// Expands the `try`s in a checked AST in place
+pub fn lower_try(ast: &mut [LocatedGlobalStmt]) {
+    for stmt in ast {
+        if let GlobalStmt::Function { ret, body, .. } = &mut stmt.node {
+            let mut tries = Tries {
+                ret: ret.node.clone(),
+                temps: 0,
+            };
+            tries.lower_body(body);
+        }
+    }
+}
+
+struct Tries {
+    /// The return type of the function, which the errors are returned as
+    ret: Type,
+
+    /// How many temporaries the function has so far
+    temps: usize,
+}
+
+impl Tries {
+    fn lower_body(&mut self, body: &mut Vec<LocatedStmt>) {
+        for mut stmt in std::mem::take(body) {
+            let mut checks = vec![];
+            match &mut stmt.node {
+                // The value of a `try` on its own isn't used, so only the check is left
+                Stmt::Expression { expr } if matches!(expr.node, Expr::Try { .. }) => {
+                    self.lower_expr(expr, &mut checks);
+                    body.append(&mut checks);
+                    continue;
+                }
+                Stmt::Variable {
+                    value: Some(expr), ..
+                }
+                | Stmt::Destructure { value: expr, .. }
+                | Stmt::Expression { expr }
+                | Stmt::Yield { value: expr }
+                | Stmt::Return { value: Some(expr) }
+                | Stmt::Destroy { expr }
+                | Stmt::Free { expr } => self.lower_expr(expr, &mut checks),
+                Stmt::If { cond, then, other } => {
+                    self.lower_expr(cond, &mut checks);
+                    self.lower_body(then);
+                    if let Some(other) = other {
+                        self.lower_body(other);
+                    }
+                }
+                // The type checker doesn't allow `try` in loop conditions, which would have to be
+                // checked again on every iteration
+                Stmt::While { body, .. } | Stmt::Defer { body } | Stmt::Block { body } => {
+                    self.lower_body(body)
+                }
+                Stmt::Switch {
+                    value,
+                    cases,
+                    default,
+                } => {
+                    self.lower_expr(value, &mut checks);
+                    for case in cases {
+                        self.lower_body(&mut case.body);
+                    }
+                    if let Some(default) = default {
+                        self.lower_body(default);
+                    }
+                }
+                Stmt::Variable { value: None, .. }
+                | Stmt::Return { value: None }
+                | Stmt::Break
+                | Stmt::Continue => {}
+            }
+
+            body.append(&mut checks);
+            body.push(stmt);
+        }
+    }
+
+    /// Replaces the `try`s in `expr` with the values of their `Result`s, adding the statements
+    /// that keep them in temporaries and check them to `checks`
+    fn lower_expr(&mut self, expr: &mut LocatedExpr, checks: &mut Vec<LocatedStmt>) {
+        match &mut expr.node {
+            Expr::Try { expr: value, t } => {
+                self.lower_expr(value, checks);
+
+                let temp = format!("__try{}", self.temps);
+                self.temps += 1;
+                let span = expr.span.clone();
+                let member = |member: &str| {
+                    located(
+                        Expr::MemAccess {
+                            expr: Box::new(located(Expr::Ident(temp.clone()), &span)),
+                            member: member.to_string(),
+                        },
+                        &span,
+                    )
+                };
+
+                let value = std::mem::replace(&mut **value, located(Expr::Bool(false), &span));
+                checks.push(located(
+                    Stmt::Variable {
+                        name: temp.clone(),
+                        t: t.take(),
+                        value: Some(value),
+                        private: false,
+                        is_const: false,
+                    },
+                    &span,
+                ));
+
+                let err = located(
+                    Expr::FnCall {
+                        name: Box::new(located(Expr::Ident("err".to_string()), &span)),
+                        args: vec![member("error")],
+                        sizes: Default::default(),
+                        types: Default::default(),
+                        result: RefCell::new(Some(self.ret.clone())),
+                    },
+                    &span,
+                );
+                let is_err = Expr::Unary {
+                    op: UnaryOp::LogicNeg,
+                    expr: Box::new(member("is_ok")),
+                };
+                checks.push(located(
+                    Stmt::If {
+                        cond: located(is_err, &span),
+                        then: vec![located(Stmt::Return { value: Some(err) }, &span)],
+                        other: None,
+                    },
+                    &span,
+                ));
+
+                *expr = member("value");
+            }
+            Expr::Binary { left, right, .. } => {
+                self.lower_expr(left, checks);
+                self.lower_expr(right, checks);
+            }
+            Expr::Parenthesized { expr }
+            | Expr::Unary { expr, .. }
+            | Expr::MemAccess { expr, .. }
+            | Expr::Cast { expr, .. } => self.lower_expr(expr, checks),
+            Expr::Assign { lvalue, value, .. } => {
+                self.lower_expr(lvalue, checks);
+                self.lower_expr(value, checks);
+            }
+            // `try` isn't allowed in the operands that may not be evaluated
+            Expr::Ternary { cond, .. } => self.lower_expr(cond, checks),
+            Expr::FnCall { name, args, .. } => {
+                self.lower_expr(name, checks);
+                for arg in args {
+                    self.lower_expr(arg, checks);
+                }
+            }
+            Expr::ArrIndex { arr, idx } => {
+                self.lower_expr(arr, checks);
+                self.lower_expr(idx, checks);
+            }
+            Expr::InitArr { elems }
+            | Expr::InitArrDesignated { elems, .. }
+            | Expr::Tuple { elems } => {
+                for elem in elems {
+                    self.lower_expr(elem, checks);
+                }
+            }
+            Expr::InitStruct { args, .. } => {
+                for (_, value) in args {
+                    self.lower_expr(value, checks);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn located<T>(node: T, span: &Span) -> Located<T> {
+    Located {
+        node,
+        span: span.clone(),
+    }
+}
@@ -0,0 +1,182 @@
+//! Renames local variables that shadow the variables of enclosing blocks
+//!
+//! C allows shadowing too, but a variable is in scope in its own initializer there, so
+//! `let x = x + 1;` in a nested block would read the new, uninitialized `x`. Other passes also
+//! assume the locals of a function have distinct names, like the hoisting of the locals of
+//! `@statemachine` functions into their state, so each shadowing variable gets a name of its own,
+//! `__x_1` for the first one shadowing `x` and so on.
+
+use std::collections::HashMap;
+
+use crate::parser::*;
+
+/// Renames the shadowing locals of a checked AST and their uses in place
+pub fn rename_shadowed(ast: &mut [LocatedGlobalStmt]) {
+    for stmt in ast {
+        if let GlobalStmt::Function {
+            size_params,
+            params,
+            body,
+            ..
+        } = &mut stmt.node
+        {
+            let mut renamer = Renamer {
+                scopes: vec![
+                    size_params
+                        .iter()
+                        .chain(params.iter())
+                        .map(|(name, _)| (name.clone(), name.clone()))
+                        .collect(),
+                ],
+                counts: HashMap::new(),
+            };
+
+            // The body is in the same scope as the parameters
+            for stmt in body {
+                renamer.rename_stmt(stmt);
+            }
+        }
+    }
+}
+
+struct Renamer {
+    /// The names of the locals in scope, innermost last, mapped to the names they are emitted as
+    scopes: Vec<HashMap<String, String>>,
+
+    /// How many variables of the function have shadowed each name so far
+    counts: HashMap<String, usize>,
+}
+
+impl Renamer {
+    fn rename_block(&mut self, body: &mut [LocatedStmt]) {
+        self.scopes.push(HashMap::new());
+        for stmt in body {
+            self.rename_stmt(stmt);
+        }
+        self.scopes.pop();
+    }
+
+    fn rename_stmt(&mut self, stmt: &mut LocatedStmt) {
+        match &mut stmt.node {
+            // The value is renamed first, since it still sees the shadowed variable
+            Stmt::Variable { name, value, .. } => {
+                if let Some(value) = value {
+                    self.rename_expr(value);
+                }
+                self.declare(name);
+            }
+            Stmt::Destructure { pattern, value, .. } => {
+                self.rename_expr(value);
+                let (Pattern::Struct(names) | Pattern::Array(names) | Pattern::Tuple(names)) =
+                    pattern;
+                for name in names {
+                    self.declare(name);
+                }
+            }
+            Stmt::Expression { expr }
+            | Stmt::Yield { value: expr }
+            | Stmt::Return { value: Some(expr) }
+            | Stmt::Destroy { expr }
+            | Stmt::Free { expr } => self.rename_expr(expr),
+            Stmt::If { cond, then, other } => {
+                self.rename_expr(cond);
+                self.rename_block(then);
+                if let Some(other) = other {
+                    self.rename_block(other);
+                }
+            }
+            Stmt::While { cond, body, .. } => {
+                self.rename_expr(cond);
+                self.rename_block(body);
+            }
+            Stmt::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                self.rename_expr(value);
+                for case in cases {
+                    for value in &mut case.values {
+                        self.rename_expr(value);
+                    }
+                    self.rename_block(&mut case.body);
+                }
+                if let Some(default) = default {
+                    self.rename_block(default);
+                }
+            }
+            Stmt::Defer { body } | Stmt::Block { body } => self.rename_block(body),
+            Stmt::Return { value: None } | Stmt::Break | Stmt::Continue => {}
+        }
+    }
+
+    /// Declares `name` in the innermost scope, renaming it if it shadows an enclosing local
+    fn declare(&mut self, name: &mut String) {
+        let original = name.clone();
+        if self.lookup(name).is_some() {
+            let count = self.counts.entry(original.clone()).or_default();
+            *count += 1;
+            *name = format!("__{original}_{count}");
+        }
+
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(original, name.clone());
+    }
+
+    fn lookup(&self, name: &str) -> Option<&String> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn rename_expr(&mut self, expr: &mut LocatedExpr) {
+        match &mut expr.node {
+            Expr::Ident(name) => {
+                if let Some(renamed) = self.lookup(name) {
+                    *name = renamed.clone();
+                }
+            }
+            Expr::Binary { left, right, .. } => {
+                self.rename_expr(left);
+                self.rename_expr(right);
+            }
+            Expr::Parenthesized { expr }
+            | Expr::Unary { expr, .. }
+            | Expr::MemAccess { expr, .. }
+            | Expr::Cast { expr, .. }
+            | Expr::Try { expr, .. } => self.rename_expr(expr),
+            Expr::Assign { lvalue, value, .. } => {
+                self.rename_expr(lvalue);
+                self.rename_expr(value);
+            }
+            Expr::Ternary { cond, lexpr, rexpr } => {
+                self.rename_expr(cond);
+                self.rename_expr(lexpr);
+                self.rename_expr(rexpr);
+            }
+            Expr::FnCall { name, args, .. } => {
+                self.rename_expr(name);
+                for arg in args {
+                    self.rename_expr(arg);
+                }
+            }
+            Expr::ArrIndex { arr, idx } => {
+                self.rename_expr(arr);
+                self.rename_expr(idx);
+            }
+            Expr::InitArr { elems }
+            | Expr::InitArrDesignated { elems, .. }
+            | Expr::Tuple { elems } => {
+                for elem in elems {
+                    self.rename_expr(elem);
+                }
+            }
+            Expr::InitStruct { args, .. } => {
+                for (_, value) in args {
+                    self.rename_expr(value);
+                }
+            }
+            _ => {}
+        }
+    }
+}
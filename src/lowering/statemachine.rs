@@ -0,0 +1,746 @@
+//! Lowers `@statemachine` functions into plain C.
+//!
+//! A generator-style function such as
+//!
+//! ```text
+//! @statemachine
+//! func countdown(n: i32): i32 {
+//!   while (n > 0) {
+//!     yield n;
+//!     n -= 1;
+//!   }
+//! }
+//! ```
+//!
+//! is turned into a `countdown_state` struct that holds every parameter and local variable
+//! (so they survive between steps), a `countdown_init` function that fills in the parameters,
+//! and a `countdown_step` function. Each call to `countdown_step` runs until the next `yield`,
+//! stores the yielded value in the `_value` field and returns `true`, or returns `false` once
+//! the body has finished. Resuming jumps straight to the label placed after the last `yield`.
+
+use std::collections::HashMap;
+
+use tamago::{
+    AssignOp, BaseType, Block, DoWhile, Expr, Field, Function, GlobalStatement, If, Parameter,
+    Statement, Struct, Switch, UnaryOp, Variable, While,
+};
+
+use crate::lowering::*;
+use crate::parser::{self, Located, LocatedExpr, LocatedStmt, LocatedType, Stmt};
+
+const STATE_FIELD: &str = "_state";
+const VALUE_FIELD: &str = "_value";
+const SELF_NAME: &str = "self";
+
+/// The `_state` value of a state machine whose body has run to completion
+const FINISHED: i64 = -1;
+
+pub struct StateMachine<'ast> {
+    name: &'ast str,
+    params: &'ast Vec<(String, LocatedType)>,
+    ret: &'ast LocatedType,
+    body: &'ast Vec<LocatedStmt>,
+
+    /// Parameters and locals that live in the state struct, with their types
+    hoisted: HashMap<&'ast str, &'ast parser::Type>,
+    fields: Vec<Field>,
+    resume_points: i64,
+
+    options: LowerOptions,
+}
+
+impl<'ast> StateMachine<'ast> {
+    pub fn new(
+        name: &'ast str,
+        params: &'ast Vec<(String, LocatedType)>,
+        ret: &'ast LocatedType,
+        body: &'ast Vec<LocatedStmt>,
+    ) -> Self {
+        Self {
+            name,
+            params,
+            ret,
+            body,
+            hoisted: HashMap::new(),
+            fields: vec![],
+            resume_points: 0,
+            options: LowerOptions::default(),
+        }
+    }
+
+    pub fn options(mut self, options: LowerOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the state struct followed by the `init` and `step` functions
+    pub fn lower(mut self) -> Result<Vec<GlobalStatement>, Message> {
+        if self.ret.node == parser::Type::Void {
+            return Err((
+                self.ret.span.clone(),
+                "A @statemachine function must declare the type it yields".to_string(),
+            ));
+        }
+
+        self.fields.push(
+            Field::new(
+                STATE_FIELD.to_string(),
+                tamago::Type::new(BaseType::Int).build(),
+            )
+            .build(),
+        );
+        self.fields
+            .push(Field::new(VALUE_FIELD.to_string(), self.lower_type(self.ret)?).build());
+
+        for (name, t) in self.params {
+            self.hoist(name, &t.node, &t.span)?;
+        }
+
+        self.hoist_locals(self.body)?;
+
+        let step_body = self.lower_body()?;
+
+        let state_struct = Struct::new(self.struct_name())
+            .fields(std::mem::take(&mut self.fields))
+            .build();
+
+        Ok(vec![
+            GlobalStatement::Struct(state_struct),
+            GlobalStatement::NewLine,
+            GlobalStatement::Function(self.lower_init()?),
+            GlobalStatement::NewLine,
+            GlobalStatement::Function(
+                Function::new(
+                    format!("{}_step", self.name),
+                    tamago::Type::new(BaseType::Bool).build(),
+                )
+                .param(Parameter::new(SELF_NAME.to_string(), self.self_type()).build())
+                .body(step_body)
+                .build(),
+            ),
+        ])
+    }
+
+    fn struct_name(&self) -> String {
+        format!("{}_state", self.name)
+    }
+
+    fn state_type(&self) -> tamago::Type {
+        tamago::Type::new(BaseType::Struct(self.struct_name())).build()
+    }
+
+    fn self_type(&self) -> tamago::Type {
+        tamago::Type::new(BaseType::Struct(self.struct_name()))
+            .make_pointer()
+            .build()
+    }
+
+    fn lower_type(&self, t: &LocatedType) -> Result<tamago::Type, Message> {
+        lower_type(&t.node).map_err(|msg| (t.span.clone(), msg))
+    }
+
+    fn hoist(
+        &mut self,
+        name: &'ast str,
+        t: &'ast parser::Type,
+        span: &Span,
+    ) -> Result<(), Message> {
+        if name == STATE_FIELD || name == VALUE_FIELD || name == SELF_NAME {
+            return Err((
+                span.clone(),
+                format!("'{name}' is reserved in @statemachine functions"),
+            ));
+        }
+
+        // Shadowing locals have been renamed by now, so the locals that share a name are in
+        // sibling blocks and never live at the same time, which lets them share a field too
+        match self.hoisted.insert(name, t) {
+            Some(prev) if prev == t => return Ok(()),
+            Some(prev) => {
+                return Err((
+                    span.clone(),
+                    format!(
+                        "'{name}' is declared as both '{prev}' and '{t}' in this @statemachine \
+                         function, whose locals share one struct"
+                    ),
+                ));
+            }
+            None => {}
+        }
+
+        let field_t = lower_type(t).map_err(|msg| (span.clone(), msg))?;
+        self.fields
+            .push(Field::new(name.to_string(), field_t).build());
+
+        Ok(())
+    }
+
+    fn hoist_locals(&mut self, body: &'ast Vec<LocatedStmt>) -> Result<(), Message> {
+        for stmt in body {
+            match &stmt.node {
+                Stmt::Variable { name, t, .. } => {
+                    let Some(t) = t else {
+                        return Err((
+                            stmt.span.clone(),
+                            format!("'{name}' needs an explicit type in a @statemachine function"),
+                        ));
+                    };
+
+                    self.hoist(name, t, &stmt.span)?;
+                }
+                Stmt::If { then, other, .. } => {
+                    self.hoist_locals(then)?;
+                    if let Some(other) = other {
+                        self.hoist_locals(other)?;
+                    }
+                }
+                Stmt::While { body, .. } | Stmt::Defer { body } | Stmt::Block { body } => {
+                    self.hoist_locals(body)?
+                }
+                Stmt::Switch { cases, default, .. } => {
+                    for case in cases {
+                        self.hoist_locals(&case.body)?;
+                    }
+                    if let Some(default) = default {
+                        self.hoist_locals(default)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn lower_init(&self) -> Result<Function, Message> {
+        let mut names = vec![STATE_FIELD.to_string()];
+        let mut values = vec![Expr::Int(0)];
+        let mut params = vec![];
+
+        for (name, t) in self.params {
+            names.push(name.clone());
+            values.push(Expr::new_ident(name.clone()));
+            params.push(Parameter::new(name.clone(), self.lower_type(t)?).build());
+        }
+
+        Ok(
+            Function::new(format!("{}_init", self.name), self.state_type())
+                .params(params)
+                .body(
+                    Block::new()
+                        .statement(Statement::decl(
+                            Variable::new(SELF_NAME.to_string(), self.state_type())
+                                .value(Expr::new_init_struct_designated(names, values))
+                                .build(),
+                        ))
+                        .statement(Statement::ret(Expr::new_ident_with_str(SELF_NAME)))
+                        .build(),
+                )
+                .build(),
+        )
+    }
+
+    fn lower_body(&mut self) -> Result<Block, Message> {
+        let mut stmts = self.lower_stmts(self.body)?;
+        stmts.append(&mut self.finish());
+
+        let mut dispatch = Switch::new(self.field(STATE_FIELD)).case(
+            Expr::Int(FINISHED),
+            Block::new()
+                .statement(Statement::ret(Expr::Bool(false)))
+                .build(),
+        );
+
+        for point in 1..=self.resume_points {
+            dispatch = dispatch.case(
+                Expr::Int(point),
+                Block::new()
+                    .statement(Statement::GoTo(Self::resume_label(point)))
+                    .build(),
+            );
+        }
+
+        Ok(Block::new()
+            .statement(Statement::Switch(dispatch.build()))
+            .new_line()
+            .merge(Block::new().statements(stmts).build())
+            .build())
+    }
+
+    fn resume_label(point: i64) -> String {
+        format!("resume_{point}")
+    }
+
+    /// `(*self).<name>`
+    fn field(&self, name: &str) -> Expr {
+        Expr::new_mem_access_with_str(
+            Expr::new_parenthesized(Expr::new_unary(
+                Expr::new_ident_with_str(SELF_NAME),
+                tamago::UnaryOp::Deref,
+            )),
+            name,
+        )
+    }
+
+    fn set_field(&self, name: &str, value: Expr) -> Statement {
+        Statement::expr(Expr::new_assign(self.field(name), AssignOp::Assign, value))
+    }
+
+    fn finish(&self) -> Vec<Statement> {
+        vec![
+            self.set_field(STATE_FIELD, Expr::Int(FINISHED)),
+            Statement::ret(Expr::Bool(false)),
+        ]
+    }
+
+    fn lower_block(&mut self, body: &'ast Vec<LocatedStmt>) -> Result<Block, Message> {
+        Ok(Block::new().statements(self.lower_stmts(body)?).build())
+    }
+
+    fn lower_case(&mut self, body: &'ast Vec<LocatedStmt>) -> Result<Block, Message> {
+        let mut stmts = self.lower_stmts(body)?;
+        if !ends_with_exit(body) {
+            stmts.push(Statement::Break);
+        }
+
+        Ok(Block::new().statements(stmts).build())
+    }
+
+    fn lower_stmts(&mut self, body: &'ast Vec<LocatedStmt>) -> Result<Vec<Statement>, Message> {
+        let mut stmts = vec![];
+
+        for stmt in body {
+            let directive = self.options.line_directive(&stmt.span);
+            stmts.extend(directive.map(Statement::LineDirective));
+            stmts.append(&mut self.lower_stmt(stmt)?);
+        }
+
+        Ok(stmts)
+    }
+
+    fn lower_stmt(&mut self, stmt: &'ast LocatedStmt) -> Result<Vec<Statement>, Message> {
+        let Located { node: stmt, span } = stmt;
+
+        match stmt {
+            Stmt::Variable {
+                name,
+                value: Some(value),
+                ..
+            } => Ok(vec![self.set_field(name, self.lower_expr(value)?)]),
+
+            Stmt::Variable { value: None, .. } => Ok(vec![]),
+
+            Stmt::Expression { expr } => Ok(vec![Statement::expr(self.lower_expr(expr)?)]),
+
+            Stmt::Return { value: None } => Ok(self.finish()),
+
+            Stmt::Return { value: Some(_) } => Err((
+                span.clone(),
+                "Cannot return a value from a @statemachine function, use 'yield' instead"
+                    .to_string(),
+            )),
+
+            Stmt::Yield { value } => {
+                self.resume_points += 1;
+                let point = self.resume_points;
+
+                Ok(vec![
+                    self.set_field(STATE_FIELD, Expr::Int(point)),
+                    self.set_field(VALUE_FIELD, self.lower_expr(value)?),
+                    Statement::ret(Expr::Bool(true)),
+                    Statement::Raw(format!("{}:;", Self::resume_label(point))),
+                ])
+            }
+
+            Stmt::Block { body } => Ok(vec![self.lower_block(body)?.scoped()]),
+
+            Stmt::Break => Ok(vec![Statement::Break]),
+
+            Stmt::Continue => Ok(vec![Statement::Continue]),
+
+            Stmt::If { cond, then, other } => {
+                let mut lowered = If::new(self.lower_expr(cond)?).then(self.lower_block(then)?);
+                if let Some(other) = other {
+                    lowered = lowered.other(self.lower_block(other)?);
+                }
+
+                Ok(vec![Statement::If(lowered.build())])
+            }
+
+            Stmt::While {
+                cond,
+                body,
+                do_while: false,
+            } => Ok(vec![Statement::While(
+                While::new(self.lower_expr(cond)?)
+                    .body(self.lower_block(body)?)
+                    .build(),
+            )]),
+
+            Stmt::While {
+                cond,
+                body,
+                do_while: true,
+            } => Ok(vec![Statement::DoWhile(
+                DoWhile::new(self.lower_expr(cond)?)
+                    .body(self.lower_block(body)?)
+                    .build(),
+            )]),
+
+            Stmt::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                let mut lowered = Switch::new(self.lower_expr(value)?);
+                for case in cases {
+                    let (last, rest) = case.values.split_last().expect("a case has a value");
+                    for value in rest {
+                        lowered = lowered.case(self.lower_expr(value)?, Block::new().build());
+                    }
+                    lowered = lowered.case(self.lower_expr(last)?, self.lower_case(&case.body)?);
+                }
+                if let Some(default) = default {
+                    lowered = lowered.default(self.lower_case(default)?);
+                }
+
+                Ok(vec![Statement::Switch(lowered.build())])
+            }
+
+            Stmt::Destroy { expr } if self.darray_elem(expr).is_some() => {
+                Ok(vec![Statement::expr(darray::destroy(
+                    self.lower_expr(expr)?,
+                ))])
+            }
+
+            Stmt::Free { expr } => {
+                let ptr = self.lower_expr(expr)?;
+                let ptr = match self.hoisted_type(expr) {
+                    Some(parser::Type::Str) => alloc::void_ptr(ptr),
+                    _ => ptr,
+                };
+                Ok(vec![Statement::expr(alloc::free(ptr))])
+            }
+
+            Stmt::Defer { .. } | Stmt::Destroy { .. } | Stmt::Destructure { .. } => Err((
+                span.clone(),
+                "This statement is not supported in @statemachine functions yet".to_string(),
+            )),
+        }
+    }
+
+    fn lower_expr(&self, expr: &LocatedExpr) -> Result<Expr, Message> {
+        use parser::Expr::*;
+
+        let Located { node: expr, span } = expr;
+
+        Ok(match expr {
+            Int(i) => Expr::Int(*i),
+            UInt(u) => Expr::UInt(*u),
+            Double(d) => Expr::Double(*d),
+            Bool(b) => Expr::Bool(*b),
+            Char(c) => Expr::Char(*c as char),
+            Str(s) => Expr::Str(escape_c_str(s)),
+            Ident(name) if self.hoisted.contains_key(name.as_str()) => self.field(name),
+            Ident(name) => Expr::new_ident(name.clone()),
+            Binary { left, op, right } => {
+                Expr::new_binary(self.lower_expr(left)?, op.clone(), self.lower_expr(right)?)
+            }
+            Parenthesized { expr } => Expr::new_parenthesized(self.lower_expr(expr)?),
+            Unary { op, expr } => Expr::new_unary(self.lower_expr(expr)?, op.clone()),
+            Assign { lvalue, op, value } => Expr::new_assign(
+                self.lower_expr(lvalue)?,
+                op.clone(),
+                self.lower_expr(value)?,
+            ),
+            Ternary { cond, lexpr, rexpr } => Expr::new_ternary(
+                self.lower_expr(cond)?,
+                self.lower_expr(lexpr)?,
+                self.lower_expr(rexpr)?,
+            ),
+            FnCall {
+                name, args, result, ..
+            } if result.borrow().is_some() => {
+                let t = result.borrow().clone().unwrap();
+                let is_ok = matches!(&name.node, Ident(name) if name == "ok");
+                results::literal(
+                    &lower_type(&t).map_err(|msg| (span.clone(), msg))?,
+                    is_ok,
+                    args.first().map(|arg| self.lower_expr(arg)).transpose()?,
+                )
+            }
+            FnCall { name, args, .. } if is_builtin(name, "append") && args.len() == 2 => {
+                let Unary {
+                    op: UnaryOp::AddrOf,
+                    expr: arr,
+                } = &args[0].node
+                else {
+                    return Err((
+                        args[0].span.clone(),
+                        "'append' expects a pointer to a dynamic array, e.g. 'append(&xs, x)'"
+                            .to_string(),
+                    ));
+                };
+                let elem = self.darray_elem_type(arr)?;
+                darray::append(
+                    self.lower_expr(&args[0])?,
+                    &elem,
+                    self.lower_expr(&args[1])?,
+                )
+            }
+            FnCall { name, args, .. } if is_builtin(name, "approx_eq") && args.len() == 3 => {
+                Expr::new_fn_call_with_name(
+                    "castella_approx_eq".to_string(),
+                    args.iter()
+                        .map(|arg| self.lower_expr(arg))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            FnCall { name, args, .. } if is_builtin(name, "int_to_str") && args.len() == 1 => {
+                conversions::int_to_str(
+                    self.lower_expr(&args[0])?,
+                    self.is_unsigned(&args[0])?,
+                    self.options.location(span),
+                )
+            }
+            FnCall { name, args, .. } if is_builtin(name, "parse_i32") && args.len() == 1 => {
+                conversions::parse_i32(self.lower_expr(&args[0])?)
+            }
+            FnCall { name, args, .. } if is_builtin(name, "len") && args.len() == 1 => {
+                self.darray_elem_type(&args[0])?;
+                darray::len(self.lower_expr(&args[0])?)
+            }
+            FnCall { name, args, .. } => Expr::new_fn_call(
+                self.lower_expr(name)?,
+                args.iter()
+                    .map(|arg| self.lower_expr(arg))
+                    .collect::<Result<_, _>>()?,
+            ),
+            MemAccess { expr, member } => {
+                Expr::new_mem_access(self.lower_expr(expr)?, member.clone())
+            }
+            EnumVarAccess { ident, variant } => Expr::new_ident(format!("{ident}_{variant}")),
+            ModuleAccess { module, member } => {
+                return Err((
+                    span.clone(),
+                    format!("'{module}::{member}' refers to a module that isn't linked"),
+                ));
+            }
+            Tuple { .. } => {
+                return Err((
+                    span.clone(),
+                    "Multiple return values must be lowered before code generation".to_string(),
+                ));
+            }
+            Try { .. } => {
+                return Err((
+                    span.clone(),
+                    "'try' cannot be used in @statemachine functions".to_string(),
+                ));
+            }
+            ArrIndex { arr, idx } if self.darray_elem(arr).is_some() => darray::index(
+                self.lower_expr(arr)?,
+                &self.darray_elem_type(arr)?,
+                self.lower_expr(idx)?,
+                self.options.trap_location(span),
+            ),
+            ArrIndex { arr, idx } => {
+                Expr::new_arr_index(self.lower_expr(arr)?, self.lower_expr(idx)?)
+            }
+            Cast { t, expr, check } => casts::cast(
+                &t.node,
+                self.lower_type(t)?,
+                self.lower_expr(expr)?,
+                check.get(),
+                self.options.cast_location(span),
+            ),
+            Sizeof { t } => Expr::new_sizeof(lower_type(t).map_err(|msg| (span.clone(), msg))?),
+            InitArr { elems } => Expr::new_init_arr_in_order(
+                elems
+                    .iter()
+                    .map(|elem| self.lower_expr(elem))
+                    .collect::<Result<_, _>>()?,
+            ),
+            InitArrDesignated { idxs, elems } => Expr::new_init_arr_designated(
+                idxs.clone(),
+                elems
+                    .iter()
+                    .map(|elem| self.lower_expr(elem))
+                    .collect::<Result<_, _>>()?,
+            ),
+            InitStruct { args, .. } => {
+                let mut names = vec![];
+                let mut values = vec![];
+                for (name, value) in args {
+                    names.push(name.clone());
+                    values.push(self.lower_expr(value)?);
+                }
+                Expr::new_init_struct_designated(names, values)
+            }
+            Make {
+                t: parser::Type::DArray(_),
+            } => darray::make(),
+            New { t } => alloc::new(
+                lower_type(t).map_err(|msg| (span.clone(), msg))?,
+                self.options.location(span),
+            ),
+            Make { .. } => {
+                return Err((
+                    span.clone(),
+                    "This expression is not supported in @statemachine functions yet".to_string(),
+                ));
+            }
+        })
+    }
+
+    /// Returns the type of `expr` if it is a hoisted parameter or local
+    fn hoisted_type(&self, expr: &LocatedExpr) -> Option<&'ast parser::Type> {
+        match &expr.node {
+            parser::Expr::Ident(name) => self.hoisted.get(name.as_str()).copied(),
+            parser::Expr::Parenthesized { expr } => self.hoisted_type(expr),
+            _ => None,
+        }
+    }
+
+    /// Returns the element type of `expr` if it is a hoisted dynamic array
+    fn darray_elem(&self, expr: &LocatedExpr) -> Option<&'ast parser::Type> {
+        match self.hoisted_type(expr) {
+            Some(parser::Type::DArray(elem)) => Some(elem),
+            _ => None,
+        }
+    }
+
+    /// Whether the integer `expr` is unsigned, which is only known for literals and hoisted
+    /// variables
+    fn is_unsigned(&self, expr: &LocatedExpr) -> Result<bool, Message> {
+        match (&expr.node, self.hoisted_type(expr)) {
+            (parser::Expr::Int(_), _) => Ok(false),
+            (parser::Expr::UInt(_), _) => Ok(true),
+            (_, Some(t)) => Ok(t.is_unsigned()),
+            _ => Err((
+                expr.span.clone(),
+                "Expected an integer literal or variable declared in this @statemachine function"
+                    .to_string(),
+            )),
+        }
+    }
+
+    fn darray_elem_type(&self, expr: &LocatedExpr) -> Result<tamago::Type, Message> {
+        let Some(elem) = self.darray_elem(expr) else {
+            return Err((
+                expr.span.clone(),
+                "Expected a dynamic array declared in this @statemachine function".to_string(),
+            ));
+        };
+
+        let lowered = lower_type(elem).map_err(|msg| (expr.span.clone(), msg))?;
+        if lowered.is_array() {
+            return Err((
+                expr.span.clone(),
+                format!("'[^]{elem}' cannot be represented in C yet"),
+            ));
+        }
+
+        Ok(lowered)
+    }
+}
+
+fn is_builtin(name: &LocatedExpr, builtin: &str) -> bool {
+    matches!(&name.node, parser::Expr::Ident(name) if name == builtin)
+}
+
+#[cfg(test)]
+mod tests {
+    use logos::Logos;
+
+    use super::*;
+    use crate::lexer::Token;
+    use crate::parser::{GlobalStmt, Parser};
+
+    fn lower_source(source: &str) -> Result<String, Message> {
+        let ast = Parser::new(Token::lexer(source)).parse().unwrap();
+        let GlobalStmt::Function {
+            name,
+            params,
+            ret,
+            body,
+            ..
+        } = &ast[0].node
+        else {
+            unreachable!()
+        };
+
+        let stmts = StateMachine::new(name, params, ret, body).lower()?;
+        Ok(stmts.iter().map(|stmt| stmt.to_string()).collect())
+    }
+
+    #[test]
+    fn countdown() {
+        let source = r#"
+            @statemachine
+            func countdown(n: i32): i32 {
+                while (n > 0) {
+                    yield n;
+                    n -= 1;
+                }
+            }
+        "#;
+
+        let res = r#"struct countdown_state {
+  int _state;
+  int32_t _value;
+  int32_t n;
+};
+
+struct countdown_state countdown_init(int32_t n) {
+  struct countdown_state self = {._state=0, .n=n};
+  return self;
+}
+
+bool countdown_step(struct countdown_state* self) {
+  switch ((*self)._state) {
+  case -1: {
+    return false;
+  }
+  case 1: {
+    goto resume_1;
+  }
+  }
+
+  while ((*self).n > 0) {
+    (*self)._state = 1;
+    (*self)._value = (*self).n;
+    return true;
+    resume_1:;
+    (*self).n -= 1;
+  }
+  (*self)._state = -1;
+  return false;
+}
+"#;
+
+        assert_eq!(lower_source(source).unwrap(), res);
+    }
+
+    #[test]
+    fn sibling_locals() {
+        let source = "@statemachine func f(c: bool): i32 {\n\
+                      if (c) { let x: i32 = 1; yield x; } else { let x: i32 = 2; yield x; } }";
+        let lowered = lower_source(source).unwrap();
+        assert_eq!(lowered.matches("int32_t x;").count(), 1);
+
+        let source = "@statemachine func f(c: bool): i32 {\n\
+                      if (c) { let x: i32 = 1; yield x; } else { let x: i64 = 2; yield 0; } }";
+        assert_eq!(
+            lower_source(source).unwrap_err().1,
+            "'x' is declared as both 'i32' and 'i64' in this @statemachine function, whose \
+             locals share one struct"
+        );
+    }
+
+    #[test]
+    fn void_statemachine() {
+        let source = "@statemachine func nothing(): void { return; }";
+        assert!(lower_source(source).is_err());
+    }
+}
@@ -0,0 +1,446 @@
+//! Lowers the functions returning multiple values, like `func divmod(a: i32, b: i32): (i32, i32)`,
+//! to functions C can declare
+//!
+//! With `MultipleReturns::Struct`, `divmod` returns a `divmod_result` struct with a field for each
+//! value, `_0` and `_1`, which `let (q, r) = divmod(7, 2);` is destructured from afterwards. With
+//! `MultipleReturns::OutParams`, it returns nothing and stores the values through a pointer
+//! parameter for each instead, so the call becomes `let q: i32; let r: i32; divmod(7, 2, &q, &r);`.
+
+use std::collections::HashMap;
+
+use tamago::{AssignOp, UnaryOp};
+
+use crate::parser::*;
+
+/// How functions returning multiple values are generated (`--multiple-returns`)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MultipleReturns {
+    /// Return a struct with a field for each value
+    #[default]
+    Struct,
+
+    /// Store the values through pointers the caller passes as extra parameters
+    OutParams,
+}
+
+impl MultipleReturns {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "struct" => Some(MultipleReturns::Struct),
+            "out-params" => Some(MultipleReturns::OutParams),
+            _ => None,
+        }
+    }
+}
+
+/// The name of the result struct field or out parameter holding the `i`th value
+pub fn field(i: usize) -> String {
+    format!("_{i}")
+}
+
+fn result_struct(func: &str) -> String {
+    format!("{func}_result")
+}
+
+fn out_param(i: usize) -> String {
+    format!("__out{i}")
+}
+
+/// Lowers the functions returning multiple values in a checked AST, along with their returns and
+/// calls, according to `policy`
+pub fn lower_multiple_returns(ast: &mut Vec<LocatedGlobalStmt>, policy: MultipleReturns) {
+    let results: HashMap<String, Vec<Type>> = ast
+        .iter()
+        .filter_map(|stmt| match &stmt.node {
+            GlobalStmt::Function {
+                name,
+                ret:
+                    Located {
+                        node: Type::Tuple(elems),
+                        ..
+                    },
+                ..
+            } => Some((name.clone(), elems.clone())),
+            _ => None,
+        })
+        .collect();
+
+    if results.is_empty() {
+        return;
+    }
+
+    let mut structs = vec![];
+
+    for stmt in ast.iter_mut() {
+        let GlobalStmt::Function {
+            name,
+            params,
+            ret,
+            body,
+            ..
+        } = &mut stmt.node
+        else {
+            continue;
+        };
+
+        let returns = results.get(name.as_str()).map(|elems| {
+            let span = ret.span.clone();
+            match policy {
+                MultipleReturns::Struct => {
+                    let result = result_struct(name);
+                    structs.push(Located {
+                        node: GlobalStmt::Struct {
+                            name: result.clone(),
+                            fields: elems
+                                .iter()
+                                .enumerate()
+                                .map(|(i, t)| {
+                                    let t = Located {
+                                        node: t.clone(),
+                                        span: span.clone(),
+                                    };
+                                    (field(i), t)
+                                })
+                                .collect(),
+                        },
+                        span: stmt.span.clone(),
+                    });
+                    ret.node = Type::UserDefinedType(result.clone());
+                    Returns::Struct(result)
+                }
+                MultipleReturns::OutParams => {
+                    for (i, t) in elems.iter().enumerate() {
+                        let t = Located {
+                            node: Type::Pointer(Box::new(t.clone())),
+                            span: span.clone(),
+                        };
+                        params.push((out_param(i), t));
+                    }
+                    ret.node = Type::Void;
+                    Returns::OutParams
+                }
+            }
+        });
+
+        let mut lowerer = Lowerer {
+            results: &results,
+            policy,
+            returns,
+            temps: 0,
+        };
+        lowerer.lower_body(body);
+    }
+
+    // The result structs only contain the types of the values, so they can go after every other
+    // type
+    ast.extend(structs);
+}
+
+/// How the function being lowered returns its values
+enum Returns {
+    /// As the struct with the given name
+    Struct(String),
+
+    /// Through the out parameters
+    OutParams,
+}
+
+struct Lowerer<'a> {
+    /// The types of the values each function returning multiple values returns
+    results: &'a HashMap<String, Vec<Type>>,
+
+    policy: MultipleReturns,
+
+    /// How the function being lowered returns, if it returns multiple values
+    returns: Option<Returns>,
+
+    /// The number of temporaries declared for the values of calls
+    temps: usize,
+}
+
+impl Lowerer<'_> {
+    fn lower_body(&mut self, body: &mut Vec<LocatedStmt>) {
+        for stmt in std::mem::take(body) {
+            let Located { mut node, span } = stmt;
+
+            match &mut node {
+                Stmt::Return { value: Some(value) } if matches!(value.node, Expr::Tuple { .. }) => {
+                    let Expr::Tuple { elems } = std::mem::replace(&mut value.node, Expr::Int(0))
+                    else {
+                        unreachable!()
+                    };
+                    self.lower_return(body, span, value.span.clone(), elems);
+                    continue;
+                }
+                Stmt::Return { value: Some(value) } => {
+                    if let Some((func, call)) = self.call(value) {
+                        self.lower_forwarded_return(body, span, func, call);
+                        continue;
+                    }
+                }
+                Stmt::Destructure {
+                    pattern: Pattern::Tuple(names),
+                    value,
+                    is_const,
+                } if self.policy == MultipleReturns::OutParams => {
+                    if let Some((func, call)) = self.call(value) {
+                        let names = std::mem::take(names);
+                        self.lower_destructure(body, span, func, call, names, *is_const);
+                        continue;
+                    }
+                }
+                Stmt::Expression { expr } if self.policy == MultipleReturns::OutParams => {
+                    if let Some((func, call)) = self.call(expr) {
+                        let temps = self.declare_temps(body, &span, &func);
+                        body.push(self.call_with_outs(span, call, &temps));
+                        continue;
+                    }
+                }
+                Stmt::If { then, other, .. } => {
+                    self.lower_body(then);
+                    if let Some(other) = other {
+                        self.lower_body(other);
+                    }
+                }
+                Stmt::While { body, .. } | Stmt::Defer { body } | Stmt::Block { body } => {
+                    self.lower_body(body)
+                }
+                Stmt::Switch { cases, default, .. } => {
+                    for case in cases {
+                        self.lower_body(&mut case.body);
+                    }
+                    if let Some(default) = default {
+                        self.lower_body(default);
+                    }
+                }
+                _ => {}
+            }
+
+            body.push(Located { node, span });
+        }
+    }
+
+    /// `return (a, b);` becomes `return divmod_result { _0 = a, _1 = b };` or
+    /// `^__out0 = a; ^__out1 = b; return;`
+    fn lower_return(
+        &self,
+        body: &mut Vec<LocatedStmt>,
+        span: Span,
+        value_span: Span,
+        elems: Vec<LocatedExpr>,
+    ) {
+        let located = |node| Located {
+            node,
+            span: value_span.clone(),
+        };
+
+        match &self.returns {
+            Some(Returns::Struct(result)) => {
+                let args = elems
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, elem)| (field(i), elem))
+                    .collect();
+                let value = located(Expr::InitStruct {
+                    ident: result.clone(),
+                    args,
+                });
+                body.push(Located {
+                    node: Stmt::Return { value: Some(value) },
+                    span,
+                });
+            }
+            Some(Returns::OutParams) => {
+                for (i, elem) in elems.into_iter().enumerate() {
+                    let out = located(Expr::Unary {
+                        op: UnaryOp::Deref,
+                        expr: Box::new(located(Expr::Ident(out_param(i)))),
+                    });
+                    let assign = Expr::Assign {
+                        lvalue: Box::new(out),
+                        op: AssignOp::Assign,
+                        value: Box::new(elem),
+                    };
+                    body.push(Located {
+                        node: Stmt::Expression {
+                            expr: located(assign),
+                        },
+                        span: span.clone(),
+                    });
+                }
+                body.push(Located {
+                    node: Stmt::Return { value: None },
+                    span,
+                });
+            }
+            None => {
+                unreachable!("the type checker only allows tuples in returns of such functions")
+            }
+        }
+    }
+
+    /// `return divmod(a, 3);` becomes `let (__results0_0, __results0_1) = divmod(a, 3);`
+    /// followed by `return (__results0_0, __results0_1);`, which are lowered like any other
+    fn lower_forwarded_return(
+        &mut self,
+        body: &mut Vec<LocatedStmt>,
+        span: Span,
+        func: String,
+        call: LocatedExpr,
+    ) {
+        let id = self.temps;
+        self.temps += 1;
+
+        let value_span = call.span.clone();
+        let names: Vec<String> = (0..self.results[&func].len())
+            .map(|i| format!("__results{id}_{i}"))
+            .collect();
+        let elems = names
+            .iter()
+            .map(|name| Located {
+                node: Expr::Ident(name.clone()),
+                span: value_span.clone(),
+            })
+            .collect();
+
+        let mut destructure = vec![Located {
+            node: Stmt::Destructure {
+                pattern: Pattern::Tuple(names),
+                value: call,
+                is_const: false,
+            },
+            span: span.clone(),
+        }];
+        self.lower_body(&mut destructure);
+        body.extend(destructure);
+
+        self.lower_return(body, span, value_span, elems);
+    }
+
+    /// `let (q, r) = divmod(7, 2);` becomes `let q: i32; let r: i32; divmod(7, 2, &q, &r);`
+    ///
+    /// Constants can't be assigned through a pointer, so they are initialized from temporaries
+    /// instead.
+    fn lower_destructure(
+        &mut self,
+        body: &mut Vec<LocatedStmt>,
+        span: Span,
+        func: String,
+        call: LocatedExpr,
+        names: Vec<String>,
+        is_const: bool,
+    ) {
+        let outs = if is_const {
+            self.declare_temps(body, &span, &func)
+        } else {
+            for (name, t) in names.iter().zip(&self.results[&func]) {
+                body.push(variable(name.clone(), Some(t.clone()), None, false, &span));
+            }
+            names.clone()
+        };
+
+        body.push(self.call_with_outs(span.clone(), call, &outs));
+
+        if is_const {
+            for (name, temp) in names.into_iter().zip(outs) {
+                let value = Located {
+                    node: Expr::Ident(temp),
+                    span: span.clone(),
+                };
+                body.push(variable(name, None, Some(value), true, &span));
+            }
+        }
+    }
+
+    /// Declares a temporary for each value `func` returns and returns their names
+    fn declare_temps(
+        &mut self,
+        body: &mut Vec<LocatedStmt>,
+        span: &Span,
+        func: &str,
+    ) -> Vec<String> {
+        let call = self.temps;
+        self.temps += 1;
+
+        self.results[func]
+            .iter()
+            .enumerate()
+            .map(|(i, t)| {
+                let temp = format!("__results{call}_{i}");
+                body.push(variable(temp.clone(), Some(t.clone()), None, false, span));
+                temp
+            })
+            .collect()
+    }
+
+    /// Appends the address of each of `outs` to the arguments of `call`
+    fn call_with_outs(&self, span: Span, mut call: LocatedExpr, outs: &[String]) -> LocatedStmt {
+        if let Expr::FnCall { args, .. } = &mut call.node {
+            for out in outs {
+                let located = |node| Located {
+                    node,
+                    span: call.span.clone(),
+                };
+                args.push(located(Expr::Unary {
+                    op: UnaryOp::AddrOf,
+                    expr: Box::new(located(Expr::Ident(out.clone()))),
+                }));
+            }
+        }
+
+        Located {
+            node: Stmt::Expression { expr: call },
+            span,
+        }
+    }
+
+    /// Returns the function returning multiple values that `expr` calls, and the call itself
+    /// without parentheses around it
+    fn call(&self, expr: &mut LocatedExpr) -> Option<(String, LocatedExpr)> {
+        let mut call = &*expr;
+        while let Expr::Parenthesized { expr } = &call.node {
+            call = expr;
+        }
+
+        let Expr::FnCall { name, .. } = &call.node else {
+            return None;
+        };
+        let Expr::Ident(func) = &name.node else {
+            return None;
+        };
+        if !self.results.contains_key(func) {
+            return None;
+        }
+        let func = func.clone();
+
+        let placeholder = Located {
+            node: Expr::Int(0),
+            span: expr.span.clone(),
+        };
+        let mut call = std::mem::replace(expr, placeholder);
+        while let Expr::Parenthesized { expr } = call.node {
+            call = *expr;
+        }
+
+        Some((func, call))
+    }
+}
+
+fn variable(
+    name: String,
+    t: Option<Type>,
+    value: Option<LocatedExpr>,
+    is_const: bool,
+    span: &Span,
+) -> LocatedStmt {
+    Located {
+        node: Stmt::Variable {
+            name,
+            t,
+            value,
+            private: false,
+            is_const,
+        },
+        span: span.clone(),
+    }
+}
@@ -1,100 +1,544 @@
-use colored::{ColoredString, Colorize};
-use logos::Logos;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
 
-mod lexer;
-mod parser;
-mod semantic_analyzer;
+use castella_core::cc::{self, Artifact, CCompiler, CDiagnostic};
+use castella_core::diagnostics::{self, Level};
+use castella_core::lowering::Sanitizers;
+use castella_core::lowering::tuples::MultipleReturns;
+use castella_core::module::ModuleLoader;
+use castella_core::output::OutputPolicy;
+use castella_core::semantic_analyzer::naming::{NamingConventions, NamingRule};
+use castella_core::timings::{Timings, Verbosity};
+use castella_core::{
+    AstStage, CompileOptions, CrateType, Message, abi_json, check, compile, compile_library,
+    dump_ast, dump_tokens, print_ast,
+};
 
-type Message = (parser::Span, String);
+const USAGE: &str = "\
+Usage: castella <command> <file.clla> [options]
 
-fn get_line_number(source: &str, span: parser::Span) -> usize {
-    source[0..span.start].chars().filter(|&c| c == '\n').count() + 1
-}
+Commands:
+  build    Compile the file to C, written next to it unless '-o' is given
+  check    Report the errors and warnings of the file without generating C
+  run      Compile the file to an executable with the C compiler and run it
 
-#[inline]
-fn show_message(
-    source: &str,
-    source_path: &str,
-    span: parser::Span,
-    pre: ColoredString,
-    msg: String,
-) {
-    eprintln!(
-        "{}({}) {}: {}",
-        source_path,
-        get_line_number(source, span),
-        pre,
-        msg
-    );
-}
-
-fn show_errors(source: &str, source_path: &str, errors: Vec<Message>) {
+Options:
+  -o, --output <file>     Write the generated C to <file>, or to stdout if it is '-'
+  --emit <kind>           Generate 'c' (the default), or compile the C to an 'obj' file or a
+                          'bin' with the C compiler, or generate 'abi-json' to describe the
+                          types and functions of the C for binding generators, or print the
+                          'tokens' or the 'ast' as a tree to inspect the compiler
+  --bin                   The same as '--emit bin'
+  --cc <compiler>         Compile the C with <compiler> instead of $CC (or 'cc')
+  -I <dir>                Let the C compiler look for headers in <dir>
+  -L <dir>                Let the C compiler look for libraries in <dir>
+  -l <lib>                Link the library <lib> into the binary
+  --no-line-directives    Don't point the C compiler and debuggers at the source with '#line'
+                          directives
+  --crate-type <type>     Build a 'bin' (the default), or a 'lib', which needs no entry
+                          point and also gets a header written next to the output
+  --entry <name>          Start the program at the function <name> instead of 'main'
+  --bounds-checks         Check indexes into dynamic arrays at runtime
+  --checked-casts         Check that casts which may lose information don't, unless the C
+                          is compiled with NDEBUG
+  --sanitize <list>       Compile with the sanitizers 'address' and 'undefined' and report
+                          runtime traps to them, which C built by hand needs '-fsanitize' for
+  --sysroot <dir>         Look for the standard library in <dir> instead of using the copy
+                          built into the compiler
+  --dump-ast              Print every field of the AST instead of compiling
+  --after <stage>         The stage to print the AST after, 'parse' or 'lowering'
+  --max-errors <n>        Show at most <n> errors
+  --no-naming <rule>      Don't warn about names of 'types', 'functions', 'variables',
+                          'constants', or 'all' that break the naming conventions
+  --naming-prefix <p>     Don't check the naming conventions of names starting with <p>
+  --multiple-returns <p>  Return multiple values as a 'struct' (the default) or through
+                          'out-params'
+  --no-color              Don't color the output
+  --timings               Print how long each phase took
+  --verbose               Print each phase as it finishes
+  -h, --help              Print this message";
+
+/// The flags followed by a value, which is not a positional argument
+const VALUE_FLAGS: &[&str] = &[
+    "-o",
+    "--output",
+    "--emit",
+    "--crate-type",
+    "--entry",
+    "--cc",
+    "-I",
+    "-L",
+    "-l",
+    "--sysroot",
+    "--after",
+    "--max-errors",
+    "--no-naming",
+    "--naming-prefix",
+    "--multiple-returns",
+    "--sanitize",
+];
+
+/// The exit code of invalid command lines, as opposed to programs that fail to compile
+const USAGE_ERROR: u8 = 2;
+
+fn show_errors(output: &OutputPolicy, source: &str, source_path: &str, errors: Vec<Message>) {
+    let (errors, omitted) = output.limit_errors(errors);
     for e in errors {
-        show_message(source, source_path, e.0, "Error".red(), e.1);
+        eprint!("{}", diagnostics::render(source, source_path, Level::Error, &e));
+    }
+    if omitted > 0 {
+        eprintln!("{omitted} more error(s) not shown");
     }
 }
 
 fn show_warnings(source: &str, source_path: &str, warnings: Vec<Message>) {
     for w in warnings {
-        show_message(source, source_path, w.0, "Warning".yellow(), w.1);
+        eprint!("{}", diagnostics::render(source, source_path, Level::Warning, &w));
+    }
+}
+
+/// Returns the value given to `--flag value` or `--flag=value`
+fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == flag {
+            return iter.next().map(String::as_str);
+        }
+        if let Some(value) = arg.strip_prefix(flag).and_then(|a| a.strip_prefix('=')) {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Returns every value given to a flag that can be repeated
+fn arg_values<'a>(args: &'a [String], flag: &str) -> Vec<&'a str> {
+    let mut res = vec![];
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == flag {
+            res.extend(iter.next().map(String::as_str));
+        } else if let Some(value) = arg.strip_prefix(flag).and_then(|a| a.strip_prefix('=')) {
+            res.push(value);
+        }
+    }
+    res
+}
+
+/// Returns every value given to a C compiler flag like `-I`, which can be given as `-I dir` or
+/// `-Idir`
+fn c_flag_values<'a>(args: &'a [String], flag: &str) -> Vec<&'a str> {
+    let mut res = vec![];
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == flag {
+            res.extend(iter.next().map(String::as_str));
+        } else if let Some(value) = arg.strip_prefix(flag) {
+            res.push(value);
+        }
     }
+    res
 }
 
-fn compile(source: &str) -> (Vec<Message>, Result<(), Vec<Message>>) {
-    let mut warnings: Vec<Message> = vec![];
+/// Returns the C compiler given to `--cc`, or else `$CC` or `cc`, along with the flags passed
+/// through to it and the `sanitizers` the C is compiled with
+fn c_compiler(args: &[String], sanitizers: Sanitizers) -> CCompiler {
+    let mut cc = match arg_value(args, "--cc") {
+        Some(program) => CCompiler::new(program),
+        None => CCompiler::detect(),
+    };
+    for dir in c_flag_values(args, "-I") {
+        cc = cc.include_dir(dir);
+    }
+    for dir in c_flag_values(args, "-L") {
+        cc = cc.lib_dir(dir);
+    }
+    for lib in c_flag_values(args, "-l") {
+        cc = cc.lib(lib);
+    }
+    cc.sanitizers(sanitizers)
+}
 
-    let lexer = lexer::Token::lexer(source);
+/// Returns the naming conventions left on by `--no-naming` along with the `--naming-prefix`es
+fn naming_conventions(args: &[String]) -> Result<NamingConventions, String> {
+    let mut naming = NamingConventions::default();
 
-    let ast: Vec<parser::LocatedGlobalStmt>;
-    match parser::Parser::new(lexer).parse() {
-        Ok(res) => ast = res,
-        Err(errs) => return (warnings, Err(errs)),
+    for name in arg_values(args, "--no-naming") {
+        let rules = match name {
+            "all" => NamingRule::ALL.to_vec(),
+            _ => vec![NamingRule::from_name(name).ok_or_else(|| {
+                format!(
+                    "Unknown naming rule '{name}', expected 'types', 'functions', 'variables', \
+                     'constants', or 'all'"
+                )
+            })?],
+        };
+        naming = rules
+            .into_iter()
+            .fold(naming, |naming, rule| naming.rule(rule, false));
     }
 
-    match semantic_analyzer::resolver::Resolver::new(&ast).resolve() {
-        (mut w, Ok(())) => warnings.append(&mut w),
-        (mut w, Err(errs)) => {
-            warnings.append(&mut w);
-            return (warnings, Err(errs));
+    Ok(arg_values(args, "--naming-prefix")
+        .into_iter()
+        .fold(naming, NamingConventions::allow_prefix))
+}
+
+/// Returns the arguments that are neither flags nor the values of flags
+fn positionals(args: &[String]) -> Vec<&str> {
+    let mut res = vec![];
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if VALUE_FLAGS.contains(&arg.as_str()) {
+            iter.next();
+        } else if !arg.starts_with('-') || arg == "-" {
+            res.push(arg.as_str());
         }
     }
+    res
+}
+
+fn usage_error(msg: &str) -> ExitCode {
+    eprintln!("{msg}\n\n{USAGE}");
+    ExitCode::from(USAGE_ERROR)
+}
+
+/// What `build` outputs (`--emit`)
+#[derive(Clone, Copy, PartialEq)]
+enum Emit {
+    C,
+    Obj,
+    Bin,
+    AbiJson,
+    Tokens,
+    Ast,
+}
 
-    match semantic_analyzer::type_checker::TypeChecker::new(&ast).check() {
-        (mut w, Ok(())) => warnings.append(&mut w),
-        (mut w, Err(errs)) => {
-            warnings.append(&mut w);
-            return (warnings, Err(errs));
+impl Emit {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "c" => Some(Self::C),
+            "obj" => Some(Self::Obj),
+            "bin" => Some(Self::Bin),
+            "abi-json" => Some(Self::AbiJson),
+            "tokens" => Some(Self::Tokens),
+            "ast" => Some(Self::Ast),
+            _ => None,
         }
     }
+}
 
-    todo!()
+/// The file the C of `input` is written to by default, e.g. `src/main.c` for `src/main.clla`,
+/// `src/main` for its binary, or `src/main.abi.json` for its ABI description
+fn default_output(input: &Path, emit: Emit) -> PathBuf {
+    let extension = match emit {
+        Emit::AbiJson => "abi.json",
+        Emit::Obj => "o",
+        Emit::Bin => std::env::consts::EXE_EXTENSION,
+        _ => "c",
+    };
+    input.with_extension(extension)
 }
 
-fn main() {
-    // println!("filepath(line) Error: ");
+/// Returns the file given to `-o`, or else `default`, or `None` for stdout if that is '-' or
+/// there is no default
+fn output_path(args: &[String], default: Option<PathBuf>) -> Option<PathBuf> {
+    match arg_value(args, "-o").or_else(|| arg_value(args, "--output")) {
+        Some("-") => None,
+        Some(out) => Some(PathBuf::from(out)),
+        None => default,
+    }
+}
 
-    // let source = "func main(): void { return (1 + 1.0) * 3; }";
-    // let source = "func main(): void { age; Person { name = \"Nobu\", age = 18 }; }";
-    let source = "func abc(): void {} func main(): void { free dhdbc; }";
-    // let source = "enum Week { Mon; Tue; Wed; Thur=4; Fri; }";
-    // let source = "alias Age = int; union Person { name: str; age: Age; }";
+/// Compiles the C `code` to `out` with `cc`, showing what it reports at the source as castella
+/// does its own diagnostics, and returns whether it succeeded
+fn compile_c(
+    cc: &CCompiler,
+    code: &str,
+    artifact: Artifact,
+    out: &Path,
+    source: &str,
+    source_path: &str,
+) -> bool {
+    let output = match cc.compile(code, artifact, out) {
+        Ok(output) => output,
+        Err(msg) => {
+            eprintln!("{msg}");
+            return false;
+        }
+    };
 
-    let lexer = lexer::Token::lexer(source);
-    let res = parser::Parser::new(lexer).parse();
-    if let Err(err) = res {
-        show_errors(source, "main.clla", err);
-        return;
+    for diagnostic in cc::map_diagnostics(&output.stderr, source, source_path) {
+        match diagnostic {
+            CDiagnostic::Mapped(level, msg) => {
+                eprint!("{}", diagnostics::render(source, source_path, level, &msg));
+            }
+            CDiagnostic::Unmapped(line) => eprintln!("{line}"),
+        }
     }
+    if !output.status.success() {
+        eprintln!("'{}' failed ({})", cc.program(), output.status);
+    }
+    output.status.success()
+}
 
-    let a = semantic_analyzer::resolver::Resolver::new(&res.unwrap()).resolve();
+/// Runs the binary `exe`, and exits with its code
+fn run_binary(exe: &Path) -> ExitCode {
+    match Command::new(exe).status() {
+        Ok(status) => match status.code() {
+            Some(code) => ExitCode::from(code as u8),
+            // Killed by a signal, which the shell reports
+            None => ExitCode::FAILURE,
+        },
+        Err(err) => {
+            eprintln!("Cannot run '{}': {err}", exe.display());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Writes `generated` to the file given to `-o`, or else to `default`, and to stdout if that is
+/// '-' or there is no default
+fn write_output(args: &[String], generated: &str, default: Option<PathBuf>) -> ExitCode {
+    let Some(out) = output_path(args, default) else {
+        print!("{generated}");
+        return ExitCode::SUCCESS;
+    };
+    if let Err(err) = std::fs::write(&out, generated) {
+        eprintln!("Cannot write '{}': {err}", out.display());
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+
+    let mut output = OutputPolicy::detect();
+    if args.iter().any(|arg| arg == "--no-color") {
+        output = output.color(false);
+    }
+    if let Some(max) = arg_value(&args, "--max-errors") {
+        match max.parse() {
+            Ok(max) => output = output.max_errors(max),
+            Err(_) => {
+                return usage_error(&format!(
+                    "Expected a number for '--max-errors' but got '{max}'"
+                ));
+            }
+        }
+    }
+    output.apply();
+
+    let (command, input) = match positionals(&args)[..] {
+        [command @ ("build" | "check" | "run"), input] => (command, Path::new(input)),
+        [command @ ("build" | "check" | "run")] => {
+            return usage_error(&format!("'{command}' expects a source file"));
+        }
+        [command, ..] if !["build", "check", "run"].contains(&command) => {
+            return usage_error(&format!("Unknown command '{command}'"));
+        }
+        _ => return usage_error("Expected a single source file"),
+    };
+
+    let source = match std::fs::read_to_string(input) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("Cannot read '{}': {err}", input.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let source_path = input.display().to_string();
+
+    // Imports are relative to the file that imports them
+    let root = input.parent().unwrap_or(Path::new("."));
+    let mut loader = ModuleLoader::new(root);
+    if let Some(sysroot) = arg_value(&args, "--sysroot") {
+        loader = loader.sysroot(sysroot);
+    }
+    let naming = match naming_conventions(&args) {
+        Ok(naming) => naming,
+        Err(msg) => return usage_error(&msg),
+    };
+    let multiple_returns = match arg_value(&args, "--multiple-returns") {
+        None => MultipleReturns::default(),
+        Some(policy) => match MultipleReturns::from_name(policy) {
+            Some(policy) => policy,
+            None => {
+                return usage_error(&format!(
+                    "Unknown policy '{policy}', expected 'struct' or 'out-params'"
+                ));
+            }
+        },
+    };
+    let sanitizers = match arg_value(&args, "--sanitize").map(Sanitizers::from_names) {
+        None => Sanitizers::default(),
+        Some(Ok(sanitizers)) => sanitizers,
+        Some(Err(msg)) => return usage_error(&msg),
+    };
+    let emit = match arg_value(&args, "--emit") {
+        None if args.iter().any(|arg| arg == "--bin") => Emit::Bin,
+        None => Emit::C,
+        Some(name) => match Emit::from_name(name) {
+            Some(emit) => emit,
+            None => {
+                return usage_error(&format!(
+                    "Unknown output '{name}', expected 'c', 'obj', 'bin', 'abi-json', 'tokens', or \
+                     'ast'"
+                ));
+            }
+        },
+    };
+    let crate_type = match arg_value(&args, "--crate-type") {
+        None => CrateType::default(),
+        Some(name) => match CrateType::from_name(name) {
+            Some(crate_type) => crate_type,
+            None => {
+                return usage_error(&format!(
+                    "Unknown crate type '{name}', expected 'bin' or 'lib'"
+                ));
+            }
+        },
+    };
+    let options = CompileOptions {
+        file: source_path.clone(),
+        bounds_checks: args.iter().any(|arg| arg == "--bounds-checks"),
+        checked_casts: args.iter().any(|arg| arg == "--checked-casts"),
+        loader,
+        naming,
+        multiple_returns,
+        sanitizers,
+        crate_type,
+        entry: arg_value(&args, "--entry").unwrap_or("main").to_string(),
+        line_directives: !args.iter().any(|arg| arg == "--no-line-directives"),
+    };
+    if crate_type == CrateType::Lib && (emit == Emit::Bin || command == "run") {
+        return usage_error("A library has no entry point, so it can only be built to C or 'obj'");
+    }
 
-    if let (w, Err(err)) = a {
-        show_warnings(source, "main.clla", w);
-        show_errors(source, "main.clla", err);
+    let stage = match arg_value(&args, "--after") {
+        None | Some("parse") => AstStage::Parse,
+        Some("lowering") => AstStage::Lowering,
+        Some(other) => {
+            return usage_error(&format!(
+                "Unknown stage '{other}', expected 'parse' or 'lowering'"
+            ));
+        }
+    };
+
+    // The tokens are shown even for source that doesn't lex, since that is when they help most
+    if emit == Emit::Tokens {
+        return write_output(&args, &dump_tokens(&source), None);
+    }
+
+    let full_dump = args.iter().any(|arg| arg == "--dump-ast");
+    if full_dump || emit == Emit::Ast {
+        let dump = if full_dump {
+            dump_ast(&source, &options, stage).map(|dump| format!("{dump}\n"))
+        } else {
+            print_ast(&source, &options, stage)
+        };
+        return match dump {
+            Ok(dump) => write_output(&args, &dump, None),
+            Err(diagnostics) => {
+                show_warnings(&source, &source_path, diagnostics.warnings);
+                show_errors(&output, &source, &source_path, diagnostics.errors);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    if command == "check" {
+        let (warnings, res) = check(&source, &options);
+        show_warnings(&source, &source_path, warnings);
+        return match res {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(errors) => {
+                show_errors(&output, &source, &source_path, errors);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let mut timings = Timings::new(Verbosity::from_args(&args));
+
+    let mut header = None;
+    let (warnings, res) = if emit == Emit::AbiJson {
+        abi_json(&source, &options)
+    } else if crate_type == CrateType::Lib {
+        let (warnings, res) = compile_library(&source, &options, &mut timings);
+        let res = res.map(|library| {
+            header = Some(library.header);
+            library.c
+        });
+        (warnings, res)
     } else {
-        let a = a.0;
-        show_warnings(source, "main.clla", a);
+        compile(&source, &options, &mut timings)
+    };
+    show_warnings(&source, &source_path, warnings);
+
+    let generated = match res {
+        Ok(generated) => generated,
+        Err(errors) => {
+            timings.report();
+            show_errors(&output, &source, &source_path, errors);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let out = output_path(&args, Some(default_output(input, emit)));
+
+    // The header goes next to the C or object file, and isn't written when the C is printed
+    if let (Some(header), Some(out)) = (header, &out) {
+        let path = out.with_extension("h");
+        if let Err(err) = std::fs::write(&path, header) {
+            eprintln!("Cannot write '{}': {err}", path.display());
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if command == "run" {
+        let exe = std::env::temp_dir().join(format!(
+            "castella-run-{}{}",
+            std::process::id(),
+            std::env::consts::EXE_SUFFIX
+        ));
+        let cc = c_compiler(&args, sanitizers);
+        let artifact = Artifact::Executable;
+        let compiled = timings.time("cc", || {
+            compile_c(&cc, &generated, artifact, &exe, &source, &source_path)
+        });
+        timings.report();
+        if !compiled {
+            return ExitCode::FAILURE;
+        }
+        let code = run_binary(&exe);
+        let _ = std::fs::remove_file(&exe);
+        return code;
+    }
+
+    if let Emit::Obj | Emit::Bin = emit {
+        let Some(out) = out else {
+            return usage_error("An object file or binary cannot be written to stdout");
+        };
+        let artifact = match emit {
+            Emit::Obj => Artifact::Object,
+            _ => Artifact::Executable,
+        };
+        let cc = c_compiler(&args, sanitizers);
+        let compiled = timings.time("cc", || {
+            compile_c(&cc, &generated, artifact, &out, &source, &source_path)
+        });
+        timings.report();
+        return match compiled {
+            true => ExitCode::SUCCESS,
+            false => ExitCode::FAILURE,
+        };
     }
 
-    // println!("{:#?}", a);
+    timings.report();
+    write_output(&args, &generated, out)
 }
@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use logos::Logos;
+
+use crate::lexer::Token;
+use crate::parser::{GlobalStmt, Located, LocatedGlobalStmt, Parser, Span};
+use crate::semantic_analyzer::const_exprs;
+
+/// Could be either a warning or an error
+type Message = (Span, String);
+
+/// The file extension of castella source files
+const EXTENSION: &str = "clla";
+
+/// The prefix of import paths that are resolved against the sysroot instead of the project root
+const STD_PREFIX: &str = "std/";
+
+/// The standard library modules built into the compiler, which are used without a sysroot and
+/// by `ModuleLoader::bundled_std`
+const BUNDLED_STD: &[(&str, &str)] = &[
+    ("std/io", include_str!("../std/io.clla")),
+    ("std/math", include_str!("../std/math.clla")),
+    ("std/mem", include_str!("../std/mem.clla")),
+];
+
+/// A parsed module that has been imported
+#[derive(Debug)]
+pub struct Module {
+    /// The path the module was imported with (e.g. `std/io`)
+    pub path: String,
+    pub ast: Vec<LocatedGlobalStmt>,
+
+    /// The modules it imports itself, keyed by the name they are imported as
+    pub modules: HashMap<String, Module>,
+}
+
+impl Module {
+    /// Finds the global statement that declares `name`, imports excluded since they aren't
+    /// re-exported
+    pub fn find(&self, name: &str) -> Option<&LocatedGlobalStmt> {
+        self.ast
+            .iter()
+            .find(|stmt| exported_name(stmt) == Some(name))
+    }
+
+    /// Returns the names declared by the module
+    pub fn exports(&self) -> impl Iterator<Item = &str> {
+        self.ast.iter().filter_map(exported_name)
+    }
+}
+
+fn exported_name(stmt: &LocatedGlobalStmt) -> Option<&str> {
+    use GlobalStmt::*;
+
+    match &stmt.node {
+        Enum { name, .. }
+        | Struct { name, .. }
+        | Union { name, .. }
+        | Function { name, .. }
+        | ExternFunction { name, .. }
+        | Alias { name, .. } => Some(name),
+        Variable { name, private, .. } | Constant { name, private, .. } => {
+            (!private).then_some(name.as_str())
+        }
+        Import { .. } => None,
+    }
+}
+
+/// Locates and parses the modules imported by a program
+#[derive(Debug)]
+pub struct ModuleLoader {
+    /// The directory containing the `std` modules, or `None` if they come from the copies built
+    /// into the compiler
+    sysroot: Option<PathBuf>,
+
+    /// The directory non-std imports are resolved against
+    root: PathBuf,
+
+    /// Sources given in memory, keyed by import path, which are used instead of reading files
+    sources: HashMap<String, String>,
+}
+
+impl ModuleLoader {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            sysroot: std::env::var_os("CASTELLA_SYSROOT").map(PathBuf::from),
+            root: root.into(),
+            sources: HashMap::new(),
+        }
+    }
+
+    /// Provides the source of the module imported as `path` in memory, so that it isn't read
+    /// from the filesystem
+    pub fn source(mut self, path: impl Into<String>, source: impl Into<String>) -> Self {
+        self.sources.insert(path.into(), source.into());
+        self
+    }
+
+    /// Overrides the sysroot (`--sysroot`)
+    pub fn sysroot(mut self, sysroot: impl Into<PathBuf>) -> Self {
+        self.sysroot = Some(sysroot.into());
+        self
+    }
+
+    /// Provides the standard library modules from the copies built into the compiler, for
+    /// environments without a filesystem (e.g. WASM)
+    pub fn bundled_std(self) -> Self {
+        BUNDLED_STD
+            .iter()
+            .fold(self, |loader, (path, source)| loader.source(*path, *source))
+    }
+
+    /// Returns the file an import path refers to, or `None` for a `std` module without a
+    /// sysroot (`--sysroot` or `CASTELLA_SYSROOT`), which comes from the copies built into the
+    /// compiler instead
+    pub fn locate(&self, path: &str) -> Option<PathBuf> {
+        let dir: &Path = if path.starts_with(STD_PREFIX) {
+            self.sysroot.as_ref()?
+        } else {
+            &self.root
+        };
+
+        Some(dir.join(path).with_extension(EXTENSION))
+    }
+
+    /// Loads every module imported by `ast`, along with the modules they import, keyed by the
+    /// name it is imported as
+    pub fn load_imports(
+        &self,
+        ast: &[LocatedGlobalStmt],
+    ) -> (HashMap<String, Module>, Vec<Message>) {
+        self.load_imports_of(ast, &mut vec![])
+    }
+
+    /// Loads the imports of `ast`, where `importers` are the paths of the modules being loaded
+    /// that (indirectly) import it, to detect cycles
+    fn load_imports_of(
+        &self,
+        ast: &[LocatedGlobalStmt],
+        importers: &mut Vec<String>,
+    ) -> (HashMap<String, Module>, Vec<Message>) {
+        let mut modules = HashMap::new();
+        let mut errors = vec![];
+
+        for stmt in ast {
+            let Located {
+                node: GlobalStmt::Import { name, path, .. },
+                span,
+            } = stmt
+            else {
+                continue;
+            };
+
+            if modules.contains_key(name) {
+                // Reported as a redeclaration by the resolver
+                continue;
+            }
+
+            if importers.contains(path) {
+                let cycle = importers
+                    .iter()
+                    .skip_while(|importer| *importer != path)
+                    .chain([path])
+                    .map(|path| format!("'{path}'"))
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+                errors.push((span.clone(), format!("Import cycle: {cycle}")));
+                continue;
+            }
+
+            match self.load(path) {
+                Ok(mut module) => {
+                    importers.push(path.clone());
+                    let (imported, errs) = self.load_imports_of(&module.ast, importers);
+                    importers.pop();
+
+                    // Errors inside the module are reported at the import that brought it in
+                    errors.extend(errs.into_iter().map(|(_, msg)| (span.clone(), msg)));
+                    module.modules = imported;
+                    modules.insert(name.clone(), module);
+                }
+                Err(msg) => errors.push((span.clone(), msg)),
+            }
+        }
+
+        (modules, errors)
+    }
+
+    fn load(&self, path: &str) -> Result<Module, String> {
+        let source = match (self.sources.get(path), self.locate(path)) {
+            (Some(source), _) => source.clone(),
+            (None, Some(file)) => std::fs::read_to_string(&file).map_err(|err| {
+                format!("Cannot find module '{path}' at '{}': {err}", file.display())
+            })?,
+            (None, None) => {
+                let path = path.strip_suffix(".clla").unwrap_or(path);
+                let Some((_, source)) = BUNDLED_STD.iter().find(|(std, _)| *std == path) else {
+                    return Err(format!("There is no standard library module '{path}'"));
+                };
+                source.to_string()
+            }
+        };
+
+        let mut ast = Parser::new(Token::lexer(&source)).parse().map_err(|errs| {
+            let (_, msg) = &errs[0];
+            format!("Failed to parse module '{path}': {msg}")
+        })?;
+        if let Some((_, msg)) = const_exprs::evaluate(&mut ast).first() {
+            return Err(format!("In module '{path}': {msg}"));
+        }
+
+        Ok(Module {
+            path: path.to_string(),
+            ast,
+            modules: HashMap::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn std_modules() {
+        let loader = ModuleLoader::new(".").sysroot(env!("CARGO_MANIFEST_DIR"));
+
+        for (path, expected) in [
+            ("std/io", vec!["println"]),
+            ("std/mem", vec!["copy"]),
+            ("std/math", vec!["abs", "min", "max"]),
+        ] {
+            let module = loader.load(path).unwrap();
+            for name in expected {
+                assert!(module.find(name).is_some(), "'{name}' is not in '{path}'");
+            }
+        }
+
+        assert!(loader.load("std/nonexistent").is_err());
+
+        let bundled = ModuleLoader::new("/nonexistent").bundled_std();
+        for (path, _) in BUNDLED_STD {
+            assert!(bundled.load(path).is_ok(), "'{path}' is not bundled");
+        }
+
+        // Without a sysroot, the std modules come from the copies built into the compiler
+        let loader = ModuleLoader {
+            sysroot: None,
+            ..ModuleLoader::new("/nonexistent")
+        };
+        for (path, _) in BUNDLED_STD {
+            assert!(loader.load(path).is_ok(), "'{path}' is not bundled");
+        }
+        assert!(loader.load("std/mem.clla").is_ok());
+        assert_eq!(
+            loader.load("std/nonexistent").unwrap_err(),
+            "There is no standard library module 'std/nonexistent'"
+        );
+    }
+
+    #[test]
+    fn nested_imports() {
+        let loader = ModuleLoader::new("/nonexistent")
+            .source("a", "import \"b\";")
+            .source("b", "import c \"c.clla\";")
+            .source("c.clla", "import \"a\";")
+            .source("d", "import \"d\";")
+            .source("x", "import \"y\";")
+            .source("y", "func f(): void {}");
+
+        let parse = |source: &str| Parser::new(Token::lexer(source)).parse().unwrap();
+
+        let (modules, errors) = loader.load_imports(&parse("import \"x\";"));
+        assert!(errors.is_empty());
+        assert!(modules["x"].modules["y"].find("f").is_some());
+
+        let (_, errors) = loader.load_imports(&parse("import \"a\";\nimport \"d\";"));
+        assert_eq!(
+            errors,
+            [
+                (
+                    0..6,
+                    "Import cycle: 'a' -> 'b' -> 'c.clla' -> 'a'".to_string()
+                ),
+                (12..18, "Import cycle: 'd' -> 'd'".to_string()),
+            ]
+        );
+    }
+}
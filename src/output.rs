@@ -0,0 +1,98 @@
+use std::io::IsTerminal;
+
+use crate::Message;
+use crate::diagnostics;
+
+/// How the driver prints diagnostics: whether to use colors and how many errors to show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputPolicy {
+    color: bool,
+    max_errors: Option<usize>,
+}
+
+impl OutputPolicy {
+    /// Colors the output only if stderr is a terminal and `NO_COLOR` isn't set to a non-empty
+    /// value (<https://no-color.org>), and shows every error
+    pub fn detect() -> Self {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        Self {
+            color: std::io::stderr().is_terminal() && !no_color,
+            max_errors: None,
+        }
+    }
+
+    /// Sets whether to color the output (`--no-color` turns it off)
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Shows at most `max` errors (`--max-errors`), or every error if `max` is 0
+    pub fn max_errors(mut self, max: usize) -> Self {
+        self.max_errors = (max != 0).then_some(max);
+        self
+    }
+
+    pub fn is_colored(&self) -> bool {
+        self.color
+    }
+
+    /// Makes everything printed from now on follow the policy
+    pub fn apply(&self) {
+        #[cfg(feature = "color")]
+        colored::control::set_override(self.color);
+    }
+
+    /// Keeps the first errors up to the limit along with their notes and help, and returns how
+    /// many errors were left out
+    pub fn limit_errors(&self, errors: Vec<Message>) -> (Vec<Message>, usize) {
+        let Some(max) = self.max_errors else {
+            return (errors, 0);
+        };
+
+        let mut shown = vec![];
+        let mut count = 0;
+        for error in errors {
+            if !diagnostics::is_attached(&error.1) {
+                count += 1;
+            }
+            if count <= max {
+                shown.push(error);
+            }
+        }
+
+        (shown, count.saturating_sub(max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_policy() {
+        let errors = vec![
+            (0..1, "a".to_string()),
+            diagnostics::note(1..2, "about a"),
+            (2..3, "b".to_string()),
+            diagnostics::note(3..4, "about b"),
+            diagnostics::help(3..4, "fix b"),
+            (4..5, "c".to_string()),
+        ];
+
+        let policy = OutputPolicy::detect().color(false);
+        assert!(!policy.is_colored());
+        assert_eq!(policy.limit_errors(errors.clone()), (errors.clone(), 0));
+
+        let (shown, omitted) = policy.max_errors(1).limit_errors(errors.clone());
+        assert_eq!(shown, errors[..2]);
+        assert_eq!(omitted, 2);
+
+        let (shown, omitted) = policy.max_errors(2).limit_errors(errors.clone());
+        assert_eq!(shown, errors[..5]);
+        assert_eq!(omitted, 1);
+
+        let (shown, omitted) = policy.max_errors(0).limit_errors(errors.clone());
+        assert_eq!((shown.len(), omitted), (6, 0));
+    }
+}
@@ -1,8 +1,12 @@
 use logos::Lexer;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::ops::Range;
 use tamago::{AssignOp, BinOp, UnaryOp};
 
+use crate::diagnostics;
 use crate::lexer::*;
+use crate::semantic_analyzer::edit_distance;
 
 macro_rules! expect {
     ($self: expr, $current: expr, $expected: pat, $line_num: expr, $msg: expr, $($f: expr),*) => {{
@@ -22,6 +26,17 @@ macro_rules! expect {
     }};
 }
 
+/// The keywords that start a global statement, suggested for misspelled ones
+const GLOBAL_KEYWORDS: &[&str] = &[
+    "enum", "struct", "union", "func", "extern", "let", "const", "alias", "import", "from",
+];
+
+/// The keywords that start a statement in a function body, suggested for misspelled ones
+const STMT_KEYWORDS: &[&str] = &[
+    "let", "const", "return", "break", "continue", "yield", "if", "while", "do", "switch",
+    "defer", "destroy", "free",
+];
+
 pub type ParseError = (Span, String);
 pub type ParseErrors = Vec<ParseError>;
 
@@ -67,15 +82,19 @@ impl<'source> Parser<'source> {
             Token::Enum => self.parse_enum(),
             Token::Struct => self.parse_struct(),
             Token::Union => self.parse_union(),
-            Token::Func => self.parse_func(),
+            Token::Func => self.parse_func(vec![]),
+            Token::At => self.parse_attributed_func(),
+            Token::Extern => self.parse_extern_func(),
             Token::Let => self.parse_let(),
             Token::Const => self.parse_const(),
             Token::Alias => self.parse_alias(),
             Token::Import => self.parse_import(),
-            _ => Err(self.create_error(format!(
-                "Expected a global statement but got {}",
-                self.current()?
-            ))),
+            Token::From => self.parse_from_import(),
+            t => Err(self.create_error_suggesting(
+                format!("Expected a global statement but got {t}"),
+                &t,
+                GLOBAL_KEYWORDS,
+            )),
         }
     }
 
@@ -109,6 +128,7 @@ impl<'source> Parser<'source> {
         self.next();
 
         let mut variants = vec![];
+        let mut values = vec![];
 
         while !matches!(self.current()?, Token::RightBrace) {
             let Token::Ident(var_name) = expect!(
@@ -126,19 +146,31 @@ impl<'source> Parser<'source> {
 
             let var_num = if matches!(self.current()?, Token::Eq) {
                 self.next();
-                let Token::Int(num) = expect!(
-                    self,
-                    self.current()?,
-                    Token::Int(..),
-                    self.lexer.span(),
-                    "Expected an integer but got {}",
-                    self.current()?
-                ) else {
-                    unreachable!();
-                };
-                self.next();
-
-                Some(num)
+                match self.parse_expression()? {
+                    Located {
+                        node: Expr::Int(num),
+                        ..
+                    } => Some(num),
+                    Located {
+                        node: Expr::UInt(num),
+                        span,
+                    } => {
+                        return Err(self.create_error_with_line_num(
+                            format!(
+                                "Enum value '{num}' is out of range, it must be between {} and {}",
+                                i64::MIN,
+                                i64::MAX
+                            ),
+                            span,
+                        ));
+                    }
+                    // Any other value is a constant expression, like `sq(2)`, which is evaluated
+                    // before type checking
+                    value => {
+                        values.push((variants.len(), value));
+                        None
+                    }
+                }
             } else {
                 None
             };
@@ -146,10 +178,11 @@ impl<'source> Parser<'source> {
             expect!(
                 self,
                 self.current()?,
-                Token::SemiColon,
+                Token::SemiColon | Token::Comma,
                 self.lexer.span(),
-                "Expected {} after enum variant but got {}",
+                "Expected {} or {} after enum variant but got {}",
                 Token::SemiColon,
+                Token::Comma,
                 self.current()?
             );
 
@@ -163,6 +196,7 @@ impl<'source> Parser<'source> {
         Ok(Located {
             node: GlobalStmt::Enum {
                 variants,
+                values,
                 name: enum_name,
             },
             span,
@@ -339,7 +373,45 @@ impl<'source> Parser<'source> {
         })
     }
 
-    fn parse_func(&mut self) -> Result<LocatedGlobalStmt, ParseError> {
+    fn parse_attributed_func(&mut self) -> Result<LocatedGlobalStmt, ParseError> {
+        let mut attrs = vec![];
+
+        while matches!(self.current()?, Token::At) {
+            self.next();
+
+            let Token::Ident(attr) = expect!(
+                self,
+                self.current()?,
+                Token::Ident(..),
+                self.lexer.span(),
+                "Expected an attribute name after {} but got {}",
+                Token::At,
+                self.current()?
+            ) else {
+                unreachable!()
+            };
+
+            attrs.push(match attr.as_str() {
+                "statemachine" => FuncAttr::StateMachine,
+                _ => return Err(self.create_error(format!("Unknown attribute '@{attr}'"))),
+            });
+
+            self.next();
+        }
+
+        expect!(
+            self,
+            self.current()?,
+            Token::Func,
+            self.lexer.span(),
+            "Expected a function after attributes but got {}",
+            self.current()?
+        );
+
+        self.parse_func(attrs)
+    }
+
+    fn parse_func(&mut self, attrs: Vec<FuncAttr>) -> Result<LocatedGlobalStmt, ParseError> {
         self.next();
 
         let Token::Ident(func_name) = expect!(
@@ -356,6 +428,19 @@ impl<'source> Parser<'source> {
 
         self.next();
 
+        // A bare name is a type parameter, and a name with a type like `N: u64` a size parameter
+        let (mut type_params, mut size_params) = (vec![], vec![]);
+        if self.current()? == Token::LT {
+            self.next();
+            let generic_params =
+                self.parse_list(Token::GT, "generic parameters", Self::parse_generic_param)?;
+            for (name, t) in generic_params {
+                match t {
+                    Some(t) => size_params.push((name.node, t)),
+                    None => type_params.push(name),
+                }
+            }
+        }
         let params = self.parse_func_params()?;
         let ret = self.parse_ret_type()?;
         let body = self.parse_curly_body()?;
@@ -363,20 +448,186 @@ impl<'source> Parser<'source> {
         Ok(Located {
             node: GlobalStmt::Function {
                 name: func_name,
+                type_params,
+                size_params,
                 params,
                 ret,
                 body,
+                attrs,
+            },
+            span,
+        })
+    }
+
+    fn parse_extern_func(&mut self) -> Result<LocatedGlobalStmt, ParseError> {
+        self.next();
+
+        expect!(
+            self,
+            self.current()?,
+            Token::Func,
+            self.lexer.span(),
+            "Expected {} after {} but got {}",
+            Token::Func,
+            Token::Extern,
+            self.current()?
+        );
+
+        self.next();
+
+        let Token::Ident(func_name) = expect!(
+            self,
+            self.current()?,
+            Token::Ident(..),
+            self.lexer.span(),
+            "Expected an identifier for the function name but got {}",
+            self.current()?
+        ) else {
+            unreachable!()
+        };
+        let span = self.lexer.span();
+
+        self.next();
+
+        let params = self.parse_func_params()?;
+        let ret = self.parse_ret_type()?;
+
+        expect!(
+            self,
+            self.current()?,
+            Token::SemiColon,
+            self.lexer.span(),
+            "Expected {} after an extern function declaration but got {}",
+            Token::SemiColon,
+            self.current()?
+        );
+
+        self.next();
+
+        Ok(Located {
+            node: GlobalStmt::ExternFunction {
+                name: func_name,
+                params,
+                ret,
             },
             span,
         })
     }
 
     fn parse_let(&mut self) -> Result<LocatedGlobalStmt, ParseError> {
-        todo!()
+        let span = self.lexer.span();
+        self.next();
+
+        let Token::Ident(name) = expect!(
+            self,
+            self.current()?,
+            Token::Ident(..),
+            self.lexer.span(),
+            "Expected a variable name after let but got {}",
+            self.current()?
+        ) else {
+            unreachable!();
+        };
+
+        self.next();
+
+        let t = if matches!(self.current()?, Token::Colon) {
+            self.next();
+            Some(self.parse_type()?.node)
+        } else {
+            None
+        };
+
+        let value = if matches!(self.current()?, Token::Eq) {
+            self.next();
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
+
+        expect!(
+            self,
+            self.current()?,
+            Token::SemiColon,
+            self.lexer.span(),
+            "Expected {} after variable declaration but got {}",
+            Token::SemiColon,
+            self.current()?
+        );
+
+        self.next();
+
+        Ok(Located {
+            node: GlobalStmt::Variable {
+                name,
+                t,
+                value,
+                private: false,
+            },
+            span,
+        })
     }
 
     fn parse_const(&mut self) -> Result<LocatedGlobalStmt, ParseError> {
-        todo!()
+        let span = self.lexer.span();
+        self.next();
+
+        let Token::Ident(name) = expect!(
+            self,
+            self.current()?,
+            Token::Ident(..),
+            self.lexer.span(),
+            "Expected a constant name after const but got {}",
+            self.current()?
+        ) else {
+            unreachable!();
+        };
+
+        self.next();
+
+        let t = if matches!(self.current()?, Token::Colon) {
+            self.next();
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+
+        expect!(
+            self,
+            self.current()?,
+            Token::Eq,
+            self.lexer.span(),
+            "Expected {} and a value for the constant '{}' but got {}",
+            Token::Eq,
+            name,
+            self.current()?
+        );
+
+        self.next();
+
+        let value = self.parse_expression()?;
+
+        expect!(
+            self,
+            self.current()?,
+            Token::SemiColon,
+            self.lexer.span(),
+            "Expected {} after constant declaration but got {}",
+            Token::SemiColon,
+            self.current()?
+        );
+
+        self.next();
+
+        Ok(Located {
+            node: GlobalStmt::Constant {
+                name,
+                t,
+                value,
+                private: false,
+            },
+            span,
+        })
     }
 
     fn parse_alias(&mut self) -> Result<LocatedGlobalStmt, ParseError> {
@@ -429,73 +680,244 @@ impl<'source> Parser<'source> {
     }
 
     fn parse_import(&mut self) -> Result<LocatedGlobalStmt, ParseError> {
-        todo!()
+        let span = self.lexer.span();
+        self.next();
+
+        // `import io = std::io;` and `import io "std/io";` rename the module, while the `std` of
+        // `import std::io;` is already part of the path
+        let mut name = None;
+        if let Token::Ident(ident) = self.current()? {
+            let start = self.lexer.span();
+            self.next();
+
+            if self.is_end() {
+                return self.finish_import(span, None, ident, None);
+            }
+            match self.current()? {
+                Token::DColon => {
+                    let path = self.parse_module_path_from(ident, start)?;
+                    return self.finish_import(span, None, path, None);
+                }
+                Token::Eq => {
+                    self.next();
+                    name = Some(ident);
+                }
+                Token::Str(_) | Token::Ident(_) => name = Some(ident),
+                _ => return self.finish_import(span, None, ident, None),
+            }
+        }
+
+        let path = self.parse_module_path()?;
+        self.finish_import(span, name, path, None)
     }
 
-    fn parse_func_params(&mut self) -> Result<Vec<(String, LocatedType)>, ParseError> {
+    /// Parses `from std::math import min, max;`
+    fn parse_from_import(&mut self) -> Result<LocatedGlobalStmt, ParseError> {
+        let span = self.lexer.span();
+        self.next();
+
+        let path = self.parse_module_path()?;
+
         expect!(
             self,
             self.current()?,
-            Token::LeftParen,
+            Token::Import,
             self.lexer.span(),
-            "Expected {} after function name but got {}",
-            Token::LeftParen,
+            "Expected {} after the module path but got {}",
+            Token::Import,
             self.current()?
         );
-
         self.next();
 
-        let mut params: Vec<(String, LocatedType)> = vec![];
-
-        while !matches!(self.current()?, Token::RightParen) {
-            let Token::Ident(param_name) = expect!(
+        let mut members: Vec<String> = vec![];
+        loop {
+            let member_span = self.lexer.span();
+            let Token::Ident(member) = expect!(
                 self,
                 self.current()?,
                 Token::Ident(..),
                 self.lexer.span(),
-                "Expected an identifier for a parameter name but got {}",
+                "Expected a name to import from '{}' but got {}",
+                path,
                 self.current()?
             ) else {
                 unreachable!()
             };
 
+            if members.contains(&member) {
+                return Err(self.create_error_with_line_num(
+                    format!("'{member}' is already imported by this import"),
+                    member_span,
+                ));
+            }
+            members.push(member);
             self.next();
 
-            expect!(
+            if self.is_end() || !matches!(self.current()?, Token::Comma) {
+                break;
+            }
+            self.next();
+        }
+
+        self.finish_import(span, Some(path.clone()), path, Some(members))
+    }
+
+    /// Parses a module path, either a string (`"std/io"`) or names separated by `::`
+    /// (`std::io`), which is the same as separating them by `/`
+    fn parse_module_path(&mut self) -> Result<String, ParseError> {
+        match self.current()? {
+            Token::Str(path) => {
+                self.next();
+                Ok(path)
+            }
+            Token::Ident(ident) => {
+                let span = self.lexer.span();
+                self.next();
+                self.parse_module_path_from(ident, span)
+            }
+            t => Err(self.create_error_with_line_num(
+                format!("Expected a module path but got {t}"),
+                self.lexer.span(),
+            )),
+        }
+    }
+
+    /// Parses the rest of a module path like `std::io` after its first name
+    fn parse_module_path_from(&mut self, first: String, span: Span) -> Result<String, ParseError> {
+        let mut path = first;
+
+        while !self.is_end() && matches!(self.current()?, Token::DColon) {
+            self.next();
+
+            let Token::Ident(name) = expect!(
                 self,
                 self.current()?,
-                Token::Colon,
-                self.lexer.span(),
-                "Expected {} after parameter name but got {}",
-                Token::Colon,
+                Token::Ident(..),
+                span.start..self.lexer.span().end,
+                "Expected a name after {} in the module path but got {}",
+                Token::DColon,
                 self.current()?
-            );
-
+            ) else {
+                unreachable!()
+            };
             self.next();
 
-            let param_type = self.parse_type()?;
+            path.push('/');
+            path.push_str(&name);
+        }
 
-            params.push((param_name, param_type));
+        Ok(path)
+    }
 
-            if !matches!(self.current()?, Token::Comma) {
-                break;
-            } else {
-                self.next();
-            }
+    fn finish_import(
+        &mut self,
+        span: Span,
+        name: Option<String>,
+        path: String,
+        members: Option<Vec<String>>,
+    ) -> Result<LocatedGlobalStmt, ParseError> {
+        // The semicolon after an import is optional
+        if !self.is_end() && matches!(self.current()?, Token::SemiColon) {
+            self.next();
         }
 
-        self.next();
+        // `import "std/io"` is the same as `import io "std/io"`, and so is `import "std/io.clla"`
+        let name = name.unwrap_or_else(|| {
+            let file = path.rsplit('/').next().unwrap_or(&path);
+            file.strip_suffix(".clla").unwrap_or(file).to_string()
+        });
 
-        Ok(params)
+        Ok(Located {
+            node: GlobalStmt::Import {
+                name,
+                path,
+                members,
+            },
+            span,
+        })
     }
 
-    fn parse_ret_type(&mut self) -> Result<LocatedType, ParseError> {
+    fn parse_func_params(&mut self) -> Result<Vec<(String, LocatedType)>, ParseError> {
         expect!(
             self,
             self.current()?,
-            Token::Colon,
+            Token::LeftParen,
             self.lexer.span(),
-            "Expected {} for specifying return type but got {}",
+            "Expected {} after function name but got {}",
+            Token::LeftParen,
+            self.current()?
+        );
+
+        self.next();
+
+        self.parse_list(Token::RightParen, "parameters", Self::parse_func_param)
+    }
+
+    fn parse_func_param(&mut self) -> Result<(String, LocatedType), ParseError> {
+        let Token::Ident(param_name) = expect!(
+            self,
+            self.current()?,
+            Token::Ident(..),
+            self.lexer.span(),
+            "Expected an identifier for a parameter name but got {}",
+            self.current()?
+        ) else {
+            unreachable!()
+        };
+
+        self.next();
+
+        expect!(
+            self,
+            self.current()?,
+            Token::Colon,
+            self.lexer.span(),
+            "Expected {} after parameter name but got {}",
+            Token::Colon,
+            self.current()?
+        );
+
+        self.next();
+
+        Ok((param_name, self.parse_type()?))
+    }
+
+    fn parse_generic_param(
+        &mut self,
+    ) -> Result<(Located<String>, Option<LocatedType>), ParseError> {
+        let Token::Ident(param_name) = expect!(
+            self,
+            self.current()?,
+            Token::Ident(..),
+            self.lexer.span(),
+            "Expected an identifier for a generic parameter but got {}",
+            self.current()?
+        ) else {
+            unreachable!()
+        };
+        let name = Located {
+            node: param_name,
+            span: self.lexer.span(),
+        };
+
+        self.next();
+
+        if self.current()? != Token::Colon {
+            return Ok((name, None));
+        }
+
+        self.next();
+
+        Ok((name, Some(self.parse_type()?)))
+    }
+
+    fn parse_ret_type(&mut self) -> Result<LocatedType, ParseError> {
+        expect!(
+            self,
+            self.current()?,
+            Token::Colon,
+            self.lexer.span(),
+            "Expected {} for specifying return type but got {}",
             Token::Colon,
             self.current()?
         );
@@ -573,64 +995,157 @@ impl<'source> Parser<'source> {
                 node: Type::Pointer(Box::new(self.parse_type()?.node)),
                 span,
             }),
+            Token::Ident(name) if name == "Result" && self.current()? == Token::LT => {
+                self.next();
+                self.parse_result_type(span)
+            }
             Token::Ident(user_def_type) => Ok(Located {
                 node: Type::UserDefinedType(user_def_type),
                 span,
             }),
             Token::LeftBrak => {
-                let mut num: usize = 0;
-                let is_darray = match self.current()? {
-                    Token::Caret => {
-                        self.next();
-                        true
-                    }
-                    Token::Int(n) => {
-                        self.next();
-                        if n < 0 {
-                            self.create_error(format!("Expected array size to be non-negative"));
-                        }
-                        num = n as usize;
-                        false
-                    }
-                    _ => {
-                        return Err(self.create_error(format!(
-                            "Expected an integer or {} for array type",
-                            Token::Caret
-                        )))
-                    }
-                };
+                if self.current()? == Token::Caret {
+                    self.next();
+                    expect!(
+                        self,
+                        self.current()?,
+                        Token::RightBrak,
+                        self.lexer.span(),
+                        "Expected {} for the dynamic array type but got {}",
+                        Token::RightBrak,
+                        self.current()?
+                    );
+                    self.next();
+
+                    let elem_type = self.parse_type()?;
+                    return Ok(Located {
+                        node: Type::DArray(Box::new(elem_type.node)),
+                        span,
+                    });
+                }
+
+                let start = self.lexer.span().start;
+                let length = self.parse_expression()?;
+                let source = self.lexer.source()[start..self.lexer.span().start].trim();
 
                 expect!(
                     self,
                     self.current()?,
                     Token::RightBrak,
                     self.lexer.span(),
-                    "Expected {} for the {} type but got {}",
+                    "Expected {} for the array type but got {}",
                     Token::RightBrak,
-                    if is_darray { "dynamic array" } else { "array" },
+                    self.current()?
+                );
+                self.next();
+
+                let elem = Box::new(self.parse_type()?.node);
+                let node = match length.node {
+                    Expr::Int(len) if len >= 0 => Type::Array(len as usize, elem),
+                    Expr::UInt(len) if usize::try_from(len).is_ok() => {
+                        Type::Array(len as usize, elem)
+                    }
+                    // `[N]T` takes its length from the size parameter `N` of a generic function,
+                    // or from the global constant `N`
+                    Expr::Ident(size) => Type::GenericArray(size, elem),
+                    // Any other length is a constant expression, like `[sq(4)]i32`, which is
+                    // evaluated before type checking
+                    _ => Type::ExprArray(
+                        LengthExpr {
+                            expr: Box::new(length),
+                            source: source.to_string(),
+                        },
+                        elem,
+                    ),
+                };
+
+                Ok(Located { node, span })
+            }
+            Token::Func => {
+                expect!(
+                    self,
+                    self.current()?,
+                    Token::LeftParen,
+                    self.lexer.span(),
+                    "Expected {} for the parameter types of the function type but got {}",
+                    Token::LeftParen,
                     self.current()?
                 );
 
                 self.next();
 
-                let elem_type = self.parse_type()?;
+                let params =
+                    self.parse_list(Token::RightParen, "parameter types", |parser| {
+                        Ok(parser.parse_type()?.node)
+                    })?;
+                let ret = self.parse_ret_type()?;
 
-                if is_darray {
-                    Ok(Located {
-                        node: Type::DArray(Box::new(elem_type.node)),
-                        span,
-                    })
-                } else {
-                    Ok(Located {
-                        node: Type::Array(num, Box::new(elem_type.node)),
+                Ok(Located {
+                    node: Type::Func(params, Box::new(ret.node)),
+                    span,
+                })
+            }
+            Token::LeftParen => {
+                let elems = self.parse_list(Token::RightParen, "types", |parser| {
+                    Ok(parser.parse_type()?.node)
+                })?;
+                if elems.len() < 2 {
+                    return Err(self.create_error_with_line_num(
+                        "A tuple type needs at least two types".to_string(),
                         span,
-                    })
+                    ));
                 }
+
+                Ok(Located {
+                    node: Type::Tuple(elems),
+                    span,
+                })
             }
             token => Err(self.create_error(format!("Expected type expression but got {}", token))),
         }
     }
 
+    /// Parses the `T, E>` of `Result<T, E>`, whose closing `>` may be the first half of the `>>`
+    /// that also closes an enclosing `Result`
+    fn parse_result_type(&mut self, span: Span) -> Result<LocatedType, ParseError> {
+        let ok = self.parse_type()?;
+
+        expect!(
+            self,
+            self.current()?,
+            Token::Comma,
+            self.lexer.span(),
+            "Expected {} between the value and error types of 'Result' but got {}",
+            Token::Comma,
+            self.current()?
+        );
+        self.next();
+
+        let err = self.parse_type()?;
+        if err.node == Type::Void {
+            return Err(self.create_error_with_line_num(
+                "The error type of a 'Result' cannot be 'void'".to_string(),
+                err.span,
+            ));
+        }
+
+        match self.current()? {
+            Token::GT => self.next(),
+            Token::RShift => self.current_token = Some(Ok(Token::GT)),
+            token => {
+                return Err(self.create_error(format!(
+                    "Expected {} after the types of 'Result' but got {token}",
+                    Token::GT
+                )));
+            }
+        }
+
+        Ok(Located {
+            node: Type::Result(Box::new(ok.node), Box::new(err.node)),
+            span,
+        })
+    }
+
     fn parse_curly_body(&mut self) -> Result<Vec<LocatedStmt>, ParseError> {
         expect!(
             self,
@@ -641,13 +1156,30 @@ impl<'source> Parser<'source> {
             Token::LeftBrace,
             self.current()?
         );
+        let open = self.lexer.span();
 
         self.next();
 
         let mut body: Vec<LocatedStmt> = vec![];
 
-        while !matches!(self.current()?, Token::RightBrace) {
-            body.push(self.parse_statement()?);
+        loop {
+            let stmt = match self.current() {
+                Ok(Token::RightBrace) => break,
+                Ok(_) => self.parse_statement(),
+                Err(err) => Err(err),
+            };
+
+            match stmt {
+                Ok(stmt) => body.push(stmt),
+                // The file ended in this block, which is the innermost one still open, so the
+                // error is followed by a note about it that the enclosing blocks pass on
+                Err(err) if self.current_token.is_none() && !diagnostics::is_attached(&err.1) => {
+                    self.errors.push(err);
+                    let note = "The block opened here is never closed";
+                    return Err(diagnostics::note(open, note));
+                }
+                Err(err) => return Err(err),
+            }
         }
 
         self.next();
@@ -657,21 +1189,40 @@ impl<'source> Parser<'source> {
 
     fn parse_statement(&mut self) -> Result<LocatedStmt, ParseError> {
         match self.current()? {
-            Token::Let => todo!(),
-            Token::Const => todo!(),
+            Token::Let | Token::Const => self.parse_local_variable(),
             Token::Return => self.parse_return(),
             Token::Break => self.parse_break(),
             Token::Continue => self.parse_continue(),
+            Token::Yield => self.parse_yield(),
             Token::If => self.parse_if(),
             Token::While => self.parse_while(),
             Token::Do => self.parse_do_while(),
+            Token::Switch => self.parse_switch(),
             Token::Defer => self.parse_defer(),
+            Token::LeftBrace => self.parse_block(),
             Token::Destroy => self.parse_destroy(),
             Token::Free => self.parse_free(),
             _ => {
                 let expr = self.parse_expression()?;
                 let span = expr.span.clone();
 
+                // A misspelled keyword is parsed as an identifier followed by what should have
+                // come after the keyword, e.g. `retrun x;`
+                if let Expr::Ident(word) = &expr.node
+                    && self.current()? != Token::SemiColon
+                    && let Some(keyword) = similar_keyword(word, STMT_KEYWORDS)
+                {
+                    return Err(self.create_error_with_line_num(
+                        format!(
+                            "Expected {} at the end of a statement but got {}, did you mean \
+                             '{keyword}' instead of '{word}'?",
+                            Token::SemiColon,
+                            self.current()?
+                        ),
+                        span,
+                    ));
+                }
+
                 expect!(
                     self,
                     self.current()?,
@@ -692,6 +1243,150 @@ impl<'source> Parser<'source> {
         }
     }
 
+    fn parse_local_variable(&mut self) -> Result<LocatedStmt, ParseError> {
+        let span = self.lexer.span();
+        let is_const = self.current()? == Token::Const;
+        let keyword = if is_const { "const" } else { "let" };
+        self.next();
+
+        if matches!(
+            self.current()?,
+            Token::LeftBrace | Token::LeftBrak | Token::LeftParen
+        ) {
+            return self.parse_destructure(span, is_const);
+        }
+
+        let Token::Ident(name) = expect!(
+            self,
+            self.current()?,
+            Token::Ident(..),
+            self.lexer.span(),
+            "Expected a variable name or a pattern after {keyword} but got {}",
+            self.current()?
+        ) else {
+            unreachable!();
+        };
+
+        self.next();
+
+        let t = if matches!(self.current()?, Token::Colon) {
+            self.next();
+            Some(self.parse_type()?.node)
+        } else {
+            None
+        };
+
+        let value = if is_const {
+            expect!(
+                self,
+                self.current()?,
+                Token::Eq,
+                self.lexer.span(),
+                "Expected {} and a value for the constant '{}' but got {}",
+                Token::Eq,
+                name,
+                self.current()?
+            );
+            self.next();
+            Some(self.parse_expression()?)
+        } else if matches!(self.current()?, Token::Eq) {
+            self.next();
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
+
+        expect!(
+            self,
+            self.current()?,
+            Token::SemiColon,
+            self.lexer.span(),
+            "Expected {} after variable declaration but got {}",
+            Token::SemiColon,
+            self.current()?
+        );
+
+        self.next();
+
+        Ok(Located {
+            node: Stmt::Variable {
+                name,
+                t,
+                value,
+                private: false,
+                is_const,
+            },
+            span,
+        })
+    }
+
+    /// Parses `{ x, y } = point;` or `[a, b] = arr;` after `let` or `const`
+    fn parse_destructure(&mut self, span: Span, is_const: bool) -> Result<LocatedStmt, ParseError> {
+        let opening = self.current()?;
+        let (closing, items) = match opening {
+            Token::LeftBrace => (Token::RightBrace, "field names"),
+            Token::LeftBrak => (Token::RightBrak, "variable names"),
+            _ => (Token::RightParen, "variable names"),
+        };
+        self.next();
+
+        let names = self.parse_list(closing, items, |parser| {
+            let Token::Ident(name) = expect!(
+                parser,
+                parser.current()?,
+                Token::Ident(..),
+                parser.lexer.span(),
+                "Expected a variable name in the pattern but got {}",
+                parser.current()?
+            ) else {
+                unreachable!();
+            };
+            parser.next();
+            Ok(name)
+        })?;
+
+        expect!(
+            self,
+            self.current()?,
+            Token::Eq,
+            self.lexer.span(),
+            "Expected {} and a value to destructure but got {}",
+            Token::Eq,
+            self.current()?
+        );
+
+        self.next();
+
+        let value = self.parse_expression()?;
+
+        expect!(
+            self,
+            self.current()?,
+            Token::SemiColon,
+            self.lexer.span(),
+            "Expected {} after variable declaration but got {}",
+            Token::SemiColon,
+            self.current()?
+        );
+
+        self.next();
+
+        let pattern = match opening {
+            Token::LeftBrace => Pattern::Struct(names),
+            Token::LeftBrak => Pattern::Array(names),
+            _ => Pattern::Tuple(names),
+        };
+
+        Ok(Located {
+            node: Stmt::Destructure {
+                pattern,
+                value,
+                is_const,
+            },
+            span,
+        })
+    }
+
     fn parse_break(&mut self) -> Result<LocatedStmt, ParseError> {
         let span = self.lexer.span();
         self.next();
@@ -764,6 +1459,30 @@ impl<'source> Parser<'source> {
         })
     }
 
+    fn parse_yield(&mut self) -> Result<LocatedStmt, ParseError> {
+        let span = self.lexer.span();
+        self.next();
+
+        let value = self.parse_expression()?;
+
+        expect!(
+            self,
+            self.current()?,
+            Token::SemiColon,
+            self.lexer.span(),
+            "Expected {} after yield but got {}",
+            Token::SemiColon,
+            self.current()?
+        );
+
+        self.next();
+
+        Ok(Located {
+            node: Stmt::Yield { value },
+            span,
+        })
+    }
+
     fn parse_if(&mut self) -> Result<LocatedStmt, ParseError> {
         let span = self.lexer.span();
         self.next();
@@ -909,18 +1628,156 @@ impl<'source> Parser<'source> {
         })
     }
 
-    fn parse_defer(&mut self) -> Result<LocatedStmt, ParseError> {
+    fn parse_switch(&mut self) -> Result<LocatedStmt, ParseError> {
         let span = self.lexer.span();
         self.next();
 
-        let body = if matches!(self.current()?, Token::LeftBrace) {
-            self.parse_curly_body()?
-        } else {
-            vec![self.parse_statement()?]
-        };
+        expect!(
+            self,
+            self.current()?,
+            Token::LeftParen,
+            self.lexer.span(),
+            "Expected {} before the switched value but got {}",
+            Token::LeftParen,
+            self.current()?
+        );
 
-        Ok(Located {
-            node: Stmt::Defer { body },
+        self.next();
+
+        let value = self.parse_expression()?;
+
+        expect!(
+            self,
+            self.current()?,
+            Token::RightParen,
+            self.lexer.span(),
+            "Expected {} after the switched value but got {}",
+            Token::RightParen,
+            self.current()?
+        );
+
+        self.next();
+
+        expect!(
+            self,
+            self.current()?,
+            Token::LeftBrace,
+            self.lexer.span(),
+            "Expected {} before cases but got {}",
+            Token::LeftBrace,
+            self.current()?
+        );
+
+        self.next();
+
+        let mut cases = vec![];
+        let mut default = None;
+
+        loop {
+            match self.current()? {
+                Token::Case => {
+                    self.next();
+
+                    let mut values = vec![self.parse_expression()?];
+                    while matches!(self.current()?, Token::Comma) {
+                        self.next();
+                        values.push(self.parse_expression()?);
+                    }
+
+                    self.expect_case_colon()?;
+                    let body = self.parse_case_body()?;
+                    cases.push(SwitchCase { values, body });
+                }
+                Token::Default => {
+                    if default.is_some() {
+                        return Err(self.create_error_with_line_num(
+                            "A switch can only have one default case".to_string(),
+                            self.lexer.span(),
+                        ));
+                    }
+
+                    self.next();
+                    self.expect_case_colon()?;
+                    default = Some(self.parse_case_body()?);
+                }
+                Token::RightBrace => break,
+                t => {
+                    return Err(self.create_error_with_line_num(
+                        format!(
+                            "Expected {}, {}, or {} but got {t}",
+                            Token::Case,
+                            Token::Default,
+                            Token::RightBrace
+                        ),
+                        self.lexer.span(),
+                    ));
+                }
+            }
+        }
+
+        self.next();
+
+        Ok(Located {
+            node: Stmt::Switch {
+                value,
+                cases,
+                default,
+            },
+            span,
+        })
+    }
+
+    fn expect_case_colon(&mut self) -> Result<(), ParseError> {
+        expect!(
+            self,
+            self.current()?,
+            Token::Colon,
+            self.lexer.span(),
+            "Expected {} after case but got {}",
+            Token::Colon,
+            self.current()?
+        );
+
+        self.next();
+        Ok(())
+    }
+
+    /// Parses the statements of a case up to the next case or the end of the switch
+    fn parse_case_body(&mut self) -> Result<Vec<LocatedStmt>, ParseError> {
+        let mut body = vec![];
+
+        while !matches!(
+            self.current()?,
+            Token::Case | Token::Default | Token::RightBrace
+        ) {
+            body.push(self.parse_statement()?);
+        }
+
+        Ok(body)
+    }
+
+    fn parse_defer(&mut self) -> Result<LocatedStmt, ParseError> {
+        let span = self.lexer.span();
+        self.next();
+
+        let body = if matches!(self.current()?, Token::LeftBrace) {
+            self.parse_curly_body()?
+        } else {
+            vec![self.parse_statement()?]
+        };
+
+        Ok(Located {
+            node: Stmt::Defer { body },
+            span,
+        })
+    }
+
+    fn parse_block(&mut self) -> Result<LocatedStmt, ParseError> {
+        let span = self.lexer.span();
+        let body = self.parse_curly_body()?;
+
+        Ok(Located {
+            node: Stmt::Block { body },
             span,
         })
     }
@@ -983,7 +1840,11 @@ impl<'source> Parser<'source> {
             Token::MulEq => self.parse_assign(expr, AssignOp::MulAssign),
             Token::DivEq => self.parse_assign(expr, AssignOp::DivAssign),
             Token::ModEq => self.parse_assign(expr, AssignOp::ModAssign),
-            // TODO: Add bitwise things as well
+            Token::AmpersandEq => self.parse_assign(expr, AssignOp::BitAndAssign),
+            Token::PipeEq => self.parse_assign(expr, AssignOp::BitOrAssign),
+            Token::CaretEq => self.parse_assign(expr, AssignOp::BitXOrAssign),
+            Token::LShiftEq => self.parse_assign(expr, AssignOp::LShiftAssign),
+            Token::RShiftEq => self.parse_assign(expr, AssignOp::RShiftAssign),
             _ => Ok(expr),
         }
     }
@@ -1031,13 +1892,13 @@ impl<'source> Parser<'source> {
     }
 
     fn parse_and_expr(&mut self) -> Result<LocatedExpr, ParseError> {
-        let mut expr = self.parse_equality()?;
+        let mut expr = self.parse_bit_or_expr()?;
 
         while matches!(self.current()?, Token::And) {
             let span = self.lexer.span();
             self.next();
 
-            let rexpr = self.parse_equality()?;
+            let rexpr = self.parse_bit_or_expr()?;
 
             expr = Located {
                 node: Expr::Binary {
@@ -1052,6 +1913,81 @@ impl<'source> Parser<'source> {
         Ok(expr)
     }
 
+    // The bitwise operators bind like in C, looser than the comparisons, since the generated C
+    // keeps only the parentheses of the source
+    fn parse_bit_or_expr(&mut self) -> Result<LocatedExpr, ParseError> {
+        let mut expr = self.parse_bit_xor_expr()?;
+
+        while matches!(self.current()?, Token::Pipe) {
+            let span = self.lexer.span();
+            self.next();
+
+            if matches!(self.current()?, Token::Pipe) {
+                return Err(self.create_error_with_line_num(
+                    "Use 'or' instead of '||'".to_string(),
+                    span.start..self.lexer.span().end,
+                ));
+            }
+
+            let rexpr = self.parse_bit_xor_expr()?;
+
+            expr = Located {
+                node: Expr::Binary {
+                    left: Box::new(expr),
+                    op: BinOp::BitOr,
+                    right: Box::new(rexpr),
+                },
+                span,
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_bit_xor_expr(&mut self) -> Result<LocatedExpr, ParseError> {
+        let mut expr = self.parse_bit_and_expr()?;
+
+        while matches!(self.current()?, Token::Caret) {
+            let span = self.lexer.span();
+            self.next();
+
+            let rexpr = self.parse_bit_and_expr()?;
+
+            expr = Located {
+                node: Expr::Binary {
+                    left: Box::new(expr),
+                    op: BinOp::XOr,
+                    right: Box::new(rexpr),
+                },
+                span,
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_bit_and_expr(&mut self) -> Result<LocatedExpr, ParseError> {
+        let mut expr = self.parse_equality()?;
+
+        while matches!(self.current()?, Token::Ampersand) {
+            let span = self.lexer.span();
+            self.next();
+
+            let rexpr = self.parse_equality()?;
+
+            expr = Located {
+                node: Expr::Binary {
+                    left: Box::new(expr),
+                    op: BinOp::BitAnd,
+                    right: Box::new(rexpr),
+                },
+                span,
+            };
+        }
+
+        Ok(expr)
+    }
+
     fn parse_equality(&mut self) -> Result<LocatedExpr, ParseError> {
         let mut expr = self.parse_comparison()?;
 
@@ -1081,7 +2017,7 @@ impl<'source> Parser<'source> {
     }
 
     fn parse_comparison(&mut self) -> Result<LocatedExpr, ParseError> {
-        let mut expr = self.parse_term_expr()?;
+        let mut expr = self.parse_shift_expr()?;
 
         while matches!(
             self.current()?,
@@ -1098,6 +2034,34 @@ impl<'source> Parser<'source> {
             let span = self.lexer.span();
             self.next();
 
+            let rexpr = self.parse_shift_expr()?;
+
+            expr = Located {
+                node: Expr::Binary {
+                    left: Box::new(expr),
+                    op: bin_op,
+                    right: Box::new(rexpr),
+                },
+                span,
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_shift_expr(&mut self) -> Result<LocatedExpr, ParseError> {
+        let mut expr = self.parse_term_expr()?;
+
+        while matches!(self.current()?, Token::LShift | Token::RShift) {
+            let bin_op = if matches!(self.current()?, Token::LShift) {
+                BinOp::LShift
+            } else {
+                BinOp::RShift
+            };
+
+            let span = self.lexer.span();
+            self.next();
+
             let rexpr = self.parse_term_expr()?;
 
             expr = Located {
@@ -1171,6 +2135,21 @@ impl<'source> Parser<'source> {
     }
 
     fn parse_unary(&mut self) -> Result<LocatedExpr, ParseError> {
+        if self.current()? == Token::Try {
+            let start = self.lexer.span().start;
+            self.next();
+
+            let expr = self.parse_unary()?;
+            let span = start..expr.span.end;
+            return Ok(Located {
+                node: Expr::Try {
+                    expr: Box::new(expr),
+                    t: RefCell::new(None),
+                },
+                span,
+            });
+        }
+
         if matches!(
             self.current()?,
             Token::Not | Token::Minus | Token::Ampersand | Token::Caret
@@ -1241,25 +2220,15 @@ impl<'source> Parser<'source> {
 
         self.next();
 
-        let mut args: Vec<LocatedExpr> = vec![];
-
-        while !matches!(self.current()?, Token::RightParen) {
-            let arg = self.parse_expression()?;
-            args.push(arg);
-
-            if !matches!(self.current()?, Token::Comma) {
-                break;
-            } else {
-                self.next();
-            }
-        }
-
-        self.next();
+        let args = self.parse_list(Token::RightParen, "arguments", Self::parse_expression)?;
 
         Ok(Located {
             node: Expr::FnCall {
                 name: Box::new(callee),
                 args,
+                sizes: RefCell::new(vec![]),
+                types: RefCell::new(vec![]),
+                result: RefCell::new(None),
             },
             span,
         })
@@ -1295,24 +2264,15 @@ impl<'source> Parser<'source> {
     fn parse_struct_init(&mut self, expr: LocatedExpr) -> Result<LocatedExpr, ParseError> {
         let span = expr.span;
         let Expr::Ident(ident) = expr.node else {
-            unreachable!()
+            return Err(self.create_error_with_line_num(
+                format!("Expected a struct name before {}", Token::LeftBrace),
+                span,
+            ));
         };
 
         self.next();
 
-        let mut args: Vec<(String, LocatedExpr)> = vec![];
-
-        while !matches!(self.current()?, Token::RightBrace) {
-            args.push(self.parse_struct_init_arg()?);
-
-            if !matches!(self.current()?, Token::Comma) {
-                break;
-            } else {
-                self.next();
-            }
-        }
-
-        self.next();
+        let args = self.parse_list(Token::RightBrace, "fields", Self::parse_struct_init_arg)?;
 
         Ok(Located {
             node: Expr::InitStruct { ident, args },
@@ -1323,15 +2283,23 @@ impl<'source> Parser<'source> {
     fn parse_enum_variant(&mut self, expr: LocatedExpr) -> Result<LocatedExpr, ParseError> {
         let span = expr.span;
         let Expr::Ident(ident) = expr.node else {
-            unreachable!()
+            return Err(self.create_error_with_line_num(
+                format!("Expected an enum name before {}", Token::RightArrow),
+                span,
+            ));
         };
 
         self.next();
 
-        self.next();
-
         let Token::Ident(variant) = self.current()? else {
-            unreachable!()
+            return Err(self.create_error_with_line_num(
+                format!(
+                    "Expected a variant name after {} but got {}",
+                    Token::RightArrow,
+                    self.current()?
+                ),
+                self.lexer.span(),
+            ));
         };
 
         self.next();
@@ -1347,7 +2315,14 @@ impl<'source> Parser<'source> {
 
         let span = expr.span.clone();
         let Token::Ident(member) = self.current()? else {
-            unreachable!()
+            return Err(self.create_error_with_line_num(
+                format!(
+                    "Expected a field name after {} but got {}",
+                    Token::Dot,
+                    self.current()?
+                ),
+                self.lexer.span(),
+            ));
         };
 
         self.next();
@@ -1362,7 +2337,34 @@ impl<'source> Parser<'source> {
     }
 
     fn parse_module_access(&mut self, expr: LocatedExpr) -> Result<LocatedExpr, ParseError> {
-        todo!()
+        let span = expr.span;
+        let Expr::Ident(module) = expr.node else {
+            return Err(self.create_error_with_line_num(
+                format!("Expected a module name before {}", Token::DColon),
+                span,
+            ));
+        };
+
+        self.next();
+
+        let Token::Ident(member) = expect!(
+            self,
+            self.current()?,
+            Token::Ident(..),
+            self.lexer.span(),
+            "Expected an identifier after {} but got {}",
+            Token::DColon,
+            self.current()?
+        ) else {
+            unreachable!()
+        };
+
+        self.next();
+
+        Ok(Located {
+            node: Expr::ModuleAccess { module, member },
+            span,
+        })
     }
 
     fn parse_primary_expr(&mut self) -> Result<LocatedExpr, ParseError> {
@@ -1388,10 +2390,14 @@ impl<'source> Parser<'source> {
             Token::Int(val) => {
                 let span = self.lexer.span();
                 self.next();
-                Ok(Located {
-                    node: Expr::Int(val),
-                    span,
-                })
+
+                // The lexer guarantees that literals too big for i64 still fit in u64
+                let node = match i64::try_from(val) {
+                    Ok(val) => Expr::Int(val),
+                    Err(_) => Expr::UInt(val as u64),
+                };
+
+                Ok(Located { node, span })
             }
 
             Token::Double(val) => {
@@ -1412,6 +2418,15 @@ impl<'source> Parser<'source> {
                 })
             }
 
+            Token::Char(c) => {
+                let span = self.lexer.span();
+                self.next();
+                Ok(Located {
+                    node: Expr::Char(c),
+                    span,
+                })
+            }
+
             Token::Ident(ident) => {
                 let span = self.lexer.span();
                 self.next();
@@ -1423,20 +2438,38 @@ impl<'source> Parser<'source> {
 
             Token::LeftParen => self.parse_parenthesized(),
 
+            Token::LeftBrak => self.parse_arr_init(),
+
             Token::Make => self.parse_make_expr(),
 
             Token::New => self.parse_new_expr(),
 
+            Token::Sizeof => self.parse_sizeof_expr(),
+
+            Token::Cast => self.parse_cast_expr(),
+
             t => Err(self.create_error(format!("Unexpected token: {}", t))),
         }
     }
 
     fn parse_parenthesized(&mut self) -> Result<LocatedExpr, ParseError> {
+        let span = self.lexer.span();
         self.next();
 
         let l = self.lexer.span();
         let expr = self.parse_expression()?;
 
+        if self.current()? == Token::Comma {
+            self.next();
+            let mut elems = vec![expr];
+            elems.extend(self.parse_list(Token::RightParen, "values", Self::parse_expression)?);
+
+            return Ok(Located {
+                node: Expr::Tuple { elems },
+                span,
+            });
+        }
+
         expect!(
             self,
             self.current()?,
@@ -1457,15 +2490,27 @@ impl<'source> Parser<'source> {
         })
     }
 
-    fn parse_make_expr(&mut self) -> Result<LocatedExpr, ParseError> {
+    fn parse_arr_init(&mut self) -> Result<LocatedExpr, ParseError> {
+        let span = self.lexer.span();
         self.next();
 
-        let l = self.lexer.span();
-        let t = self.parse_type()?.node;
+        let elems = self.parse_list(Token::RightBrak, "elements", Self::parse_expression)?;
 
         Ok(Located {
-            node: Expr::Make { t },
-            span: l,
+            node: Expr::InitArr { elems },
+            span,
+        })
+    }
+
+    fn parse_make_expr(&mut self) -> Result<LocatedExpr, ParseError> {
+        self.next();
+
+        let l = self.lexer.span();
+        let t = self.parse_type()?.node;
+
+        Ok(Located {
+            node: Expr::Make { t },
+            span: l,
         })
     }
 
@@ -1481,8 +2526,107 @@ impl<'source> Parser<'source> {
         })
     }
 
+    fn parse_sizeof_expr(&mut self) -> Result<LocatedExpr, ParseError> {
+        let span = self.lexer.span();
+        self.next();
+
+        expect!(
+            self,
+            self.current()?,
+            Token::LeftParen,
+            self.lexer.span(),
+            "Expected {} after sizeof but got {}",
+            Token::LeftParen,
+            self.current()?
+        );
+
+        self.next();
+
+        let t = self.parse_type()?.node;
+
+        expect!(
+            self,
+            self.current()?,
+            Token::RightParen,
+            self.lexer.span(),
+            "Expected {} after the type of sizeof but got {}",
+            Token::RightParen,
+            self.current()?
+        );
+
+        self.next();
+
+        Ok(Located {
+            node: Expr::Sizeof { t },
+            span,
+        })
+    }
+
+    /// Parses `cast(T, value)`
+    fn parse_cast_expr(&mut self) -> Result<LocatedExpr, ParseError> {
+        let start = self.lexer.span().start;
+        self.next();
+
+        expect!(
+            self,
+            self.current()?,
+            Token::LeftParen,
+            self.lexer.span(),
+            "Expected {} after cast but got {}",
+            Token::LeftParen,
+            self.current()?
+        );
+
+        self.next();
+
+        let t = self.parse_type()?;
+
+        expect!(
+            self,
+            self.current()?,
+            Token::Comma,
+            self.lexer.span(),
+            "Expected {} between the type and the value of the cast but got {}",
+            Token::Comma,
+            self.current()?
+        );
+
+        self.next();
+
+        let expr = self.parse_expression()?;
+
+        expect!(
+            self,
+            self.current()?,
+            Token::RightParen,
+            self.lexer.span(),
+            "Expected {} after the value of the cast but got {}",
+            Token::RightParen,
+            self.current()?
+        );
+
+        let span = start..self.lexer.span().end;
+        self.next();
+
+        Ok(Located {
+            node: Expr::Cast {
+                t,
+                expr: Box::new(expr),
+                check: Cell::new(None),
+            },
+            span,
+        })
+    }
+
     fn parse_struct_init_arg(&mut self) -> Result<(String, LocatedExpr), ParseError> {
-        let Token::Ident(ident) = self.current()? else {
+        let Token::Ident(ident) = expect!(
+            self,
+            self.current()?,
+            Token::Ident(..),
+            self.lexer.span(),
+            "Expected a field name but got {}",
+            self.current()?
+        ) else {
             unreachable!()
         };
 
@@ -1505,14 +2649,50 @@ impl<'source> Parser<'source> {
         Ok((ident, expr))
     }
 
+    /// Parses comma-separated items up to and including `closing`, allowing a trailing comma.
+    /// A missing comma between two items is reported, but parsing continues with the next item.
+    fn parse_list<T>(
+        &mut self,
+        closing: Token,
+        items: &str,
+        mut parse_item: impl FnMut(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<Vec<T>, ParseError> {
+        let mut list = vec![];
+
+        while self.current()? != closing {
+            list.push(parse_item(self)?);
+
+            match self.current()? {
+                Token::Comma => self.next(),
+                t if t == closing => {}
+                t => self.errors.push(self.create_error(format!(
+                    "Expected {} between {items} but got {t}",
+                    Token::Comma
+                ))),
+            }
+        }
+
+        self.next();
+
+        Ok(list)
+    }
+
     #[inline]
     fn current(&self) -> Result<Token, ParseError> {
         if let Some(res) = &self.current_token {
             if let Ok(res) = res {
                 Ok(res.clone())
             } else {
-                let e = res.clone().unwrap_err();
-                Err((self.lexer.span(), e.msg))
+                let mut e = res.clone().unwrap_err();
+                if e.msg.is_empty() {
+                    e = LexError::unexpected(self.lexer.slice());
+                }
+                let span = self.lexer.span();
+                let span = match e.span {
+                    Some(part) => span.start + part.start..span.start + part.end,
+                    None => span,
+                };
+                Err((span, e.msg))
             }
         } else {
             Err((self.lexer.span(), "Unexpected end of file".to_string()))
@@ -1532,6 +2712,18 @@ impl<'source> Parser<'source> {
         (self.lexer.span(), msg)
     }
 
+    /// Creates an error at the current token, suggesting the keyword it is likely a misspelling
+    /// of if it is an identifier close to one of `keywords`
+    fn create_error_suggesting(&self, msg: String, token: &Token, keywords: &[&str]) -> ParseError {
+        match token {
+            Token::Ident(word) => match similar_keyword(word, keywords) {
+                Some(keyword) => self.create_error(format!("{msg}, did you mean '{keyword}'?")),
+                None => self.create_error(msg),
+            },
+            _ => self.create_error(msg),
+        }
+    }
+
     fn synchronize(&mut self) {
         while !self.is_end() {
             match {
@@ -1545,8 +2737,11 @@ impl<'source> Parser<'source> {
                             | Token::Struct
                             | Token::Union
                             | Token::Func
+                            | Token::At
+                            | Token::Extern
                             | Token::Alias
                             | Token::Import
+                            | Token::From
                     ) {
                         return;
                     } else {
@@ -1577,9 +2772,11 @@ pub struct Located<T> {
     pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Expr {
     Int(i64),
+    /// An integer literal too big for `i64`
+    UInt(u64),
     Double(f64),
     Bool(bool),
     Char(u8),
@@ -1610,6 +2807,18 @@ pub enum Expr {
     FnCall {
         name: Box<LocatedExpr>,
         args: Vec<LocatedExpr>,
+
+        /// Set by the type checker to the values of the size parameters of a generic function,
+        /// so that the call can be monomorphized
+        sizes: RefCell<Vec<Size>>,
+
+        /// Set by the type checker to the types of the type parameters of a generic function,
+        /// which it is instantiated for
+        types: RefCell<Vec<Type>>,
+
+        /// Set by the type checker to the `Result` that `ok(x)` or `err(e)` creates, which
+        /// depends on where it is used
+        result: RefCell<Option<Type>>,
     },
     MemAccess {
         expr: Box<LocatedExpr>,
@@ -1619,6 +2828,10 @@ pub enum Expr {
         ident: String,
         variant: String,
     },
+    ModuleAccess {
+        module: String,
+        member: String,
+    },
     ArrIndex {
         arr: Box<LocatedExpr>,
         idx: Box<LocatedExpr>,
@@ -1626,6 +2839,10 @@ pub enum Expr {
     Cast {
         t: LocatedType,
         expr: Box<LocatedExpr>,
+
+        /// Set by the type checker if the value may not fit into `t`, so that `--checked-casts`
+        /// can check it at runtime
+        check: Cell<Option<CastCheck>>,
     },
     Sizeof {
         t: Type,
@@ -1641,6 +2858,19 @@ pub enum Expr {
         ident: String,
         args: Vec<(String, LocatedExpr)>,
     },
+    /// `try expr`, which is the value of the `Result` `expr`, or returns its error from the
+    /// function
+    Try {
+        expr: Box<LocatedExpr>,
+
+        /// Set by the type checker to the type of `expr`, so that it can be kept in a variable
+        /// while its error is checked
+        t: RefCell<Option<Type>>,
+    },
+    /// `(q, r)`, the values returned from a function returning multiple values
+    Tuple {
+        elems: Vec<LocatedExpr>,
+    },
     Make {
         t: Type,
     },
@@ -1649,7 +2879,55 @@ pub enum Expr {
     },
 }
 
-#[derive(Debug)]
+/// The constant expression an array is as long as, like the `sq(4)` of `[sq(4)]i32`
+#[derive(Debug, Clone)]
+pub struct LengthExpr {
+    pub expr: Box<LocatedExpr>,
+
+    /// How the expression is written, which tells lengths apart until they are evaluated
+    pub source: String,
+}
+
+impl PartialEq for LengthExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl std::fmt::Display for LengthExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// The value of a size parameter of a generic function at a call
+#[derive(Debug, Clone, PartialEq)]
+pub enum Size {
+    Known(usize),
+    /// A size parameter of the calling function, which is known once the caller is
+    /// monomorphized
+    Param(String),
+}
+
+impl std::fmt::Display for Size {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Size::Known(n) => write!(f, "{n}"),
+            Size::Param(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// The kind of value a cast that may lose information converts, which decides how
+/// `--checked-casts` checks that it fits into the target type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CastCheck {
+    Signed,
+    Unsigned,
+    Float,
+}
+
+#[derive(Debug, Clone)]
 pub enum Stmt {
     Variable {
         name: String,
@@ -1658,12 +2936,22 @@ pub enum Stmt {
         private: bool,
         is_const: bool,
     },
+    /// `let { x, y } = point;` or `let [a, b] = arr;`, which declares a variable for each field
+    /// or element
+    Destructure {
+        pattern: Pattern,
+        value: LocatedExpr,
+        is_const: bool,
+    },
     Expression {
         expr: LocatedExpr,
     },
     Return {
         value: Option<LocatedExpr>,
     },
+    Yield {
+        value: LocatedExpr,
+    },
     Break,
     Continue,
     If {
@@ -1676,9 +2964,20 @@ pub enum Stmt {
         body: Vec<LocatedStmt>,
         do_while: bool,
     },
+    /// `switch (value) { case A, B: ... default: ... }`, where each case leaves the switch at
+    /// its end instead of falling through
+    Switch {
+        value: LocatedExpr,
+        cases: Vec<SwitchCase>,
+        default: Option<Vec<LocatedStmt>>,
+    },
     Defer {
         body: Vec<LocatedStmt>,
     },
+    /// `{ ... }`, whose variables go out of scope at its end
+    Block {
+        body: Vec<LocatedStmt>,
+    },
     Destroy {
         expr: LocatedExpr,
     },
@@ -1687,11 +2986,35 @@ pub enum Stmt {
     },
 }
 
-#[derive(Debug)]
+/// A case of a switch, which runs `body` if the switched value equals any of `values`
+#[derive(Debug, Clone)]
+pub struct SwitchCase {
+    pub values: Vec<LocatedExpr>,
+    pub body: Vec<LocatedStmt>,
+}
+
+/// The variables a destructuring `let` declares
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// `{ x, y }`, which declares each variable from the struct field with the same name
+    Struct(Vec<String>),
+
+    /// `[a, b]`, which declares the variables from the elements in order
+    Array(Vec<String>),
+
+    /// `(q, r)`, which declares the variables from the values a function returns in order
+    Tuple(Vec<String>),
+}
+
+#[derive(Debug, Clone)]
 pub enum GlobalStmt {
     Enum {
         name: String,
         variants: Vec<(String, Option<i64>)>,
+
+        /// The values of variants given by constant expressions, like `A = sq(2)`, by the index
+        /// of the variant, which are evaluated into `variants` before type checking
+        values: Vec<(usize, LocatedExpr)>,
     },
     Struct {
         name: String,
@@ -1703,9 +3026,20 @@ pub enum GlobalStmt {
     },
     Function {
         name: String,
+        /// The type parameters of a generic function, like `T` in `func max<T>(a: T, b: T): T`
+        type_params: Vec<Located<String>>,
+        /// The size parameters of a generic function, like `N` in
+        /// `func sum<N: u64>(xs: [N]i32): i32`
+        size_params: Vec<(String, LocatedType)>,
         params: Vec<(String, LocatedType)>,
         ret: LocatedType,
         body: Vec<LocatedStmt>,
+        attrs: Vec<FuncAttr>,
+    },
+    ExternFunction {
+        name: String,
+        params: Vec<(String, LocatedType)>,
+        ret: LocatedType,
     },
     Variable {
         name: String,
@@ -1724,11 +3058,24 @@ pub enum GlobalStmt {
         name: String,
     },
     Import {
+        /// The name the module is accessed by, or its path for a selective import, whose module
+        /// can't be accessed by name
         name: String,
         path: String,
+
+        /// The members a selective import (`from std::math import min, max;`) declares as
+        /// globals, or `None` if the module is imported as a whole
+        members: Option<Vec<String>>,
     },
 }
 
+/// Attributes that can be attached to a function with `@name`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FuncAttr {
+    /// The function is a generator that is lowered to a resumable state machine
+    StateMachine,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Type {
     Void,
@@ -1747,8 +3094,19 @@ pub enum Type {
     Bool,
     Pointer(Box<Type>),
     Array(usize, Box<Type>),
+    /// An array whose length is a size parameter of a generic function, like `[N]i32`
+    GenericArray(String, Box<Type>),
+    /// An array whose length is a constant expression, like `[sq(4)]i32`, which is evaluated
+    /// into an `Array` before type checking
+    ExprArray(LengthExpr, Box<Type>),
     DArray(Box<Type>),
     UserDefinedType(String),
+    /// A pointer to a function with the given parameter and return types
+    Func(Vec<Type>, Box<Type>),
+    /// The values a function returns, like `(i32, i32)`
+    Tuple(Vec<Type>),
+    /// `Result<T, E>`, either a `T` or an error `E`, which `ok(x)` and `err(e)` create
+    Result(Box<Type>, Box<Type>),
 }
 
 impl std::fmt::Display for Type {
@@ -1772,8 +3130,538 @@ impl std::fmt::Display for Type {
             Bool => write!(f, "bool"),
             Pointer(t) => write!(f, "^{t}"),
             Array(l, t) => write!(f, "[{l}]{t}"),
+            GenericArray(size, t) => write!(f, "[{size}]{t}"),
+            ExprArray(length, t) => write!(f, "[{length}]{t}"),
             DArray(t) => write!(f, "[^]{t}"),
             UserDefinedType(n) => write!(f, "{n}"),
+            Func(params, ret) => {
+                let params = params
+                    .iter()
+                    .map(|t| t.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "func({params}): {ret}")
+            }
+            Tuple(elems) => {
+                let elems = elems
+                    .iter()
+                    .map(|t| t.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "({elems})")
+            }
+            Result(ok, err) => write!(f, "Result<{ok}, {err}>"),
+        }
+    }
+}
+
+impl Type {
+    pub fn is_integer(&self) -> bool {
+        self.int_range().is_some()
+    }
+
+    pub fn is_unsigned(&self) -> bool {
+        matches!(
+            self,
+            Type::UInt8 | Type::UInt16 | Type::UInt32 | Type::UInt64
+        )
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || matches!(self, Type::Double | Type::Float)
+    }
+
+    /// The smallest and largest values an integer type can hold
+    pub fn int_range(&self) -> Option<(i128, i128)> {
+        use Type::*;
+
+        match self {
+            Int8 => Some((i8::MIN.into(), i8::MAX.into())),
+            Int16 => Some((i16::MIN.into(), i16::MAX.into())),
+            Int32 => Some((i32::MIN.into(), i32::MAX.into())),
+            Int64 => Some((i64::MIN.into(), i64::MAX.into())),
+            UInt8 => Some((0, u8::MAX.into())),
+            UInt16 => Some((0, u16::MAX.into())),
+            UInt32 => Some((0, u32::MAX.into())),
+            UInt64 => Some((0, u64::MAX.into())),
+            _ => None,
+        }
+    }
+
+    /// Replaces the size parameters in `[N]T` arrays with their values in `sizes`, which turns
+    /// them into `[3]T` for a known size
+    pub fn with_sizes(&self, sizes: &HashMap<String, Size>) -> Type {
+        use Type::*;
+
+        match self {
+            GenericArray(size, t) => match sizes.get(size) {
+                Some(Size::Known(len)) => Array(*len, Box::new(t.with_sizes(sizes))),
+                Some(Size::Param(param)) => {
+                    GenericArray(param.clone(), Box::new(t.with_sizes(sizes)))
+                }
+                None => GenericArray(size.clone(), Box::new(t.with_sizes(sizes))),
+            },
+            Pointer(t) => Pointer(Box::new(t.with_sizes(sizes))),
+            Array(len, t) => Array(*len, Box::new(t.with_sizes(sizes))),
+            DArray(t) => DArray(Box::new(t.with_sizes(sizes))),
+            Func(params, ret) => Func(
+                params.iter().map(|t| t.with_sizes(sizes)).collect(),
+                Box::new(ret.with_sizes(sizes)),
+            ),
+            Tuple(elems) => Tuple(elems.iter().map(|t| t.with_sizes(sizes)).collect()),
+            Result(ok, err) => Result(
+                Box::new(ok.with_sizes(sizes)),
+                Box::new(err.with_sizes(sizes)),
+            ),
+            t => t.clone(),
+        }
+    }
+
+    /// Replaces the type parameters in `self` with their types in `types`
+    pub fn with_types(&self, types: &HashMap<String, Type>) -> Type {
+        use Type::*;
+
+        match self {
+            UserDefinedType(name) => types.get(name).cloned().unwrap_or_else(|| self.clone()),
+            Pointer(t) => Pointer(Box::new(t.with_types(types))),
+            Array(len, t) => Array(*len, Box::new(t.with_types(types))),
+            GenericArray(size, t) => GenericArray(size.clone(), Box::new(t.with_types(types))),
+            DArray(t) => DArray(Box::new(t.with_types(types))),
+            Func(params, ret) => Func(
+                params.iter().map(|t| t.with_types(types)).collect(),
+                Box::new(ret.with_types(types)),
+            ),
+            Tuple(elems) => Tuple(elems.iter().map(|t| t.with_types(types)).collect()),
+            Result(ok, err) => Result(
+                Box::new(ok.with_types(types)),
+                Box::new(err.with_types(types)),
+            ),
+            t => t.clone(),
+        }
+    }
+}
+
+/// Returns the keyword `word` is at most one edit away from
+fn similar_keyword<'a>(word: &str, keywords: &[&'a str]) -> Option<&'a str> {
+    keywords
+        .iter()
+        .find(|keyword| edit_distance(word, keyword) <= 1)
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logos::Logos;
+
+    fn parse(source: &str) -> Result<Vec<LocatedGlobalStmt>, ParseErrors> {
+        Parser::new(Token::lexer(source)).parse()
+    }
+
+    #[test]
+    fn trailing_commas() {
+        let ast = parse(
+            "enum E { A, B, }\n\
+             func f(a: i32, b: [2]i32,): i32 { return a; }\n\
+             func main(): void { f(1, [2, 3,],); P { x = 1, y = 2, }; }",
+        )
+        .unwrap();
+        let GlobalStmt::Function { params, .. } = &ast[1].node else {
+            panic!("expected a function");
+        };
+        assert_eq!(params.len(), 2);
+
+        // Both missing commas are reported instead of just the first one
+        let errors = parse("func f(a: i32 b: i32): void {}\nfunc main(): void { f(1 2); }")
+            .unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].1, "Expected ',' between parameters but got identifier 'b'");
+        assert_eq!(errors[1].1, "Expected ',' between arguments but got integer literal '2'");
+    }
+
+    #[test]
+    fn global_variables() {
+        let ast = parse("let a: i32 = 1;\nlet b = 2.0;\nlet c: ^u8;").unwrap();
+        assert!(matches!(
+            &ast[0].node,
+            GlobalStmt::Variable { name, t: Some(Type::Int32), value: Some(_), .. } if name == "a"
+        ));
+        assert!(matches!(
+            &ast[1].node,
+            GlobalStmt::Variable { name, t: None, value: Some(_), .. } if name == "b"
+        ));
+        assert!(matches!(
+            &ast[2].node,
+            GlobalStmt::Variable { name, t: Some(Type::Pointer(_)), value: None, .. } if name == "c"
+        ));
+
+        let errors = parse("let 1 = 2;").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Expected a variable name after let but got integer literal '1'"
+        );
+        let errors = parse("let a = 1\nfunc main(): void {}").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Expected ';' after variable declaration but got 'func'"
+        );
+    }
+
+    #[test]
+    fn local_variables() {
+        let ast = parse(
+            "func main(): void { let a: i32 = 1; const b = 2; let { x, y } = p; const [c, d] = e; }",
+        )
+        .unwrap();
+        let GlobalStmt::Function { body, .. } = &ast[0].node else {
+            panic!("expected a function");
+        };
+        assert!(matches!(
+            &body[0].node,
+            Stmt::Variable { name, t: Some(Type::Int32), is_const: false, .. } if name == "a"
+        ));
+        assert!(matches!(
+            &body[1].node,
+            Stmt::Variable { name, is_const: true, .. } if name == "b"
+        ));
+        assert!(matches!(
+            &body[2].node,
+            Stmt::Destructure { pattern: Pattern::Struct(names), is_const: false, .. }
+                if names == &["x", "y"]
+        ));
+        assert!(matches!(
+            &body[3].node,
+            Stmt::Destructure { pattern: Pattern::Array(names), is_const: true, .. }
+                if names == &["c", "d"]
+        ));
+
+        let errors = parse("func main(): void { const c; }").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Expected '=' and a value for the constant 'c' but got ';'"
+        );
+        let errors = parse("func main(): void { let { x, 1 } = p; }").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Expected a variable name in the pattern but got integer literal '1'"
+        );
+    }
+
+    #[test]
+    fn keyword_suggestions() {
+        let errors = parse("fnuc main(): void {}").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Expected a global statement but got identifier 'fnuc', did you mean 'func'?"
+        );
+
+        let errors = parse("func main(): i32 { retrun 0; }").unwrap_err();
+        assert_eq!(
+            errors[0],
+            (
+                19..25,
+                "Expected ';' at the end of a statement but got integer literal '0', did you \
+                 mean 'return' instead of 'retrun'?"
+                    .to_string()
+            )
+        );
+
+        // Identifiers that aren't close to a keyword get the plain error
+        let errors = parse("main(): void {}").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Expected a global statement but got identifier 'main'"
+        );
+    }
+
+    #[test]
+    fn imports() {
+        let ast = parse(
+            "import \"std/io\"\nimport geo \"geo/shapes.clla\";\nimport \"lib/util.clla\";\n\
+             func main(): void { geo::area(io::x); }",
+        )
+        .unwrap();
+        let names: Vec<_> = ast[..3]
+            .iter()
+            .map(|stmt| match &stmt.node {
+                GlobalStmt::Import { name, path, .. } => (name.as_str(), path.as_str()),
+                _ => panic!("expected an import"),
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("io", "std/io"),
+                ("geo", "geo/shapes.clla"),
+                ("util", "lib/util.clla")
+            ]
+        );
+
+        let ast = parse(
+            "import m = std::math;\nimport std::io\nimport s = \"geo/shapes\";\n\
+             from std::math import min, max;",
+        )
+        .unwrap();
+        let imports: Vec<_> = ast
+            .iter()
+            .map(|stmt| match &stmt.node {
+                GlobalStmt::Import {
+                    name,
+                    path,
+                    members,
+                } => (name.as_str(), path.as_str(), members.clone()),
+                _ => panic!("expected an import"),
+            })
+            .collect();
+        let members = Some(vec!["min".to_string(), "max".to_string()]);
+        assert_eq!(
+            imports,
+            [
+                ("m", "std/math", None),
+                ("io", "std/io", None),
+                ("s", "geo/shapes", None),
+                ("std/math", "std/math", members),
+            ]
+        );
+
+        let errors = parse("func main(): void { 1::x; }").unwrap_err();
+        assert_eq!(errors[0].1, "Expected a module name before '::'");
+
+        for (source, expected) in [
+            ("func main(): void { (a) { y = 1 }; }", "Expected a struct name before {"),
+            ("func main(): void { (a)->B; }", "Expected an enum name before '->'"),
+            (
+                "func main(): void { a.5; }",
+                "Expected a field name after '.' but got integer literal '5'",
+            ),
+        ] {
+            let errors = parse(source).unwrap_err();
+            assert_eq!(errors[0].1, expected, "{source}");
         }
+
+        let errors = parse("from std::math import;").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Expected a name to import from 'std/math' but got ';'"
+        );
+
+        let errors = parse("from std::math import min, min;").unwrap_err();
+        assert_eq!(errors[0].1, "'min' is already imported by this import");
+    }
+
+    #[test]
+    fn sizeof() {
+        let ast = parse("const S: u64 = sizeof(^i32) * 2;").unwrap();
+        let GlobalStmt::Constant { value, .. } = &ast[0].node else {
+            panic!("expected a constant");
+        };
+        assert!(matches!(
+            &value.node,
+            Expr::Binary { left, .. } if matches!(&left.node, Expr::Sizeof { t: Type::Pointer(_) })
+        ));
+
+        let errors = parse("const S: u64 = sizeof i32;").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Expected '(' after sizeof but got 'i32'"
+        );
+    }
+
+    #[test]
+    fn switch() {
+        let ast = parse(
+            "func main(): void { switch (c) { case Color->Red, Color->Green: f(); default: } }",
+        )
+        .unwrap();
+        let GlobalStmt::Function { body, .. } = &ast[0].node else {
+            panic!("expected a function");
+        };
+        let Stmt::Switch { cases, default, .. } = &body[0].node else {
+            panic!("expected a switch");
+        };
+        assert_eq!(cases.len(), 1);
+        assert!(matches!(
+            &cases[0].values[1].node,
+            Expr::EnumVarAccess { ident, variant } if ident == "Color" && variant == "Green"
+        ));
+        assert_eq!(cases[0].body.len(), 1);
+        assert!(default.as_ref().is_some_and(|body| body.is_empty()));
+
+        let errors = parse("func main(): void { switch (x) { case 1 f(); } }").unwrap_err();
+        assert_eq!(errors[0].1, "Expected ':' after case but got identifier 'f'");
+
+        let errors = parse("func main(): void { switch (x) { default: default: } }").unwrap_err();
+        assert_eq!(errors[0].1, "A switch can only have one default case");
+    }
+
+    #[test]
+    fn tuples() {
+        let ast = parse(
+            "func f(): (i32, ^u8) { return (1, null); }\n\
+             func main(): void { let (a, b) = f(); }",
+        )
+        .unwrap();
+        let GlobalStmt::Function { ret, body, .. } = &ast[0].node else {
+            panic!("expected a function");
+        };
+        assert_eq!(ret.node.to_string(), "(i32, ^u8)");
+        let Stmt::Return { value: Some(value) } = &body[0].node else {
+            panic!("expected a return");
+        };
+        assert!(matches!(&value.node, Expr::Tuple { elems } if elems.len() == 2));
+        let GlobalStmt::Function { body, .. } = &ast[1].node else {
+            panic!("expected a function");
+        };
+        assert!(matches!(
+            &body[0].node,
+            Stmt::Destructure { pattern: Pattern::Tuple(names), .. } if names == &["a", "b"]
+        ));
+
+        // A single parenthesized expression is still just that
+        let ast = parse("func main(): void { (1); }").unwrap();
+        let GlobalStmt::Function { body, .. } = &ast[0].node else {
+            panic!("expected a function");
+        };
+        assert!(matches!(
+            &body[0].node,
+            Stmt::Expression { expr } if matches!(expr.node, Expr::Parenthesized { .. })
+        ));
+
+        let errors = parse("func f(): (i32) {}").unwrap_err();
+        assert_eq!(errors[0].1, "A tuple type needs at least two types");
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        let ast = parse("func main(): void { x |= a & 1 == b ^ c << 2; }").unwrap();
+        let GlobalStmt::Function { body, .. } = &ast[0].node else {
+            panic!("expected a function");
+        };
+        let Stmt::Expression { expr } = &body[0].node else {
+            panic!("expected an expression");
+        };
+        let Expr::Assign {
+            op: AssignOp::BitOrAssign,
+            value,
+            ..
+        } = &expr.node
+        else {
+            panic!("expected a compound assignment");
+        };
+
+        // `(a & (1 == b)) ^ (c << 2)`, like in C
+        let Expr::Binary {
+            left,
+            op: BinOp::XOr,
+            right,
+        } = &value.node
+        else {
+            panic!("expected '^' at the top");
+        };
+        assert!(matches!(
+            &left.node,
+            Expr::Binary { op: BinOp::BitAnd, right, .. }
+                if matches!(right.node, Expr::Binary { op: BinOp::Eq, .. })
+        ));
+        assert!(matches!(
+            right.node,
+            Expr::Binary {
+                op: BinOp::LShift,
+                ..
+            }
+        ));
+
+        let errors = parse("func main(): void { x = a || b; }").unwrap_err();
+        assert_eq!(errors[0], (26..28, "Use 'or' instead of '||'".to_string()));
+    }
+
+    #[test]
+    fn casts() {
+        let ast = parse("let b = cast(^u8, x + 1);").unwrap();
+        let GlobalStmt::Variable {
+            value: Some(value), ..
+        } = &ast[0].node
+        else {
+            panic!("expected a variable");
+        };
+        let Expr::Cast { t, expr, check } = &value.node else {
+            panic!("expected a cast");
+        };
+        assert_eq!(t.node, Type::Pointer(Box::new(Type::UInt8)));
+        assert!(matches!(expr.node, Expr::Binary { .. }));
+        assert_eq!(check.get(), None);
+        assert_eq!(value.span, 8..24);
+
+        let errors = parse("let b = cast(u8 x);").unwrap_err();
+        assert_eq!(
+            errors[0].1,
+            "Expected ',' between the type and the value of the cast but got identifier 'x'"
+        );
+    }
+
+    #[test]
+    fn size_params() {
+        let ast = parse("func sum<N: u64>(xs: [N]i32): i32 { return 0; }").unwrap();
+        let GlobalStmt::Function {
+            size_params,
+            params,
+            ..
+        } = &ast[0].node
+        else {
+            panic!("expected a function");
+        };
+        assert_eq!(size_params[0].0, "N");
+        assert_eq!(size_params[0].1.node, Type::UInt64);
+        assert_eq!(
+            params[0].1.node,
+            Type::GenericArray("N".to_string(), Box::new(Type::Int32))
+        );
+        assert_eq!(params[0].1.node.to_string(), "[N]i32");
+
+        let ast = parse("func main(): void { f(a); }").unwrap();
+        let GlobalStmt::Function { size_params, .. } = &ast[0].node else {
+            panic!("expected a function");
+        };
+        assert!(size_params.is_empty());
+
+        let ast = parse("func first<T, N: u64>(xs: [N]T): T { return xs[0]; }").unwrap();
+        let GlobalStmt::Function {
+            type_params,
+            size_params,
+            params,
+            ..
+        } = &ast[0].node
+        else {
+            panic!("expected a function");
+        };
+        assert_eq!(type_params[0].node, "T");
+        assert_eq!(size_params[0].0, "N");
+        assert_eq!(params[0].1.node.to_string(), "[N]T");
+    }
+
+    #[test]
+    fn char_literals() {
+        let ast = parse("let c = '\\n';").unwrap();
+        let GlobalStmt::Variable {
+            value: Some(value), ..
+        } = &ast[0].node
+        else {
+            panic!("expected a variable");
+        };
+        assert!(matches!(value.node, Expr::Char(b'\n')));
+        assert_eq!(value.span, 8..12);
+
+        // The error points at the invalid escape rather than the whole literal
+        let errors = parse("let s = \"ab\\qc\";").unwrap_err();
+        assert_eq!(errors[0].0, 11..13);
+    }
+
+    #[test]
+    fn stray_characters() {
+        let errors = parse("func main(): void { x # 1; }").unwrap_err();
+        assert_eq!(
+            errors[0],
+            (22..23, "Unexpected character '#' (U+0023 NUMBER SIGN)".to_string())
+        );
     }
 }
@@ -0,0 +1,351 @@
+//! Prints the AST as an indented tree for `--emit ast`
+//!
+//! Declarations and statements are shown with the keywords they are written with, and
+//! expressions with the kind of node and its operator or name, each child one level deeper:
+//!
+//! ```text
+//! func main(): i32
+//!   let x: i32
+//!     Int 1
+//!   return
+//!     Binary +
+//!       Ident x
+//!       Int 2
+//! ```
+//!
+//! Types are shown as they are written, and spans are left out to keep the tree readable, so
+//! `--dump-ast` is still there for everything the nodes hold.
+
+use tamago::{BinOp, UnaryOp};
+
+use crate::parser::*;
+
+/// Returns the tree of `ast`, one node per line
+pub fn print_ast(ast: &[LocatedGlobalStmt]) -> String {
+    let mut printer = Printer::default();
+    for stmt in ast {
+        printer.global_stmt(&stmt.node);
+    }
+    printer.out
+}
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    depth: usize,
+}
+
+impl Printer {
+    fn line(&mut self, text: impl AsRef<str>) {
+        self.out.push_str(&"  ".repeat(self.depth));
+        self.out.push_str(text.as_ref());
+        self.out.push('\n');
+    }
+
+    /// Prints `text`, and what `children` prints one level deeper
+    fn node(&mut self, text: impl AsRef<str>, children: impl FnOnce(&mut Self)) {
+        self.line(text);
+        self.depth += 1;
+        children(self);
+        self.depth -= 1;
+    }
+
+    fn global_stmt(&mut self, stmt: &GlobalStmt) {
+        use GlobalStmt::*;
+
+        match stmt {
+            Enum {
+                name,
+                variants,
+                values,
+            } => self.node(format!("enum {name}"), |p| {
+                for (i, (variant, value)) in variants.iter().enumerate() {
+                    let expr = values.iter().find(|(idx, _)| *idx == i);
+                    match (value, expr) {
+                        (Some(value), _) => p.line(format!("{variant} = {value}")),
+                        (None, Some((_, expr))) => {
+                            p.node(format!("{variant} ="), |p| p.expr(&expr.node))
+                        }
+                        (None, None) => p.line(variant),
+                    }
+                }
+            }),
+            Struct { name, fields } => self.node(format!("struct {name}"), |p| p.fields(fields)),
+            Union { name, fields } => self.node(format!("union {name}"), |p| p.fields(fields)),
+            Function {
+                name,
+                type_params,
+                size_params,
+                params,
+                ret,
+                body,
+                attrs,
+            } => {
+                let attrs: String = attrs
+                    .iter()
+                    .map(|attr| match attr {
+                        FuncAttr::StateMachine => "@statemachine ",
+                    })
+                    .collect();
+                let generics: Vec<String> = type_params
+                    .iter()
+                    .map(|t| t.node.clone())
+                    .chain(size_params.iter().map(|(n, t)| format!("{n}: {}", t.node)))
+                    .collect();
+                let generics = match generics.is_empty() {
+                    true => String::new(),
+                    false => format!("<{}>", generics.join(", ")),
+                };
+                self.node(
+                    format!(
+                        "{attrs}func {name}{generics}({}): {}",
+                        params_list(params),
+                        ret.node
+                    ),
+                    |p| p.block(body),
+                );
+            }
+            ExternFunction { name, params, ret } => self.line(format!(
+                "extern func {name}({}): {}",
+                params_list(params),
+                ret.node
+            )),
+            Variable {
+                name,
+                t,
+                value,
+                private,
+            } => {
+                let t = t.as_ref().map(|t| format!(": {t}")).unwrap_or_default();
+                self.node(format!("{}let {name}{t}", visibility(*private)), |p| {
+                    if let Some(value) = value {
+                        p.expr(&value.node);
+                    }
+                });
+            }
+            Constant {
+                name,
+                t,
+                value,
+                private,
+            } => {
+                let t = t
+                    .as_ref()
+                    .map(|t| format!(": {}", t.node))
+                    .unwrap_or_default();
+                self.node(format!("{}const {name}{t}", visibility(*private)), |p| {
+                    p.expr(&value.node)
+                });
+            }
+            Alias { t, name } => self.line(format!("alias {name} = {}", t.node)),
+            Import {
+                name,
+                path,
+                members: None,
+            } => self.line(format!("import \"{path}\" as {name}")),
+            Import {
+                path,
+                members: Some(members),
+                ..
+            } => self.line(format!("from \"{path}\" import {}", members.join(", "))),
+        }
+    }
+
+    fn fields(&mut self, fields: &[(String, LocatedType)]) {
+        for (name, t) in fields {
+            self.line(format!("{name}: {}", t.node));
+        }
+    }
+
+    fn block(&mut self, body: &[LocatedStmt]) {
+        for stmt in body {
+            self.stmt(&stmt.node);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Variable {
+                name,
+                t,
+                value,
+                is_const,
+                ..
+            } => {
+                let keyword = if *is_const { "const" } else { "let" };
+                let t = t.as_ref().map(|t| format!(": {t}")).unwrap_or_default();
+                self.node(format!("{keyword} {name}{t}"), |p| {
+                    if let Some(value) = value {
+                        p.expr(&value.node);
+                    }
+                });
+            }
+            Stmt::Destructure {
+                pattern,
+                value,
+                is_const,
+            } => {
+                let keyword = if *is_const { "const" } else { "let" };
+                let pattern = match pattern {
+                    Pattern::Struct(names) => format!("{{ {} }}", names.join(", ")),
+                    Pattern::Array(names) => format!("[{}]", names.join(", ")),
+                    Pattern::Tuple(names) => format!("({})", names.join(", ")),
+                };
+                self.node(format!("{keyword} {pattern}"), |p| p.expr(&value.node));
+            }
+            Stmt::Expression { expr } => self.node("expr", |p| p.expr(&expr.node)),
+            Stmt::Return { value } => self.node("return", |p| {
+                if let Some(value) = value {
+                    p.expr(&value.node);
+                }
+            }),
+            Stmt::Yield { value } => self.node("yield", |p| p.expr(&value.node)),
+            Stmt::Break => self.line("break"),
+            Stmt::Continue => self.line("continue"),
+            Stmt::If { cond, then, other } => self.node("if", |p| {
+                p.expr(&cond.node);
+                p.node("then", |p| p.block(then));
+                if let Some(other) = other {
+                    p.node("else", |p| p.block(other));
+                }
+            }),
+            Stmt::While {
+                cond,
+                body,
+                do_while,
+            } => {
+                let keyword = if *do_while { "do while" } else { "while" };
+                self.node(keyword, |p| {
+                    p.expr(&cond.node);
+                    p.node("body", |p| p.block(body));
+                });
+            }
+            Stmt::Switch {
+                value,
+                cases,
+                default,
+            } => self.node("switch", |p| {
+                p.expr(&value.node);
+                for case in cases {
+                    p.node("case", |p| {
+                        for value in &case.values {
+                            p.expr(&value.node);
+                        }
+                        p.node("body", |p| p.block(&case.body));
+                    });
+                }
+                if let Some(default) = default {
+                    p.node("default", |p| p.block(default));
+                }
+            }),
+            Stmt::Defer { body } => self.node("defer", |p| p.block(body)),
+            Stmt::Block { body } => self.node("block", |p| p.block(body)),
+            Stmt::Destroy { expr } => self.node("destroy", |p| p.expr(&expr.node)),
+            Stmt::Free { expr } => self.node("free", |p| p.expr(&expr.node)),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Int(i) => self.line(format!("Int {i}")),
+            Expr::UInt(u) => self.line(format!("UInt {u}")),
+            Expr::Double(d) => self.line(format!("Double {d:?}")),
+            Expr::Bool(b) => self.line(format!("Bool {b}")),
+            Expr::Char(c) => self.line(format!("Char '{}'", c.escape_ascii())),
+            Expr::Str(s) => self.line(format!("Str \"{}\"", s.escape_default())),
+            Expr::Ident(name) => self.line(format!("Ident {name}")),
+            Expr::Binary { left, op, right } => self.node(format!("Binary {}", bin_op(op)), |p| {
+                p.expr(&left.node);
+                p.expr(&right.node);
+            }),
+            Expr::Parenthesized { expr } => self.node("Parenthesized", |p| p.expr(&expr.node)),
+            Expr::Unary { op, expr } => {
+                self.node(format!("Unary {}", unary_op(op)), |p| p.expr(&expr.node))
+            }
+            Expr::Assign { lvalue, op, value } => self.node(format!("Assign {op}"), |p| {
+                p.expr(&lvalue.node);
+                p.expr(&value.node);
+            }),
+            Expr::Ternary { cond, lexpr, rexpr } => self.node("Ternary", |p| {
+                p.expr(&cond.node);
+                p.expr(&lexpr.node);
+                p.expr(&rexpr.node);
+            }),
+            Expr::FnCall { name, args, .. } => self.node("Call", |p| {
+                p.expr(&name.node);
+                for arg in args {
+                    p.expr(&arg.node);
+                }
+            }),
+            Expr::MemAccess { expr, member } => {
+                self.node(format!("Member .{member}"), |p| p.expr(&expr.node))
+            }
+            Expr::EnumVarAccess { ident, variant } => {
+                self.line(format!("EnumVariant {ident}->{variant}"))
+            }
+            Expr::ModuleAccess { module, member } => {
+                self.line(format!("ModuleAccess {module}::{member}"))
+            }
+            Expr::ArrIndex { arr, idx } => self.node("Index", |p| {
+                p.expr(&arr.node);
+                p.expr(&idx.node);
+            }),
+            Expr::Cast { t, expr, .. } => {
+                self.node(format!("Cast {}", t.node), |p| p.expr(&expr.node))
+            }
+            Expr::Sizeof { t } => self.line(format!("Sizeof {t}")),
+            Expr::InitArr { elems } => self.node("Array", |p| {
+                for elem in elems {
+                    p.expr(&elem.node);
+                }
+            }),
+            Expr::InitArrDesignated { idxs, elems } => self.node("Array", |p| {
+                for (idx, elem) in idxs.iter().zip(elems) {
+                    p.node(format!("[{idx}]"), |p| p.expr(&elem.node));
+                }
+            }),
+            Expr::InitStruct { ident, args } => self.node(format!("Struct {ident}"), |p| {
+                for (name, value) in args {
+                    p.node(format!(".{name}"), |p| p.expr(&value.node));
+                }
+            }),
+            Expr::Try { expr, .. } => self.node("Try", |p| p.expr(&expr.node)),
+            Expr::Tuple { elems } => self.node("Tuple", |p| {
+                for elem in elems {
+                    p.expr(&elem.node);
+                }
+            }),
+            Expr::Make { t } => self.line(format!("Make {t}")),
+            Expr::New { t } => self.line(format!("New {t}")),
+        }
+    }
+}
+
+fn params_list(params: &[(String, LocatedType)]) -> String {
+    params
+        .iter()
+        .map(|(name, t)| format!("{name}: {}", t.node))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn visibility(private: bool) -> &'static str {
+    if private { "private " } else { "" }
+}
+
+/// The operator as it is written in castella, which differs from C for the logical ones
+fn bin_op(op: &BinOp) -> String {
+    match op {
+        BinOp::And => "and".to_string(),
+        BinOp::Or => "or".to_string(),
+        op => op.to_string(),
+    }
+}
+
+fn unary_op(op: &UnaryOp) -> String {
+    match op {
+        UnaryOp::LogicNeg => "not".to_string(),
+        UnaryOp::Deref => "^".to_string(),
+        op => op.to_string(),
+    }
+}
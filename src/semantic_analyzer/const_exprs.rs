@@ -0,0 +1,420 @@
+//! Evaluates the constant expressions types depend on, before type checking
+//!
+//! The length of an array can be a global constant, like `[LEN]i32`, or any other constant
+//! expression, like `[sq(4)]i32`. The type checker compares arrays by their lengths, so each such
+//! length is evaluated first, and the array becomes a plain `[16]i32`. A length named like a size
+//! parameter of the function it is in is left to the type checker.
+//!
+//! The values of enum variants, like `A = sq(2)`, are evaluated here too, since C needs them to
+//! be integer constants.
+
+use std::collections::HashMap;
+
+use crate::parser::*;
+use crate::semantic_analyzer::Message;
+use crate::semantic_analyzer::consteval::{ConstEvaluator, Value};
+
+/// Replaces the constant expressions used as lengths of arrays and values of enum variants in
+/// `ast` with their values, and returns the errors of the ones that can't be
+pub fn evaluate(ast: &mut [LocatedGlobalStmt]) -> Vec<Message> {
+    let mut lengths = vec![];
+    visit_types(ast, &mut |t, _, size_params| {
+        collect_lengths(t, size_params, &mut lengths)
+    });
+
+    let mut errors = vec![];
+    let mut evaluator = ConstEvaluator::new(ast);
+    let lengths = evaluate_lengths(ast, &mut evaluator, &lengths);
+    let enum_values = evaluate_enum_values(ast, &mut evaluator, &mut errors);
+
+    for (stmt, variant, value) in enum_values {
+        if let GlobalStmt::Enum { variants, .. } = &mut ast[stmt].node {
+            variants[variant].1 = Some(value);
+        }
+    }
+
+    visit_types(ast, &mut |t, span, size_params| {
+        replace_lengths(t, span, size_params, &lengths, &mut errors)
+    });
+    errors
+}
+
+/// A length of an array that is evaluated before type checking
+enum Length {
+    /// A global constant, or a size parameter of a function it isn't in
+    Constant(String),
+    Expr(LengthExpr),
+}
+
+impl Length {
+    /// How the length is written, which its value is looked up by
+    fn source(&self) -> &str {
+        match self {
+            Length::Constant(name) => name,
+            Length::Expr(length) => &length.source,
+        }
+    }
+}
+
+/// Why a length can't be the length of an array, along with notes explaining it
+type LengthError = (String, Vec<Message>);
+
+/// Evaluates `lengths` by how they are written
+fn evaluate_lengths<'ast>(
+    ast: &'ast [LocatedGlobalStmt],
+    evaluator: &mut ConstEvaluator<'ast>,
+    lengths: &'ast [Length],
+) -> HashMap<String, Result<usize, LengthError>> {
+    let constants: HashMap<&str, &LocatedExpr> = ast
+        .iter()
+        .filter_map(|stmt| match &stmt.node {
+            GlobalStmt::Constant { name, value, .. } => Some((name.as_str(), value)),
+            _ => None,
+        })
+        .collect();
+
+    let mut values = HashMap::new();
+    for length in lengths {
+        let value = match length {
+            Length::Constant(name) => match constants.get(name.as_str()) {
+                Some(value) => *value,
+                None => continue,
+            },
+            Length::Expr(length) => &length.expr,
+        };
+
+        let source = length.source();
+        let value = match evaluator.eval(value) {
+            Ok(Some(Value::Int(len))) => usize::try_from(len).map_err(|_| {
+                (
+                    format!("'{source}' cannot be the length of an array, since it is {len}"),
+                    vec![],
+                )
+            }),
+            Ok(Some(_)) => Err((
+                format!("'{source}' cannot be the length of an array, since it isn't an integer"),
+                vec![],
+            )),
+            Ok(None) => Err((
+                format!(
+                    "'{source}' cannot be the length of an array, since its value isn't known at \
+                     compile time"
+                ),
+                evaluator.take_notes(),
+            )),
+            // The type checker reports the errors of constants along with the rest of theirs,
+            // but never sees the expressions
+            Err((_, msg)) => match length {
+                Length::Constant(_) => continue,
+                Length::Expr(_) => Err((msg, vec![])),
+            },
+        };
+        values.insert(source.to_string(), value);
+    }
+
+    values
+}
+
+/// Evaluates the values of enum variants given by constant expressions, and returns them along
+/// with the index of their enum in `ast` and their index in it
+fn evaluate_enum_values<'ast>(
+    ast: &'ast [LocatedGlobalStmt],
+    evaluator: &mut ConstEvaluator<'ast>,
+    errors: &mut Vec<Message>,
+) -> Vec<(usize, usize, i64)> {
+    let mut enum_values = vec![];
+    for (i, stmt) in ast.iter().enumerate() {
+        let GlobalStmt::Enum {
+            name,
+            variants,
+            values,
+        } = &stmt.node
+        else {
+            continue;
+        };
+
+        for (variant, value) in values {
+            let variant_name = format!("{name}->{}", variants[*variant].0);
+            match evaluator.eval(value) {
+                Ok(Some(Value::Int(n))) => match i64::try_from(n) {
+                    Ok(n) => enum_values.push((i, *variant, n)),
+                    Err(_) => errors.push((
+                        value.span.clone(),
+                        format!(
+                            "Enum value '{n}' is out of range, it must be between {} and {}",
+                            i64::MIN,
+                            i64::MAX
+                        ),
+                    )),
+                },
+                Ok(Some(_)) => errors.push((
+                    value.span.clone(),
+                    format!("The value of '{variant_name}' must be an integer"),
+                )),
+                Ok(None) => {
+                    errors.push((
+                        value.span.clone(),
+                        format!("The value of '{variant_name}' must be known at compile time"),
+                    ));
+                    errors.extend(evaluator.take_notes());
+                }
+                Err(err) => errors.push(err),
+            }
+        }
+    }
+
+    enum_values
+}
+
+/// Adds the lengths of the arrays in `t` that are evaluated before type checking to `lengths`
+fn collect_lengths(t: &Type, size_params: &[String], lengths: &mut Vec<Length>) {
+    match t {
+        Type::GenericArray(size, elem) => {
+            if !size_params.contains(size) && lengths.iter().all(|len| len.source() != size) {
+                lengths.push(Length::Constant(size.clone()));
+            }
+            collect_lengths(elem, size_params, lengths);
+        }
+        Type::ExprArray(length, elem) => {
+            if lengths.iter().all(|len| len.source() != length.source) {
+                lengths.push(Length::Expr(length.clone()));
+            }
+            collect_lengths(elem, size_params, lengths);
+        }
+        Type::Pointer(elem) | Type::Array(_, elem) | Type::DArray(elem) => {
+            collect_lengths(elem, size_params, lengths)
+        }
+        Type::Func(params, ret) => {
+            for t in params.iter().chain([&**ret]) {
+                collect_lengths(t, size_params, lengths);
+            }
+        }
+        Type::Tuple(elems) => {
+            for t in elems {
+                collect_lengths(t, size_params, lengths);
+            }
+        }
+        Type::Result(ok, err) => {
+            collect_lengths(ok, size_params, lengths);
+            collect_lengths(err, size_params, lengths);
+        }
+        _ => {}
+    }
+}
+
+/// Replaces the lengths in `t` that are evaluated with their values
+fn replace_lengths(
+    t: &mut Type,
+    span: &Span,
+    size_params: &[String],
+    lengths: &HashMap<String, Result<usize, LengthError>>,
+    errors: &mut Vec<Message>,
+) {
+    let mut replace = |t: &mut Type| replace_lengths(t, span, size_params, lengths, errors);
+
+    let source = match t {
+        Type::GenericArray(size, _) if !size_params.contains(size) => Some(size.clone()),
+        Type::ExprArray(length, _) => Some(length.source.clone()),
+        _ => None,
+    };
+
+    match t {
+        Type::Pointer(elem)
+        | Type::Array(_, elem)
+        | Type::GenericArray(_, elem)
+        | Type::ExprArray(_, elem)
+        | Type::DArray(elem) => replace(elem),
+        Type::Func(params, ret) => {
+            for t in params.iter_mut().chain([&mut **ret]) {
+                replace(t);
+            }
+        }
+        Type::Tuple(elems) => {
+            for t in elems {
+                replace(t);
+            }
+        }
+        Type::Result(ok, err) => {
+            replace(ok);
+            replace(err);
+        }
+        _ => {}
+    }
+
+    match source.and_then(|source| lengths.get(&source)) {
+        Some(Ok(len)) => {
+            let (Type::GenericArray(_, elem) | Type::ExprArray(_, elem)) = t else {
+                unreachable!();
+            };
+            let elem = std::mem::replace(&mut **elem, Type::Void);
+            *t = Type::Array(*len, Box::new(elem));
+        }
+        Some(Err((msg, notes))) => {
+            errors.push((span.clone(), msg.clone()));
+            errors.extend(notes.iter().cloned());
+        }
+        None => {}
+    }
+}
+
+/// A function called with each type written in a program, the span it is written at, and the
+/// names of the size parameters of the function it is in
+type TypeVisitor<'f> = dyn FnMut(&mut Type, &Span, &[String]) + 'f;
+
+fn visit_types(ast: &mut [LocatedGlobalStmt], f: &mut TypeVisitor) {
+    for stmt in ast {
+        let span = &stmt.span;
+        match &mut stmt.node {
+            GlobalStmt::Struct { fields, .. } | GlobalStmt::Union { fields, .. } => {
+                for (_, t) in fields {
+                    f(&mut t.node, &t.span, &[]);
+                }
+            }
+            GlobalStmt::Function {
+                size_params,
+                params,
+                ret,
+                body,
+                ..
+            } => {
+                let size_params: Vec<String> =
+                    size_params.iter().map(|(name, _)| name.clone()).collect();
+                for (_, t) in params {
+                    f(&mut t.node, &t.span, &size_params);
+                }
+                f(&mut ret.node, &ret.span, &size_params);
+                visit_block(body, f, &size_params);
+            }
+            GlobalStmt::ExternFunction { params, ret, .. } => {
+                for (_, t) in params {
+                    f(&mut t.node, &t.span, &[]);
+                }
+                f(&mut ret.node, &ret.span, &[]);
+            }
+            GlobalStmt::Variable { t, value, .. } => {
+                if let Some(t) = t {
+                    f(t, span, &[]);
+                }
+                if let Some(value) = value {
+                    visit_expr(value, f, &[]);
+                }
+            }
+            GlobalStmt::Constant { t, value, .. } => {
+                if let Some(t) = t {
+                    f(&mut t.node, &t.span, &[]);
+                }
+                visit_expr(value, f, &[]);
+            }
+            GlobalStmt::Alias { t, .. } => f(&mut t.node, &t.span, &[]),
+            GlobalStmt::Enum { .. } | GlobalStmt::Import { .. } => {}
+        }
+    }
+}
+
+fn visit_block(body: &mut [LocatedStmt], f: &mut TypeVisitor, size_params: &[String]) {
+    for stmt in body {
+        let span = &stmt.span;
+        match &mut stmt.node {
+            Stmt::Variable { t, value, .. } => {
+                if let Some(t) = t {
+                    f(t, span, size_params);
+                }
+                if let Some(value) = value {
+                    visit_expr(value, f, size_params);
+                }
+            }
+            Stmt::Destructure { value: expr, .. }
+            | Stmt::Expression { expr }
+            | Stmt::Yield { value: expr }
+            | Stmt::Return { value: Some(expr) }
+            | Stmt::Destroy { expr }
+            | Stmt::Free { expr } => visit_expr(expr, f, size_params),
+            Stmt::If { cond, then, other } => {
+                visit_expr(cond, f, size_params);
+                visit_block(then, f, size_params);
+                if let Some(other) = other {
+                    visit_block(other, f, size_params);
+                }
+            }
+            Stmt::While { cond, body, .. } => {
+                visit_expr(cond, f, size_params);
+                visit_block(body, f, size_params);
+            }
+            Stmt::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                visit_expr(value, f, size_params);
+                for case in cases {
+                    for value in &mut case.values {
+                        visit_expr(value, f, size_params);
+                    }
+                    visit_block(&mut case.body, f, size_params);
+                }
+                if let Some(default) = default {
+                    visit_block(default, f, size_params);
+                }
+            }
+            Stmt::Defer { body } | Stmt::Block { body } => visit_block(body, f, size_params),
+            Stmt::Return { value: None } | Stmt::Break | Stmt::Continue => {}
+        }
+    }
+}
+
+fn visit_expr(expr: &mut LocatedExpr, f: &mut TypeVisitor, size_params: &[String]) {
+    let span = &expr.span;
+    match &mut expr.node {
+        Expr::Cast { t, expr, .. } => {
+            f(&mut t.node, &t.span, size_params);
+            visit_expr(expr, f, size_params);
+        }
+        Expr::Sizeof { t } | Expr::Make { t } | Expr::New { t } => f(t, span, size_params),
+        Expr::Binary { left, right, .. } => {
+            visit_expr(left, f, size_params);
+            visit_expr(right, f, size_params);
+        }
+        Expr::Assign { lvalue, value, .. } => {
+            visit_expr(lvalue, f, size_params);
+            visit_expr(value, f, size_params);
+        }
+        Expr::ArrIndex { arr, idx } => {
+            visit_expr(arr, f, size_params);
+            visit_expr(idx, f, size_params);
+        }
+        Expr::Ternary { cond, lexpr, rexpr } => {
+            visit_expr(cond, f, size_params);
+            visit_expr(lexpr, f, size_params);
+            visit_expr(rexpr, f, size_params);
+        }
+        Expr::Parenthesized { expr }
+        | Expr::Unary { expr, .. }
+        | Expr::MemAccess { expr, .. }
+        | Expr::Try { expr, .. } => visit_expr(expr, f, size_params),
+        Expr::FnCall { name, args, .. } => {
+            visit_expr(name, f, size_params);
+            for arg in args {
+                visit_expr(arg, f, size_params);
+            }
+        }
+        Expr::InitArr { elems } | Expr::InitArrDesignated { elems, .. } | Expr::Tuple { elems } => {
+            for elem in elems {
+                visit_expr(elem, f, size_params);
+            }
+        }
+        Expr::InitStruct { args, .. } => {
+            for (_, value) in args {
+                visit_expr(value, f, size_params);
+            }
+        }
+        Expr::Int(_)
+        | Expr::UInt(_)
+        | Expr::Double(_)
+        | Expr::Bool(_)
+        | Expr::Char(_)
+        | Expr::Str(_)
+        | Expr::Ident(_)
+        | Expr::EnumVarAccess { .. }
+        | Expr::ModuleAccess { .. } => {}
+    }
+}
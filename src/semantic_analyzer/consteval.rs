@@ -0,0 +1,724 @@
+//! Evaluates constant expressions at compile time
+//!
+//! Calls to functions that aren't generic are evaluated by interpreting their bodies with the
+//! values of the arguments, as long as they only declare and assign their own variables, branch,
+//! loop, and return, so `const AREA: i32 = square(SIDE);` is a constant. If a function does
+//! anything else, like calling a function C provides or reading a global variable, the call isn't
+//! constant, and notes point at the statement that made it so. A call that runs more than
+//! `MAX_STEPS` statements is given up on, since it may not terminate.
+
+use std::collections::{HashMap, HashSet};
+
+use tamago::{AssignOp, BinOp, UnaryOp};
+
+use crate::diagnostics::note;
+use crate::parser::*;
+use crate::semantic_analyzer::*;
+
+/// The number of statements a call evaluated at compile time may run, including the calls it
+/// makes
+const MAX_STEPS: usize = 100_000;
+
+/// How deeply calls evaluated at compile time may be nested
+const MAX_DEPTH: usize = 128;
+
+/// The value of a constant expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i128),
+    Double(f64),
+    Bool(bool),
+    Char(u8),
+    Str(String),
+    Array(Vec<Value>),
+    /// The initialized fields of a struct, in the order they are given
+    Struct(Vec<(String, Value)>),
+}
+
+/// The parameters and body of a function
+type FuncDef<'ast> = (&'ast [(String, LocatedType)], &'ast [LocatedStmt]);
+
+/// Evaluates the initializers of global constants, which may refer to each other
+#[derive(Debug)]
+pub struct ConstEvaluator<'ast> {
+    /// The initializer of each global constant
+    constants: HashMap<&'ast str, &'ast LocatedExpr>,
+
+    /// The values of the constants evaluated so far, or `None` if they aren't known at compile
+    /// time
+    values: HashMap<String, Option<Value>>,
+
+    /// The constants whose initializers are being evaluated, to catch ones that depend on
+    /// themselves
+    evaluating: HashSet<&'ast str>,
+
+    /// The parameters and body of each function that isn't generic
+    functions: HashMap<&'ast str, FuncDef<'ast>>,
+
+    /// The variables of the function being interpreted, innermost scope last
+    locals: Vec<HashMap<&'ast str, Value>>,
+
+    /// The functions being interpreted, innermost call last
+    calls: Vec<&'ast str>,
+
+    /// The number of statements the outermost call being interpreted may still run
+    steps: usize,
+
+    /// Why the statement being run in a function isn't constant, e.g. "calls 'puts', which isn't
+    /// a constant function"
+    reason: Option<String>,
+
+    /// Notes pointing at the statements that made the calls of the last evaluated expression
+    /// non-constant, innermost first
+    notes: Vec<Message>,
+}
+
+/// Where a statement of a function being interpreted leaves it
+enum Flow {
+    Next,
+    Break,
+    Continue,
+    Return(Option<Value>),
+}
+
+impl<'ast> ConstEvaluator<'ast> {
+    pub fn new(ast: &'ast [LocatedGlobalStmt]) -> Self {
+        let constants = ast
+            .iter()
+            .filter_map(|stmt| match &stmt.node {
+                GlobalStmt::Constant { name, value, .. } => Some((name.as_str(), value)),
+                _ => None,
+            })
+            .collect();
+        let functions = ast
+            .iter()
+            .filter_map(|stmt| match &stmt.node {
+                GlobalStmt::Function {
+                    name,
+                    type_params,
+                    size_params,
+                    params,
+                    body,
+                    ..
+                } if type_params.is_empty() && size_params.is_empty() => {
+                    Some((name.as_str(), (params.as_slice(), body.as_slice())))
+                }
+                _ => None,
+            })
+            .collect();
+
+        Self {
+            constants,
+            values: HashMap::new(),
+            evaluating: HashSet::new(),
+            functions,
+            locals: vec![],
+            calls: vec![],
+            steps: 0,
+            reason: None,
+            notes: vec![],
+        }
+    }
+
+    /// Creates an evaluator that knows the values of constants evaluated before, e.g. by an
+    /// evaluator over an AST that has been modified since
+    pub fn with_values(values: HashMap<String, Value>) -> Self {
+        Self {
+            constants: HashMap::new(),
+            values: values
+                .into_iter()
+                .map(|(name, value)| (name, Some(value)))
+                .collect(),
+            evaluating: HashSet::new(),
+            functions: HashMap::new(),
+            locals: vec![],
+            calls: vec![],
+            steps: 0,
+            reason: None,
+            notes: vec![],
+        }
+    }
+
+    /// Returns the value of `expr`, or `None` if it can't be known at compile time, and fails if
+    /// evaluating it does, e.g. because of an index out of bounds
+    pub fn eval(&mut self, expr: &'ast LocatedExpr) -> Result<Option<Value>, Message> {
+        self.reason = None;
+        self.notes.clear();
+        self.eval_expr(expr)
+    }
+
+    /// Returns the notes explaining why the functions called by the last expression `eval` found
+    /// not to be constant aren't, starting with the outermost call
+    pub fn take_notes(&mut self) -> Vec<Message> {
+        let mut notes = std::mem::take(&mut self.notes);
+        notes.reverse();
+        notes
+    }
+
+    fn eval_expr(&mut self, expr: &'ast LocatedExpr) -> Result<Option<Value>, Message> {
+        use Expr::*;
+
+        let Located { node: e, span } = expr;
+
+        let value = match e {
+            Int(i) => Value::Int((*i).into()),
+            UInt(u) => Value::Int((*u).into()),
+            Double(d) => Value::Double(*d),
+            Bool(b) => Value::Bool(*b),
+            Char(c) => Value::Char(*c),
+            Str(s) => Value::Str(s.clone()),
+            Ident(name) => {
+                if let Some(value) = self.local(name) {
+                    return Ok(Some(value.clone()));
+                }
+                let value = self.eval_constant(span, name)?;
+                if value.is_none() {
+                    self.not_constant(format!("reads '{name}', which isn't a constant"));
+                }
+                return Ok(value);
+            }
+            Parenthesized { expr } => return self.eval_expr(expr),
+            Unary { op, expr } => {
+                let Some(value) = self.eval_expr(expr)? else {
+                    return Ok(None);
+                };
+
+                match (op, value) {
+                    (UnaryOp::Neg, Value::Int(i)) => Value::Int(-i),
+                    (UnaryOp::Neg, Value::Double(d)) => Value::Double(-d),
+                    (UnaryOp::LogicNeg, Value::Bool(b)) => Value::Bool(!b),
+                    (UnaryOp::BitNot, Value::Int(i)) => Value::Int(!i),
+                    _ => return Ok(None),
+                }
+            }
+            Binary { left, op, right } => {
+                let (Some(left), Some(right)) = (self.eval_expr(left)?, self.eval_expr(right)?)
+                else {
+                    return Ok(None);
+                };
+
+                return eval_binary(span, left, op, right);
+            }
+            Ternary { cond, lexpr, rexpr } => {
+                return match self.eval_expr(cond)? {
+                    Some(Value::Bool(true)) => self.eval_expr(lexpr),
+                    Some(Value::Bool(false)) => self.eval_expr(rexpr),
+                    _ => Ok(None),
+                };
+            }
+            ArrIndex { arr, idx } => {
+                let (Some(arr_value), Some(Value::Int(i))) =
+                    (self.eval_expr(arr)?, self.eval_expr(idx)?)
+                else {
+                    return Ok(None);
+                };
+
+                let (elem, len, kind) = match arr_value {
+                    Value::Str(s) => (
+                        usize::try_from(i)
+                            .ok()
+                            .and_then(|i| s.as_bytes().get(i))
+                            .map(|c| Value::Char(*c)),
+                        s.len(),
+                        "string",
+                    ),
+                    Value::Array(elems) => (
+                        usize::try_from(i).ok().and_then(|i| elems.get(i)).cloned(),
+                        elems.len(),
+                        "array",
+                    ),
+                    _ => return Ok(None),
+                };
+
+                match elem {
+                    Some(elem) => elem,
+                    None => {
+                        return Err((
+                            idx.span.clone(),
+                            format!(
+                                "Index {i} is out of bounds for {} of length {len}",
+                                describe_indexed(arr, kind)
+                            ),
+                        ));
+                    }
+                }
+            }
+            MemAccess { expr, member } => match self.eval_expr(expr)? {
+                Some(Value::Struct(fields)) => {
+                    match fields.into_iter().find(|(name, _)| name == member) {
+                        Some((_, value)) => value,
+                        None => return Ok(None),
+                    }
+                }
+                _ => return Ok(None),
+            },
+            InitArr { elems } => {
+                let mut values = vec![];
+                for elem in elems {
+                    let Some(value) = self.eval_expr(elem)? else {
+                        return Ok(None);
+                    };
+                    values.push(value);
+                }
+                Value::Array(values)
+            }
+            InitStruct { args, .. } => {
+                let mut fields = vec![];
+                for (name, arg) in args {
+                    let Some(value) = self.eval_expr(arg)? else {
+                        return Ok(None);
+                    };
+                    fields.push((name.clone(), value));
+                }
+                Value::Struct(fields)
+            }
+            FnCall { name, args, .. }
+                if matches!(&name.node, Ident(func) if func == "len"
+                    && !self.constants.contains_key(func.as_str()))
+                    && args.len() == 1 =>
+            {
+                match self.eval_expr(&args[0])? {
+                    Some(Value::Str(s)) => Value::Int(s.len() as i128),
+                    _ => return Ok(None),
+                }
+            }
+            FnCall { name, args, .. } => {
+                let Ident(func) = &name.node else {
+                    return Ok(None);
+                };
+                if self.local(func).is_some() || !self.functions.contains_key(func.as_str()) {
+                    self.not_constant(format!("calls '{func}', which isn't a constant function"));
+                    return Ok(None);
+                }
+                return self.call(span, func, args);
+            }
+            New { .. } | Make { .. } => {
+                self.not_constant("allocates memory".to_string());
+                return Ok(None);
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(value))
+    }
+
+    /// Interprets the body of the function `func` with the values of `args`, returning what it
+    /// returns, or `None` if it doesn't only compute a value
+    fn call(
+        &mut self,
+        span: &Span,
+        func: &'ast str,
+        args: &'ast [LocatedExpr],
+    ) -> Result<Option<Value>, Message> {
+        let (params, body) = self.functions[func];
+        if params.len() != args.len() {
+            return Ok(None);
+        }
+
+        let mut frame = HashMap::new();
+        for ((param, _), arg) in params.iter().zip(args) {
+            let Some(value) = self.eval_expr(arg)? else {
+                return Ok(None);
+            };
+            frame.insert(param.as_str(), value);
+        }
+
+        if self.calls.len() >= MAX_DEPTH {
+            self.notes.push(note(
+                span.clone(),
+                format!("'{func}' is called more than {MAX_DEPTH} calls deep at compile time"),
+            ));
+            self.not_constant(format!("calls '{func}', which isn't constant"));
+            return Ok(None);
+        }
+        if self.calls.is_empty() {
+            self.steps = MAX_STEPS;
+        }
+
+        let outer = std::mem::replace(&mut self.locals, vec![frame]);
+        self.calls.push(func);
+        let flow = self.exec_block(body);
+        self.calls.pop();
+        self.locals = outer;
+
+        match flow? {
+            Some(Flow::Return(Some(value))) => Ok(Some(value)),
+            Some(_) => {
+                self.not_constant(format!("calls '{func}', which doesn't return a value"));
+                Ok(None)
+            }
+            None => {
+                self.not_constant(format!("calls '{func}', which isn't constant"));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Runs the statements of a new scope, returning `None` if one of them isn't constant
+    fn exec_block(&mut self, body: &'ast [LocatedStmt]) -> Result<Option<Flow>, Message> {
+        self.locals.push(HashMap::new());
+        let mut flow = Ok(Some(Flow::Next));
+        for stmt in body {
+            flow = self.exec(stmt);
+            if !matches!(flow, Ok(Some(Flow::Next))) {
+                break;
+            }
+        }
+        self.locals.pop();
+        flow
+    }
+
+    fn exec(&mut self, stmt: &'ast LocatedStmt) -> Result<Option<Flow>, Message> {
+        let Located { node, span } = stmt;
+        if !self.tick(span) {
+            return Ok(None);
+        }
+
+        let flow = match node {
+            Stmt::Variable {
+                name,
+                value: Some(value),
+                ..
+            } => {
+                let Some(value) = self.eval_expr(value)? else {
+                    return Ok(self.stmt_not_constant(span));
+                };
+                self.locals.last_mut().unwrap().insert(name, value);
+                Flow::Next
+            }
+            Stmt::Variable { name, .. } => {
+                self.not_constant(format!("declares '{name}' without a value"));
+                return Ok(self.stmt_not_constant(span));
+            }
+            Stmt::Expression {
+                expr:
+                    Located {
+                        node: Expr::Assign { lvalue, op, value },
+                        ..
+                    },
+            } => {
+                let Some(current) = (match &lvalue.node {
+                    Expr::Ident(name) => self.local(name).cloned(),
+                    _ => None,
+                }) else {
+                    self.not_constant(match &lvalue.node {
+                        Expr::Ident(name) => format!("assigns to '{name}', which isn't local"),
+                        _ => "assigns to something other than a local variable".to_string(),
+                    });
+                    return Ok(self.stmt_not_constant(span));
+                };
+                let Some(value) = self.eval_expr(value)? else {
+                    return Ok(self.stmt_not_constant(span));
+                };
+                let value = match assign_binop(op) {
+                    Some(op) => eval_binary(span, current, &op, value)?,
+                    None => Some(value),
+                };
+                let (Some(value), Expr::Ident(name)) = (value, &lvalue.node) else {
+                    return Ok(self.stmt_not_constant(span));
+                };
+
+                let mut scopes = self.locals.iter_mut().rev();
+                if let Some(var) = scopes.find_map(|scope| scope.get_mut(name.as_str())) {
+                    *var = value;
+                }
+                Flow::Next
+            }
+            Stmt::Expression { expr } => {
+                if self.eval_expr(expr)?.is_none() {
+                    return Ok(self.stmt_not_constant(span));
+                }
+                Flow::Next
+            }
+            Stmt::Return { value: None } => Flow::Return(None),
+            Stmt::Return { value: Some(value) } => match self.eval_expr(value)? {
+                Some(value) => Flow::Return(Some(value)),
+                None => return Ok(self.stmt_not_constant(span)),
+            },
+            Stmt::Break => Flow::Break,
+            Stmt::Continue => Flow::Continue,
+            Stmt::If { cond, then, other } => match self.eval_expr(cond)? {
+                Some(Value::Bool(true)) => return self.exec_block(then),
+                Some(Value::Bool(false)) => match other {
+                    Some(other) => return self.exec_block(other),
+                    None => Flow::Next,
+                },
+                _ => return Ok(self.stmt_not_constant(&cond.span)),
+            },
+            Stmt::While {
+                cond,
+                body,
+                do_while,
+            } => {
+                let mut first = true;
+                loop {
+                    if !(*do_while && first) {
+                        match self.eval_expr(cond)? {
+                            Some(Value::Bool(true)) => {}
+                            Some(Value::Bool(false)) => break,
+                            _ => return Ok(self.stmt_not_constant(&cond.span)),
+                        }
+                    }
+                    // An empty body still counts towards giving up on a loop that doesn't end
+                    if !std::mem::take(&mut first) && !self.tick(span) {
+                        return Ok(None);
+                    }
+                    match self.exec_block(body)? {
+                        Some(Flow::Next | Flow::Continue) => {}
+                        Some(Flow::Break) => break,
+                        flow => return Ok(flow),
+                    }
+                }
+                Flow::Next
+            }
+            Stmt::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                let Some(value) = self.eval_expr(value)? else {
+                    return Ok(self.stmt_not_constant(span));
+                };
+                let mut body = default.as_deref();
+                'cases: for case in cases {
+                    for case_value in &case.values {
+                        match self.eval_expr(case_value)? {
+                            Some(case_value) if case_value == value => {
+                                body = Some(&case.body);
+                                break 'cases;
+                            }
+                            Some(_) => {}
+                            None => return Ok(self.stmt_not_constant(&case_value.span)),
+                        }
+                    }
+                }
+                // `break` leaves the switch rather than the enclosing loop
+                match body.map(|body| self.exec_block(body)).transpose()? {
+                    Some(Some(Flow::Break)) | None => Flow::Next,
+                    Some(flow) => return Ok(flow),
+                }
+            }
+            Stmt::Block { body } => return self.exec_block(body),
+            Stmt::Free { .. } | Stmt::Destroy { .. } => {
+                self.not_constant("frees memory".to_string());
+                return Ok(self.stmt_not_constant(span));
+            }
+            Stmt::Destructure { .. } | Stmt::Yield { .. } | Stmt::Defer { .. } => {
+                return Ok(self.stmt_not_constant(span));
+            }
+        };
+
+        Ok(Some(flow))
+    }
+
+    /// Counts a statement run by the call being interpreted, returning `false` with a note if it
+    /// has run too many to be expected to terminate
+    fn tick(&mut self, span: &Span) -> bool {
+        if self.steps == 0 {
+            let func = self.calls.last().unwrap();
+            self.notes.push(note(
+                span.clone(),
+                format!(
+                    "'{func}' ran more than {MAX_STEPS} statements at compile time, so it may \
+                     not terminate"
+                ),
+            ));
+            return false;
+        }
+        self.steps -= 1;
+        true
+    }
+
+    /// Records why the expression being evaluated isn't constant, unless something it contains
+    /// already has
+    fn not_constant(&mut self, reason: String) {
+        if self.reason.is_none() {
+            self.reason = Some(reason);
+        }
+    }
+
+    /// Records a note pointing at the statement of the function being interpreted that isn't
+    /// constant, and returns that it isn't
+    fn stmt_not_constant(&mut self, span: &Span) -> Option<Flow> {
+        let func = self.calls.last().unwrap();
+        let msg = match self.reason.take() {
+            Some(reason) => format!("'{func}' isn't constant because this statement {reason}"),
+            None => format!(
+                "'{func}' isn't constant because this statement can't be evaluated at compile time"
+            ),
+        };
+        // A recursive function would be pointed at once for each call
+        let note = note(span.clone(), msg);
+        if !self.notes.contains(&note) {
+            self.notes.push(note);
+        }
+        None
+    }
+
+    /// Returns the value of the variable `name` of the function being interpreted
+    fn local(&self, name: &str) -> Option<&Value> {
+        self.locals.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn eval_constant(&mut self, span: &Span, name: &'ast str) -> Result<Option<Value>, Message> {
+        if let Some(value) = self.values.get(name) {
+            return Ok(value.clone());
+        }
+
+        let Some(&init) = self.constants.get(name) else {
+            return Ok(None);
+        };
+
+        if !self.evaluating.insert(name) {
+            return Err((
+                span.clone(),
+                format!("The constant '{name}' depends on its own value"),
+            ));
+        }
+        let value = self.eval_expr(init);
+        self.evaluating.remove(name);
+
+        let value = value?;
+        self.values.insert(name.to_string(), value.clone());
+        Ok(value)
+    }
+}
+
+/// Whether C can evaluate `expr` at compile time even if the constant evaluator can't, like
+/// `sizeof(T)`, which depends on the target
+pub fn is_c_constant(expr: &LocatedExpr) -> bool {
+    use Expr::*;
+
+    match &expr.node {
+        Int(_)
+        | UInt(_)
+        | Double(_)
+        | Bool(_)
+        | Char(_)
+        | Str(_)
+        | Ident(_)
+        | Sizeof { .. }
+        | EnumVarAccess { .. } => true,
+        Parenthesized { expr } | Cast { expr, .. } => is_c_constant(expr),
+        Unary { op, expr } => {
+            matches!(op, UnaryOp::Neg | UnaryOp::LogicNeg | UnaryOp::BitNot) && is_c_constant(expr)
+        }
+        Binary { left, right, .. } => is_c_constant(left) && is_c_constant(right),
+        Ternary { cond, lexpr, rexpr } => {
+            is_c_constant(cond) && is_c_constant(lexpr) && is_c_constant(rexpr)
+        }
+        _ => false,
+    }
+}
+
+fn eval_binary(
+    span: &Span,
+    left: Value,
+    op: &BinOp,
+    right: Value,
+) -> Result<Option<Value>, Message> {
+    use BinOp::*;
+
+    let value = match (left, right) {
+        (Value::Int(l), Value::Int(r)) => {
+            let value = match op {
+                Add => l.checked_add(r),
+                Sub => l.checked_sub(r),
+                Mul => l.checked_mul(r),
+                Div | Mod if r == 0 => {
+                    return Err((
+                        span.clone(),
+                        "Division by zero in a constant expression".to_string(),
+                    ));
+                }
+                Div => l.checked_div(r),
+                Mod => l.checked_rem(r),
+                BitAnd => Some(l & r),
+                BitOr => Some(l | r),
+                XOr => Some(l ^ r),
+                LShift | RShift if !(0..64).contains(&r) => {
+                    return Err((
+                        span.clone(),
+                        format!("Cannot shift by {r} in a constant expression"),
+                    ));
+                }
+                LShift => l.checked_shl(r as u32),
+                RShift => Some(l >> r),
+                Eq => return Ok(Some(Value::Bool(l == r))),
+                NEq => return Ok(Some(Value::Bool(l != r))),
+                LT => return Ok(Some(Value::Bool(l < r))),
+                GT => return Ok(Some(Value::Bool(l > r))),
+                LTE => return Ok(Some(Value::Bool(l <= r))),
+                GTE => return Ok(Some(Value::Bool(l >= r))),
+                _ => return Ok(None),
+            };
+
+            match value {
+                Some(value) => Value::Int(value),
+                None => {
+                    return Err((
+                        span.clone(),
+                        "Overflow in a constant expression".to_string(),
+                    ));
+                }
+            }
+        }
+        (Value::Double(l), Value::Double(r)) => match op {
+            Add => Value::Double(l + r),
+            Sub => Value::Double(l - r),
+            Mul => Value::Double(l * r),
+            Div => Value::Double(l / r),
+            Eq => Value::Bool(l == r),
+            NEq => Value::Bool(l != r),
+            LT => Value::Bool(l < r),
+            GT => Value::Bool(l > r),
+            LTE => Value::Bool(l <= r),
+            GTE => Value::Bool(l >= r),
+            _ => return Ok(None),
+        },
+        (Value::Bool(l), Value::Bool(r)) => match op {
+            And => Value::Bool(l && r),
+            Or => Value::Bool(l || r),
+            Eq => Value::Bool(l == r),
+            NEq => Value::Bool(l != r),
+            _ => return Ok(None),
+        },
+        (Value::Char(l), Value::Char(r)) => match op {
+            Eq => Value::Bool(l == r),
+            NEq => Value::Bool(l != r),
+            LT => Value::Bool(l < r),
+            GT => Value::Bool(l > r),
+            LTE => Value::Bool(l <= r),
+            GTE => Value::Bool(l >= r),
+            _ => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+
+    Ok(Some(value))
+}
+
+/// The operator a compound assignment applies, or `None` for `=`
+fn assign_binop(op: &AssignOp) -> Option<BinOp> {
+    Some(match op {
+        AssignOp::Assign => return None,
+        AssignOp::AddAssign => BinOp::Add,
+        AssignOp::SubAssign => BinOp::Sub,
+        AssignOp::MulAssign => BinOp::Mul,
+        AssignOp::DivAssign => BinOp::Div,
+        AssignOp::ModAssign => BinOp::Mod,
+        AssignOp::BitAndAssign => BinOp::BitAnd,
+        AssignOp::BitOrAssign => BinOp::BitOr,
+        AssignOp::BitXOrAssign => BinOp::XOr,
+        AssignOp::LShiftAssign => BinOp::LShift,
+        AssignOp::RShiftAssign => BinOp::RShift,
+    })
+}
+
+/// Describes the string or array (`kind`) being indexed into for error messages
+fn describe_indexed(arr: &LocatedExpr, kind: &str) -> String {
+    match &arr.node {
+        Expr::Ident(name) => format!("the constant {kind} '{name}'"),
+        _ => format!("a {kind}"),
+    }
+}
@@ -0,0 +1,308 @@
+//! Lints for code that is valid but most likely a mistake
+
+use tamago::{BinOp, UnaryOp};
+
+use crate::parser::{Expr, LocatedExpr, Type};
+
+/// Returns the value of an integer literal, looking through parentheses and negation
+pub fn const_int(expr: &LocatedExpr) -> Option<i128> {
+    match &expr.node {
+        Expr::Int(i) => Some((*i).into()),
+        Expr::UInt(u) => Some((*u).into()),
+        Expr::Parenthesized { expr } => const_int(expr),
+        Expr::Unary {
+            op: UnaryOp::Neg,
+            expr,
+        } => const_int(expr).map(|i| -i),
+        _ => None,
+    }
+}
+
+/// Checks a comparison for results that are always true or false because of the range of the
+/// operand types, and for comparisons mixing signed and unsigned integers
+pub fn check_comparison(
+    left: (&Type, &LocatedExpr),
+    op: &BinOp,
+    right: (&Type, &LocatedExpr),
+) -> Option<String> {
+    let (left_t, left) = left;
+    let (right_t, right) = right;
+
+    match (const_int(left), const_int(right)) {
+        (None, Some(c)) => always(left_t, op, c),
+        (Some(c), None) => always(right_t, &flip(op)?, c),
+        (None, None) if left_t.is_integer() && right_t.is_integer() => {
+            let (signed, unsigned) = match (left_t.is_unsigned(), right_t.is_unsigned()) {
+                (false, true) => (left_t, right_t),
+                (true, false) => (right_t, left_t),
+                _ => return None,
+            };
+
+            Some(format!(
+                "Comparison between signed '{signed}' and unsigned '{unsigned}', negative values \
+                 will compare as large unsigned ones; cast one side explicitly"
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Checks for `==` and `!=` on floating-point values, whose rounding errors make exact equality
+/// unreliable, e.g. `0.1 + 0.2 == 0.3` is false
+pub fn check_float_equality(left_t: &Type, op: &BinOp, right_t: &Type) -> Option<String> {
+    let is_float = |t: &Type| matches!(t, Type::Double | Type::Float);
+
+    if !matches!(op, BinOp::Eq | BinOp::NEq) || !(is_float(left_t) || is_float(right_t)) {
+        return None;
+    }
+
+    Some(format!(
+        "Comparing floating-point values with '{op}' is exact, so rounding errors can make it \
+         fail; use 'approx_eq(a, b, eps)' to allow for a difference of up to 'eps'"
+    ))
+}
+
+/// Checks `%` on signed integers for a negative constant divisor, which doesn't affect the
+/// result, since the remainder takes the sign of the left operand like in C
+pub fn check_negative_divisor(t: &Type, right: &LocatedExpr) -> Option<String> {
+    let c = const_int(right)?;
+    if !is_signed(t) || c >= 0 {
+        return None;
+    }
+
+    Some(format!(
+        "The result of '%' takes the sign of the left operand, so '% {c}' is the same as '% {}'",
+        -c
+    ))
+}
+
+/// Checks `==` and `!=` between the remainder of a signed integer and a positive constant, which
+/// misses negative values, e.g. `x % 2 == 1` is false for every negative `x` since `-3 % 2` is
+/// `-1`
+pub fn check_remainder_comparison(
+    left: (&Type, &LocatedExpr),
+    op: &BinOp,
+    right: (&Type, &LocatedExpr),
+) -> Option<String> {
+    if !matches!(op, BinOp::Eq | BinOp::NEq) {
+        return None;
+    }
+
+    let ((t, _), c) = [(left, right), (right, left)]
+        .into_iter()
+        .find(|((t, expr), _)| is_signed(t) && is_remainder(expr))
+        .and_then(|(remainder, (_, other))| Some((remainder, const_int(other)?)))?;
+    if c <= 0 {
+        return None;
+    }
+
+    Some(format!(
+        "The remainder of a negative '{t}' is negative, so it is never {c} for negative values; \
+         compare it with 0 or use an unsigned type"
+    ))
+}
+
+fn is_signed(t: &Type) -> bool {
+    t.is_integer() && !t.is_unsigned()
+}
+
+fn is_remainder(expr: &LocatedExpr) -> bool {
+    match &expr.node {
+        Expr::Binary { op: BinOp::Mod, .. } => true,
+        Expr::Parenthesized { expr } => is_remainder(expr),
+        _ => false,
+    }
+}
+
+/// Checks whether `x op c` has the same result for every `x` of type `t`
+fn always(t: &Type, op: &BinOp, c: i128) -> Option<String> {
+    let (min, max) = t.int_range()?;
+
+    let res = match op {
+        BinOp::LT if c <= min => false,
+        BinOp::LT if c > max => true,
+        BinOp::LTE if c < min => false,
+        BinOp::LTE if c >= max => true,
+        BinOp::GT if c >= max => false,
+        BinOp::GT if c < min => true,
+        BinOp::GTE if c > max => false,
+        BinOp::GTE if c <= min => true,
+        BinOp::Eq if c < min || c > max => false,
+        BinOp::NEq if c < min || c > max => true,
+        _ => return None,
+    };
+
+    if t.is_unsigned() && c <= 0 {
+        Some(format!(
+            "Comparison is always {res} because '{t}' is unsigned and can never be negative"
+        ))
+    } else {
+        Some(format!(
+            "Comparison is always {res} because '{t}' only holds values from {min} to {max}"
+        ))
+    }
+}
+
+/// Returns the operator that gives the same result with the operands swapped
+fn flip(op: &BinOp) -> Option<BinOp> {
+    Some(match op {
+        BinOp::LT => BinOp::GT,
+        BinOp::LTE => BinOp::GTE,
+        BinOp::GT => BinOp::LT,
+        BinOp::GTE => BinOp::LTE,
+        BinOp::Eq => BinOp::Eq,
+        BinOp::NEq => BinOp::NEq,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Located;
+
+    fn expr(node: Expr) -> LocatedExpr {
+        Located { node, span: 0..1 }
+    }
+
+    #[test]
+    fn suspicious_comparisons() {
+        let x = expr(Expr::Ident("x".to_string()));
+        let zero = expr(Expr::Int(0));
+        let big = expr(Expr::Int(256));
+
+        // x >= 0 where x: u8
+        assert_eq!(
+            check_comparison((&Type::UInt8, &x), &BinOp::GTE, (&Type::Int32, &zero)),
+            Some(
+                "Comparison is always true because 'u8' is unsigned and can never be negative"
+                    .to_string()
+            )
+        );
+
+        // 0 > x where x: u32
+        assert_eq!(
+            check_comparison((&Type::Int32, &zero), &BinOp::GT, (&Type::UInt32, &x)),
+            Some(
+                "Comparison is always false because 'u32' is unsigned and can never be negative"
+                    .to_string()
+            )
+        );
+
+        // x == 256 where x: u8
+        assert_eq!(
+            check_comparison((&Type::UInt8, &x), &BinOp::Eq, (&Type::Int32, &big)),
+            Some(
+                "Comparison is always false because 'u8' only holds values from 0 to 255"
+                    .to_string()
+            )
+        );
+
+        // x < 256 where x: i16 is fine
+        assert_eq!(
+            check_comparison((&Type::Int16, &x), &BinOp::LT, (&Type::Int32, &big)),
+            None
+        );
+
+        let y = expr(Expr::Ident("y".to_string()));
+        assert!(
+            check_comparison((&Type::Int32, &x), &BinOp::LT, (&Type::UInt64, &y))
+                .unwrap()
+                .starts_with("Comparison between signed 'i32' and unsigned 'u64'")
+        );
+        assert_eq!(
+            check_comparison((&Type::UInt8, &x), &BinOp::LT, (&Type::UInt64, &y)),
+            None
+        );
+    }
+
+    #[test]
+    fn float_equality() {
+        assert!(
+            check_float_equality(&Type::Double, &BinOp::Eq, &Type::Int32)
+                .unwrap()
+                .starts_with("Comparing floating-point values with '==' is exact")
+        );
+        assert!(check_float_equality(&Type::Int32, &BinOp::NEq, &Type::Float).is_some());
+        assert_eq!(
+            check_float_equality(&Type::Double, &BinOp::LT, &Type::Double),
+            None
+        );
+        assert_eq!(
+            check_float_equality(&Type::Int64, &BinOp::Eq, &Type::Int64),
+            None
+        );
+    }
+    #[test]
+    fn signed_remainders() {
+        let x = expr(Expr::Ident("x".to_string()));
+        let two = expr(Expr::Int(2));
+        let minus_three = expr(Expr::Unary {
+            op: UnaryOp::Neg,
+            expr: Box::new(expr(Expr::Int(3))),
+        });
+
+        assert_eq!(
+            check_negative_divisor(&Type::Int32, &minus_three),
+            Some(
+                "The result of '%' takes the sign of the left operand, so '% -3' is the same as \
+                 '% 3'"
+                    .to_string()
+            )
+        );
+        assert_eq!(check_negative_divisor(&Type::Int32, &two), None);
+        assert_eq!(check_negative_divisor(&Type::Int32, &x), None);
+
+        // x % 2 == 1 where x: i32
+        let remainder = expr(Expr::Binary {
+            left: Box::new(x),
+            op: BinOp::Mod,
+            right: Box::new(two),
+        });
+        let one = expr(Expr::Int(1));
+        assert!(
+            check_remainder_comparison(
+                (&Type::Int32, &remainder),
+                &BinOp::Eq,
+                (&Type::Int32, &one)
+            )
+            .unwrap()
+            .starts_with("The remainder of a negative 'i32' is negative")
+        );
+        assert!(
+            check_remainder_comparison(
+                (&Type::Int32, &one),
+                &BinOp::NEq,
+                (&Type::Int32, &remainder)
+            )
+            .is_some()
+        );
+
+        // Comparing with 0 works for negative values, and unsigned remainders are never negative
+        let zero = expr(Expr::Int(0));
+        assert_eq!(
+            check_remainder_comparison(
+                (&Type::Int32, &remainder),
+                &BinOp::Eq,
+                (&Type::Int32, &zero)
+            ),
+            None
+        );
+        assert_eq!(
+            check_remainder_comparison(
+                (&Type::UInt32, &remainder),
+                &BinOp::Eq,
+                (&Type::UInt32, &one)
+            ),
+            None
+        );
+        assert_eq!(
+            check_remainder_comparison(
+                (&Type::Int32, &remainder),
+                &BinOp::LT,
+                (&Type::Int32, &one)
+            ),
+            None
+        );
+    }
+}
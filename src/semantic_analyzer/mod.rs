@@ -1,11 +1,53 @@
+pub mod const_exprs;
+pub mod consteval;
+pub mod lints;
+pub mod naming;
 pub mod resolver;
 pub mod type_checker;
+pub mod unused;
 
+use crate::diagnostics::note;
 use crate::parser::Span;
 
 /// Could be either a warning or an error
 type Message = (Span, String);
 
+/// Functions provided by the compiler that don't need to be declared
+pub const BUILTIN_FUNCS: &[&str] = &[
+    "append",
+    "approx_eq",
+    "err",
+    "int_to_str",
+    "len",
+    "ok",
+    "parse_i32",
+];
+
+/// Returns the error for `name` being declared again at `span`, followed by a note pointing at
+/// the `original` declaration and, if given, one showing how the two declarations differ
+pub fn redeclaration(
+    name: &str,
+    span: Span,
+    original: Span,
+    signatures: Option<(String, String)>,
+) -> Vec<Message> {
+    let mut res = vec![
+        (span, format!("'{name}' is already declared")),
+        note(original.clone(), format!("'{name}' was first declared here")),
+    ];
+
+    if let Some((previous, current)) = signatures
+        && previous != current
+    {
+        res.push(note(
+            original,
+            format!("The previous declaration is '{previous}', but this one is '{current}'"),
+        ));
+    }
+
+    res
+}
+
 /// Implements the Damerau-Levenshtein distance
 pub fn edit_distance(x: &str, y: &str) -> usize {
     let x_chars = x.chars().collect::<Vec<char>>();
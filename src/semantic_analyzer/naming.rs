@@ -0,0 +1,303 @@
+//! Lints for names that don't follow the naming conventions, which keeps the generated C
+//! consistent and catches typos like `Lenght` for a variable early
+//!
+//! Types are CamelCase, functions and variables snake_case, and global constants
+//! SCREAMING_SNAKE_CASE. Extern functions are left alone since their names come from C.
+
+use crate::parser::*;
+
+use super::Message;
+
+/// The kinds of names that have a convention
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NamingRule {
+    /// Enums, structs, unions, and aliases are CamelCase
+    Types,
+
+    /// Functions are snake_case
+    Functions,
+
+    /// Global and local variables, and parameters, are snake_case
+    Variables,
+
+    /// Global constants are SCREAMING_SNAKE_CASE
+    Constants,
+}
+
+impl NamingRule {
+    pub const ALL: [NamingRule; 4] = [
+        NamingRule::Types,
+        NamingRule::Functions,
+        NamingRule::Variables,
+        NamingRule::Constants,
+    ];
+
+    /// The name of the rule on the command line (`--no-naming types`)
+    pub fn name(self) -> &'static str {
+        match self {
+            NamingRule::Types => "types",
+            NamingRule::Functions => "functions",
+            NamingRule::Variables => "variables",
+            NamingRule::Constants => "constants",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|rule| rule.name() == name)
+    }
+
+    fn case(self) -> Case {
+        match self {
+            NamingRule::Types => Case::Camel,
+            NamingRule::Functions | NamingRule::Variables => Case::Snake,
+            NamingRule::Constants => Case::ScreamingSnake,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Case {
+    Camel,
+    Snake,
+    ScreamingSnake,
+}
+
+impl Case {
+    fn name(self) -> &'static str {
+        match self {
+            Case::Camel => "CamelCase",
+            Case::Snake => "snake_case",
+            Case::ScreamingSnake => "SCREAMING_SNAKE_CASE",
+        }
+    }
+
+    fn matches(self, name: &str) -> bool {
+        let name = name.trim_start_matches('_');
+        match self {
+            Case::Camel => {
+                name.starts_with(|c: char| c.is_ascii_uppercase()) && !name.contains('_')
+            }
+            Case::Snake => !name.contains(|c: char| c.is_ascii_uppercase()),
+            Case::ScreamingSnake => !name.contains(|c: char| c.is_ascii_lowercase()),
+        }
+    }
+
+    /// Converts `name` to the case, keeping leading underscores
+    fn convert(self, name: &str) -> String {
+        let trimmed = name.trim_start_matches('_');
+        let underscores = &name[..name.len() - trimmed.len()];
+
+        let words = words(trimmed);
+        let converted = match self {
+            Case::Camel => words
+                .iter()
+                .map(|word| {
+                    let mut chars = word.chars();
+                    chars.next().map_or_else(String::new, |first| {
+                        first.to_ascii_uppercase().to_string() + &chars.as_str().to_lowercase()
+                    })
+                })
+                .collect::<String>(),
+            Case::Snake => words.join("_").to_lowercase(),
+            Case::ScreamingSnake => words.join("_").to_uppercase(),
+        };
+
+        format!("{underscores}{converted}")
+    }
+}
+
+/// Splits a name into words at underscores and at the start of each capitalized word, e.g.
+/// `parseHTTPRequest` into `parse`, `HTTP`, and `Request`
+fn words(name: &str) -> Vec<&str> {
+    let mut words = vec![];
+
+    for part in name.split('_').filter(|part| !part.is_empty()) {
+        let chars: Vec<(usize, char)> = part.char_indices().collect();
+        let mut start = 0;
+
+        for i in 1..chars.len() {
+            let (idx, c) = chars[i];
+            let prev = chars[i - 1].1;
+            let next_is_lower = chars
+                .get(i + 1)
+                .is_some_and(|(_, c)| c.is_ascii_lowercase());
+
+            let starts_word = c.is_ascii_uppercase()
+                && (prev.is_ascii_lowercase()
+                    || prev.is_ascii_digit()
+                    || (prev.is_ascii_uppercase() && next_is_lower));
+            if starts_word {
+                words.push(&part[start..idx]);
+                start = idx;
+            }
+        }
+
+        words.push(&part[start..]);
+    }
+
+    words
+}
+
+/// Which naming conventions are checked (`--no-naming`), and the prefixes names may start with
+/// regardless of their case (`--naming-prefix`), e.g. `SDL_` for bindings to a C library
+#[derive(Debug, Clone, Default)]
+pub struct NamingConventions {
+    disabled: Vec<NamingRule>,
+    allowed_prefixes: Vec<String>,
+}
+
+impl NamingConventions {
+    /// Turns `rule` on or off
+    pub fn rule(mut self, rule: NamingRule, enabled: bool) -> Self {
+        self.disabled.retain(|r| *r != rule);
+        if !enabled {
+            self.disabled.push(rule);
+        }
+        self
+    }
+
+    /// Lets names start with `prefix`, which isn't checked
+    pub fn allow_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.allowed_prefixes.push(prefix.into());
+        self
+    }
+
+    pub fn is_enabled(&self, rule: NamingRule) -> bool {
+        !self.disabled.contains(&rule)
+    }
+
+    /// Returns a warning for every declaration in `ast` whose name breaks an enabled convention
+    pub fn check(&self, ast: &[LocatedGlobalStmt]) -> Vec<Message> {
+        let mut warnings = vec![];
+
+        for stmt in ast {
+            let span = &stmt.span;
+            match &stmt.node {
+                GlobalStmt::Enum { name, .. } => {
+                    self.check_name(span, "enum", name, NamingRule::Types, &mut warnings)
+                }
+                GlobalStmt::Struct { name, .. } => {
+                    self.check_name(span, "struct", name, NamingRule::Types, &mut warnings)
+                }
+                GlobalStmt::Union { name, .. } => {
+                    self.check_name(span, "union", name, NamingRule::Types, &mut warnings)
+                }
+                GlobalStmt::Alias { name, .. } => {
+                    self.check_name(span, "alias", name, NamingRule::Types, &mut warnings)
+                }
+                GlobalStmt::Function {
+                    name, params, body, ..
+                } => {
+                    self.check_name(span, "function", name, NamingRule::Functions, &mut warnings);
+                    for (param, _) in params {
+                        self.check_name(
+                            span,
+                            "parameter",
+                            param,
+                            NamingRule::Variables,
+                            &mut warnings,
+                        );
+                    }
+                    self.check_body(body, &mut warnings);
+                }
+                GlobalStmt::Variable { name, .. } => {
+                    self.check_name(span, "variable", name, NamingRule::Variables, &mut warnings)
+                }
+                GlobalStmt::Constant { name, .. } => {
+                    self.check_name(span, "constant", name, NamingRule::Constants, &mut warnings)
+                }
+                GlobalStmt::ExternFunction { .. } | GlobalStmt::Import { .. } => {}
+            }
+        }
+
+        warnings
+    }
+
+    fn check_body(&self, body: &[LocatedStmt], warnings: &mut Vec<Message>) {
+        for stmt in body {
+            let span = &stmt.span;
+            match &stmt.node {
+                Stmt::Variable { name, .. } => {
+                    self.check_name(span, "variable", name, NamingRule::Variables, warnings)
+                }
+                Stmt::Destructure { pattern, .. } => {
+                    let (Pattern::Struct(names) | Pattern::Array(names) | Pattern::Tuple(names)) =
+                        pattern;
+                    for name in names {
+                        self.check_name(span, "variable", name, NamingRule::Variables, warnings);
+                    }
+                }
+                Stmt::If { then, other, .. } => {
+                    self.check_body(then, warnings);
+                    if let Some(other) = other {
+                        self.check_body(other, warnings);
+                    }
+                }
+                Stmt::While { body, .. } | Stmt::Defer { body } | Stmt::Block { body } => {
+                    self.check_body(body, warnings)
+                }
+                Stmt::Switch { cases, default, .. } => {
+                    for case in cases {
+                        self.check_body(&case.body, warnings);
+                    }
+                    if let Some(default) = default {
+                        self.check_body(default, warnings);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_name(
+        &self,
+        span: &Span,
+        kind: &str,
+        name: &str,
+        rule: NamingRule,
+        warnings: &mut Vec<Message>,
+    ) {
+        let case = rule.case();
+        if !self.is_enabled(rule)
+            || case.matches(name)
+            || self
+                .allowed_prefixes
+                .iter()
+                .any(|prefix| name.starts_with(prefix.as_str()))
+        {
+            return;
+        }
+
+        warnings.push((
+            span.clone(),
+            format!(
+                "The {kind} '{name}' should be named in {}, like '{}'",
+                case.name(),
+                case.convert(name)
+            ),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cases() {
+        assert!(Case::Camel.matches("HttpRequest"));
+        assert!(Case::Camel.matches("Vec3"));
+        assert!(!Case::Camel.matches("http_request"));
+        assert!(Case::Snake.matches("_unused"));
+        assert!(!Case::Snake.matches("parseInput"));
+        assert!(Case::ScreamingSnake.matches("MAX_LEN2"));
+
+        assert_eq!(Case::Camel.convert("http_request"), "HttpRequest");
+        assert_eq!(
+            Case::Snake.convert("parseHTTPRequest"),
+            "parse_http_request"
+        );
+        assert_eq!(Case::Snake.convert("_unusedValue"), "_unused_value");
+        assert_eq!(Case::ScreamingSnake.convert("maxLen"), "MAX_LEN");
+    }
+}
@@ -1,12 +1,17 @@
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 
+use crate::diagnostics::{help, note};
+use crate::module::Module;
 use crate::parser::*;
 use crate::semantic_analyzer::*;
 
+/// Where the name each use refers to is declared, keyed by the span of the use
+pub type Declarations = HashMap<Span, Span>;
+
 #[derive(Debug, Default)]
 struct Scope<'ast> {
-    names: HashMap<&'ast str, (Span, bool)>,
+    names: HashMap<&'ast str, Span>,
     enclosing: Option<Box<Scope<'ast>>>,
 }
 
@@ -14,6 +19,23 @@ struct Scope<'ast> {
 pub struct Resolver<'ast> {
     ast: &'ast Vec<LocatedGlobalStmt>,
     scope: Scope<'ast>,
+    modules: Option<&'ast HashMap<String, Module>>,
+
+    /// The first declaration of each global, to compare redeclarations with
+    globals: HashMap<&'ast str, &'ast GlobalStmt>,
+
+    /// Every local variable of the current function in the order they are declared, to point
+    /// uses that aren't in scope at their declarations
+    locals: Vec<(&'ast str, &'ast Span)>,
+
+    /// The variables of the declaration being resolved, which its value can't use yet
+    initializing: Vec<(&'ast str, &'ast Span)>,
+
+    in_statemachine: bool,
+
+    /// Where the name each use refers to is declared, keyed by the span of the use, if they are
+    /// recorded (`resolve_with_declarations`)
+    declarations: Option<Declarations>,
 
     errors: Vec<Message>,
     warnings: Vec<Message>,
@@ -24,22 +46,54 @@ impl<'ast> Resolver<'ast> {
         Self {
             ast,
             scope: Scope::new(),
+            modules: None,
+            globals: HashMap::new(),
+            locals: vec![],
+            initializing: vec![],
+            in_statemachine: false,
+            declarations: None,
             errors: vec![],
             warnings: vec![],
         }
     }
 
+    /// Sets the modules loaded for the imports, so that accesses to their members can be checked
+    pub fn modules(mut self, modules: &'ast HashMap<String, Module>) -> Self {
+        self.modules = Some(modules);
+        self
+    }
+
     pub fn resolve(mut self) -> (Vec<Message>, Result<(), Vec<Message>>) {
+        self.resolve_all();
+        self.finish()
+    }
+
+    /// Resolves the program like `resolve`, and also returns where the name each use refers to
+    /// is declared, keyed by the span of the use, which is recorded even if there are errors
+    pub fn resolve_with_declarations(
+        mut self,
+    ) -> (Vec<Message>, Result<(), Vec<Message>>, Declarations) {
+        self.declarations = Some(HashMap::new());
+        self.resolve_all();
+
+        let declarations = self.declarations.take().unwrap_or_default();
+        let (warnings, res) = self.finish();
+        (warnings, res, declarations)
+    }
+
+    fn resolve_all(&mut self) {
+        // Every global is declared before any body or initializer is resolved, so that they can
+        // refer to globals declared later in the file
         for stmt in self.ast {
-            self.resolve_global_stmt(stmt);
+            self.declare_global_stmt(stmt);
         }
 
-        for (name, (span, used)) in self.scope.names {
-            if !used && name != "main" {
-                self.warnings.push((span, format!("'{name}' is not used")));
-            }
+        for stmt in self.ast {
+            self.resolve_global_stmt(stmt);
         }
+    }
 
+    fn finish(self) -> (Vec<Message>, Result<(), Vec<Message>>) {
         if self.errors.is_empty() {
             (self.warnings, Ok(()))
         } else {
@@ -47,7 +101,16 @@ impl<'ast> Resolver<'ast> {
         }
     }
 
-    fn resolve_global_stmt(&mut self, stmt: &'ast LocatedGlobalStmt) {
+    /// Records that the use of `name` at `span` refers to its declaration in scope, if any
+    fn record_use(&mut self, name: &str, span: &Span) {
+        if let Some(declarations) = &mut self.declarations
+            && let Some(declaration) = self.scope.find(name)
+        {
+            declarations.insert(span.clone(), declaration.clone());
+        }
+    }
+
+    fn declare_global_stmt(&mut self, stmt: &'ast LocatedGlobalStmt) {
         use GlobalStmt::*;
 
         let Located { node: stmt, span } = stmt;
@@ -58,41 +121,289 @@ impl<'ast> Resolver<'ast> {
             | Union { name, .. }
             | Variable { name, .. }
             | Constant { name, .. }
-            | Alias { name, .. } => {
-                if let Err(err) = self.scope.declare(&name, span.clone()) {
-                    self.errors.push(err);
+            | ExternFunction { name, .. }
+            | Alias { name, .. }
+            | Function { name, .. } => self.declare_global(name, stmt, span),
+            // Whether the module exists or not is already reported by the module loader
+            Import {
+                name,
+                members: None,
+                ..
+            } => self.declare_global(name, stmt, span),
+            // The members are declared as what they are in the module, so that collisions
+            // describe them
+            Import {
+                name,
+                members: Some(members),
+                ..
+            } => {
+                for member in members {
+                    let declared = self
+                        .modules
+                        .and_then(|modules| modules.get(name))
+                        .and_then(|m| m.find(member))
+                        .map_or(stmt, |found| &found.node);
+                    self.declare_global(member, declared, span);
                 }
             }
+        }
+    }
+
+    fn resolve_global_stmt(&mut self, stmt: &'ast LocatedGlobalStmt) {
+        use GlobalStmt::*;
+
+        match &stmt.node {
             Function {
-                name,
+                size_params,
                 params,
-                ret: _,
                 body,
-            } => self.resolve_func(span, name, params, body),
-            Import { name, path } => self.resolve_import(span, name, path),
+                attrs,
+                ..
+            } => {
+                self.in_statemachine = attrs.contains(&FuncAttr::StateMachine);
+                self.resolve_func(size_params, params, body);
+                self.in_statemachine = false;
+            }
+            Variable {
+                name,
+                value: Some(value),
+                ..
+            }
+            | Constant { name, value, .. } => {
+                self.resolve_expr(value);
+                let is_const = matches!(stmt.node, Constant { .. });
+                self.check_constant_init(name, value, is_const);
+            }
+            Import {
+                name,
+                path,
+                members: Some(members),
+            } => {
+                for member in members {
+                    self.check_imported_member(&stmt.span, name, path, member);
+                }
+            }
+            Enum { .. }
+            | Struct { .. }
+            | Union { .. }
+            | Variable { value: None, .. }
+            | ExternFunction { .. }
+            | Alias { .. }
+            | Import { members: None, .. } => {}
         }
     }
 
+    /// Reports `member` of a selective import if the module imported as `module` from `path`
+    /// doesn't export it
+    fn check_imported_member(&mut self, span: &Span, module: &str, path: &str, member: &str) {
+        let Some(m) = self.modules.and_then(|modules| modules.get(module)) else {
+            return;
+        };
+
+        if m.find(member).is_some() {
+            return;
+        }
+
+        let threshold = 1;
+        let msg = match m
+            .exports()
+            .find(|name| threshold >= edit_distance(name, member))
+        {
+            Some(name) => format!("By '{member}' from '{path}', did you mean '{name}'?"),
+            None => format!("'{member}' is not declared in module '{path}'"),
+        };
+        self.errors.push((span.clone(), msg));
+    }
+
+    /// Reports the parts of the initializer of the global `name` that can't be evaluated before
+    /// the program starts, i.e. function calls, allocations, and reads of other global variables
+    ///
+    /// A constant (`is_const`) may call the functions of the program, which the type checker
+    /// tries to evaluate at compile time.
+    fn check_constant_init(&mut self, name: &str, expr: &'ast LocatedExpr, is_const: bool) {
+        use crate::parser::Expr::*;
+
+        let Located { node: expr, span } = expr;
+
+        let reason = match expr {
+            // The length of a constant string is known at compile time
+            FnCall { name: callee, args, .. }
+                if matches!(&callee.node, Ident(func) if func == "len"
+                    && !self.globals.contains_key(func.as_str())) =>
+            {
+                for arg in args {
+                    self.check_constant_init(name, arg, is_const);
+                }
+                return;
+            }
+            FnCall {
+                name: callee, args, ..
+            } if is_const && self.is_function(callee) => {
+                for arg in args {
+                    self.check_constant_init(name, arg, is_const);
+                }
+                return;
+            }
+            FnCall { .. } => "by calling a function".to_string(),
+            Make { .. } | New { .. } => "by allocating memory".to_string(),
+            Assign { .. } => "with an assignment".to_string(),
+            Ident(var)
+                if matches!(
+                    self.globals.get(var.as_str()),
+                    Some(GlobalStmt::Variable { .. })
+                ) =>
+            {
+                format!("with the value of the global variable '{var}'")
+            }
+            Binary { left, right, .. } => {
+                self.check_constant_init(name, left, is_const);
+                self.check_constant_init(name, right, is_const);
+                return;
+            }
+            Parenthesized { expr }
+            | Unary { expr, .. }
+            | MemAccess { expr, .. }
+            | Cast { expr, .. }
+            | Try { expr, .. } => {
+                self.check_constant_init(name, expr, is_const);
+                return;
+            }
+            Ternary { cond, lexpr, rexpr } => {
+                self.check_constant_init(name, cond, is_const);
+                self.check_constant_init(name, lexpr, is_const);
+                self.check_constant_init(name, rexpr, is_const);
+                return;
+            }
+            ArrIndex { arr, idx } => {
+                self.check_constant_init(name, arr, is_const);
+                self.check_constant_init(name, idx, is_const);
+                return;
+            }
+            InitArr { elems } | InitArrDesignated { elems, .. } | Tuple { elems } => {
+                for elem in elems {
+                    self.check_constant_init(name, elem, is_const);
+                }
+                return;
+            }
+            InitStruct { args, .. } => {
+                for (_, arg) in args {
+                    self.check_constant_init(name, arg, is_const);
+                }
+                return;
+            }
+            Int(_) | UInt(_) | Double(_) | Bool(_) | Char(_) | Str(_) | Ident(_) | Sizeof { .. }
+            | EnumVarAccess { .. } | ModuleAccess { .. } => return,
+        };
+
+        self.errors.push((
+            span.clone(),
+            format!("Non-constant global initializer: '{name}' cannot be initialized {reason}"),
+        ));
+    }
+
+    /// Whether `callee` names a function of the program rather than an extern one
+    fn is_function(&self, callee: &LocatedExpr) -> bool {
+        let Located {
+            node: crate::parser::Expr::Ident(func),
+            ..
+        } = callee
+        else {
+            return false;
+        };
+        matches!(
+            self.globals.get(func.as_str()),
+            Some(GlobalStmt::Function { .. })
+        )
+    }
+
+    fn declare_global(&mut self, name: &'ast str, stmt: &'ast GlobalStmt, span: &Span) {
+        match self.scope.declare(name, span.clone()) {
+            Ok(()) => {
+                self.globals.insert(name, stmt);
+            }
+            Err(original) => {
+                let signatures = self
+                    .globals
+                    .get(name)
+                    .map(|previous| (describe(previous), describe(stmt)));
+                self.errors
+                    .extend(redeclaration(name, span.clone(), original, signatures));
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &'ast str, span: &Span) {
+        if let Err(original) = self.scope.declare(name, span.clone()) {
+            self.errors
+                .extend(redeclaration(name, span.clone(), original, None));
+            return;
+        }
+
+        // Locals can shadow the ones of enclosing blocks, while globals are shadowed silently
+        let shadowed = self
+            .scope
+            .enclosing
+            .as_ref()
+            .and_then(|scope| scope.local(name));
+        if let Some(shadowed) = shadowed {
+            self.warnings.push((
+                span.clone(),
+                format!("'{name}' shadows a variable of the same name"),
+            ));
+            self.warnings.push(note(
+                shadowed.clone(),
+                format!("The shadowed '{name}' is declared here"),
+            ));
+        }
+    }
+
+    /// Reports the use of `name` at `span`, which isn't in scope, with `err` unless it refers to
+    /// a local variable of the function that isn't declared yet or whose block has ended
+    fn undeclared(&mut self, name: &str, span: &Span, err: Message) {
+        let initializing = self.initializing.iter().find(|(local, _)| *local == name);
+        let later = self
+            .locals
+            .iter()
+            .find(|(local, decl)| *local == name && decl.start > span.start);
+        let earlier = self
+            .locals
+            .iter()
+            .rev()
+            .find(|(local, decl)| *local == name && decl.start < span.start);
+
+        let (msg, decl) = match initializing.or(later).map(|(_, decl)| decl) {
+            Some(decl) => (format!("'{name}' is used before it is declared"), decl),
+            None => match earlier {
+                Some((_, decl)) => (format!("'{name}' is used after its block has ended"), decl),
+                None => {
+                    self.errors.push(err);
+                    return;
+                }
+            },
+        };
+
+        self.errors.push((span.clone(), msg));
+        self.errors
+            .push(note((*decl).clone(), format!("'{name}' is declared here")));
+    }
+
     fn resolve_func(
         &mut self,
-        span: &Span,
-        name: &'ast String,
+        size_params: &'ast [(String, LocatedType)],
         params: &'ast Vec<(String, LocatedType)>,
         body: &'ast Vec<LocatedStmt>,
     ) {
-        if let Err(err) = self.scope.declare(&name, span.clone()) {
-            self.errors.push(err);
-        }
-
         let old_scope = std::mem::take(&mut self.scope);
         self.scope = Scope::new_with_scope(old_scope);
 
-        for (name, t) in params {
-            if let Err(err) = self.scope.declare(name, t.span.clone()) {
-                self.errors.push(err);
-            }
+        for (name, t) in size_params.iter().chain(params) {
+            self.declare(name, &t.span);
         }
 
+        self.locals.clear();
+        collect_locals(body, &mut self.locals);
+
+        // The body is in the same scope as the parameters, which it can't redeclare
         for stmt in body {
             self.resolve_stmt(stmt);
         }
@@ -100,9 +411,16 @@ impl<'ast> Resolver<'ast> {
         self.scope = *std::mem::take(&mut self.scope.enclosing).unwrap();
     }
 
-    fn resolve_import(&mut self, span: &Span, name: &String, path: &String) {
-        // TODO: Handle whether the module being imported even exists or not
-        todo!()
+    /// Resolves the statements of a block, whose variables go out of scope at its end
+    fn resolve_block(&mut self, body: &'ast Vec<LocatedStmt>) {
+        let old_scope = std::mem::take(&mut self.scope);
+        self.scope = Scope::new_with_scope(old_scope);
+
+        for stmt in body {
+            self.resolve_stmt(stmt);
+        }
+
+        self.scope = *std::mem::take(&mut self.scope.enclosing).unwrap();
     }
 
     fn resolve_stmt(&mut self, stmt: &'ast LocatedStmt) {
@@ -112,12 +430,42 @@ impl<'ast> Resolver<'ast> {
 
         match stmt {
             Variable { name, value, .. } => {
-                if let Err(err) = self.scope.has(&name, span.clone()) {
-                    self.errors.push(err);
-                }
                 if let Some(value) = value {
+                    self.initializing = vec![(name, span)];
                     self.resolve_expr(value);
+                    self.initializing.clear();
+                }
+                self.declare(name, span);
+            }
+            Destructure { pattern, value, .. } => {
+                let (Pattern::Struct(names) | Pattern::Array(names) | Pattern::Tuple(names)) =
+                    pattern;
+                self.initializing = names.iter().map(|name| (name.as_str(), span)).collect();
+                self.resolve_expr(value);
+                self.initializing.clear();
+                for name in names {
+                    self.declare(name, span);
+                }
+            }
+            Return { value: Some(expr) } if self.in_statemachine => {
+                self.errors.push((
+                    span.clone(),
+                    "Cannot return a value from a @statemachine function".to_string(),
+                ));
+                self.errors.push(help(
+                    span.clone(),
+                    "Use 'yield' to give the caller a value, and 'return;' to finish",
+                ));
+                self.resolve_expr(expr);
+            }
+            Yield { value } => {
+                if !self.in_statemachine {
+                    self.errors.push((
+                        span.clone(),
+                        "'yield' is only allowed in @statemachine functions".to_string(),
+                    ));
                 }
+                self.resolve_expr(value);
             }
             Expression { expr }
             | Return { value: Some(expr) }
@@ -127,11 +475,12 @@ impl<'ast> Resolver<'ast> {
             }
             If { cond, then, other } => self.resolve_if(cond, then, other),
             While { cond, body, .. } => self.resolve_while(cond, body),
-            Defer { body } => {
-                for stmt in body {
-                    self.resolve_stmt(stmt);
-                }
-            }
+            Switch {
+                value,
+                cases,
+                default,
+            } => self.resolve_switch(value, cases, default),
+            Defer { body } | Block { body } => self.resolve_block(body),
             Break | Continue | Return { value: None } => {}
         }
     }
@@ -143,23 +492,34 @@ impl<'ast> Resolver<'ast> {
         other: &'ast Option<Vec<LocatedStmt>>,
     ) {
         self.resolve_expr(cond);
-
-        for stmt in then {
-            self.resolve_stmt(stmt);
-        }
-
+        self.resolve_block(then);
         if let Some(other) = other {
-            for stmt in other {
-                self.resolve_stmt(stmt);
-            }
+            self.resolve_block(other);
         }
     }
 
     fn resolve_while(&mut self, cond: &'ast LocatedExpr, body: &'ast Vec<LocatedStmt>) {
         self.resolve_expr(cond);
+        self.resolve_block(body);
+    }
 
-        for stmt in body {
-            self.resolve_stmt(stmt);
+    fn resolve_switch(
+        &mut self,
+        value: &'ast LocatedExpr,
+        cases: &'ast Vec<SwitchCase>,
+        default: &'ast Option<Vec<LocatedStmt>>,
+    ) {
+        self.resolve_expr(value);
+
+        for case in cases {
+            for value in &case.values {
+                self.resolve_expr(value);
+            }
+            self.resolve_block(&case.body);
+        }
+
+        if let Some(default) = default {
+            self.resolve_block(default);
         }
     }
 
@@ -170,6 +530,7 @@ impl<'ast> Resolver<'ast> {
 
         match expr {
             Int(_)
+            | UInt(_)
             | Double(_)
             | Bool(_)
             | Char(_)
@@ -178,8 +539,9 @@ impl<'ast> Resolver<'ast> {
             | Make { .. }
             | New { .. } => {}
             Ident(name) => {
+                self.record_use(name, span);
                 if let Err(err) = self.scope.has(&name, span.clone()) {
-                    self.errors.push(err);
+                    self.undeclared(name, span, err);
                 }
             }
             Binary { left, right, .. } => {
@@ -189,41 +551,48 @@ impl<'ast> Resolver<'ast> {
             Parenthesized { expr }
             | Unary { expr, .. }
             | MemAccess { expr, .. }
-            | Cast { expr, .. } => {
+            | Cast { expr, .. }
+            | Try { expr, .. } => {
                 self.resolve_expr(expr);
             }
+            // Whether the lvalue can be assigned to is up to the type checker
             Assign { lvalue, value, .. } => {
-                self.resolve_assign(lvalue, value);
+                self.resolve_expr(lvalue);
+                self.resolve_expr(value);
             }
             Ternary { cond, lexpr, rexpr } => {
                 self.resolve_expr(cond);
                 self.resolve_expr(lexpr);
                 self.resolve_expr(rexpr);
             }
-            FnCall { name, args } => {
+            FnCall { name, args, .. } => {
                 self.resolve_expr(name);
                 for arg in args {
                     self.resolve_expr(arg);
                 }
             }
             EnumVarAccess { ident, .. } => {
+                self.record_use(ident, span);
                 if let Err(err) = self.scope.has(&ident, span.clone()) {
                     self.errors.push(err);
                 }
             }
+            ModuleAccess { module, member } => self.resolve_module_access(span, module, member),
             ArrIndex { arr, idx } => {
                 self.resolve_expr(arr);
                 self.resolve_expr(idx);
             }
-            InitArr { elems } | InitArrDesignated { elems, .. } => {
+            InitArr { elems } | InitArrDesignated { elems, .. } | Tuple { elems } => {
                 for elem in elems {
                     self.resolve_expr(elem);
                 }
             }
             InitStruct { ident, args } => {
+                self.record_use(ident, span);
                 if let Err(err) = self.scope.has(&ident, span.clone()) {
                     self.errors.push(err);
                 }
+                self.check_struct_init(span, ident, args);
                 for arg in args {
                     self.resolve_expr(&arg.1);
                 }
@@ -231,34 +600,97 @@ impl<'ast> Resolver<'ast> {
         }
     }
 
-    fn resolve_assign(&mut self, lvalue: &'ast Box<LocatedExpr>, value: &'ast Box<LocatedExpr>) {
-        use crate::parser::Expr::*;
-
-        let Located {
-            node: lvalue,
-            span: lspan,
-        } = &**lvalue;
+    /// Reports the fields of `ident { ... }` that the struct doesn't have, that are initialized
+    /// more than once, or that are missing
+    ///
+    /// A field the struct doesn't have is most likely a typo of a missing one, so those are
+    /// suggested first, and missing fields are only reported once every field is known.
+    fn check_struct_init(
+        &mut self,
+        span: &Span,
+        ident: &str,
+        args: &'ast [(String, LocatedExpr)],
+    ) {
+        let Some(GlobalStmt::Struct { fields, .. }) = self.globals.get(ident) else {
+            return;
+        };
+
+        let mut missing: Vec<&str> = fields.iter().map(|(name, _)| name.as_str()).collect();
+        let mut unknown = vec![];
+        for (name, value) in args {
+            if let Some(i) = missing.iter().position(|field| field == name) {
+                missing.remove(i);
+            } else if fields.iter().any(|(field, _)| field == name) {
+                self.errors.push((
+                    value.span.clone(),
+                    format!("The field '{name}' is initialized more than once"),
+                ));
+            } else {
+                unknown.push((name, value));
+            }
+        }
 
-        match lvalue {
-            Ident(var) => {
-                if let Err(err) = self.scope.has(&var, lspan.clone()) {
-                    self.errors.push(err);
+        let threshold = 1;
+        for (name, value) in &unknown {
+            let suggestion = missing
+                .iter()
+                .copied()
+                .chain(fields.iter().map(|(field, _)| field.as_str()))
+                .map(|field| (edit_distance(field, name), field))
+                .filter(|(distance, _)| *distance <= threshold)
+                .min_by_key(|(distance, _)| *distance);
+            let msg = match suggestion {
+                Some((_, field)) => {
+                    format!("'{ident}' has no field '{name}', did you mean '{field}'?")
                 }
+                None => format!("'{ident}' has no field '{name}'"),
+            };
+            self.errors.push((value.span.clone(), msg));
+        }
+
+        if unknown.is_empty() && !missing.is_empty() {
+            let quoted: Vec<String> = missing.iter().map(|field| format!("'{field}'")).collect();
+            let msg = match quoted.as_slice() {
+                [field] => format!("The field {field} of '{ident}' is not initialized"),
+                [init @ .., last] => format!(
+                    "The fields {} and {last} of '{ident}' are not initialized",
+                    init.join(", ")
+                ),
+                [] => unreachable!(),
+            };
+            self.errors.push((span.clone(), msg));
+        }
+    }
+
+    fn resolve_module_access(&mut self, span: &Span, module: &'ast String, member: &String) {
+        if let Err(err) = self.scope.has(module, span.clone()) {
+            self.errors.push(err);
+            return;
+        }
+
+        let Some(m) = self.modules.and_then(|modules| modules.get(module)) else {
+            return;
+        };
+
+        if m.find(member).is_some() {
+            return;
+        }
+
+        let threshold = 1;
+        for name in m.exports() {
+            if threshold >= edit_distance(name, member) {
+                self.errors.push((
+                    span.clone(),
+                    format!("By '{module}::{member}', did you mean '{module}::{name}'?"),
+                ));
+                return;
             }
-            // Should I allow Expr::Assign to be a lvalue?
-            MemAccess { expr, .. } => {
-                self.resolve_expr(&expr);
-            }
-            ArrIndex { arr, idx } => {
-                self.resolve_expr(&arr);
-                self.resolve_expr(&idx);
-            }
-            _ => self
-                .errors
-                .push((lspan.clone(), format!("Invalid lvalue for assignment"))),
         }
 
-        self.resolve_expr(value);
+        self.errors.push((
+            span.clone(),
+            format!("'{member}' is not declared in module '{module}'"),
+        ));
     }
 }
 
@@ -277,22 +709,39 @@ impl<'ast> Scope<'ast> {
         }
     }
 
-    pub fn declare(&mut self, name: &'ast str, span: Span) -> Result<(), Message> {
+    /// Declares `name`, or returns the span of the original declaration if it is already declared
+    pub fn declare(&mut self, name: &'ast str, span: Span) -> Result<(), Span> {
         match self.names.entry(name) {
-            Entry::Occupied(_) => Err((span, format!("'{name}' is already declared"))),
+            Entry::Occupied(entry) => Err(entry.get().clone()),
             Entry::Vacant(entry) => {
-                entry.insert((span, false));
+                entry.insert(span);
                 Ok(())
             }
         }
     }
 
-    pub fn has(&mut self, name: &'ast str, span: Span) -> Result<(), Message> {
+    /// Returns where `name` is declared in this scope or an enclosing one
+    fn find(&self, name: &str) -> Option<&Span> {
+        self.names
+            .get(name)
+            .or_else(|| self.enclosing.as_ref()?.find(name))
+    }
+
+    /// Returns where `name` is declared in this scope or an enclosing one, unless it is global
+    fn local(&self, name: &str) -> Option<&Span> {
+        let enclosing = self.enclosing.as_ref()?;
+        self.names
+            .get(name)
+            .or_else(|| enclosing.local(name))
+    }
+
+    pub fn has(&self, name: &str, span: Span) -> Result<(), Message> {
         if self.names.contains_key(name) {
-            *self.names.get_mut(&name).unwrap() = (span, true);
             Ok(())
-        } else if let Some(scope) = &mut self.enclosing {
+        } else if let Some(scope) = &self.enclosing {
             scope.has(name, span)
+        } else if BUILTIN_FUNCS.contains(&name) {
+            Ok(())
         } else {
             let threshold = 1;
             for n in self.names.keys() {
@@ -304,3 +753,69 @@ impl<'ast> Scope<'ast> {
         }
     }
 }
+
+/// Collects the variables declared in `body` and the blocks in it, in order
+fn collect_locals<'ast>(body: &'ast [LocatedStmt], locals: &mut Vec<(&'ast str, &'ast Span)>) {
+    for stmt in body {
+        match &stmt.node {
+            Stmt::Variable { name, .. } => locals.push((name, &stmt.span)),
+            Stmt::Destructure { pattern, .. } => {
+                let (Pattern::Struct(names) | Pattern::Array(names) | Pattern::Tuple(names)) =
+                    pattern;
+                locals.extend(names.iter().map(|name| (name.as_str(), &stmt.span)));
+            }
+            Stmt::If { then, other, .. } => {
+                collect_locals(then, locals);
+                if let Some(other) = other {
+                    collect_locals(other, locals);
+                }
+            }
+            Stmt::While { body, .. } | Stmt::Defer { body } | Stmt::Block { body } => {
+                collect_locals(body, locals)
+            }
+            Stmt::Switch { cases, default, .. } => {
+                for case in cases {
+                    collect_locals(&case.body, locals);
+                }
+                if let Some(default) = default {
+                    collect_locals(default, locals);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Describes what a global declares, e.g. `func(i32, str): void`
+fn describe(stmt: &GlobalStmt) -> String {
+    use GlobalStmt::*;
+
+    match stmt {
+        Function { params, ret, .. } | ExternFunction { params, ret, .. } => {
+            let params = params
+                .iter()
+                .map(|(_, t)| t.node.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("func({params}): {}", ret.node)
+        }
+        Enum { .. } => "enum".to_string(),
+        Struct { .. } => "struct".to_string(),
+        Union { .. } => "union".to_string(),
+        Alias { t, .. } => format!("alias = {}", t.node),
+        Variable { t: Some(t), .. } => format!("let: {t}"),
+        Constant { t: Some(t), .. } => format!("const: {}", t.node),
+        Variable { t: None, .. } => "let".to_string(),
+        Constant { t: None, .. } => "const".to_string(),
+        Import {
+            path,
+            members: None,
+            ..
+        } => format!("import \"{path}\""),
+        Import {
+            path,
+            members: Some(_),
+            ..
+        } => format!("from \"{path}\" import"),
+    }
+}
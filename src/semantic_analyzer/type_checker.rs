@@ -1,13 +1,31 @@
+use std::cell::{Cell, RefCell};
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use tamago::{AssignOp, BinOp, UnaryOp};
 
+use crate::diagnostics::note;
+use crate::lowering::{conversions, results};
+use crate::module::Module;
 use crate::parser::*;
 use crate::semantic_analyzer::*;
 
+use super::consteval::{self, ConstEvaluator, Value};
+use super::lints;
+
 type ReturnType = Option<(Type, bool)>;
 
+/// The type of each expression, keyed by its span
+pub type ExprTypes = HashMap<Span, Type>;
+
+/// The type parameters, size parameters, parameters, and return type of a function
+type Signature<'ast> = (
+    &'ast [Located<String>],
+    &'ast [(String, LocatedType)],
+    &'ast Vec<(String, LocatedType)>,
+    &'ast LocatedType,
+);
+
 #[derive(Debug)]
 enum UserDefinedType<'ast> {
     Enum {
@@ -24,8 +42,11 @@ enum UserDefinedType<'ast> {
     },
     Function {
         span: &'ast Span,
+        type_params: &'ast [Located<String>],
+        size_params: &'ast [(String, LocatedType)],
         params: &'ast Vec<(String, LocatedType)>,
         ret: &'ast LocatedType,
+        is_statemachine: bool,
     },
     Alias {
         span: &'ast Span,
@@ -37,17 +58,84 @@ enum UserDefinedType<'ast> {
     },
 }
 
+impl UserDefinedType<'_> {
+    fn span(&self) -> &Span {
+        match self {
+            Self::Enum { span, .. }
+            | Self::Struct { span, .. }
+            | Self::Union { span, .. }
+            | Self::Function { span, .. }
+            | Self::Alias { span, .. }
+            | Self::Import { span, .. } => span,
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 struct Types<'ast> {
     types: HashMap<&'ast str, LocatedType>,
+
+    /// The names in `types` declared with `const`
+    consts: HashSet<&'ast str>,
+
     enclosing: Option<Box<Types<'ast>>>,
 }
 
+/// The statements around the one being checked that `break` and `continue` can leave
+#[derive(Debug, Clone, Copy, Default)]
+struct Jumps {
+    loops: usize,
+    cases: usize,
+
+    /// Whether the statement is in a deferred block, which runs when its scope is left and so
+    /// can't be left itself
+    in_defer: bool,
+}
+
+/// Where a variable or constant is declared, which decides what it may be initialized with
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DeclContext {
+    is_global: bool,
+    is_const: bool,
+}
+
 #[derive(Debug)]
 pub struct TypeChecker<'ast> {
     ast: &'ast Vec<LocatedGlobalStmt>,
     types: Types<'ast>,
     user_def_types: HashMap<&'ast str, UserDefinedType<'ast>>,
+    modules: Option<&'ast HashMap<String, Module>>,
+
+    /// The `[N]T` parameters of the current function, which are passed by reference and read-only
+    array_params: HashSet<&'ast str>,
+
+    /// The size parameters of the current function if it is generic
+    size_params: HashSet<&'ast str>,
+
+    /// What `break` and `continue` can leave in the current function
+    jumps: Jumps,
+
+    /// The return type of the current function, which `try` returns errors as, or `None` outside
+    /// functions and in `@statemachine` functions
+    ret: Option<&'ast Type>,
+
+    /// Why `try` cannot be used in the expression being checked, if it can't
+    no_try: Option<&'static str>,
+
+    /// Evaluates the initializers of constants
+    consts: ConstEvaluator<'ast>,
+
+    /// The variables whose declarations failed without an explicit type, whose uses aren't
+    /// reported as undeclared on top of that
+    failed_decls: HashSet<&'ast str>,
+
+    /// Whether the error of the current statement comes from a use of `failed_decls`, so it
+    /// isn't recorded
+    is_cascading: bool,
+
+    /// The type of each expression, keyed by its span, if they are recorded
+    /// (`check_with_types`)
+    expr_types: Option<ExprTypes>,
 
     errors: Vec<Message>,
     warnings: Vec<Message>,
@@ -59,16 +147,56 @@ impl<'ast> TypeChecker<'ast> {
             ast,
             types: Types::new(),
             user_def_types: HashMap::new(),
+            modules: None,
+            array_params: HashSet::new(),
+            size_params: HashSet::new(),
+            jumps: Jumps::default(),
+            ret: None,
+            no_try: None,
+            consts: ConstEvaluator::new(ast),
+            failed_decls: HashSet::new(),
+            is_cascading: false,
+            expr_types: None,
             errors: vec![],
             warnings: vec![],
         }
     }
 
+    /// Sets the modules loaded for the imports, so that the types of their members are known
+    pub fn modules(mut self, modules: &'ast HashMap<String, Module>) -> Self {
+        self.modules = Some(modules);
+        self
+    }
+
     pub fn check(mut self) -> (Vec<Message>, Result<(), Vec<Message>>) {
+        self.check_all();
+        self.finish()
+    }
+
+    /// Checks the program like `check`, and also returns the type of each expression keyed by
+    /// its span, which is recorded for the expressions that check even if others don't
+    pub fn check_with_types(mut self) -> (Vec<Message>, Result<(), Vec<Message>>, ExprTypes) {
+        self.expr_types = Some(HashMap::new());
+        self.check_all();
+
+        let expr_types = self.expr_types.take().unwrap_or_default();
+        let (warnings, res) = self.finish();
+        (warnings, res, expr_types)
+    }
+
+    fn check_all(&mut self) {
+        // Types and functions are declared up front so that they can be used before their
+        // declarations
+        for stmt in self.ast {
+            self.declare_global_stmt(stmt);
+        }
+
         for stmt in self.ast {
             self.check_global_stmt(stmt);
         }
+    }
 
+    fn finish(self) -> (Vec<Message>, Result<(), Vec<Message>>) {
         if self.errors.is_empty() {
             (self.warnings, Ok(()))
         } else {
@@ -76,7 +204,15 @@ impl<'ast> TypeChecker<'ast> {
         }
     }
 
-    fn check_global_stmt(&mut self, stmt: &'ast LocatedGlobalStmt) {
+    /// Records the type of the expression at `span` if types are recorded, keeping the first
+    /// one for an expression checked more than once
+    fn record_type(&mut self, span: &Span, t: &Type) {
+        if let Some(expr_types) = &mut self.expr_types {
+            expr_types.entry(span.clone()).or_insert_with(|| t.clone());
+        }
+    }
+
+    fn declare_global_stmt(&mut self, stmt: &'ast LocatedGlobalStmt) {
         use GlobalStmt::*;
 
         let Located { node: gstmt, span } = stmt;
@@ -86,118 +222,433 @@ impl<'ast> TypeChecker<'ast> {
             | Struct { name, .. }
             | Union { name, .. }
             | Alias { name, .. }
-            | Import { name, .. } => {
+            | Import {
+                name,
+                members: None,
+                ..
+            } => {
                 let _ = self.define_user_type(name, stmt);
             }
 
-            Function {
+            Import {
                 name,
-                params,
-                ret,
-                body,
+                members: Some(members),
+                ..
             } => {
+                for member in members {
+                    self.declare_imported_member(span, name, member);
+                }
+            }
+
+            Function {
+                name, params, ret, ..
+            }
+            | ExternFunction { name, params, ret } => {
                 if let Err(w) = self.types.declare(
                     name,
                     Located {
-                        node: Type::UserDefinedType(name.to_string()),
+                        node: func_type(params, ret),
                         span: span.clone(),
                     },
                 ) {
-                    self.warnings.push(w);
+                    self.warnings.extend(w);
                 }
 
                 let _ = self.define_user_type(name, stmt);
+            }
 
-                let old_types = std::mem::take(&mut self.types);
-                self.types = Types::new_with_types(old_types);
+            Variable { .. } | Constant { .. } => {}
+        }
+    }
+
+    /// Declares `member` of the module imported as `module` under its own name, as the module
+    /// declares it
+    fn declare_imported_member(&mut self, span: &'ast Span, module: &'ast str, member: &'ast str) {
+        use GlobalStmt::*;
+
+        // Members the module doesn't export are reported by the resolver
+        let Some(stmt) = self
+            .modules
+            .and_then(|modules| modules.get(module))
+            .and_then(|m| m.find(member))
+        else {
+            return;
+        };
+
+        let t = match &stmt.node {
+            Function { params, ret, .. } | ExternFunction { params, ret, .. } => {
+                let _ = self.define_user_type(member, stmt);
+                func_type(params, ret)
+            }
+            Variable { t: Some(t), .. } => t.clone(),
+            Constant { t: Some(t), .. } => {
+                self.types.consts.insert(member);
+                t.node.clone()
+            }
+            Variable { t: None, .. } | Constant { t: None, .. } => {
+                self.errors.push((
+                    span.clone(),
+                    format!(
+                        "The type of '{member}' must be explicit to be imported from another \
+                         module"
+                    ),
+                ));
+                return;
+            }
+            Enum { .. } | Struct { .. } | Union { .. } | Alias { .. } => {
+                let _ = self.define_user_type(member, stmt);
+                return;
+            }
+            Import { .. } => return,
+        };
+
+        let _ = self.types.declare(
+            member,
+            Located {
+                node: t,
+                span: span.clone(),
+            },
+        );
+    }
 
-                for (name, t) in params {
-                    if let Err(err) = self.types.declare(name, t.clone()) {
+    fn check_global_stmt(&mut self, stmt: &'ast LocatedGlobalStmt) {
+        use GlobalStmt::*;
+
+        let Located { node: gstmt, span } = stmt;
+
+        match gstmt {
+            Enum { .. } | Import { .. } => {}
+
+            Struct { fields, .. } | Union { fields, .. } => {
+                for (_, t) in fields {
+                    self.check_not_tuple(t);
+                    if let Err(err) = self.check_sizes(&t.span, &t.node) {
                         self.errors.push(err);
                     }
                 }
+            }
 
-                self.check_func_body(ret, body);
+            Alias { t, .. } => {
+                self.check_not_tuple(t);
+                if let Err(err) = self.check_sizes(&t.span, &t.node) {
+                    self.errors.push(err);
+                }
             }
 
-            Variable {
+            ExternFunction { params, ret, .. } => {
+                for t in params.iter().map(|(_, t)| t).chain([ret]) {
+                    if let Err(err) = self.check_sizes(&t.span, &t.node) {
+                        self.errors.push(err);
+                    }
+                }
+                for (_, t) in params {
+                    self.check_not_tuple(t);
+                }
+                if let Type::Tuple(_) = ret.node {
+                    self.errors.push((
+                        ret.span.clone(),
+                        "Extern functions cannot return multiple values".to_string(),
+                    ));
+                } else {
+                    self.check_not_tuple(ret);
+                }
+            }
+
+            Function {
                 name,
-                t,
-                value: Some(value),
+                type_params,
+                size_params,
+                params,
+                ret,
+                body,
+                attrs,
                 ..
             } => {
-                let var_t: Type;
-                if let Some(t) = t {
-                    var_t = t.clone();
-                    let given_t: Type;
-                    match self.check_expr(value) {
-                        Err(err) => {
-                            self.errors.push(err);
-                            return;
-                        }
-                        Ok(t) => given_t = t,
-                    }
-
-                    if var_t != given_t {
+                for (_, t) in params {
+                    self.check_not_tuple(t);
+                }
+                if let Type::Tuple(elems) = &ret.node {
+                    if elems.iter().any(|t| *t == Type::Void || contains_tuple(t)) {
                         self.errors.push((
-                            span.clone(),
-                            format!("Expected '{var_t}' but got '{given_t}'"),
+                            ret.span.clone(),
+                            format!("Cannot return '{}' as one of multiple values", ret.node),
                         ));
-                        return;
                     }
                 } else {
-                    match self.check_expr(value) {
-                        Err(err) => {
-                            self.errors.push(err);
-                            return;
+                    self.check_not_tuple(ret);
+                }
+
+                let old_types = std::mem::take(&mut self.types);
+                self.types = Types::new_with_types(old_types);
+
+                self.check_size_params(name, size_params, params, ret);
+
+                // The body of a function with type parameters is checked for each instance
+                // instead, since what it can do with a `T` depends on the type
+                if type_params.is_empty() {
+                    self.array_params.clear();
+                    for (name, t) in params {
+                        if let Err(err) = self.types.declare(name, t.clone()) {
+                            self.errors.extend(err);
+                        }
+                        if let Type::Array(..) | Type::GenericArray(..) = t.node {
+                            self.array_params.insert(name);
                         }
-                        Ok(t) => var_t = t,
                     }
+
+                    self.jumps = Jumps::default();
+                    self.ret = Some(&ret.node).filter(|_| !attrs.contains(&FuncAttr::StateMachine));
+                    self.check_func_body(ret, body);
+                    self.ret = None;
+                } else {
+                    self.check_type_params(name, type_params, params);
                 }
+                self.size_params.clear();
 
-                if let Err(err) = self.types.declare(
-                    name,
-                    Located {
-                        node: var_t,
-                        span: span.clone(),
-                    },
-                ) {
-                    self.errors.push(err);
+                self.types = *std::mem::take(&mut self.types.enclosing).unwrap();
+            }
+
+            Variable { name, t, value, .. } => {
+                let ctx = DeclContext {
+                    is_global: true,
+                    is_const: false,
+                };
+                self.is_cascading = false;
+                if let Err(err) = self.check_declaration(ctx, span, name, t.as_ref(), value.as_ref())
+                {
+                    self.record(err);
                 }
             }
 
-            Variable {
-                name,
-                t,
-                value: None,
-                ..
-            } => {
-                if let Some(t) = t {
-                    if let Err(err) = self.types.declare(
+            Constant { name, t, value, .. } => {
+                let ctx = DeclContext {
+                    is_global: true,
+                    is_const: true,
+                };
+                let t = t.as_ref().map(|t| &t.node);
+                self.is_cascading = false;
+                if let Err(err) = self.check_declaration(ctx, span, name, t, Some(value)) {
+                    self.record(err);
+                }
+            }
+        }
+    }
+
+    /// Checks the size parameters of a generic function and declares them as constants
+    ///
+    /// Each one has to be an integer and the length of an array parameter, so that calls can
+    /// infer it, and the parameters and return type can only use the function's own ones.
+    fn check_size_params(
+        &mut self,
+        func: &'ast str,
+        size_params: &'ast [(String, LocatedType)],
+        params: &'ast [(String, LocatedType)],
+        ret: &'ast LocatedType,
+    ) {
+        self.size_params = size_params.iter().map(|(name, _)| name.as_str()).collect();
+
+        for (name, t) in size_params {
+            if !t.node.is_integer() {
+                self.errors.push((
+                    t.span.clone(),
+                    format!("The size parameter '{name}' must be an integer but got '{}'", t.node),
+                ));
+            }
+
+            let is_inferred = params
+                .iter()
+                .any(|(_, param_t)| array_sizes(&param_t.node).contains(&name.as_str()));
+            if !is_inferred {
+                self.errors.push((
+                    t.span.clone(),
+                    format!(
+                        "The size parameter '{name}' of '{func}' must be the length of an array \
+                         parameter, like '[{name}]i32'"
+                    ),
+                ));
+            }
+
+            if let Err(err) = self.types.declare(name, t.clone()) {
+                self.errors.extend(err);
+            }
+            self.types.consts.insert(name);
+        }
+
+        for t in params.iter().map(|(_, t)| t).chain([ret]) {
+            if let Err(err) = self.check_sizes(&t.span, &t.node) {
+                self.errors.push(err);
+            }
+        }
+    }
+
+    /// Checks that the type parameters of a generic function are the types of parameters, so
+    /// that calls can infer them, and don't hide the types they are named after
+    fn check_type_params(
+        &mut self,
+        func: &str,
+        type_params: &[Located<String>],
+        params: &[(String, LocatedType)],
+    ) {
+        for Located { node: name, span } in type_params {
+            if let Some(global) = self.user_def_types.get(name.as_str()) {
+                self.errors.push((
+                    span.clone(),
+                    format!("The type parameter '{name}' has the same name as a global"),
+                ));
+                self.errors.push(note(
+                    global.span().clone(),
+                    format!("'{name}' is declared here"),
+                ));
+            }
+
+            let is_inferred = params
+                .iter()
+                .any(|(_, t)| type_names(&t.node).contains(&name.as_str()));
+            if !is_inferred {
+                self.errors.push((
+                    span.clone(),
+                    format!(
+                        "The type parameter '{name}' of '{func}' must be the type of a \
+                         parameter, like 'x: {name}'"
+                    ),
+                ));
+            }
+        }
+    }
+
+    /// Checks that the lengths of the `[N]T` arrays in `t` are size parameters of the current
+    /// function, since the global constants have been replaced with their values
+    fn check_sizes(&self, span: &Span, t: &Type) -> Result<(), Message> {
+        match array_sizes(t)
+            .into_iter()
+            .find(|size| !self.size_params.contains(size))
+        {
+            Some(size) => Err((
+                span.clone(),
+                format!("'{size}' is neither a global constant nor a size parameter"),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks a variable or constant, global or local, against its initializer and declares it
+    ///
+    /// Which initializers have to be known before the program starts is checked by the resolver,
+    /// since it knows what every name refers to. Global constants are evaluated here so that
+    /// mistakes like indexing out of bounds are caught at compile time instead of at runtime.
+    /// What can't be evaluated has to be something C evaluates at compile time, like `sizeof`,
+    /// and arrays and structs, which are emitted as `static const` data, have to be fully known.
+    fn check_declaration(
+        &mut self,
+        ctx: DeclContext,
+        span: &'ast Span,
+        name: &'ast str,
+        t: Option<&'ast Type>,
+        value: Option<&'ast LocatedExpr>,
+    ) -> Result<(), Message> {
+        let decl_t = match self.declared_type(ctx, span, name, t, value) {
+            Ok(decl_t) => decl_t,
+            Err(err) => {
+                // Later uses are checked against the explicit type, or not at all, rather than
+                // reported as undeclared
+                match t {
+                    Some(t) => self.declare(
                         name,
                         Located {
                             node: t.clone(),
                             span: span.clone(),
                         },
-                    ) {
-                        self.errors.push(err);
+                    ),
+                    None => {
+                        self.failed_decls.insert(name);
                     }
-                } else {
+                }
+                return Err(err);
+            }
+        };
+
+        if let (true, Some(value)) = (ctx.is_global && ctx.is_const, value) {
+            match self.consts.eval(value) {
+                Err(err) => self.errors.push(err),
+                Ok(None) if !consteval::is_c_constant(value) => {
                     self.errors.push((
-                        span.clone(),
-                        format!("Expected an explicit type but got nothing"),
+                        value.span.clone(),
+                        format!("'{name}' must be initialized with constant expressions"),
                     ));
+                    // Why the functions it calls aren't constant
+                    self.errors.extend(self.consts.take_notes());
                 }
+                Ok(_) => {}
             }
+        }
 
-            Constant { name, t, value, .. } => {
-                todo!() // TODO: Only allow compile-time expressions
+        self.declare(
+            name,
+            Located {
+                node: decl_t,
+                span: span.clone(),
+            },
+        );
+        if ctx.is_const {
+            self.types.consts.insert(name);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the type of a variable or constant declaration, checking that its value fits it
+    fn declared_type(
+        &mut self,
+        ctx: DeclContext,
+        span: &'ast Span,
+        name: &'ast str,
+        t: Option<&'ast Type>,
+        value: Option<&'ast LocatedExpr>,
+    ) -> Result<Type, Message> {
+        if let Some(t) = t {
+            self.check_sizes(span, t)?;
+        }
+
+        let decl_t = match (t, value) {
+            (Some(t), Some(value)) => {
+                let value_t = self.check_expr_as(t, value)?;
+                if !Self::assignable(t, value, &value_t) {
+                    return Err((span.clone(), Self::mismatch(t, value, &value_t)));
+                }
+                t.clone()
+            }
+            (None, Some(value)) => self.check_expr(value)?,
+            (Some(_), None) if ctx.is_const => {
+                return Err((span.clone(), format!("The constant '{name}' must be initialized")));
             }
+            (Some(t), None) => t.clone(),
+            (None, None) => {
+                return Err((
+                    span.clone(),
+                    "Expected an explicit type but got nothing".to_string(),
+                ));
+            }
+        };
+
+        if let Some(value) = value {
+            Self::check_array_init(&decl_t, value)?;
         }
+
+        if contains_tuple(&decl_t) {
+            return Err((
+                span.clone(),
+                format!(
+                    "Cannot store '{decl_t}' in a variable, destructure multiple return values \
+                     like 'let (a, b) = ...'"
+                ),
+            ));
+        }
+
+        Ok(decl_t)
     }
 
     fn check_func_body(&mut self, ret: &'ast LocatedType, body: &'ast Vec<LocatedStmt>) {
-        todo!()
+        self.check_branch(ret, body);
     }
 
     /// Some(true) -> full return
@@ -216,57 +667,49 @@ impl<'ast> TypeChecker<'ast> {
             Variable {
                 name,
                 t,
-                value: Some(value),
+                value,
+                is_const,
                 ..
             } => {
-                let var_t: Type;
-                if let Some(t) = t {
-                    var_t = t.clone();
-                    let given_t = self.check_expr(value)?;
-
-                    if var_t != given_t {
-                        return Err((
-                            span.clone(),
-                            format!("Expected '{var_t}' but got '{given_t}'"),
-                        ));
-                    }
-                } else {
-                    var_t = self.check_expr(value)?;
-                }
-
-                self.types.declare(
-                    name,
-                    Located {
-                        node: var_t,
-                        span: span.clone(),
-                    },
-                )?;
-
+                let ctx = DeclContext {
+                    is_global: false,
+                    is_const: *is_const,
+                };
+                self.check_declaration(ctx, span, name, t.as_ref(), value.as_ref())?;
                 Ok(None)
             }
 
-            Variable {
-                name,
-                t,
-                value: None,
-                ..
+            Destructure {
+                pattern,
+                value,
+                is_const,
             } => {
-                if let Some(t) = t {
-                    self.types.declare(
+                let bindings = self
+                    .check_expr(value)
+                    .and_then(|value_t| self.check_pattern(span, pattern, &value_t));
+                let bindings = match bindings {
+                    Ok(bindings) => bindings,
+                    Err(err) => {
+                        let (Pattern::Struct(names)
+                        | Pattern::Array(names)
+                        | Pattern::Tuple(names)) = pattern;
+                        self.failed_decls.extend(names.iter().map(String::as_str));
+                        return Err(err);
+                    }
+                };
+                for (name, t) in bindings {
+                    self.declare(
                         name,
                         Located {
-                            node: t.clone(),
+                            node: t,
                             span: span.clone(),
                         },
-                    )?;
-
-                    Ok(None)
-                } else {
-                    Err((
-                        span.clone(),
-                        format!("Expected an explicit type but got nothing"),
-                    ))
+                    );
+                    if *is_const {
+                        self.types.consts.insert(name);
+                    }
                 }
+                Ok(None)
             }
 
             Expression { expr } => {
@@ -274,9 +717,18 @@ impl<'ast> TypeChecker<'ast> {
                 Ok(None)
             }
 
+            // Returning would leave the deferred block while it runs on the way out
+            Return { .. } if self.jumps.in_defer => Err((
+                span.clone(),
+                "No return statement is allowed in defer body".to_string(),
+            )),
             Return { value } => {
+                if let Type::Tuple(expected) = &expected_ret.node {
+                    return self.check_return_tuple(span, expected_ret, expected, value.as_ref());
+                }
+
                 if let Some(val) = value {
-                    let t = self.check_expr(val)?;
+                    let t = self.check_expr_as(&expected_ret.node, val)?;
                     if expected_ret.node == t {
                         Ok(Some(true))
                     } else {
@@ -304,22 +756,44 @@ impl<'ast> TypeChecker<'ast> {
                 }
             }
 
+            Yield { value } => {
+                let t = self.check_expr_as(&expected_ret.node, value)?;
+                if expected_ret.node == t {
+                    Ok(None)
+                } else {
+                    Err((
+                        span.clone(),
+                        format!("Expected {} as yield type but got {}", expected_ret.node, t),
+                    ))
+                }
+            }
+
+            Break if self.jumps.loops == 0 && self.jumps.cases == 0 => Err((
+                span.clone(),
+                if self.jumps.in_defer {
+                    "'break' cannot leave a deferred block".to_string()
+                } else {
+                    "'break' must be in a loop or a switch".to_string()
+                },
+            )),
+            Continue if self.jumps.loops == 0 => Err((
+                span.clone(),
+                if self.jumps.in_defer {
+                    "'continue' cannot leave a deferred block".to_string()
+                } else {
+                    "'continue' must be in a loop".to_string()
+                },
+            )),
             Break | Continue => Ok(None),
 
             If { cond, then, other } => {
-                let cond_t = self.check_expr(cond)?;
-                if cond_t != Type::Bool {
-                    return Err((
-                        span.clone(),
-                        format!("If condition must be boolean but got {}", cond_t),
-                    ));
-                }
+                self.check_cond("If", span, cond);
 
-                let then_returns = self.check_branch(expected_ret, then)?;
+                let then_returns = self.check_block(expected_ret, then);
 
                 let mut else_returns = false;
                 if let Some(other) = other {
-                    else_returns = self.check_branch(expected_ret, other)?;
+                    else_returns = self.check_block(expected_ret, other);
                 }
 
                 if then_returns && else_returns {
@@ -334,241 +808,1839 @@ impl<'ast> TypeChecker<'ast> {
                 }
             }
 
+            // The condition is checked again on every iteration, which the checks of `try`
+            // would have to be too
             While { cond, body, .. } => {
-                let cond_t = self.check_expr(cond)?;
-                if cond_t != Type::Bool {
-                    return Err((
-                        span.clone(),
-                        format!("While condition must be boolean but got {}", cond_t),
-                    ));
-                }
+                let outer = self
+                    .no_try
+                    .replace("'try' cannot be used in a loop condition");
+                self.check_cond("While", span, cond);
+                self.no_try = outer;
 
-                if self.check_branch(expected_ret, body)? {
+                self.jumps.loops += 1;
+                let returns = self.check_block(expected_ret, body);
+                self.jumps.loops -= 1;
+
+                if returns {
                     Ok(Some(false))
                 } else {
                     Ok(None)
                 }
             }
 
+            Switch {
+                value,
+                cases,
+                default,
+            } => Ok(self.check_switch(expected_ret, span, value, cases, default.as_ref())),
+
             Defer { body } => {
-                for stmt in body {
-                    if matches!(self.check_stmt(expected_ret, stmt)?, Some(..)) {
-                        return Err((
-                            stmt.span.clone(),
-                            "No return statement is allowed in defer body".to_string(),
-                        ));
-                    }
-                }
+                let outer = std::mem::replace(
+                    &mut self.jumps,
+                    Jumps {
+                        in_defer: true,
+                        ..Default::default()
+                    },
+                );
+                self.check_block(expected_ret, body);
+                self.jumps = outer;
                 Ok(None)
             }
 
-            Destroy { expr } => {
-                let t = self.check_expr(expr);
-                Ok(None)
+            Block { body } => {
+                if self.check_block(expected_ret, body) {
+                    Ok(Some(true))
+                } else {
+                    Ok(None)
+                }
             }
 
-            Free { expr } => {
-                let t = self.check_expr(expr);
-                Ok(None)
-            }
+            Destroy { expr } => match self.check_expr(expr)? {
+                Type::DArray(..) => Ok(None),
+                t => Err((
+                    expr.span.clone(),
+                    match t {
+                        Type::Pointer(..) => format!(
+                            "'destroy' expects a dynamic array but got '{t}', use 'free' for \
+                             pointers"
+                        ),
+                        _ => format!("'destroy' expects a dynamic array but got '{t}'"),
+                    },
+                )),
+            },
+
+            Free { expr } => match self.check_expr(expr)? {
+                Type::Pointer(..) | Type::Str => Ok(None),
+                t => Err((
+                    expr.span.clone(),
+                    match t {
+                        Type::DArray(..) => format!(
+                            "'free' expects a pointer but got '{t}', use 'destroy' for dynamic \
+                             arrays"
+                        ),
+                        _ => format!("'free' expects a pointer but got '{t}'"),
+                    },
+                )),
+            },
         }
     }
 
-    fn check_expr(&self, expr: &'ast LocatedExpr) -> Result<Type, Message> {
+    fn check_expr(&mut self, expr: &'ast LocatedExpr) -> Result<Type, Message> {
+        let t = self.check_expr_kind(expr)?;
+        self.record_type(&expr.span, &t);
+        Ok(t)
+    }
+
+    fn check_expr_kind(&mut self, expr: &'ast LocatedExpr) -> Result<Type, Message> {
         use Expr::*;
 
         let Located { node: e, span } = expr;
 
         match e {
-            Int(..) => Ok(Type::Int32),
+            Int(i) if i32::try_from(*i).is_ok() => Ok(Type::Int32),
+            Int(..) => Ok(Type::Int64),
+            UInt(..) => Ok(Type::UInt64),
             Double(..) => Ok(Type::Double),
             Bool(..) => Ok(Type::Bool),
             Char(..) => Ok(Type::Char),
             Str(..) => Ok(Type::Str),
-            Ident(name) => todo!(),
+            // Generic functions only exist for the types and sizes they are called with
+            Ident(name) if self.is_generic_func(name) => {
+                Err((
+                    span.clone(),
+                    format!("The generic function '{name}' can only be called"),
+                ))
+            }
+            // A state machine only exists as the `_init` and `_step` functions it is lowered to
+            Ident(name) if self.is_statemachine(name) => Err(statemachine_use(span, name)),
+            Ident(name) => match self.types.get(name) {
+                Some(t) => Ok(t.node.clone()),
+                None => {
+                    self.is_cascading |= self.failed_decls.contains(name.as_str());
+                    Err((span.clone(), format!("'{name}' is not declared")))
+                }
+            },
             Binary { left, op, right } => self.check_binary(span, left, op, right),
             Parenthesized { expr } => self.check_expr(expr),
             Unary { op, expr } => self.check_unary(span, op, expr),
             Assign { lvalue, op, value } => self.check_assign(span, lvalue, op, value),
-            Ternary { cond, lexpr, rexpr } => self.check_ternary(span, lexpr, rexpr),
-            FnCall { name, args } => self.check_fn_call(span, name, args),
+            Ternary { cond, lexpr, rexpr } => self.check_ternary(span, cond, lexpr, rexpr),
+            FnCall {
+                name,
+                args,
+                sizes,
+                types,
+                ..
+            } => self.check_fn_call(span, name, args, sizes, types),
             MemAccess { expr, member } => self.check_mem_access(span, expr, member),
             EnumVarAccess { ident, variant } => self.check_enum_var_access(span, ident, variant),
+            ModuleAccess { module, member } => self.check_module_access(span, module, member),
             ArrIndex { arr, idx } => self.check_arr_index(span, arr, idx),
-            Cast { t, expr } => self.check_cast(span, t, expr),
+            Cast { t, expr, check } => self.check_cast(span, t, expr, check),
             Sizeof { t } => self.check_sizeof(span, t),
             InitArr { elems } => self.check_init_arr(span, elems),
             InitArrDesignated { idxs, elems } => self.check_init_arr_designated(span, idxs, elems),
             InitStruct { ident, args } => self.check_init_struct(span, ident, args),
             Make { t } => self.check_make(span, t),
             New { t } => self.check_new(span, t),
+            Tuple { .. } => Err((
+                span.clone(),
+                "Multiple values can only be returned from a function declared to return them"
+                    .to_string(),
+            )),
+            Try { expr, t } => self.check_try(span, expr, t),
         }
     }
 
-    fn check_new(&self, span: &'ast Span, t: &'ast Type) -> Result<Type, Message> {
-        todo!()
-    }
+    /// Checks `expr` where a value of type `expected` is needed, which gives `ok(x)` and `err(e)`
+    /// the `Result` they create
+    fn check_expr_as(&mut self, expected: &Type, expr: &'ast LocatedExpr) -> Result<Type, Message> {
+        if let Expr::FnCall {
+            name, args, result, ..
+        } = &expr.node
+            && let Expr::Ident(func) = &name.node
+            && (func == "ok" || func == "err")
+            && self.types.get(func).is_none()
+            && let Some((ok, err)) = self.result_of(expected)
+        {
+            let value_t = if func == "ok" { ok } else { err };
+            let arity = usize::from(value_t != Type::Void);
+            if args.len() != arity {
+                return Err((
+                    expr.span.clone(),
+                    format!(
+                        "'{func}' expects {arity} argument(s) but got {}",
+                        args.len()
+                    ),
+                ));
+            }
+
+            if let Some(arg) = args.first() {
+                let arg_t = self.check_expr_as(&value_t, arg)?;
+                if !Self::assignable(&value_t, arg, &arg_t) {
+                    return Err((arg.span.clone(), Self::mismatch(&value_t, arg, &arg_t)));
+                }
+            }
+
+            *result.borrow_mut() = Some(expected.clone());
+            self.record_type(&expr.span, expected);
+            return Ok(expected.clone());
+        }
 
-    fn check_make(&self, span: &'ast Span, t: &'ast Type) -> Result<Type, Message> {
-        todo!()
+        self.check_expr(expr)
     }
 
-    fn check_init_struct(
-        &self,
+    /// Checks `try expr`, which returns the error of the `Result` from the function, and
+    /// evaluates to its value otherwise
+    fn check_try(
+        &mut self,
         span: &'ast Span,
-        ident: &'ast String,
-        args: &'ast Vec<(String, LocatedExpr)>,
+        expr: &'ast LocatedExpr,
+        t: &'ast RefCell<Option<Type>>,
     ) -> Result<Type, Message> {
-        todo!()
+        if let Some(reason) = self.no_try {
+            return Err((span.clone(), reason.to_string()));
+        }
+        if self.jumps.in_defer {
+            return Err((
+                span.clone(),
+                "'try' cannot return from a deferred block".to_string(),
+            ));
+        }
+
+        let result_t = self.check_expr(expr)?;
+        let Some((ok, err)) = self.result_of(&result_t) else {
+            return Err((
+                expr.span.clone(),
+                format!("'try' expects a 'Result' but got '{result_t}'"),
+            ));
+        };
+
+        let Some(ret) = self.ret else {
+            return Err((
+                span.clone(),
+                "'try' can only be used in a function returning a 'Result'".to_string(),
+            ));
+        };
+        match self.result_of(ret) {
+            Some((_, ret_err)) if ret_err == err => {}
+            Some((_, ret_err)) => {
+                return Err((
+                    span.clone(),
+                    format!(
+                        "'try' cannot return the error '{err}' from a function whose error type \
+                         is '{ret_err}'"
+                    ),
+                ));
+            }
+            None => {
+                return Err((
+                    span.clone(),
+                    format!(
+                        "'try' can only be used in a function returning a 'Result', not '{ret}'"
+                    ),
+                ));
+            }
+        }
+
+        *t.borrow_mut() = Some(result_t);
+        Ok(ok)
+    }
+
+    /// Returns the value and error types of the `Result` `t` is, looking through aliases
+    fn result_of(&self, t: &Type) -> Option<(Type, Type)> {
+        match t {
+            Type::Result(ok, err) => Some(((**ok).clone(), (**err).clone())),
+            Type::UserDefinedType(name) => match self.user_def_types.get(name.as_str())? {
+                UserDefinedType::Alias { t, .. } => self.result_of(&t.node),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Checks an operand that may not be evaluated, which can't return from the function with
+    /// `try`
+    fn check_conditional(&mut self, expr: &'ast LocatedExpr) -> Result<Type, Message> {
+        let outer = self
+            .no_try
+            .replace("'try' cannot be used in an operand that may not be evaluated");
+        let res = self.check_expr(expr);
+        self.no_try = outer;
+        res
+    }
+
+    fn check_module_access(
+        &mut self,
+        span: &'ast Span,
+        module: &'ast String,
+        member: &'ast String,
+    ) -> Result<Type, Message> {
+        use GlobalStmt::*;
+
+        let Some(stmt) = self
+            .modules
+            .and_then(|modules| modules.get(module))
+            .and_then(|m| m.find(member))
+        else {
+            return Err((
+                span.clone(),
+                format!("'{member}' is not declared in module '{module}'"),
+            ));
+        };
+
+        match &stmt.node {
+            Variable { t: Some(t), .. } => Ok(t.clone()),
+            Constant { t: Some(t), .. } => Ok(t.node.clone()),
+            Variable { t: None, .. } | Constant { t: None, .. } => Err((
+                span.clone(),
+                format!("The type of '{module}::{member}' must be explicit to be used from another module"),
+            )),
+            Function { params, ret, .. } | ExternFunction { params, ret, .. } => {
+                Ok(func_type(params, ret))
+            }
+            _ => Ok(Type::UserDefinedType(member.to_string())),
+        }
+    }
+
+    /// Checks `new T`, which allocates a zeroed `T` and yields a `^T` to it
+    fn check_new(&mut self, span: &'ast Span, t: &'ast Type) -> Result<Type, Message> {
+        match t {
+            Type::Void => Err((span.clone(), "'new' cannot allocate 'void'".to_string())),
+            _ => {
+                self.check_allocated(span, t)?;
+                Ok(Type::Pointer(Box::new(t.clone())))
+            }
+        }
+    }
+
+    /// Checks `make [^]T`, which creates an empty dynamic array
+    fn check_make(&mut self, span: &'ast Span, t: &'ast Type) -> Result<Type, Message> {
+        match t {
+            Type::DArray(elem) if **elem == Type::Void => Err((
+                span.clone(),
+                "A dynamic array cannot hold 'void'".to_string(),
+            )),
+            Type::DArray(elem) => {
+                self.check_allocated(span, elem)?;
+                Ok(t.clone())
+            }
+            _ => Err((
+                span.clone(),
+                format!("'make' creates dynamic arrays, but got '{t}'"),
+            )),
+        }
+    }
+
+    /// Whether `name` refers to a generic function rather than a variable
+    fn is_generic_func(&self, name: &str) -> bool {
+        match self.user_def_types.get(name) {
+            Some(UserDefinedType::Function {
+                type_params: [_, ..],
+                ..
+            }) => true,
+            Some(UserDefinedType::Function {
+                size_params: [_, ..],
+                ..
+            }) => matches!(self.types.get(name), Some(t) if !array_sizes(&t.node).is_empty()),
+            _ => false,
+        }
+    }
+
+    /// Whether `name` refers to a `@statemachine` function rather than a local that shadows it
+    fn is_statemachine(&self, name: &str) -> bool {
+        match self.user_def_types.get(name) {
+            Some(UserDefinedType::Function {
+                span,
+                is_statemachine: true,
+                ..
+            }) => matches!(self.types.get(name), Some(t) if t.span == **span),
+            _ => false,
+        }
+    }
+
+    /// Checks that the type `new` or `make` allocates is declared
+    fn check_allocated(&self, span: &Span, t: &Type) -> Result<(), Message> {
+        self.check_sizes(span, t)?;
+
+        match t {
+            Type::Pointer(t) | Type::Array(_, t) | Type::DArray(t) | Type::GenericArray(_, t) => {
+                self.check_allocated(span, t)
+            }
+            Type::UserDefinedType(name) if !self.user_def_types.contains_key(name.as_str()) => {
+                Err((span.clone(), format!("'{name}' is not declared")))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_init_struct(
+        &mut self,
+        span: &'ast Span,
+        ident: &'ast String,
+        args: &'ast Vec<(String, LocatedExpr)>,
+    ) -> Result<Type, Message> {
+        let t = Type::UserDefinedType(ident.clone());
+        let Some(UserDefinedType::Struct { fields, .. }) = self.user_type_of(&t) else {
+            return Err((span.clone(), format!("'{ident}' is not a struct")));
+        };
+        let fields = *fields;
+
+        let mut initialized = HashSet::new();
+        for (name, value) in args {
+            let Some((_, field_t)) = fields.iter().find(|(field, _)| field == name) else {
+                return Err((
+                    value.span.clone(),
+                    format!("'{ident}' has no field '{name}'"),
+                ));
+            };
+            if !initialized.insert(name) {
+                return Err((
+                    value.span.clone(),
+                    format!("The field '{name}' is initialized more than once"),
+                ));
+            }
+
+            let value_t = self.check_expr_as(&field_t.node, value)?;
+            if !Self::assignable(&field_t.node, value, &value_t) {
+                return Err((
+                    value.span.clone(),
+                    Self::mismatch(&field_t.node, value, &value_t),
+                ));
+            }
+            Self::check_array_init(&field_t.node, value)?;
+        }
+
+        Ok(t)
     }
 
     fn check_init_arr_designated(
-        &self,
+        &mut self,
         span: &'ast Span,
-        idx: &'ast Vec<usize>,
-        elems: &'ast Vec<LocatedExpr>,
+        idxs: &'ast [usize],
+        elems: &'ast [LocatedExpr],
     ) -> Result<Type, Message> {
-        todo!()
+        let Some((first, rest)) = elems.split_first() else {
+            return Err((
+                span.clone(),
+                "The type of an empty array cannot be inferred".to_string(),
+            ));
+        };
+
+        let mut initialized = HashSet::new();
+        for (idx, elem) in idxs.iter().zip(elems) {
+            if !initialized.insert(idx) {
+                return Err((
+                    elem.span.clone(),
+                    format!("The index {idx} is initialized more than once"),
+                ));
+            }
+        }
+
+        // The array is as long as its highest index needs
+        let elem_t = self.check_elems(first, rest)?;
+
+        let len = idxs.iter().max().map_or(0, |i| i + 1);
+        Ok(Type::Array(len, Box::new(elem_t)))
     }
 
     fn check_init_arr(
-        &self,
+        &mut self,
         span: &'ast Span,
-        elems: &'ast Vec<LocatedExpr>,
+        elems: &'ast [LocatedExpr],
     ) -> Result<Type, Message> {
-        todo!()
+        let Some((first, rest)) = elems.split_first() else {
+            return Err((
+                span.clone(),
+                "The type of an empty array cannot be inferred".to_string(),
+            ));
+        };
+
+        let elem_t = self.check_elems(first, rest)?;
+
+        Ok(Type::Array(elems.len(), Box::new(elem_t)))
     }
 
-    fn check_sizeof(&self, span: &'ast Span, t: &'ast Type) -> Result<Type, Message> {
-        todo!()
+    /// Returns the type of the elements of an array literal, which is the type of the first one
+    /// unless the others are numbers that need a wider type
+    fn check_elems(
+        &mut self,
+        first: &'ast LocatedExpr,
+        rest: &'ast [LocatedExpr],
+    ) -> Result<Type, Message> {
+        let first_t = self.check_expr(first)?;
+        let mut widest = (first, first_t.clone());
+        for elem in rest {
+            let t = self.check_expr(elem)?;
+            widest = Self::unify_elem(widest, elem, t.clone()).ok_or_else(|| {
+                (
+                    elem.span.clone(),
+                    format!("Expected '{first_t}' like the first element but got '{t}'"),
+                )
+            })?;
+        }
+
+        Ok(widest.1)
     }
 
+    /// Adds `elem` of type `t` to the elements of an array literal, where `widest` is the element
+    /// that decided their type so far along with that type
+    ///
+    /// Numbers are promoted like the operands of `+`, so `[1, 2.5]` is a `[2]double` and
+    /// `[x, 300]` a `[2]i32` for a `u8` `x`. Returns `None` if the element can't be combined with
+    /// the others.
+    pub(crate) fn unify_elem(
+        widest: (&'ast LocatedExpr, Type),
+        elem: &'ast LocatedExpr,
+        t: Type,
+    ) -> Option<(&'ast LocatedExpr, Type)> {
+        let (widest, widest_t) = widest;
+
+        if Self::assignable(&widest_t, elem, &t) {
+            Some((widest, widest_t))
+        } else if widest_t.is_numeric() && t.is_numeric() {
+            match Self::arith_result(widest, widest_t.clone(), elem, t) {
+                unified if unified == widest_t => Some((widest, unified)),
+                unified => Some((elem, unified)),
+            }
+        } else {
+            None
+        }
+    }
+
+    fn check_sizeof(&mut self, span: &'ast Span, t: &'ast Type) -> Result<Type, Message> {
+        match t {
+            Type::Void => Err((span.clone(), "'void' has no size".to_string())),
+            Type::UserDefinedType(name) if !self.user_def_types.contains_key(name.as_str()) => {
+                Err((span.clone(), format!("'{name}' is not declared")))
+            }
+            _ => Ok(Type::UInt64),
+        }
+    }
+
+    /// Checks `cast(t, expr)` and marks it with how `--checked-casts` checks it if the value may
+    /// not fit into `t`
+    ///
+    /// Numbers, characters, and enums convert to numbers and characters, booleans to integers,
+    /// and pointers to other pointers and 64-bit integers. Casts to and from `char` aren't
+    /// marked since whether C's `char` is signed depends on the compiler.
     fn check_cast(
-        &self,
+        &mut self,
         span: &'ast Span,
         t: &'ast LocatedType,
         expr: &'ast LocatedExpr,
+        check: &'ast Cell<Option<CastCheck>>,
     ) -> Result<Type, Message> {
-        todo!()
+        let from = self.check_expr(expr)?;
+        let to = &t.node;
+
+        if let Type::UserDefinedType(name) = to
+            && !self.user_def_types.contains_key(name.as_str())
+        {
+            return Err((t.span.clone(), format!("'{name}' is not declared")));
+        }
+
+        let is_enum = |t: &Type| match t {
+            Type::UserDefinedType(name) => matches!(
+                self.user_def_types.get(name.as_str()),
+                Some(UserDefinedType::Enum { .. })
+            ),
+            _ => false,
+        };
+        let is_pointer = |t: &Type| matches!(t, Type::Pointer(_) | Type::Str);
+        let is_word = |t: &Type| matches!(t, Type::Int64 | Type::UInt64);
+
+        let allowed = from == *to
+            || ((from.is_numeric() || is_enum(&from) || matches!(from, Type::Char | Type::Bool))
+                && (to.is_numeric() || *to == Type::Char))
+            || (is_pointer(&from) && (is_pointer(to) || is_word(to)))
+            || (is_word(&from) && is_pointer(to));
+        if !allowed {
+            let hint = match to {
+                Type::Bool if from.is_numeric() => ", compare it with 0 instead",
+                _ => "",
+            };
+            return Err((span.clone(), format!("Cannot cast '{from}' to '{to}'{hint}")));
+        }
+
+        let Some((min, max)) = to.int_range() else {
+            return Ok(to.clone());
+        };
+
+        // Values known at compile time are checked now, which also keeps casts in the
+        // initializers of globals constant in C
+        let fits = match self.consts.eval(expr)? {
+            Some(Value::Int(c)) => Some((min <= c && c <= max, c.to_string())),
+            Some(Value::Double(d)) => Some(((min - 1) as f64) < d && d < ((max + 1) as f64))
+                .map(|fits| (fits, d.to_string())),
+            _ => None,
+        };
+        if let Some((fits, value)) = fits {
+            if !fits {
+                self.warnings.push((
+                    span.clone(),
+                    format!("{value} doesn't fit into '{to}', so the cast changes it"),
+                ));
+            }
+            return Ok(to.clone());
+        }
+
+        check.set(match from.int_range() {
+            Some((from_min, from_max)) if min <= from_min && from_max <= max => None,
+            Some(_) if from.is_unsigned() => Some(CastCheck::Unsigned),
+            Some(_) => Some(CastCheck::Signed),
+            None if matches!(from, Type::Double | Type::Float) => Some(CastCheck::Float),
+            None => None,
+        });
+
+        Ok(to.clone())
     }
 
     fn check_arr_index(
-        &self,
+        &mut self,
         span: &'ast Span,
-        args: &'ast LocatedExpr,
+        arr: &'ast LocatedExpr,
         idx: &'ast LocatedExpr,
     ) -> Result<Type, Message> {
-        todo!()
+        let arr_t = self.check_expr(arr)?;
+        let idx_t = self.check_expr(idx)?;
+
+        if !idx_t.is_integer() {
+            return Err((
+                idx.span.clone(),
+                format!("Expected an integer index but got '{idx_t}'"),
+            ));
+        }
+
+        // Only fixed arrays have a length known at compile time, and only pointers may point into
+        // the middle of something, e.g. `p[-1]`
+        if let Some(Value::Int(i)) = self.consts.eval(idx)? {
+            match &arr_t {
+                Type::Array(len, _) if i < 0 || i >= *len as i128 => self.warnings.push((
+                    idx.span.clone(),
+                    format!("Index {i} is out of bounds for an array of length {len}"),
+                )),
+                Type::DArray(_) | Type::Str if i < 0 => self.warnings.push((
+                    idx.span.clone(),
+                    format!("Index {i} is always out of bounds for '{arr_t}'"),
+                )),
+                _ => {}
+            }
+        }
+
+        match arr_t {
+            Type::Array(_, elem)
+            | Type::GenericArray(_, elem)
+            | Type::DArray(elem)
+            | Type::Pointer(elem) => Ok(*elem),
+            Type::Str => Ok(Type::Char),
+            t => Err((span.clone(), format!("Cannot index into '{t}'"))),
+        }
     }
 
     fn check_enum_var_access(
-        &self,
+        &mut self,
         span: &'ast Span,
         ident: &'ast String,
         variant: &'ast String,
     ) -> Result<Type, Message> {
-        todo!()
+        match self.user_def_types.get(ident.as_str()) {
+            Some(UserDefinedType::Enum { variants, .. }) => {
+                if variants.iter().any(|(name, _)| name == variant) {
+                    Ok(Type::UserDefinedType(ident.clone()))
+                } else {
+                    Err((
+                        span.clone(),
+                        format!("The enum '{ident}' has no variant '{variant}'"),
+                    ))
+                }
+            }
+            _ => Err((span.clone(), format!("'{ident}' is not an enum"))),
+        }
     }
 
     fn check_mem_access(
-        &self,
+        &mut self,
         span: &'ast Span,
         expr: &'ast LocatedExpr,
         member: &'ast String,
     ) -> Result<Type, Message> {
-        todo!()
+        let t = self.check_expr(expr)?;
+
+        if let Some((ok, err)) = self.result_of(&t) {
+            return results::fields(&ok, &err)
+                .into_iter()
+                .find(|(name, _)| name == member)
+                .map(|(_, field_t)| field_t)
+                .ok_or_else(|| (span.clone(), format!("'{t}' has no field '{member}'")));
+        }
+
+        let fields = match self.user_type_of(&t) {
+            Some(UserDefinedType::Struct { fields, .. } | UserDefinedType::Union { fields, .. }) => {
+                fields
+            }
+            _ => {
+                return Err((
+                    span.clone(),
+                    format!("Cannot access '{member}' on '{t}', which is not a struct or union"),
+                ));
+            }
+        };
+
+        fields
+            .iter()
+            .find(|(name, _)| name == member)
+            .map(|(_, field_t)| field_t.node.clone())
+            .ok_or_else(|| (span.clone(), format!("'{t}' has no field '{member}'")))
     }
 
-    fn check_fn_call(
+    /// Returns the variables the pattern declares from a value of type `t`, with their types
+    fn check_pattern(
         &self,
+        span: &Span,
+        pattern: &'ast Pattern,
+        t: &Type,
+    ) -> Result<Vec<(&'ast str, Type)>, Message> {
+        match pattern {
+            Pattern::Struct(names) => {
+                let fields = match self.user_type_of(t) {
+                    Some(UserDefinedType::Struct { fields, .. }) => *fields,
+                    _ => {
+                        return Err((
+                            span.clone(),
+                            format!("Cannot destructure '{t}' with '{{ }}', which is not a struct"),
+                        ));
+                    }
+                };
+
+                names
+                    .iter()
+                    .map(|name| {
+                        fields
+                            .iter()
+                            .find(|(field, _)| field == name)
+                            .map(|(_, field_t)| (name.as_str(), field_t.node.clone()))
+                            .ok_or_else(|| (span.clone(), format!("'{t}' has no field '{name}'")))
+                    })
+                    .collect()
+            }
+            Pattern::Tuple(names) => match t {
+                Type::Tuple(elems) if names.len() == elems.len() => Ok(names
+                    .iter()
+                    .map(String::as_str)
+                    .zip(elems.iter().cloned())
+                    .collect()),
+                Type::Tuple(elems) => Err((
+                    span.clone(),
+                    format!(
+                        "Cannot destructure the {} values of '{t}' into {} variables",
+                        elems.len(),
+                        names.len()
+                    ),
+                )),
+                _ => Err((
+                    span.clone(),
+                    format!(
+                        "Cannot destructure '{t}' with '( )', which is only for multiple return \
+                         values"
+                    ),
+                )),
+            },
+            Pattern::Array(names) => match t {
+                Type::Array(len, elem) if names.len() <= *len => Ok(names
+                    .iter()
+                    .map(|name| (name.as_str(), (**elem).clone()))
+                    .collect()),
+                Type::Array(len, _) => Err((
+                    span.clone(),
+                    format!(
+                        "Cannot destructure {} elements from '{t}', which has only {len}",
+                        names.len()
+                    ),
+                )),
+                Type::DArray(_) => Err((
+                    span.clone(),
+                    format!("Cannot destructure '{t}', whose length is only known at run time"),
+                )),
+                _ => Err((
+                    span.clone(),
+                    format!("Cannot destructure '{t}' with '[ ]', which is not an array"),
+                )),
+            },
+        }
+    }
+
+    /// Returns the user-defined type `t` refers to, looking through aliases
+    fn user_type_of(&self, t: &Type) -> Option<&UserDefinedType<'ast>> {
+        let Type::UserDefinedType(name) = t else {
+            return None;
+        };
+
+        match self.user_def_types.get(name.as_str())? {
+            UserDefinedType::Alias { t, .. } => self.user_type_of(&t.node),
+            user_type => Some(user_type),
+        }
+    }
+
+    fn check_fn_call(
+        &mut self,
         span: &'ast Span,
         name: &'ast LocatedExpr,
         args: &'ast Vec<LocatedExpr>,
+        sizes: &'ast RefCell<Vec<Size>>,
+        types: &'ast RefCell<Vec<Type>>,
     ) -> Result<Type, Message> {
-        todo!()
+        if let Expr::Ident(func) = &name.node
+            && self.types.get(func).is_none()
+            && BUILTIN_FUNCS.contains(&func.as_str())
+        {
+            return self.check_builtin_call(span, func, args);
+        }
+        if let Expr::Ident(func) = &name.node
+            && self.is_statemachine(func)
+        {
+            return Err(statemachine_use(&name.span, func));
+        }
+
+        // Functions called by name are looked up directly, and anything else, like a struct field
+        // (`h.on_read(buf, n)`), is called through its function type
+        let (type_params, size_params, mut params, mut ret): (&[_], &[_], Vec<_>, _) =
+            match self.signature(name) {
+                Some((type_params, size_params, params, ret)) => (
+                    type_params,
+                    size_params,
+                    params.iter().map(|(_, t)| t.node.clone()).collect(),
+                    ret.node.clone(),
+                ),
+                None => match self.check_expr(name)? {
+                    Type::Func(params, ret) => (&[], &[], params, *ret),
+                    t => return Err((name.span.clone(), format!("'{t}' is not a function"))),
+                },
+            };
+
+        if params.len() != args.len() {
+            return Err((
+                span.clone(),
+                format!(
+                    "Expected {} argument(s) but got {}",
+                    params.len(),
+                    args.len()
+                ),
+            ));
+        }
+
+        // The parameter types of a generic function aren't known before its arguments are
+        let is_generic = !type_params.is_empty() || !size_params.is_empty();
+        let arg_ts = args
+            .iter()
+            .zip(&params)
+            .map(|(arg, param_t)| match is_generic {
+                true => self.check_expr(arg),
+                false => self.check_expr_as(param_t, arg),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // A function with type parameters takes them from the types of its arguments, and is
+        // instantiated for them, after which the instance is checked like any other function
+        if !type_params.is_empty() {
+            let mut inferred = HashMap::new();
+            for (param_t, arg_t) in params.iter().zip(&arg_ts) {
+                infer_types(param_t, arg_t, type_params, &mut inferred);
+            }
+
+            let mut instance = vec![];
+            for param in type_params {
+                let Some(t) = inferred.get(&param.node) else {
+                    return Err((
+                        span.clone(),
+                        format!(
+                            "The type parameter '{}' cannot be inferred from the arguments",
+                            param.node
+                        ),
+                    ));
+                };
+                instance.push(t.clone());
+            }
+
+            params = params.iter().map(|t| t.with_types(&inferred)).collect();
+            ret = ret.with_types(&inferred);
+            *types.borrow_mut() = instance;
+        }
+
+        // A generic function takes the lengths of the arrays it is called with as its size
+        // parameters, and is monomorphized for them
+        if !size_params.is_empty() {
+            let mut inferred = HashMap::new();
+            for ((param_t, arg), arg_t) in params.iter().zip(args).zip(&arg_ts) {
+                infer_sizes(param_t, arg_t, &mut inferred).map_err(|(size, a, b)| {
+                    (
+                        arg.span.clone(),
+                        format!("The size parameter '{size}' cannot be both {a} and {b}"),
+                    )
+                })?;
+            }
+
+            params = params.iter().map(|t| t.with_sizes(&inferred)).collect();
+            ret = ret.with_sizes(&inferred);
+            *sizes.borrow_mut() = size_params
+                .iter()
+                .map(|(size, _)| {
+                    inferred
+                        .get(size)
+                        .cloned()
+                        .unwrap_or_else(|| Size::Param(size.clone()))
+                })
+                .collect();
+        }
+
+        for ((param_t, arg), arg_t) in params.iter().zip(args).zip(&arg_ts) {
+            if !Self::assignable(param_t, arg, arg_t) {
+                return Err((arg.span.clone(), Self::mismatch(param_t, arg, arg_t)));
+            }
+        }
+
+        Ok(ret)
     }
 
+    /// Checks a call to one of `BUILTIN_FUNCS`:
+    ///
+    /// - `append(arr: ^[^]T, x: T): void`
+    /// - `approx_eq(a: double, b: double, eps: double): bool`, whether `a` and `b` differ by at most `eps`
+    /// - `int_to_str(x: integer): str`, `x` in decimal in a string on the heap
+    /// - `len(arr: [^]T): u64`
+    /// - `len(s: str): u64`
+    /// - `parse_i32(s: str): Result<i32, str>`, with why `s` isn't an `i32` as the error
+    fn check_builtin_call(
+        &mut self,
+        span: &'ast Span,
+        func: &str,
+        args: &'ast Vec<LocatedExpr>,
+    ) -> Result<Type, Message> {
+        if func == "ok" || func == "err" {
+            return Err((
+                span.clone(),
+                format!(
+                    "'{func}' needs to know the 'Result' it creates, use it where one is \
+                     expected, like 'let r: Result<i32, Error> = {func}(x);'"
+                ),
+            ));
+        }
+
+        let arity = match func {
+            "append" => 2,
+            "approx_eq" => 3,
+            _ => 1,
+        };
+        if args.len() != arity {
+            return Err((
+                span.clone(),
+                format!("'{func}' expects {arity} argument(s) but got {}", args.len()),
+            ));
+        }
+
+        if func == "approx_eq" {
+            for arg in args {
+                let t = self.check_expr(arg)?;
+                if !t.is_numeric() {
+                    return Err((
+                        arg.span.clone(),
+                        format!("'approx_eq' expects numbers but got '{t}'"),
+                    ));
+                }
+            }
+            return Ok(Type::Bool);
+        }
+
+        if func == "int_to_str" {
+            let t = self.check_expr(&args[0])?;
+            if !t.is_integer() {
+                return Err((
+                    args[0].span.clone(),
+                    format!("'int_to_str' expects an integer but got '{t}'"),
+                ));
+            }
+            return Ok(Type::Str);
+        }
+
+        if func == "parse_i32" {
+            let t = self.check_expr(&args[0])?;
+            if t != Type::Str {
+                return Err((
+                    args[0].span.clone(),
+                    format!("'parse_i32' expects a string but got '{t}'"),
+                ));
+            }
+            return Ok(conversions::parse_i32_type());
+        }
+
+        let arr_t = self.check_expr(&args[0])?;
+
+        match (func, &arr_t) {
+            ("append", Type::Pointer(inner)) if matches!(**inner, Type::DArray(..)) => {
+                let Type::DArray(elem) = &**inner else {
+                    unreachable!()
+                };
+
+                let x_t = self.check_expr(&args[1])?;
+                if !Self::assignable(elem, &args[1], &x_t) {
+                    return Err((
+                        args[1].span.clone(),
+                        format!("Cannot append '{x_t}' to '{inner}'"),
+                    ));
+                }
+
+                Ok(Type::Void)
+            }
+            ("append", t) => Err((
+                args[0].span.clone(),
+                format!("'append' expects a pointer to a dynamic array but got '{t}'"),
+            )),
+            (_, Type::DArray(..) | Type::Str) => Ok(Type::UInt64),
+            (_, t) => Err((
+                args[0].span.clone(),
+                format!("'len' expects a dynamic array or a string but got '{t}'"),
+            )),
+        }
+    }
+
+    /// Returns the type parameters, size parameters, parameters, and return type of the function
+    /// `name` refers to
+    fn signature(&self, name: &'ast LocatedExpr) -> Option<Signature<'ast>> {
+        match &name.node {
+            Expr::Ident(func) => match self.user_def_types.get(func.as_str()) {
+                Some(UserDefinedType::Function {
+                    type_params,
+                    size_params,
+                    params,
+                    ret,
+                    ..
+                }) => Some((*type_params, *size_params, *params, *ret)),
+                _ => None,
+            },
+            Expr::ModuleAccess { module, member } => {
+                let stmt = self.modules?.get(module)?.find(member)?;
+                match &stmt.node {
+                    GlobalStmt::Function {
+                        type_params,
+                        size_params,
+                        params,
+                        ret,
+                        ..
+                    } => Some((type_params, size_params, params, ret)),
+                    GlobalStmt::ExternFunction { params, ret, .. } => {
+                        Some((&[], &[], params, ret))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether `expr` of type `given` can be used where `expected` is needed, which also allows
+    /// integer literals that fit into `expected`, and any pointer where `^void` is needed
+    fn assignable(expected: &Type, expr: &LocatedExpr, given: &Type) -> bool {
+        if expected == given {
+            return true;
+        }
+
+        if let (Type::Pointer(to), Type::Pointer(_)) = (expected, given)
+            && **to == Type::Void
+        {
+            return true;
+        }
+
+        // Each element of an array literal only has to fit the expected element type, so that
+        // `[1, 2]` can initialize a `[2]u8`
+        if let (Type::Array(n, elem_t), Expr::InitArr { elems }, Type::Array(m, given_t)) =
+            (expected, &expr.node, given)
+        {
+            return n == m && elems.iter().all(|e| Self::assignable(elem_t, e, given_t));
+        }
+
+        match (expected.int_range(), lints::const_int(expr)) {
+            (Some((min, max)), Some(c)) => min <= c && c <= max,
+            _ => false,
+        }
+    }
+
+    /// Checks that an array of type `t` is initialized with an array literal, since C can't
+    /// initialize an array from another one
+    fn check_array_init(t: &Type, value: &LocatedExpr) -> Result<(), Message> {
+        let elem_t = match t {
+            Type::Array(_, elem_t) | Type::GenericArray(_, elem_t) | Type::ExprArray(_, elem_t) => {
+                elem_t
+            }
+            _ => return Ok(()),
+        };
+
+        match &value.node {
+            Expr::InitArr { elems } | Expr::InitArrDesignated { elems, .. } => elems
+                .iter()
+                .try_for_each(|elem| Self::check_array_init(elem_t, elem)),
+            _ => Err((
+                value.span.clone(),
+                format!(
+                    "Cannot copy the array '{t}', copy its elements one by one or with 'mem::copy'"
+                ),
+            )),
+        }
+    }
+
+    /// The error for `expr` of type `given` where `expected` is needed, which points out
+    /// single-character string literals used as characters and the other way around
+    fn mismatch(expected: &Type, expr: &LocatedExpr, given: &Type) -> String {
+        let hint = match (expected, &expr.node) {
+            (Type::Char, Expr::Str(s)) if s.len() == 1 => {
+                format!(", use '{}' for a character literal", s.escape_default())
+            }
+            (Type::Str, Expr::Char(c)) => {
+                format!(", use \"{}\" for a string literal", c.escape_ascii())
+            }
+            _ => String::new(),
+        };
+
+        format!("Expected '{expected}' but got '{given}'{hint}")
+    }
+
+    /// Checks `cond ? lexpr : rexpr`, whose branches must have the same type, or numeric types
+    /// that are promoted like the operands of `+`
     fn check_ternary(
-        &self,
+        &mut self,
         span: &'ast Span,
+        cond: &'ast LocatedExpr,
         lexpr: &'ast LocatedExpr,
         rexpr: &'ast LocatedExpr,
     ) -> Result<Type, Message> {
-        todo!()
+        let cond_t = self.check_expr(cond)?;
+        if cond_t != Type::Bool {
+            return Err((cond.span.clone(), Self::mismatch(&Type::Bool, cond, &cond_t)));
+        }
+
+        let left_t = self.check_conditional(lexpr)?;
+        let right_t = self.check_conditional(rexpr)?;
+
+        if left_t.is_numeric() && right_t.is_numeric() {
+            Ok(Self::arith_result(lexpr, left_t, rexpr, right_t))
+        } else if Self::assignable(&left_t, rexpr, &right_t) {
+            Ok(left_t)
+        } else {
+            Err((
+                span.clone(),
+                format!("The branches have different types, '{left_t}' and '{right_t}'"),
+            ))
+        }
     }
 
     fn check_binary(
-        &self,
+        &mut self,
         span: &'ast Span,
         left: &'ast LocatedExpr,
         op: &'ast BinOp,
         right: &'ast LocatedExpr,
     ) -> Result<Type, Message> {
-        todo!()
+        use BinOp::*;
+
+        let left_t = self.check_expr(left)?;
+        let right_t = match op {
+            And | Or => self.check_conditional(right)?,
+            _ => self.check_expr(right)?,
+        };
+
+        match op {
+            Eq | NEq | LT | GT | LTE | GTE => {
+                if let (Type::Pointer(l), Type::Pointer(r)) = (&left_t, &right_t)
+                    && l != r
+                {
+                    return Err((
+                        span.clone(),
+                        format!(
+                            "Cannot compare '{left_t}' with '{right_t}', pointers can only be \
+                             compared if they point to the same type"
+                        ),
+                    ));
+                }
+
+                if left_t != right_t && !(left_t.is_numeric() && right_t.is_numeric()) {
+                    return Err((
+                        span.clone(),
+                        format!("Cannot compare '{left_t}' with '{right_t}'"),
+                    ));
+                }
+
+                if let Some(w) = lints::check_comparison((&left_t, left), op, (&right_t, right)) {
+                    self.warnings.push((span.clone(), w));
+                }
+                if let Some(w) = lints::check_float_equality(&left_t, op, &right_t) {
+                    self.warnings.push((span.clone(), w));
+                }
+                if let Some(w) =
+                    lints::check_remainder_comparison((&left_t, left), op, (&right_t, right))
+                {
+                    self.warnings.push((span.clone(), w));
+                }
+
+                Ok(Type::Bool)
+            }
+
+            And | Or => {
+                if left_t == Type::Bool && right_t == Type::Bool {
+                    Ok(Type::Bool)
+                } else {
+                    Err((
+                        span.clone(),
+                        format!(
+                            "Expected 'bool' operands for '{op}' but got '{left_t}' and '{right_t}'"
+                        ),
+                    ))
+                }
+            }
+
+            Add | Sub
+                if matches!(left_t, Type::Pointer(..)) || matches!(right_t, Type::Pointer(..)) =>
+            {
+                Self::check_pointer_arith(span, op, left_t, right_t)
+            }
+
+            Add | Sub | Mul | Div => {
+                if left_t.is_numeric() && right_t.is_numeric() {
+                    let t = Self::arith_result(left, left_t, right, right_t);
+                    if matches!(op, Div) {
+                        self.check_divisor(op, right, &t)?;
+                    }
+                    Ok(t)
+                } else {
+                    Err((
+                        span.clone(),
+                        format!("Cannot apply '{op}' to '{left_t}' and '{right_t}'"),
+                    ))
+                }
+            }
+
+            Mod | BitAnd | BitOr | XOr | LShift | RShift => {
+                if !left_t.is_integer() || !right_t.is_integer() {
+                    // `x & 1 == 0` is `x & (1 == 0)` like in C
+                    let is_comparison = |e: &LocatedExpr| {
+                        matches!(
+                            e.node,
+                            Expr::Binary {
+                                op: Eq | NEq | LT | GT | LTE | GTE,
+                                ..
+                            }
+                        )
+                    };
+                    let hint = if matches!(op, BitAnd | BitOr | XOr)
+                        && (is_comparison(left) || is_comparison(right))
+                    {
+                        format!(
+                            ", '{op}' binds looser than comparisons like in C, so it needs \
+                             parentheses"
+                        )
+                    } else {
+                        String::new()
+                    };
+                    Err((
+                        span.clone(),
+                        format!(
+                            "Expected integer operands for '{op}' but got '{left_t}' and \
+                             '{right_t}'{hint}"
+                        ),
+                    ))
+                } else if matches!(op, LShift | RShift) {
+                    Ok(left_t)
+                } else {
+                    let t = Self::arith_result(left, left_t, right, right_t);
+                    if matches!(op, Mod) {
+                        self.check_divisor(op, right, &t)?;
+                        if let Some(w) = lints::check_negative_divisor(&t, right) {
+                            self.warnings.push((span.clone(), w));
+                        }
+                    }
+                    Ok(t)
+                }
+            }
+        }
+    }
+
+    /// Reports integer `/` and `%` by a divisor that is always zero, which is undefined behavior
+    ///
+    /// Like in C, integer division truncates toward zero and the remainder takes the sign of the
+    /// left operand, so `-7 / 2` is `-3` and `-7 % 2` is `-1`. Floating-point division by zero
+    /// is allowed, since it gives an infinity or NaN.
+    fn check_divisor(
+        &mut self,
+        op: &BinOp,
+        right: &'ast LocatedExpr,
+        t: &Type,
+    ) -> Result<(), Message> {
+        if !t.is_integer() || self.may_be_local(right) {
+            return Ok(());
+        }
+
+        match self.consts.eval(right) {
+            Ok(Some(Value::Int(0))) => Err((
+                right.span.clone(),
+                format!("Division by zero, the right operand of '{op}' is always 0"),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether `expr` may refer to a parameter or local variable, which the constant evaluator
+    /// would mistake for a global constant of the same name
+    fn may_be_local(&self, expr: &LocatedExpr) -> bool {
+        match &expr.node {
+            Expr::Int(_) | Expr::UInt(_) | Expr::Double(_) | Expr::Bool(_) | Expr::Char(_) => false,
+            Expr::Ident(name) => self.types.is_local(name),
+            Expr::Parenthesized { expr } | Expr::Unary { expr, .. } | Expr::Cast { expr, .. } => {
+                self.may_be_local(expr)
+            }
+            Expr::Binary { left, right, .. } => self.may_be_local(left) || self.may_be_local(right),
+            _ => true,
+        }
+    }
+
+    /// The type of `+` or `-` with a pointer operand, which is either the pointer moved by an
+    /// integer number of elements (`p + n`, `n + p`, `p - n`), or the signed number of elements
+    /// between two pointers to the same type (`p - q`)
+    fn check_pointer_arith(
+        span: &'ast Span,
+        op: &'ast BinOp,
+        left_t: Type,
+        right_t: Type,
+    ) -> Result<Type, Message> {
+        use Type::*;
+
+        for t in [&left_t, &right_t] {
+            let Pointer(inner) = t else {
+                continue;
+            };
+
+            let reason = match **inner {
+                Void => " because 'void' has no size",
+                // `^[N]T` points to a single array, so stepping over whole arrays would leave it
+                Array(..) => ", index into the array it points to instead",
+                _ => continue,
+            };
+
+            return Err((
+                span.clone(),
+                format!("Cannot do pointer arithmetic on '{t}'{reason}"),
+            ));
+        }
+
+        match (op, &left_t, &right_t) {
+            (_, Pointer(_), r) if r.is_integer() => Ok(left_t),
+            (BinOp::Add, l, Pointer(_)) if l.is_integer() => Ok(right_t),
+            (BinOp::Sub, Pointer(l), Pointer(r)) if l == r => Ok(Int64),
+            (BinOp::Sub, Pointer(_), Pointer(_)) => Err((
+                span.clone(),
+                format!(
+                    "Cannot subtract '{right_t}' from '{left_t}', pointers can only be \
+                     subtracted if they point to the same type"
+                ),
+            )),
+            _ => Err((
+                span.clone(),
+                format!("Cannot apply '{op}' to '{left_t}' and '{right_t}'"),
+            )),
+        }
+    }
+
+    /// The type of an arithmetic expression, where integer literals take the type of the other
+    /// operand if they fit in it
+    pub(crate) fn arith_result(
+        left: &'ast LocatedExpr,
+        left_t: Type,
+        right: &'ast LocatedExpr,
+        right_t: Type,
+    ) -> Type {
+        use Type::*;
+
+        let fits = |c: Option<i128>, t: &Type| {
+            c.zip(t.int_range())
+                .is_some_and(|(c, (min, max))| min <= c && c <= max)
+        };
+
+        if left_t == right_t {
+            left_t
+        } else if matches!(left_t, Double) || matches!(right_t, Double) {
+            Double
+        } else if matches!(left_t, Float) || matches!(right_t, Float) {
+            Float
+        } else if fits(lints::const_int(right), &left_t) {
+            left_t
+        } else if fits(lints::const_int(left), &right_t) {
+            right_t
+        } else {
+            // Like C, the wider type wins, and unsigned wins between types of the same width
+            let width = |t: &Type| t.int_range().map_or(0, |(min, max)| max - min);
+            match width(&left_t).cmp(&width(&right_t)) {
+                std::cmp::Ordering::Less => right_t,
+                std::cmp::Ordering::Greater => left_t,
+                std::cmp::Ordering::Equal if left_t.is_unsigned() => left_t,
+                std::cmp::Ordering::Equal => right_t,
+            }
+        }
     }
 
     fn check_unary(
-        &self,
+        &mut self,
         span: &'ast Span,
         op: &'ast UnaryOp,
         expr: &'ast LocatedExpr,
     ) -> Result<Type, Message> {
-        todo!()
+        use UnaryOp::*;
+
+        if matches!(op, Inc | Dec) {
+            self.check_mutable(expr)?;
+        }
+
+        if let (AddrOf, Some(name)) = (op, self.array_param_root(expr)) {
+            let t = &self.types.get(name).unwrap().node;
+            let msg = match &expr.node {
+                Expr::Ident(_) => format!(
+                    "Cannot take the address of the array parameter '{name}', which is already \
+                     passed by reference; declare it as '^{t}' to modify it"
+                ),
+                _ => format!(
+                    "Cannot take the address of part of the read-only array parameter '{name}'; \
+                     declare it as '^{t}' to modify it"
+                ),
+            };
+            return Err((span.clone(), msg));
+        }
+
+        let t = self.check_expr(expr)?;
+
+        match op {
+            Neg if t.is_numeric() => Ok(t),
+            LogicNeg if t == Type::Bool => Ok(t),
+            BitNot if t.is_integer() => Ok(t),
+            Inc | Dec if t.is_integer() || matches!(t, Type::Pointer(..)) => Ok(t),
+            AddrOf => Ok(Type::Pointer(Box::new(t))),
+            Deref => match t {
+                Type::Pointer(inner) => Ok(*inner),
+                _ => Err((span.clone(), format!("Cannot dereference '{t}'"))),
+            },
+            _ => Err((span.clone(), format!("Cannot apply '{op}' to '{t}'"))),
+        }
+    }
+
+    /// Returns the read-only `[N]T` parameter that `expr` is, or is an element or field of
+    fn array_param_root(&self, expr: &'ast LocatedExpr) -> Option<&'ast str> {
+        match &expr.node {
+            Expr::ArrIndex { arr: expr, .. }
+            | Expr::MemAccess { expr, .. }
+            | Expr::Parenthesized { expr } => self.array_param_root(expr),
+            Expr::Ident(name)
+                if self.array_params.contains(name.as_str()) && self.types.is_param(name) =>
+            {
+                Some(name)
+            }
+            _ => None,
+        }
     }
 
     fn check_assign(
-        &self,
+        &mut self,
         span: &'ast Span,
         lvalue: &'ast LocatedExpr,
         op: &'ast AssignOp,
         value: &'ast LocatedExpr,
     ) -> Result<Type, Message> {
-        todo!()
+        self.check_mutable(lvalue)?;
+
+        let lvalue_t = self.check_expr(lvalue)?;
+        if let (AssignOp::Assign, Type::Array(..) | Type::GenericArray(..) | Type::ExprArray(..)) =
+            (op, &lvalue_t)
+        {
+            return Err((
+                span.clone(),
+                format!(
+                    "Cannot assign to the array '{lvalue_t}', assign its elements one by one or \
+                     copy them with 'mem::copy'"
+                ),
+            ));
+        }
+        let value_t = match op {
+            AssignOp::Assign => self.check_expr_as(&lvalue_t, value)?,
+            _ => self.check_expr(value)?,
+        };
+
+        let ok = match op {
+            AssignOp::Assign => Self::assignable(&lvalue_t, value, &value_t),
+            AssignOp::AddAssign | AssignOp::SubAssign
+                if matches!(lvalue_t, Type::Pointer(..)) =>
+            {
+                value_t.is_integer()
+            }
+            AssignOp::AddAssign
+            | AssignOp::SubAssign
+            | AssignOp::MulAssign
+            | AssignOp::DivAssign => {
+                lvalue_t.is_numeric() && Self::assignable(&lvalue_t, value, &value_t)
+            }
+            _ => lvalue_t.is_integer() && value_t.is_integer(),
+        };
+
+        if ok {
+            Ok(lvalue_t)
+        } else {
+            Err((
+                span.clone(),
+                format!("Cannot apply '{op}' to '{lvalue_t}' and '{value_t}'"),
+            ))
+        }
+    }
+
+    /// Checks that `lvalue` is a variable, field, element, or dereferenced pointer, and that it
+    /// doesn't modify a constant or a `[N]T` parameter, since those are passed by reference and
+    /// only `^[N]T` parameters may modify the caller's array
+    fn check_mutable(&self, lvalue: &'ast LocatedExpr) -> Result<(), Message> {
+        let mut root = lvalue;
+        let mut indexed = false;
+        loop {
+            match &root.node {
+                Expr::ArrIndex { arr: expr, .. } => {
+                    indexed = true;
+                    root = expr;
+                }
+                Expr::MemAccess { expr, .. } | Expr::Parenthesized { expr } => root = expr,
+                Expr::Ident(name)
+                    if self.array_params.contains(name.as_str()) && self.types.is_param(name) =>
+                {
+                    let t = &self.types.get(name).unwrap().node;
+                    return Err((
+                        lvalue.span.clone(),
+                        format!(
+                            "'{name}' is a read-only array parameter, declare it as '^{t}' to \
+                             modify it"
+                        ),
+                    ));
+                }
+                // Only the pointer of a constant pointer or dynamic array is constant, not the
+                // elements it points to
+                Expr::Ident(name) if self.types.is_const(name) => {
+                    let t = &self.types.get(name).unwrap().node;
+                    if indexed && !matches!(t, Type::Array(..) | Type::Str) {
+                        return Ok(());
+                    }
+                    return Err((
+                        lvalue.span.clone(),
+                        format!("'{name}' is a constant and cannot be modified"),
+                    ));
+                }
+                Expr::Ident(_)
+                | Expr::ModuleAccess { .. }
+                | Expr::Unary {
+                    op: UnaryOp::Deref,
+                    ..
+                } => return Ok(()),
+                // Indexing a pointer returned from a call, like `buf()[0] = 1`, is fine
+                _ if indexed => return Ok(()),
+                _ => {
+                    return Err((
+                        lvalue.span.clone(),
+                        "Expected a variable, field, element, or dereferenced pointer to modify"
+                            .to_string(),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Checks a switch and the bodies of its cases, which are checked even if the cases are wrong
+    fn check_switch(
+        &mut self,
+        expected_ret: &'ast LocatedType,
+        span: &'ast Span,
+        value: &'ast LocatedExpr,
+        cases: &'ast Vec<SwitchCase>,
+        default: Option<&'ast Vec<LocatedStmt>>,
+    ) -> Option<bool> {
+        let is_enum = match self.check_cases(span, value, cases, default) {
+            Ok(is_enum) => is_enum,
+            Err(err) => {
+                self.record(err);
+                false
+            }
+        };
+
+        // A switch on an enum handles every variant, or there is an error
+        let mut all_return = default.is_some() || is_enum;
+        let mut any_returns = false;
+        for body in cases.iter().map(|case| &case.body).chain(default) {
+            self.jumps.cases += 1;
+            let returns = self.check_block(expected_ret, body);
+            self.jumps.cases -= 1;
+            all_return &= returns;
+            any_returns |= returns;
+        }
+
+        if all_return {
+            Some(true)
+        } else if any_returns {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// Checks that the cases are distinct constants of the switched type, and that a switch on an
+    /// enum without a default case handles every variant, returning whether it is on an enum
+    fn check_cases(
+        &mut self,
+        span: &'ast Span,
+        value: &'ast LocatedExpr,
+        cases: &'ast Vec<SwitchCase>,
+        default: Option<&'ast Vec<LocatedStmt>>,
+    ) -> Result<bool, Message> {
+        let t = self.check_expr(value)?;
+        let variants = match self.user_type_of(&t) {
+            Some(UserDefinedType::Enum { variants, .. }) => Some(*variants),
+            _ => None,
+        };
+
+        if variants.is_none() && !(t.is_integer() || matches!(t, Type::Char | Type::Bool)) {
+            return Err((
+                value.span.clone(),
+                format!(
+                    "Cannot switch on '{t}', only on integers, characters, booleans, and enums"
+                ),
+            ));
+        }
+
+        let mut handled_variants: Vec<&str> = vec![];
+        let mut handled_values: Vec<Value> = vec![];
+
+        for case in cases {
+            for case_value in &case.values {
+                let case_t = self.check_expr(case_value)?;
+                if !Self::assignable(&t, case_value, &case_t) {
+                    return Err((
+                        case_value.span.clone(),
+                        format!("Expected a case of type '{t}' but got '{case_t}'"),
+                    ));
+                }
+
+                if variants.is_some() {
+                    let Expr::EnumVarAccess { ident, variant } = &case_value.node else {
+                        return Err((
+                            case_value.span.clone(),
+                            format!("The cases of a switch on '{t}' must be its variants"),
+                        ));
+                    };
+                    if handled_variants.contains(&variant.as_str()) {
+                        return Err((
+                            case_value.span.clone(),
+                            format!("'{ident}->{variant}' is already handled by an earlier case"),
+                        ));
+                    }
+                    handled_variants.push(variant);
+                    continue;
+                }
+
+                match self.consts.eval(case_value)? {
+                    Some(v @ (Value::Int(_) | Value::Char(_) | Value::Bool(_))) => {
+                        if handled_values.contains(&v) {
+                            let shown = match v {
+                                Value::Char(c) => format!("'{}'", c.escape_ascii()),
+                                Value::Int(i) => i.to_string(),
+                                Value::Bool(b) => b.to_string(),
+                                _ => unreachable!(),
+                            };
+                            return Err((
+                                case_value.span.clone(),
+                                format!("{shown} is already handled by an earlier case"),
+                            ));
+                        }
+                        handled_values.push(v);
+                    }
+                    _ => {
+                        return Err((
+                            case_value.span.clone(),
+                            "Cases must be constant expressions".to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+
+        if let Some(variants) = variants
+            && default.is_none()
+        {
+            let missing: Vec<String> = variants
+                .iter()
+                .filter(|(name, _)| !handled_variants.contains(&name.as_str()))
+                .map(|(name, _)| format!("'{name}'"))
+                .collect();
+            if !missing.is_empty() {
+                return Err((
+                    span.clone(),
+                    format!(
+                        "The switch on '{t}' doesn't handle {}, add cases for them or a default",
+                        missing.join(", ")
+                    ),
+                ));
+            }
+        }
+
+        Ok(variants.is_some())
+    }
+
+    /// Checks `return (a, b);` in a function returning the multiple values `expected`, or
+    /// `return divmod(a, b);`, which returns the values of a call
+    fn check_return_tuple(
+        &mut self,
+        span: &'ast Span,
+        expected_ret: &'ast LocatedType,
+        expected: &[Type],
+        value: Option<&'ast LocatedExpr>,
+    ) -> Result<Option<bool>, Message> {
+        if let Some(call) = value.filter(|value| is_call(value)) {
+            let t = self.check_expr(call)?;
+            if t != expected_ret.node {
+                return Err((call.span.clone(), Self::mismatch(&expected_ret.node, call, &t)));
+            }
+            return Ok(Some(true));
+        }
+
+        let Some(Located {
+            node: Expr::Tuple { elems },
+            ..
+        }) = value
+        else {
+            return Err((
+                span.clone(),
+                format!(
+                    "Expected {} values like 'return (a, b);' for '{}'",
+                    expected.len(),
+                    expected_ret.node
+                ),
+            ));
+        };
+
+        if elems.len() != expected.len() {
+            return Err((
+                span.clone(),
+                format!(
+                    "Expected {} values for '{}' but got {}",
+                    expected.len(),
+                    expected_ret.node,
+                    elems.len()
+                ),
+            ));
+        }
+
+        for (elem, t) in elems.iter().zip(expected) {
+            let elem_t = self.check_expr(elem)?;
+            if !Self::assignable(t, elem, &elem_t) {
+                return Err((elem.span.clone(), Self::mismatch(t, elem, &elem_t)));
+            }
+        }
+
+        Ok(Some(true))
+    }
+
+    /// Records an error if `t` has multiple values, which only functions can return
+    fn check_not_tuple(&mut self, t: &LocatedType) {
+        if contains_tuple(&t.node) {
+            self.errors.push((
+                t.span.clone(),
+                format!("'{}' can only be the return type of a function", t.node),
+            ));
+        }
+    }
+
+    /// Records an error if `cond` isn't a boolean, leaving the statements it guards to be
+    /// checked either way
+    fn check_cond(&mut self, kind: &str, span: &'ast Span, cond: &'ast LocatedExpr) {
+        match self.check_expr(cond) {
+            Ok(Type::Bool) => {}
+            Ok(t) => self.errors.push((
+                span.clone(),
+                format!("{kind} condition must be boolean but got {t}"),
+            )),
+            Err(err) => self.record(err),
+        }
+    }
+
+    /// Checks a branch in a scope of its own, whose variables go out of scope at its end
+    fn check_block(
+        &mut self,
+        expected_ret: &'ast LocatedType,
+        branch: &'ast Vec<LocatedStmt>,
+    ) -> bool {
+        let old_types = std::mem::take(&mut self.types);
+        self.types = Types::new_with_types(old_types);
+        let returns = self.check_branch(expected_ret, branch);
+        self.types = *std::mem::take(&mut self.types.enclosing).unwrap();
+        returns
     }
 
     /// true -> the branch has a return
     /// false -> the branch doesn't have a return
+    ///
+    /// The error of each statement is recorded, and the ones after it are still checked.
     fn check_branch(
         &mut self,
         expected_ret: &'ast LocatedType,
         branch: &'ast Vec<LocatedStmt>,
-    ) -> Result<bool, Message> {
-        let mut result: Result<bool, Message> = Ok(false);
+    ) -> bool {
+        let mut returns = false;
         for stmt in branch {
-            if result != Ok(false) {
+            if returns {
                 self.warnings
                     .push((stmt.span.clone(), format!("Unreachable code after return")));
                 break;
             }
 
-            match self.check_stmt(expected_ret, stmt)? {
-                Some(p) if p => {
-                    result = Ok(true);
+            self.is_cascading = false;
+            match self.check_stmt(expected_ret, stmt) {
+                Ok(Some(true)) => returns = true,
+                Ok(_) => {}
+                // A return with a wrong value still leaves the branch
+                Err(err) => {
+                    returns = matches!(stmt.node, Stmt::Return { .. });
+                    self.record(err);
                 }
-                _ => {}
             }
         }
 
-        result
+        returns
+    }
+
+    /// Records `err` unless it comes from a use of a variable whose declaration already failed
+    fn record(&mut self, err: Message) {
+        if !std::mem::take(&mut self.is_cascading) {
+            self.errors.push(err);
+        }
+    }
+
+    /// Declares a local, recording the error if it is already declared so that checking can go on
+    fn declare(&mut self, name: &'ast str, t: LocatedType) {
+        if let Err(errs) = self.types.declare(name, t) {
+            self.errors.extend(errs);
+        }
     }
 
     fn define_user_type(
         &mut self,
         name: &'ast str,
         stmt: &'ast LocatedGlobalStmt,
-    ) -> Result<(), Message> {
+    ) -> Result<(), Vec<Message>> {
         let span = stmt.span.clone();
-        let t: UserDefinedType<'ast>;
 
-        if let Ok(ty) = UserDefinedType::<'ast>::try_from(stmt) {
-            t = ty;
-        } else {
-            todo!(); // TODO: probably just ignore?
-                     // return Err((span, format!("")));
-        }
+        // Only types, functions, and imports are defined, so anything else has nothing to define
+        let Ok(t) = UserDefinedType::<'ast>::try_from(stmt) else {
+            return Ok(());
+        };
 
         match self.user_def_types.entry(name) {
-            Entry::Occupied(_) => Err((span, format!("Type '{name}' is already declared"))),
+            Entry::Occupied(entry) => {
+                Err(redeclaration(name, span, entry.get().span().clone(), None))
+            }
             Entry::Vacant(entry) => {
                 entry.insert(t);
                 Ok(())
@@ -577,10 +2649,154 @@ impl<'ast> TypeChecker<'ast> {
     }
 }
 
+/// Infers the size parameters of a generic function's parameter of type `param` from its argument
+/// of type `arg`, or returns the parameter with the two sizes it would have
+fn infer_sizes(
+    param: &Type,
+    arg: &Type,
+    sizes: &mut HashMap<String, Size>,
+) -> Result<(), (String, Size, Size)> {
+    let (size, inferred, param, arg) = match (param, arg) {
+        (Type::GenericArray(size, param), Type::Array(len, arg)) => {
+            (size, Size::Known(*len), param, arg)
+        }
+        (Type::GenericArray(size, param), Type::GenericArray(other, arg)) => {
+            (size, Size::Param(other.clone()), param, arg)
+        }
+        (Type::Pointer(param), Type::Pointer(arg))
+        | (Type::Array(_, param), Type::Array(_, arg))
+        | (Type::DArray(param), Type::DArray(arg)) => return infer_sizes(param, arg, sizes),
+        (Type::Result(param_ok, param_err), Type::Result(arg_ok, arg_err)) => {
+            infer_sizes(param_ok, arg_ok, sizes)?;
+            return infer_sizes(param_err, arg_err, sizes);
+        }
+        _ => return Ok(()),
+    };
+
+    match sizes.entry(size.clone()) {
+        Entry::Occupied(entry) if *entry.get() != inferred => {
+            return Err((size.clone(), entry.get().clone(), inferred));
+        }
+        Entry::Occupied(_) => {}
+        Entry::Vacant(entry) => {
+            entry.insert(inferred);
+        }
+    }
+
+    infer_sizes(param, arg, sizes)
+}
+
+/// Infers the type parameters of a generic function's parameter of type `param` from its argument
+/// of type `arg`
+///
+/// The first argument a type parameter appears in decides its type, so that e.g. `max(x, 1)` is
+/// instantiated for the type of `x`, and the others are checked against it like any argument.
+fn infer_types(
+    param: &Type,
+    arg: &Type,
+    type_params: &[Located<String>],
+    types: &mut HashMap<String, Type>,
+) {
+    match (param, arg) {
+        (Type::UserDefinedType(name), arg) if type_params.iter().any(|t| t.node == *name) => {
+            types.entry(name.clone()).or_insert_with(|| arg.clone());
+        }
+        (Type::Pointer(param), Type::Pointer(arg))
+        | (Type::Array(_, param), Type::Array(_, arg))
+        | (Type::GenericArray(_, param), Type::Array(_, arg) | Type::GenericArray(_, arg))
+        | (Type::DArray(param), Type::DArray(arg)) => infer_types(param, arg, type_params, types),
+        (Type::Func(params, ret), Type::Func(args, arg_ret)) => {
+            for (param, arg) in params.iter().zip(args).chain([(&**ret, &**arg_ret)]) {
+                infer_types(param, arg, type_params, types);
+            }
+        }
+        (Type::Result(param_ok, param_err), Type::Result(arg_ok, arg_err)) => {
+            infer_types(param_ok, arg_ok, type_params, types);
+            infer_types(param_err, arg_err, type_params, types);
+        }
+        _ => {}
+    }
+}
+
+/// The user-defined types `t` refers to, like `T` in `^[3]T`
+fn type_names(t: &Type) -> Vec<&str> {
+    match t {
+        Type::UserDefinedType(name) => vec![name],
+        Type::Pointer(t) | Type::Array(_, t) | Type::DArray(t) | Type::GenericArray(_, t) => {
+            type_names(t)
+        }
+        Type::Func(params, ret) => params.iter().chain([&**ret]).flat_map(type_names).collect(),
+        Type::Tuple(elems) => elems.iter().flat_map(type_names).collect(),
+        Type::Result(ok, err) => [&**ok, &**err].into_iter().flat_map(type_names).collect(),
+        _ => vec![],
+    }
+}
+
+/// The size parameters `t` uses as the lengths of arrays, like `N` in `^[N]i32`
+fn array_sizes(t: &Type) -> Vec<&str> {
+    match t {
+        Type::GenericArray(size, t) => {
+            let mut sizes = array_sizes(t);
+            sizes.push(size);
+            sizes
+        }
+        Type::Pointer(t) | Type::Array(_, t) | Type::DArray(t) => array_sizes(t),
+        Type::Func(params, ret) => params
+            .iter()
+            .chain([&**ret])
+            .flat_map(array_sizes)
+            .collect(),
+        Type::Tuple(elems) => elems.iter().flat_map(array_sizes).collect(),
+        Type::Result(ok, err) => [&**ok, &**err].into_iter().flat_map(array_sizes).collect(),
+        _ => vec![],
+    }
+}
+
+/// Whether `expr` is a call, possibly in parentheses
+fn is_call(expr: &LocatedExpr) -> bool {
+    match &expr.node {
+        Expr::FnCall { .. } => true,
+        Expr::Parenthesized { expr } => is_call(expr),
+        _ => false,
+    }
+}
+
+/// Whether `t` is or refers to multiple return values, e.g. `func(): (i32, i32)`
+fn contains_tuple(t: &Type) -> bool {
+    match t {
+        Type::Tuple(_) => true,
+        Type::Pointer(t) | Type::Array(_, t) | Type::DArray(t) => contains_tuple(t),
+        Type::Func(params, ret) => params.iter().any(contains_tuple) || contains_tuple(ret),
+        Type::Result(ok, err) => contains_tuple(ok) || contains_tuple(err),
+        _ => false,
+    }
+}
+
+/// Returns the type of a pointer to the function with the given parameters and return type
+fn func_type(params: &[(String, LocatedType)], ret: &LocatedType) -> Type {
+    Type::Func(
+        params.iter().map(|(_, t)| t.node.clone()).collect(),
+        Box::new(ret.node.clone()),
+    )
+}
+
+/// The error for using the `@statemachine` function `name` like a plain function, which it
+/// isn't once it's lowered
+fn statemachine_use(span: &Span, name: &str) -> Message {
+    (
+        span.clone(),
+        format!(
+            "The @statemachine function '{name}' cannot be used directly, C runs it through \
+             '{name}_init' and '{name}_step'"
+        ),
+    )
+}
+
 impl<'ast> Types<'ast> {
     pub fn new() -> Self {
         Self {
             types: HashMap::new(),
+            consts: HashSet::new(),
             enclosing: None,
         }
     }
@@ -588,13 +2804,22 @@ impl<'ast> Types<'ast> {
     pub fn new_with_types(types: Types<'ast>) -> Self {
         Self {
             types: HashMap::new(),
+            consts: HashSet::new(),
             enclosing: Some(Box::new(types)),
         }
     }
 
-    pub fn declare(&mut self, name: &'ast str, t: LocatedType) -> Result<(), Message> {
+    pub fn declare(&mut self, name: &'ast str, t: LocatedType) -> Result<(), Vec<Message>> {
         match self.types.entry(name) {
-            Entry::Occupied(_) => Err((t.span.clone(), format!("'{name}' is already declared"))),
+            Entry::Occupied(entry) => {
+                let original = entry.get();
+                Err(redeclaration(
+                    name,
+                    t.span.clone(),
+                    original.span.clone(),
+                    Some((original.node.to_string(), t.node.to_string())),
+                ))
+            }
             Entry::Vacant(entry) => {
                 entry.insert(t);
                 Ok(())
@@ -602,6 +2827,41 @@ impl<'ast> Types<'ast> {
         }
     }
 
+    pub fn get(&self, name: &str) -> Option<&LocatedType> {
+        self.types
+            .get(name)
+            .or_else(|| self.enclosing.as_ref().and_then(|types| types.get(name)))
+    }
+
+    /// Whether `name` is declared in a function rather than globally
+    pub fn is_local(&self, name: &str) -> bool {
+        match &self.enclosing {
+            Some(enclosing) => self.types.contains_key(name) || enclosing.is_local(name),
+            None => false,
+        }
+    }
+
+    /// Whether the innermost declaration of `name` is in the outermost scope of a function, which
+    /// holds its parameters, rather than in a block or globally
+    pub fn is_param(&self, name: &str) -> bool {
+        match &self.enclosing {
+            Some(enclosing) if self.types.contains_key(name) => enclosing.enclosing.is_none(),
+            Some(enclosing) => enclosing.is_param(name),
+            None => false,
+        }
+    }
+
+    /// Whether the innermost declaration of `name` is a constant
+    pub fn is_const(&self, name: &str) -> bool {
+        if self.types.contains_key(name) {
+            self.consts.contains(name)
+        } else {
+            self.enclosing
+                .as_ref()
+                .is_some_and(|types| types.is_const(name))
+        }
+    }
+
     pub fn is_declared(&mut self, name: &'ast str, span: Span) -> Result<(), Message> {
         if self.types.contains_key(name) {
             Ok(())
@@ -625,7 +2885,29 @@ impl<'ast> TryFrom<&'ast LocatedGlobalStmt> for UserDefinedType<'ast> {
             Enum { variants, .. } => Ok(UserDefinedType::Enum { span, variants }),
             Struct { fields, .. } => Ok(UserDefinedType::Struct { span, fields }),
             Union { fields, .. } => Ok(UserDefinedType::Union { span, fields }),
-            Function { params, ret, .. } => Ok(UserDefinedType::Function { span, params, ret }),
+            Function {
+                type_params,
+                size_params,
+                params,
+                ret,
+                attrs,
+                ..
+            } => Ok(UserDefinedType::Function {
+                span,
+                type_params,
+                size_params,
+                params,
+                ret,
+                is_statemachine: attrs.contains(&FuncAttr::StateMachine),
+            }),
+            ExternFunction { params, ret, .. } => Ok(UserDefinedType::Function {
+                span,
+                type_params: &[],
+                size_params: &[],
+                params,
+                ret,
+                is_statemachine: false,
+            }),
             Alias { t, .. } => Ok(UserDefinedType::Alias { span, t }),
             Import { name, .. } => Ok(UserDefinedType::Import { span, name }),
             _ => Err(()),
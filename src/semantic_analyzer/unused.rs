@@ -0,0 +1,420 @@
+//! Lints for declarations that are never used
+//!
+//! Runs on a resolved AST, so every name refers to something in scope. A global is used if
+//! anything other than its own declaration refers to it, so a function that only calls itself
+//! is reported too, and a local is used if it is read, rather than only assigned to. Names
+//! starting with `_` are never reported, for things that are unused on purpose, like a
+//! parameter that a callback's signature requires. Neither is the entry point of a program, nor
+//! what a library exports, which is everything but its imports and private globals.
+
+use std::collections::HashMap;
+
+use crate::CrateType;
+use crate::diagnostics::help;
+use crate::parser::*;
+
+use super::Message;
+
+/// Returns the warnings for the globals, imports, parameters, and locals of `ast` that are never
+/// used, where `ast` is a program starting at `entry` or a library
+pub fn check(ast: &[LocatedGlobalStmt], crate_type: CrateType, entry: &str) -> Vec<Message> {
+    let mut unused = Unused::default();
+    for stmt in ast {
+        unused.declare_global(stmt);
+    }
+    for stmt in ast {
+        unused.visit_global(stmt);
+    }
+
+    for global in &unused.globals {
+        let exported = match crate_type {
+            CrateType::Bin => global.name == entry,
+            CrateType::Lib => !global.is_import && !global.private,
+        };
+        if global.used || exported || global.name.starts_with('_') {
+            continue;
+        }
+
+        let msg = if global.is_import {
+            format!("'{}' is imported but not used", global.name)
+        } else {
+            format!("'{}' is not used", global.name)
+        };
+        unused.warnings.push((global.span.clone(), msg));
+    }
+
+    unused.warnings
+}
+
+#[derive(Default)]
+struct Unused<'ast> {
+    /// The globals and imported names in the order they are declared
+    globals: Vec<Global<'ast>>,
+
+    /// The index of each global in `globals` by name
+    global_idxs: HashMap<&'ast str, usize>,
+
+    /// The global whose declaration is being visited, whose uses of itself don't count
+    current: Option<&'ast str>,
+
+    /// The parameters and locals of the function being visited in the order they are declared
+    locals: Vec<Local<'ast>>,
+
+    /// The indices of the locals in `locals` by name, innermost scope last
+    scopes: Vec<HashMap<&'ast str, usize>>,
+
+    warnings: Vec<Message>,
+}
+
+struct Global<'ast> {
+    name: &'ast str,
+    span: &'ast Span,
+    is_import: bool,
+    private: bool,
+    used: bool,
+}
+
+struct Local<'ast> {
+    name: &'ast str,
+    span: &'ast Span,
+    read: bool,
+    assigned: bool,
+}
+
+impl<'ast> Unused<'ast> {
+    fn declare_global(&mut self, stmt: &'ast LocatedGlobalStmt) {
+        use GlobalStmt::*;
+
+        match &stmt.node {
+            Enum { name, .. }
+            | Struct { name, .. }
+            | Union { name, .. }
+            | Alias { name, .. }
+            | Function { name, .. }
+            | ExternFunction { name, .. } => self.add_global(name, &stmt.span, false, false),
+            Variable { name, private, .. } | Constant { name, private, .. } => {
+                self.add_global(name, &stmt.span, false, *private)
+            }
+            Import {
+                name,
+                members: None,
+                ..
+            } => self.add_global(name, &stmt.span, true, true),
+            Import {
+                members: Some(members),
+                ..
+            } => {
+                for member in members {
+                    self.add_global(member, &stmt.span, true, true);
+                }
+            }
+        }
+    }
+
+    fn add_global(&mut self, name: &'ast str, span: &'ast Span, is_import: bool, private: bool) {
+        if !self.global_idxs.contains_key(name) {
+            self.global_idxs.insert(name, self.globals.len());
+            self.globals.push(Global {
+                name,
+                span,
+                is_import,
+                private,
+                used: false,
+            });
+        }
+    }
+
+    fn visit_global(&mut self, stmt: &'ast LocatedGlobalStmt) {
+        use GlobalStmt::*;
+
+        match &stmt.node {
+            Import { name, .. } => self.current = Some(name),
+            Enum { name, values, .. } => {
+                self.current = Some(name);
+                for (_, value) in values {
+                    self.visit_expr(value);
+                }
+            }
+            Struct { name, fields } | Union { name, fields } => {
+                self.current = Some(name);
+                for (_, t) in fields {
+                    self.use_type(&t.node);
+                }
+            }
+            Alias { name, t } => {
+                self.current = Some(name);
+                self.use_type(&t.node);
+            }
+            ExternFunction { name, params, ret } => {
+                self.current = Some(name);
+                for (_, t) in params {
+                    self.use_type(&t.node);
+                }
+                self.use_type(&ret.node);
+            }
+            Function {
+                name,
+                size_params,
+                params,
+                ret,
+                body,
+                ..
+            } => {
+                self.current = Some(name);
+                for (_, t) in size_params.iter().chain(params) {
+                    self.use_type(&t.node);
+                }
+                self.use_type(&ret.node);
+
+                // The body is in the same scope as the parameters
+                self.scopes = vec![HashMap::new()];
+                for (name, t) in params {
+                    self.declare(name, &t.span);
+                }
+                for stmt in body {
+                    self.visit_stmt(stmt);
+                }
+                self.scopes.clear();
+                self.report_locals();
+            }
+            Variable { name, t, value, .. } => {
+                self.current = Some(name);
+                if let Some(t) = t {
+                    self.use_type(t);
+                }
+                if let Some(value) = value {
+                    self.visit_expr(value);
+                }
+            }
+            Constant { name, t, value, .. } => {
+                self.current = Some(name);
+                if let Some(t) = t {
+                    self.use_type(&t.node);
+                }
+                self.visit_expr(value);
+            }
+        }
+        self.current = None;
+    }
+
+    /// Reports the locals of the function just visited that are never read
+    fn report_locals(&mut self) {
+        for local in std::mem::take(&mut self.locals) {
+            if local.read || local.name.starts_with('_') {
+                continue;
+            }
+
+            let msg = if local.assigned {
+                format!("'{}' is assigned but never read", local.name)
+            } else {
+                format!("'{}' is not used", local.name)
+            };
+            self.warnings.push((local.span.clone(), msg));
+            self.warnings.push(help(
+                local.span.clone(),
+                format!("Name it '_{}' if it is unused on purpose", local.name),
+            ));
+        }
+    }
+
+    fn declare(&mut self, name: &'ast str, span: &'ast Span) {
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name, self.locals.len());
+        self.locals.push(Local {
+            name,
+            span,
+            read: false,
+            assigned: false,
+        });
+    }
+
+    fn local(&mut self, name: &str) -> Option<&mut Local<'ast>> {
+        let idx = self.scopes.iter().rev().find_map(|scope| scope.get(name))?;
+        Some(&mut self.locals[*idx])
+    }
+
+    /// Marks the local or global `name` refers to as used
+    fn use_name(&mut self, name: &str) {
+        match self.local(name) {
+            Some(local) => local.read = true,
+            None => self.use_global(name),
+        }
+    }
+
+    fn use_global(&mut self, name: &str) {
+        if self.current != Some(name)
+            && let Some(idx) = self.global_idxs.get(name)
+        {
+            self.globals[*idx].used = true;
+        }
+    }
+
+    /// Marks the user-defined types `t` refers to as used
+    fn use_type(&mut self, t: &'ast Type) {
+        match t {
+            Type::UserDefinedType(name) => self.use_global(name),
+            // The length of an array may be a global constant
+            Type::GenericArray(size, t) => {
+                self.use_global(size);
+                self.use_type(t);
+            }
+            Type::ExprArray(length, t) => {
+                self.visit_expr(&length.expr);
+                self.use_type(t);
+            }
+            Type::Pointer(t) | Type::Array(_, t) | Type::DArray(t) => self.use_type(t),
+            Type::Func(params, ret) => {
+                for t in params.iter().chain([&**ret]) {
+                    self.use_type(t);
+                }
+            }
+            Type::Tuple(elems) => {
+                for t in elems {
+                    self.use_type(t);
+                }
+            }
+            Type::Result(ok, err) => {
+                self.use_type(ok);
+                self.use_type(err);
+            }
+            _ => {}
+        }
+    }
+
+    fn visit_block(&mut self, body: &'ast [LocatedStmt]) {
+        self.scopes.push(HashMap::new());
+        for stmt in body {
+            self.visit_stmt(stmt);
+        }
+        self.scopes.pop();
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast LocatedStmt) {
+        match &stmt.node {
+            // The value is visited first, since it still sees a variable the new one shadows
+            Stmt::Variable { name, t, value, .. } => {
+                if let Some(t) = t {
+                    self.use_type(t);
+                }
+                if let Some(value) = value {
+                    self.visit_expr(value);
+                }
+                self.declare(name, &stmt.span);
+            }
+            Stmt::Destructure { pattern, value, .. } => {
+                self.visit_expr(value);
+                let (Pattern::Struct(names) | Pattern::Array(names) | Pattern::Tuple(names)) =
+                    pattern;
+                for name in names {
+                    self.declare(name, &stmt.span);
+                }
+            }
+            Stmt::Expression { expr }
+            | Stmt::Yield { value: expr }
+            | Stmt::Return { value: Some(expr) }
+            | Stmt::Destroy { expr }
+            | Stmt::Free { expr } => self.visit_expr(expr),
+            Stmt::If { cond, then, other } => {
+                self.visit_expr(cond);
+                self.visit_block(then);
+                if let Some(other) = other {
+                    self.visit_block(other);
+                }
+            }
+            Stmt::While { cond, body, .. } => {
+                self.visit_expr(cond);
+                self.visit_block(body);
+            }
+            Stmt::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                self.visit_expr(value);
+                for case in cases {
+                    for value in &case.values {
+                        self.visit_expr(value);
+                    }
+                    self.visit_block(&case.body);
+                }
+                if let Some(default) = default {
+                    self.visit_block(default);
+                }
+            }
+            Stmt::Defer { body } | Stmt::Block { body } => self.visit_block(body),
+            Stmt::Return { value: None } | Stmt::Break | Stmt::Continue => {}
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &'ast LocatedExpr) {
+        match &expr.node {
+            Expr::Ident(name) => self.use_name(name),
+            // Assigning to a local doesn't read it, even with an operator like `+=`, since only
+            // the local itself sees the result
+            Expr::Assign { lvalue, value, .. } => {
+                let mut target = &**lvalue;
+                while let Expr::Parenthesized { expr } = &target.node {
+                    target = expr;
+                }
+                match &target.node {
+                    Expr::Ident(name) if self.local(name).is_some() => {
+                        self.local(name).unwrap().assigned = true;
+                    }
+                    _ => self.visit_expr(lvalue),
+                }
+                self.visit_expr(value);
+            }
+            Expr::Binary { left, right, .. } => {
+                self.visit_expr(left);
+                self.visit_expr(right);
+            }
+            Expr::Parenthesized { expr }
+            | Expr::Unary { expr, .. }
+            | Expr::MemAccess { expr, .. }
+            | Expr::Try { expr, .. } => self.visit_expr(expr),
+            Expr::Cast { t, expr, .. } => {
+                self.use_type(&t.node);
+                self.visit_expr(expr);
+            }
+            Expr::Sizeof { t } | Expr::Make { t } | Expr::New { t } => self.use_type(t),
+            Expr::Ternary { cond, lexpr, rexpr } => {
+                self.visit_expr(cond);
+                self.visit_expr(lexpr);
+                self.visit_expr(rexpr);
+            }
+            Expr::FnCall { name, args, .. } => {
+                self.visit_expr(name);
+                for arg in args {
+                    self.visit_expr(arg);
+                }
+            }
+            Expr::ArrIndex { arr, idx } => {
+                self.visit_expr(arr);
+                self.visit_expr(idx);
+            }
+            Expr::InitArr { elems }
+            | Expr::InitArrDesignated { elems, .. }
+            | Expr::Tuple { elems } => {
+                for elem in elems {
+                    self.visit_expr(elem);
+                }
+            }
+            Expr::InitStruct { ident, args } => {
+                self.use_global(ident);
+                for (_, value) in args {
+                    self.visit_expr(value);
+                }
+            }
+            Expr::EnumVarAccess { ident, .. } => self.use_global(ident),
+            Expr::ModuleAccess { module, .. } => self.use_global(module),
+            Expr::Int(_)
+            | Expr::UInt(_)
+            | Expr::Double(_)
+            | Expr::Bool(_)
+            | Expr::Char(_)
+            | Expr::Str(_) => {}
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "color")]
+use colored::Colorize;
+
+/// How much instrumentation output the compiler should print
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Verbosity {
+    /// Prints nothing
+    #[default]
+    Quiet,
+    /// Prints a summary table of all phases at the end (`--timings`)
+    Timings,
+    /// Logs each phase once it finishes, and prints the summary table (`--verbose`)
+    Verbose,
+}
+
+/// A single compiler phase that has been measured
+#[derive(Debug)]
+struct Phase {
+    name: &'static str,
+    elapsed: Duration,
+    items: Option<(usize, &'static str)>,
+
+    /// Whether the phase has been logged in verbose mode
+    logged: bool,
+}
+
+/// Records how long each compiler phase (lex, parse, resolve, typecheck, codegen, cc) takes,
+/// along with how many items it processed
+#[derive(Debug, Default)]
+pub struct Timings {
+    verbosity: Verbosity,
+    phases: Vec<Phase>,
+}
+
+impl Timings {
+    pub fn new(verbosity: Verbosity) -> Self {
+        Self {
+            verbosity,
+            phases: vec![],
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.verbosity != Verbosity::Quiet
+    }
+
+    /// The names of the phases measured so far, in the order they ran
+    pub fn phases(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.phases.iter().map(|phase| phase.name)
+    }
+
+    /// Runs `f` as the phase `name` and records how long it took
+    pub fn time<T>(&mut self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        self.log_last();
+
+        let start = Instant::now();
+        let res = f();
+        self.phases.push(Phase {
+            name,
+            elapsed: start.elapsed(),
+            items: None,
+            logged: false,
+        });
+        res
+    }
+
+    /// Attaches an item count (e.g. `15 tokens`) to the most recently measured phase, and logs
+    /// the phase if verbose
+    pub fn count(&mut self, n: usize, what: &'static str) {
+        if let Some(phase) = self.phases.last_mut() {
+            phase.items = Some((n, what));
+        }
+        self.log_last();
+    }
+
+    /// Logs the most recently measured phase if verbose, unless it already has been
+    ///
+    /// A phase is logged once its item count is attached, or otherwise when the next phase
+    /// starts or the summary is printed.
+    fn log_last(&mut self) {
+        if self.verbosity != Verbosity::Verbose {
+            return;
+        }
+
+        if let Some(phase) = self.phases.last_mut().filter(|phase| !phase.logged) {
+            phase.logged = true;
+            #[cfg(feature = "color")]
+            eprintln!("{} {}", "[verbose]".cyan(), Self::describe(phase));
+            #[cfg(not(feature = "color"))]
+            eprintln!("[verbose] {}", Self::describe(phase));
+        }
+    }
+
+    /// Prints a summary of all measured phases
+    pub fn report(&mut self) {
+        self.log_last();
+        if !self.is_enabled() || self.phases.is_empty() {
+            return;
+        }
+
+        let total: Duration = self.phases.iter().map(|p| p.elapsed).sum();
+
+        #[cfg(feature = "color")]
+        eprintln!("{}", "Timings:".bold());
+        #[cfg(not(feature = "color"))]
+        eprintln!("Timings:");
+        for phase in &self.phases {
+            let percent = if total.is_zero() {
+                0.0
+            } else {
+                phase.elapsed.as_secs_f64() / total.as_secs_f64() * 100.0
+            };
+            eprintln!("  {} ({:5.1}%)", Self::describe(phase), percent);
+        }
+        eprintln!("  {:<12} {:>10.3}ms", "total", total.as_secs_f64() * 1000.0);
+    }
+
+    fn describe(phase: &Phase) -> String {
+        let mut res = format!(
+            "{:<12} {:>10.3}ms",
+            phase.name,
+            phase.elapsed.as_secs_f64() * 1000.0
+        );
+        if let Some((n, what)) = phase.items {
+            res.push_str(&format!(" {n:>8} {what}"));
+        }
+        res
+    }
+}
+
+impl Verbosity {
+    /// Picks the verbosity from command line arguments (`--verbose` wins over `--timings`)
+    pub fn from_args<S: AsRef<str>>(args: &[S]) -> Self {
+        if args.iter().any(|a| a.as_ref() == "--verbose") {
+            Self::Verbose
+        } else if args.iter().any(|a| a.as_ref() == "--timings") {
+            Self::Timings
+        } else {
+            Self::Quiet
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timings() {
+        assert_eq!(Verbosity::from_args(&["main.clla"]), Verbosity::Quiet);
+        assert_eq!(
+            Verbosity::from_args(&["--timings", "main.clla"]),
+            Verbosity::Timings
+        );
+        assert_eq!(
+            Verbosity::from_args(&["--timings", "--verbose"]),
+            Verbosity::Verbose
+        );
+
+        let mut timings = Timings::new(Verbosity::Timings);
+        let res = timings.time("lex", || 1 + 1);
+        timings.count(3, "tokens");
+
+        assert_eq!(res, 2);
+        assert_eq!(timings.phases.len(), 1);
+        assert_eq!(timings.phases[0].name, "lex");
+        assert_eq!(timings.phases[0].items, Some((3, "tokens")));
+        assert!(Timings::describe(&timings.phases[0]).ends_with("3 tokens"));
+
+        let mut timings = Timings::new(Verbosity::Verbose);
+        timings.time("instantiate", || {});
+        assert!(!timings.phases[0].logged);
+        timings.time("lower", || {});
+        assert!(timings.phases[0].logged);
+        timings.count(2, "items");
+        assert!(timings.phases[1].logged);
+        assert!(Timings::describe(&timings.phases[0]).starts_with("instantiate  "));
+    }
+}
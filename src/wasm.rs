@@ -0,0 +1,108 @@
+//! `wasm-bindgen` bindings so that a web playground can run the compiler client-side.
+//!
+//! Build with
+//!
+//! ```text
+//! cargo build --lib --release --target wasm32-unknown-unknown --no-default-features --features wasm
+//! wasm-bindgen --target web target/wasm32-unknown-unknown/release/castella_core.wasm --out-dir pkg
+//! ```
+//!
+//! There is no filesystem in the browser, so the standard library is taken from the copies
+//! bundled into the compiler and other imports can't be resolved.
+
+use wasm_bindgen::prelude::*;
+
+use crate::module::ModuleLoader;
+use crate::diagnostics::{self, Level};
+use crate::{CompileOptions, Message};
+
+/// A warning or an error, with the location it refers to
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub is_error: bool,
+
+    /// Whether this adds context to the diagnostic before it, e.g. where a symbol was first
+    /// declared
+    pub is_note: bool,
+
+    /// Whether this suggests how to fix the diagnostic before it
+    pub is_help: bool,
+
+    /// The byte offsets of the span in the source
+    pub start: usize,
+    pub end: usize,
+
+    /// The 1-based line `start` is on
+    pub line: usize,
+
+    /// The 1-based column `start` is at, counted in characters
+    pub column: usize,
+
+    pub message: String,
+}
+
+/// The result of `compile`, with `c` set only if the compilation succeeded
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug)]
+pub struct Compiled {
+    pub c: Option<String>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Runs the front-end on `source` and returns all warnings and errors
+#[wasm_bindgen]
+pub fn check(source: &str) -> Vec<Diagnostic> {
+    let (warnings, res) = crate::check(source, &options(false));
+    diagnostics(source, warnings, res.err())
+}
+
+/// Compiles `source` to C
+#[wasm_bindgen]
+pub fn compile(source: &str, bounds_checks: bool) -> Compiled {
+    match crate::compile(source, &options(bounds_checks), &mut Default::default()) {
+        (warnings, Ok(c)) => Compiled {
+            c: Some(c),
+            diagnostics: diagnostics(source, warnings, None),
+        },
+        (warnings, Err(errors)) => Compiled {
+            c: None,
+            diagnostics: diagnostics(source, warnings, Some(errors)),
+        },
+    }
+}
+
+fn options(bounds_checks: bool) -> CompileOptions {
+    CompileOptions {
+        bounds_checks,
+        loader: ModuleLoader::new(".").bundled_std(),
+        ..Default::default()
+    }
+}
+
+fn diagnostics(
+    source: &str,
+    warnings: Vec<Message>,
+    errors: Option<Vec<Message>>,
+) -> Vec<Diagnostic> {
+    let warnings = warnings.into_iter().map(|w| (false, w));
+    let errors = errors.into_iter().flatten().map(|e| (true, e));
+
+    errors
+        .chain(warnings)
+        .map(|(is_error, (span, message))| {
+            let (level, text) = Level::of(Level::Error, &message);
+            let location = diagnostics::locate(source, span.start);
+            Diagnostic {
+                is_error,
+                is_note: level == Level::Note,
+                is_help: level == Level::Help,
+                line: location.line,
+                column: location.column,
+                start: span.start,
+                end: span.end,
+                message: text.to_string(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,521 @@
+// Copyright (c) 2025 Nobuharu Shimazu
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Random well-formed translation units for fuzzing and property-based tests, behind the
+//! `arbitrary` feature.
+//!
+//! A `Scope` generated with `arbitrary::Arbitrary` is C that compiles: it declares structs, enums,
+//! global variables, and functions, whose bodies use only what is declared before them, with
+//! `break` and `continue` only where a loop or `switch` allows them. Every value is an integer,
+//! so any expression fits anywhere another does, and compound operands are parenthesized since
+//! `Expr` is formatted as written.
+//!
+//! ```rust,ignore
+//! use arbitrary::{Arbitrary, Unstructured};
+//!
+//! let scope = Scope::arbitrary(&mut Unstructured::new(&bytes))?;
+//! println!("{scope}");
+//! ```
+
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::*;
+
+/// How deeply statements and expressions nest
+const MAX_DEPTH: usize = 4;
+
+/// The most declarations of each kind at the top level, and statements in a block
+const MAX_ITEMS: usize = 6;
+
+/// The integer types variables, fields, and casts are given
+const INT_TYPES: &[BaseType] = &[
+    BaseType::Int,
+    BaseType::Char,
+    BaseType::Bool,
+    BaseType::Int8,
+    BaseType::Int16,
+    BaseType::Int32,
+    BaseType::Int64,
+    BaseType::UInt8,
+    BaseType::UInt16,
+    BaseType::UInt32,
+    BaseType::UInt64,
+    BaseType::Size,
+];
+
+impl<'a> Arbitrary<'a> for Scope {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Generator::new(u).scope()
+    }
+}
+
+/// A variable in scope
+enum Var {
+    Int(String),
+    Struct { name: String, fields: Vec<String> },
+    Array { name: String, len: usize },
+}
+
+struct Generator<'u, 'a> {
+    u: &'u mut Unstructured<'a>,
+
+    /// The fields of each struct declared so far
+    structs: Vec<(String, Vec<String>)>,
+
+    /// The enum constants declared so far
+    constants: Vec<String>,
+
+    /// The functions declared so far and how many parameters each takes
+    functions: Vec<(String, usize)>,
+
+    /// The global variables, then the variables of each block being generated, innermost last
+    scopes: Vec<Vec<Var>>,
+
+    /// How many names have been made, which keeps them unique
+    names: usize,
+
+    depth: usize,
+    in_loop: bool,
+    in_switch: bool,
+}
+
+impl<'u, 'a> Generator<'u, 'a> {
+    fn new(u: &'u mut Unstructured<'a>) -> Self {
+        Self {
+            u,
+            structs: vec![],
+            constants: vec![],
+            functions: vec![],
+            scopes: vec![vec![]],
+            names: 0,
+            depth: 0,
+            in_loop: false,
+            in_switch: false,
+        }
+    }
+
+    fn name(&mut self, prefix: &str) -> String {
+        self.names += 1;
+        format!("{prefix}{}", self.names)
+    }
+
+    fn count(&mut self, max: usize) -> Result<usize> {
+        self.u.int_in_range(0..=max)
+    }
+
+    fn int_type(&mut self) -> Result<Type> {
+        Ok(Type::new(self.u.choose(INT_TYPES)?.clone()).build())
+    }
+
+    fn scope(mut self) -> Result<Scope> {
+        let mut globals = vec![];
+        for _ in 0..self.count(MAX_ITEMS)? {
+            let global = match self.u.int_in_range(0..=2)? {
+                0 => self.struct_()?,
+                1 => self.enum_()?,
+                _ => self.global_variable()?,
+            };
+            globals.push(global);
+        }
+        for _ in 0..=self.count(MAX_ITEMS)? {
+            globals.push(self.function()?);
+        }
+
+        let mut builder = ScopeBuilder::new();
+        for global in globals {
+            builder = builder.global_statement(global).new_line();
+        }
+        let mut scope = builder.build();
+
+        // The integer types come from `stdint.h`, `stddef.h`, and `stdbool.h`
+        IncludeAnalysis::new().insert_missing(&mut scope);
+        Ok(scope)
+    }
+
+    fn struct_(&mut self) -> Result<GlobalStatement> {
+        let name = self.name("s");
+        let mut builder = StructBuilder::new(name.clone());
+        let mut fields = vec![];
+        for _ in 0..=self.count(MAX_ITEMS)? {
+            let field = self.name("f");
+            builder = builder.field(FieldBuilder::new(field.clone(), self.int_type()?).build());
+            fields.push(field);
+        }
+        self.structs.push((name, fields));
+        Ok(GlobalStatement::Struct(builder.build()))
+    }
+
+    fn enum_(&mut self) -> Result<GlobalStatement> {
+        let mut builder = EnumBuilder::new(self.name("e"));
+        for _ in 0..=self.count(MAX_ITEMS)? {
+            let constant = self.name("E");
+            let mut variant = VariantBuilder::new(constant.clone());
+            if self.u.arbitrary()? {
+                variant = variant.value(self.u.int_in_range(-128..=127)?);
+            }
+            builder = builder.variant(variant.build());
+            self.constants.push(constant);
+        }
+        Ok(GlobalStatement::Enum(builder.build()))
+    }
+
+    /// A global variable, whose initializer has to be constant
+    fn global_variable(&mut self) -> Result<GlobalStatement> {
+        let name = self.name("g");
+        let mut builder = VariableBuilder::new(name.clone(), self.int_type()?);
+        if self.u.arbitrary()? {
+            builder = builder.value(self.literal()?);
+        }
+        self.scopes[0].push(Var::Int(name));
+        Ok(GlobalStatement::Variable(builder.build()))
+    }
+
+    fn function(&mut self) -> Result<GlobalStatement> {
+        let name = self.name("fn");
+        let mut builder = FunctionBuilder::new(name.clone(), self.int_type()?);
+        let mut params = vec![];
+        for _ in 0..self.count(3)? {
+            let param = self.name("p");
+            builder = builder.param(ParameterBuilder::new(param.clone(), self.int_type()?).build());
+            params.push(Var::Int(param));
+        }
+
+        // Declared before the body so that it can call itself
+        self.functions.push((name, params.len()));
+        self.scopes.push(params);
+        let mut body = self.statements()?;
+        body.push(Statement::ret(self.expr()?));
+        self.scopes.pop();
+
+        Ok(GlobalStatement::Function(
+            builder.body(Block::new().statements(body).build()).build(),
+        ))
+    }
+
+    /// A block in a scope of its own
+    fn block(&mut self) -> Result<Block> {
+        self.scopes.push(vec![]);
+        let body = self.statements()?;
+        self.scopes.pop();
+        Ok(Block::new().statements(body).build())
+    }
+
+    fn statements(&mut self) -> Result<Vec<Statement>> {
+        let mut res = vec![];
+        for _ in 0..self.count(MAX_ITEMS)? {
+            res.push(self.statement()?);
+        }
+        Ok(res)
+    }
+
+    fn statement(&mut self) -> Result<Statement> {
+        if self.depth >= MAX_DEPTH {
+            return self.simple_statement();
+        }
+
+        self.depth += 1;
+        let res = match self.u.int_in_range(0..=9)? {
+            0 => self.if_(),
+            1 => self.while_(),
+            2 => self.do_while(),
+            3 => self.for_(),
+            4 => self.switch(),
+            5 => self.block().map(Statement::Block),
+            _ => self.simple_statement(),
+        };
+        self.depth -= 1;
+        res
+    }
+
+    /// A statement without a body
+    fn simple_statement(&mut self) -> Result<Statement> {
+        Ok(match self.u.int_in_range(0..=7)? {
+            0 | 1 => self.declaration()?,
+            2 => Statement::ret(self.expr()?),
+            3 if self.in_loop || self.in_switch => Statement::Break,
+            4 if self.in_loop => Statement::Continue,
+            5 if !self.functions.is_empty() => Statement::expr(self.call()?),
+            _ if !self.has_vars() => self.declaration()?,
+            6 => Statement::expr(Expr::new_unary(self.lvalue()?, UnaryOp::Inc)),
+            _ => {
+                let op = self.u.choose(&[
+                    AssignOp::Assign,
+                    AssignOp::AddAssign,
+                    AssignOp::SubAssign,
+                    AssignOp::MulAssign,
+                    AssignOp::BitOrAssign,
+                    AssignOp::BitXOrAssign,
+                ])?;
+                Statement::expr(Expr::new_assign(self.lvalue()?, op.clone(), self.expr()?))
+            }
+        })
+    }
+
+    fn declaration(&mut self) -> Result<Statement> {
+        let name = self.name("v");
+        let (var, decl) = match self.u.int_in_range(0..=3)? {
+            0 if !self.structs.is_empty() => {
+                let idx = self.u.choose_index(self.structs.len())?;
+                let (t, fields) = self.structs[idx].clone();
+                let mut values = vec![];
+                for field in &fields {
+                    values.push((Some(field.clone()), self.expr()?));
+                }
+                let decl =
+                    VariableBuilder::new(name.clone(), Type::new(BaseType::Struct(t)).build())
+                        .value(Expr::InitStruct(values))
+                        .build();
+                (Var::Struct { name, fields }, decl)
+            }
+            1 => {
+                let len = self.u.int_in_range(1..=8)?;
+                let mut elems = vec![];
+                for _ in 0..self.count(len)? {
+                    elems.push((None, self.expr()?));
+                }
+                let t = Type::new(BaseType::Int).make_array(len).build();
+                let decl = VariableBuilder::new(name.clone(), t)
+                    .value(Expr::InitArr(elems))
+                    .build();
+                (Var::Array { name, len }, decl)
+            }
+            _ => {
+                let decl = VariableBuilder::new(name.clone(), self.int_type()?)
+                    .value(self.expr()?)
+                    .build();
+                (Var::Int(name), decl)
+            }
+        };
+
+        // Declared after its initializer, which can't refer to it
+        self.scopes.last_mut().unwrap().push(var);
+        Ok(Statement::decl(decl))
+    }
+
+    fn if_(&mut self) -> Result<Statement> {
+        let mut builder = IfBuilder::new(self.expr()?).then(self.block()?);
+        if self.u.arbitrary()? {
+            builder = builder.other(self.block()?);
+        }
+        Ok(Statement::If(builder.build()))
+    }
+
+    fn loop_body(&mut self) -> Result<Block> {
+        let in_loop = std::mem::replace(&mut self.in_loop, true);
+        let body = self.block();
+        self.in_loop = in_loop;
+        body
+    }
+
+    fn while_(&mut self) -> Result<Statement> {
+        let cond = self.expr()?;
+        Ok(Statement::While(
+            WhileBuilder::new(cond).body(self.loop_body()?).build(),
+        ))
+    }
+
+    fn do_while(&mut self) -> Result<Statement> {
+        let body = self.loop_body()?;
+        Ok(Statement::DoWhile(
+            DoWhileBuilder::new(self.expr()?).body(body).build(),
+        ))
+    }
+
+    fn for_(&mut self) -> Result<Statement> {
+        // The counter is only in scope in the loop
+        let counter = self.name("i");
+        let init = VariableBuilder::new(counter.clone(), Type::new(BaseType::Int).build())
+            .value(self.expr()?)
+            .build();
+        self.scopes.push(vec![Var::Int(counter)]);
+
+        let mut builder = ForBuilder::new().init(Expr::Variable(Box::new(init)));
+        if self.u.arbitrary()? {
+            builder = builder.cond(self.expr()?);
+        }
+        if self.u.arbitrary()? {
+            builder = builder.step(Expr::new_unary(self.lvalue()?, UnaryOp::Inc));
+        }
+        let res = builder.body(self.loop_body()?).build();
+
+        self.scopes.pop();
+        Ok(Statement::For(res))
+    }
+
+    fn switch(&mut self) -> Result<Statement> {
+        let mut builder = SwitchBuilder::new(self.expr()?);
+
+        // Case labels have to be distinct
+        let mut labels: Vec<i64> = vec![];
+        for _ in 0..self.count(MAX_ITEMS)? {
+            labels.push(self.u.int_in_range(0..=31)?);
+        }
+        labels.sort_unstable();
+        labels.dedup();
+
+        let in_switch = std::mem::replace(&mut self.in_switch, true);
+        for label in labels {
+            builder = builder.case(Expr::Int(label), self.block()?);
+        }
+        if self.u.arbitrary()? {
+            builder = builder.default(self.block()?);
+        }
+        self.in_switch = in_switch;
+
+        Ok(Statement::Switch(builder.build()))
+    }
+
+    fn literal(&mut self) -> Result<Expr> {
+        Ok(match self.u.int_in_range(0..=2)? {
+            0 => Expr::Int(self.u.int_in_range(0..=255)?),
+            1 => Expr::UInt(self.u.int_in_range(0..=255)?),
+            _ => Expr::Char(char::from(self.u.int_in_range(b' '..=b'~')?)),
+        })
+    }
+
+    fn has_vars(&self) -> bool {
+        self.scopes.iter().any(|scope| !scope.is_empty())
+    }
+
+    /// A variable, field, or element to assign to, of which there has to be one in scope
+    fn lvalue(&mut self) -> Result<Expr> {
+        let vars: Vec<&Var> = self.scopes.iter().flatten().collect();
+        let var = *self.u.choose(&vars)?;
+        Ok(match var {
+            Var::Int(name) => Expr::Ident(name.clone()),
+            Var::Struct { name, fields } => {
+                let field = self.u.choose(fields)?.clone();
+                Expr::new_mem_access(Expr::Ident(name.clone()), field)
+            }
+            Var::Array { name, len } => {
+                let idx = self.u.int_in_range(0..=*len as i64 - 1)?;
+                Expr::new_arr_index(Expr::Ident(name.clone()), Expr::Int(idx))
+            }
+        })
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        if self.depth >= MAX_DEPTH {
+            return self.leaf();
+        }
+
+        self.depth += 1;
+        let res = self.compound_expr();
+        self.depth -= 1;
+        res
+    }
+
+    fn compound_expr(&mut self) -> Result<Expr> {
+        Ok(match self.u.int_in_range(0..=7)? {
+            0 => {
+                let op = self.u.choose(&[
+                    BinOp::Add,
+                    BinOp::Sub,
+                    BinOp::Mul,
+                    BinOp::Eq,
+                    BinOp::NEq,
+                    BinOp::LT,
+                    BinOp::GTE,
+                    BinOp::And,
+                    BinOp::Or,
+                    BinOp::BitAnd,
+                    BinOp::BitOr,
+                    BinOp::XOr,
+                ])?;
+                Expr::new_binary(self.operand()?, op.clone(), self.operand()?)
+            }
+            1 => {
+                let op = self
+                    .u
+                    .choose(&[UnaryOp::Neg, UnaryOp::LogicNeg, UnaryOp::BitNot])?;
+                Expr::new_unary(self.operand()?, op.clone())
+            }
+            2 => Expr::new_ternary(self.operand()?, self.operand()?, self.operand()?),
+            3 => Expr::new_cast(self.int_type()?, self.expr()?),
+            4 if !self.functions.is_empty() => self.call()?,
+            5 => Expr::new_sizeof(self.int_type()?),
+            _ => self.leaf()?,
+        })
+    }
+
+    /// An operand of an operator, parenthesized unless it is a single token
+    fn operand(&mut self) -> Result<Expr> {
+        let expr = self.expr()?;
+        Ok(match expr {
+            Expr::Int(_) | Expr::UInt(_) | Expr::Char(_) | Expr::Ident(_) => expr,
+            _ => Expr::new_parenthesized(expr),
+        })
+    }
+
+    fn call(&mut self) -> Result<Expr> {
+        let (name, params) = self.u.choose(&self.functions)?.clone();
+        let mut args = vec![];
+        for _ in 0..params {
+            args.push(self.expr()?);
+        }
+        Ok(Expr::new_fn_call_with_name(name, args))
+    }
+
+    fn leaf(&mut self) -> Result<Expr> {
+        Ok(match self.u.int_in_range(0..=2)? {
+            0 if self.has_vars() => self.lvalue()?,
+            1 if !self.constants.is_empty() => Expr::Ident(self.u.choose(&self.constants)?.clone()),
+            _ => self.literal()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::collection::vec;
+    use proptest::prelude::{ProptestConfig, any, prop_assert, proptest};
+
+    use super::*;
+
+    fn scope(bytes: &[u8]) -> Scope {
+        Scope::arbitrary(&mut Unstructured::new(bytes)).unwrap()
+    }
+
+    proptest! {
+        #[test]
+        fn formatting(bytes in vec(any::<u8>(), 0..4096)) {
+            let code = scope(&bytes).to_string();
+            prop_assert!(code.ends_with('\n') && !code.ends_with("\n\n"), "{code}");
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        /// Checked with `TAMAGO_CC` like the corpus, but only for errors, since random code is
+        /// bound to have unused variables and the like
+        #[test]
+        fn compiles(bytes in vec(any::<u8>(), 0..4096)) {
+            let Some(cc) = std::env::var("TAMAGO_CC").ok().filter(|cc| !cc.is_empty()) else {
+                return Ok(());
+            };
+
+            let code = scope(&bytes).to_string();
+            if let Err(err) = crate::corpus::compile(&cc, &code, &[]) {
+                prop_assert!(false, "{code}\n{err}");
+            }
+        }
+    }
+}
@@ -0,0 +1,575 @@
+// Copyright (c) 2025 Nobuharu Shimazu
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! This module provides tools for emitting inline assembly statements.
+//!
+//! Under the GNU dialect, statements are emitted in GCC's extended asm syntax, with output and
+//! input operands, clobbers, and goto labels. MSVC and standard C only support the basic form, so
+//! only the template is emitted for those dialects.
+
+use std::fmt::{self, Write};
+
+use crate::{Dialect, Expr, Format, Formatter};
+use tamacro::DisplayFromFormat;
+
+/// Represents an inline assembly statement.
+///
+/// # Examples
+///
+/// ```c
+/// __asm__ volatile("addl %2, %0" : "=r"(sum) : "0"(a), "r"(b) : "cc");
+/// ```
+#[derive(Debug, Clone, DisplayFromFormat)]
+pub struct Asm {
+    /// The assembly template
+    pub template: String,
+
+    /// The output operands
+    pub outputs: Vec<AsmOperand>,
+
+    /// The input operands
+    pub inputs: Vec<AsmOperand>,
+
+    /// The registers and other resources clobbered by the assembly, e.g. `"cc"` or `"memory"`
+    pub clobbers: Vec<String>,
+
+    /// The C labels the assembly may jump to
+    pub labels: Vec<String>,
+
+    /// Whether the statement is `volatile`, which prevents the compiler from optimizing it away
+    pub is_volatile: bool,
+
+    /// Whether the statement is an `asm goto`
+    pub is_goto: bool,
+}
+
+impl Asm {
+    /// Creates and returns a new `AsmBuilder` to construct an `Asm` using the builder pattern.
+    ///
+    /// # Parameters
+    ///
+    /// * `template` - The assembly template
+    ///
+    /// # Returns
+    ///
+    /// A new `AsmBuilder` instance
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let asm = Asm::new("nop".to_string()).make_volatile().build();
+    /// ```
+    pub fn new(template: String) -> AsmBuilder {
+        AsmBuilder::new(template)
+    }
+}
+
+impl Format for Asm {
+    fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match fmt.dialect {
+            Dialect::Gnu => self.format_extended(fmt),
+            Dialect::Msvc => writeln!(fmt, "__asm {{ {} }}", self.template),
+            Dialect::Standard => writeln!(fmt, "asm(\"{}\");", self.template),
+        }
+    }
+}
+
+impl Asm {
+    fn format_extended(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "__asm__ ")?;
+
+        if self.is_volatile {
+            write!(fmt, "volatile ")?;
+        }
+
+        if self.is_goto {
+            write!(fmt, "goto ")?;
+        }
+
+        write!(fmt, "(\"{}\"", self.template)?;
+
+        // Trailing empty sections are left out, but the ones before a non-empty one are needed
+        let sections = if self.is_goto || !self.labels.is_empty() {
+            4
+        } else if !self.clobbers.is_empty() {
+            3
+        } else if !self.inputs.is_empty() {
+            2
+        } else if !self.outputs.is_empty() {
+            1
+        } else {
+            0
+        };
+
+        if sections >= 1 {
+            write!(fmt, " :")?;
+            Self::format_operands(fmt, &self.outputs)?;
+        }
+
+        if sections >= 2 {
+            write!(fmt, " :")?;
+            Self::format_operands(fmt, &self.inputs)?;
+        }
+
+        if sections >= 3 {
+            write!(fmt, " :")?;
+            Self::format_list(fmt, self.clobbers.iter().map(|c| format!("\"{c}\"")))?;
+        }
+
+        if sections >= 4 {
+            write!(fmt, " :")?;
+            Self::format_list(fmt, self.labels.iter().cloned())?;
+        }
+
+        writeln!(fmt, ");")
+    }
+
+    fn format_operands(fmt: &mut Formatter<'_>, operands: &[AsmOperand]) -> fmt::Result {
+        for (i, operand) in operands.iter().enumerate() {
+            write!(fmt, "{}", if i == 0 { " " } else { ", " })?;
+            operand.format(fmt)?;
+        }
+        Ok(())
+    }
+
+    fn format_list(fmt: &mut Formatter<'_>, items: impl Iterator<Item = String>) -> fmt::Result {
+        for (i, item) in items.enumerate() {
+            write!(fmt, "{}{item}", if i == 0 { " " } else { ", " })?;
+        }
+        Ok(())
+    }
+}
+
+/// A builder for constructing an `Asm` instance.
+pub struct AsmBuilder {
+    template: String,
+    outputs: Vec<AsmOperand>,
+    inputs: Vec<AsmOperand>,
+    clobbers: Vec<String>,
+    labels: Vec<String>,
+    is_volatile: bool,
+    is_goto: bool,
+}
+
+impl AsmBuilder {
+    /// Creates and returns a new `AsmBuilder` to construct an `Asm`.
+    ///
+    /// # Parameters
+    ///
+    /// * `template` - The assembly template
+    ///
+    /// # Returns
+    ///
+    /// A new `AsmBuilder` instance with no operands, clobbers, or labels
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let builder = AsmBuilder::new("rdtsc".to_string());
+    /// ```
+    pub fn new(template: String) -> Self {
+        Self {
+            template,
+            outputs: vec![],
+            inputs: vec![],
+            clobbers: vec![],
+            labels: vec![],
+            is_volatile: false,
+            is_goto: false,
+        }
+    }
+
+    /// Creates and returns a new `AsmBuilder` to construct an `Asm` with the given template
+    /// string slice.
+    ///
+    /// # Parameters
+    ///
+    /// * `template` - The assembly template as a string slice
+    ///
+    /// # Returns
+    ///
+    /// A new `AsmBuilder` instance with no operands, clobbers, or labels
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let builder = AsmBuilder::new_with_str("rdtsc");
+    /// ```
+    pub fn new_with_str(template: &str) -> Self {
+        Self::new(template.to_string())
+    }
+
+    /// Adds an output operand.
+    ///
+    /// # Parameters
+    ///
+    /// * `operand` - The output operand, whose constraint usually starts with `=` or `+`
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let builder = AsmBuilder::new_with_str("movl %1, %0")
+    ///     .output(AsmOperand::new_with_str("=r", Expr::new_ident_with_str("dst")));
+    /// ```
+    pub fn output(mut self, operand: AsmOperand) -> Self {
+        self.outputs.push(operand);
+        self
+    }
+
+    /// Adds an input operand.
+    ///
+    /// # Parameters
+    ///
+    /// * `operand` - The input operand
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let builder = AsmBuilder::new_with_str("movl %1, %0")
+    ///     .input(AsmOperand::new_with_str("r", Expr::new_ident_with_str("src")));
+    /// ```
+    pub fn input(mut self, operand: AsmOperand) -> Self {
+        self.inputs.push(operand);
+        self
+    }
+
+    /// Adds a clobbered register or resource.
+    ///
+    /// # Parameters
+    ///
+    /// * `clobber` - The clobbered resource, such as `"cc"`, `"memory"`, or a register name
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let builder = AsmBuilder::new_with_str("cpuid").clobber_with_str("memory");
+    /// ```
+    pub fn clobber(mut self, clobber: String) -> Self {
+        self.clobbers.push(clobber);
+        self
+    }
+
+    /// Adds a clobbered register or resource using a string slice.
+    ///
+    /// # Parameters
+    ///
+    /// * `clobber` - The clobbered resource as a string slice
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let builder = AsmBuilder::new_with_str("cpuid").clobber_with_str("cc");
+    /// ```
+    pub fn clobber_with_str(self, clobber: &str) -> Self {
+        self.clobber(clobber.to_string())
+    }
+
+    /// Adds a label the assembly may jump to, which makes the statement an `asm goto`.
+    ///
+    /// # Parameters
+    ///
+    /// * `label` - The name of the C label
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let builder = AsmBuilder::new_with_str("jmp %l0").label_with_str("error");
+    /// ```
+    pub fn label(mut self, label: String) -> Self {
+        self.labels.push(label);
+        self.is_goto = true;
+        self
+    }
+
+    /// Adds a label the assembly may jump to using a string slice, which makes the statement an
+    /// `asm goto`.
+    ///
+    /// # Parameters
+    ///
+    /// * `label` - The name of the C label as a string slice
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let builder = AsmBuilder::new_with_str("jmp %l0").label_with_str("error");
+    /// ```
+    pub fn label_with_str(self, label: &str) -> Self {
+        self.label(label.to_string())
+    }
+
+    /// Makes the statement `volatile`, so that the compiler doesn't optimize it away or move it.
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let builder = AsmBuilder::new_with_str("nop").make_volatile();
+    /// ```
+    pub fn make_volatile(mut self) -> Self {
+        self.is_volatile = true;
+        self
+    }
+
+    /// Makes the statement an `asm goto`, even if no labels are given yet.
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let builder = AsmBuilder::new_with_str("jmp %l0").make_goto();
+    /// ```
+    pub fn make_goto(mut self) -> Self {
+        self.is_goto = true;
+        self
+    }
+
+    /// Consumes the builder and returns the constructed `Asm`.
+    ///
+    /// # Returns
+    ///
+    /// The fully constructed `Asm` instance
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let asm = AsmBuilder::new_with_str("nop").make_volatile().build();
+    /// assert_eq!(asm.to_string(), "__asm__ volatile (\"nop\");\n");
+    /// ```
+    pub fn build(self) -> Asm {
+        Asm {
+            template: self.template,
+            outputs: self.outputs,
+            inputs: self.inputs,
+            clobbers: self.clobbers,
+            labels: self.labels,
+            is_volatile: self.is_volatile,
+            is_goto: self.is_goto,
+        }
+    }
+}
+
+/// Represents an operand of an inline assembly statement, a constraint paired with a C
+/// expression and an optional symbolic name.
+///
+/// # Examples
+///
+/// ```c
+/// [result] "=r"(x)
+/// ```
+#[derive(Debug, Clone, DisplayFromFormat)]
+pub struct AsmOperand {
+    /// The name the operand can be referred to with as `%[name]` in the template
+    pub name: Option<String>,
+
+    /// The constraint, e.g. `"r"`, `"=m"`, or `"+r"`
+    pub constraint: String,
+
+    /// The C expression bound to the operand
+    pub expr: Expr,
+}
+
+impl AsmOperand {
+    /// Creates a new operand.
+    ///
+    /// # Parameters
+    ///
+    /// * `constraint` - The constraint
+    /// * `expr` - The C expression bound to the operand
+    ///
+    /// # Returns
+    ///
+    /// A new `AsmOperand` without a symbolic name
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let operand = AsmOperand::new("=r".to_string(), Expr::new_ident_with_str("x"));
+    /// ```
+    pub fn new(constraint: String, expr: Expr) -> Self {
+        Self {
+            name: None,
+            constraint,
+            expr,
+        }
+    }
+
+    /// Creates a new operand with the constraint given as a string slice.
+    ///
+    /// # Parameters
+    ///
+    /// * `constraint` - The constraint as a string slice
+    /// * `expr` - The C expression bound to the operand
+    ///
+    /// # Returns
+    ///
+    /// A new `AsmOperand` without a symbolic name
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let operand = AsmOperand::new_with_str("r", Expr::new_ident_with_str("y"));
+    /// ```
+    pub fn new_with_str(constraint: &str, expr: Expr) -> Self {
+        Self::new(constraint.to_string(), expr)
+    }
+
+    /// Gives the operand a symbolic name that the template can refer to as `%[name]`.
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - The symbolic name
+    ///
+    /// # Returns
+    ///
+    /// The operand with the symbolic name set
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let operand = AsmOperand::new_with_str("=r", Expr::new_ident_with_str("x")).name_with_str("out");
+    /// ```
+    pub fn name(mut self, name: String) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Gives the operand a symbolic name using a string slice.
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - The symbolic name as a string slice
+    ///
+    /// # Returns
+    ///
+    /// The operand with the symbolic name set
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let operand = AsmOperand::new_with_str("r", Expr::new_ident_with_str("y")).name_with_str("in");
+    /// ```
+    pub fn name_with_str(self, name: &str) -> Self {
+        self.name(name.to_string())
+    }
+}
+
+impl Format for AsmOperand {
+    fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(name) = &self.name {
+            write!(fmt, "[{name}] ")?;
+        }
+
+        write!(fmt, "\"{}\"(", self.constraint)?;
+        self.expr.format(fmt)?;
+        write!(fmt, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn asm() {
+        let nop = AsmBuilder::new_with_str("nop").make_volatile().build();
+        assert_eq!(nop.to_string(), "__asm__ volatile (\"nop\");\n");
+
+        let add = AsmBuilder::new_with_str("addl %2, %0")
+            .output(AsmOperand::new_with_str(
+                "=r",
+                Expr::new_ident_with_str("sum"),
+            ))
+            .input(AsmOperand::new_with_str("0", Expr::new_ident_with_str("a")))
+            .input(AsmOperand::new_with_str("r", Expr::new_ident_with_str("b")))
+            .clobber_with_str("cc")
+            .build();
+        assert_eq!(
+            add.to_string(),
+            "__asm__ (\"addl %2, %0\" : \"=r\"(sum) : \"0\"(a), \"r\"(b) : \"cc\");\n"
+        );
+
+        let fence = AsmBuilder::new_with_str("")
+            .make_volatile()
+            .clobber_with_str("memory")
+            .build();
+        assert_eq!(
+            fence.to_string(),
+            "__asm__ volatile (\"\" : : : \"memory\");\n"
+        );
+
+        let jump = AsmBuilder::new_with_str("testl %[v], %[v]; jz %l1")
+            .make_volatile()
+            .input(AsmOperand::new_with_str("r", Expr::new_ident_with_str("x")).name_with_str("v"))
+            .clobber_with_str("cc")
+            .label_with_str("zero")
+            .build();
+        assert_eq!(
+            jump.to_string(),
+            "__asm__ volatile goto (\"testl %[v], %[v]; jz %l1\" : : [v] \"r\"(x) : \"cc\" : zero);\n"
+        );
+
+        let mut res = String::new();
+        nop.format(&mut Formatter::new_with_dialect(&mut res, Dialect::Msvc))
+            .unwrap();
+        assert_eq!(res, "__asm { nop }\n");
+
+        let mut res = String::new();
+        nop.format(&mut Formatter::new_with_dialect(
+            &mut res,
+            Dialect::Standard,
+        ))
+        .unwrap();
+        assert_eq!(res, "asm(\"nop\");\n");
+
+        let block = Block::new().statement(Statement::Asm(nop)).build();
+        assert_eq!(block.to_string(), "__asm__ volatile (\"nop\");\n");
+    }
+}
@@ -28,15 +28,18 @@
 //! - `Block`: Represents a sequence of statements within curly braces in C
 //! - `BlockBuilder`: Facilitates constructing blocks using the builder pattern
 //! - `Statement`: Enumerates the various statement types that can appear within a block
+//! - `Label`: A label that `goto` statements jump to, optionally with the statement it labels
+//! - `LabelBuilder`: Facilitates constructing labels using the builder pattern
 //!
 //! Use this module to programmatically generate well-structured C code with proper
 //! scoping and nesting of statements.
 
 use std::fmt::{self, Write};
+use std::ops::RangeBounds;
 
 use crate::{
-    Comment, DoWhile, ErrorDirective, Expr, For, Format, Formatter, If, IfDefDirective,
-    IfDirective, Include, LineDirective, Macro, PragmaDirective, Switch, Variable,
+    Asm, Comment, DoWhile, ErrorDirective, Expr, For, Format, Formatter, If, IfDefDirective,
+    IfDirective, Include, LineDirective, Macro, PragmaDirective, ScopeOrBlock, Switch, Variable,
     WarningDirective, While,
 };
 use tamacro::DisplayFromFormat;
@@ -100,6 +103,186 @@ impl Block {
     pub fn new() -> BlockBuilder {
         BlockBuilder::new()
     }
+
+    /// Inserts a statement at the start of the block.
+    ///
+    /// # Parameters
+    ///
+    /// * `stmt` - The statement to insert
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// block.prepend(Statement::Raw("trace_enter();".to_string()));
+    /// ```
+    pub fn prepend(&mut self, stmt: Statement) {
+        self.stmts.insert(0, stmt);
+    }
+
+    /// Inserts a statement at position `idx`, shifting the ones after it.
+    ///
+    /// # Parameters
+    ///
+    /// * `idx` - The position the statement will be at
+    /// * `stmt` - The statement to insert
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is greater than the number of statements.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// block.insert_at(2, Statement::NewLine);
+    /// ```
+    pub fn insert_at(&mut self, idx: usize, stmt: Statement) {
+        self.stmts.insert(idx, stmt);
+    }
+
+    /// Inserts a statement right after the one at position `idx`.
+    ///
+    /// # Parameters
+    ///
+    /// * `idx` - The position of the statement to insert after
+    /// * `stmt` - The statement to insert
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no statement at `idx`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// block.insert_after(0, Statement::NewLine);
+    /// ```
+    pub fn insert_after(&mut self, idx: usize, stmt: Statement) {
+        assert!(
+            idx < self.stmts.len(),
+            "index {idx} is out of bounds for {} statements",
+            self.stmts.len()
+        );
+        self.stmts.insert(idx + 1, stmt);
+    }
+
+    /// Replaces the statements in `range` with `replacement`, returning the removed ones.
+    ///
+    /// # Parameters
+    ///
+    /// * `range` - The positions of the statements to remove
+    /// * `replacement` - The statements to insert in their place
+    ///
+    /// # Returns
+    ///
+    /// The removed statements
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let removed = block.splice(1..3, vec![Statement::NewLine]);
+    /// ```
+    pub fn splice<R, I>(&mut self, range: R, replacement: I) -> Vec<Statement>
+    where
+        R: RangeBounds<usize>,
+        I: IntoIterator<Item = Statement>,
+    {
+        self.stmts.splice(range, replacement).collect()
+    }
+
+    /// Keeps only the statements for which `f` returns `true`.
+    ///
+    /// # Parameters
+    ///
+    /// * `f` - The predicate deciding which statements to keep
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// // Removes all the blank lines
+    /// block.retain(|stmt| !matches!(stmt, Statement::NewLine));
+    /// ```
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&Statement) -> bool,
+    {
+        self.stmts.retain(f);
+    }
+
+    /// Turns the block into a statement of its own, so that the locals declared in it are only
+    /// visible inside it.
+    ///
+    /// # Returns
+    ///
+    /// A `Statement::Block` holding the block
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let swap = Block::new()
+    ///     .statement(Statement::Raw("int tmp = a;".to_string()))
+    ///     .statement(Statement::Raw("a = b;".to_string()))
+    ///     .statement(Statement::Raw("b = tmp;".to_string()))
+    ///     .build()
+    ///     .scoped();
+    /// ```
+    pub fn scoped(self) -> Statement {
+        Statement::Block(self)
+    }
+
+    /// Inserts a probe before every statement that runs, including the ones nested in branches
+    /// and loops, for coverage and tracing.
+    ///
+    /// The statements are numbered from 0 in the order they are written, with a statement coming
+    /// before the ones nested in it, so the same block always gets the same IDs. Comments, blank
+    /// lines, labels, and preprocessor directives aren't numbered, though the statements in the
+    /// branches of `#if` and `#ifdef` are. A probe goes after the labels before its statement, so
+    /// that jumping to the label runs it.
+    ///
+    /// # Parameters
+    ///
+    /// * `probe` - Returns the expression to evaluate before the statement with the given ID
+    ///
+    /// # Returns
+    ///
+    /// The number of statements probed, e.g. for the length of an array of counters
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// // Counts how many times each statement runs
+    /// let count = body.instrument(|id| {
+    ///     Expr::new_unary(
+    ///         Expr::new_arr_index(Expr::new_ident_with_str("coverage"), Expr::UInt(id as u64)),
+    ///         UnaryOp::PostIncrement,
+    ///     )
+    /// });
+    /// ```
+    pub fn instrument<F>(&mut self, mut probe: F) -> usize
+    where
+        F: FnMut(usize) -> Expr,
+    {
+        let mut next_id = 0;
+        self.instrument_from(&mut next_id, &mut probe);
+        next_id
+    }
+
+    fn instrument_from(&mut self, next_id: &mut usize, probe: &mut dyn FnMut(usize) -> Expr) {
+        let mut stmts = Vec::with_capacity(self.stmts.len() * 2);
+        for mut stmt in std::mem::take(&mut self.stmts) {
+            if stmt.is_executed() {
+                stmts.push(Statement::Expr(probe(*next_id)));
+                *next_id += 1;
+            }
+            for block in stmt.blocks_mut() {
+                block.instrument_from(next_id, probe);
+            }
+            stmts.push(stmt);
+        }
+        self.stmts = stmts;
+    }
 }
 
 impl Format for Block {
@@ -298,6 +481,7 @@ impl BlockBuilder {
 /// Each variant corresponds to a specific type of C statement or directive,
 /// with associated data where necessary.
 #[derive(Debug, Clone, DisplayFromFormat)]
+#[non_exhaustive]
 pub enum Statement {
     /// A C-style comment (either line comment `//` or block comment `/* */`)
     Comment(Comment),
@@ -337,10 +521,11 @@ pub enum Statement {
     /// Example: `goto error_handler;`
     GoTo(String),
 
-    /// A label declaration that can be targeted by goto statements
+    /// A label declaration that can be targeted by goto statements, written at the indentation
+    /// of the enclosing block
     ///
-    /// Example: `some_label:`
-    Label(String),
+    /// Example: `cleanup: free(buf);`
+    Label(Label),
 
     /// An if statement with optional else and else if branches
     ///
@@ -367,6 +552,18 @@ pub enum Statement {
     /// Example: `for (int i = 0; i < 10; i++) { ... }`
     For(For),
 
+    /// A compound statement on its own, which opens a new scope for the locals declared in it
+    ///
+    /// Example: `{ int tmp = a; a = b; b = tmp; }`
+    Block(Block),
+
+    /// A loop with a label, which `Statement::break_to` and `Statement::continue_to` can jump
+    /// out of from nested loops. The `{label}_continue` and `{label}_end` labels they jump to
+    /// are only emitted if they are used.
+    ///
+    /// Example: `while (a) { ... outer_continue:; } outer_end:;`
+    Labelled(String, Box<Statement>),
+
     /// A `#error` preprocessor directive that causes compilation to fail with a message
     ///
     /// Example: `#error "This platform is not supported"`
@@ -411,6 +608,11 @@ pub enum Statement {
     /// Example: `#warning "This code is deprecated!"`
     WarningDirective(WarningDirective),
 
+    /// An inline assembly statement
+    ///
+    /// Example: `__asm__ volatile ("" : : : "memory");`
+    Asm(Asm),
+
     /// Raw C code inserted verbatim without processing
     ///
     /// Useful for edge cases not covered by other statement types
@@ -422,12 +624,318 @@ pub enum Statement {
     NewLine,
 }
 
+impl Statement {
+    /// Returns a `return` of the value of `expr`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// assert_eq!(Statement::ret(Expr::Int(0)).to_string(), "return 0;\n");
+    /// ```
+    pub fn ret(expr: Expr) -> Self {
+        Self::Return(Some(expr))
+    }
+
+    /// Returns a `return` without a value, for functions returning `void`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// assert_eq!(Statement::ret_void().to_string(), "return;\n");
+    /// ```
+    pub fn ret_void() -> Self {
+        Self::Return(None)
+    }
+
+    /// Returns a statement evaluating `expr`, such as a call or an assignment.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let call = Statement::expr(Expr::new_fn_call_with_name("tick".to_string(), vec![]));
+    /// ```
+    pub fn expr(expr: Expr) -> Self {
+        Self::Expr(expr)
+    }
+
+    /// Returns a statement declaring `var`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let i = VariableBuilder::new_with_str("i", Type::new(BaseType::Int).build()).build();
+    /// let decl = Statement::decl(i);
+    /// ```
+    pub fn decl(var: Variable) -> Self {
+        Self::Variable(var)
+    }
+
+    /// Returns a statement printing a message to `stderr`, prefixed with the file and line of
+    /// the generated C.
+    ///
+    /// # Parameters
+    ///
+    /// * `fmt` - The `printf` format of the message, written into the C string as is, so it
+    ///   must already be escaped
+    /// * `args` - The values for the conversions in `fmt`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let log = Statement::debug_log("n = %d", vec![Expr::new_ident_with_str("n")]);
+    /// assert_eq!(
+    ///     log.to_string(),
+    ///     "fprintf(stderr, \"%s:%d: n = %d\\n\", __FILE__, __LINE__, n);\n"
+    /// );
+    /// ```
+    pub fn debug_log(fmt: &str, args: Vec<Expr>) -> Self {
+        let mut all_args = vec![
+            Expr::new_ident_with_str("stderr"),
+            Expr::Str(format!("%s:%d: {fmt}\\n")),
+            Expr::file(),
+            Expr::line(),
+        ];
+        all_args.extend(args);
+
+        Self::Expr(Expr::new_fn_call_with_name("fprintf".to_string(), all_args))
+    }
+
+    /// Returns a label that `goto` statements can jump to, such as the cleanup at the end of a
+    /// function.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// assert_eq!(Statement::label("cleanup").to_string(), "cleanup:\n");
+    /// ```
+    pub fn label(name: &str) -> Self {
+        Self::Label(LabelBuilder::new_with_str(name).build())
+    }
+
+    /// Labels a loop so that nested loops can break out of it or continue it.
+    ///
+    /// # Parameters
+    ///
+    /// * `label` - The label, which `Scope::unique_label` can generate
+    /// * `stmt` - The loop to label
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let outer = Statement::labelled("outer", Statement::While(while_loop));
+    /// ```
+    pub fn labelled(label: &str, stmt: Statement) -> Self {
+        Self::Labelled(label.to_string(), Box::new(stmt))
+    }
+
+    /// Returns a `goto` that exits the loop labelled `label`, like a labelled `break`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// assert_eq!(Statement::break_to("outer").to_string(), "goto outer_end;\n");
+    /// ```
+    pub fn break_to(label: &str) -> Self {
+        Self::GoTo(format!("{label}_end"))
+    }
+
+    /// Returns a `goto` that skips to the next iteration of the loop labelled `label`, like a
+    /// labelled `continue`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// assert_eq!(Statement::continue_to("outer").to_string(), "goto outer_continue;\n");
+    /// ```
+    pub fn continue_to(label: &str) -> Self {
+        Self::GoTo(format!("{label}_continue"))
+    }
+
+    /// Returns the statements directly nested in the statement, in the order they are written.
+    ///
+    /// These are the statements in the branches of `if` and `switch` statements, the bodies of
+    /// loops, bare blocks, the statement after a label, and the branches of `#ifdef` and `#if` directives that
+    /// hold blocks. Statements nested deeper aren't returned.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let stmt = Statement::While(
+    ///     WhileBuilder::new(Expr::Bool(true))
+    ///         .statement(Statement::Break)
+    ///         .build(),
+    /// );
+    /// assert_eq!(stmt.children().count(), 1);
+    /// ```
+    pub fn children(&self) -> impl Iterator<Item = &Statement> {
+        use Statement::*;
+
+        let mut blocks = vec![];
+        match self {
+            If(i) => blocks.extend([Some(&i.then), i.other.as_ref()].into_iter().flatten()),
+            Switch(s) => {
+                blocks.extend(s.cases.iter().map(|(_, body)| body));
+                blocks.extend(&s.default);
+            }
+            While(crate::While { body, .. })
+            | DoWhile(crate::DoWhile { body, .. })
+            | For(crate::For { body, .. })
+            | Block(body) => blocks.push(body),
+            IfDefDirective(crate::IfDefDirective { then, other, .. })
+            | IfDirective(crate::IfDirective { then, other, .. }) => blocks.extend(
+                [Some(then), other.as_ref()]
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|branch| match branch {
+                        ScopeOrBlock::Block(block) => Some(block),
+                        ScopeOrBlock::Scope(_) => None,
+                    }),
+            ),
+            _ => {}
+        }
+
+        let labelled = match self {
+            Labelled(_, stmt) => Some(&**stmt),
+            Label(label) => label.stmt.as_deref(),
+            _ => None,
+        };
+
+        labelled
+            .into_iter()
+            .chain(blocks.into_iter().flat_map(|block| &block.stmts))
+    }
+
+    /// Returns the expressions directly in the statement, in the order they are written.
+    ///
+    /// These are conditions, the clauses of `for` loops, `case` values, returned values, the
+    /// initial values of variables, and the operands of inline assembly, but not the expressions
+    /// of nested statements, which can be reached through [`Statement::children`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let stmt = Statement::Return(Some(Expr::Int(0)));
+    /// assert_eq!(stmt.exprs().count(), 1);
+    /// ```
+    pub fn exprs(&self) -> impl Iterator<Item = &Expr> {
+        use Statement::*;
+
+        let exprs: Vec<&crate::Expr> = match self {
+            Variable(v) => v.value.iter().collect(),
+            Expr(e) | Return(Some(e)) => vec![e],
+            If(i) => vec![&i.cond],
+            Switch(s) => std::iter::once(&s.cond)
+                .chain(s.cases.iter().map(|(case, _)| case))
+                .collect(),
+            While(w) => vec![&w.cond],
+            DoWhile(w) => vec![&w.cond],
+            For(f) => [&f.init, &f.cond, &f.step].into_iter().flatten().collect(),
+            Asm(a) => a
+                .outputs
+                .iter()
+                .chain(&a.inputs)
+                .map(|operand| &operand.expr)
+                .collect(),
+            _ => vec![],
+        };
+
+        exprs.into_iter()
+    }
+
+    /// The blocks directly nested in the statement, like [`Statement::children`] but mutable and
+    /// through the loop of a labelled loop.
+    fn blocks_mut(&mut self) -> Vec<&mut Block> {
+        use Statement::*;
+
+        match self {
+            If(i) => [Some(&mut i.then), i.other.as_mut()].into_iter().flatten().collect(),
+            Switch(s) => s
+                .cases
+                .iter_mut()
+                .map(|(_, body)| body)
+                .chain(&mut s.default)
+                .collect(),
+            While(crate::While { body, .. })
+            | DoWhile(crate::DoWhile { body, .. })
+            | For(crate::For { body, .. })
+            | Block(body) => vec![body],
+            Labelled(_, stmt) => stmt.blocks_mut(),
+            Label(label) => label.stmt.as_mut().map_or(vec![], |stmt| stmt.blocks_mut()),
+            IfDefDirective(crate::IfDefDirective { then, other, .. })
+            | IfDirective(crate::IfDirective { then, other, .. }) => [Some(then), other.as_mut()]
+                .into_iter()
+                .flatten()
+                .filter_map(|branch| match branch {
+                    ScopeOrBlock::Block(block) => Some(block),
+                    ScopeOrBlock::Scope(_) => None,
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Whether the statement does something when it is reached, unlike comments, labels without
+    /// a statement, and preprocessor directives.
+    fn is_executed(&self) -> bool {
+        use Statement::*;
+
+        if let Label(label) = self {
+            return label.stmt.as_ref().is_some_and(|stmt| stmt.is_executed());
+        }
+
+        !matches!(
+            self,
+            Comment(_)
+                | ErrorDirective(_)
+                | IfDefDirective(_)
+                | IfDirective(_)
+                | Include(_)
+                | LineDirective(_)
+                | Macro(_)
+                | PragmaDirective(_)
+                | WarningDirective(_)
+                | NewLine
+        )
+    }
+
+    /// Calls `f` on the statement and on every statement nested in it.
+    pub(crate) fn walk(&self, f: &mut dyn FnMut(&Statement)) {
+        f(self);
+
+        for child in self.children() {
+            child.walk(f);
+        }
+    }
+
+    /// Whether the statement contains a `goto label`.
+    fn jumps_to(&self, label: &str) -> bool {
+        let mut found = false;
+        self.walk(&mut |stmt| {
+            found |= matches!(stmt, Statement::GoTo(l) if l == label);
+        });
+        found
+    }
+}
+
 impl Format for Statement {
     fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
         use Statement::*;
         match self {
             Comment(comment) => comment.format(fmt),
             Variable(variable) => {
+                // A static local is initialized only once, before the program starts, so C only
+                // allows a constant initializer
+                if variable.is_static
+                    && let Some(value) = &variable.value
+                    && !value.is_constant()
+                {
+                    fmt.warn(format!(
+                        "The static local variable '{}' must be initialized with a constant \
+                         expression",
+                        variable.name
+                    ));
+                }
+
                 variable.format(fmt)?;
                 writeln!(fmt, ";")
             }
@@ -444,12 +952,35 @@ impl Format for Statement {
             Break => writeln!(fmt, "break;"),
             Continue => writeln!(fmt, "continue;"),
             GoTo(s) => writeln!(fmt, "goto {s};"),
-            Label(s) => writeln!(fmt, "{s}:"),
+            Label(label) => label.format(fmt),
             If(i) => i.format(fmt),
             Switch(s) => s.format(fmt),
             While(w) => w.format(fmt),
             DoWhile(w) => w.format(fmt),
             For(f) => f.format(fmt),
+            Block(block) => {
+                fmt.block(|fmt| block.format(fmt))?;
+                writeln!(fmt)
+            }
+            Labelled(label, stmt) => {
+                let next = format!("{label}_continue");
+                let end = format!("{label}_end");
+
+                let mut stmt = stmt.clone();
+                if let While(crate::While { body, .. })
+                | DoWhile(crate::DoWhile { body, .. })
+                | For(crate::For { body, .. }) = &mut *stmt
+                    && body.stmts.iter().any(|s| s.jumps_to(&next))
+                {
+                    body.stmts.push(Raw(format!("{next}:;")));
+                }
+                stmt.format(fmt)?;
+
+                if stmt.jumps_to(&end) {
+                    writeln!(fmt, "{end}:;")?;
+                }
+                Ok(())
+            }
             ErrorDirective(e) => e.format(fmt),
             IfDefDirective(i) => i.format(fmt),
             IfDirective(i) => i.format(fmt),
@@ -458,17 +989,205 @@ impl Format for Statement {
             Macro(m) => m.format(fmt),
             PragmaDirective(p) => p.format(fmt),
             WarningDirective(w) => w.format(fmt),
-            Raw(s) => writeln!(fmt, "{s}"),
+            Asm(a) => a.format(fmt),
+            Raw(s) => {
+                fmt.lint_raw(s);
+                writeln!(fmt, "{s}")
+            }
             NewLine => writeln!(fmt),
         }
     }
 }
 
+/// Represents a label in C, which `goto` statements jump to, optionally followed by the
+/// statement it labels.
+///
+/// The label is written at the indentation of the block enclosing the one it is in, so it
+/// stands out from the statements around it, and the statement it labels on the next line.
+///
+/// ## C Syntax
+/// ```c
+/// int read_config(void) {
+///   FILE *f = fopen("config", "r");
+///   if (!f) {
+///     goto fail;
+///   }
+///   return 0;
+/// fail:
+///   return -1;
+/// }
+/// ```
+///
+/// ## Example Usage
+/// ```rust
+/// let fail = Label::new("fail")
+///     .statement(Statement::ret(Expr::Int(-1)))
+///     .build();
+/// ```
+#[derive(Debug, Clone, DisplayFromFormat)]
+pub struct Label {
+    /// The name of the label, which `Statement::GoTo` refers to
+    pub name: String,
+
+    /// The statement the label is on, if any. A label without one has to be followed by
+    /// another statement in the block before C23.
+    pub stmt: Option<Box<Statement>>,
+}
+
+impl Label {
+    /// Creates a new `LabelBuilder` for a label named `name`.
+    ///
+    /// ## Example
+    /// ```rust
+    /// let cleanup = Label::new("cleanup").build();
+    /// ```
+    pub fn new(name: &str) -> LabelBuilder {
+        LabelBuilder::new_with_str(name)
+    }
+}
+
+impl Format for Label {
+    fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        let spaces = fmt.spaces;
+        fmt.spaces = spaces.saturating_sub(fmt.indent);
+        let res = writeln!(fmt, "{}:", self.name);
+        fmt.spaces = spaces;
+        res?;
+
+        if let Some(stmt) = &self.stmt {
+            stmt.format(fmt)?;
+        }
+        Ok(())
+    }
+}
+
+/// A builder for constructing a `Label` instance.
+pub struct LabelBuilder {
+    name: String,
+    stmt: Option<Box<Statement>>,
+}
+
+impl LabelBuilder {
+    /// Creates a new `LabelBuilder` for a label named `name`, without a statement.
+    ///
+    /// ## Example
+    /// ```rust
+    /// let builder = LabelBuilder::new("cleanup".to_string());
+    /// ```
+    pub fn new(name: String) -> Self {
+        Self { name, stmt: None }
+    }
+
+    /// Creates a new `LabelBuilder` for a label named `name`, given as a string slice.
+    pub fn new_with_str(name: &str) -> Self {
+        Self::new(name.to_string())
+    }
+
+    /// Sets the statement the label is on.
+    ///
+    /// ## Example
+    /// ```rust
+    /// let builder = LabelBuilder::new_with_str("cleanup")
+    ///     .statement(Statement::expr(expr!("free(buf)")));
+    /// ```
+    pub fn statement(mut self, stmt: Statement) -> Self {
+        self.stmt = Some(Box::new(stmt));
+        self
+    }
+
+    /// Consumes the builder and creates a `Label` instance.
+    pub fn build(self) -> Label {
+        Label {
+            name: self.name,
+            stmt: self.stmt,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::*;
 
+    #[test]
+    fn labels() {
+        let body = BlockBuilder::new()
+            .statement(Statement::If(
+                IfBuilder::new(Expr::new_ident_with_str("failed"))
+                    .statement_to_then(Statement::GoTo("cleanup".to_string()))
+                    .build(),
+            ))
+            .statement(Statement::Label(
+                Label::new("cleanup")
+                    .statement(Statement::expr(Expr::new_fn_call_with_name(
+                        "free".to_string(),
+                        vec![Expr::new_ident_with_str("buf")],
+                    )))
+                    .build(),
+            ))
+            .statement(Statement::label("done"))
+            .statement(Statement::ret_void())
+            .build();
+        let res = r#"{
+  if (failed) {
+    goto cleanup;
+  }
+cleanup:
+  free(buf);
+done:
+  return;
+}
+"#;
+        assert_eq!(Statement::Block(body.clone()).to_string(), res);
+
+        // Outside of a block there is nothing to outdent from
+        assert_eq!(body.stmts[1].to_string(), "cleanup:\nfree(buf);\n");
+
+        let mut body = body;
+        assert_eq!(body.instrument(|_| Expr::Int(0)), 4);
+    }
+
+    #[test]
+    fn labelled_loops() {
+        let inner = WhileBuilder::new(Expr::Ident("b".to_string()))
+            .body(
+                BlockBuilder::new()
+                    .statement(Statement::continue_to("outer"))
+                    .statement(Statement::break_to("outer"))
+                    .build(),
+            )
+            .build();
+        let outer = Statement::labelled(
+            "outer",
+            Statement::While(
+                WhileBuilder::new(Expr::Ident("a".to_string()))
+                    .body(BlockBuilder::new().statement(Statement::While(inner)).build())
+                    .build(),
+            ),
+        );
+        let res = r#"while (a) {
+  while (b) {
+    goto outer_continue;
+    goto outer_end;
+  }
+  outer_continue:;
+}
+outer_end:;
+"#;
+        assert_eq!(outer.to_string(), res);
+
+        // Unused labels are not emitted
+        let s = Statement::labelled(
+            "outer",
+            Statement::While(
+                WhileBuilder::new(Expr::Ident("a".to_string()))
+                    .body(BlockBuilder::new().statement(Statement::Break).build())
+                    .build(),
+            ),
+        );
+        assert_eq!(s.to_string(), "while (a) {\n  break;\n}\n");
+    }
+
     #[test]
     fn statement() {
         let mut s = Statement::Comment(Comment::new().comment_with_str("Hello").build());
@@ -495,7 +1214,7 @@ mod tests {
         s = Statement::GoTo("some_label".to_string());
         assert_eq!(s.to_string(), "goto some_label;\n");
 
-        s = Statement::Label("some_label".to_string());
+        s = Statement::label("some_label");
         assert_eq!(s.to_string(), "some_label:\n");
     }
 
@@ -521,4 +1240,239 @@ mod tests {
 
         assert_eq!(b2.to_string(), "something else\nabc;\n\n\nsome_func();\n");
     }
+
+    #[test]
+    fn block_editing() {
+        let mut b = Block::new()
+            .statement(Statement::Raw("a();".to_string()))
+            .statement(Statement::Raw("b();".to_string()))
+            .statement(Statement::Return(None))
+            .build();
+
+        b.prepend(Statement::Raw("trace_enter();".to_string()));
+        b.insert_at(1, Statement::NewLine);
+        b.insert_after(3, Statement::Raw("c();".to_string()));
+        assert_eq!(
+            b.to_string(),
+            "trace_enter();\n\na();\nb();\nc();\nreturn;\n"
+        );
+
+        let removed = b.splice(2..4, vec![Statement::Raw("ab();".to_string())]);
+        assert_eq!(removed.len(), 2);
+        assert_eq!(b.to_string(), "trace_enter();\n\nab();\nc();\nreturn;\n");
+
+        b.retain(|stmt| !matches!(stmt, Statement::NewLine));
+        b.splice(b.stmts.len() - 1.., []);
+        assert_eq!(b.to_string(), "trace_enter();\nab();\nc();\n");
+    }
+    #[test]
+    fn static_locals() {
+        let int = Type::new(BaseType::Int).build();
+        let b = Block::new()
+            .statement(Statement::decl(
+                VariableBuilder::new_with_str("calls", int.clone())
+                    .value(Expr::Int(0))
+                    .make_static()
+                    .build(),
+            ))
+            .statement(Statement::decl(
+                VariableBuilder::new_with_str("cached", int.clone())
+                    .value(Expr::new_fn_call_with_name("compute".to_string(), vec![]))
+                    .make_static()
+                    .build(),
+            ))
+            .statement(Statement::decl(
+                VariableBuilder::new_with_str("now", int)
+                    .value(Expr::new_fn_call_with_name("compute".to_string(), vec![]))
+                    .build(),
+            ))
+            .build();
+
+        let mut res = String::new();
+        let mut fmt = Formatter::new(&mut res);
+        b.format(&mut fmt).unwrap();
+
+        assert_eq!(
+            fmt.warnings(),
+            ["The static local variable 'cached' must be initialized with a constant expression"]
+        );
+        assert_eq!(
+            res,
+            "static int calls = 0;\nstatic int cached = compute();\nint now = compute();\n"
+        );
+    }
+
+    #[test]
+    fn raw_checks() {
+        let b = Block::new()
+            .statements(
+                [
+                    "a();",
+                    "b()",
+                    "c();;",
+                    "if (x) { y(); }",
+                    "done:",
+                    "#define N 4",
+                    "puts(\"(\"); // ) {",
+                    "f(g(1);",
+                    "xs[0));",
+                    "z = 1; }",
+                ]
+                .map(|raw| Statement::Raw(raw.to_string()))
+                .to_vec(),
+            )
+            .build();
+
+        let mut res = String::new();
+        let mut fmt = Formatter::new(&mut res);
+        fmt.check_raw = true;
+        b.format(&mut fmt).unwrap();
+
+        assert_eq!(
+            fmt.warnings(),
+            [
+                "Raw statement 'b()' is missing a terminating ';'",
+                "Raw statement 'c();;' ends with an extra ';'",
+                "Raw statement 'f(g(1);' has an unclosed '('",
+                "Raw statement 'xs[0));' closes '[' with ')'",
+                "Raw statement 'z = 1; }' has an unmatched '}'",
+            ]
+        );
+
+        // The code is emitted as it is either way
+        assert!(res.contains("b()\n"));
+    }
+    #[test]
+    fn statement_shorthands() {
+        let b = Block::new()
+            .statement(Statement::decl(
+                VariableBuilder::new_with_str("i", Type::new(BaseType::Int).build())
+                    .value(Expr::Int(0))
+                    .build(),
+            ))
+            .statement(Statement::expr(Expr::new_fn_call_with_name(
+                "tick".to_string(),
+                vec![],
+            )))
+            .statement(Statement::ret(Expr::Ident("i".to_string())))
+            .statement(Statement::ret_void())
+            .build();
+
+        assert_eq!(b.to_string(), "int i = 0;\ntick();\nreturn i;\nreturn;\n");
+    }
+
+    #[test]
+    fn instrument() {
+        let lp = WhileBuilder::new(Expr::new_ident_with_str("a"))
+            .body(
+                BlockBuilder::new()
+                    .statement(Statement::label("again"))
+                    .statement(Statement::Raw("f();".to_string()))
+                    .statement(Statement::Break)
+                    .build(),
+            )
+            .build();
+        let mut body = BlockBuilder::new()
+            .statement(Statement::Comment(
+                Comment::new().comment_with_str("Loops").build(),
+            ))
+            .statement(Statement::labelled("outer", Statement::While(lp)))
+            .new_line()
+            .statement(Statement::ret(Expr::Int(0)))
+            .build();
+
+        let count = body.instrument(|id| {
+            Expr::new_fn_call_with_name("hit".to_string(), vec![Expr::UInt(id as u64)])
+        });
+        assert_eq!(count, 4);
+        let res = r#"// Loops
+hit(0);
+while (a) {
+again:
+  hit(1);
+  f();
+  hit(2);
+  break;
+}
+
+hit(3);
+return 0;
+"#;
+        assert_eq!(body.to_string(), res);
+    }
+
+    #[test]
+    fn debug_log() {
+        let log = Statement::debug_log(
+            "%s failed with %d",
+            vec![Expr::func(), Expr::new_ident_with_str("err")],
+        );
+        assert_eq!(
+            log.to_string(),
+            "fprintf(stderr, \"%s:%d: %s failed with %d\\n\", __FILE__, __LINE__, __func__, \
+             err);\n"
+        );
+    }
+
+    #[test]
+    fn children() {
+        let body = BlockBuilder::new()
+            .statement(Statement::Expr(Expr::new_ident_with_str("a")))
+            .statement(Statement::Break)
+            .build();
+        let stmt = Statement::For(
+            ForBuilder::new()
+                .init(Expr::Int(0))
+                .step(Expr::Int(1))
+                .body(body)
+                .build(),
+        );
+        assert_eq!(stmt.children().count(), 2);
+        assert_eq!(
+            stmt.exprs().map(|e| e.to_string()).collect::<Vec<_>>(),
+            ["0", "1"]
+        );
+
+        let labelled = Statement::Labelled("l".to_string(), Box::new(stmt));
+        assert!(matches!(
+            labelled.children().collect::<Vec<_>>()[..],
+            [Statement::For(_)]
+        ));
+        assert_eq!(labelled.exprs().count(), 0);
+        assert!(Statement::Break.children().next().is_none());
+    }
+    #[test]
+    fn scoped_blocks() {
+        let swap = BlockBuilder::new()
+            .statement(Statement::Variable(
+                VariableBuilder::new_with_str("tmp", Type::new(BaseType::Int).build())
+                    .value(Expr::new_ident_with_str("a"))
+                    .build(),
+            ))
+            .statement(Statement::Raw("a = b;".to_string()))
+            .statement(
+                BlockBuilder::new()
+                    .statement(Statement::Raw("b = tmp;".to_string()))
+                    .build()
+                    .scoped(),
+            )
+            .build()
+            .scoped();
+        assert_eq!(swap.children().count(), 3);
+
+        let f = FunctionBuilder::new_with_str("swap", Type::new(BaseType::Void).build())
+            .statement(swap)
+            .build();
+        let res = r#"void swap(void) {
+  {
+    int tmp = a;
+    a = b;
+    {
+      b = tmp;
+    }
+  }
+}
+"#;
+        assert_eq!(f.to_string(), res);
+    }
 }
@@ -27,6 +27,7 @@
 //! - `CommentBuilder`: Facilitates constructing comments using the builder pattern
 //! - `DocComment`: Represents documentation comments in C (using `///` style)
 //! - `DocCommentBuilder`: Facilitates constructing documentation comments
+//! - `FileHeader`: Represents a license or banner block comment at the top of a file
 //!
 //! Use this module to add properly formatted comments to generated C code for improved
 //! readability, documentation, and code organization.
@@ -127,6 +128,10 @@ impl Comment {
 
 impl Format for Comment {
     fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        if fmt.is_compact() {
+            return Ok(());
+        }
+
         self.push_heading(fmt)?;
         for line in self.comment.lines() {
             writeln!(fmt, "// {line}")?;
@@ -266,6 +271,97 @@ impl CommentBuilder {
         self
     }
 
+    /// Appends each item as a separate line of the comment.
+    ///
+    /// This is useful when the comment is generated from structured data, since the lines
+    /// don't have to be joined beforehand.
+    ///
+    /// ## Parameters
+    ///
+    /// - `lines`: The lines to append
+    ///
+    /// ## Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// let fields = ["x", "y", "z"];
+    /// let comment = CommentBuilder::new()
+    ///     .lines(fields.iter().map(|f| format!("{f}: the {f} coordinate")))
+    ///     .build();
+    /// ```
+    pub fn lines(mut self, lines: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        for line in lines {
+            self.push_line(&line.into());
+        }
+        self
+    }
+
+    /// Appends a markdown bullet point (`- text`) to the comment.
+    ///
+    /// If the text spans multiple lines, the following lines are indented so that they line up
+    /// with the first one.
+    ///
+    /// ## Parameters
+    ///
+    /// - `text`: The text of the bullet point
+    ///
+    /// ## Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// let comment = CommentBuilder::new()
+    ///     .comment_with_str("Supported formats:")
+    ///     .bullet("PNG")
+    ///     .bullet("JPEG")
+    ///     .build();
+    /// ```
+    pub fn bullet(mut self, text: &str) -> Self {
+        for line in bullet_lines(text) {
+            self.push_line(&line);
+        }
+        self
+    }
+
+    /// Appends a fenced markdown code block to the comment.
+    ///
+    /// ## Parameters
+    ///
+    /// - `lang`: The language of the code, which may be empty
+    /// - `text`: The code, which may span multiple lines
+    ///
+    /// ## Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// let comment = CommentBuilder::new()
+    ///     .comment_with_str("Usage:")
+    ///     .code_block("c", "int x = parse(\"42\");")
+    ///     .build();
+    /// ```
+    pub fn code_block(mut self, lang: &str, text: &str) -> Self {
+        for line in code_block_lines(lang, text) {
+            self.push_line(&line);
+        }
+        self
+    }
+
+    /// Internal helper method to append a line to the comment text.
+    fn push_line(&mut self, line: &str) {
+        if !self.comment.is_empty() {
+            self.comment.push('\n');
+        }
+        self.comment.push_str(line);
+    }
+
     /// Consumes the builder and returns a new `Comment` instance.
     ///
     /// This method finalizes the building process and returns the constructed comment
@@ -314,7 +410,7 @@ impl CommentBuilder {
 /// /// @return The square of the input value
 /// int square(int value);
 /// ```
-#[derive(Debug, Clone, DisplayFromFormat)]
+#[derive(Debug, Clone, PartialEq, Eq, DisplayFromFormat)]
 pub struct DocComment {
     /// Lines of documentation comment text.
     /// Each line will be prefixed with `///` during formatting.
@@ -344,6 +440,10 @@ impl DocComment {
 
 impl Format for DocComment {
     fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        if fmt.is_compact() {
+            return Ok(());
+        }
+
         for line in &self.docs {
             writeln!(fmt, "/// {line}")?;
         }
@@ -514,6 +614,83 @@ impl DocCommentBuilder {
         res
     }
 
+    /// Appends each item as a separate line of the documentation comment.
+    ///
+    /// Unlike `text`, the lines are added as they are, without splitting or wrapping.
+    ///
+    /// ## Parameters
+    ///
+    /// - `lines`: The lines to append
+    ///
+    /// ## Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// let params = [("width", "The width in pixels"), ("height", "The height in pixels")];
+    /// let doc = DocCommentBuilder::new()
+    ///     .line_str("Creates a new image")
+    ///     .lines(params.iter().map(|(name, desc)| format!("@param {name} {desc}")))
+    ///     .build();
+    /// ```
+    pub fn lines(mut self, lines: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.docs.extend(lines.into_iter().map(Into::into));
+        self
+    }
+
+    /// Appends a markdown bullet point (`- text`) to the documentation comment.
+    ///
+    /// If the text spans multiple lines, the following lines are indented so that they line up
+    /// with the first one.
+    ///
+    /// ## Parameters
+    ///
+    /// - `text`: The text of the bullet point
+    ///
+    /// ## Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// let doc = DocCommentBuilder::new()
+    ///     .line_str("Errors:")
+    ///     .bullet("`EINVAL` if the buffer is NULL")
+    ///     .bullet("`ENOMEM` if allocation fails")
+    ///     .build();
+    /// ```
+    pub fn bullet(mut self, text: &str) -> Self {
+        self.docs.extend(bullet_lines(text));
+        self
+    }
+
+    /// Appends a fenced markdown code block to the documentation comment.
+    ///
+    /// ## Parameters
+    ///
+    /// - `lang`: The language of the code, which may be empty
+    /// - `text`: The code, which may span multiple lines
+    ///
+    /// ## Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// let doc = DocCommentBuilder::new()
+    ///     .line_str("Example:")
+    ///     .code_block("c", "vec2 v = vec2_new(1, 2);\nvec2_normalize(&v);")
+    ///     .build();
+    /// ```
+    pub fn code_block(mut self, lang: &str, text: &str) -> Self {
+        self.docs.extend(code_block_lines(lang, text));
+        self
+    }
+
     /// Consumes the builder and returns a new `DocComment` instance.
     ///
     /// This method finalizes the building process and returns the constructed documentation
@@ -539,6 +716,91 @@ impl DocCommentBuilder {
     }
 }
 
+/// A license or banner comment written at the very top of a file, before the includes and the
+/// doc comment of the scope.
+///
+/// A header is cloned into each scope it is set on, so the same one can be built once and reused
+/// for every file a program generates. Unlike other comments, it is kept in `Style::Compact`,
+/// since a license has to stay with the code.
+///
+/// ## Example
+///
+/// ```c
+/// // Copyright (c) 2025 Example
+/// //
+/// // Generated by castella, do not edit.
+///
+/// #include <stdio.h>
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, DisplayFromFormat)]
+pub struct FileHeader {
+    /// The text of the header, which may span multiple lines
+    pub text: String,
+}
+
+impl FileHeader {
+    /// Creates a file header with the given text.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// let license = FileHeader::new("SPDX-License-Identifier: MIT");
+    /// let a = Scope::new().file_header(license.clone()).build();
+    /// let b = Scope::new().file_header(license).build();
+    /// ```
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+        }
+    }
+}
+
+impl From<&str> for FileHeader {
+    fn from(text: &str) -> Self {
+        Self::new(text)
+    }
+}
+
+impl From<String> for FileHeader {
+    fn from(text: String) -> Self {
+        Self { text }
+    }
+}
+
+impl Format for FileHeader {
+    fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        for line in self.text.lines() {
+            if line.is_empty() {
+                writeln!(fmt, "//")?;
+            } else {
+                writeln!(fmt, "// {line}")?;
+            }
+        }
+        writeln!(fmt)
+    }
+}
+
+/// Splits `text` into the lines of a markdown bullet point, indenting continuation lines.
+fn bullet_lines(text: &str) -> impl Iterator<Item = String> + '_ {
+    let mut lines = text.lines();
+    let first = lines.next().unwrap_or_default();
+
+    std::iter::once(format!("- {first}")).chain(lines.map(|line| {
+        if line.is_empty() {
+            String::new()
+        } else {
+            format!("  {line}")
+        }
+    }))
+}
+
+/// Surrounds the lines of `text` with markdown code fences.
+fn code_block_lines<'a>(lang: &str, text: &'a str) -> impl Iterator<Item = String> + 'a {
+    std::iter::once(format!("```{lang}"))
+        .chain(text.lines().map(str::to_string))
+        .chain(std::iter::once("```".to_string()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -560,4 +822,38 @@ mod tests {
         c = DocComment::new().line_str("ABC").build();
         assert_eq!(c.to_string(), "/// ABC\n");
     }
+
+    #[test]
+    fn file_header() {
+        let h = FileHeader::new("Copyright (c) 2025 Example\n\nDo not edit");
+        assert_eq!(
+            h.to_string(),
+            "// Copyright (c) 2025 Example\n//\n// Do not edit\n\n"
+        );
+    }
+
+    #[test]
+    fn structured_comments() {
+        let c = CommentBuilder::new()
+            .lines(["Fields:", "in order"])
+            .bullet("x")
+            .bullet("y\nand more")
+            .code_block("c", "p.x = 1;")
+            .build();
+        assert_eq!(
+            c.to_string(),
+            "// Fields:\n// in order\n// - x\n// - y\n//   and more\n// ```c\n// p.x = 1;\n// ```\n"
+        );
+
+        let d = DocComment::new()
+            .line_str("Usage:")
+            .lines(vec![String::from("a"), String::from("b")])
+            .bullet("first")
+            .code_block("", "f();\ng();")
+            .build();
+        assert_eq!(
+            d.to_string(),
+            "/// Usage:\n/// a\n/// b\n/// - first\n/// ```\n/// f();\n/// g();\n/// ```\n"
+        );
+    }
 }
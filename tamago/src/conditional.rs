@@ -93,6 +93,18 @@ use tamacro::DisplayFromFormat;
 ///   return 0;
 /// }
 /// ```
+///
+/// An "else" block holding nothing but another if statement is written as an `else if`, so a
+/// ladder of conditions built with `IfBuilder::else_if` isn't nested any deeper:
+/// ```c
+/// if (x > 5) {
+///   return 1;
+/// } else if (x < 0) {
+///   return -1;
+/// } else {
+///   return 0;
+/// }
+/// ```
 #[derive(Debug, Clone, DisplayFromFormat)]
 pub struct If {
     /// The condition expression that determines which branch to execute
@@ -129,19 +141,33 @@ impl If {
     }
 }
 
-impl Format for If {
-    fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+impl If {
+    /// Writes the if statement without the newline after it, continuing with an `else if` when
+    /// the "else" block is just another if statement.
+    fn format_chain(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
         write!(fmt, "if (")?;
         self.cond.format(fmt)?;
         write!(fmt, ")")?;
 
         fmt.block(|fmt| self.then.format(fmt))?;
 
-        if let Some(other) = &self.other {
-            write!(fmt, " else")?;
-            fmt.block(|fmt| other.format(fmt))?;
+        match self.other.as_ref().map(|other| other.stmts.as_slice()) {
+            Some([Statement::If(next)]) => {
+                write!(fmt, " else ")?;
+                next.format_chain(fmt)
+            }
+            Some(_) => {
+                write!(fmt, " else")?;
+                fmt.block(|fmt| self.other.as_ref().unwrap().format(fmt))
+            }
+            None => Ok(()),
         }
+    }
+}
 
+impl Format for If {
+    fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        self.format_chain(fmt)?;
         writeln!(fmt)
     }
 }
@@ -255,6 +281,29 @@ impl IfBuilder {
         self
     }
 
+    /// Sets the "else" block to just another if statement, which is written as an `else if`.
+    ///
+    /// # Parameters
+    /// - `next`: The if statement to check when the condition is false
+    ///
+    /// # Returns
+    /// `self` for method chaining
+    ///
+    /// # Examples
+    /// ```rust
+    /// let if_stmt = IfBuilder::new(expr!("x > 0"))
+    ///     .statement_to_then(Statement::Return(Some(Expr::Int(1))))
+    ///     .else_if(
+    ///         IfBuilder::new(expr!("x < 0"))
+    ///             .statement_to_then(Statement::Return(Some(Expr::Int(-1))))
+    ///             .build(),
+    ///     )
+    ///     .build();
+    /// ```
+    pub fn else_if(self, next: If) -> Self {
+        self.other(Block::new().statement(Statement::If(next)).build())
+    }
+
     /// Appends a statement to the "then" block of the if statement.
     ///
     /// This convenience method adds a statement directly to the "then" block without
@@ -614,14 +663,62 @@ mod tests {
   // Some comment
   #error "some error"
   return;
+} else if (another_var == some_var) {
+  goto hello;
+  #warning "some warning"
+}
+"#;
+
+        assert_eq!(i.to_string(), res);
+    }
+
+    #[test]
+    fn else_if_ladder() {
+        let sign = |cond: &str, value| {
+            IfBuilder::new(Expr::new_ident_with_str(cond))
+                .statement_to_then(Statement::Return(Some(Expr::Int(value))))
+        };
+        let i = sign("positive", 1)
+            .else_if(
+                sign("negative", -1)
+                    .other(
+                        Block::new()
+                            .statement(Statement::Return(Some(Expr::Int(0))))
+                            .build(),
+                    )
+                    .build(),
+            )
+            .build();
+
+        let res = r#"if (positive) {
+  return 1;
+} else if (negative) {
+  return -1;
 } else {
-  if (another_var == some_var) {
-    goto hello;
-    #warning "some warning"
-  }
+  return 0;
 }
 "#;
+        assert_eq!(i.to_string(), res);
+
+        // An if statement with anything else in the block stays nested
+        let i = sign("positive", 1)
+            .other(
+                Block::new()
+                    .statement(Statement::If(sign("negative", -1).build()))
+                    .statement(Statement::Break)
+                    .build(),
+            )
+            .build();
 
+        let res = r#"if (positive) {
+  return 1;
+} else {
+  if (negative) {
+    return -1;
+  }
+  break;
+}
+"#;
         assert_eq!(i.to_string(), res);
     }
 
@@ -0,0 +1,291 @@
+// Copyright (c) 2025 Nobuharu Shimazu
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! This module provides tables of named constants.
+//!
+//! A table is emitted either as a block of `#define` object macros or as the constants of an
+//! `enum`, with the values aligned, which saves a hand-written loop over `ObjMacroBuilder` for
+//! configuration constants.
+
+use std::fmt::{self, Write};
+
+use crate::{DocComment, Expr, Format, Formatter};
+use tamacro::DisplayFromFormat;
+
+/// How the constants of a `ConstantTable` are emitted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ConstantStyle {
+    /// As `#define` object macros, which can have any value
+    #[default]
+    Define,
+
+    /// As the constants of an `enum` with the given name, or of an anonymous one if `None`,
+    /// which requires integer values but gives the constants a type and scope
+    Enum(Option<String>),
+}
+
+/// A table of named constants, emitted with their values aligned.
+///
+/// # Examples
+/// ```c
+/// #define WIDTH  800
+/// #define HEIGHT 600
+/// #define TITLE  "Snake"
+/// ```
+#[derive(Debug, Clone, DisplayFromFormat)]
+pub struct ConstantTable {
+    /// How the constants are emitted
+    pub style: ConstantStyle,
+
+    /// The name, value, and doc comment of each constant, in the order they are emitted
+    pub constants: Vec<(String, Expr, Option<DocComment>)>,
+}
+
+impl ConstantTable {
+    /// Creates and returns a new `ConstantTableBuilder` to construct a `ConstantTable` using the
+    /// builder pattern.
+    ///
+    /// # Returns
+    /// A new `ConstantTableBuilder` emitting `#define`s
+    ///
+    /// # Examples
+    /// ```rust
+    /// let table = ConstantTable::new()
+    ///     .constant("WIDTH", Expr::Int(800), None)
+    ///     .build();
+    /// ```
+    pub fn new() -> ConstantTableBuilder {
+        ConstantTableBuilder::new()
+    }
+}
+
+impl Format for ConstantTable {
+    fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        let width = self
+            .constants
+            .iter()
+            .map(|(name, _, _)| name.len())
+            .max()
+            .unwrap_or(0);
+
+        match &self.style {
+            ConstantStyle::Define => {
+                for (name, value, doc) in &self.constants {
+                    if let Some(doc) = doc {
+                        doc.format(fmt)?;
+                    }
+
+                    write!(fmt, "#define {name:width$} ")?;
+                    // Compound values are parenthesized so that they expand as a unit
+                    if matches!(
+                        value,
+                        Expr::Binary { .. }
+                            | Expr::Unary { .. }
+                            | Expr::Ternary { .. }
+                            | Expr::Cast { .. }
+                            | Expr::Assign { .. }
+                    ) {
+                        write!(fmt, "(")?;
+                        value.format(fmt)?;
+                        writeln!(fmt, ")")?;
+                    } else {
+                        value.format(fmt)?;
+                        writeln!(fmt)?;
+                    }
+                }
+                Ok(())
+            }
+            ConstantStyle::Enum(name) => {
+                match name {
+                    Some(name) => write!(fmt, "enum {name}")?,
+                    None => write!(fmt, "enum")?,
+                }
+
+                fmt.block(|fmt| {
+                    for (name, value, doc) in &self.constants {
+                        if let Some(doc) = doc {
+                            doc.format(fmt)?;
+                        }
+
+                        write!(fmt, "{name:width$} = ")?;
+                        value.format(fmt)?;
+                        writeln!(fmt, ",")?;
+                    }
+                    Ok(())
+                })?;
+
+                writeln!(fmt, ";")
+            }
+        }
+    }
+}
+
+/// A builder for constructing a `ConstantTable` instance with a fluent interface.
+pub struct ConstantTableBuilder {
+    style: ConstantStyle,
+    constants: Vec<(String, Expr, Option<DocComment>)>,
+}
+
+impl ConstantTableBuilder {
+    /// Creates and returns a new `ConstantTableBuilder` emitting `#define`s.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let builder = ConstantTableBuilder::new();
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            style: ConstantStyle::Define,
+            constants: vec![],
+        }
+    }
+
+    /// Sets how the constants are emitted.
+    ///
+    /// # Parameters
+    /// * `style` - `#define`s or the constants of an `enum`
+    ///
+    /// # Returns
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    /// ```rust
+    /// let builder = ConstantTableBuilder::new()
+    ///     .style(ConstantStyle::Enum(Some("config".to_string())));
+    /// ```
+    pub fn style(mut self, style: ConstantStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Adds a constant to the table.
+    ///
+    /// # Parameters
+    /// * `name` - The name of the constant
+    /// * `value` - The value of the constant, which must be an integer for an `enum`
+    /// * `doc` - The doc comment emitted above the constant, if any
+    ///
+    /// # Returns
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    /// ```rust
+    /// let builder = ConstantTableBuilder::new()
+    ///     .constant("TITLE", Expr::Str("Snake".to_string()), None);
+    /// ```
+    pub fn constant(mut self, name: &str, value: Expr, doc: Option<DocComment>) -> Self {
+        self.constants.push((name.to_string(), value, doc));
+        self
+    }
+
+    /// Adds constants to the table in the order they are given.
+    ///
+    /// Since the iteration order of a `HashMap` isn't specified, pass a `Vec` or a `BTreeMap`
+    /// for the output to be reproducible.
+    ///
+    /// # Parameters
+    /// * `constants` - The name, value, and doc comment of each constant
+    ///
+    /// # Returns
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    /// ```rust
+    /// let builder = ConstantTableBuilder::new().constants(vec![
+    ///     ("WIDTH", Expr::Int(800), None),
+    ///     ("HEIGHT", Expr::Int(600), None),
+    /// ]);
+    /// ```
+    pub fn constants<I, S>(mut self, constants: I) -> Self
+    where
+        I: IntoIterator<Item = (S, Expr, Option<DocComment>)>,
+        S: Into<String>,
+    {
+        self.constants.extend(
+            constants
+                .into_iter()
+                .map(|(name, value, doc)| (name.into(), value, doc)),
+        );
+        self
+    }
+
+    /// Finalizes the table and returns a fully constructed `ConstantTable`.
+    ///
+    /// # Returns
+    /// A fully constructed `ConstantTable` instance
+    pub fn build(self) -> ConstantTable {
+        ConstantTable {
+            style: self.style,
+            constants: self.constants,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn constant_table() {
+        let doc = DocCommentBuilder::new().line_str("In pixels").build();
+        let table = ConstantTableBuilder::new()
+            .constant("WIDTH", Expr::Int(800), Some(doc))
+            .constants(vec![
+                ("HEIGHT", Expr::Int(600), None),
+                ("TITLE", Expr::Str("Snake".to_string()), None),
+                (
+                    "AREA",
+                    Expr::Binary {
+                        left: Box::new(Expr::Ident("WIDTH".to_string())),
+                        op: BinOp::Mul,
+                        right: Box::new(Expr::Ident("HEIGHT".to_string())),
+                    },
+                    None,
+                ),
+            ])
+            .build();
+
+        let res = r#"/// In pixels
+#define WIDTH  800
+#define HEIGHT 600
+#define TITLE  "Snake"
+#define AREA   (WIDTH * HEIGHT)
+"#;
+        assert_eq!(table.to_string(), res);
+
+        let table = ConstantTableBuilder::new()
+            .style(ConstantStyle::Enum(Some("config".to_string())))
+            .constants([
+                ("WIDTH", Expr::Int(800), None),
+                ("FPS", Expr::Int(60), None),
+            ])
+            .build();
+        assert_eq!(
+            table.to_string(),
+            "enum config {\n  WIDTH = 800,\n  FPS   = 60,\n};\n"
+        );
+
+        let scope = ScopeBuilder::new()
+            .global_statement(GlobalStatement::Constants(table))
+            .build();
+        assert!(scope.to_string().starts_with("enum config {"));
+    }
+}
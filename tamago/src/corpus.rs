@@ -0,0 +1,401 @@
+// Copyright (c) 2025 Nobuharu Shimazu
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A corpus of complete translation units generated with tamago, which are checked with a real C
+//! compiler if `TAMAGO_CC` is set:
+//!
+//! ```sh
+//! TAMAGO_CC=cc cargo test -p tamago corpus
+//! ```
+//!
+//! Each case is passed to `$TAMAGO_CC -fsyntax-only -std=c11 -Wall -Werror`, so changes that
+//! produce invalid C, or C that compilers warn about, fail the test instead of going unnoticed
+//! until the output is compiled. Without `TAMAGO_CC` the cases are only generated.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::*;
+
+/// How long the compiler gets for a single case
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The largest case in bytes, to keep the corpus quick to compile
+const MAX_SIZE: usize = 16 * 1024;
+
+fn int() -> Type {
+    Type::new(BaseType::Int).build()
+}
+
+fn ident(name: &str) -> Expr {
+    Expr::new_ident_with_str(name)
+}
+
+fn call(name: &str, args: Vec<Expr>) -> Expr {
+    Expr::new_fn_call_with_name(name.to_string(), args)
+}
+
+fn include(header: &str) -> GlobalStatement {
+    GlobalStatement::Include(IncludeBuilder::new_system_with_str(header).build())
+}
+
+fn main_fn(body: Block) -> GlobalStatement {
+    GlobalStatement::Function(
+        FunctionBuilder::new("main".to_string(), int())
+            .body(body)
+            .build(),
+    )
+}
+
+fn hello() -> String {
+    ScopeBuilder::new()
+        .global_statement(include("stdio.h"))
+        .new_line()
+        .global_statement(main_fn(
+            Block::new()
+                .statement(Statement::expr(call(
+                    "printf",
+                    vec![Expr::Str("hello\\n".to_string())],
+                )))
+                .statement(Statement::ret(Expr::Int(0)))
+                .build(),
+        ))
+        .build()
+        .to_string()
+}
+
+fn structs_and_function_pointers() -> String {
+    let u8_ptr = Type::new(BaseType::UInt8).make_pointer().build();
+    let on_read = Type::new(BaseType::FnPtr {
+        ret: Box::new(Type::new(BaseType::Int64).build()),
+        params: vec![u8_ptr.clone(), Type::new(BaseType::UInt64).build()],
+    })
+    .build();
+
+    let handler = StructBuilder::new_with_str("Handler")
+        .field(FieldBuilder::new_with_str("on_read", on_read).build())
+        .field(
+            FieldBuilder::new_with_str("flags", Type::new(BaseType::UInt32).build())
+                .bitfield_width(4)
+                .build(),
+        )
+        .build();
+    let handler_t = handler.to_type();
+
+    let read_all = FunctionBuilder::new("read_all".to_string(), Type::new(BaseType::Int64).build())
+        .make_static()
+        .param(ParameterBuilder::new_with_str("buf", u8_ptr).build())
+        .param(ParameterBuilder::new_with_str("n", Type::new(BaseType::UInt64).build()).build())
+        .body(
+            Block::new()
+                .statement(Statement::expr(Expr::new_cast(
+                    Type::new(BaseType::Void).build(),
+                    ident("buf"),
+                )))
+                .statement(Statement::ret(Expr::new_cast(
+                    Type::new(BaseType::Int64).build(),
+                    ident("n"),
+                )))
+                .build(),
+        )
+        .build();
+
+    let h = Expr::new_mem_access_with_str(ident("h"), "on_read");
+
+    ScopeBuilder::new()
+        .global_statement(include("stdint.h"))
+        .new_line()
+        .global_statement(GlobalStatement::Struct(handler))
+        .new_line()
+        .global_statement(GlobalStatement::TypeDef(
+            TypeDefBuilder::new_with_str(handler_t.clone(), "Handler").build(),
+        ))
+        .new_line()
+        .global_statement(GlobalStatement::Function(read_all))
+        .new_line()
+        .global_statement(main_fn(
+            Block::new()
+                .statement(Statement::decl(
+                    VariableBuilder::new_with_str("h", handler_t).build(),
+                ))
+                .statement(Statement::decl(
+                    VariableBuilder::new_with_str(
+                        "buf",
+                        Type::new(BaseType::UInt8).make_array(4).build(),
+                    )
+                    .value(Expr::InitArr(vec![(None, Expr::Int(0))]))
+                    .build(),
+                ))
+                .statement(Statement::expr(Expr::new_assign(
+                    h.clone(),
+                    AssignOp::Assign,
+                    ident("read_all"),
+                )))
+                .statement(Statement::ret(Expr::new_cast(
+                    int(),
+                    Expr::new_fn_call(h, vec![ident("buf"), Expr::Int(4)]),
+                )))
+                .build(),
+        ))
+        .build()
+        .to_string()
+}
+
+fn control_flow() -> String {
+    let mut scope = ScopeBuilder::new().build();
+    let outer = scope.unique_label("outer");
+
+    let i = VariableBuilder::new_with_str("i", int())
+        .value(Expr::Int(0))
+        .build();
+    let lt = |name: &str, n: i64| Expr::new_binary(ident(name), BinOp::LT, Expr::Int(n));
+    let inc = |name: &str| Expr::new_unary(ident(name), UnaryOp::Inc);
+    let add =
+        |value: Expr| Statement::expr(Expr::new_assign(ident("total"), AssignOp::AddAssign, value));
+
+    let inner = ForBuilder::new()
+        .init(Expr::Variable(Box::new(
+            VariableBuilder::new_with_str("j", int())
+                .value(Expr::Int(0))
+                .build(),
+        )))
+        .cond(lt("j", 10))
+        .step(inc("j"))
+        .body(
+            Block::new()
+                .statement(Statement::If(
+                    IfBuilder::new(Expr::new_binary(ident("j"), BinOp::GT, ident("i")))
+                        .then(
+                            Block::new()
+                                .statement(Statement::continue_to(&outer))
+                                .build(),
+                        )
+                        .other(
+                            Block::new()
+                                .statement(Statement::If(
+                                    IfBuilder::new(Expr::new_binary(
+                                        ident("total"),
+                                        BinOp::GT,
+                                        Expr::Int(100),
+                                    ))
+                                    .then(
+                                        Block::new().statement(Statement::break_to(&outer)).build(),
+                                    )
+                                    .build(),
+                                ))
+                                .build(),
+                        )
+                        .build(),
+                ))
+                .statement(add(ident("j")))
+                .build(),
+        )
+        .build();
+
+    let body = Block::new()
+        .statement(Statement::decl(
+            VariableBuilder::new_with_str("total", int())
+                .value(Expr::Int(0))
+                .build(),
+        ))
+        .statement(Statement::labelled(
+            &outer,
+            Statement::For(
+                ForBuilder::new()
+                    .init(Expr::Variable(Box::new(i)))
+                    .cond(lt("i", 10))
+                    .step(inc("i"))
+                    .body(Block::new().statement(Statement::For(inner)).build())
+                    .build(),
+            ),
+        ))
+        .statement(Statement::While(
+            WhileBuilder::new(lt("total", 200))
+                .body(Block::new().statement(add(Expr::Int(7))).build())
+                .build(),
+        ))
+        .statement(Statement::DoWhile(
+            DoWhileBuilder::new(lt("total", 0))
+                .body(Block::new().statement(add(Expr::Int(-1))).build())
+                .build(),
+        ))
+        .statement(Statement::Switch(
+            SwitchBuilder::new(Expr::new_binary(ident("total"), BinOp::Mod, Expr::Int(3)))
+                .case(
+                    Expr::Int(0),
+                    Block::new().statement(Statement::ret(Expr::Int(0))).build(),
+                )
+                .default(Block::new().statement(Statement::ret(Expr::Int(1))).build())
+                .build(),
+        ))
+        .build();
+
+    scope.insert_at(0, main_fn(body));
+    scope.to_string()
+}
+
+/// The shape of a header, with an include guard
+fn include_guard() -> String {
+    let prototype = FunctionBuilder::new("area".to_string(), Type::new(BaseType::Double).build())
+        .param(ParameterBuilder::new_with_str("r", Type::new(BaseType::Double).build()).build())
+        .build();
+
+    let guarded = ScopeBuilder::new()
+        .global_statement(GlobalStatement::Macro(Macro::Obj(
+            ObjMacroBuilder::new_with_str("AREA_H").build(),
+        )))
+        .new_line()
+        .global_statement(GlobalStatement::Function(prototype))
+        .build();
+
+    let mut res = String::new();
+    let mut fmt = Formatter::new(&mut res);
+    fmt.prototypes_only = true;
+    ScopeBuilder::new()
+        .global_statement(GlobalStatement::IfDefDirective(
+            IfDefDirectiveBuilder::new_with_str("AREA_H")
+                .not()
+                .then(ScopeOrBlock::Scope(guarded))
+                .build(),
+        ))
+        .build()
+        .format(&mut fmt)
+        .unwrap();
+    res
+}
+
+fn constants_and_globals() -> String {
+    let limits = ConstantTableBuilder::new()
+        .constants([
+            ("WIDTH", Expr::Int(800), None),
+            ("HEIGHT", Expr::Int(600), None),
+            (
+                "AREA",
+                Expr::new_binary(ident("WIDTH"), BinOp::Mul, ident("HEIGHT")),
+                None,
+            ),
+        ])
+        .build();
+    let colors = ConstantTableBuilder::new()
+        .style(ConstantStyle::Enum(Some("color".to_string())))
+        .constants([("RED", Expr::Int(1), None), ("GREEN", Expr::Int(2), None)])
+        .build();
+
+    let mut scope = ScopeBuilder::new()
+        .global_statement(GlobalStatement::Constants(limits))
+        .new_line()
+        .global_statement(GlobalStatement::Constants(colors))
+        .new_line()
+        .global_statement(GlobalStatement::Variable(
+            VariableBuilder::new_with_str("ready", Type::new(BaseType::Bool).build())
+                .value(Expr::Bool(true))
+                .build(),
+        ))
+        .new_line()
+        .global_statement(main_fn(
+            Block::new()
+                .statement(Statement::ret(Expr::new_ternary(
+                    ident("ready"),
+                    Expr::new_binary(ident("AREA"), BinOp::Sub, ident("GREEN")),
+                    ident("RED"),
+                )))
+                .build(),
+        ))
+        .build();
+
+    // `bool` needs `stdbool.h`
+    IncludeAnalysis::new().insert_missing(&mut scope);
+    scope.to_string()
+}
+
+/// Returns the name and generated code of each case
+fn cases() -> Vec<(&'static str, String)> {
+    vec![
+        ("hello", hello()),
+        (
+            "structs_and_function_pointers",
+            structs_and_function_pointers(),
+        ),
+        ("control_flow", control_flow()),
+        ("include_guard", include_guard()),
+        ("constants_and_globals", constants_and_globals()),
+    ]
+}
+
+/// Checks `code` with `cc` and the extra `flags`, returning the compiler's complaints if it
+/// rejects it
+pub(crate) fn compile(cc: &str, code: &str, flags: &[&str]) -> Result<(), String> {
+    let mut child = Command::new(cc)
+        .args(["-fsyntax-only", "-std=c11"])
+        .args(flags)
+        .args(["-x", "c", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run '{cc}': {e}"))?;
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(code.as_bytes())
+        .map_err(|e| e.to_string())?;
+
+    let start = Instant::now();
+    while child.try_wait().map_err(|e| e.to_string())?.is_none() {
+        if start.elapsed() > TIMEOUT {
+            let _ = child.kill();
+            return Err(format!("'{cc}' took longer than {TIMEOUT:?}"));
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).into_owned())
+    }
+}
+
+#[test]
+fn corpus() {
+    let cc = std::env::var("TAMAGO_CC").ok().filter(|cc| !cc.is_empty());
+
+    let mut failures = vec![];
+    for (name, code) in cases() {
+        assert!(
+            code.len() <= MAX_SIZE,
+            "the corpus case '{name}' is {} bytes, keep it under {MAX_SIZE}",
+            code.len()
+        );
+
+        if let Some(cc) = &cc
+            && let Err(err) = compile(cc, &code, &["-Wall", "-Werror"])
+        {
+            failures.push(format!("{name}:\n{code}\n{err}"));
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -31,18 +31,20 @@
 //! - Unary operations (increment, decrement, negation, etc.)
 //! - Assignment operations
 //! - Ternary conditionals
+//! - Comma expressions
 //! - Function calls
 //! - Member access
 //! - Array indexing
 //! - Type casting
 //! - Size queries
 //! - Array and struct initializations
+//! - Predefined identifiers (`__FILE__`, `__LINE__`, `__func__`)
 //!
 //! The module is designed to facilitate C code generation with a type-safe Rust interface.
 
 use std::fmt::{self, Write};
 
-use crate::{Format, Formatter, Type, Variable};
+use crate::{Format, Formatter, Language, Type, Variable};
 use tamacro::{DisplayFromConstSymbol, DisplayFromFormat, FormatFromConstSymbol};
 
 /// Encapsulates all types of expressions in C.
@@ -71,6 +73,7 @@ use tamacro::{DisplayFromConstSymbol, DisplayFromFormat, FormatFromConstSymbol};
 /// );
 /// ```
 #[derive(Debug, Clone, DisplayFromFormat)]
+#[non_exhaustive]
 pub enum Expr {
     /// A signed integer literal (e.g., `42`, `-17`).
     Int(i64),
@@ -79,9 +82,13 @@ pub enum Expr {
     UInt(u64),
 
     /// A double precision floating point number literal (e.g., `3.14159`).
+    ///
+    /// It is written with the fewest digits that read back as the same value, and always with a
+    /// `.` or an exponent so that C doesn't take it for an integer (e.g., `1.0`, `1e300`).
+    /// Infinities and NaN, which have no literal, are written as divisions by zero.
     Double(f64),
 
-    /// A single precision floating point number literal (e.g., `3.14f`).
+    /// A single precision floating point number literal (e.g., `3.14f`), written like `Double`.
     Float(f32),
 
     /// A boolean value (`true` or `false`).
@@ -96,6 +103,12 @@ pub enum Expr {
     /// An identifier representing a variable or function name.
     Ident(String),
 
+    /// A null pointer constant, `NULL` in C and `nullptr` in C++.
+    Null,
+
+    /// One of the identifiers the compiler predefines, like `__FILE__` or `__func__`.
+    Predefined(Predefined),
+
     /// Variable declaration or definition with type information.
     Variable(Box<Variable>),
 
@@ -136,6 +149,13 @@ pub enum Expr {
         rexpr: Box<Expr>,
     },
 
+    /// A comma expression, which evaluates its operands in order and has the value of the last
+    /// one. It is always parenthesized, so it can be passed as an argument or used in a macro
+    /// without its commas separating anything.
+    ///
+    /// Example: `(i++, j--)`
+    Comma(Vec<Expr>),
+
     /// A function call expression.
     ///
     /// Example: `printf("Hello, %s", name)`
@@ -212,9 +232,56 @@ impl Expr {
     ///
     /// # Returns
     ///
-    /// A new `Expr::Ident` representing the NULL constant in C.
+    /// A new `Expr::Null`, which is emitted as `NULL` or `nullptr` depending on the language.
     pub fn new_null() -> Self {
-        Self::new_ident("NULL".to_string())
+        Self::Null
+    }
+
+    /// Creates a `__FILE__` expression, the name of the C file being compiled.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// assert_eq!(Expr::file().to_string(), "__FILE__");
+    /// ```
+    pub fn file() -> Self {
+        Self::Predefined(Predefined::File)
+    }
+
+    /// Creates a `__LINE__` expression, the line of the C file it is written on.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// assert_eq!(Expr::line().to_string(), "__LINE__");
+    /// ```
+    pub fn line() -> Self {
+        Self::Predefined(Predefined::Line)
+    }
+
+    /// Creates a `__func__` expression, the name of the enclosing function.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// assert_eq!(Expr::func().to_string(), "__func__");
+    /// ```
+    pub fn func() -> Self {
+        Self::Predefined(Predefined::Func)
+    }
+
+    /// Creates a new variable declaration expression, e.g. the initialization clause of a `for`
+    /// loop.
+    ///
+    /// # Arguments
+    ///
+    /// * `var` - The variable being declared.
+    ///
+    /// # Returns
+    ///
+    /// A new `Expr::Variable` declaring the variable.
+    pub fn new_variable(var: Variable) -> Self {
+        Self::Variable(Box::new(var))
     }
 
     /// Creates a new binary expression with the given expressions and binary operator.
@@ -306,6 +373,29 @@ impl Expr {
         }
     }
 
+    /// Creates a new comma expression.
+    ///
+    /// # Arguments
+    ///
+    /// * `exprs` - The expressions to evaluate in order, the last of which is the value.
+    ///
+    /// # Returns
+    ///
+    /// A new `Expr::Comma` representing the sequence of expressions.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let step = Expr::new_comma(vec![
+    ///     Expr::new_unary(Expr::new_ident_with_str("i"), UnaryOp::Inc),
+    ///     Expr::new_unary(Expr::new_ident_with_str("j"), UnaryOp::Dec),
+    /// ]);
+    /// assert_eq!(step.to_string(), "(i++, j--)");
+    /// ```
+    pub fn new_comma(exprs: Vec<Expr>) -> Self {
+        Self::Comma(exprs)
+    }
+
     /// Creates a new function call expression.
     ///
     /// # Arguments
@@ -342,6 +432,31 @@ impl Expr {
         }
     }
 
+    /// Creates a new function call expression with the arguments of an iterator, such as the
+    /// lowered arguments of another call.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the function.
+    /// * `args` - The expressions of the arguments, in order.
+    ///
+    /// # Returns
+    ///
+    /// A new `Expr::FnCall` representing the function call.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let call = Expr::new_fn_call_from_iter("max", [1, 2].map(Expr::Int));
+    /// assert_eq!(call.to_string(), "max(1, 2)");
+    /// ```
+    pub fn new_fn_call_from_iter<I>(name: &str, args: I) -> Self
+    where
+        I: IntoIterator<Item = Expr>,
+    {
+        Self::new_fn_call_with_name(name.to_string(), args.into_iter().collect())
+    }
+
     /// Creates a new struct member access expression.
     ///
     /// # Arguments
@@ -485,137 +600,348 @@ impl Expr {
         assert!(x.len() == y.len());
         Self::InitStruct(x.into_iter().map(Some).zip(y).collect())
     }
+
+    /// Checks whether the expression is a constant expression, which is all that `static`
+    /// variables, including ones inside functions, can be initialized with.
+    ///
+    /// Identifiers are assumed to name enumeration constants or macros, and taking the address of
+    /// anything is assumed to be taking the address of a static object, so an expression that
+    /// isn't reported here may still not be constant. `Raw` expressions are never reported.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the expression calls a function, assigns, reads through a pointer, accesses a
+    /// member or an element, or has a comma operator, `true` otherwise.
+    pub fn is_constant(&self) -> bool {
+        use Expr::*;
+
+        // Walks the expression with an explicit stack like `format`, since generated expressions
+        // can be nested deeply enough to overflow the call stack
+        let mut stack = vec![self];
+        while let Some(expr) = stack.pop() {
+            match expr {
+                Int(_) | UInt(_) | Double(_) | Float(_) | Bool(_) | Char(_) | Str(_) | Ident(_)
+                | Null | Predefined(_) | SizeOf(_) | Raw(_) => {}
+                Unary {
+                    op: UnaryOp::AddrOf,
+                    ..
+                } => {}
+                Unary {
+                    op: UnaryOp::Inc | UnaryOp::Dec | UnaryOp::Deref,
+                    ..
+                }
+                | Variable(_)
+                | Assign { .. }
+                | Comma(_)
+                | FnCall { .. }
+                | MemAccess { .. }
+                | ArrIndex { .. } => return false,
+                Parenthesized { expr } | Unary { expr, .. } | Cast { expr, .. } => stack.push(expr),
+                Binary { left, right, .. } => stack.extend([&**left, &**right]),
+                Ternary { cond, lexpr, rexpr } => stack.extend([&**cond, &**lexpr, &**rexpr]),
+                InitArr(elems) => stack.extend(elems.iter().map(|(_, elem)| elem)),
+                InitStruct(fields) => stack.extend(fields.iter().map(|(_, field)| field)),
+            }
+        }
+
+        true
+    }
+
+    /// Returns the direct sub-expressions of the expression, in the order they are written.
+    ///
+    /// Only the immediate children are returned, so tools can implement their own traversals,
+    /// e.g. with an explicit stack, without matching on every variant. The initial value of a
+    /// [`Expr::Variable`] counts as a child.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let expr = Expr::new_binary(Expr::Int(1), BinOp::Add, Expr::new_ident_with_str("x"));
+    /// let children: Vec<String> = expr.children().map(|c| c.to_string()).collect();
+    /// assert_eq!(children, ["1", "x"]);
+    /// ```
+    pub fn children(&self) -> impl Iterator<Item = &Expr> {
+        use Expr::*;
+
+        let children: Vec<&Expr> = match self {
+            Int(_) | UInt(_) | Double(_) | Float(_) | Bool(_) | Char(_) | Str(_) | Ident(_)
+            | Null | Predefined(_) | SizeOf(_) | Raw(_) => vec![],
+            Variable(v) => v.value.iter().collect(),
+            Parenthesized { expr }
+            | Unary { expr, .. }
+            | MemAccess { expr, .. }
+            | Cast { expr, .. } => vec![expr],
+            Binary { left, right, .. } => vec![left, right],
+            Assign { lvalue, value, .. } => vec![lvalue, value],
+            Ternary { cond, lexpr, rexpr } => vec![cond, lexpr, rexpr],
+            Comma(exprs) => exprs.iter().collect(),
+            FnCall { name, args } => std::iter::once(&**name).chain(args).collect(),
+            ArrIndex { arr, idx } => vec![arr, idx],
+            InitArr(elems) => elems.iter().map(|(_, elem)| elem).collect(),
+            InitStruct(fields) => fields.iter().map(|(_, field)| field).collect(),
+        };
+
+        children.into_iter()
+    }
 }
 
-impl Format for Expr {
-    fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+/// Returns the C literal of a floating point number from its `Debug` representation, which is
+/// the same on every platform unlike the C library's `printf`, with `suffix` for its type.
+/// Infinities and NaN become the `INFINITY` and `NAN` macros of `math.h`, which convert to
+/// either type without changing their value.
+fn float_literal(repr: String, suffix: &str) -> String {
+    match repr.as_str() {
+        "NaN" => "NAN".to_string(),
+        "inf" => "INFINITY".to_string(),
+        "-inf" => "(-INFINITY)".to_string(),
+        _ => repr + suffix,
+    }
+}
+
+/// A pending piece of work for the explicit stack used by [`Expr`]'s formatter.
+enum Work<'e> {
+    Expr(&'e Expr),
+    Node(&'e dyn Format),
+    Text(&'static str),
+    Owned(String),
+}
+
+impl Expr {
+    /// Writes the leaf expressions directly and pushes the parts of compound expressions onto
+    /// `stack` so that they are formatted in order.
+    fn expand<'e>(&'e self, fmt: &mut Formatter<'_>, stack: &mut Vec<Work<'e>>) -> fmt::Result {
         use Expr::*;
+        use Work::{Node, Owned, Text};
+
+        let mut push = |items: Vec<Work<'e>>| stack.extend(items.into_iter().rev());
+
         match self {
-            Int(num) => write!(fmt, "{num}"),
-            UInt(num) => write!(fmt, "{num}"),
-            Double(num) => write!(fmt, "{num}"),
-            Float(num) => write!(fmt, "{num}f"),
-            Bool(b) => write!(fmt, "{}", if *b { "true" } else { "false" }),
-            Char(c) => write!(fmt, "'{c}'"),
-            Str(s) => write!(fmt, "\"{s}\""),
-            Ident(name) => write!(fmt, "{name}"),
-            Variable(var) => var.format(fmt),
-            Binary { left, op, right } => {
-                left.format(fmt)?;
-                write!(fmt, " ")?;
-                op.format(fmt)?;
-                write!(fmt, " ")?;
-                right.format(fmt)
-            }
-            Parenthesized { expr } => {
-                write!(fmt, "(")?;
-                expr.format(fmt)?;
-                write!(fmt, ")")
-            }
+            Int(num) => write!(fmt, "{num}")?,
+            UInt(num) => write!(fmt, "{num}")?,
+            Double(num) => write!(fmt, "{}", float_literal(format!("{num:?}"), ""))?,
+            Float(num) => write!(fmt, "{}", float_literal(format!("{num:?}"), "f"))?,
+            Bool(b) => write!(fmt, "{}", if *b { "true" } else { "false" })?,
+            Char(c) => match c {
+                '\'' => write!(fmt, "'\\''")?,
+                '\\' => write!(fmt, "'\\\\'")?,
+                '\n' => write!(fmt, "'\\n'")?,
+                '\r' => write!(fmt, "'\\r'")?,
+                '\t' => write!(fmt, "'\\t'")?,
+                '\0' => write!(fmt, "'\\0'")?,
+                c if c.is_ascii_control() => write!(fmt, "'\\{:03o}'", *c as u8)?,
+                c => write!(fmt, "'{c}'")?,
+            },
+            Str(s) => write!(fmt, "\"{s}\"")?,
+            Ident(name) => write!(fmt, "{name}")?,
+            Null if fmt.language == Language::Cpp => write!(fmt, "nullptr")?,
+            Null => write!(fmt, "NULL")?,
+            Predefined(p) => p.format(fmt)?,
+            Variable(var) => var.format(fmt)?,
+            Binary { left, op, right } => push(vec![
+                Work::Expr(left),
+                Text(" "),
+                Node(op),
+                Text(" "),
+                Work::Expr(right),
+            ]),
+            Parenthesized { expr } => push(vec![Text("("), Work::Expr(expr), Text(")")]),
             Unary { op, expr } => {
-                if !matches!(op, UnaryOp::Inc | UnaryOp::Dec) {
-                    op.format(fmt)?;
-                }
-                expr.format(fmt)?;
                 if matches!(op, UnaryOp::Inc | UnaryOp::Dec) {
-                    op.format(fmt)?;
+                    push(vec![Work::Expr(expr), Node(op)]);
+                } else {
+                    push(vec![Node(op), Work::Expr(expr)]);
                 }
-                Ok(())
-            }
-            Assign { lvalue, op, value } => {
-                lvalue.format(fmt)?;
-                write!(fmt, " ")?;
-                op.format(fmt)?;
-                write!(fmt, " ")?;
-                value.format(fmt)
             }
-            Ternary { cond, lexpr, rexpr } => {
-                cond.format(fmt)?;
-                write!(fmt, " ? ")?;
-                lexpr.format(fmt)?;
-                write!(fmt, " : ")?;
-                rexpr.format(fmt)
+            Assign { lvalue, op, value } => push(vec![
+                Work::Expr(lvalue),
+                Text(" "),
+                Node(op),
+                Text(" "),
+                Work::Expr(value),
+            ]),
+            Ternary { cond, lexpr, rexpr } => push(vec![
+                Work::Expr(cond),
+                Text(" ? "),
+                Work::Expr(lexpr),
+                Text(" : "),
+                Work::Expr(rexpr),
+            ]),
+            Comma(exprs) => {
+                let mut items = vec![Text("(")];
+                for (i, expr) in exprs.iter().enumerate() {
+                    if i != 0 {
+                        items.push(Text(", "));
+                    }
+                    items.push(Work::Expr(expr));
+                }
+                items.push(Text(")"));
+                push(items);
             }
             FnCall { name, args } => {
-                name.format(fmt)?;
-                write!(fmt, "(")?;
-                if !args.is_empty() {
-                    for arg in &args[..args.len() - 1] {
-                        arg.format(fmt)?;
-                        write!(fmt, ", ")?;
+                let params = match &**name {
+                    Ident(func) => fmt.param_names(func).unwrap_or_default(),
+                    _ => &[],
+                };
+
+                let mut items = vec![Work::Expr(name), Text("(")];
+                for (i, arg) in args.iter().enumerate() {
+                    if i != 0 {
+                        items.push(Text(", "));
                     }
-                    if let Some(arg) = args.last() {
-                        arg.format(fmt)?;
+                    // The variadic arguments of a function have no names
+                    if let Some(param) = params.get(i) {
+                        items.push(Owned(format!("/* {param}= */ ")));
                     }
+                    items.push(Work::Expr(arg));
                 }
-                write!(fmt, ")")
+                items.push(Text(")"));
+                push(items);
             }
             MemAccess { expr, member } => {
-                expr.format(fmt)?;
-                write!(fmt, ".{member}")
-            }
-            ArrIndex { arr, idx } => {
-                arr.format(fmt)?;
-                write!(fmt, "[")?;
-                idx.format(fmt)?;
-                write!(fmt, "]")
-            }
-            Cast { t, expr } => {
-                write!(fmt, "(")?;
-                t.format(fmt)?;
-                write!(fmt, ")")?;
-                write!(fmt, "(")?;
-                expr.format(fmt)?;
-                write!(fmt, ")")
+                push(vec![Work::Expr(expr), Owned(format!(".{member}"))]);
             }
+            ArrIndex { arr, idx } => push(vec![
+                Work::Expr(arr),
+                Text("["),
+                Work::Expr(idx),
+                Text("]"),
+            ]),
+            Cast { t, expr } => push(vec![
+                Text("("),
+                Node(t),
+                Text(")("),
+                Work::Expr(expr),
+                Text(")"),
+            ]),
             SizeOf(t) => {
                 write!(fmt, "sizeof(")?;
                 t.format(fmt)?;
                 if t.is_array() {
-                    write!(fmt, "[{}]", t.array)?;
+                    for size in &t.array {
+                        write!(fmt, "[{size}]")?;
+                    }
                 }
-                write!(fmt, ")")
+                write!(fmt, ")")?;
+            }
+            InitArr(v) if fmt.language == Language::Cpp && v.iter().any(|(i, _)| i.is_some()) => {
+                // C++ has no array designators, so the elements are laid out by index and the
+                // gaps are value-initialized
+                let mut slots: Vec<Option<&Expr>> = vec![];
+                let mut pos = 0;
+                for (idx, elem) in v {
+                    pos = idx.unwrap_or(pos);
+                    place(&mut slots, pos, elem);
+                    pos += 1;
+                }
+                push(positional(slots));
             }
             InitArr(v) => {
-                write!(fmt, "{{")?;
-                if !v.is_empty() {
-                    for x in &v[..v.len() - 1] {
-                        if let Some(idx) = x.0 {
-                            write!(fmt, "[{idx}]=")?;
-                        }
-                        x.1.format(fmt)?;
-                        write!(fmt, ", ")?;
+                let mut items = vec![Text("{")];
+                for (i, (idx, elem)) in v.iter().enumerate() {
+                    if i != 0 {
+                        items.push(Text(", "));
                     }
-                    if let Some(last) = v.last() {
-                        if let Some(idx) = last.0 {
-                            write!(fmt, "[{idx}]=")?;
-                        }
-                        last.1.format(fmt)?;
+                    if let Some(idx) = idx {
+                        items.push(Owned(format!("[{idx}]=")));
                     }
+                    items.push(Work::Expr(elem));
                 }
-                write!(fmt, "}}")
+                items.push(Text("}"));
+                push(items);
             }
-            InitStruct(v) => {
-                write!(fmt, "{{")?;
-                if !v.is_empty() {
-                    for x in &v[..v.len() - 1] {
-                        if let Some(name) = &x.0 {
-                            write!(fmt, ".{name}=")?;
-                        }
-                        x.1.format(fmt)?;
-                        write!(fmt, ", ")?;
-                    }
-                    if let Some(last) = v.last() {
-                        if let Some(name) = &last.0 {
-                            write!(fmt, ".{name}=")?;
-                        }
-                        last.1.format(fmt)?;
+            InitStruct(v)
+                if fmt.language == Language::Cpp && v.iter().any(|(n, _)| n.is_some()) =>
+            {
+                // Designated struct initializers need C++20, so the fields are laid out in the
+                // order of the struct that has them, and the gaps are value-initialized
+                let names: Vec<&str> = v.iter().filter_map(|(name, _)| name.as_deref()).collect();
+                let Some(fields) = fmt.struct_fields(&names) else {
+                    // Guessing the order could initialize the wrong fields, so the designators
+                    // are kept, which C++20 accepts if they are in the order of the struct
+                    fmt.warn(format!(
+                        "Couldn't find the struct with the fields '{}' to put its initializer in \
+                         order, so it keeps its designators, which need C++20",
+                        names.join("', '")
+                    ));
+                    push(designated(v));
+                    return Ok(());
+                };
+
+                let mut slots: Vec<Option<&Expr>> = vec![];
+                let mut pos = 0;
+                for (name, elem) in v {
+                    if let Some(name) = name {
+                        pos = fields.iter().position(|field| field == name).unwrap();
                     }
+                    place(&mut slots, pos, elem);
+                    pos += 1;
                 }
-                write!(fmt, "}}")
+                push(positional(slots));
+            }
+            InitStruct(v) => push(designated(v)),
+            Raw(s) => write!(fmt, "{s}")?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Puts `elem` into `slots` at `pos`, growing it with gaps as needed
+fn place<'e>(slots: &mut Vec<Option<&'e Expr>>, pos: usize, elem: &'e Expr) {
+    if slots.len() <= pos {
+        slots.resize(pos + 1, None);
+    }
+    slots[pos] = Some(elem);
+}
+
+/// A struct initializer of `fields`, with the designators of those that are named
+fn designated(fields: &[(Option<String>, Expr)]) -> Vec<Work<'_>> {
+    let mut items = vec![Work::Text("{")];
+    for (i, (name, elem)) in fields.iter().enumerate() {
+        if i != 0 {
+            items.push(Work::Text(", "));
+        }
+        if let Some(name) = name {
+            items.push(Work::Owned(format!(".{name}=")));
+        }
+        items.push(Work::Expr(elem));
+    }
+    items.push(Work::Text("}"));
+    items
+}
+
+/// A positional initializer of `slots`, whose gaps are value-initialized with `{}`
+fn positional(slots: Vec<Option<&Expr>>) -> Vec<Work<'_>> {
+    let mut items = vec![Work::Text("{")];
+    for (i, slot) in slots.into_iter().enumerate() {
+        if i != 0 {
+            items.push(Work::Text(", "));
+        }
+        items.push(match slot {
+            Some(elem) => Work::Expr(elem),
+            None => Work::Text("{}"),
+        });
+    }
+    items.push(Work::Text("}"));
+    items
+}
+
+impl Format for Expr {
+    fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        // Deeply nested expressions (e.g. long generated chains of binary operations) would
+        // overflow the native stack if formatted recursively, so an explicit stack is used instead.
+        let mut stack = vec![Work::Expr(self)];
+
+        while let Some(work) = stack.pop() {
+            match work {
+                Work::Expr(expr) => expr.expand(fmt, &mut stack)?,
+                Work::Node(node) => node.format(fmt)?,
+                Work::Text(s) => write!(fmt, "{s}")?,
+                Work::Owned(s) => write!(fmt, "{s}")?,
             }
-            Raw(s) => write!(fmt, "{s}"),
         }
+
+        Ok(())
     }
 }
 
@@ -698,6 +1024,25 @@ pub enum BinOp {
     RShift,
 }
 
+/// The identifiers the C compiler predefines, which tell where in the code they are written.
+///
+/// `__FILE__` and `__LINE__` are macros, and `__func__` is a string declared in every function
+/// since C99.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DisplayFromConstSymbol, FormatFromConstSymbol)]
+pub enum Predefined {
+    /// The name of the file being compiled (`__FILE__`)
+    #[symbol = "__FILE__"]
+    File,
+
+    /// The current line, as an `int` (`__LINE__`)
+    #[symbol = "__LINE__"]
+    Line,
+
+    /// The name of the enclosing function (`__func__`)
+    #[symbol = "__func__"]
+    Func,
+}
+
 /// Encapsulates unary operators used in C expressions.
 ///
 /// This enum represents all unary operators in C, including prefix
@@ -801,6 +1146,28 @@ mod tests {
         assert_eq!(b.to_string(), res);
     }
 
+    #[test]
+    fn float_literals() {
+        let literals = [
+            (Expr::Double(1.0), "1.0"),
+            (Expr::Double(-0.0), "-0.0"),
+            (Expr::Double(0.1), "0.1"),
+            (Expr::Double(1e300), "1e300"),
+            (Expr::Double(2.5e-8), "2.5e-8"),
+            (Expr::Double(f64::MAX), "1.7976931348623157e308"),
+            (Expr::Double(f64::NAN), "NAN"),
+            (Expr::Double(f64::NEG_INFINITY), "(-INFINITY)"),
+            (Expr::Float(3.0), "3.0f"),
+            (Expr::Float(0.1), "0.1f"),
+            (Expr::Float(f32::MAX), "3.4028235e38f"),
+            (Expr::Float(f32::INFINITY), "INFINITY"),
+            (Expr::Float(1e-30), "1e-30f"),
+        ];
+        for (expr, res) in literals {
+            assert_eq!(expr.to_string(), res);
+        }
+    }
+
     #[test]
     fn parenthesized() {
         let p = Expr::new_binary(
@@ -870,6 +1237,72 @@ mod tests {
         );
         let res2 = "some_func('a', sizeof(char))";
         assert_eq!(f2.to_string(), res2);
+
+        let f3 = Expr::new_fn_call_from_iter("max", (1..=3).map(Expr::Int));
+        assert_eq!(f3.to_string(), "max(1, 2, 3)");
+    }
+
+    #[test]
+    fn comma() {
+        let i = || Expr::new_ident_with_str("i");
+        let step = Expr::new_comma(vec![
+            Expr::new_unary(i(), UnaryOp::Inc),
+            Expr::new_assign(Expr::new_ident_with_str("sum"), AssignOp::AddAssign, i()),
+        ]);
+        assert_eq!(step.to_string(), "(i++, sum += i)");
+        assert_eq!(step.children().count(), 2);
+        assert!(!Expr::new_comma(vec![Expr::Int(1), Expr::Int(2)]).is_constant());
+
+        // The parentheses keep it a single argument
+        let f = Expr::new_fn_call_from_iter("f", [step, Expr::Int(0)]);
+        assert_eq!(f.to_string(), "f((i++, sum += i), 0)");
+    }
+
+    #[test]
+    fn param_comments() {
+        let int = || Type::new(BaseType::Int).build();
+        let resize = FunctionBuilder::new_with_str("resize", Type::new(BaseType::Void).build())
+            .param(ParameterBuilder::new_with_str("width", int()).build())
+            .param(ParameterBuilder::new_with_str("height", int()).build())
+            .build();
+        let log = FunctionBuilder::new_with_str("log", Type::new(BaseType::Void).build())
+            .param(ParameterBuilder::new_with_str("fmt", int()).build())
+            .build();
+        let call = |name: &str| Expr::new_fn_call_from_iter(name, [1, 2].map(Expr::Int));
+
+        let mut res = String::new();
+        let mut fmt = Formatter::new(&mut res);
+        fmt.param_comments = true;
+        call("resize").format(&mut fmt).unwrap();
+        resize.format(&mut fmt).unwrap();
+        fmt.register_params(&log);
+        call("resize").format(&mut fmt).unwrap();
+        writeln!(fmt).unwrap();
+        call("log").format(&mut fmt).unwrap();
+        assert_eq!(
+            res,
+            "resize(1, 2)void resize(int width, int height);\n\
+             resize(/* width= */ 1, /* height= */ 2)\n\
+             log(/* fmt= */ 1, 2)"
+        );
+
+        // Without the option, nothing is annotated
+        let mut res = String::new();
+        let mut fmt = Formatter::new(&mut res);
+        fmt.register_params(&resize);
+        call("resize").format(&mut fmt).unwrap();
+        assert_eq!(res, "resize(1, 2)");
+    }
+
+    #[test]
+    fn chars() {
+        assert_eq!(Expr::Char('a').to_string(), "'a'");
+        assert_eq!(Expr::Char('"').to_string(), "'\"'");
+        assert_eq!(Expr::Char('\'').to_string(), "'\\''");
+        assert_eq!(Expr::Char('\\').to_string(), "'\\\\'");
+        assert_eq!(Expr::Char('\n').to_string(), "'\\n'");
+        assert_eq!(Expr::Char('\0').to_string(), "'\\0'");
+        assert_eq!(Expr::Char('\x1b').to_string(), "'\\033'");
     }
 
     #[test]
@@ -934,4 +1367,92 @@ mod tests {
         let res2 = "{.name=\"bichanna\", .age=18}";
         assert_eq!(i2.to_string(), res2);
     }
+
+    #[test]
+    fn deep_binary_chain() {
+        let depth = 100_000;
+
+        let mut e = Expr::Int(0);
+        for _ in 0..depth {
+            e = Expr::new_binary(e, BinOp::Add, Expr::Int(1));
+        }
+
+        let res = format!("0{}", " + 1".repeat(depth));
+        assert_eq!(e.to_string(), res);
+
+        // Dismantle the chain iteratively, since the recursive drop would overflow the stack too
+        while let Expr::Binary { left, .. } = e {
+            e = *left;
+        }
+    }
+
+    #[test]
+    fn cpp_compat() {
+        let cpp = |e: &Expr| {
+            let mut res = String::new();
+            e.format(&mut Formatter::new_with_language(&mut res, Language::Cpp))
+                .unwrap();
+            res
+        };
+
+        let null = Expr::new_null();
+        assert_eq!(null.to_string(), "NULL");
+        assert_eq!(cpp(&null), "nullptr");
+
+        let arr = Expr::new_init_arr_designated(vec![1, 3], vec![Expr::Int(1), Expr::Int(2)]);
+        assert_eq!(arr.to_string(), "{[1]=1, [3]=2}");
+        assert_eq!(cpp(&arr), "{{}, 1, {}, 2}");
+
+        let s = Expr::new_init_struct_designated(
+            vec!["z".to_string(), "x".to_string()],
+            vec![Expr::Int(1), Expr::Int(2)],
+        );
+        assert_eq!(s.to_string(), "{.z=1, .x=2}");
+
+        // The fields are put in the order of the struct that has them, if it is registered, and
+        // keep their designators otherwise rather than initializing the wrong fields
+        let mut res = String::new();
+        let mut fmt = Formatter::new_with_language(&mut res, Language::Cpp);
+        s.format(&mut fmt).unwrap();
+        assert_eq!(fmt.warnings().len(), 1);
+        let point = Struct::new("Point".to_string())
+            .fields(
+                ["x", "y", "z"]
+                    .map(|name| {
+                        Field::new(name.to_string(), Type::new(BaseType::Int).build()).build()
+                    })
+                    .to_vec(),
+            )
+            .build();
+        fmt.register_fields(&point);
+        write!(fmt, " ").unwrap();
+        s.format(&mut fmt).unwrap();
+        assert_eq!(fmt.warnings().len(), 1);
+        assert_eq!(res, "{.z=1, .x=2} {2, {}, 1}");
+    }
+
+    #[test]
+    fn children() {
+        let names = |e: &Expr| e.children().map(|c| c.to_string()).collect::<Vec<_>>();
+
+        assert!(Expr::Int(1).children().next().is_none());
+
+        let call = Expr::new_fn_call_with_name(
+            "f".to_string(),
+            vec![Expr::Int(1), Expr::new_ident_with_str("x")],
+        );
+        assert_eq!(names(&call), ["f", "1", "x"]);
+
+        let ternary = Expr::new_ternary(Expr::Bool(true), call, Expr::Int(0));
+        assert_eq!(names(&ternary), ["true", "f(1, x)", "0"]);
+
+        // Counting the nodes with an explicit stack
+        let mut count = 0;
+        let mut stack = vec![&ternary];
+        while let Some(e) = stack.pop() {
+            count += 1;
+            stack.extend(e.children());
+        }
+        assert_eq!(count, 7);
+    }
 }
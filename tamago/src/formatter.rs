@@ -21,10 +21,84 @@
 //! This module provides a formatter implementation that emits a string of C code with the right
 //! indentation.
 
+use std::collections::BTreeMap;
 use std::fmt::{self, Write};
 
+use crate::{Abi, BaseType, Function, LayoutError, Struct, StructLayout, TypeLayout};
+
 const DEFAULT_INDENT: usize = 2;
 
+/// The family of C compilers the generated code is meant for.
+///
+/// Compiler-specific extensions such as `__attribute__` are emitted according to the dialect and
+/// silently dropped when the dialect doesn't support them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dialect {
+    /// GCC, Clang, and other compilers that understand `__attribute__((...))`
+    #[default]
+    Gnu,
+
+    /// Microsoft Visual C++, which uses `__declspec(...)` and `__forceinline`
+    Msvc,
+
+    /// Plain ISO C without any compiler extensions
+    Standard,
+}
+
+/// The language the generated code has to compile as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    /// C
+    #[default]
+    C,
+
+    /// C++ (before C++20), e.g. for headers generated for C++ code.
+    ///
+    /// Empty parameter lists are emitted as `()` instead of `(void)`, null pointers as `nullptr`
+    /// instead of `NULL`, and designated initializers are replaced with positional ones. The
+    /// fields of a struct initializer are put in the order of the struct definition formatted
+    /// before it, or `register_fields` registers one defined elsewhere. Without one, the
+    /// initializer keeps its designators, which need C++20, and a warning is recorded.
+    Cpp,
+}
+
+/// The line ending written for each newline.
+///
+/// Line endings in the written text itself, like in `Raw` statements, are normalized to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Newline {
+    /// `\n`, as on Unix
+    #[default]
+    Lf,
+
+    /// `\r\n`, as on Windows
+    CrLf,
+}
+
+impl Newline {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Newline::Lf => "\n",
+            Newline::CrLf => "\r\n",
+        }
+    }
+}
+
+/// How much of the layout of the generated code is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Style {
+    /// Indented code with comments, meant to be read
+    #[default]
+    Pretty,
+
+    /// Code without indentation, comments, or the newlines C doesn't need, e.g. for code that is
+    /// only compiled.
+    ///
+    /// Newlines are kept after preprocessor directives and lines that a `Raw` statement ends with
+    /// a comment or a `\`, and replaced with a space where two words would run together.
+    Compact,
+}
+
 pub trait Format {
     fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result;
 }
@@ -43,6 +117,73 @@ pub struct Formatter<'a> {
 
     /// Indentation level
     pub indent: usize,
+
+    /// The dialect compiler-specific extensions are emitted for
+    pub dialect: Dialect,
+
+    /// The language the emitted code has to compile as
+    pub language: Language,
+
+    /// The maximum number of consecutive blank lines, with any further ones dropped
+    pub max_blank_lines: Option<usize>,
+
+    /// The line ending of each line
+    pub newline: Newline,
+
+    /// Whether the code is written to be read or as compactly as possible
+    pub style: Style,
+
+    /// Whether a formatted `Scope` ends with exactly one newline, no matter how many its last
+    /// statement writes
+    pub single_trailing_newline: bool,
+
+    /// Whether a `#line` directive is emitted before each `GlobalStatement::Tagged`
+    pub line_directives: bool,
+
+    /// Whether functions are emitted as prototypes even if they have a body, e.g. for a header
+    pub prototypes_only: bool,
+
+    /// Whether prototypes keep the names of their parameters, which definitions always need
+    pub param_names: bool,
+
+    /// Whether the code of `Raw` statements is checked for unbalanced brackets and a missing or
+    /// extra terminating `;`, which is reported in `warnings` without failing the formatting
+    pub check_raw: bool,
+
+    /// The ABI that the definitions of structs and unions are laid out under to document their
+    /// size and alignment in a trailing comment (`// size: 24, align: 8`), if any.
+    ///
+    /// Each struct and union formatted is registered with it, so the ones containing them can be
+    /// laid out as well.
+    pub layout_comments: Option<Abi>,
+
+    /// Whether the arguments of calls to functions whose parameters are known are preceded by
+    /// the names of the parameters, like `resize(/* width= */ 640, /* height= */ 480)`.
+    ///
+    /// The parameters of each function formatted are registered, so calls after its definition or
+    /// prototype are annotated, and `register_params` registers the ones declared elsewhere.
+    pub param_comments: bool,
+
+    /// The names of the parameters of the functions registered for `param_comments`
+    params: BTreeMap<String, Vec<String>>,
+
+    /// The names of the fields of the structs registered for C++ initializers
+    fields: BTreeMap<String, Vec<String>>,
+
+    /// The problems found while formatting
+    warnings: Vec<String>,
+
+    /// The number of blank lines at the end of the buffer
+    blank_lines: usize,
+
+    /// The path of the last `#line` directive, which doesn't need to be repeated in the next one
+    line_path: Option<String>,
+
+    /// Whether a newline was left out in the `Compact` style after the last line written
+    pending_newline: bool,
+
+    /// Whether the current line can't be joined with the next one in the `Compact` style
+    keeps_newline: bool,
 }
 
 impl<'a> Formatter<'a> {
@@ -52,9 +193,54 @@ impl<'a> Formatter<'a> {
             spaces: 0,
             scope: vec![],
             indent: DEFAULT_INDENT,
+            dialect: Dialect::default(),
+            language: Language::default(),
+            max_blank_lines: None,
+            newline: Newline::default(),
+            style: Style::default(),
+            single_trailing_newline: true,
+            line_directives: false,
+            prototypes_only: false,
+            param_names: true,
+            check_raw: false,
+            layout_comments: None,
+            param_comments: false,
+            params: BTreeMap::new(),
+            fields: BTreeMap::new(),
+            warnings: vec![],
+            blank_lines: 0,
+            line_path: None,
+            pending_newline: false,
+            keeps_newline: false,
+        }
+    }
+
+    pub fn new_with_dialect(dst: &'a mut String, dialect: Dialect) -> Self {
+        Self {
+            dialect,
+            ..Self::new(dst)
+        }
+    }
+
+    pub fn new_with_language(dst: &'a mut String, language: Language) -> Self {
+        Self {
+            language,
+            ..Self::new(dst)
         }
     }
 
+    pub fn new_with_style(dst: &'a mut String, style: Style) -> Self {
+        Self {
+            style,
+            ..Self::new(dst)
+        }
+    }
+
+    /// Whether comments, including doc comments, are left out
+    pub fn is_compact(&self) -> bool {
+        self.style == Style::Compact
+    }
+
     pub fn scope<F, R>(&mut self, name: &str, f: F) -> R
     where
         F: FnOnce(&mut Self) -> R,
@@ -69,7 +255,7 @@ impl<'a> Formatter<'a> {
     where
         F: FnOnce(&mut Self) -> fmt::Result,
     {
-        if !self.is_start_of_line() {
+        if !self.is_start_of_line() && !self.is_compact() {
             write!(self, " ")?;
         }
 
@@ -94,8 +280,176 @@ impl<'a> Formatter<'a> {
         self.dst.is_empty() || self.dst.ends_with('\n')
     }
 
+    /// Writes a `#line` directive, leaving out the path if it is the same as the last one's
+    pub fn line_directive(&mut self, line: u64, path: &str) -> fmt::Result {
+        if self.line_path.as_deref() == Some(path) {
+            writeln!(self, "#line {line}")
+        } else {
+            self.line_path = Some(path.to_string());
+            writeln!(self, "#line {line} \"{path}\"")
+        }
+    }
+
+    /// Returns the problems found while formatting, e.g. by `check_raw` or static local variables
+    /// initialized with a non-constant expression
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Records a problem found while formatting
+    pub(crate) fn warn(&mut self, warning: String) {
+        self.warnings.push(warning);
+    }
+
+    /// Records a warning for `raw` if `check_raw` is set and it doesn't look like a complete
+    /// statement
+    pub(crate) fn lint_raw(&mut self, raw: &str) {
+        if !self.check_raw {
+            return;
+        }
+
+        if let Some(problem) = raw_problem(raw) {
+            self.warnings
+                .push(format!("Raw statement '{}' {problem}", raw.trim()));
+        }
+    }
+
+    /// Writes the size and alignment of the struct or union `t` after its definition if
+    /// `layout_comments` is set, recording a warning instead if its layout can't be computed
+    pub(crate) fn layout_comment(
+        &mut self,
+        t: BaseType,
+        layout: impl FnOnce(&Abi) -> Result<StructLayout, LayoutError>,
+    ) -> fmt::Result {
+        let Some(abi) = self.layout_comments.take() else {
+            return Ok(());
+        };
+
+        match layout(&abi) {
+            Ok(layout) => {
+                let t_layout = TypeLayout::new(layout.size, layout.align);
+                self.layout_comments = Some(abi.layout(t, t_layout));
+                if self.is_compact() {
+                    return Ok(());
+                }
+                write!(self, " // size: {}, align: {}", layout.size, layout.align)
+            }
+            Err(err) => {
+                self.warn(format!("Couldn't compute the layout of '{t}': {err}"));
+                self.layout_comments = Some(abi);
+                Ok(())
+            }
+        }
+    }
+
+    /// Registers the names of the parameters of `f`, so that the arguments of the calls to it
+    /// formatted afterwards are annotated with them if `param_comments` is set
+    pub fn register_params(&mut self, f: &Function) {
+        let names = f.params.iter().map(|param| param.name.clone()).collect();
+        self.params.insert(f.name.clone(), names);
+    }
+
+    /// Registers the names of the fields of `s`, so that the designated initializers formatted
+    /// afterwards can be put in its order when the language is `Cpp`
+    pub fn register_fields(&mut self, s: &Struct) {
+        let names = s.fields.iter().map(|field| field.name.clone()).collect();
+        self.fields.insert(s.name.clone(), names);
+    }
+
+    /// Returns the names of the fields of the only registered struct that has all of `names`,
+    /// or `None` if there isn't exactly one, unless they all have the same fields
+    pub(crate) fn struct_fields(&self, names: &[&str]) -> Option<&[String]> {
+        let mut structs = self
+            .fields
+            .values()
+            .filter(|fields| names.iter().all(|name| fields.iter().any(|f| f == name)));
+        let fields = structs.next()?;
+        structs
+            .all(|other| other == fields)
+            .then_some(fields.as_slice())
+    }
+
+    /// Returns the names of the parameters of the function `name` if `param_comments` is set and
+    /// they are registered, unless comments are left out
+    pub(crate) fn param_names(&self, name: &str) -> Option<&[String]> {
+        if !self.param_comments || self.is_compact() {
+            return None;
+        }
+        self.params.get(name).map(Vec::as_slice)
+    }
+
+    /// Ends the buffer with exactly one newline if `single_trailing_newline` is set, unless it
+    /// is empty
+    pub(crate) fn finish(&mut self) {
+        if !self.single_trailing_newline || self.dst.is_empty() {
+            return;
+        }
+
+        let trimmed = self.dst.trim_end_matches(['\r', '\n']).len();
+        self.dst.truncate(trimmed);
+        self.dst.push_str(self.newline.as_str());
+        self.blank_lines = 0;
+        self.pending_newline = false;
+        self.keeps_newline = false;
+    }
+
+    fn push_newline(&mut self) {
+        if self.is_compact() {
+            // A line continued with `\` stays a directive or a comment
+            let continued = self.dst.trim_end_matches('\r').ends_with('\\');
+            if self.keeps_newline || continued {
+                self.dst.push_str(self.newline.as_str());
+                self.keeps_newline = continued && self.keeps_newline;
+            } else if !self.is_start_of_line() {
+                self.pending_newline = true;
+            }
+            return;
+        }
+
+        if self.is_start_of_line() {
+            self.blank_lines += 1;
+            if self
+                .max_blank_lines
+                .is_some_and(|max| self.blank_lines > max)
+            {
+                return;
+            }
+        }
+
+        self.dst.push_str(self.newline.as_str());
+    }
+
     fn push_spaces(&mut self) {
-        self.dst.push_str(&" ".repeat(self.spaces))
+        if !self.is_compact() {
+            self.dst.push_str(&" ".repeat(self.spaces))
+        }
+    }
+
+    /// Writes what the newline left out before `line` in the `Compact` style has to become, and
+    /// returns `line` without the indentation it may have
+    fn join_line<'s>(&mut self, line: &'s str) -> &'s str {
+        let line = if self.pending_newline || self.is_start_of_line() {
+            line.trim_start()
+        } else {
+            line
+        };
+        if line.is_empty() {
+            return line;
+        }
+
+        if std::mem::take(&mut self.pending_newline) {
+            let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+            if line.starts_with('#') {
+                self.dst.push_str(self.newline.as_str());
+            } else if is_word(self.dst.chars().next_back()) && is_word(line.chars().next()) {
+                self.dst.push(' ');
+            }
+        }
+
+        if (self.is_start_of_line() && line.starts_with('#')) || line.contains("//") {
+            self.keeps_newline = true;
+        }
+        line
     }
 }
 
@@ -105,7 +459,7 @@ impl Write for Formatter<'_> {
 
         for (idx, line) in s.lines().enumerate() {
             if idx != 0 {
-                self.dst.push('\n');
+                self.push_newline();
             }
 
             if should_indent && !line.is_empty() && line.as_bytes()[0] != b'\n' {
@@ -114,13 +468,103 @@ impl Write for Formatter<'_> {
 
             should_indent = true;
 
+            let line = if self.is_compact() {
+                self.join_line(line)
+            } else {
+                line
+            };
+
+            if !line.is_empty() {
+                self.blank_lines = 0;
+            }
             self.dst.push_str(line);
         }
 
         if s.as_bytes().last() == Some(&b'\n') {
-            self.dst.push('\n');
+            self.push_newline();
         }
 
         Ok(())
     }
 }
+
+/// Returns what is wrong with a piece of raw C code, ignoring string and character literals and
+/// comments: an unbalanced bracket, or, unless it ends with a block, a label, or a preprocessor
+/// directive, a missing or doubled terminating `;`
+fn raw_problem(raw: &str) -> Option<String> {
+    let mut open = vec![];
+    // The last two characters of code and whether the last one is on a preprocessor line
+    let mut last = (None, None);
+    let mut in_directive = false;
+    let mut last_in_directive = false;
+    let mut at_line_start = true;
+
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => {
+                at_line_start = true;
+                in_directive = false;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+                continue;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+                continue;
+            }
+            '"' | '\'' => {
+                while let Some(next) = chars.next() {
+                    if next == '\\' {
+                        chars.next();
+                    } else if next == c {
+                        break;
+                    }
+                }
+            }
+            '(' | '[' | '{' => open.push(c),
+            ')' | ']' | '}' => {
+                let Some(opening) = open.pop() else {
+                    return Some(format!("has an unmatched '{c}'"));
+                };
+                let expected = match opening {
+                    '(' => ')',
+                    '[' => ']',
+                    _ => '}',
+                };
+                if c != expected {
+                    return Some(format!("closes '{opening}' with '{c}'"));
+                }
+            }
+            _ => {}
+        }
+
+        if at_line_start {
+            in_directive = c == '#';
+            at_line_start = false;
+        }
+        last = (last.1, Some(c));
+        last_in_directive = in_directive;
+    }
+
+    if let Some(c) = open.last() {
+        return Some(format!("has an unclosed '{c}'"));
+    }
+
+    match last {
+        _ if last_in_directive => None,
+        (Some(';'), Some(';')) => Some("ends with an extra ';'".to_string()),
+        (_, Some(';' | '}' | ':') | None) => None,
+        _ => Some("is missing a terminating ';'".to_string()),
+    }
+}
@@ -26,8 +26,8 @@
 
 use std::fmt::{self, Write};
 
-use crate::{Block, DocComment, Format, Formatter, Statement, Type};
-use tamacro::DisplayFromFormat;
+use crate::{Block, Dialect, DocComment, Format, Formatter, Language, Statement, Type};
+use tamacro::{DisplayFromConstSymbol, DisplayFromFormat};
 
 /// Represents a C function with all its components and attributes.
 ///
@@ -75,6 +75,9 @@ pub struct Function {
     /// Whether the function is declared with the 'extern' keyword
     pub is_extern: bool,
 
+    /// Optimization hints such as `noinline` or `cold`
+    pub hints: Vec<FunctionHint>,
+
     /// The body of the function represented as a Block of statements
     pub body: Block,
 
@@ -124,6 +127,13 @@ impl Format for Function {
         if let Some(doc) = &self.doc {
             doc.format(fmt)?;
         }
+        self.format_param_docs(fmt)?;
+        if fmt.param_comments {
+            fmt.register_params(self);
+        }
+
+        let is_prototype = self.body.stmts.is_empty() || self.is_extern || fmt.prototypes_only;
+        let named = !is_prototype || fmt.param_names;
 
         if self.body.stmts.is_empty() && self.is_extern {
             write!(fmt, "extern ")?;
@@ -133,30 +143,37 @@ impl Format for Function {
             write!(fmt, "static ")?;
         }
 
-        if self.is_inline {
+        let force_inline =
+            fmt.dialect == Dialect::Msvc && self.hints.contains(&FunctionHint::AlwaysInline);
+
+        if self.is_inline && !force_inline {
             write!(fmt, "inline ")?;
         }
 
+        self.format_hints(fmt)?;
+
         self.ret.format(fmt)?;
         write!(fmt, " ")?;
 
         write!(fmt, "{}(", self.name)?;
         if self.params.is_empty() {
-            write!(fmt, "void")?;
+            if fmt.language == Language::C {
+                write!(fmt, "void")?;
+            }
         } else if !self.params.is_empty() {
             for param in &self.params[..self.params.len() - 1] {
-                param.format(fmt)?;
+                param.format_named(fmt, named)?;
                 write!(fmt, ", ")?;
             }
 
             if let Some(last) = self.params.last() {
-                last.format(fmt)?;
+                last.format_named(fmt, named)?;
             }
         }
 
         write!(fmt, ")")?;
 
-        if !self.body.stmts.is_empty() && !self.is_extern {
+        if !is_prototype {
             fmt.block(|fmt| self.body.format(fmt))?;
             writeln!(fmt)
         } else {
@@ -165,6 +182,93 @@ impl Format for Function {
     }
 }
 
+impl Function {
+    fn format_param_docs(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        if fmt.is_compact() {
+            return Ok(());
+        }
+
+        for param in &self.params {
+            let Some(doc) = &param.doc else {
+                continue;
+            };
+
+            let tag = format!("@param {}", param.name);
+            for (i, line) in doc.docs.iter().enumerate() {
+                if i == 0 {
+                    writeln!(fmt, "/// {tag} {line}")?;
+                } else {
+                    writeln!(fmt, "/// {:width$} {line}", "", width = tag.len())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn format_hints(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        if self.hints.is_empty() {
+            return Ok(());
+        }
+
+        match fmt.dialect {
+            Dialect::Gnu => {
+                let hints = self
+                    .hints
+                    .iter()
+                    .map(|hint| hint.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(fmt, "__attribute__(({hints})) ")
+            }
+            Dialect::Msvc => {
+                for hint in &self.hints {
+                    match hint {
+                        FunctionHint::NoInline => write!(fmt, "__declspec(noinline) ")?,
+                        FunctionHint::AlwaysInline => write!(fmt, "__forceinline ")?,
+                        FunctionHint::Hot | FunctionHint::Cold | FunctionHint::Flatten => {}
+                    }
+                }
+                Ok(())
+            }
+            Dialect::Standard => Ok(()),
+        }
+    }
+}
+
+/// Hints that tell the C compiler how a function should be optimized.
+///
+/// These are emitted as `__attribute__((...))` for the GNU dialect. MSVC only understands
+/// `noinline` and `always_inline` (as `__forceinline`), and the rest are dropped there as well as
+/// in standard C.
+///
+/// # Examples
+///
+/// ```c
+/// __attribute__((noinline, cold)) void report_error(const char* msg);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DisplayFromConstSymbol)]
+pub enum FunctionHint {
+    /// Never inline the function
+    #[symbol = "noinline"]
+    NoInline,
+
+    /// Always inline the function, even when optimizations are disabled
+    #[symbol = "always_inline"]
+    AlwaysInline,
+
+    /// The function is called often and should be optimized aggressively
+    #[symbol = "hot"]
+    Hot,
+
+    /// The function is rarely called and should be optimized for size
+    #[symbol = "cold"]
+    Cold,
+
+    /// Inline every call made inside the function body
+    #[symbol = "flatten"]
+    Flatten,
+}
+
 /// A builder for constructing a `Function` instance with a fluent interface.
 ///
 /// The `FunctionBuilder` provides a step-by-step approach to creating complex C functions
@@ -177,6 +281,7 @@ pub struct FunctionBuilder {
     is_inline: bool,
     is_static: bool,
     is_extern: bool,
+    hints: Vec<FunctionHint>,
     body: Block,
     doc: Option<DocComment>,
 }
@@ -206,6 +311,7 @@ impl FunctionBuilder {
             is_inline: false,
             is_static: false,
             is_extern: false,
+            hints: vec![],
             body: Block::new().build(),
             doc: None,
         }
@@ -315,6 +421,109 @@ impl FunctionBuilder {
         self
     }
 
+    /// Adds an optimization hint to the function being built.
+    ///
+    /// # Parameters
+    ///
+    /// * `hint` - The hint to attach to the function
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let builder = FunctionBuilder::new_with_str("slow_path", Type::new(BaseType::Void).build())
+    ///     .hint(FunctionHint::Cold);
+    /// ```
+    pub fn hint(mut self, hint: FunctionHint) -> Self {
+        if !self.hints.contains(&hint) {
+            self.hints.push(hint);
+        }
+        self
+    }
+
+    /// Prevents the function from being inlined.
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let builder = FunctionBuilder::new_with_str("trace", Type::new(BaseType::Void).build())
+    ///     .make_noinline();
+    /// ```
+    pub fn make_noinline(self) -> Self {
+        self.hint(FunctionHint::NoInline)
+    }
+
+    /// Forces the function to be inlined at every call site.
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let builder = FunctionBuilder::new_with_str("square", Type::new(BaseType::Int).build())
+    ///     .make_always_inline();
+    /// ```
+    pub fn make_always_inline(self) -> Self {
+        self.hint(FunctionHint::AlwaysInline)
+    }
+
+    /// Marks the function as a hot spot that should be optimized aggressively.
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let builder = FunctionBuilder::new_with_str("update", Type::new(BaseType::Void).build())
+    ///     .make_hot();
+    /// ```
+    pub fn make_hot(self) -> Self {
+        self.hint(FunctionHint::Hot)
+    }
+
+    /// Marks the function as unlikely to be called.
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let builder = FunctionBuilder::new_with_str("panic", Type::new(BaseType::Void).build())
+    ///     .make_cold();
+    /// ```
+    pub fn make_cold(self) -> Self {
+        self.hint(FunctionHint::Cold)
+    }
+
+    /// Inlines every call made inside the function body.
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let builder = FunctionBuilder::new_with_str("render", Type::new(BaseType::Void).build())
+    ///     .make_flatten();
+    /// ```
+    pub fn make_flatten(self) -> Self {
+        self.hint(FunctionHint::Flatten)
+    }
+
     /// Sets the body block for the function being built.
     ///
     /// # Parameters
@@ -454,6 +663,13 @@ impl FunctionBuilder {
     ///
     /// A fully constructed `Function` instance
     ///
+    /// # Panics
+    ///
+    /// Panics if the configured modifiers contradict each other, see [`try_build`] for a
+    /// version that returns the error instead
+    ///
+    /// [`try_build`]: FunctionBuilder::try_build
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -464,19 +680,77 @@ impl FunctionBuilder {
     ///     .build();
     /// ```
     pub fn build(self) -> Function {
-        Function {
+        self.try_build().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Finalizes the function definition, checking that the configured modifiers are valid C.
+    ///
+    /// An `extern` function is defined in another translation unit, so it can have neither a
+    /// body nor internal linkage through `static`.
+    ///
+    /// # Returns
+    ///
+    /// A fully constructed `Function` instance, or the reason it cannot be represented in C
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let err = FunctionBuilder::new_with_str("write", Type::new(BaseType::Int).build())
+    ///     .make_extern()
+    ///     .make_static()
+    ///     .try_build()
+    ///     .unwrap_err();
+    /// assert_eq!(err, FunctionError::StaticExtern("write".to_string()));
+    /// ```
+    pub fn try_build(self) -> Result<Function, FunctionError> {
+        if self.is_extern && self.is_static {
+            return Err(FunctionError::StaticExtern(self.name));
+        }
+
+        if self.is_extern && !self.body.stmts.is_empty() {
+            return Err(FunctionError::ExternWithBody(self.name));
+        }
+
+        Ok(Function {
             name: self.name,
             ret: self.ret,
             params: self.params,
-            is_inline: self.is_extern,
+            is_inline: self.is_inline,
             is_static: self.is_static,
             is_extern: self.is_extern,
+            hints: self.hints,
             body: self.body,
             doc: self.doc,
+        })
+    }
+}
+
+/// The reasons a [`FunctionBuilder`] cannot build a valid C function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FunctionError {
+    /// The function, e.g. `write`, is `extern` but has a body, which only a definition can have
+    ExternWithBody(String),
+
+    /// The function is both `static` and `extern`, which are contradictory storage classes
+    StaticExtern(String),
+}
+
+impl fmt::Display for FunctionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FunctionError::ExternWithBody(name) => write!(
+                f,
+                "the extern function '{name}' cannot have a body, it is defined elsewhere"
+            ),
+            FunctionError::StaticExtern(name) => {
+                write!(f, "the function '{name}' cannot be both static and extern")
+            }
         }
     }
 }
 
+impl std::error::Error for FunctionError {}
+
 /// Represents a single parameter in a C function declaration or definition.
 ///
 /// A parameter consists of a name and a type, with special handling for array parameters.
@@ -488,6 +762,12 @@ pub struct Parameter {
 
     /// The type of the parameter
     pub t: Type,
+
+    /// Whether the parameter is declared with the `register` keyword
+    pub is_register: bool,
+
+    /// The doc comment, emitted as `@param` lines in the doc comment of the function
+    pub doc: Option<DocComment>,
 }
 
 impl Parameter {
@@ -520,6 +800,8 @@ impl Parameter {
 pub struct ParameterBuilder {
     name: String,
     t: Type,
+    is_register: bool,
+    doc: Option<DocComment>,
 }
 
 impl ParameterBuilder {
@@ -540,7 +822,12 @@ impl ParameterBuilder {
     /// let builder = ParameterBuilder::new("count".to_string(), Type::new(BaseType::Int).build());
     /// ```
     pub fn new(name: String, t: Type) -> Self {
-        Self { name, t }
+        Self {
+            name,
+            t,
+            is_register: false,
+            doc: None,
+        }
     }
 
     /// Creates and returns a new `ParameterBuilder` using a string slice for the name.
@@ -566,6 +853,53 @@ impl ParameterBuilder {
         Self::new(name.to_string(), t)
     }
 
+    /// Makes the parameter a `register` parameter.
+    ///
+    /// In C, the `register` keyword hints that the value should be kept in a CPU register,
+    /// and forbids taking its address.
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let param = ParameterBuilder::new_with_str("i", Type::new(BaseType::Int).build())
+    ///     .make_register()
+    ///     .build();
+    /// assert_eq!(param.to_string(), "register int i");
+    /// ```
+    pub fn make_register(mut self) -> Self {
+        self.is_register = true;
+        self
+    }
+
+    /// Sets the documentation comment for the parameter.
+    ///
+    /// It is emitted as `@param` lines in the doc comment of the function, so it is kept even
+    /// when prototypes are emitted without parameter names.
+    ///
+    /// # Parameters
+    ///
+    /// * `doc` - The documentation comment to attach to the parameter
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let param = ParameterBuilder::new_with_str("len", Type::new(BaseType::Size).build())
+    ///     .doc(DocCommentBuilder::new().line_str("the number of bytes").build())
+    ///     .build();
+    /// ```
+    pub fn doc(mut self, doc: DocComment) -> Self {
+        self.doc = Some(doc);
+        self
+    }
+
     /// Finalizes the parameter definition and returns a fully constructed `Parameter`.
     ///
     /// This method consumes the builder and produces the final `Parameter` object
@@ -585,21 +919,26 @@ impl ParameterBuilder {
         Parameter {
             name: self.name,
             t: self.t,
+            is_register: self.is_register,
+            doc: self.doc,
         }
     }
 }
 
 impl Format for Parameter {
     fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
-        self.t.format(fmt)?;
-
-        write!(fmt, " {}", self.name)?;
+        self.format_named(fmt, true)
+    }
+}
 
-        if self.t.is_array() {
-            write!(fmt, "[{}]", self.t.array)?;
+impl Parameter {
+    fn format_named(&self, fmt: &mut Formatter<'_>, named: bool) -> fmt::Result {
+        if self.is_register {
+            write!(fmt, "register ")?;
         }
 
-        Ok(())
+        self.t
+            .format_declarator(fmt, if named { &self.name } else { "" })
     }
 }
 
@@ -625,10 +964,154 @@ mod tests {
                     .build(),
             )
             .build();
-        let res = r#"double some_function(double val) {
+        let res = r#"inline double some_function(double val) {
   return 1.23 + val;
 }
 "#;
         assert_eq!(f.to_string(), res);
     }
+
+    #[test]
+    fn prototypes() {
+        let f = FunctionBuilder::new_with_str("add", Type::new(BaseType::Int).build())
+            .doc(DocCommentBuilder::new().line_str("Adds two numbers").build())
+            .param(
+                ParameterBuilder::new_with_str("a", Type::new(BaseType::Int).build())
+                    .doc(DocCommentBuilder::new().line_str("the first number").build())
+                    .build(),
+            )
+            .param(
+                ParameterBuilder::new_with_str("b", Type::new(BaseType::Int).make_array(2).build())
+                    .doc(
+                        DocCommentBuilder::new()
+                            .line_str("the other numbers,")
+                            .line_str("only the first is used")
+                            .build(),
+                    )
+                    .build(),
+            )
+            .statement(Statement::Return(Some(Expr::Ident("a".to_string()))))
+            .build();
+        let docs = "/// Adds two numbers\n/// @param a the first number\n\
+                    /// @param b the other numbers,\n///          only the first is used\n";
+
+        assert_eq!(
+            f.to_string(),
+            format!("{docs}int add(int a, int b[2]) {{\n  return a;\n}}\n")
+        );
+
+        let mut res = String::new();
+        let mut fmt = Formatter::new(&mut res);
+        fmt.prototypes_only = true;
+        f.format(&mut fmt).unwrap();
+        assert_eq!(res, format!("{docs}int add(int a, int b[2]);\n"));
+
+        let mut res = String::new();
+        let mut fmt = Formatter::new(&mut res);
+        fmt.prototypes_only = true;
+        fmt.param_names = false;
+        f.format(&mut fmt).unwrap();
+        assert_eq!(res, format!("{docs}int add(int, int[2]);\n"));
+
+        // Definitions keep their parameter names
+        let mut res = String::new();
+        let mut fmt = Formatter::new(&mut res);
+        fmt.param_names = false;
+        f.format(&mut fmt).unwrap();
+        assert!(res.contains("int add(int a, int b[2]) {"));
+    }
+
+    #[test]
+    fn function_hints() {
+        let f = FunctionBuilder::new_with_str("report", Type::new(BaseType::Void).build())
+            .make_static()
+            .make_noinline()
+            .make_cold()
+            .make_cold()
+            .param(
+                ParameterBuilder::new_with_str("code", Type::new(BaseType::Int).build())
+                    .make_register()
+                    .build(),
+            )
+            .build();
+
+        assert_eq!(
+            f.to_string(),
+            "static __attribute__((noinline, cold)) void report(register int code);\n"
+        );
+
+        let mut res = String::new();
+        f.format(&mut Formatter::new_with_dialect(&mut res, Dialect::Msvc))
+            .unwrap();
+        assert_eq!(
+            res,
+            "static __declspec(noinline) void report(register int code);\n"
+        );
+
+        let mut res = String::new();
+        f.format(&mut Formatter::new_with_dialect(&mut res, Dialect::Standard))
+            .unwrap();
+        assert_eq!(res, "static void report(register int code);\n");
+
+        let g = FunctionBuilder::new_with_str("square", Type::new(BaseType::Int).build())
+            .make_always_inline()
+            .make_hot()
+            .make_flatten()
+            .build();
+
+        assert_eq!(
+            g.to_string(),
+            "__attribute__((always_inline, hot, flatten)) int square(void);\n"
+        );
+
+        let mut res = String::new();
+        g.format(&mut Formatter::new_with_dialect(&mut res, Dialect::Msvc))
+            .unwrap();
+        assert_eq!(res, "__forceinline int square(void);\n");
+
+        let mut res = String::new();
+        g.format(&mut Formatter::new_with_language(&mut res, Language::Cpp))
+            .unwrap();
+        assert_eq!(
+            res,
+            "__attribute__((always_inline, hot, flatten)) int square();\n"
+        );
+    }
+    #[test]
+    fn invalid_modifiers() {
+        let int = || Type::new(BaseType::Int).build();
+
+        let err = FunctionBuilder::new_with_str("write", int())
+            .make_static()
+            .make_extern()
+            .try_build()
+            .unwrap_err();
+        assert_eq!(err, FunctionError::StaticExtern("write".to_string()));
+        assert_eq!(
+            err.to_string(),
+            "the function 'write' cannot be both static and extern"
+        );
+
+        let err = FunctionBuilder::new_with_str("read", int())
+            .make_extern()
+            .statement(Statement::Return(Some(Expr::Int(0))))
+            .try_build()
+            .unwrap_err();
+        assert_eq!(err, FunctionError::ExternWithBody("read".to_string()));
+
+        let f = FunctionBuilder::new_with_str("read", int())
+            .make_extern()
+            .try_build()
+            .unwrap();
+        assert_eq!(f.to_string(), "extern int read(void);\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot have a body")]
+    fn extern_with_body() {
+        FunctionBuilder::new_with_str("read", Type::new(BaseType::Int).build())
+            .make_extern()
+            .statement(Statement::Return(Some(Expr::Int(0))))
+            .build();
+    }
 }
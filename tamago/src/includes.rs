@@ -0,0 +1,518 @@
+// Copyright (c) 2025 Nobuharu Shimazu
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! This module provides an include-what-you-use analysis of generated code.
+//!
+//! It scans a `Scope` for the constructs that need a standard header, such as `bool` or
+//! `uint32_t`, and for calls to registered standard functions, and compares the headers they need
+//! with the system headers that are actually included.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    BaseType, Block, Expr, GlobalStatement, IncludeBuilder, Scope, ScopeOrBlock, Statement, Type,
+};
+
+/// Headers needed by the C types and literals themselves, whatever symbols are registered.
+const TYPE_HEADERS: &[&str] = &["stdbool.h", "stddef.h", "stdint.h"];
+
+/// Standard symbols known to `IncludeAnalysis::new`, with the header declaring them.
+const STANDARD_SYMBOLS: &[(&str, &[&str])] = &[
+    ("assert.h", &["assert"]),
+    (
+        "math.h",
+        &["ceil", "cos", "fabs", "floor", "pow", "sin", "sqrt", "tan"],
+    ),
+    (
+        "stdio.h",
+        &[
+            "fclose", "fflush", "fgets", "fopen", "fprintf", "fputs", "fread", "fwrite", "getchar",
+            "printf", "putchar", "puts", "scanf", "snprintf", "sprintf", "stderr", "stdin",
+            "stdout",
+        ],
+    ),
+    (
+        "stdlib.h",
+        &[
+            "abort",
+            "atoi",
+            "calloc",
+            "exit",
+            "EXIT_FAILURE",
+            "EXIT_SUCCESS",
+            "free",
+            "malloc",
+            "qsort",
+            "realloc",
+        ],
+    ),
+    (
+        "string.h",
+        &[
+            "memcmp", "memcpy", "memmove", "memset", "strcat", "strchr", "strcmp", "strcpy",
+            "strlen", "strncmp", "strncpy",
+        ],
+    ),
+];
+
+/// Finds the standard headers a scope needs, given the symbols it knows the headers of.
+///
+/// # Examples
+/// ```rust
+/// let analysis = IncludeAnalysis::new().symbol("castella_trap", "castella.h");
+/// let report = analysis.analyze(&scope);
+/// for header in &report.missing {
+///     println!("missing #include <{header}>");
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct IncludeAnalysis {
+    /// The header declaring each known function, variable, or macro
+    symbols: BTreeMap<String, String>,
+}
+
+/// The result of `IncludeAnalysis::analyze`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IncludeReport {
+    /// The headers needed by the constructs used in the scope
+    pub required: BTreeSet<String>,
+
+    /// The system headers included by the scope
+    pub included: BTreeSet<String>,
+
+    /// The required headers that are not included
+    pub missing: Vec<String>,
+
+    /// The included headers that the analysis knows about but nothing in the scope needs.
+    ///
+    /// Headers the analysis knows nothing about are never reported, since it cannot tell what
+    /// they are used for.
+    pub unused: Vec<String>,
+}
+
+impl IncludeAnalysis {
+    /// Creates an analysis that knows the headers of the common standard functions, such as
+    /// `printf` and `malloc`.
+    ///
+    /// # Returns
+    /// A new `IncludeAnalysis` with the standard symbols registered
+    pub fn new() -> Self {
+        let symbols = STANDARD_SYMBOLS
+            .iter()
+            .flat_map(|(header, names)| {
+                names
+                    .iter()
+                    .map(|name| (name.to_string(), header.to_string()))
+            })
+            .collect();
+
+        Self { symbols }
+    }
+
+    /// Registers the header declaring `name`, so that using it requires the header.
+    ///
+    /// # Parameters
+    /// * `name` - The name of a function, variable, or macro
+    /// * `header` - The header declaring it, e.g. `stdio.h`
+    ///
+    /// # Returns
+    /// The analysis with the symbol registered, for method chaining
+    pub fn symbol(mut self, name: &str, header: &str) -> Self {
+        self.symbols.insert(name.to_string(), header.to_string());
+        self
+    }
+
+    /// Returns the header declaring `name`, if the analysis knows it.
+    ///
+    /// # Parameters
+    /// * `name` - The name of a function, variable, or macro
+    ///
+    /// # Returns
+    /// The header, e.g. `stdio.h` for `printf`
+    pub fn header_of(&self, name: &str) -> Option<&str> {
+        self.symbols.get(name).map(String::as_str)
+    }
+
+    /// Scans `scope` for the headers its constructs need and compares them with the system
+    /// headers it includes.
+    ///
+    /// The analysis follows C semantics, so `bool` requires `stdbool.h` and `NULL` requires
+    /// `stddef.h`.
+    ///
+    /// # Parameters
+    /// * `scope` - The scope to analyze
+    ///
+    /// # Returns
+    /// The headers that are required, included, missing, and unused
+    pub fn analyze(&self, scope: &Scope) -> IncludeReport {
+        let mut usage = Usage {
+            symbols: &self.symbols,
+            required: BTreeSet::new(),
+            included: BTreeSet::new(),
+        };
+        usage.scope(scope);
+
+        let Usage {
+            required, included, ..
+        } = usage;
+        let missing = required.difference(&included).cloned().collect();
+        let unused = included
+            .iter()
+            .filter(|header| !required.contains(*header) && self.knows(header))
+            .cloned()
+            .collect();
+
+        IncludeReport {
+            required,
+            included,
+            missing,
+            unused,
+        }
+    }
+
+    /// Adds `#include` directives for the headers `scope` needs but does not include.
+    ///
+    /// The directives are inserted after the last global `#include`, or at the top of the scope
+    /// followed by a blank line if it has none.
+    ///
+    /// # Parameters
+    /// * `scope` - The scope to add the directives to
+    ///
+    /// # Returns
+    /// The headers that were added
+    pub fn insert_missing(&self, scope: &mut Scope) -> Vec<String> {
+        let missing = self.analyze(scope).missing;
+        if missing.is_empty() {
+            return missing;
+        }
+
+        let includes = missing.iter().map(|header| {
+            GlobalStatement::Include(IncludeBuilder::new_system_with_str(header).build())
+        });
+
+        let last_include = scope
+            .global_stmts
+            .iter()
+            .rposition(|stmt| matches!(stmt, GlobalStatement::Include(_)));
+        match last_include {
+            Some(idx) => {
+                scope.splice(idx + 1..idx + 1, includes);
+            }
+            None => {
+                scope.splice(0..0, includes.chain([GlobalStatement::NewLine]));
+            }
+        }
+
+        missing
+    }
+
+    fn knows(&self, header: &str) -> bool {
+        TYPE_HEADERS.contains(&header) || self.symbols.values().any(|h| h == header)
+    }
+}
+
+impl Default for IncludeAnalysis {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Collects the headers needed and included while walking a scope
+struct Usage<'a> {
+    symbols: &'a BTreeMap<String, String>,
+    required: BTreeSet<String>,
+    included: BTreeSet<String>,
+}
+
+impl Usage<'_> {
+    fn require(&mut self, header: &str) {
+        self.required.insert(header.to_string());
+    }
+
+    fn scope(&mut self, scope: &Scope) {
+        for stmt in &scope.global_stmts {
+            self.global_stmt(stmt);
+        }
+    }
+
+    fn global_stmt(&mut self, stmt: &GlobalStatement) {
+        use GlobalStatement::*;
+
+        match stmt {
+            Struct(s) => s.fields.iter().for_each(|f| self.t(&f.t)),
+            Union(u) => u.fields.iter().for_each(|f| self.t(&f.t)),
+            Function(f) => {
+                self.t(&f.ret);
+                f.params.iter().for_each(|p| self.t(&p.t));
+                self.block(&f.body);
+            }
+            Variable(v) => self.variable(v),
+            TypeDef(t) => self.t(&t.t),
+            IfDefDirective(i) => {
+                self.scope_or_block(&i.then);
+                if let Some(other) = &i.other {
+                    self.scope_or_block(other);
+                }
+            }
+            IfDirective(i) => {
+                self.scope_or_block(&i.then);
+                if let Some(other) = &i.other {
+                    self.scope_or_block(other);
+                }
+            }
+            Include(i) if i.is_system => {
+                self.included.insert(i.path.clone());
+            }
+            Constants(c) => c.constants.iter().for_each(|(_, value, _)| self.expr(value)),
+            Tagged(_, stmt) => self.global_stmt(stmt),
+            Comment(_) | Enum(_) | ErrorDirective(_) | Include(_) | LineDirective(_) | Macro(_)
+            | PragmaDirective(_) | WarningDirective(_) | Raw(_) | NewLine => {}
+        }
+    }
+
+    fn scope_or_block(&mut self, scope: &ScopeOrBlock) {
+        match scope {
+            ScopeOrBlock::Scope(scope) => self.scope(scope),
+            ScopeOrBlock::Block(block) => self.block(block),
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        for stmt in &block.stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Statement) {
+        use Statement::*;
+
+        match stmt {
+            Variable(v) => self.variable(v),
+            Expr(e) | Return(Some(e)) => self.expr(e),
+            If(i) => {
+                self.expr(&i.cond);
+                self.block(&i.then);
+                if let Some(other) = &i.other {
+                    self.block(other);
+                }
+            }
+            Switch(s) => {
+                self.expr(&s.cond);
+                for (case, body) in &s.cases {
+                    self.expr(case);
+                    self.block(body);
+                }
+                if let Some(default) = &s.default {
+                    self.block(default);
+                }
+            }
+            While(w) => {
+                self.expr(&w.cond);
+                self.block(&w.body);
+            }
+            DoWhile(w) => {
+                self.block(&w.body);
+                self.expr(&w.cond);
+            }
+            For(f) => {
+                for e in [&f.init, &f.cond, &f.step].into_iter().flatten() {
+                    self.expr(e);
+                }
+                self.block(&f.body);
+            }
+            IfDefDirective(i) => {
+                self.scope_or_block(&i.then);
+                if let Some(other) = &i.other {
+                    self.scope_or_block(other);
+                }
+            }
+            IfDirective(i) => {
+                self.scope_or_block(&i.then);
+                if let Some(other) = &i.other {
+                    self.scope_or_block(other);
+                }
+            }
+            Include(i) if i.is_system => {
+                self.included.insert(i.path.clone());
+            }
+            Block(b) => self.block(b),
+            Labelled(_, stmt) => self.stmt(stmt),
+            Label(label) => {
+                if let Some(stmt) = &label.stmt {
+                    self.stmt(stmt);
+                }
+            }
+            Asm(a) => {
+                for operand in a.outputs.iter().chain(&a.inputs) {
+                    self.expr(&operand.expr);
+                }
+            }
+            Comment(_) | Return(None) | Break | Continue | GoTo(_) | ErrorDirective(_)
+            | Include(_) | LineDirective(_) | Macro(_) | PragmaDirective(_)
+            | WarningDirective(_) | Raw(_) | NewLine => {}
+        }
+    }
+
+    fn variable(&mut self, v: &crate::Variable) {
+        self.t(&v.t);
+        if let Some(value) = &v.value {
+            self.expr(value);
+        }
+    }
+
+    fn t(&mut self, t: &Type) {
+        use BaseType::*;
+
+        match t.base {
+            Bool => self.require("stdbool.h"),
+            Size => self.require("stddef.h"),
+            UInt8 | UInt16 | UInt32 | UInt64 | Int8 | Int16 | Int32 | Int64 | UIntPtr => {
+                self.require("stdint.h")
+            }
+            FnPtr {
+                ref ret,
+                ref params,
+            } => {
+                self.t(ret);
+                params.iter().for_each(|p| self.t(p));
+            }
+            Void | Double | Float | Char | Int | Enum(_) | Struct(_) | Union(_) | TypeDef(_) => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        use Expr::*;
+
+        match expr {
+            Bool(_) => self.require("stdbool.h"),
+            Null => self.require("stddef.h"),
+            Ident(name) => {
+                if let Some(header) = self.symbols.get(name) {
+                    self.required.insert(header.clone());
+                }
+            }
+            Variable(v) => self.variable(v),
+            Binary { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Parenthesized { expr } | Unary { expr, .. } | MemAccess { expr, .. } => self.expr(expr),
+            Assign { lvalue, value, .. } => {
+                self.expr(lvalue);
+                self.expr(value);
+            }
+            Ternary { cond, lexpr, rexpr } => {
+                self.expr(cond);
+                self.expr(lexpr);
+                self.expr(rexpr);
+            }
+            Comma(exprs) => exprs.iter().for_each(|e| self.expr(e)),
+            FnCall { name, args } => {
+                self.expr(name);
+                args.iter().for_each(|arg| self.expr(arg));
+            }
+            ArrIndex { arr, idx } => {
+                self.expr(arr);
+                self.expr(idx);
+            }
+            Cast { t, expr } => {
+                self.t(t);
+                self.expr(expr);
+            }
+            SizeOf(t) => self.t(t),
+            InitArr(elems) => elems.iter().for_each(|(_, e)| self.expr(e)),
+            InitStruct(elems) => elems.iter().for_each(|(_, e)| self.expr(e)),
+            Double(num) if !num.is_finite() => self.require("math.h"),
+            Float(num) if !num.is_finite() => self.require("math.h"),
+            Int(_) | UInt(_) | Double(_) | Float(_) | Char(_) | Str(_) | Predefined(_)
+            | Raw(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn include_analysis() {
+        let func = FunctionBuilder::new_with_str("count", Type::new(BaseType::Bool).build())
+            .param(ParameterBuilder::new_with_str("n", Type::new(BaseType::UInt32).build()).build())
+            .statement(Statement::Expr(Expr::FnCall {
+                name: Box::new(Expr::Ident("printf".to_string())),
+                args: vec![Expr::Str("%u\\n".to_string()), Expr::Ident("n".to_string())],
+            }))
+            .statement(Statement::Expr(Expr::FnCall {
+                name: Box::new(Expr::Ident("castella_trap".to_string())),
+                args: vec![],
+            }))
+            .statement(Statement::Return(Some(Expr::Bool(true))))
+            .build();
+        let mut scope = ScopeBuilder::new()
+            .global_statement(GlobalStatement::Include(
+                IncludeBuilder::new_system_with_str("string.h").build(),
+            ))
+            .global_statement(GlobalStatement::Include(
+                IncludeBuilder::new_system_with_str("stdbool.h").build(),
+            ))
+            .global_statement(GlobalStatement::Include(
+                IncludeBuilder::new_system_with_str("unknown.h").build(),
+            ))
+            .new_line()
+            .global_statement(GlobalStatement::Function(func))
+            .build();
+
+        let analysis = IncludeAnalysis::new().symbol("castella_trap", "castella.h");
+        assert_eq!(analysis.header_of("castella_trap"), Some("castella.h"));
+        assert_eq!(analysis.header_of("printf"), Some("stdio.h"));
+        assert_eq!(analysis.header_of("castella_main"), None);
+        let report = analysis.analyze(&scope);
+        assert_eq!(report.missing, vec!["castella.h", "stdint.h", "stdio.h"]);
+        assert_eq!(report.unused, vec!["string.h"]);
+
+        let added = analysis.insert_missing(&mut scope);
+        assert_eq!(added.len(), 3);
+        assert!(analysis.analyze(&scope).missing.is_empty());
+        assert!(scope.to_string().starts_with(
+            "#include <string.h>\n#include <stdbool.h>\n#include <unknown.h>\n#include <castella.h>\n#include <stdint.h>\n#include <stdio.h>\n\n"
+        ));
+
+        let mut scope = ScopeBuilder::new()
+            .global_statement(GlobalStatement::Variable(
+                VariableBuilder::new_with_str("p", Type::new(BaseType::Size).build())
+                    .value(Expr::Null)
+                    .build(),
+            ))
+            .build();
+        assert_eq!(analysis.insert_missing(&mut scope), vec!["stddef.h"]);
+        assert_eq!(scope.to_string(), "#include <stddef.h>\n\nsize_t p = NULL;\n");
+
+        let mut scope = ScopeBuilder::new()
+            .global_statement(GlobalStatement::Variable(
+                VariableBuilder::new_with_str("limit", Type::new(BaseType::Double).build())
+                    .value(Expr::Double(f64::INFINITY))
+                    .build(),
+            ))
+            .build();
+        assert_eq!(analysis.insert_missing(&mut scope), vec!["math.h"]);
+        assert_eq!(scope.to_string(), "#include <math.h>\n\ndouble limit = INFINITY;\n");
+    }
+}
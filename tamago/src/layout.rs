@@ -0,0 +1,501 @@
+// Copyright (c) 2025 Nobuharu Shimazu
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! This module computes the memory layout of structs and unions.
+//!
+//! The sizes and alignments of the C types are not fixed by the language, so the analysis takes
+//! them from an `Abi` describing the target. It reports the offset of each field and the padding
+//! the compiler inserts, and can reorder the fields of a struct to minimize that padding.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::{BaseType, DocComment, Field, Struct, Type, Union};
+
+/// The size and alignment of a type, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypeLayout {
+    pub size: usize,
+    pub align: usize,
+}
+
+impl TypeLayout {
+    /// Creates the layout of a type with the given size and alignment.
+    pub fn new(size: usize, align: usize) -> Self {
+        Self { size, align }
+    }
+}
+
+/// The sizes and alignments of C types on a target.
+///
+/// Tag types are unknown until their definitions are registered with `register_struct` or
+/// `register_union`, except for enums, which are laid out like `int` unless registered with
+/// `layout`.
+///
+/// # Examples
+/// ```rust
+/// let abi = Abi::lp64().register_struct(&vec2)?;
+/// let layout = abi.struct_layout(&particle)?;
+/// println!("{} bytes, {} of them padding", layout.size, layout.padding());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Abi {
+    /// The layout of each base type, by how it is written in C (e.g. `uint32_t`, `struct Vec2`)
+    layouts: BTreeMap<String, TypeLayout>,
+
+    /// The layout of data and function pointers
+    pointer: TypeLayout,
+}
+
+/// The layout of a field of a struct or union.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldLayout {
+    /// The name of the field
+    pub name: String,
+
+    /// The offset of the field from the start of the struct
+    pub offset: usize,
+
+    /// The size of the field
+    pub size: usize,
+
+    /// The padding inserted before the field to align it
+    pub padding_before: usize,
+}
+
+/// The layout of a struct or union computed by `Abi::struct_layout` or `Abi::union_layout`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructLayout {
+    /// The size of the whole struct, including the trailing padding
+    pub size: usize,
+
+    /// The alignment of the struct, which is the largest alignment of its fields
+    pub align: usize,
+
+    /// The fields in declaration order
+    pub fields: Vec<FieldLayout>,
+
+    /// The padding after the last field that rounds the size up to the alignment
+    pub trailing_padding: usize,
+}
+
+/// Why a layout could not be computed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutError {
+    /// The layout of the type, e.g. `struct Node`, is not known to the `Abi`
+    UnknownType(String),
+
+    /// The field is a bitfield, whose layout is implementation-defined
+    Bitfield(String),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::UnknownType(t) => write!(f, "the layout of '{t}' is unknown"),
+            LayoutError::Bitfield(name) => {
+                write!(
+                    f,
+                    "the layout of the bitfield '{name}' is implementation-defined"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+impl Abi {
+    /// Creates the ABI of 64-bit Linux and macOS on x86-64 and AArch64, where `int` is 32 bits
+    /// and pointers, `size_t`, and `uintptr_t` are 64 bits.
+    pub fn lp64() -> Self {
+        Self::with_pointer(TypeLayout::new(8, 8))
+    }
+
+    /// Creates the ABI of 32-bit targets such as wasm32 and 32-bit ARM, where pointers,
+    /// `size_t`, and `uintptr_t` are 32 bits and 64-bit types are aligned to 8 bytes.
+    pub fn ilp32() -> Self {
+        Self::with_pointer(TypeLayout::new(4, 4))
+    }
+
+    fn with_pointer(pointer: TypeLayout) -> Self {
+        use BaseType::*;
+
+        let scalars = [
+            (Char, 1),
+            (Bool, 1),
+            (Int8, 1),
+            (UInt8, 1),
+            (Int16, 2),
+            (UInt16, 2),
+            (Int, 4),
+            (Int32, 4),
+            (UInt32, 4),
+            (Float, 4),
+            (Int64, 8),
+            (UInt64, 8),
+            (Double, 8),
+        ];
+        let mut layouts: BTreeMap<_, _> = scalars
+            .into_iter()
+            .map(|(t, size)| (t.to_string(), TypeLayout::new(size, size)))
+            .collect();
+        layouts.insert(Size.to_string(), pointer);
+        layouts.insert(UIntPtr.to_string(), pointer);
+
+        Self { layouts, pointer }
+    }
+
+    /// Sets the layout of a base type, e.g. an opaque `struct` or a `typedef`, and returns the
+    /// ABI for more chaining.
+    pub fn layout(mut self, t: BaseType, layout: TypeLayout) -> Self {
+        self.layouts.insert(t.to_string(), layout);
+        self
+    }
+
+    /// Sets the layout of data and function pointers and returns the ABI for more chaining.
+    pub fn pointer(mut self, layout: TypeLayout) -> Self {
+        self.pointer = layout;
+        self
+    }
+
+    /// Computes the layout of `s` and registers it, so that it can be used as a field of other
+    /// structs.
+    pub fn register_struct(self, s: &Struct) -> Result<Self, LayoutError> {
+        let layout = self.struct_layout(s)?;
+        Ok(self.layout(
+            BaseType::Struct(s.name.clone()),
+            TypeLayout::new(layout.size, layout.align),
+        ))
+    }
+
+    /// Computes the layout of `u` and registers it, so that it can be used as a field of
+    /// structs.
+    pub fn register_union(self, u: &Union) -> Result<Self, LayoutError> {
+        let layout = self.union_layout(u)?;
+        Ok(self.layout(
+            BaseType::Union(u.name.clone()),
+            TypeLayout::new(layout.size, layout.align),
+        ))
+    }
+
+    /// Returns the layout of `t`.
+    pub fn type_layout(&self, t: &Type) -> Result<TypeLayout, LayoutError> {
+        let layout = if !t.pointers.is_empty()
+            || !t.array_pointers.is_empty()
+            || matches!(t.base, BaseType::FnPtr { .. })
+        {
+            self.pointer
+        } else {
+            let name = t.base.to_string();
+            match (self.layouts.get(&name), &t.base) {
+                (Some(layout), _) => *layout,
+                (None, BaseType::Enum(_)) => self.layouts[&BaseType::Int.to_string()],
+                (None, _) => return Err(LayoutError::UnknownType(name)),
+            }
+        };
+
+        Ok(TypeLayout::new(layout.size * t.array_len(), layout.align))
+    }
+
+    /// Computes the offset of each field of `s` and the padding between them.
+    pub fn struct_layout(&self, s: &Struct) -> Result<StructLayout, LayoutError> {
+        let mut fields = vec![];
+        let mut offset: usize = 0;
+        let mut align = 1;
+
+        for (field, layout) in self.field_layouts(&s.fields)? {
+            let padding_before = offset.next_multiple_of(layout.align) - offset;
+            offset += padding_before;
+            fields.push(FieldLayout {
+                name: field.name.clone(),
+                offset,
+                size: layout.size,
+                padding_before,
+            });
+            offset += layout.size;
+            align = align.max(layout.align);
+        }
+
+        let size = offset.next_multiple_of(align);
+        Ok(StructLayout {
+            size,
+            align,
+            fields,
+            trailing_padding: size - offset,
+        })
+    }
+
+    /// Computes the layout of `u`, whose fields all start at offset 0.
+    pub fn union_layout(&self, u: &Union) -> Result<StructLayout, LayoutError> {
+        let fields = self.field_layouts(&u.fields)?;
+        let largest = fields.iter().map(|(_, l)| l.size).max().unwrap_or(0);
+        let align = fields.iter().map(|(_, l)| l.align).max().unwrap_or(1);
+        let size = largest.next_multiple_of(align);
+
+        Ok(StructLayout {
+            size,
+            align,
+            fields: fields
+                .into_iter()
+                .map(|(field, layout)| FieldLayout {
+                    name: field.name.clone(),
+                    offset: 0,
+                    size: layout.size,
+                    padding_before: 0,
+                })
+                .collect(),
+            trailing_padding: size - largest,
+        })
+    }
+
+    /// Returns a copy of `s` with its fields ordered by decreasing alignment, which leaves no
+    /// padding between fields whose sizes are multiples of their alignments.
+    ///
+    /// Fields with the same alignment keep their relative order. Only use this for structs whose
+    /// layout isn't shared with code expecting the declared order.
+    pub fn minimize_padding(&self, s: &Struct) -> Result<Struct, LayoutError> {
+        let mut fields: Vec<_> = self
+            .field_layouts(&s.fields)?
+            .into_iter()
+            .map(|(field, layout)| (field.clone(), layout.align))
+            .collect();
+        fields.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+        let mut reordered = s.clone();
+        reordered.fields = fields.into_iter().map(|(field, _)| field).collect();
+        Ok(reordered)
+    }
+
+    /// Returns a copy of `s` whose doc comment ends with a description of its layout.
+    pub fn document_layout(&self, s: &Struct) -> Result<Struct, LayoutError> {
+        let layout = self.struct_layout(s)?;
+
+        let mut documented = s.clone();
+        let mut docs = documented
+            .doc
+            .take()
+            .map(|doc| doc.docs)
+            .unwrap_or_default();
+        docs.extend(layout.doc_comment().docs);
+        documented.doc = Some(DocComment::new().lines(docs).build());
+        Ok(documented)
+    }
+
+    fn field_layouts<'a>(
+        &self,
+        fields: &'a [Field],
+    ) -> Result<Vec<(&'a Field, TypeLayout)>, LayoutError> {
+        fields
+            .iter()
+            .map(|field| {
+                if field.width.is_some() {
+                    return Err(LayoutError::Bitfield(field.name.clone()));
+                }
+                Ok((field, self.type_layout(&field.t)?))
+            })
+            .collect()
+    }
+}
+
+impl StructLayout {
+    /// Returns the total number of padding bytes, between the fields and after the last one.
+    pub fn padding(&self) -> usize {
+        self.fields.iter().map(|f| f.padding_before).sum::<usize>() + self.trailing_padding
+    }
+
+    /// Describes the layout as a doc comment listing the offset and size of each field and where
+    /// the padding is.
+    ///
+    /// # Examples
+    /// ```c
+    /// /// Layout: 16 bytes, aligned to 8, with 7 bytes of padding
+    /// /// - 0: `name` (8 bytes)
+    /// /// - 8: `age` (1 byte)
+    /// /// - 9: 7 bytes of padding
+    /// ```
+    pub fn doc_comment(&self) -> DocComment {
+        let mut lines = vec![format!(
+            "Layout: {}, aligned to {}, with {} of padding",
+            bytes(self.size),
+            self.align,
+            bytes(self.padding())
+        )];
+
+        for field in &self.fields {
+            if field.padding_before != 0 {
+                lines.push(format!(
+                    "- {}: {} of padding",
+                    field.offset - field.padding_before,
+                    bytes(field.padding_before)
+                ));
+            }
+            lines.push(format!(
+                "- {}: `{}` ({})",
+                field.offset,
+                field.name,
+                bytes(field.size)
+            ));
+        }
+
+        if self.trailing_padding != 0 {
+            lines.push(format!(
+                "- {}: {} of padding",
+                self.size - self.trailing_padding,
+                bytes(self.trailing_padding)
+            ));
+        }
+
+        DocComment::new().lines(lines).build()
+    }
+}
+
+fn bytes(n: usize) -> String {
+    if n == 1 {
+        "1 byte".to_string()
+    } else {
+        format!("{n} bytes")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    fn field(name: &str, base: BaseType) -> Field {
+        FieldBuilder::new_with_str(name, Type::new(base).build()).build()
+    }
+
+    #[test]
+    fn struct_layout() {
+        let s = StructBuilder::new_with_str("Packet")
+            .doc(DocComment::new().line_str("A network packet").build())
+            .field(field("flag", BaseType::Bool))
+            .field(
+                FieldBuilder::new_with_str(
+                    "data",
+                    Type::new(BaseType::UInt8).make_pointer().build(),
+                )
+                .build(),
+            )
+            .field(field("len", BaseType::UInt16))
+            .build();
+
+        let abi = Abi::lp64();
+        let layout = abi.struct_layout(&s).unwrap();
+        assert_eq!((layout.size, layout.align, layout.padding()), (24, 8, 13));
+        assert_eq!(layout.fields[1].offset, 8);
+
+        let reordered = abi.minimize_padding(&s).unwrap();
+        let layout = abi.struct_layout(&reordered).unwrap();
+        assert_eq!((layout.size, layout.padding()), (16, 5));
+
+        let res = r#"/// A network packet
+/// Layout: 16 bytes, aligned to 8, with 5 bytes of padding
+/// - 0: `data` (8 bytes)
+/// - 8: `len` (2 bytes)
+/// - 10: `flag` (1 byte)
+/// - 11: 5 bytes of padding
+struct Packet {
+  uint8_t* data;
+  uint16_t len;
+  bool flag;
+};
+"#;
+        assert_eq!(abi.document_layout(&reordered).unwrap().to_string(), res);
+
+        // Pointers are half as big on 32-bit targets
+        assert_eq!(Abi::ilp32().struct_layout(&s).unwrap().size, 12);
+
+        let outer = StructBuilder::new_with_str("Outer")
+            .field(field("packet", BaseType::Struct("Packet".to_string())))
+            .build();
+        assert_eq!(
+            abi.struct_layout(&outer),
+            Err(LayoutError::UnknownType("struct Packet".to_string()))
+        );
+        let abi = abi.register_struct(&s).unwrap();
+        assert_eq!(abi.struct_layout(&outer).unwrap().size, 24);
+
+        let u = UnionBuilder::new_with_str("Value")
+            .field(field("i", BaseType::Int64))
+            .field(
+                FieldBuilder::new_with_str(
+                    "bytes",
+                    Type::new(BaseType::Char).make_array(9).build(),
+                )
+                .build(),
+            )
+            .build();
+        let layout = abi.union_layout(&u).unwrap();
+        assert_eq!((layout.size, layout.trailing_padding), (16, 7));
+    }
+
+    #[test]
+    fn layout_comments() {
+        let s = ScopeBuilder::new()
+            .global_statement(GlobalStatement::Struct(
+                StructBuilder::new_with_str("Header")
+                    .field(field("kind", BaseType::UInt8))
+                    .field(field("len", BaseType::UInt32))
+                    .build(),
+            ))
+            .global_statement(GlobalStatement::Struct(
+                StructBuilder::new_with_str("Packet")
+                    .field(field("header", BaseType::Struct("Header".to_string())))
+                    .field(field("checksum", BaseType::UInt64))
+                    .build(),
+            ))
+            .global_statement(GlobalStatement::Struct(
+                StructBuilder::new_with_str("Node")
+                    .field(field("next", BaseType::Struct("Opaque".to_string())))
+                    .build(),
+            ))
+            .build();
+
+        let mut res = String::new();
+        let mut fmt = Formatter::new(&mut res);
+        fmt.layout_comments = Some(Abi::lp64());
+        s.format(&mut fmt).unwrap();
+
+        assert_eq!(
+            fmt.warnings(),
+            [
+                "Couldn't compute the layout of 'struct Node': the layout of 'struct Opaque' is unknown"
+            ]
+        );
+        assert_eq!(
+            res,
+            r#"struct Header {
+  uint8_t kind;
+  uint32_t len;
+}; // size: 8, align: 4
+struct Packet {
+  struct Header header;
+  uint64_t checksum;
+}; // size: 16, align: 8
+struct Node {
+  struct Opaque next;
+};
+"#
+        );
+    }
+}
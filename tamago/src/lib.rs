@@ -36,10 +36,15 @@
 //! - `conditional`: Handles conditional statements like `if`, `switch`.
 //! - `formatter`: Provides utilities for formatting generated C code.
 //! - `scope`: Defines scope management for C code generation.
+//! - `asm`: Emits inline assembly statements.
 //! - `block`: Manages blocks of code within functions and control structures.
 //! - `comment`: Supports adding inline and documentation comments.
 //! - `expr`: Handles C expressions and operations.
 //! - `enums`: Supports enumeration (`enum`) type definitions.
+//! - `constants`: Emits tables of named constants as `#define`s or `enum` constants.
+//! - `includes`: Finds the `#include`s generated code is missing or doesn't need.
+//! - `layout`: Computes the size and padding of structs for a target ABI.
+//! - `arbitrary`: Generates random well-formed scopes for fuzzing (with the `arbitrary` feature).
 //!
 //! ## Example Usage
 //! ```rust
@@ -67,29 +72,48 @@
 //! ```
 //!
 
+// Lets the code generated by `#[derive(ToTamago)]` refer to `::tamago` inside this crate as well
+extern crate self as tamago;
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+mod asm;
 mod block;
 mod comment;
 mod conditional;
+mod constants;
+#[cfg(test)]
+mod corpus;
 mod enums;
 mod expr;
 mod formatter;
 mod function;
+mod includes;
+mod layout;
 mod loops;
+mod macros;
 mod preprocessor;
 mod scope;
 mod structs;
+mod symbols;
 mod typedef;
 mod types;
 mod union;
 mod variable;
 
-pub use block::{Block, BlockBuilder, Statement};
-pub use comment::{Comment, CommentBuilder, DocComment, DocCommentBuilder};
+pub use asm::{Asm, AsmBuilder, AsmOperand};
+pub use block::{Block, BlockBuilder, Label, LabelBuilder, Statement};
+pub use comment::{Comment, CommentBuilder, DocComment, DocCommentBuilder, FileHeader};
 pub use conditional::{If, IfBuilder, Switch, SwitchBuilder};
+pub use constants::{ConstantStyle, ConstantTable, ConstantTableBuilder};
 pub use enums::{Enum, EnumBuilder, Variant, VariantBuilder};
-pub use expr::{AssignOp, BinOp, Expr, UnaryOp};
-pub use formatter::{Format, Formatter};
-pub use function::{Function, FunctionBuilder, Parameter, ParameterBuilder};
+pub use expr::{AssignOp, BinOp, Expr, Predefined, UnaryOp};
+pub use formatter::{Dialect, Format, Formatter, Language, Newline, Style};
+pub use function::{
+    Function, FunctionBuilder, FunctionError, FunctionHint, Parameter, ParameterBuilder,
+};
+pub use includes::{IncludeAnalysis, IncludeReport};
+pub use layout::{Abi, FieldLayout, LayoutError, StructLayout, TypeLayout};
 pub use loops::{DoWhile, DoWhileBuilder, For, ForBuilder, While, WhileBuilder};
 pub use preprocessor::{
     ErrorDirective, ErrorDirectiveBuilder, FuncMacro, FuncMacroBuilder, IfDefDirective,
@@ -97,9 +121,12 @@ pub use preprocessor::{
     Macro, ObjMacro, ObjMacroBuilder, PragmaDirective, PragmaDirectiveBuilder, ScopeOrBlock,
     WarningDirective, WarningDirectiveBuilder,
 };
-pub use scope::{GlobalStatement, Scope, ScopeBuilder};
-pub use structs::{Field, FieldBuilder, Struct, StructBuilder};
+pub use scope::{GlobalStatement, MergeConflict, MergeError, Scope, ScopeBuilder, SourceTag};
+pub use structs::{Field, FieldBuilder, FieldError, Struct, StructBuilder, ToTamago};
+pub use symbols::Symbols;
 pub use typedef::{TypeDef, TypeDefBuilder};
-pub use types::{BaseType, Type, TypeBuilder, TypeQualifier};
+pub use types::{BaseType, CType, ParseTypeError, Type, TypeBuilder, TypeError, TypeQualifier};
 pub use union::{Union, UnionBuilder};
 pub use variable::{Variable, VariableBuilder};
+
+pub use tamacro::ToTamago;
@@ -0,0 +1,255 @@
+// Copyright (c) 2025 Nobuharu Shimazu
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! This module provides the `scope!` macro for writing the static parts of a C file
+//! declaratively instead of through the builders.
+
+/// Builds a `Scope` from a declarative description of a translation unit.
+///
+/// Types are written as C type strings parsed with `Type::parse`, and statements in function
+/// bodies as raw C. Anything else can be spliced in with `{ stmt }` for a single
+/// `GlobalStatement` (or `Statement` inside a function body) and `{ ..stmts }` for an iterator of
+/// them, so that the dynamic parts of a file can still be built with the builders. Struct and
+/// function definitions are separated from what precedes them by a blank line. A function without
+/// a body is a prototype.
+///
+/// # Panics
+///
+/// Panics if a type string isn't a valid C type.
+///
+/// # Examples
+///
+/// ```rust
+/// use tamago::*;
+///
+/// let helper = Function::new("helper".to_string(), Type::new(BaseType::Void).build()).build();
+/// let scope = scope! {
+///     #include <"stdio.h">
+///     #include "point.h"
+///     #define ORIGIN 0
+///     #define DEBUG
+///
+///     struct Point { x: "int", y: "int" }
+///
+///     fn sum(p: "struct Point") -> "int";
+///
+///     fn main() -> "int" {
+///         "printf(\"%d\\n\", ORIGIN);"
+///         "return 0;"
+///     }
+///
+///     { GlobalStatement::Function(helper) }
+/// };
+/// ```
+#[macro_export]
+macro_rules! scope {
+    // Statements are added with `extend` instead of `push` so that clippy doesn't suggest
+    // `vec![]` in the code using the macro
+    (@items $stmts:ident;) => {};
+
+    (@items $stmts:ident; # include < $path:literal > $($rest:tt)*) => {
+        $stmts.extend([$crate::GlobalStatement::Include(
+            $crate::IncludeBuilder::new_system_with_str($path).build(),
+        )]);
+        $crate::scope!(@items $stmts; $($rest)*);
+    };
+
+    (@items $stmts:ident; # include $path:literal $($rest:tt)*) => {
+        $stmts.extend([$crate::GlobalStatement::Include(
+            $crate::IncludeBuilder::new_with_str($path).build(),
+        )]);
+        $crate::scope!(@items $stmts; $($rest)*);
+    };
+
+    (@items $stmts:ident; # define $name:ident $value:literal $($rest:tt)*) => {
+        $stmts.extend([$crate::GlobalStatement::Macro($crate::Macro::Obj(
+            $crate::ObjMacroBuilder::new_with_str(stringify!($name))
+                .value($value.to_string())
+                .build(),
+        ))]);
+        $crate::scope!(@items $stmts; $($rest)*);
+    };
+
+    (@items $stmts:ident; # define $name:ident $($rest:tt)*) => {
+        $stmts.extend([$crate::GlobalStatement::Macro($crate::Macro::Obj(
+            $crate::ObjMacroBuilder::new_with_str(stringify!($name)).build(),
+        ))]);
+        $crate::scope!(@items $stmts; $($rest)*);
+    };
+
+    (@items $stmts:ident;
+        struct $name:ident { $($field:ident : $t:literal),* $(,)? } $($rest:tt)*
+    ) => {
+        $crate::scope!(@separate $stmts);
+        $stmts.extend([$crate::GlobalStatement::Struct(
+            $crate::StructBuilder::new_with_str(stringify!($name))
+                .fields(vec![$(
+                    $crate::FieldBuilder::new_with_str(
+                        stringify!($field),
+                        $crate::scope!(@type $t),
+                    )
+                    .build()
+                ),*])
+                .build(),
+        )]);
+        $crate::scope!(@items $stmts; $($rest)*);
+    };
+
+    (@items $stmts:ident;
+        fn $name:ident ( $($param:ident : $t:literal),* $(,)? ) -> $ret:literal ; $($rest:tt)*
+    ) => {
+        $stmts.extend([$crate::GlobalStatement::Function(
+            $crate::scope!(@function $name ( $($param : $t),* ) -> $ret).build(),
+        )]);
+        $crate::scope!(@items $stmts; $($rest)*);
+    };
+
+    (@items $stmts:ident;
+        fn $name:ident ( $($param:ident : $t:literal),* $(,)? ) -> $ret:literal
+        { $($body:tt)* } $($rest:tt)*
+    ) => {
+        $crate::scope!(@separate $stmts);
+        let mut body: ::std::vec::Vec<$crate::Statement> = ::std::vec::Vec::new();
+        $crate::scope!(@body body; $($body)*);
+        $stmts.extend([$crate::GlobalStatement::Function(
+            $crate::scope!(@function $name ( $($param : $t),* ) -> $ret)
+                .body($crate::BlockBuilder::new().statements(body).build())
+                .build(),
+        )]);
+        $crate::scope!(@items $stmts; $($rest)*);
+    };
+
+    (@items $stmts:ident; { .. $splice:expr } $($rest:tt)*) => {
+        $stmts.extend($splice);
+        $crate::scope!(@items $stmts; $($rest)*);
+    };
+
+    (@items $stmts:ident; { $splice:expr } $($rest:tt)*) => {
+        $stmts.extend([$splice]);
+        $crate::scope!(@items $stmts; $($rest)*);
+    };
+
+    (@body $body:ident;) => {};
+
+    (@body $body:ident; $raw:literal $($rest:tt)*) => {
+        $body.extend([$crate::Statement::Raw($raw.to_string())]);
+        $crate::scope!(@body $body; $($rest)*);
+    };
+
+    (@body $body:ident; { .. $splice:expr } $($rest:tt)*) => {
+        $body.extend($splice);
+        $crate::scope!(@body $body; $($rest)*);
+    };
+
+    (@body $body:ident; { $splice:expr } $($rest:tt)*) => {
+        $body.extend([$splice]);
+        $crate::scope!(@body $body; $($rest)*);
+    };
+
+    (@function $name:ident ( $($param:ident : $t:literal),* ) -> $ret:literal) => {
+        $crate::FunctionBuilder::new_with_str(stringify!($name), $crate::scope!(@type $ret))
+            .params(vec![$(
+                $crate::ParameterBuilder::new_with_str(stringify!($param), $crate::scope!(@type $t))
+                    .build()
+            ),*])
+    };
+
+    (@type $t:literal) => {
+        $crate::Type::parse($t).unwrap_or_else(|err| panic!("invalid type '{}': {err}", $t))
+    };
+
+    (@separate $stmts:ident) => {
+        if !$stmts.is_empty() {
+            $stmts.extend([$crate::GlobalStatement::NewLine]);
+        }
+    };
+
+    ($($items:tt)*) => {{
+        let mut stmts: ::std::vec::Vec<$crate::GlobalStatement> = ::std::vec::Vec::new();
+        $crate::scope!(@items stmts; $($items)*);
+        $crate::Scope::new().global_statements(stmts).build()
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn scope_macro() {
+        let helpers = vec![
+            GlobalStatement::Raw("int twice(int x) { return x * 2; }".to_string()),
+            GlobalStatement::Raw("int thrice(int x) { return x * 3; }".to_string()),
+        ];
+        let ret = Statement::Return(Some(Expr::new_ident_with_str("0")));
+
+        let scope = scope! {
+            #include <"stdio.h">
+            #include "point.h"
+            #define ORIGIN 0
+            #define GREETING "\"hi\""
+            #define DEBUG
+
+            struct Point { x: "int", y: "int", }
+
+            fn sum(p: "struct Point") -> "int";
+            fn log(msg: "const char*", level: "int") -> "void";
+
+            { ..helpers }
+
+            fn main() -> "int" {
+                "printf(\"%s %d\\n\", GREETING, ORIGIN);"
+                { ret }
+            }
+        };
+
+        assert_eq!(
+            scope.to_string(),
+            r#"#include <stdio.h>
+#include "point.h"
+#define ORIGIN 0
+#define GREETING "hi"
+#define DEBUG
+
+struct Point {
+  int x;
+  int y;
+};
+int sum(struct Point p);
+void log(const char* msg, int level);
+int twice(int x) { return x * 2; }
+int thrice(int x) { return x * 3; }
+
+int main(void) {
+  printf("%s %d\n", GREETING, ORIGIN);
+  return 0;
+}
+"#
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid type 'int]'")]
+    fn scope_macro_invalid_type() {
+        scope! {
+            fn main() -> "int]";
+        };
+    }
+}
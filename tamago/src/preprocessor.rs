@@ -1132,7 +1132,7 @@ pub enum ScopeOrBlock {
 impl Format for ScopeOrBlock {
     fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            ScopeOrBlock::Scope(s) => s.format(fmt),
+            ScopeOrBlock::Scope(s) => s.format_contents(fmt),
             ScopeOrBlock::Block(b) => b.format(fmt),
         }
     }
@@ -1413,7 +1413,7 @@ impl IfDefDirective {
 impl Format for IfDefDirective {
     fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
         if self.not {
-            writeln!(fmt, "ifndef {}", self.symbol)?;
+            writeln!(fmt, "#ifndef {}", self.symbol)?;
         } else {
             writeln!(fmt, "#ifdef {}", self.symbol)?;
         }
@@ -1582,7 +1582,8 @@ impl IfDefDirectiveBuilder {
 /// The `#line` directive changes the compiler's internal line number counter and
 /// file name for error reporting and debugging information. This is commonly used
 /// in generated code to make error messages refer to the original source file
-/// rather than the generated file.
+/// rather than the generated file. The path is left out when it is the same as the
+/// previous directive's, since the compiler keeps the file name.
 ///
 /// # Examples
 /// ```c
@@ -1632,8 +1633,7 @@ impl Format for LineDirective {
             doc.format(fmt)?;
         }
 
-        write!(fmt, "#line {} ", self.line)?;
-        writeln!(fmt, "\"{}\"", self.path)
+        fmt.line_directive(self.line, &self.path)
     }
 }
 
@@ -1912,7 +1912,7 @@ identifier;
             .global_statement(GlobalStatement::NewLine)
             .not()
             .build();
-        let res = r#"ifndef SOMETHING
+        let res = r#"#ifndef SOMETHING
 
 #endif
 "#;
@@ -1924,6 +1924,22 @@ identifier;
         let l = LineDirectiveBuilder::new_with_str(123, "hello.h").build();
         let res = "#line 123 \"hello.h\"\n";
         assert_eq!(l.to_string(), res);
+
+        let b = BlockBuilder::new()
+            .statement(Statement::LineDirective(l))
+            .statement(Statement::Raw("a();".to_string()))
+            .statement(Statement::LineDirective(
+                LineDirectiveBuilder::new_with_str(125, "hello.h").build(),
+            ))
+            .statement(Statement::Raw("b();".to_string()))
+            .statement(Statement::LineDirective(
+                LineDirectiveBuilder::new_with_str(1, "world.h").build(),
+            ))
+            .build();
+        assert_eq!(
+            b.to_string(),
+            "#line 123 \"hello.h\"\na();\n#line 125\nb();\n#line 1 \"world.h\"\n"
+        );
     }
 
     #[test]
@@ -25,7 +25,9 @@
 //! which contains global statements and serves as the root container for generated code.
 //! The module follows a builder pattern approach for constructing C code structures.
 
+use std::collections::BTreeSet;
 use std::fmt::{self, Write};
+use std::ops::RangeBounds;
 
 use crate::*;
 use tamacro::DisplayFromFormat;
@@ -45,11 +47,17 @@ use tamacro::DisplayFromFormat;
 /// ```
 #[derive(Debug, Clone, DisplayFromFormat)]
 pub struct Scope {
+    /// Optional license or banner comment written at the very top, before everything else.
+    pub file_header: Option<FileHeader>,
+
     /// Optional documentation comment associated with the scope.
     pub doc: Option<DocComment>,
 
     /// The collection of global statements contained within this scope.
     pub global_stmts: Vec<GlobalStatement>,
+
+    /// The labels handed out by `unique_label`, which aren't necessarily in the scope yet
+    labels: BTreeSet<String>,
 }
 
 impl Scope {
@@ -72,10 +80,328 @@ impl Scope {
     pub fn new() -> ScopeBuilder {
         ScopeBuilder::new()
     }
-}
 
-impl Format for Scope {
-    fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+    /// Inserts a global statement at the start of the scope.
+    ///
+    /// # Parameters
+    ///
+    /// * `stmt` - The global statement to insert
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// scope.prepend(GlobalStatement::Raw("#define TRACING 1".to_string()));
+    /// ```
+    pub fn prepend(&mut self, stmt: GlobalStatement) {
+        self.global_stmts.insert(0, stmt);
+    }
+
+    /// Inserts a global statement at position `idx`, shifting the ones after it.
+    ///
+    /// # Parameters
+    ///
+    /// * `idx` - The position the global statement will be at
+    /// * `stmt` - The global statement to insert
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is greater than the number of global statements.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// scope.insert_at(2, GlobalStatement::NewLine);
+    /// ```
+    pub fn insert_at(&mut self, idx: usize, stmt: GlobalStatement) {
+        self.global_stmts.insert(idx, stmt);
+    }
+
+    /// Inserts a global statement right after the one at position `idx`.
+    ///
+    /// # Parameters
+    ///
+    /// * `idx` - The position of the global statement to insert after
+    /// * `stmt` - The global statement to insert
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no global statement at `idx`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// scope.insert_after(0, GlobalStatement::NewLine);
+    /// ```
+    pub fn insert_after(&mut self, idx: usize, stmt: GlobalStatement) {
+        assert!(
+            idx < self.global_stmts.len(),
+            "index {idx} is out of bounds for {} global statements",
+            self.global_stmts.len()
+        );
+        self.global_stmts.insert(idx + 1, stmt);
+    }
+
+    /// Replaces the global statements in `range` with `replacement`, returning the removed ones.
+    ///
+    /// # Parameters
+    ///
+    /// * `range` - The positions of the global statements to remove
+    /// * `replacement` - The global statements to insert in their place
+    ///
+    /// # Returns
+    ///
+    /// The removed global statements
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let removed = scope.splice(1..3, vec![GlobalStatement::NewLine]);
+    /// ```
+    pub fn splice<R, I>(&mut self, range: R, replacement: I) -> Vec<GlobalStatement>
+    where
+        R: RangeBounds<usize>,
+        I: IntoIterator<Item = GlobalStatement>,
+    {
+        self.global_stmts.splice(range, replacement).collect()
+    }
+
+    /// Keeps only the global statements for which `f` returns `true`.
+    ///
+    /// # Parameters
+    ///
+    /// * `f` - The predicate deciding which global statements to keep
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// // Removes all the blank lines
+    /// scope.retain(|stmt| !matches!(stmt, GlobalStatement::NewLine));
+    /// ```
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&GlobalStatement) -> bool,
+    {
+        self.global_stmts.retain(f);
+    }
+
+    /// Appends the global statements of `other`, which was generated independently, leaving out
+    /// the struct and typedef definitions the scope already has.
+    ///
+    /// Definitions are compared structurally, so a struct defined again with the same fields is
+    /// left out even if its doc comments differ, and a forward declaration like `struct Node;`
+    /// is compatible with any definition. Nothing is appended if a definition conflicts with one
+    /// of the scope.
+    ///
+    /// # Parameters
+    ///
+    /// * `other` - The scope whose global statements will be appended
+    ///
+    /// # Returns
+    ///
+    /// A `MergeError` with both definitions of every name defined differently by the scopes
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// // Both scopes define `struct Vec2`, which ends up in `scope` once
+    /// scope.merge(math_scope)?;
+    /// ```
+    pub fn merge(&mut self, other: Scope) -> Result<(), MergeError> {
+        let mut defined: Vec<&GlobalStatement> = self
+            .global_stmts
+            .iter()
+            .filter(|stmt| definition(stmt).is_some())
+            .collect();
+        let mut conflicts = vec![];
+        let mut duplicates = vec![];
+
+        for (idx, stmt) in other.global_stmts.iter().enumerate() {
+            let Some((kind, name)) = definition(stmt) else {
+                continue;
+            };
+
+            let mut same_name = defined
+                .iter()
+                .filter(|existing| definition(existing) == Some((kind, name)));
+            match same_name.find(|existing| !compatible(existing, stmt)) {
+                Some(existing) => conflicts.push(MergeConflict {
+                    name: name.to_string(),
+                    existing: (*existing).clone(),
+                    incoming: stmt.clone(),
+                }),
+                None if is_redundant(&defined, stmt) => duplicates.push(idx),
+                None => defined.push(stmt),
+            }
+        }
+
+        if !conflicts.is_empty() {
+            return Err(MergeError { conflicts });
+        }
+
+        let mut skip_newline = false;
+        for (idx, stmt) in other.global_stmts.into_iter().enumerate() {
+            if duplicates.contains(&idx) {
+                // The blank line separating the duplicate from the next statement goes with it
+                skip_newline = true;
+                continue;
+            }
+            if std::mem::take(&mut skip_newline) && matches!(stmt, GlobalStatement::NewLine) {
+                continue;
+            }
+            self.global_stmts.push(stmt);
+        }
+
+        self.labels.extend(other.labels);
+        Ok(())
+    }
+
+    /// Returns a label based on `name` that doesn't collide with the labels in the functions of
+    /// the scope or with the ones returned before, along with the `_continue` and `_end` labels
+    /// of `Statement::Labelled`.
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - The preferred name of the label
+    ///
+    /// # Returns
+    ///
+    /// `name` itself if it is free, otherwise `name` followed by a number
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let outer = scope.unique_label("outer");
+    /// let loop_stmt = Statement::labelled(&outer, Statement::While(while_loop));
+    /// ```
+    pub fn unique_label(&mut self, name: &str) -> String {
+        let mut used = self.labels.clone();
+        for stmt in &self.global_stmts {
+            let GlobalStatement::Function(f) = stmt.untagged() else {
+                continue;
+            };
+            for stmt in &f.body.stmts {
+                stmt.walk(&mut |stmt| match stmt {
+                    Statement::Label(crate::Label { name: label, .. })
+                    | Statement::Labelled(label, _) => {
+                        used.insert(label.clone());
+                    }
+                    _ => {}
+                });
+            }
+        }
+
+        let is_free = |label: &str| {
+            [
+                label.to_string(),
+                format!("{label}_continue"),
+                format!("{label}_end"),
+            ]
+            .iter()
+            .all(|l| !used.contains(l))
+        };
+
+        let label = if is_free(name) {
+            name.to_string()
+        } else {
+            (1..)
+                .map(|i| format!("{name}_{i}"))
+                .find(|label| is_free(label))
+                .unwrap()
+        };
+
+        self.labels.insert(label.clone());
+        label
+    }
+
+    /// Returns an index of the names the scope defines at the top level, for looking up many of
+    /// them without scanning the global statements each time.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let symbols = scope.symbols();
+    /// if symbols.find_function("init").is_none() && !symbols.contains("INIT") {
+    ///     scope.global_stmts.push(GlobalStatement::Function(init));
+    /// }
+    /// ```
+    pub fn symbols(&self) -> Symbols<'_> {
+        Symbols::new(self)
+    }
+
+    /// Returns the function named `name`, preferring its definition to a prototype.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// if scope.find_function("init").is_none() {
+    ///     scope.global_stmts.push(GlobalStatement::Function(init));
+    /// }
+    /// ```
+    pub fn find_function(&self, name: &str) -> Option<&Function> {
+        self.symbols().find_function(name)
+    }
+
+    /// Returns the function named `name` to modify, preferring its definition to a prototype.
+    pub fn find_function_mut(&mut self, name: &str) -> Option<&mut Function> {
+        let (idx, _) = self.symbols().function_at(name)?;
+        match self.global_stmts[idx].untagged_mut() {
+            GlobalStatement::Function(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    /// Returns the struct named `name`, preferring its definition to a forward declaration.
+    pub fn find_struct(&self, name: &str) -> Option<&Struct> {
+        self.symbols().find_struct(name)
+    }
+
+    /// Returns the struct named `name` to modify, preferring its definition to a forward
+    /// declaration.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// if let Some(state) = scope.find_struct_mut("State") {
+    ///     let frame = FieldBuilder::new_with_str("frame", Type::new(BaseType::Int).build());
+    ///     state.push_field(frame.build());
+    /// }
+    /// ```
+    pub fn find_struct_mut(&mut self, name: &str) -> Option<&mut Struct> {
+        let (idx, _) = self.symbols().struct_at(name)?;
+        match self.global_stmts[idx].untagged_mut() {
+            GlobalStatement::Struct(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the enum named `name`.
+    pub fn find_enum(&self, name: &str) -> Option<&Enum> {
+        self.symbols().find_enum(name)
+    }
+
+    /// Returns the union named `name`.
+    pub fn find_union(&self, name: &str) -> Option<&Union> {
+        self.symbols().find_union(name)
+    }
+
+    /// Returns the global variable named `name`, preferring its definition to an `extern`
+    /// declaration.
+    pub fn find_variable(&self, name: &str) -> Option<&Variable> {
+        self.symbols().find_variable(name)
+    }
+
+    /// Returns the typedef named `name`.
+    pub fn find_typedef(&self, name: &str) -> Option<&TypeDef> {
+        self.symbols().find_typedef(name)
+    }
+
+    /// Writes the doc comment and statements of the scope without ending the file, for scopes
+    /// nested in preprocessor conditionals
+    pub(crate) fn format_contents(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
         if let Some(doc) = &self.doc {
             doc.format(fmt)?;
         }
@@ -88,11 +414,108 @@ impl Format for Scope {
     }
 }
 
+/// Returns the kind and name of the type `stmt` defines if `Scope::merge` deduplicates it.
+fn definition(stmt: &GlobalStatement) -> Option<(&'static str, &str)> {
+    match stmt.untagged() {
+        GlobalStatement::Struct(s) => Some(("struct", &s.name)),
+        GlobalStatement::TypeDef(t) => Some(("typedef", &t.name)),
+        _ => None,
+    }
+}
+
+/// Whether two definitions of the same name can both be in a scope, comparing fields without
+/// their doc comments.
+fn compatible(a: &GlobalStatement, b: &GlobalStatement) -> bool {
+    match (a.untagged(), b.untagged()) {
+        (GlobalStatement::Struct(a), GlobalStatement::Struct(b)) => {
+            let same_field =
+                |(a, b): (&Field, &Field)| a.name == b.name && a.t == b.t && a.width == b.width;
+            a.fields.is_empty()
+                || b.fields.is_empty()
+                || (a.fields.len() == b.fields.len()
+                    && a.fields.iter().zip(&b.fields).all(same_field))
+        }
+        (GlobalStatement::TypeDef(a), GlobalStatement::TypeDef(b)) => a.t == b.t,
+        _ => false,
+    }
+}
+
+/// Whether `stmt`, which is compatible with `defined`, adds nothing to them: a typedef that is
+/// already there, a declaration of a struct that is, or a definition of a struct that is already
+/// defined.
+fn is_redundant(defined: &[&GlobalStatement], stmt: &GlobalStatement) -> bool {
+    let key = definition(stmt);
+    let mut same_name = defined
+        .iter()
+        .filter(|existing| definition(existing) == key);
+
+    match stmt.untagged() {
+        GlobalStatement::Struct(s) if !s.fields.is_empty() => same_name.any(|existing| {
+            matches!(existing.untagged(), GlobalStatement::Struct(e) if !e.fields.is_empty())
+        }),
+        _ => same_name.next().is_some(),
+    }
+}
+
+/// A name `Scope::merge` found defined differently by the two scopes.
+#[derive(Debug, Clone)]
+pub struct MergeConflict {
+    /// The name of the struct or typedef
+    pub name: String,
+
+    /// The definition in the scope being merged into
+    pub existing: GlobalStatement,
+
+    /// The definition in the scope being merged
+    pub incoming: GlobalStatement,
+}
+
+/// Why `Scope::merge` could not merge two scopes.
+#[derive(Debug, Clone)]
+pub struct MergeError {
+    /// Every name the scopes define differently, in the order of the merged scope
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, conflict) in self.conflicts.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(
+                f,
+                "'{}' is defined differently by the merged scopes, as\n{}and as\n{}",
+                conflict.name,
+                conflict.existing.untagged(),
+                conflict.incoming.untagged()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+impl Format for Scope {
+    fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(header) = &self.file_header {
+            header.format(fmt)?;
+        }
+
+        self.format_contents(fmt)?;
+        fmt.finish();
+
+        Ok(())
+    }
+}
+
 /// A builder for constructing a `Scope` instance using the builder pattern.
 ///
 /// This builder provides methods to add various elements to a scope and finally build
 /// the complete `Scope` instance.
 pub struct ScopeBuilder {
+    file_header: Option<FileHeader>,
     doc: Option<DocComment>,
     global_stmts: Vec<GlobalStatement>,
 }
@@ -112,11 +535,34 @@ impl ScopeBuilder {
     /// ```
     pub fn new() -> Self {
         Self {
+            file_header: None,
             doc: None,
             global_stmts: vec![],
         }
     }
 
+    /// Sets the license or banner comment written at the very top of the scope, before the
+    /// includes and the documentation comment.
+    ///
+    /// # Parameters
+    /// * `header` - The text of the header, or a `FileHeader` shared by many scopes.
+    ///
+    /// # Returns
+    /// The builder instance for method chaining.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let license = FileHeader::new("SPDX-License-Identifier: MIT");
+    /// let scope = ScopeBuilder::new()
+    ///     .file_header(license.clone())
+    ///     .global_statement(GlobalStatement::Include(include))
+    ///     .build();
+    /// ```
+    pub fn file_header(mut self, header: impl Into<FileHeader>) -> Self {
+        self.file_header = Some(header.into());
+        self
+    }
+
     /// Sets the documentation comment for the scope being built.
     ///
     /// # Parameters
@@ -170,8 +616,10 @@ impl ScopeBuilder {
     /// configured in this builder.
     pub fn build(self) -> Scope {
         Scope {
+            file_header: self.file_header,
             doc: self.doc,
             global_stmts: self.global_stmts,
+            labels: BTreeSet::new(),
         }
     }
 }
@@ -191,6 +639,7 @@ impl ScopeBuilder {
 /// };
 /// ```
 #[derive(Debug, Clone, DisplayFromFormat)]
+#[non_exhaustive]
 pub enum GlobalStatement {
     /// A comment in the code.
     Comment(Comment),
@@ -231,6 +680,9 @@ pub enum GlobalStatement {
     /// A macro definition (e.g., `#define MAX(a, b) ((a) > (b) ? (a) : (b))`).
     Macro(Macro),
 
+    /// A table of constants emitted as aligned `#define`s or `enum` constants.
+    Constants(ConstantTable),
+
     /// A pragma preprocessor directive (e.g., `#pragma once`).
     PragmaDirective(PragmaDirective),
 
@@ -242,6 +694,54 @@ pub enum GlobalStatement {
 
     /// A new line for formatting purposes.
     NewLine,
+
+    /// A global statement that records where in the original source it comes from, which is
+    /// emitted as a `#line` directive if `Formatter::line_directives` is set.
+    Tagged(SourceTag, Box<GlobalStatement>),
+}
+
+impl GlobalStatement {
+    /// Tags the statement with the location in the original source it was generated from.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let stmt = GlobalStatement::Raw("int x = 0;".to_string()).tagged(12, "main.clla");
+    /// ```
+    pub fn tagged(self, line: u64, path: &str) -> Self {
+        Self::Tagged(
+            SourceTag {
+                line,
+                path: path.to_string(),
+            },
+            Box::new(self),
+        )
+    }
+
+    /// Returns the statement without the source tags around it.
+    pub fn untagged(&self) -> &Self {
+        match self {
+            Self::Tagged(_, stmt) => stmt.untagged(),
+            stmt => stmt,
+        }
+    }
+
+    /// Returns the statement without the source tags around it to modify.
+    pub fn untagged_mut(&mut self) -> &mut Self {
+        match self {
+            Self::Tagged(_, stmt) => stmt.untagged_mut(),
+            stmt => stmt,
+        }
+    }
+}
+
+/// A location in the original source that generated code comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceTag {
+    /// The line in the original source
+    pub line: u64,
+
+    /// The path of the original source
+    pub path: String,
 }
 
 impl Format for GlobalStatement {
@@ -253,7 +753,10 @@ impl Format for GlobalStatement {
             Struct(s) => s.format(fmt),
             Function(f) => f.format(fmt),
             Union(u) => u.format(fmt),
-            Variable(v) => v.format(fmt),
+            Variable(v) => {
+                v.format(fmt)?;
+                writeln!(fmt, ";")
+            }
             TypeDef(t) => t.format(fmt),
             ErrorDirective(e) => e.format(fmt),
             IfDefDirective(i) => i.format(fmt),
@@ -261,10 +764,20 @@ impl Format for GlobalStatement {
             Include(i) => i.format(fmt),
             LineDirective(l) => l.format(fmt),
             Macro(m) => m.format(fmt),
+            Constants(c) => c.format(fmt),
             PragmaDirective(p) => p.format(fmt),
             WarningDirective(w) => w.format(fmt),
-            Raw(r) => writeln!(fmt, "{r}"),
+            Raw(r) => {
+                fmt.lint_raw(r);
+                writeln!(fmt, "{r}")
+            }
             NewLine => writeln!(fmt),
+            Tagged(tag, stmt) => {
+                if fmt.line_directives {
+                    fmt.line_directive(tag.line, &tag.path)?;
+                }
+                stmt.format(fmt)
+            }
         }
     }
 }
@@ -321,4 +834,212 @@ bool some_func(void) {
 
         assert_eq!(s.to_string(), res);
     }
+
+    #[test]
+    fn scope_editing() {
+        let mut s = ScopeBuilder::new()
+            .global_statement(GlobalStatement::Raw("int a;".to_string()))
+            .global_statement(GlobalStatement::Raw("int b;".to_string()))
+            .build();
+
+        s.prepend(GlobalStatement::Include(
+            IncludeBuilder::new_system_with_str("stdio.h").build(),
+        ));
+        s.insert_after(0, GlobalStatement::NewLine);
+        assert_eq!(s.to_string(), "#include <stdio.h>\n\nint a;\nint b;\n");
+
+        let removed = s.splice(2..3, vec![GlobalStatement::Raw("int c;".to_string())]);
+        assert_eq!(removed.len(), 1);
+        s.retain(|stmt| !matches!(stmt, GlobalStatement::NewLine));
+        assert_eq!(s.to_string(), "#include <stdio.h>\nint c;\nint b;\n");
+    }
+
+    #[test]
+    fn file_header() {
+        let license = FileHeader::new("SPDX-License-Identifier: MIT");
+        let mut s = ScopeBuilder::new()
+            .file_header(license.clone())
+            .doc(DocCommentBuilder::new().line_str("Generated").build())
+            .global_statement(GlobalStatement::Raw("int a;".to_string()))
+            .build();
+        s.prepend(GlobalStatement::Include(
+            IncludeBuilder::new_system_with_str("stdio.h").build(),
+        ));
+        let res = r#"// SPDX-License-Identifier: MIT
+
+/// Generated
+#include <stdio.h>
+int a;
+"#;
+
+        assert_eq!(s.to_string(), res);
+
+        // The header is kept in compact style, unlike the doc comment
+        let s = ScopeBuilder::new().file_header(license).build();
+        let mut out = String::new();
+        s.format(&mut Formatter::new_with_style(&mut out, Style::Compact))
+            .unwrap();
+        assert_eq!(out, "// SPDX-License-Identifier: MIT\n");
+    }
+
+    #[test]
+    fn merge() {
+        let vec2 = |x: &str, doc: Option<&str>| {
+            let mut s = StructBuilder::new_with_str("Vec2")
+                .field(FieldBuilder::new_with_str(x, Type::new(BaseType::Float).build()).build())
+                .field(FieldBuilder::new_with_str("y", Type::new(BaseType::Float).build()).build());
+            if let Some(doc) = doc {
+                s = s.doc(DocCommentBuilder::new().line_str(doc).build());
+            }
+            GlobalStatement::Struct(s.build())
+        };
+        let typedef = |t: BaseType| {
+            GlobalStatement::TypeDef(
+                TypeDefBuilder::new_with_str(Type::new(t).build(), "id").build(),
+            )
+        };
+
+        let mut s = ScopeBuilder::new()
+            .global_statement(GlobalStatement::Struct(
+                StructBuilder::new_with_str("Vec2").build(),
+            ))
+            .global_statement(typedef(BaseType::Int))
+            .build();
+        let other = ScopeBuilder::new()
+            .global_statement(vec2("x", None))
+            .new_line()
+            .global_statement(vec2("x", Some("A point")).tagged(3, "math.clla"))
+            .new_line()
+            .global_statement(typedef(BaseType::Int))
+            .global_statement(GlobalStatement::Raw("int a;".to_string()))
+            .build();
+
+        s.merge(other).unwrap();
+        assert_eq!(
+            s.to_string(),
+            "struct Vec2;\ntypedef int id;\nstruct Vec2 {\n  float x;\n  float y;\n};\n\nint a;\n"
+        );
+
+        let conflicting = ScopeBuilder::new()
+            .global_statement(vec2("u", None))
+            .global_statement(typedef(BaseType::Char))
+            .global_statement(GlobalStatement::Raw("int b;".to_string()))
+            .build();
+        let err = s.merge(conflicting).unwrap_err();
+        assert_eq!(err.conflicts.len(), 2);
+        assert_eq!(err.conflicts[0].name, "Vec2");
+        assert_eq!(
+            err.to_string(),
+            "'Vec2' is defined differently by the merged scopes, as\n\
+             struct Vec2 {\n  float x;\n  float y;\n};\nand as\n\
+             struct Vec2 {\n  float u;\n  float y;\n};\n\n\
+             'id' is defined differently by the merged scopes, as\n\
+             typedef int id;\nand as\ntypedef char id;\n"
+        );
+        assert!(!s.to_string().contains("int b;"));
+    }
+
+    #[test]
+    fn unique_labels() {
+        let mut s = ScopeBuilder::new()
+            .global_statement(GlobalStatement::Function(
+                FunctionBuilder::new_with_str("f", Type::new(BaseType::Void).build())
+                    .statement(Statement::label("outer"))
+                    .statement(Statement::label("outer_1_end"))
+                    .build(),
+            ))
+            .build();
+
+        assert_eq!(s.unique_label("outer"), "outer_2");
+        assert_eq!(s.unique_label("outer"), "outer_3");
+        assert_eq!(s.unique_label("inner"), "inner");
+        assert_eq!(s.unique_label("inner"), "inner_1");
+    }
+
+    #[test]
+    fn newlines() {
+        let s = ScopeBuilder::new()
+            .global_statement(GlobalStatement::Raw("int a;\r\nint b;".to_string()))
+            .new_line()
+            .new_line()
+            .global_statement(GlobalStatement::Raw("int c;\n".to_string()))
+            .new_line()
+            .new_line()
+            .build();
+
+        assert_eq!(s.to_string(), "int a;\nint b;\n\n\nint c;\n");
+
+        let mut res = String::new();
+        let mut fmt = Formatter::new(&mut res);
+        fmt.newline = Newline::CrLf;
+        s.format(&mut fmt).unwrap();
+        assert_eq!(res, "int a;\r\nint b;\r\n\r\n\r\nint c;\r\n");
+
+        let mut res = String::new();
+        let mut fmt = Formatter::new(&mut res);
+        fmt.single_trailing_newline = false;
+        s.format(&mut fmt).unwrap();
+        assert!(res.ends_with("int c;\n\n\n\n"));
+
+        assert_eq!(ScopeBuilder::new().build().to_string(), "");
+    }
+
+    #[test]
+    fn formatter_options() {
+        let s = ScopeBuilder::new()
+            .global_statement(GlobalStatement::Raw("int a;".to_string()).tagged(1, "main.clla"))
+            .new_line()
+            .new_line()
+            .new_line()
+            .global_statement(GlobalStatement::Raw("int b;".to_string()).tagged(5, "main.clla"))
+            .global_statement(GlobalStatement::Raw("int c;".to_string()).tagged(2, "util.clla"))
+            .build();
+
+        assert_eq!(s.to_string(), "int a;\n\n\n\nint b;\nint c;\n");
+
+        let mut res = String::new();
+        let mut fmt = Formatter::new(&mut res);
+        fmt.max_blank_lines = Some(1);
+        fmt.line_directives = true;
+        s.format(&mut fmt).unwrap();
+
+        assert_eq!(
+            res,
+            "#line 1 \"main.clla\"\nint a;\n\n#line 5\nint b;\n#line 2 \"util.clla\"\nint c;\n"
+        );
+    }
+
+    #[test]
+    fn compact_style() {
+        let s = ScopeBuilder::new()
+            .global_statement(GlobalStatement::Include(
+                IncludeBuilder::new_system_with_str("stdio.h").build(),
+            ))
+            .new_line()
+            .global_statement(GlobalStatement::Comment(
+                CommentBuilder::new().comment_with_str("Hello").build(),
+            ))
+            .global_statement(GlobalStatement::Function(
+                FunctionBuilder::new_with_str("some_func", Type::new(BaseType::Int).build())
+                    .body(
+                        BlockBuilder::new()
+                            .statement(Statement::Raw("  int x = 1; // one".to_string()))
+                            .statement(Statement::Return(Some(Expr::new_ident_with_str("x"))))
+                            .build(),
+                    )
+                    .doc(DocCommentBuilder::new().line_str("A function").build())
+                    .build(),
+            ))
+            .global_statement(GlobalStatement::Raw("unsigned\nlong y;".to_string()))
+            .build();
+
+        let mut res = String::new();
+        s.format(&mut Formatter::new_with_style(&mut res, Style::Compact))
+            .unwrap();
+        assert_eq!(
+            res,
+            "#include <stdio.h>\nint some_func(void){int x = 1; // one\n\
+             return x;}unsigned long y;\n"
+        );
+    }
 }
@@ -28,7 +28,7 @@
 
 use std::fmt::{self, Write};
 
-use crate::{BaseType, DocComment, Format, Formatter, Type};
+use crate::{BaseType, CType, DocComment, Format, Formatter, Type};
 use tamacro::DisplayFromFormat;
 
 /// Represents a struct in C.
@@ -55,16 +55,16 @@ use tamacro::DisplayFromFormat;
 ///     .field(Field::new("age".to_string(), Type::new(BaseType::Int).build()))
 ///     .build();
 /// ```
-#[derive(Debug, Clone, DisplayFromFormat)]
+#[derive(Debug, Clone, PartialEq, Eq, DisplayFromFormat)]
 pub struct Struct {
     /// The name of the struct
-    name: String,
+    pub(crate) name: String,
 
     /// The fields of the struct
-    fields: Vec<Field>,
+    pub(crate) fields: Vec<Field>,
 
     /// The doc comment of the struct
-    doc: Option<DocComment>,
+    pub(crate) doc: Option<DocComment>,
 }
 
 impl Struct {
@@ -115,6 +115,24 @@ impl Struct {
     pub fn to_type(&self) -> Type {
         Type::new(BaseType::Struct(self.name.clone())).build()
     }
+
+    /// Returns the name of the struct.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the fields of the struct, which a forward declaration has none of.
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// Appends a field to the struct, e.g. one found with `Scope::find_struct_mut`.
+    ///
+    /// # Parameters
+    /// * `field` - The field to append
+    pub fn push_field(&mut self, field: Field) {
+        self.fields.push(field);
+    }
 }
 
 impl Format for Struct {
@@ -126,6 +144,7 @@ impl Format for Struct {
         write!(fmt, "struct {}", self.name)?;
 
         if !self.fields.is_empty() {
+            fmt.register_fields(self);
             fmt.block(|fmt| {
                 for field in &self.fields {
                     field.format(fmt)?;
@@ -134,7 +153,13 @@ impl Format for Struct {
             })?;
         }
 
-        writeln!(fmt, ";")
+        write!(fmt, ";")?;
+        if !self.fields.is_empty() {
+            fmt.layout_comment(BaseType::Struct(self.name.clone()), |abi| {
+                abi.struct_layout(self)
+            })?;
+        }
+        writeln!(fmt)
     }
 }
 
@@ -270,12 +295,40 @@ impl StructBuilder {
     }
 }
 
+/// Converts a Rust struct definition into the matching C struct.
+///
+/// This is meant to be derived with `#[derive(ToTamago)]`, which also implements [`CType`] for
+/// the struct so that it can be used as a field of other derived structs. The Rust struct should
+/// be `#[repr(C)]` for the layouts to actually match.
+///
+/// # Examples
+///
+/// ```rust
+/// use tamago::ToTamago;
+///
+/// #[derive(ToTamago)]
+/// #[repr(C)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// assert_eq!(
+///     Point::to_tamago_struct().to_string(),
+///     "struct Point {\n  int32_t x;\n  int32_t y;\n};\n"
+/// );
+/// ```
+pub trait ToTamago: CType {
+    /// Returns the C struct definition for `Self`
+    fn to_tamago_struct() -> Struct;
+}
+
 /// Represents a struct field in C.
 ///
 /// This struct holds all the information needed to represent a field
 /// within a C struct, including its name, type, bitfield width (if any),
 /// and documentation.
-#[derive(Debug, Clone, DisplayFromFormat)]
+#[derive(Debug, Clone, PartialEq, Eq, DisplayFromFormat)]
 pub struct Field {
     /// The name of the field
     pub name: String,
@@ -336,16 +389,12 @@ impl Format for Field {
             doc.format(fmt)?;
         }
 
-        self.t.format(fmt)?;
-        write!(fmt, " {}", self.name)?;
+        self.t.format_declarator(fmt, &self.name)?;
 
         if let Some(w) = self.width {
             write!(fmt, " : {w}")?;
         }
 
-        if self.t.is_array() {
-            write!(fmt, "[{}]", self.t.array)?;
-        }
         writeln!(fmt, ";")
     }
 }
@@ -457,6 +506,12 @@ impl FieldBuilder {
     /// # Returns
     /// A fully constructed `Field` instance
     ///
+    /// # Panics
+    /// Panics if the bitfield width isn't valid for the field, see [`try_build`] for a version
+    /// that returns the error instead
+    ///
+    /// [`try_build`]: FieldBuilder::try_build
+    ///
     /// # Examples
     /// ```rust
     /// let field = FieldBuilder::new_with_str("name", Type::new(BaseType::Char).make_pointer().build())
@@ -469,15 +524,115 @@ impl FieldBuilder {
     /// // char* name;
     /// ```
     pub fn build(self) -> Field {
-        Field {
+        self.try_build().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Consumes the builder and returns the `Field`, checking that its bitfield width is valid C.
+    ///
+    /// A bitfield must have an integer type, which includes `bool`, enums, and typedefs, since
+    /// they may name one, and can't be wider than its type. Only an unnamed bitfield can have a
+    /// width of 0, which starts the next one at the next unit of storage.
+    ///
+    /// # Returns
+    /// A fully constructed `Field` instance, or the reason it cannot be represented in C
+    ///
+    /// # Examples
+    /// ```rust
+    /// let err = FieldBuilder::new_with_str("ratio", Type::new(BaseType::Float).build())
+    ///     .bitfield_width(3)
+    ///     .try_build()
+    ///     .unwrap_err();
+    /// assert_eq!(err, FieldError::NonIntegerBitfield("ratio".to_string()));
+    /// ```
+    pub fn try_build(self) -> Result<Field, FieldError> {
+        if let Some(width) = self.width {
+            let t = &self.t;
+            let is_integer =
+                t.base.is_integer() || matches!(t.base, BaseType::Enum(_) | BaseType::TypeDef(_));
+            if !is_integer || !t.pointers.is_empty() || !t.array.is_empty() {
+                return Err(FieldError::NonIntegerBitfield(self.name));
+            }
+
+            if width == 0 && !self.name.is_empty() {
+                return Err(FieldError::NamedZeroWidth(self.name));
+            }
+
+            if let Some(bits) = fixed_bits(&t.base)
+                && width > bits
+            {
+                return Err(FieldError::TooWide {
+                    name: self.name,
+                    width,
+                    bits,
+                });
+            }
+        }
+
+        Ok(Field {
             name: self.name,
             t: self.t,
             width: self.width,
             doc: self.doc,
+        })
+    }
+}
+
+/// Returns the number of bits of `base` if it's the same on every target.
+fn fixed_bits(base: &BaseType) -> Option<u8> {
+    use BaseType::*;
+
+    match base {
+        Bool => Some(1),
+        Char | Int8 | UInt8 => Some(8),
+        Int16 | UInt16 => Some(16),
+        Int32 | UInt32 => Some(32),
+        Int64 | UInt64 => Some(64),
+        _ => None,
+    }
+}
+
+/// The reasons a [`FieldBuilder`] cannot build a valid C struct field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldError {
+    /// The field, e.g. `ratio`, is a bitfield but its type isn't an integer type
+    NonIntegerBitfield(String),
+
+    /// The field is a named bitfield with a width of 0, which only an unnamed one can have
+    NamedZeroWidth(String),
+
+    /// The bitfield is wider than its type
+    TooWide {
+        /// The name of the field
+        name: String,
+
+        /// The width of the bitfield
+        width: u8,
+
+        /// The number of bits of its type
+        bits: u8,
+    },
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldError::NonIntegerBitfield(name) => {
+                write!(f, "the bitfield '{name}' must have an integer type")
+            }
+            FieldError::NamedZeroWidth(name) => write!(
+                f,
+                "the bitfield '{name}' cannot have a width of 0, only an unnamed one can"
+            ),
+            FieldError::TooWide { name, width, bits } => write!(
+                f,
+                "the bitfield '{name}' is {width} bits wide, but its type only has {bits}"
+            ),
         }
     }
 }
 
+impl std::error::Error for FieldError {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -502,6 +657,38 @@ char some_field;
         assert_eq!(f2.to_string(), res2);
     }
 
+    #[test]
+    fn bitfields() {
+        let bitfield = |name: &str, t: Type, width: u8| {
+            FieldBuilder::new_with_str(name, t)
+                .bitfield_width(width)
+                .try_build()
+        };
+
+        let s = StructBuilder::new_with_str("Flags")
+            .field(bitfield("mode", Type::new(BaseType::UInt32).build(), 3).unwrap())
+            .field(bitfield("", Type::new(BaseType::UInt32).build(), 0).unwrap())
+            .field(bitfield("kind", Type::new(BaseType::Enum("Kind".into())).build(), 2).unwrap())
+            .build();
+        assert_eq!(
+            s.to_string(),
+            "struct Flags {\n  uint32_t mode : 3;\n  uint32_t : 0;\n  enum Kind kind : 2;\n};\n"
+        );
+
+        let err = bitfield("ratio", Type::new(BaseType::Double).build(), 3).unwrap_err();
+        assert_eq!(err, FieldError::NonIntegerBitfield("ratio".to_string()));
+        let err = bitfield("next", Type::new(BaseType::Int).make_pointer().build(), 3);
+        assert_eq!(err, Err(FieldError::NonIntegerBitfield("next".to_string())));
+        let err = bitfield("pad", Type::new(BaseType::Int).build(), 0).unwrap_err();
+        assert_eq!(err, FieldError::NamedZeroWidth("pad".to_string()));
+
+        let err = bitfield("small", Type::new(BaseType::UInt8).build(), 9).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the bitfield 'small' is 9 bits wide, but its type only has 8"
+        );
+    }
+
     #[test]
     fn structs() {
         let s = StructBuilder::new_with_str("Person")
@@ -522,4 +709,48 @@ char some_field;
 
         assert_eq!(s.to_string(), res);
     }
+
+    #[test]
+    fn derive_to_tamago() {
+        #[derive(ToTamago)]
+        #[repr(C)]
+        #[allow(dead_code)]
+        struct Vec2 {
+            x: f32,
+            y: f32,
+        }
+
+        /// A particle in the simulation
+        #[derive(ToTamago)]
+        #[repr(C)]
+        #[allow(dead_code)]
+        struct Particle {
+            /// Position in world space
+            pos: Vec2,
+            mass: f64,
+            alive: bool,
+            tags: [u8; 4],
+            name: *const u8,
+            next: *mut Particle,
+        }
+
+        let res = r#"/// A particle in the simulation
+struct Particle {
+  /// Position in world space
+  struct Vec2 pos;
+  double mass;
+  bool alive;
+  uint8_t tags[4];
+  const uint8_t* name;
+  struct Particle* next;
+};
+"#;
+
+        assert_eq!(Particle::to_tamago_struct().to_string(), res);
+        assert_eq!(Particle::c_type().to_string(), "struct Particle");
+        assert_eq!(
+            Vec2::to_tamago_struct().to_string(),
+            "struct Vec2 {\n  float x;\n  float y;\n};\n"
+        );
+    }
 }
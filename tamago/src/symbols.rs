@@ -0,0 +1,297 @@
+// Copyright (c) 2025 Nobuharu Shimazu
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! This module provides an index of the names a `Scope` defines.
+//!
+//! Looking up many names in a large scope, like checking whether a function is already defined
+//! before generating it, would otherwise scan the global statements for each of them. `Symbols`
+//! scans them once and maps every name to the statements defining or declaring it, with typed
+//! lookups that prefer definitions over forward declarations and prototypes.
+
+use std::collections::BTreeMap;
+
+use crate::{Enum, Function, GlobalStatement, Macro, Scope, Struct, TypeDef, Union, Variable};
+
+/// An index of the names defined at the top level of a `Scope`, built by `Scope::symbols`.
+///
+/// Source tags are looked through, while statements nested in preprocessor conditionals aren't
+/// indexed since they may not be compiled. The index borrows the scope, so it can't go out of
+/// date; build a new one after changing the scope.
+///
+/// # Examples
+/// ```rust
+/// let symbols = scope.symbols();
+/// for name in ["init", "update", "draw"] {
+///     if symbols.find_function(name).is_none() {
+///         // generate it
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Symbols<'a> {
+    /// The statements of each name with their positions in the scope, in order
+    names: BTreeMap<&'a str, Vec<(usize, &'a GlobalStatement)>>,
+}
+
+impl<'a> Symbols<'a> {
+    /// Creates an index of the names `scope` defines.
+    pub fn new(scope: &'a Scope) -> Self {
+        let mut names: BTreeMap<&str, Vec<_>> = BTreeMap::new();
+        for (idx, stmt) in scope.global_stmts.iter().enumerate() {
+            let stmt = stmt.untagged();
+            for name in defined_names(stmt) {
+                names.entry(name).or_default().push((idx, stmt));
+            }
+        }
+
+        Self { names }
+    }
+
+    /// Whether the scope defines or declares anything named `name`, including macros and
+    /// constants.
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains_key(name)
+    }
+
+    /// Returns the positions in `global_stmts` of the statements defining or declaring `name`,
+    /// in order.
+    pub fn positions(&self, name: &str) -> Vec<usize> {
+        self.names
+            .get(name)
+            .map_or(vec![], |stmts| stmts.iter().map(|(idx, _)| *idx).collect())
+    }
+
+    /// Returns the function named `name`, preferring its definition to a prototype.
+    pub fn find_function(&self, name: &str) -> Option<&'a Function> {
+        self.function_at(name).map(|(_, f)| f)
+    }
+
+    /// Returns the struct named `name`, preferring its definition to a forward declaration.
+    pub fn find_struct(&self, name: &str) -> Option<&'a Struct> {
+        self.struct_at(name).map(|(_, s)| s)
+    }
+
+    /// Returns the enum named `name`.
+    pub fn find_enum(&self, name: &str) -> Option<&'a Enum> {
+        self.find(name, |stmt| match stmt {
+            GlobalStatement::Enum(e) => Some(e),
+            _ => None,
+        })
+        .map(|(_, e)| e)
+    }
+
+    /// Returns the union named `name`.
+    pub fn find_union(&self, name: &str) -> Option<&'a Union> {
+        self.find(name, |stmt| match stmt {
+            GlobalStatement::Union(u) => Some(u),
+            _ => None,
+        })
+        .map(|(_, u)| u)
+    }
+
+    /// Returns the global variable named `name`, preferring its definition to an `extern`
+    /// declaration.
+    pub fn find_variable(&self, name: &str) -> Option<&'a Variable> {
+        self.find(name, |stmt| match stmt {
+            GlobalStatement::Variable(v) => Some(v),
+            _ => None,
+        })
+        .map(|(_, v)| v)
+    }
+
+    /// Returns the typedef named `name`.
+    pub fn find_typedef(&self, name: &str) -> Option<&'a TypeDef> {
+        self.find(name, |stmt| match stmt {
+            GlobalStatement::TypeDef(t) => Some(t),
+            _ => None,
+        })
+        .map(|(_, t)| t)
+    }
+
+    /// Returns the macro named `name`.
+    pub fn find_macro(&self, name: &str) -> Option<&'a Macro> {
+        self.find(name, |stmt| match stmt {
+            GlobalStatement::Macro(m) => Some(m),
+            _ => None,
+        })
+        .map(|(_, m)| m)
+    }
+
+    /// Returns the position and definition of the function named `name`
+    pub(crate) fn function_at(&self, name: &str) -> Option<(usize, &'a Function)> {
+        self.find(name, |stmt| match stmt {
+            GlobalStatement::Function(f) => Some(f),
+            _ => None,
+        })
+    }
+
+    /// Returns the position and definition of the struct named `name`
+    pub(crate) fn struct_at(&self, name: &str) -> Option<(usize, &'a Struct)> {
+        self.find(name, |stmt| match stmt {
+            GlobalStatement::Struct(s) => Some(s),
+            _ => None,
+        })
+    }
+
+    /// Returns the first statement of `name` that `get` accepts and is a definition, or the
+    /// first one it accepts if none is
+    fn find<T: Definition>(
+        &self,
+        name: &str,
+        get: impl Fn(&'a GlobalStatement) -> Option<&'a T>,
+    ) -> Option<(usize, &'a T)> {
+        let found: Vec<_> = self
+            .names
+            .get(name)?
+            .iter()
+            .filter_map(|(idx, stmt)| Some((*idx, get(stmt)?)))
+            .collect();
+
+        found
+            .iter()
+            .find(|(_, t)| t.is_definition())
+            .or(found.first())
+            .copied()
+    }
+}
+
+/// Something that can be declared before it is defined
+trait Definition {
+    /// Whether this is the definition rather than a declaration
+    fn is_definition(&self) -> bool {
+        true
+    }
+}
+
+impl Definition for Function {
+    /// A function without statements is written as a prototype
+    fn is_definition(&self) -> bool {
+        !self.body.stmts.is_empty() && !self.is_extern
+    }
+}
+
+impl Definition for Struct {
+    fn is_definition(&self) -> bool {
+        !self.fields.is_empty()
+    }
+}
+
+impl Definition for Variable {
+    fn is_definition(&self) -> bool {
+        !self.is_extern
+    }
+}
+
+impl Definition for Enum {}
+impl Definition for Union {}
+impl Definition for TypeDef {}
+impl Definition for Macro {}
+
+/// Returns the names `stmt` defines or declares
+fn defined_names(stmt: &GlobalStatement) -> Vec<&str> {
+    match stmt {
+        GlobalStatement::Function(f) => vec![&f.name],
+        GlobalStatement::Struct(s) => vec![&s.name],
+        GlobalStatement::Enum(e) if !e.name.is_empty() => vec![&e.name],
+        GlobalStatement::Union(u) => vec![&u.name],
+        GlobalStatement::Variable(v) => vec![&v.name],
+        GlobalStatement::TypeDef(t) => vec![&t.name],
+        GlobalStatement::Macro(Macro::Obj(m)) => vec![&m.name],
+        GlobalStatement::Macro(Macro::Func(m)) => vec![&m.name],
+        GlobalStatement::Constants(table) => table
+            .constants
+            .iter()
+            .map(|(name, ..)| name.as_str())
+            .collect(),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn symbols() {
+        let int = || Type::new(BaseType::Int).build();
+        let body = BlockBuilder::new()
+            .statement(Statement::Return(Some(Expr::Int(0))))
+            .build();
+        let mut scope = ScopeBuilder::new()
+            .global_statement(GlobalStatement::Struct(
+                StructBuilder::new_with_str("State").build(),
+            ))
+            .global_statement(GlobalStatement::Function(
+                FunctionBuilder::new_with_str("init", int()).build(),
+            ))
+            .global_statement(GlobalStatement::Variable(
+                VariableBuilder::new_with_str("count", int())
+                    .make_extern()
+                    .build(),
+            ))
+            .global_statement(
+                GlobalStatement::Struct(
+                    StructBuilder::new_with_str("State")
+                        .field(FieldBuilder::new_with_str("x", int()).build())
+                        .build(),
+                )
+                .tagged(3, "main.clla"),
+            )
+            .global_statement(GlobalStatement::Function(
+                FunctionBuilder::new_with_str("init", int())
+                    .body(body)
+                    .build(),
+            ))
+            .global_statement(GlobalStatement::Constants(
+                ConstantTable::new()
+                    .constant("WIDTH", Expr::Int(800), None)
+                    .build(),
+            ))
+            .build();
+
+        let symbols = scope.symbols();
+        assert_eq!(symbols.positions("State"), [0, 3]);
+        assert_eq!(symbols.positions("init"), [1, 4]);
+        assert!(symbols.contains("WIDTH"));
+        assert!(!symbols.contains("draw"));
+
+        // Definitions are preferred to declarations, wherever they are
+        assert_eq!(symbols.find_struct("State").unwrap().fields().len(), 1);
+        assert!(!symbols.find_function("init").unwrap().body.stmts.is_empty());
+        assert!(symbols.find_variable("count").unwrap().is_extern);
+
+        // Lookups are typed
+        assert!(symbols.find_struct("init").is_none());
+        assert!(symbols.find_enum("State").is_none());
+
+        scope
+            .find_struct_mut("State")
+            .unwrap()
+            .push_field(FieldBuilder::new_with_str("y", int()).build());
+        let fields: Vec<&str> = scope
+            .find_struct("State")
+            .unwrap()
+            .fields()
+            .iter()
+            .map(|field| field.name.as_str())
+            .collect();
+        assert_eq!(fields, ["x", "y"]);
+    }
+}
@@ -52,7 +52,7 @@ use tamacro::DisplayFromFormat;
 /// let typedef = TypeDef::new(callback_type, "Callback".to_string()).build();
 /// // Outputs something like: typedef void (*Callback)(int, void*);
 /// ```
-#[derive(Debug, Clone, DisplayFromFormat)]
+#[derive(Debug, Clone, PartialEq, Eq, DisplayFromFormat)]
 pub struct TypeDef {
     /// The type to be aliased.
     pub t: Type,
@@ -119,8 +119,8 @@ impl TypeDef {
 impl Format for TypeDef {
     fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
         write!(fmt, "typedef ")?;
-        self.t.format(fmt)?;
-        writeln!(fmt, " {};", self.name)
+        self.t.format_declarator(fmt, &self.name)?;
+        writeln!(fmt, ";")
     }
 }
 
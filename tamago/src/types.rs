@@ -25,6 +25,7 @@
 //! useful for generating C code or bindings programmatically from Rust.
 
 use std::fmt::{self, Write};
+use std::str::FromStr;
 
 use crate::{Format, Formatter};
 use tamacro::DisplayFromFormat;
@@ -52,7 +53,8 @@ use tamacro::DisplayFromFormat;
 /// ```c
 /// struct Point
 /// ```
-#[derive(Debug, Clone, DisplayFromFormat)]
+#[derive(Debug, Clone, PartialEq, Eq, DisplayFromFormat)]
+#[non_exhaustive]
 pub enum BaseType {
     /// Represents the `void` type.
     Void,
@@ -113,6 +115,10 @@ pub enum BaseType {
 
     /// `typedef`
     TypeDef(String),
+
+    /// A pointer to a function returning `ret` and taking `params`, which C declares around the
+    /// name (e.g. `int64_t (*on_read)(uint8_t*, uint64_t)`).
+    FnPtr { ret: Box<Type>, params: Vec<Type> },
 }
 
 impl BaseType {
@@ -184,6 +190,33 @@ impl BaseType {
         }
     }
 
+    /// Creates a new function pointer type.
+    ///
+    /// # Parameters
+    ///
+    /// * `ret` - The return type of the function pointed to
+    /// * `params` - The types of its parameters, in order
+    ///
+    /// # Returns
+    ///
+    /// A `BaseType::FnPtr`, which is declared around the name of what it types
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let on_read = BaseType::new_fn_ptr(
+    ///     Type::new(BaseType::Int64).build(),
+    ///     vec![Type::new(BaseType::UInt8).make_pointer().build()],
+    /// );
+    /// assert_eq!(on_read.to_string(), "int64_t (*)(uint8_t*)");
+    /// ```
+    pub fn new_fn_ptr(ret: Type, params: Vec<Type>) -> Self {
+        BaseType::FnPtr {
+            ret: Box::new(ret),
+            params,
+        }
+    }
+
     /// Checks whether the type is an integer type.
     ///
     /// This includes all signed and unsigned integer types, `char`, `size_t`, `uintptr_t`, and `bool`.
@@ -263,6 +296,7 @@ impl Format for BaseType {
             Struct(s) => write!(fmt, "struct {s}"),
             Union(s) => write!(fmt, "union {s}"),
             TypeDef(s) => write!(fmt, "{s}"),
+            FnPtr { .. } => Type::new(self.clone()).build().format(fmt),
         }
     }
 }
@@ -283,7 +317,7 @@ impl Format for BaseType {
 /// ```c
 /// volatile char
 /// ```
-#[derive(Debug, Clone, Copy, DisplayFromFormat)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DisplayFromFormat)]
 pub enum TypeQualifier {
     /// The `volatile` keyword, indicating the variable may change unexpectedly.
     Volatile,
@@ -319,11 +353,17 @@ impl Format for TypeQualifier {
 /// int[10]
 /// ```
 ///
+/// Two-dimensional array of pointers, and pointer to a two-dimensional array:
+/// ```c
+/// char* names[4][8]
+/// int (*grid)[4][8]
+/// ```
+///
 /// Double pointer to volatile float:
 /// ```c
 /// volatile float**
 /// ```
-#[derive(Debug, Clone, DisplayFromFormat)]
+#[derive(Debug, Clone, PartialEq, Eq, DisplayFromFormat)]
 pub struct Type {
     /// The base type used to construct a type.
     pub base: BaseType,
@@ -331,11 +371,21 @@ pub struct Type {
     /// All the qualifiers for the type.
     pub qualifiers: Vec<TypeQualifier>,
 
-    /// Pointers
-    pub pointers: u8,
+    /// The qualifiers of each level of indirection, from the innermost pointer to the outermost.
+    ///
+    /// For example, `const char* const*` has two levels, qualified with `[Const]` and `[]`.
+    pub pointers: Vec<Vec<TypeQualifier>>,
 
-    /// Array
-    pub array: usize,
+    /// The sizes of the dimensions of the array, from the outermost to the innermost, which is
+    /// empty if the type isn't an array.
+    ///
+    /// For example, `int grid[4][8]` has the dimensions `[4, 8]`.
+    pub array: Vec<usize>,
+
+    /// The qualifiers of each level of indirection to the array, from the innermost pointer to
+    /// the outermost, which makes the type a pointer to an array (`int (*)[4]`) rather than an
+    /// array of pointers (`int* [4]`).
+    pub array_pointers: Vec<Vec<TypeQualifier>>,
 }
 
 impl Type {
@@ -368,7 +418,7 @@ impl Type {
     ///
     /// # Returns
     ///
-    /// `true` if the type is an array (array size > 0), `false` otherwise
+    /// `true` if the type has array dimensions and isn't a pointer to them, `false` otherwise
     ///
     /// # Examples
     ///
@@ -379,12 +429,139 @@ impl Type {
     /// assert!(!simple_type.is_array());
     /// ```
     pub fn is_array(&self) -> bool {
-        self.array != 0
+        !self.array.is_empty() && self.array_pointers.is_empty()
     }
-}
 
-impl Format for Type {
-    fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+    /// Returns the number of elements of the array, multiplying the sizes of its dimensions, or
+    /// 1 if the type isn't an array.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let grid = Type::new(BaseType::Int).make_array(4).add_array_dimension(8).build();
+    /// assert_eq!(grid.array_len(), 32);
+    /// ```
+    pub fn array_len(&self) -> usize {
+        match self.is_array() {
+            true => self.array.iter().product(),
+            false => 1,
+        }
+    }
+
+    /// Parses a C type written as a string, e.g. a field type given in a configuration file.
+    ///
+    /// The string is what [`Type`]'s `Display` prints, optionally followed by array sizes, so
+    /// `Type::parse(&t.to_string())` gives back `t` for any type that isn't an array. Function
+    /// pointers and pointers to arrays are written without a name (`int (*)(char*, size_t)`,
+    /// `int (*)[4]`), and any identifier that isn't a keyword is taken to be a `typedef` name.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The type, e.g. `"const char*"`
+    ///
+    /// # Returns
+    ///
+    /// The parsed `Type`, or a `ParseTypeError` if `s` isn't a type that `Type` can represent,
+    /// such as `unsigned long`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let t = Type::parse("const char* const").unwrap();
+    /// assert_eq!(t.to_string(), "const char* const");
+    ///
+    /// let t = Type::parse("uint8_t[16][4]").unwrap();
+    /// assert_eq!(t.array, vec![16, 4]);
+    ///
+    /// assert!(Type::parse("unsigned long").is_err());
+    /// ```
+    pub fn parse(s: &str) -> Result<Self, ParseTypeError> {
+        let mut parser = TypeParser {
+            tokens: tokenize(s)?,
+            pos: 0,
+        };
+
+        let t = parser.parse_type()?;
+        match parser.next() {
+            Some(token) => Err(ParseTypeError::Unexpected(token.to_string())),
+            None => Ok(t),
+        }
+    }
+
+    /// Formats the type around the name it declares, which is where C puts the array sizes and
+    /// the name of a function pointer (`int xs[4][2]`, `int (*f)(void)`, `int (*p)[4]`), or the
+    /// type alone if `name` is empty.
+    pub(crate) fn format_declarator(&self, fmt: &mut Formatter<'_>, name: &str) -> fmt::Result {
+        let declarator = self.array_declarator(name)?;
+        let BaseType::FnPtr { ret, params } = &self.base else {
+            self.format_specifiers(fmt)?;
+            if !name.is_empty() || !self.array_pointers.is_empty() {
+                write!(fmt, " ")?;
+            }
+            return write!(fmt, "{declarator}");
+        };
+
+        // The qualifiers of a function pointer apply to the pointer itself, and any further
+        // pointers point to it
+        let mut fn_declarator = "*".to_string();
+        for q in &self.qualifiers {
+            write!(fn_declarator, " {q}")?;
+        }
+        for level in &self.pointers {
+            fn_declarator.push('*');
+            for q in level {
+                write!(fn_declarator, " {q}")?;
+            }
+        }
+        if !declarator.is_empty() {
+            if !fn_declarator.ends_with('*') && !declarator.starts_with('[') {
+                fn_declarator.push(' ');
+            }
+            fn_declarator.push_str(&declarator);
+        }
+
+        ret.format(fmt)?;
+        write!(fmt, " ({fn_declarator})(")?;
+        if params.is_empty() {
+            write!(fmt, "void")?;
+        }
+        for (i, param) in params.iter().enumerate() {
+            if i > 0 {
+                write!(fmt, ", ")?;
+            }
+            param.format_declarator(fmt, "")?;
+        }
+        write!(fmt, ")")
+    }
+
+    /// Returns the part of the declarator for the array dimensions and the pointers to them,
+    /// around `name`: `name[4][2]`, or `(* const name)[4]` for a pointer to an array.
+    fn array_declarator(&self, name: &str) -> Result<String, fmt::Error> {
+        let mut declarator = String::new();
+        if !self.array_pointers.is_empty() {
+            declarator.push('(');
+            for level in &self.array_pointers {
+                declarator.push('*');
+                for q in level {
+                    write!(declarator, " {q}")?;
+                }
+            }
+            if !name.is_empty() && !declarator.ends_with('*') {
+                declarator.push(' ');
+            }
+        }
+        declarator.push_str(name);
+        if !self.array_pointers.is_empty() {
+            declarator.push(')');
+        }
+
+        for size in &self.array {
+            write!(declarator, "[{size}]")?;
+        }
+        Ok(declarator)
+    }
+
+    fn format_specifiers(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
         for q in &self.qualifiers {
             q.format(fmt)?;
             write!(fmt, " ")?;
@@ -392,12 +569,359 @@ impl Format for Type {
 
         self.base.format(fmt)?;
 
-        write!(fmt, "{}", "*".repeat(self.pointers.into()))?;
+        for level in &self.pointers {
+            write!(fmt, "*")?;
+            for q in level {
+                write!(fmt, " ")?;
+                q.format(fmt)?;
+            }
+        }
 
         Ok(())
     }
 }
 
+impl Format for Type {
+    fn format(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self.base {
+            BaseType::FnPtr { .. } => self.format_declarator(fmt, ""),
+            // The dimensions are part of what a pointer to an array points to
+            _ if !self.array_pointers.is_empty() => self.format_declarator(fmt, ""),
+            _ => self.format_specifiers(fmt),
+        }
+    }
+}
+
+impl FromStr for Type {
+    type Err = ParseTypeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+/// An error from parsing a C type with [`Type::parse`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseTypeError {
+    /// The type ends early, e.g. `struct` without a name
+    UnexpectedEnd,
+
+    /// The token doesn't belong where it is, e.g. the `]` in `int]`
+    Unexpected(String),
+
+    /// The type is valid C but can't be represented by `Type`, e.g. `unsigned int`
+    Unsupported(String),
+}
+
+impl fmt::Display for ParseTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseTypeError::UnexpectedEnd => write!(f, "the type ends unexpectedly"),
+            ParseTypeError::Unexpected(token) => write!(f, "unexpected '{token}' in the type"),
+            ParseTypeError::Unsupported(word) => {
+                write!(f, "types with '{word}' are not supported")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseTypeError {}
+
+/// Splits a C type into identifiers, numbers, and punctuation.
+fn tokenize(s: &str) -> Result<Vec<&str>, ParseTypeError> {
+    let mut tokens = vec![];
+    let mut chars = s.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if c.is_ascii_alphanumeric() || c == '_' {
+            let mut end = start + 1;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = i + 1;
+                chars.next();
+            }
+            tokens.push(&s[start..end]);
+        } else if "*()[],".contains(c) {
+            tokens.push(&s[start..start + 1]);
+        } else {
+            return Err(ParseTypeError::Unexpected(c.to_string()));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parses the tokens of a C type, recursing into the parameters of function pointers.
+struct TypeParser<'s> {
+    tokens: Vec<&'s str>,
+    pos: usize,
+}
+
+impl<'s> TypeParser<'s> {
+    fn parse_type(&mut self) -> Result<Type, ParseTypeError> {
+        let mut qualifiers = self.qualifiers();
+        let base = self.base()?;
+        qualifiers.extend(self.qualifiers());
+
+        let mut pointers = vec![];
+        while self.eat("*") {
+            pointers.push(self.qualifiers());
+        }
+
+        let t = Type {
+            base,
+            qualifiers,
+            pointers,
+            array: vec![],
+            array_pointers: vec![],
+        };
+
+        if self.peek() == Some("(") {
+            self.parenthesized(t)
+        } else {
+            let array = self.array()?;
+            Ok(Type { array, ..t })
+        }
+    }
+
+    /// Parses the rest of a function pointer returning `ret`, e.g. `(*)(int, char)`, or of a
+    /// pointer to an array of `ret`, e.g. `(*)[4]`.
+    fn parenthesized(&mut self, ret: Type) -> Result<Type, ParseTypeError> {
+        self.expect("(")?;
+        self.expect("*")?;
+        let qualifiers = self.qualifiers();
+        let mut pointers = vec![];
+        while self.eat("*") {
+            pointers.push(self.qualifiers());
+        }
+        let array = self.array()?;
+        self.expect(")")?;
+
+        if self.peek() == Some("[") {
+            // An array of pointers to arrays (`int (*[2])[4]`) can't be represented
+            if !array.is_empty() {
+                return Err(ParseTypeError::Unsupported("(".to_string()));
+            }
+            pointers.insert(0, qualifiers);
+            return Ok(Type {
+                array: self.array()?,
+                array_pointers: pointers,
+                ..ret
+            });
+        }
+
+        self.expect("(")?;
+        let mut params = vec![];
+        if self.peek() == Some("void") && self.tokens.get(self.pos + 1) == Some(&")") {
+            self.pos += 1;
+        } else if self.peek() != Some(")") {
+            loop {
+                params.push(self.parse_type()?);
+                if !self.eat(",") {
+                    break;
+                }
+            }
+        }
+        self.expect(")")?;
+
+        Ok(Type {
+            base: BaseType::FnPtr {
+                ret: Box::new(ret),
+                params,
+            },
+            qualifiers,
+            pointers,
+            array,
+            array_pointers: vec![],
+        })
+    }
+
+    fn base(&mut self) -> Result<BaseType, ParseTypeError> {
+        use BaseType::*;
+
+        let token = self.next().ok_or(ParseTypeError::UnexpectedEnd)?;
+        Ok(match token {
+            "void" => Void,
+            "double" => Double,
+            "float" => Float,
+            "char" => Char,
+            "int" => Int,
+            "uint8_t" => UInt8,
+            "uint16_t" => UInt16,
+            "uint32_t" => UInt32,
+            "uint64_t" => UInt64,
+            "int8_t" => Int8,
+            "int16_t" => Int16,
+            "int32_t" => Int32,
+            "int64_t" => Int64,
+            "size_t" => Size,
+            "uintptr_t" => UIntPtr,
+            "bool" => Bool,
+            "enum" => Enum(self.name()?),
+            "struct" => Struct(self.name()?),
+            "union" => Union(self.name()?),
+            "unsigned" | "signed" | "short" | "long" => {
+                return Err(ParseTypeError::Unsupported(token.to_string()));
+            }
+            _ if is_name(token) => TypeDef(token.to_string()),
+            _ => return Err(ParseTypeError::Unexpected(token.to_string())),
+        })
+    }
+
+    fn name(&mut self) -> Result<String, ParseTypeError> {
+        match self.next() {
+            Some(token) if is_name(token) => Ok(token.to_string()),
+            Some(token) => Err(ParseTypeError::Unexpected(token.to_string())),
+            None => Err(ParseTypeError::UnexpectedEnd),
+        }
+    }
+
+    fn qualifiers(&mut self) -> Vec<TypeQualifier> {
+        let mut qualifiers = vec![];
+        loop {
+            if self.eat("const") {
+                qualifiers.push(TypeQualifier::Const);
+            } else if self.eat("volatile") {
+                qualifiers.push(TypeQualifier::Volatile);
+            } else {
+                return qualifiers;
+            }
+        }
+    }
+
+    /// Parses the sizes of any array dimensions.
+    fn array(&mut self) -> Result<Vec<usize>, ParseTypeError> {
+        let mut array = vec![];
+        while self.eat("[") {
+            let size = match self.next() {
+                Some(token) => match token.parse() {
+                    Ok(size) if size > 0 => size,
+                    _ => return Err(ParseTypeError::Unexpected(token.to_string())),
+                },
+                None => return Err(ParseTypeError::UnexpectedEnd),
+            };
+            self.expect("]")?;
+            array.push(size);
+        }
+        Ok(array)
+    }
+
+    fn peek(&self) -> Option<&'s str> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<&'s str> {
+        let token = self.peek()?;
+        self.pos += 1;
+        Some(token)
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        let found = self.peek() == Some(token);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), ParseTypeError> {
+        match self.next() {
+            Some(t) if t == token => Ok(()),
+            Some(t) => Err(ParseTypeError::Unexpected(t.to_string())),
+            None => Err(ParseTypeError::UnexpectedEnd),
+        }
+    }
+}
+
+/// Whether the token can name a `typedef` or tag, i.e. is an identifier but not a keyword.
+fn is_name(token: &str) -> bool {
+    const KEYWORDS: [&str; 14] = [
+        "const", "volatile", "enum", "struct", "union", "void", "char", "int", "float", "double",
+        "signed", "unsigned", "short", "long",
+    ];
+
+    token.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') && !KEYWORDS.contains(&token)
+}
+
+/// Maps a Rust type to the C type with the same memory representation.
+///
+/// This is implemented for primitive numeric types, `bool`, fixed-size arrays, and raw pointers,
+/// and for any struct deriving [`ToTamago`](crate::ToTamago).
+///
+/// # Examples
+///
+/// ```rust
+/// assert_eq!(u64::c_type().to_string(), "uint64_t");
+/// assert_eq!(<[[u8; 4]; 2]>::c_type().array, vec![2, 4]);
+/// assert_eq!(<*const i32>::c_type().to_string(), "const int32_t*");
+/// ```
+pub trait CType {
+    /// Returns the C type corresponding to `Self`
+    fn c_type() -> Type;
+}
+
+macro_rules! impl_c_type {
+    ($($t:ty => $base:expr),* $(,)?) => {
+        $(
+            impl CType for $t {
+                fn c_type() -> Type {
+                    Type::new($base).build()
+                }
+            }
+        )*
+    };
+}
+
+impl_c_type! {
+    () => BaseType::Void,
+    f32 => BaseType::Float,
+    f64 => BaseType::Double,
+    i8 => BaseType::Int8,
+    i16 => BaseType::Int16,
+    i32 => BaseType::Int32,
+    i64 => BaseType::Int64,
+    u8 => BaseType::UInt8,
+    u16 => BaseType::UInt16,
+    u32 => BaseType::UInt32,
+    u64 => BaseType::UInt64,
+    usize => BaseType::Size,
+    bool => BaseType::Bool,
+}
+
+impl<T: CType, const N: usize> CType for [T; N] {
+    fn c_type() -> Type {
+        let mut t = T::c_type();
+        t.array.insert(0, N);
+        t
+    }
+}
+
+impl<T: CType> CType for *mut T {
+    fn c_type() -> Type {
+        let mut t = T::c_type();
+        t.pointers.push(vec![]);
+        t
+    }
+}
+
+impl<T: CType> CType for *const T {
+    fn c_type() -> Type {
+        let mut t = T::c_type();
+        let pointee = match t.pointers.last_mut() {
+            Some(level) => level,
+            None => &mut t.qualifiers,
+        };
+        if !pointee.iter().any(|q| matches!(q, TypeQualifier::Const)) {
+            pointee.push(TypeQualifier::Const);
+        }
+        t.pointers.push(vec![]);
+        t
+    }
+}
+
 /// A builder for constructing a `Type` instance with a fluent interface.
 ///
 /// The `TypeBuilder` allows incremental configuration of a C type's properties,
@@ -405,8 +929,9 @@ impl Format for Type {
 pub struct TypeBuilder {
     base: BaseType,
     qualifiers: Vec<TypeQualifier>,
-    pointers: u8,
-    array: usize,
+    pointers: Vec<Vec<TypeQualifier>>,
+    array: Vec<usize>,
+    array_pointers: Vec<Vec<TypeQualifier>>,
 }
 
 impl TypeBuilder {
@@ -418,7 +943,7 @@ impl TypeBuilder {
     ///
     /// # Returns
     ///
-    /// A new `TypeBuilder` instance with default values for qualifiers, pointers, and array size
+    /// A new `TypeBuilder` instance with default values for qualifiers, pointers, and array sizes
     ///
     /// # Examples
     ///
@@ -429,12 +954,13 @@ impl TypeBuilder {
         Self {
             base,
             qualifiers: vec![],
-            pointers: 0,
-            array: 0,
+            pointers: vec![],
+            array: vec![],
+            array_pointers: vec![],
         }
     }
 
-    /// Adds a type qualifier to the type being built.
+    /// Adds a type qualifier to the base of the type being built, whatever pointers it has.
     ///
     /// # Parameters
     ///
@@ -493,7 +1019,7 @@ impl TypeBuilder {
 
     /// Makes the type a pointer.
     ///
-    /// Increases the pointer level by one.
+    /// Adds an unqualified level of indirection, which `pointer_qualifier` can then qualify.
     ///
     /// # Returns
     ///
@@ -506,11 +1032,63 @@ impl TypeBuilder {
     /// assert_eq!(builder.build().to_string(), "void*");
     /// ```
     pub fn make_pointer(mut self) -> Self {
-        self.pointers += 1;
+        self.pointers.push(vec![]);
         self
     }
 
-    /// Makes the type an array with the given size.
+    /// Adds a type qualifier to the outermost pointer, rather than to what it points to.
+    ///
+    /// # Parameters
+    ///
+    /// * `q` - The type qualifier to add
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Panics
+    ///
+    /// Panics if the type isn't a pointer yet.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let builder = TypeBuilder::new(BaseType::Int)
+    ///     .make_pointer()
+    ///     .pointer_qualifier(TypeQualifier::Volatile);
+    /// assert_eq!(builder.build().to_string(), "int* volatile");
+    /// ```
+    pub fn pointer_qualifier(mut self, q: TypeQualifier) -> Self {
+        self.pointers
+            .last_mut()
+            .expect("pointer_qualifier requires a pointer, call make_pointer first")
+            .push(q);
+        self
+    }
+
+    /// Makes the type a const pointer, which cannot be changed to point somewhere else.
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let builder = TypeBuilder::new(BaseType::Char).make_const_pointer();
+    /// assert_eq!(builder.build().to_string(), "char* const");
+    /// ```
+    pub fn make_const_pointer(self) -> Self {
+        self.make_pointer().pointer_qualifier(TypeQualifier::Const)
+    }
+
+    /// Makes the type an array of the given size, or not an array if the size is 0.
+    ///
+    /// Calling it again replaces the size, and for a multidimensional array it sets the size of
+    /// the outermost dimension, leaving the ones added with `add_array_dimension`.
+    ///
+    /// Pointers added with `make_pointer` are part of the element type, making an array of
+    /// pointers, which `make_array_pointer` turns into a pointer to an array.
     ///
     /// # Parameters
     ///
@@ -526,7 +1104,60 @@ impl TypeBuilder {
     /// let builder = TypeBuilder::new(BaseType::Int).make_array(10);
     /// ```
     pub fn make_array(mut self, size: usize) -> Self {
-        self.array = size;
+        match self.array.first_mut() {
+            _ if size == 0 => self.array.clear(),
+            Some(outermost) => *outermost = size,
+            None => self.array.push(size),
+        }
+        self
+    }
+
+    /// Adds an array dimension with the given size, inside the ones added before, so that
+    /// `.make_array(4).add_array_dimension(8)` declares `int grid[4][8]`. The first dimension
+    /// makes the type an array like `make_array`.
+    ///
+    /// # Parameters
+    ///
+    /// * `size` - The size of the dimension
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let grid = TypeBuilder::new(BaseType::Int)
+    ///     .make_array(4)
+    ///     .add_array_dimension(8)
+    ///     .build();
+    /// assert_eq!(grid.array, vec![4, 8]);
+    /// ```
+    pub fn add_array_dimension(mut self, size: usize) -> Self {
+        self.array.push(size);
+        self
+    }
+
+    /// Makes the type a pointer to the array built so far, e.g. `int (*)[4]` rather than the
+    /// array of pointers `int* [4]`.
+    ///
+    /// The type should be an array by the time it is built, which `try_build` checks.
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let rows = TypeBuilder::new(BaseType::Int)
+    ///     .make_array(8)
+    ///     .make_array_pointer()
+    ///     .build();
+    /// assert_eq!(rows.to_string(), "int (*)[8]");
+    /// ```
+    pub fn make_array_pointer(mut self) -> Self {
+        self.array_pointers.push(vec![]);
         self
     }
 
@@ -545,16 +1176,78 @@ impl TypeBuilder {
     ///     .build();
     /// assert_eq!(t.to_string(), "const double*");
     /// ```
-    pub fn build(self) -> Type {
+    ///
+    /// A pointer to an array of a type that isn't an array is built as a plain pointer, which
+    /// `try_build` reports instead.
+    pub fn build(mut self) -> Type {
+        if self.array.is_empty() {
+            self.pointers.append(&mut self.array_pointers);
+        }
+        self.into_type()
+    }
+
+    /// Consumes the builder and returns the `Type`, checking that it can be represented in C.
+    ///
+    /// A pointer to an array (`make_array_pointer`) needs an array to point to.
+    ///
+    /// # Returns
+    ///
+    /// A fully constructed `Type` instance, or the reason it cannot be represented in C
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let err = TypeBuilder::new(BaseType::Int)
+    ///     .make_array_pointer()
+    ///     .try_build()
+    ///     .unwrap_err();
+    /// assert_eq!(err, TypeError::ArrayPointerWithoutArray("int".to_string()));
+    /// ```
+    pub fn try_build(self) -> Result<Type, TypeError> {
+        let t = self.into_type();
+        if !t.array_pointers.is_empty() && t.array.is_empty() {
+            let element = Type {
+                array_pointers: vec![],
+                ..t
+            };
+            return Err(TypeError::ArrayPointerWithoutArray(element.to_string()));
+        }
+
+        Ok(t)
+    }
+
+    fn into_type(self) -> Type {
         Type {
             base: self.base,
             qualifiers: self.qualifiers,
             pointers: self.pointers,
             array: self.array,
+            array_pointers: self.array_pointers,
         }
     }
 }
 
+/// The reasons a [`TypeBuilder`] cannot build a valid C type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeError {
+    /// The type, e.g. `int`, is made a pointer to an array but isn't an array
+    ArrayPointerWithoutArray(String),
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeError::ArrayPointerWithoutArray(t) => write!(
+                f,
+                "'{t}' cannot be made a pointer to an array since it isn't one, call make_array \
+                 first"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TypeError {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -608,4 +1301,202 @@ mod tests {
         assert_eq!(t.to_string(), "const void*");
         assert!(t.is_array())
     }
+
+    #[test]
+    fn pointer_qualifiers() {
+        use BaseType::*;
+        let t = Type::new(Char).make_pointer().build();
+        assert_eq!(t.to_string(), "char*");
+
+        let t = Type::new(Char).make_const().make_pointer().build();
+        assert_eq!(t.to_string(), "const char*");
+
+        let t = Type::new(Char).make_const_pointer().build();
+        assert_eq!(t.to_string(), "char* const");
+
+        let t = Type::new(Char).make_const().make_const_pointer().build();
+        assert_eq!(t.to_string(), "const char* const");
+
+        let t = Type::new(Char)
+            .make_const_pointer()
+            .make_pointer()
+            .pointer_qualifier(TypeQualifier::Volatile)
+            .build();
+        assert_eq!(t.to_string(), "char* const* volatile");
+
+        assert_eq!(
+            <*const *const i32>::c_type().to_string(),
+            "const int32_t* const*"
+        );
+        assert_eq!(<*mut *const u8>::c_type().to_string(), "const uint8_t**");
+    }
+    #[test]
+    fn function_pointers() {
+        let on_read = Type::new(BaseType::FnPtr {
+            ret: Box::new(Type::new(BaseType::Int64).build()),
+            params: vec![
+                Type::new(BaseType::UInt8).make_pointer().build(),
+                Type::new(BaseType::UInt64).build(),
+            ],
+        })
+        .build();
+        assert_eq!(on_read.to_string(), "int64_t (*)(uint8_t*, uint64_t)");
+
+        let field = crate::FieldBuilder::new_with_str("on_read", on_read).build();
+        assert_eq!(
+            field.to_string(),
+            "int64_t (*on_read)(uint8_t*, uint64_t);\n"
+        );
+
+        let callback = BaseType::new_fn_ptr(Type::new(BaseType::Void).build(), vec![]);
+        let table = Type::new(callback.clone())
+            .make_const()
+            .make_array(4)
+            .build();
+        let var = crate::VariableBuilder::new_with_str("handlers", table).build();
+        assert_eq!(var.to_string(), "void (* const handlers[4])(void)");
+
+        let t = Type::new(callback).make_pointer().build();
+        assert_eq!(t.to_string(), "void (**)(void)");
+    }
+
+    #[test]
+    fn arrays() {
+        use crate::VariableBuilder;
+        use BaseType::*;
+
+        let decl = |t: Type| VariableBuilder::new_with_str("x", t).build().to_string();
+
+        let grid = Type::new(Int).make_array(4).add_array_dimension(8).build();
+        assert_eq!(decl(grid.clone()), "int x[4][8]");
+        assert_eq!(grid.array_len(), 32);
+
+        // `make_array` sets the size of the outermost dimension, and 0 makes it not an array
+        let t = Type::new(Int).make_array(4).make_array(8).build();
+        assert_eq!(decl(t), "int x[8]");
+        let t = Type::new(Int).make_array(4).make_array(0).build();
+        assert_eq!(decl(t), "int x");
+        let t = Type::new(Int)
+            .add_array_dimension(4)
+            .add_array_dimension(8)
+            .make_array(2)
+            .build();
+        assert_eq!(decl(t), "int x[2][8]");
+
+        // An array of pointers and a pointer to an array
+        let names = Type::new(Char).make_pointer().make_array(4).build();
+        assert_eq!(decl(names), "char* x[4]");
+        let rows = Type::new(Int)
+            .make_array(4)
+            .add_array_dimension(8)
+            .make_array_pointer()
+            .build();
+        assert!(!rows.is_array());
+        assert_eq!(rows.array_len(), 1);
+        assert_eq!(rows.to_string(), "int (*)[4][8]");
+        assert_eq!(decl(rows), "int (*x)[4][8]");
+
+        let mut t = Type::new(Char)
+            .make_const()
+            .make_pointer()
+            .make_array(2)
+            .make_array_pointer()
+            .make_array_pointer()
+            .build();
+        t.array_pointers[1].push(TypeQualifier::Const);
+        assert_eq!(decl(t), "const char* (** const x)[2]");
+
+        // A pointer to an array of function pointers
+        let callback = BaseType::new_fn_ptr(Type::new(Void).build(), vec![]);
+        let table = Type::new(callback)
+            .make_array(4)
+            .make_array_pointer()
+            .build();
+        assert_eq!(decl(table.clone()), "void (*(*x)[4])(void)");
+        assert_eq!(table.to_string(), "void (*(*)[4])(void)");
+    }
+
+    #[test]
+    fn parse() {
+        use BaseType::*;
+
+        let round_trip = |t: Type| assert_eq!(Type::parse(&t.to_string()), Ok(t));
+        round_trip(Type::new(Char).make_const().make_pointer().build());
+        round_trip(
+            Type::new(Struct("Node".to_string()))
+                .make_pointer()
+                .make_const_pointer()
+                .make_volatile()
+                .build(),
+        );
+        round_trip(Type::new(TypeDef("Handle".to_string())).build());
+        round_trip(
+            Type::new(BaseType::new_fn_ptr(
+                Type::new(Int64).build(),
+                vec![
+                    Type::new(UInt8).make_pointer().build(),
+                    Type::new(BaseType::new_fn_ptr(Type::new(Void).build(), vec![])).build(),
+                ],
+            ))
+            .make_const()
+            .build(),
+        );
+
+        let t: Type = " char const *[ 4 ]".parse().unwrap();
+        assert_eq!(t.to_string(), "const char*");
+        assert_eq!(t.array, vec![4]);
+
+        round_trip(
+            Type::new(Int)
+                .make_array(4)
+                .add_array_dimension(8)
+                .make_array_pointer()
+                .build(),
+        );
+        let t = Type::parse("int32_t[2][3]").unwrap();
+        assert_eq!(t, <[[i32; 3]; 2]>::c_type());
+        assert_eq!(
+            Type::parse("int (*[2])[4]"),
+            Err(ParseTypeError::Unsupported("(".to_string()))
+        );
+
+        assert_eq!(Type::parse("struct"), Err(ParseTypeError::UnexpectedEnd));
+        assert_eq!(
+            Type::parse("int]"),
+            Err(ParseTypeError::Unexpected("]".to_string()))
+        );
+        assert_eq!(
+            Type::parse("unsigned int"),
+            Err(ParseTypeError::Unsupported("unsigned".to_string()))
+        );
+        assert_eq!(
+            Type::parse("int[0]"),
+            Err(ParseTypeError::Unexpected("0".to_string()))
+        );
+        assert!(Type::parse("struct int").is_err());
+    }
+
+    #[test]
+    fn array_pointer_without_array() {
+        let err = Type::new(BaseType::Int)
+            .make_pointer()
+            .make_array_pointer()
+            .try_build()
+            .unwrap_err();
+        assert_eq!(err, TypeError::ArrayPointerWithoutArray("int*".to_string()));
+
+        // The array only has to be there by the time the type is built
+        let t = Type::new(BaseType::Int)
+            .make_array_pointer()
+            .make_array(4)
+            .try_build();
+        assert_eq!(t.unwrap().to_string(), "int (*)[4]");
+    }
+
+    #[test]
+    fn array_pointer_without_array_builds_pointer() {
+        let t = Type::new(BaseType::Int).make_array_pointer().build();
+        assert_eq!(t.to_string(), "int*");
+        assert!(t.array_pointers.is_empty());
+    }
 }
@@ -125,7 +125,13 @@ impl Format for Union {
             })?;
         }
 
-        writeln!(fmt, ";")
+        write!(fmt, ";")?;
+        if !self.fields.is_empty() {
+            fmt.layout_comment(BaseType::Union(self.name.clone()), |abi| {
+                abi.union_layout(self)
+            })?;
+        }
+        writeln!(fmt)
     }
 }
 
@@ -69,6 +69,9 @@ pub struct Variable {
     /// Whether the variable is declared with the `extern` keyword
     pub is_extern: bool,
 
+    /// Whether the variable is declared with the `register` keyword
+    pub is_register: bool,
+
     /// The optional documentation comment for the variable
     pub doc: Option<DocComment>,
 }
@@ -131,13 +134,12 @@ impl Format for Variable {
             write!(fmt, "static ")?;
         }
 
-        self.t.format(fmt)?;
-        write!(fmt, " {}", self.name)?;
-
-        if self.t.is_array() {
-            write!(fmt, "[{}]", self.t.array)?;
+        if self.is_register {
+            write!(fmt, "register ")?;
         }
 
+        self.t.format_declarator(fmt, &self.name)?;
+
         if !self.is_extern {
             if let Some(value) = &self.value {
                 write!(fmt, " = ")?;
@@ -159,6 +161,7 @@ pub struct VariableBuilder {
     value: Option<Expr>,
     is_static: bool,
     is_extern: bool,
+    is_register: bool,
     doc: Option<DocComment>,
 }
 
@@ -186,6 +189,7 @@ impl VariableBuilder {
             value: None,
             is_static: false,
             is_extern: false,
+            is_register: false,
             doc: None,
         }
     }
@@ -260,6 +264,10 @@ impl VariableBuilder {
     /// In C, the `static` keyword limits the variable's scope to the file it is defined in
     /// and preserves its value between function calls if local.
     ///
+    /// A static local variable is initialized only once, before the program starts, so its value
+    /// must be a constant expression. Formatting one with a value that isn't (see
+    /// `Expr::is_constant`) records a warning in `Formatter::warnings`.
+    ///
     /// # Returns
     ///
     /// The builder instance for method chaining
@@ -295,6 +303,26 @@ impl VariableBuilder {
         self
     }
 
+    /// Makes the variable a `register` variable.
+    ///
+    /// In C, the `register` keyword hints that the variable should be kept in a CPU register,
+    /// and forbids taking its address.
+    ///
+    /// # Returns
+    ///
+    /// The builder instance for method chaining
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let builder = VariableBuilder::new_with_str("i", Type::new(BaseType::Int).build())
+    ///     .make_register();
+    /// ```
+    pub fn make_register(mut self) -> Self {
+        self.is_register = true;
+        self
+    }
+
     /// Sets the initial value of the variable using a raw string.
     ///
     /// This is a convenience method for setting the value without constructing an `Expr` manually.
@@ -339,6 +367,7 @@ impl VariableBuilder {
             value: self.value,
             is_static: self.is_static,
             is_extern: self.is_extern,
+            is_register: self.is_register,
             doc: self.doc,
         }
     }
@@ -373,5 +402,13 @@ mod tests {
         let another_res = "static bool another_var";
 
         assert_eq!(another_var.to_string(), another_res);
+
+        let register_var =
+            VariableBuilder::new_with_str("i", TypeBuilder::new(BaseType::Int).build())
+                .make_register()
+                .value(Expr::Int(0))
+                .build();
+
+        assert_eq!(register_var.to_string(), "register int i = 0");
     }
 }
@@ -1,6 +1,6 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{Data, DeriveInput, Expr, Lit, Meta, Variant};
+use syn::{Attribute, Data, DeriveInput, Expr, Fields, Lit, Meta, Variant};
 
 #[proc_macro_derive(DisplayFromFormat)]
 pub fn derive_display_from_format(input: TokenStream) -> TokenStream {
@@ -104,6 +104,102 @@ pub fn derive_format_from_const_symbol(input: TokenStream) -> TokenStream {
     output.into()
 }
 
+#[proc_macro_derive(ToTamago)]
+pub fn derive_to_tamago(input: TokenStream) -> TokenStream {
+    let input = syn::parse(input).unwrap_or_else(|_| {
+        panic!("Failed to parse");
+    });
+    let DeriveInput {
+        ident: struct_name,
+        data,
+        generics,
+        attrs,
+        ..
+    } = input;
+
+    if !generics.params.is_empty() {
+        panic!("ToTamago derive macro cannot be used on generic structs");
+    }
+
+    let fields = match data {
+        Data::Struct(data_struct) => match data_struct.fields {
+            Fields::Named(fields) => fields.named,
+            _ => panic!("ToTamago derive macro can only be used on structs with named fields"),
+        },
+        _ => panic!("ToTamago derive macro can only be used on structs"),
+    };
+
+    let name = struct_name.to_string();
+    let struct_doc = doc_comment(&attrs);
+
+    let field_builders = fields.iter().map(|field| {
+        let field_name = field.ident.as_ref().unwrap().to_string();
+        let t = &field.ty;
+        let doc = doc_comment(&field.attrs);
+
+        quote! {
+            .field(
+                ::tamago::FieldBuilder::new_with_str(
+                    #field_name,
+                    <#t as ::tamago::CType>::c_type(),
+                )
+                #doc
+                .build()
+            )
+        }
+    });
+
+    let output = quote! {
+        impl ::tamago::CType for #struct_name {
+            fn c_type() -> ::tamago::Type {
+                ::tamago::Type::new(::tamago::BaseType::Struct(#name.to_string())).build()
+            }
+        }
+
+        impl ::tamago::ToTamago for #struct_name {
+            fn to_tamago_struct() -> ::tamago::Struct {
+                ::tamago::StructBuilder::new_with_str(#name)
+                    #struct_doc
+                    #(#field_builders)*
+                    .build()
+            }
+        }
+    };
+
+    output.into()
+}
+
+/// Converts `///` comments into a `.doc(...)` call for the tamago builders
+fn doc_comment(attrs: &[Attribute]) -> proc_macro2::TokenStream {
+    let lines = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(name_value) => match &name_value.value {
+                Expr::Lit(syn::ExprLit {
+                    lit: Lit::Str(lit_str),
+                    ..
+                }) => Some(lit_str.value()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .map(|line| line.strip_prefix(' ').map(str::to_string).unwrap_or(line))
+        .collect::<Vec<_>>();
+
+    if lines.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            .doc(
+                ::tamago::DocCommentBuilder::new()
+                    #(.line_str(#lines))*
+                    .build()
+            )
+        }
+    }
+}
+
 fn get_symbol_attr(variant: &Variant) -> Option<String> {
     for attr in &variant.attrs {
         if attr.path().is_ident("symbol") {